@@ -0,0 +1,989 @@
+//! The things the editor can be asked to do. The events of the terminal are
+//! translated to actions and all of them run through `input::execute`, so
+//! anything that drives the editor (keys, the mouse, commands or a test)
+//! goes through the same path
+
+use crossterm::event::*;
+
+use crate::EditorState;
+use crate::print::PrintTarget;
+
+/// A movement of the cursor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Motion {
+    /// A line up or down, `exact` keeps the column instead of following the
+    /// indentation of the new line
+    Up { exact: bool },
+    Down { exact: bool },
+
+    /// A row of the terminal up or down, with the lines wrapped the rows of
+    /// a long line are visited one by one
+    RowUp,
+    RowDown,
+
+    /// A whole terminal up or down
+    PageUp { exact: bool },
+    PageDown { exact: bool },
+
+    /// A char, at the ends of the line it goes to the next or previous line
+    Left,
+    Right,
+
+    /// To the start of the next or previous word or run of punctuation
+    WordLeft,
+    WordRight,
+
+    /// The first non-whitespace char of the line, or its start when already
+    /// there, and the end of the line
+    LineStart,
+    LineEnd,
+
+    /// The start and the end of the document
+    DocumentStart,
+    DocumentEnd,
+
+    /// To the bracket that pairs with the one under the cursor
+    MatchingBracket,
+}
+
+/// An action of the editor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// Quit, with unsaved changes only when asked twice in a row
+    Quit,
+
+    Undo,
+    Redo,
+    Save,
+
+    /// Ask on the minibuffer the path to save the document as
+    SaveAs,
+
+    /// Ask on the minibuffer the line to go to
+    GoToLinePrompt,
+
+    /// Open the prompts of the search, the replace or the command line
+    Search,
+    Replace,
+    CommandLine,
+
+    /// Pick a named command to run, filtering them as the name is typed
+    CommandPalette,
+
+    /// List the keys bound to the commands, grouped by what they do
+    Help,
+
+    /// Tell the lines, the words and the chars of the document, and the
+    /// size of its file
+    Count,
+
+    /// Open the command line to search the project files
+    Grep,
+
+    /// Go to the position of an older or newer edit
+    OlderEdit,
+    NewerEdit,
+
+    /// Re-indent the whole document
+    Reindent,
+
+    /// Open the panels of the errors, the diff and the bookmarks
+    ShowErrors,
+
+    /// List the TODO comments of the project
+    ListTodos,
+
+    /// Preview the document with the program of its filetype
+    Preview,
+    ShowDiff,
+    ListBookmarks,
+
+    /// Bookmark the current line
+    Bookmark,
+
+    /// Mark the current line with a key, or go to the mark of a key, the
+    /// key is asked
+    SetMark,
+    GoToMark,
+
+    /// Go back to where the cursor jumped from, or forward again
+    JumpBack,
+    JumpForward,
+
+    Print(PrintTarget),
+    ToggleCrosshair,
+
+    /// Soft wrap of the long lines on the current window
+    ToggleWrap,
+
+    /// Show the whitespace on the current window
+    ToggleWhitespace,
+
+    /// Go through the modes of the line numbers of the current window
+    CycleLineNumbers,
+
+    /// Track changes mode and its pending deletions
+    ToggleReview,
+    AcceptDeletion { all: bool },
+    RejectDeletion { all: bool },
+
+    /// Allow or refuse the edits of the document
+    ToggleReadOnly,
+
+    /// Delete the current line
+    DeleteLine,
+
+    /// Copy or cut the selection to the clipboard, or paste it at the
+    /// cursor replacing the selection
+    Copy,
+    Cut,
+    Paste,
+
+    /// List the words of the open documents that complete the word before
+    /// the cursor
+    Complete,
+
+    /// Go to the next line longer than the limit, wrapping at the end
+    NextLongLine,
+
+    /// Go to the next or previous run of lines changed from the baseline
+    JumpToHunk { forward: bool },
+
+    /// Ask the language server about the symbol at the cursor, the answer
+    /// is shown on the minibuffer
+    ShowHover,
+
+    /// Go where the language server says the symbol at the cursor is
+    /// defined
+    GoToDefinition,
+
+    NewTab,
+    CloseTab,
+    SwitchTab(usize),
+
+    /// Ask for a file to open on a new buffer
+    OpenFile,
+
+    /// Pick a file of the project to open on a new buffer, filtering them
+    /// as the query is typed
+    FindFile,
+
+    /// Show the file tree and give it the focus, or hide it when it has it
+    FileTree,
+
+    /// Open the file under the cursor on a buffer, at its line when it has
+    /// one, or the URL on the browser
+    OpenUnderCursor,
+
+    /// Go to the next or previous buffer of the tab
+    NextBuffer,
+    PrevBuffer,
+
+    /// Go back to the buffer shown before the current one
+    AlternateBuffer,
+
+    /// Close the current buffer if it has no unsaved changes
+    CloseBuffer,
+
+    /// Open an empty buffer without file, saving it asks for one
+    NewBuffer,
+
+    MoveCursor(Motion),
+
+    /// Move the cursor extending the selection, it starts where the cursor
+    /// was when there's none
+    Select(Motion),
+
+    /// Go to the start of the line, centering it when not visible
+    GoToLine(usize),
+
+    /// Scroll the view, the cursor keeps its line while visible
+    Scroll { lines: isize, exact: bool },
+
+    /// Scroll the view with the wheel, the cursor is left where it is even
+    /// out of the view
+    ScrollView { lines: isize, exact: bool },
+
+    /// Scroll half a page, the cursor moves as many lines
+    HalfPage { up: bool },
+
+    InsertChar(char),
+    InsertNewline,
+    DeleteBackward,
+    DeleteForward,
+
+    /// Insert a copy of the line of the cursor below it
+    DuplicateLine,
+
+    /// Add a cursor on the line above the first cursor or below the last
+    /// one, at the column of the main cursor
+    AddCursor { up: bool },
+
+    /// Add a cursor at the next occurrence of the word of the main cursor,
+    /// the new one becomes the main cursor
+    AddCursorAtMatch,
+
+    /// Leave only the main cursor
+    DropCursors,
+
+    /// Swap the line of the cursor with the one above or below, the cursor
+    /// goes with it
+    MoveLine { up: bool },
+
+    /// Indent or dedent a level the selected lines, without a selection
+    /// indent inserts a tab at the cursor and dedent shifts its line
+    Indent,
+    Dedent,
+
+    /// Scroll to the position of the row of the scrollbar, until the
+    /// scrubbing ends
+    ScrubTo(u16),
+    EndScrub,
+
+    /// Place the cursor at the cell of the terminal
+    Click { row: u16, column: u16 },
+
+    /// Select from where the cursor was to the cell of the terminal
+    DragTo { row: u16, column: u16 },
+
+    /// The button was released, the selection goes to the primary
+    /// selection
+    EndDrag,
+
+    /// Paste the primary selection at the cell of the terminal
+    PastePrimary { row: u16, column: u16 },
+}
+
+impl Action {
+    /// If the action changes the text of the document
+    pub fn modifies(&self) -> bool {
+        matches!(self,
+            Self::Undo
+            | Self::Redo
+            | Self::Replace
+            | Self::Reindent
+            | Self::ToggleReview
+            | Self::AcceptDeletion { .. }
+            | Self::RejectDeletion { .. }
+            | Self::DeleteLine
+            | Self::DuplicateLine
+            | Self::MoveLine { .. }
+            | Self::Cut
+            | Self::Paste
+            | Self::PastePrimary { .. }
+            | Self::InsertChar(_)
+            | Self::InsertNewline
+            | Self::DeleteBackward
+            | Self::DeleteForward
+            | Self::Indent
+            | Self::Dedent
+            | Self::Complete)
+    }
+
+    /// If the action can take the cursor far, where it was goes to the jump
+    /// list
+    pub fn jumps(&self) -> bool {
+        matches!(self,
+            Self::Search
+            | Self::GoToLinePrompt
+            | Self::GoToLine(_)
+            | Self::NextLongLine
+            | Self::JumpToHunk { .. }
+            | Self::MoveCursor(
+                Motion::PageUp { .. }
+                | Motion::PageDown { .. }
+                | Motion::DocumentStart
+                | Motion::DocumentEnd
+                | Motion::MatchingBracket))
+    }
+
+    /// If the action is done at every cursor when there are many, the
+    /// other actions leave only the main one
+    pub fn at_every_cursor(&self) -> bool {
+        matches!(self,
+            Self::InsertChar(_)
+            | Self::InsertNewline
+            | Self::DeleteBackward
+            | Self::DeleteForward
+            | Self::Indent
+            | Self::Dedent
+            | Self::MoveCursor(
+                Motion::Up { .. }
+                | Motion::Down { .. }
+                | Motion::Left
+                | Motion::Right
+                | Motion::WordLeft
+                | Motion::WordRight
+                | Motion::LineStart
+                | Motion::LineEnd))
+    }
+
+    /// The action of an event when editing the document, `None` for the
+    /// events that do nothing
+    pub fn from_event(
+        event: &Event,
+        editor_state: &EditorState
+    ) -> Option<Self> {
+        let action = match event {
+            Event::Key(key) => Self::from_key(key, editor_state)?,
+
+            Event::Mouse(MouseEvent { kind, row, column, modifiers }) => {
+                let exact = modifiers.contains(KeyModifiers::CONTROL);
+                let page = modifiers.contains(KeyModifiers::SHIFT);
+                let gutter = editor_state.gutter_width();
+                match kind {
+                    MouseEventKind::ScrollUp if page =>
+                        Self::MoveCursor(Motion::PageUp { exact }),
+                    MouseEventKind::ScrollUp => Self::ScrollView {
+                        lines: -(editor_state.wheel_step as isize),
+                        exact,
+                    },
+                    MouseEventKind::ScrollDown if page =>
+                        Self::MoveCursor(Motion::PageDown { exact }),
+                    MouseEventKind::ScrollDown => Self::ScrollView {
+                        lines: editor_state.wheel_step as isize,
+                        exact,
+                    },
+
+                    // Scrub dragging the scrollbar, or the gutter holding
+                    // Ctrl
+                    MouseEventKind::Down(MouseButton::Left)
+                        if *column as usize == editor_state.columns + gutter
+                            || ((*column as usize) < gutter && exact) =>
+                                Self::ScrubTo(*row),
+                    MouseEventKind::Drag(MouseButton::Left)
+                        if editor_state.scrubbing => Self::ScrubTo(*row),
+                    MouseEventKind::Up(_) if editor_state.scrubbing =>
+                        Self::EndScrub,
+
+                    // Pressing places the cursor and dragging selects from
+                    // there
+                    MouseEventKind::Down(MouseButton::Left) =>
+                        Self::Click { row: *row, column: *column },
+                    MouseEventKind::Drag(MouseButton::Left) =>
+                        Self::DragTo { row: *row, column: *column },
+                    MouseEventKind::Up(MouseButton::Left) => Self::EndDrag,
+                    MouseEventKind::Down(MouseButton::Middle) =>
+                        Self::PastePrimary { row: *row, column: *column },
+                    _ => return None,
+                }
+            }
+            _ => return None,
+        };
+
+        Some(action)
+    }
+
+    /// The action of a key, the ones bound on the keymap or else the chars
+    /// typed
+    fn from_key(key: &KeyEvent, editor_state: &EditorState) -> Option<Self> {
+        if let Some(action) = editor_state.keymap.lookup(key) {
+            return Some(action);
+        }
+
+        match key.code {
+            KeyCode::Char(c) if !key.modifiers.intersects(
+                    KeyModifiers::CONTROL | KeyModifiers::ALT) =>
+                Some(Self::InsertChar(c)),
+            _ => None,
+        }
+    }
+
+    /// The group the action is listed under on the help, one of
+    /// `CATEGORIES`
+    pub fn category(&self) -> &'static str {
+        match self {
+            Self::Quit | Self::Save | Self::SaveAs | Self::OpenFile
+                | Self::FindFile | Self::FileTree | Self::OpenUnderCursor
+                | Self::Print(_) | Self::ToggleReadOnly => "Files",
+            Self::NextBuffer | Self::PrevBuffer | Self::AlternateBuffer
+                | Self::CloseBuffer | Self::NewBuffer | Self::NewTab
+                | Self::CloseTab
+                | Self::SwitchTab(_) => "Buffers and tabs",
+            Self::MoveCursor(_) | Self::GoToLinePrompt | Self::GoToLine(_)
+                | Self::Scroll { .. } | Self::ScrollView { .. }
+                | Self::HalfPage { .. } | Self::JumpBack | Self::JumpForward
+                | Self::SetMark | Self::GoToMark | Self::Bookmark
+                | Self::ListBookmarks | Self::NextLongLine
+                | Self::OlderEdit | Self::NewerEdit => "Moving around",
+            Self::Select(_) | Self::Copy | Self::Cut | Self::Paste
+                | Self::AddCursor { .. } | Self::AddCursorAtMatch
+                | Self::DropCursors => "Selection and cursors",
+            Self::Undo | Self::Redo | Self::InsertChar(_)
+                | Self::InsertNewline | Self::DeleteBackward
+                | Self::DeleteForward | Self::DeleteLine
+                | Self::DuplicateLine | Self::MoveLine { .. } | Self::Indent
+                | Self::Dedent | Self::Reindent | Self::Complete => "Editing",
+            Self::Search | Self::Replace | Self::Grep | Self::ListTodos =>
+                "Search",
+            Self::ShowErrors | Self::ShowHover | Self::GoToDefinition
+                | Self::ShowDiff | Self::JumpToHunk { .. } | Self::Preview
+                | Self::ToggleReview | Self::AcceptDeletion { .. }
+                | Self::RejectDeletion { .. } => "Code and changes",
+            Self::ToggleCrosshair | Self::ToggleWrap | Self::ToggleWhitespace
+                | Self::CycleLineNumbers => "View",
+            _ => "Other",
+        }
+    }
+
+    /// The action with the name used to bind keys on the config, the ones
+    /// of the registry plus the tabs as `tab-<n>`
+    pub fn from_name(name: &str) -> Option<Self> {
+        let command = COMMANDS.iter().find(|command| command.name == name);
+        if let Some(command) = command {
+            return Some(command.action);
+        }
+
+        let tab: usize = name.strip_prefix("tab-")?.parse().ok()?;
+        Some(Self::SwitchTab(tab.checked_sub(1)?))
+    }
+}
+
+/// The groups of the actions, in the order the help lists them
+pub const CATEGORIES: [&str; 9] = [
+    "Files",
+    "Buffers and tabs",
+    "Moving around",
+    "Editing",
+    "Selection and cursors",
+    "Search",
+    "Code and changes",
+    "View",
+    "Other",
+];
+
+/// An action with the name it's bound and run by from the palette
+#[derive(Debug, Clone, Copy)]
+pub struct Command {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub action: Action,
+}
+
+/// The registry of the named actions, the ones with arguments have a
+/// command for each of their useful values
+pub const COMMANDS: &[Command] = &[
+    Command {
+        name: "quit",
+        description: "Quit the editor",
+        action: Action::Quit,
+    },
+    Command {
+        name: "undo",
+        description: "Undo the last change",
+        action: Action::Undo,
+    },
+    Command {
+        name: "redo",
+        description: "Redo the last change undone",
+        action: Action::Redo,
+    },
+    Command {
+        name: "save",
+        description: "Save the document",
+        action: Action::Save,
+    },
+    Command {
+        name: "save-as",
+        description: "Save the document on another path",
+        action: Action::SaveAs,
+    },
+    Command {
+        name: "go-to-line",
+        description: "Go to a line",
+        action: Action::GoToLinePrompt,
+    },
+    Command {
+        name: "search",
+        description: "Search on the document",
+        action: Action::Search,
+    },
+    Command {
+        name: "replace",
+        description: "Replace on the document",
+        action: Action::Replace,
+    },
+    Command {
+        name: "command-line",
+        description: "Type a command",
+        action: Action::CommandLine,
+    },
+    Command {
+        name: "command-palette",
+        description: "Run a command by its name",
+        action: Action::CommandPalette,
+    },
+    Command {
+        name: "help",
+        description: "List the keys bound to the commands",
+        action: Action::Help,
+    },
+    Command {
+        name: "count",
+        description: "Count the lines, the words and the chars of the \
+            document",
+        action: Action::Count,
+    },
+    Command {
+        name: "grep",
+        description: "Search the lines of the project files",
+        action: Action::Grep,
+    },
+    Command {
+        name: "older-edit",
+        description: "Go to an older edit",
+        action: Action::OlderEdit,
+    },
+    Command {
+        name: "newer-edit",
+        description: "Go to a newer edit",
+        action: Action::NewerEdit,
+    },
+    Command {
+        name: "reindent",
+        description: "Re-indent the document with equalprg",
+        action: Action::Reindent,
+    },
+    Command {
+        name: "show-errors",
+        description: "List the errors of the quickfix list",
+        action: Action::ShowErrors,
+    },
+    Command {
+        name: "list-todos",
+        description: "List the TODO comments of the project",
+        action: Action::ListTodos,
+    },
+    Command {
+        name: "preview",
+        description: "Preview the document with the program of its filetype",
+        action: Action::Preview,
+    },
+    Command {
+        name: "show-diff",
+        description: "Show the changes since the last save",
+        action: Action::ShowDiff,
+    },
+    Command {
+        name: "list-bookmarks",
+        description: "List the bookmarks",
+        action: Action::ListBookmarks,
+    },
+    Command {
+        name: "set-mark",
+        description: "Mark the current line with a key",
+        action: Action::SetMark,
+    },
+    Command {
+        name: "go-to-mark",
+        description: "Go to the line marked with a key",
+        action: Action::GoToMark,
+    },
+    Command {
+        name: "jump-back",
+        description: "Go back to where the cursor jumped from",
+        action: Action::JumpBack,
+    },
+    Command {
+        name: "jump-forward",
+        description: "Go forward again on the jump list",
+        action: Action::JumpForward,
+    },
+    Command {
+        name: "bookmark",
+        description: "Bookmark the current line",
+        action: Action::Bookmark,
+    },
+    Command {
+        name: "print",
+        description: "Print the document",
+        action: Action::Print(PrintTarget::Lp),
+    },
+    Command {
+        name: "export-pdf",
+        description: "Export the document as a PDF",
+        action: Action::Print(PrintTarget::Pdf),
+    },
+    Command {
+        name: "toggle-crosshair",
+        description: "Toggle the crosshair",
+        action: Action::ToggleCrosshair,
+    },
+    Command {
+        name: "cycle-line-numbers",
+        description: "Cycle the line numbers: absolute, relative, hybrid, off",
+        action: Action::CycleLineNumbers,
+    },
+    Command {
+        name: "toggle-wrap",
+        description: "Toggle the wrap of the long lines",
+        action: Action::ToggleWrap,
+    },
+    Command {
+        name: "toggle-whitespace",
+        description: "Toggle the marks of the spaces, the tabs and the \
+            trailing whitespace",
+        action: Action::ToggleWhitespace,
+    },
+    Command {
+        name: "toggle-review",
+        description: "Toggle the review of the deletions",
+        action: Action::ToggleReview,
+    },
+    Command {
+        name: "accept-deletion",
+        description: "Accept the deletion of the line",
+        action: Action::AcceptDeletion { all: false },
+    },
+    Command {
+        name: "accept-all-deletions",
+        description: "Accept all the deletions",
+        action: Action::AcceptDeletion { all: true },
+    },
+    Command {
+        name: "reject-deletion",
+        description: "Reject the deletion of the line",
+        action: Action::RejectDeletion { all: false },
+    },
+    Command {
+        name: "reject-all-deletions",
+        description: "Reject all the deletions",
+        action: Action::RejectDeletion { all: true },
+    },
+    Command {
+        name: "toggle-readonly",
+        description: "Toggle the read-only mode of the document",
+        action: Action::ToggleReadOnly,
+    },
+    Command {
+        name: "delete-line",
+        description: "Delete the current line",
+        action: Action::DeleteLine,
+    },
+    Command {
+        name: "duplicate-line",
+        description: "Duplicate the current line",
+        action: Action::DuplicateLine,
+    },
+    Command {
+        name: "add-cursor-above",
+        description: "Add a cursor on the line above",
+        action: Action::AddCursor { up: true },
+    },
+    Command {
+        name: "add-cursor-below",
+        description: "Add a cursor on the line below",
+        action: Action::AddCursor { up: false },
+    },
+    Command {
+        name: "add-cursor-at-match",
+        description: "Add a cursor at the next occurrence of the word",
+        action: Action::AddCursorAtMatch,
+    },
+    Command {
+        name: "drop-cursors",
+        description: "Leave only the main cursor",
+        action: Action::DropCursors,
+    },
+    Command {
+        name: "move-line-up",
+        description: "Move the current line up",
+        action: Action::MoveLine { up: true },
+    },
+    Command {
+        name: "move-line-down",
+        description: "Move the current line down",
+        action: Action::MoveLine { up: false },
+    },
+    Command {
+        name: "copy",
+        description: "Copy the selection",
+        action: Action::Copy,
+    },
+    Command {
+        name: "cut",
+        description: "Cut the selection",
+        action: Action::Cut,
+    },
+    Command {
+        name: "paste",
+        description: "Paste the clipboard",
+        action: Action::Paste,
+    },
+    Command {
+        name: "complete",
+        description: "Complete the word before the cursor",
+        action: Action::Complete,
+    },
+    Command {
+        name: "next-long-line",
+        description: "Go to the next line too long",
+        action: Action::NextLongLine,
+    },
+    Command {
+        name: "next-hunk",
+        description: "Go to the next block of changed lines",
+        action: Action::JumpToHunk { forward: true },
+    },
+    Command {
+        name: "previous-hunk",
+        description: "Go to the previous block of changed lines",
+        action: Action::JumpToHunk { forward: false },
+    },
+    Command {
+        name: "hover",
+        description: "Show what the language server knows of the symbol \
+            at the cursor",
+        action: Action::ShowHover,
+    },
+    Command {
+        name: "go-to-definition",
+        description: "Go to the definition of the symbol at the cursor",
+        action: Action::GoToDefinition,
+    },
+    Command {
+        name: "new-tab",
+        description: "Open a new tab",
+        action: Action::NewTab,
+    },
+    Command {
+        name: "close-tab",
+        description: "Close the tab",
+        action: Action::CloseTab,
+    },
+    Command {
+        name: "open-file",
+        description: "Open a file",
+        action: Action::OpenFile,
+    },
+    Command {
+        name: "find-file",
+        description: "Find a file of the project",
+        action: Action::FindFile,
+    },
+    Command {
+        name: "file-tree",
+        description: "Show the file tree, or hide it when focused",
+        action: Action::FileTree,
+    },
+    Command {
+        name: "open-under-cursor",
+        description: "Open the file or the URL under the cursor",
+        action: Action::OpenUnderCursor,
+    },
+    Command {
+        name: "next-buffer",
+        description: "Go to the next buffer",
+        action: Action::NextBuffer,
+    },
+    Command {
+        name: "prev-buffer",
+        description: "Go to the previous buffer",
+        action: Action::PrevBuffer,
+    },
+    Command {
+        name: "alternate-buffer",
+        description: "Go to the buffer shown before",
+        action: Action::AlternateBuffer,
+    },
+    Command {
+        name: "close-buffer",
+        description: "Close the buffer",
+        action: Action::CloseBuffer,
+    },
+    Command {
+        name: "new-buffer",
+        description: "Open a scratch buffer, without file until saved",
+        action: Action::NewBuffer,
+    },
+    Command {
+        name: "up",
+        description: "Move the cursor up",
+        action: Action::MoveCursor(Motion::Up { exact: false }),
+    },
+    Command {
+        name: "down",
+        description: "Move the cursor down",
+        action: Action::MoveCursor(Motion::Down { exact: false }),
+    },
+    Command {
+        name: "row-up",
+        description: "Move the cursor a row up",
+        action: Action::MoveCursor(Motion::RowUp),
+    },
+    Command {
+        name: "row-down",
+        description: "Move the cursor a row down",
+        action: Action::MoveCursor(Motion::RowDown),
+    },
+    Command {
+        name: "page-up",
+        description: "Move the cursor a page up",
+        action: Action::MoveCursor(Motion::PageUp { exact: false }),
+    },
+    Command {
+        name: "page-down",
+        description: "Move the cursor a page down",
+        action: Action::MoveCursor(Motion::PageDown { exact: false }),
+    },
+    Command {
+        name: "left",
+        description: "Move the cursor left",
+        action: Action::MoveCursor(Motion::Left),
+    },
+    Command {
+        name: "right",
+        description: "Move the cursor right",
+        action: Action::MoveCursor(Motion::Right),
+    },
+    Command {
+        name: "word-left",
+        description: "Move the cursor a word left",
+        action: Action::MoveCursor(Motion::WordLeft),
+    },
+    Command {
+        name: "word-right",
+        description: "Move the cursor a word right",
+        action: Action::MoveCursor(Motion::WordRight),
+    },
+    Command {
+        name: "line-start",
+        description: "Move the cursor to the indentation or the line start",
+        action: Action::MoveCursor(Motion::LineStart),
+    },
+    Command {
+        name: "line-end",
+        description: "Move the cursor to the line end",
+        action: Action::MoveCursor(Motion::LineEnd),
+    },
+    Command {
+        name: "document-start",
+        description: "Move the cursor to the document start",
+        action: Action::MoveCursor(Motion::DocumentStart),
+    },
+    Command {
+        name: "document-end",
+        description: "Move the cursor to the document end",
+        action: Action::MoveCursor(Motion::DocumentEnd),
+    },
+    Command {
+        name: "matching-bracket",
+        description: "Jump to the matching bracket",
+        action: Action::MoveCursor(Motion::MatchingBracket),
+    },
+    Command {
+        name: "select-up",
+        description: "Select up",
+        action: Action::Select(Motion::Up { exact: false }),
+    },
+    Command {
+        name: "select-down",
+        description: "Select down",
+        action: Action::Select(Motion::Down { exact: false }),
+    },
+    Command {
+        name: "select-row-up",
+        description: "Select a row up",
+        action: Action::Select(Motion::RowUp),
+    },
+    Command {
+        name: "select-row-down",
+        description: "Select a row down",
+        action: Action::Select(Motion::RowDown),
+    },
+    Command {
+        name: "select-page-up",
+        description: "Select a page up",
+        action: Action::Select(Motion::PageUp { exact: false }),
+    },
+    Command {
+        name: "select-page-down",
+        description: "Select a page down",
+        action: Action::Select(Motion::PageDown { exact: false }),
+    },
+    Command {
+        name: "select-left",
+        description: "Select left",
+        action: Action::Select(Motion::Left),
+    },
+    Command {
+        name: "select-right",
+        description: "Select right",
+        action: Action::Select(Motion::Right),
+    },
+    Command {
+        name: "select-word-left",
+        description: "Select a word left",
+        action: Action::Select(Motion::WordLeft),
+    },
+    Command {
+        name: "select-word-right",
+        description: "Select a word right",
+        action: Action::Select(Motion::WordRight),
+    },
+    Command {
+        name: "select-line-start",
+        description: "Select to the indentation or the line start",
+        action: Action::Select(Motion::LineStart),
+    },
+    Command {
+        name: "select-line-end",
+        description: "Select to the line end",
+        action: Action::Select(Motion::LineEnd),
+    },
+    Command {
+        name: "select-document-start",
+        description: "Select to the document start",
+        action: Action::Select(Motion::DocumentStart),
+    },
+    Command {
+        name: "select-document-end",
+        description: "Select to the document end",
+        action: Action::Select(Motion::DocumentEnd),
+    },
+    Command {
+        name: "scroll-up",
+        description: "Scroll the view up",
+        action: Action::Scroll { lines: -1, exact: false },
+    },
+    Command {
+        name: "scroll-down",
+        description: "Scroll the view down",
+        action: Action::Scroll { lines: 1, exact: false },
+    },
+    Command {
+        name: "half-page-up",
+        description: "Scroll half a page up with the cursor",
+        action: Action::HalfPage { up: true },
+    },
+    Command {
+        name: "half-page-down",
+        description: "Scroll half a page down with the cursor",
+        action: Action::HalfPage { up: false },
+    },
+    Command {
+        name: "newline",
+        description: "Insert a new line",
+        action: Action::InsertNewline,
+    },
+    Command {
+        name: "backspace",
+        description: "Delete the char before the cursor",
+        action: Action::DeleteBackward,
+    },
+    Command {
+        name: "delete",
+        description: "Delete the char after the cursor",
+        action: Action::DeleteForward,
+    },
+    Command {
+        name: "indent",
+        description: "Indent the selected lines",
+        action: Action::Indent,
+    },
+    Command {
+        name: "dedent",
+        description: "Dedent the selected lines",
+        action: Action::Dedent,
+    },
+];
@@ -0,0 +1,178 @@
+//! Where the frames go. The grid tells the backend the cells that changed
+//! and the backend writes them its own way: crossterm commands on the
+//! terminal, a list of the operations on the tests. Another frontend is
+//! another backend, what the editor draws stays the same
+
+use std::io::Write;
+
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::queue;
+use crossterm::style::{Attribute, ContentStyle, Print, SetAttribute, SetStyle};
+use crossterm::terminal::{self, Clear, ClearType};
+
+use crate::Result;
+
+/// What draws the frames, the cursor is where the last operation left it
+pub trait Backend {
+    /// The columns and the rows there are to draw on
+    fn size(&self) -> Result<(u16, u16)>;
+
+    fn move_to(&mut self, column: u16, row: u16) -> Result<()>;
+
+    /// The text from the cursor, which ends past it
+    fn print_styled(&mut self, text: &str, style: ContentStyle) -> Result<()>;
+
+    /// Clear from the cursor to the end of its row
+    fn clear_line(&mut self) -> Result<()>;
+    fn clear_all(&mut self) -> Result<()>;
+    fn show_cursor(&mut self, visible: bool) -> Result<()>;
+
+    /// The sequences only a terminal understands, like the bell or the OSC
+    /// ones, the other backends leave them out
+    fn write_raw(&mut self, _bytes: &[u8]) -> Result<()> {
+        Ok(())
+    }
+
+    /// Show what was drawn since the last flush
+    fn flush(&mut self) -> Result<()>;
+}
+
+/// The terminal, written with crossterm. The style is only set when it
+/// changes
+pub struct Crossterm<W: Write> {
+    out: W,
+    style: ContentStyle,
+}
+
+impl<W: Write> Crossterm<W> {
+    pub fn new(out: W) -> Self {
+        Self { out, style: ContentStyle::default() }
+    }
+
+    /// Back to the style of the terminal, what comes next isn't styled
+    fn reset(&mut self) -> Result<()> {
+        if self.style != ContentStyle::default() {
+            queue!(self.out, SetAttribute(Attribute::Reset))?;
+            self.style = ContentStyle::default();
+        }
+
+        Ok(())
+    }
+}
+
+impl<W: Write> Backend for Crossterm<W> {
+    fn size(&self) -> Result<(u16, u16)> {
+        Ok(terminal::size()?)
+    }
+
+    fn move_to(&mut self, column: u16, row: u16) -> Result<()> {
+        Ok(queue!(self.out, MoveTo(column, row))?)
+    }
+
+    fn print_styled(&mut self, text: &str, style: ContentStyle) -> Result<()> {
+        if style != self.style {
+            queue!(self.out, SetAttribute(Attribute::Reset), SetStyle(style))?;
+            self.style = style;
+        }
+
+        Ok(queue!(self.out, Print(text))?)
+    }
+
+    fn clear_line(&mut self) -> Result<()> {
+        self.reset()?;
+        Ok(queue!(self.out, Clear(ClearType::UntilNewLine))?)
+    }
+
+    fn clear_all(&mut self) -> Result<()> {
+        // The style is reset even when it's known, the terminal may have
+        // been written by someone else
+        queue!(self.out, SetAttribute(Attribute::Reset))?;
+        self.style = ContentStyle::default();
+        Ok(queue!(self.out, Clear(ClearType::All))?)
+    }
+
+    fn show_cursor(&mut self, visible: bool) -> Result<()> {
+        if visible {
+            queue!(self.out, Show)?;
+        } else {
+            queue!(self.out, Hide)?;
+        }
+
+        Ok(())
+    }
+
+    fn write_raw(&mut self, bytes: &[u8]) -> Result<()> {
+        Ok(self.out.write_all(bytes)?)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.reset()?;
+        Ok(self.out.flush()?)
+    }
+}
+
+/// The operations a frame was drawn with, for the tests to compare. The
+/// texts printed one after the other go together, the styles are left out
+#[cfg(test)]
+#[derive(Debug, Default)]
+pub struct Recorder {
+    pub size: (u16, u16),
+    pub ops: Vec<String>,
+    printing: bool,
+}
+
+#[cfg(test)]
+impl Recorder {
+    pub fn new(size: (u16, u16)) -> Self {
+        Self { size, ..Self::default() }
+    }
+
+    fn record(&mut self, op: String) {
+        self.ops.push(op);
+        self.printing = false;
+    }
+}
+
+#[cfg(test)]
+impl Backend for Recorder {
+    fn size(&self) -> Result<(u16, u16)> {
+        Ok(self.size)
+    }
+
+    fn move_to(&mut self, column: u16, row: u16) -> Result<()> {
+        self.record(format!("move {} {}", column, row));
+        Ok(())
+    }
+
+    fn print_styled(&mut self, text: &str, _: ContentStyle) -> Result<()> {
+        match self.ops.last_mut() {
+            Some(op) if self.printing => op.push_str(text),
+            _ => {
+                self.record(format!("print {}", text));
+                self.printing = true;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn clear_line(&mut self) -> Result<()> {
+        self.record("clear line".to_owned());
+        Ok(())
+    }
+
+    fn clear_all(&mut self) -> Result<()> {
+        self.record("clear".to_owned());
+        Ok(())
+    }
+
+    fn show_cursor(&mut self, visible: bool) -> Result<()> {
+        self.record(if visible { "show" } else { "hide" }.to_owned());
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.record("flush".to_owned());
+        Ok(())
+    }
+}
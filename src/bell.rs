@@ -0,0 +1,90 @@
+//! Feedback for what can't be done, like scrolling past the ends of the
+//! document. The actions only ask for the bell, it's rung after the frame
+//! is drawn by the backend chosen with the `bell` option. Other backends,
+//! like the sounds of a platform, only have to implement `Bell`
+
+use std::time::Duration;
+
+use crossterm::style::{PrintStyledContent, Stylize};
+
+use crate::Result;
+use crate::backend::Backend;
+use crate::grid::{draw, Grid};
+
+/// How long the visual bell is shown
+const FLASH_DURATION: Duration = Duration::from_millis(100);
+
+/// Something that tells the user an action couldn't be done
+pub trait Bell {
+    /// Ring the bell, `status_row` and `width` are where the status bar is.
+    /// What's drawn on the grid is flushed, the status bar is repainted on
+    /// the next frame
+    fn ring(
+        &mut self,
+        grid: &mut Grid,
+        backend: &mut dyn Backend,
+        status_row: u16,
+        width: usize
+    ) -> Result<()>;
+}
+
+/// The bell of the terminal, it may beep or flash depending on the terminal
+pub struct AudibleBell;
+
+impl Bell for AudibleBell {
+    fn ring(
+        &mut self,
+        _: &mut Grid,
+        backend: &mut dyn Backend,
+        _: u16,
+        _: usize
+    ) -> Result<()> {
+        backend.write_raw(b"\x07")?;
+        backend.flush()?;
+
+        Ok(())
+    }
+}
+
+/// A flash of the status bar
+pub struct VisualBell;
+
+impl Bell for VisualBell {
+    fn ring(
+        &mut self,
+        grid: &mut Grid,
+        backend: &mut dyn Backend,
+        status_row: u16,
+        width: usize
+    ) -> Result<()> {
+        draw!(grid,
+            crossterm::cursor::SavePosition,
+            crossterm::cursor::MoveTo(0, status_row),
+            PrintStyledContent(" ".repeat(width).on_red()),
+            crossterm::cursor::RestorePosition)?;
+        grid.flush(backend)?;
+        std::thread::sleep(FLASH_DURATION);
+
+        Ok(())
+    }
+}
+
+/// Nothing at all
+pub struct NoBell;
+
+impl Bell for NoBell {
+    fn ring(&mut self, _: &mut Grid, _: &mut dyn Backend, _: u16, _: usize)
+            -> Result<()> {
+        Ok(())
+    }
+}
+
+/// The bell with the name used by the `bell` option
+pub fn from_name(name: &str) -> Option<Box<dyn Bell>> {
+    match name {
+        "audible" => Some(Box::new(AudibleBell)),
+        "visual" => Some(Box::new(VisualBell)),
+        "none" => Some(Box::new(NoBell)),
+        _ => None,
+    }
+}
@@ -0,0 +1,41 @@
+//! The binary files, the ones with NUL bytes. Their bytes that aren't
+//! UTF-8 are replaced when read as text, so they are opened read-only and
+//! their bytes can be seen as a hex dump instead
+
+/// Bytes looked at to tell a binary file, like git does
+pub const SNIFF_LEN: usize = 8000;
+
+/// Bytes on each line of the hex dump
+const BYTES_PER_LINE: usize = 16;
+
+/// If the contents look binary, there's a NUL byte at their start
+pub fn is_binary(bytes: &[u8]) -> bool {
+    bytes[..usize::min(bytes.len(), SNIFF_LEN)].contains(&0)
+}
+
+/// The lines of the hex dump of the bytes: the offset, the bytes in hex and
+/// the printable ones as text, like `hexdump -C`
+pub fn hex_lines(bytes: &[u8]) -> Vec<String> {
+    bytes
+        .chunks(BYTES_PER_LINE)
+        .enumerate()
+        .map(|(idx, chunk)| {
+            let hex: Vec<String> = chunk
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect();
+            let text: String = chunk
+                .iter()
+                .map(|byte| if byte.is_ascii_graphic() || *byte == b' ' {
+                    *byte as char
+                } else {
+                    '.'
+                })
+                .collect();
+
+            format!("{:08x}  {:width$}  |{}|",
+                idx * BYTES_PER_LINE, hex.join(" "), text,
+                width = BYTES_PER_LINE * 3 - 1)
+        })
+        .collect()
+}
@@ -0,0 +1,131 @@
+//! Annotated bookmarks on lines of any file of the project, they are stored
+//! on `.pepe/bookmarks` inside the project so they survive between sessions
+
+use std::path::{Path, PathBuf};
+
+use crate::Result;
+use crate::text::LineShift;
+
+/// A line of a file with a note attached
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bookmark {
+    pub path: PathBuf,
+
+    /// Line on the document, starting from 0
+    pub line: usize,
+
+    /// Free text written by the user, it can't contain newlines
+    pub note: String,
+}
+
+/// All the bookmarks of the project
+pub struct Bookmarks {
+    pub list: Vec<Bookmark>,
+
+    /// Where the bookmarks are persisted
+    file: PathBuf,
+}
+
+impl Bookmarks {
+    /// Load the bookmarks of the project at `root`, a project without
+    /// bookmarks file just has no bookmarks
+    pub fn load(root: impl AsRef<Path>) -> Self {
+        let file = root.as_ref().join(".pepe").join("bookmarks");
+
+        // Each line is `line<TAB>path<TAB>note`, malformed ones are ignored
+        let list = std::fs::read_to_string(&file)
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|entry| {
+                let mut fields = entry.splitn(3, '\t');
+                let line = fields.next()?.parse().ok()?;
+                let path = PathBuf::from(fields.next()?);
+                let note = fields.next().unwrap_or("").to_owned();
+
+                Some(Bookmark { path, line, note })
+            })
+            .collect();
+
+        Self { list, file }
+    }
+
+    /// Write the bookmarks to the project bookmarks file
+    pub fn save(&self) -> Result<()> {
+        if let Some(dir) = self.file.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+
+        let mut contents = String::new();
+        for Bookmark { path, line, note } in &self.list {
+            contents.push_str(
+                &format!("{}\t{}\t{}\n", line, path.display(), note));
+        }
+        std::fs::write(&self.file, contents)?;
+
+        Ok(())
+    }
+
+    /// Bookmark the line of the file, returns the index of the bookmark that
+    /// may already exist
+    pub fn add(&mut self, path: &Path, line: usize) -> usize {
+        if let Some(idx) = self.list
+                .iter()
+                .position(|b| b.path == path && b.line == line) {
+            return idx;
+        }
+
+        self.list.push(Bookmark {
+            path: path.to_owned(),
+            line,
+            note: String::new(),
+        });
+
+        self.list.len() - 1
+    }
+
+    /// Change the note of a bookmark, the separators of the file format are
+    /// not allowed inside
+    pub fn set_note(&mut self, idx: usize, note: &str) {
+        if let Some(bookmark) = self.list.get_mut(idx) {
+            bookmark.note = note.replace(['\t', '\n', '\r'], " ");
+        }
+    }
+
+    /// Delete the bookmark if exists
+    pub fn remove(&mut self, idx: usize) {
+        if idx < self.list.len() {
+            self.list.remove(idx);
+        }
+    }
+
+    /// Point the bookmarks of a file to its new path after a rename
+    pub fn rename_file(&mut self, from: &Path, to: &Path) {
+        for bookmark in self.list.iter_mut().filter(|b| b.path == from) {
+            bookmark.path = to.to_owned();
+        }
+    }
+
+    /// The bookmarks of the file follow the lines inserted and removed on
+    /// it, returns if any moved
+    pub fn shift(&mut self, path: &Path, shifts: &[LineShift]) -> bool {
+        let mut moved = false;
+        for bookmark in self.list.iter_mut().filter(|b| b.path == path) {
+            let line = shifts.iter()
+                .fold(bookmark.line, |line, shift| shift.apply(line));
+            moved |= line != bookmark.line;
+            bookmark.line = line;
+        }
+
+        moved
+    }
+
+    /// Human readable version of the bookmarks, one per line
+    pub fn lines(&self) -> Vec<String> {
+        self.list
+            .iter()
+            .map(|Bookmark { path, line, note }| {
+                format!("{}:{}  {}", path.display(), line + 1, note)
+            })
+            .collect()
+    }
+}
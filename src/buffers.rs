@@ -0,0 +1,151 @@
+//! The documents open on a tab. Only one of them is shown at a time, the
+//! others keep their cursor and scroll so switching back to them shows them
+//! as they were left
+
+use std::path::{Path, PathBuf};
+
+use crate::fileops;
+use crate::input::{Cursor, CursorState};
+use crate::text::Document;
+
+/// A document open and the view of it
+#[derive(Default)]
+pub struct OpenBuffer {
+    pub doc: Option<Document>,
+    pub cursor: Cursor,
+    pub cursor_state: CursorState,
+}
+
+/// All the buffers open on a tab
+pub struct BufferList {
+    /// The state of every buffer, the one of the current buffer is stale
+    /// because the editor works directly with it until it switches to
+    /// another buffer
+    pub list: Vec<OpenBuffer>,
+
+    /// Index of the buffer being shown
+    pub current: usize,
+
+    /// Index of the buffer shown before the current one
+    pub alternate: Option<usize>,
+}
+
+impl Default for BufferList {
+    fn default() -> Self {
+        Self {
+            list: vec![OpenBuffer::default()],
+            current: 0,
+            alternate: None,
+        }
+    }
+}
+
+impl BufferList {
+    /// Every document open, the current one is the one being edited because
+    /// its stored state is stale
+    pub fn documents<'a>(
+        &'a self,
+        current_doc: &'a Option<Document>
+    ) -> impl Iterator<Item = &'a Document> {
+        self.list
+            .iter()
+            .enumerate()
+            .filter_map(move |(idx, buffer)| if idx == self.current {
+                current_doc.as_ref()
+            } else {
+                buffer.doc.as_ref()
+            })
+    }
+
+    /// The documents of the buffers other than the current one, whose
+    /// stored state is stale
+    pub fn others_mut(
+        &mut self
+    ) -> impl Iterator<Item = &mut Option<Document>> {
+        let current = self.current;
+        self.list
+            .iter_mut()
+            .enumerate()
+            .filter(move |(idx, _)| *idx != current)
+            .map(|(_, buffer)| &mut buffer.doc)
+    }
+
+    /// Index of the first buffer with unsaved changes, the documents on
+    /// `skip` don't count
+    pub fn unsaved(
+        &self,
+        current_doc: &Option<Document>,
+        skip: &[PathBuf]
+    ) -> Option<usize> {
+        self.list
+            .iter()
+            .enumerate()
+            .position(|(idx, buffer)| {
+                let doc = if idx == self.current {
+                    current_doc
+                } else {
+                    &buffer.doc
+                };
+                doc.as_ref()
+                    .map(|doc| doc.dirty && !skip.contains(&doc.path))
+                    .unwrap_or(false)
+            })
+    }
+
+    /// Index of the buffer of the file, if open, with the path written in
+    /// any way
+    pub fn find(
+        &self,
+        path: &Path,
+        current_doc: &Option<Document>
+    ) -> Option<usize> {
+        self.list
+            .iter()
+            .enumerate()
+            .position(|(idx, buffer)| {
+                let doc = if idx == self.current {
+                    current_doc
+                } else {
+                    &buffer.doc
+                };
+                doc.as_ref()
+                    .map(|doc| fileops::same_file(&doc.path, path))
+                    .unwrap_or(false)
+            })
+    }
+
+    /// Add a buffer at the index, the ones after it move
+    pub fn insert(&mut self, idx: usize, buffer: OpenBuffer) {
+        self.list.insert(idx, buffer);
+        if self.current >= idx {
+            self.current += 1;
+        }
+        if let Some(alternate) = self.alternate.as_mut() {
+            if *alternate >= idx {
+                *alternate += 1;
+            }
+        }
+    }
+
+    /// Remove the current buffer, the alternate one takes its place or
+    /// without it the one after, or before when it was the last
+    pub fn remove_current(&mut self) {
+        self.list.remove(self.current);
+        self.current = match self.alternate.take() {
+            Some(alternate) if alternate > self.current => alternate - 1,
+            Some(alternate) => alternate,
+            None => usize::min(self.current, self.list.len() - 1),
+        };
+    }
+
+    /// The index of the buffer after or before the current one, wrapping
+    /// around at the ends
+    pub fn cycle(&self, forward: bool) -> usize {
+        let len = self.list.len();
+        if forward {
+            (self.current + 1) % len
+        } else {
+            (self.current + len - 1) % len
+        }
+    }
+}
@@ -0,0 +1,103 @@
+//! Cancellation of the long operations, like replacing every match of a
+//! huge file, reading a huge file or waiting for an external program. They
+//! run on the editor thread but the input is read on its own, so an Esc or a
+//! Ctrl+C pressed while one runs cancels it instead of waiting its turn. The
+//! operations check the token as they go and leave everything as it was
+
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+
+use crate::Result;
+
+/// Bytes read from a file between the checks of the token
+const READ_CHUNK: usize = 1 << 20;
+
+#[derive(Debug, Default)]
+struct Shared {
+    /// An operation is running, the keys can cancel it
+    running: AtomicBool,
+    cancelled: AtomicBool,
+}
+
+/// Shared by the input thread, which cancels, and the operation running,
+/// which checks it
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken {
+    shared: Arc<Shared>,
+}
+
+/// An operation that can be cancelled, it runs until dropped
+pub struct Operation {
+    shared: Arc<Shared>,
+}
+
+impl Drop for Operation {
+    fn drop(&mut self) {
+        self.shared.running.store(false, Ordering::Release);
+    }
+}
+
+impl CancelToken {
+    /// Start an operation, the keys cancel it until the guard is dropped
+    pub fn start(&self) -> Operation {
+        self.shared.cancelled.store(false, Ordering::Release);
+        self.shared.running.store(true, Ordering::Release);
+
+        Operation { shared: Arc::clone(&self.shared) }
+    }
+
+    /// If the operation running was cancelled
+    pub fn is_cancelled(&self) -> bool {
+        self.shared.cancelled.load(Ordering::Acquire)
+    }
+
+    /// An error once the operation was cancelled, to return it with `?`
+    pub fn check(&self) -> Result<()> {
+        if self.is_cancelled() {
+            return Err("cancelled".into());
+        }
+
+        Ok(())
+    }
+
+    /// Cancel the operation running if the event is an Esc or a Ctrl+C,
+    /// returns if it did, then the event is not for the editor
+    pub fn interrupts(&self, event: &Event) -> bool {
+        let cancels = matches!(event,
+            Event::Key(KeyEvent { code: KeyCode::Esc, .. })
+            | Event::Key(KeyEvent {
+                code: KeyCode::Char('c'),
+                modifiers: KeyModifiers::CONTROL,
+                ..
+            }));
+        if !cancels || !self.shared.running.load(Ordering::Acquire) {
+            return false;
+        }
+        self.shared.cancelled.store(true, Ordering::Release);
+
+        true
+    }
+}
+
+/// Read the whole file, stopping when the operation is cancelled
+pub fn read(path: &Path, cancel: &CancelToken) -> Result<Vec<u8>> {
+    let mut file = File::open(path)?;
+    let mut bytes = Vec::new();
+    loop {
+        if cancel.is_cancelled() {
+            return Err(format!("the read of {} was cancelled",
+                path.display()).into());
+        }
+        let read = (&mut file)
+            .take(READ_CHUNK as u64)
+            .read_to_end(&mut bytes)?;
+        if read == 0 {
+            return Ok(bytes);
+        }
+    }
+}
@@ -0,0 +1,204 @@
+//! The lines changed since a baseline, marked on the gutter as added,
+//! modified or with lines deleted above them. The baseline is the document
+//! as it was loaded or last saved, taken again every time the document is
+//! clean. Other versions, like the one of the git index, can be given as
+//! the baseline instead and are kept until replaced. The marks are only
+//! computed again when the buffer changed since the last time. The runs of
+//! marked lines are the hunks, which can be visited one by one
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::diff::{self, Change};
+use crate::text::Document;
+
+/// The documents longer than this are not compared, the gutter only shows
+/// the lines edited. Neither are the large files, which aren't read whole
+const MAX_LINES: usize = 100_000;
+
+/// The changed lines compared one by one, more than this and they are
+/// marked as a block. The diff of a big block takes too much memory
+const MAX_DIFF_LINES: usize = 2000;
+
+/// How a line differs from the baseline
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineChange {
+    Added,
+    Modified,
+
+    /// Lines of the baseline were deleted right above the line, or below
+    /// when it's the last one
+    Deleted,
+}
+
+/// Where the baseline comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    /// The document as loaded or last saved
+    Saved,
+
+    /// Lines given from elsewhere, like a revision of the repository
+    Given,
+}
+
+/// The lines the document is compared with, and the marks of the version
+/// of the buffer compared last
+#[derive(Debug)]
+struct Baseline {
+    source: Source,
+    lines: Vec<String>,
+    marks: Vec<Option<LineChange>>,
+    version: Option<u64>,
+}
+
+/// The baselines of the documents opened
+#[derive(Debug, Default)]
+pub struct LineChanges {
+    baselines: HashMap<PathBuf, Baseline>,
+}
+
+impl LineChanges {
+    /// Compare the document with the lines instead of its saved version,
+    /// until another baseline is set
+    pub fn set_baseline(&mut self, path: &Path, lines: Vec<String>) {
+        self.baselines.insert(path.to_owned(), Baseline {
+            source: Source::Given,
+            lines,
+            marks: Vec::new(),
+            version: None,
+        });
+    }
+
+    /// Compare the document with its saved version again
+    pub fn reset_baseline(&mut self, path: &Path) {
+        self.baselines.remove(path);
+    }
+
+    /// If the document is compared with a baseline, the long ones are not
+    pub fn compares(&self, path: &Path) -> bool {
+        self.baselines.contains_key(path)
+    }
+
+    /// Compare the document with its baseline if it changed since the last
+    /// time, returns if the marks changed
+    pub fn update(&mut self, doc: &Document) -> bool {
+        if doc.large || doc.buffer.len() > MAX_LINES {
+            return self.baselines.remove(&doc.path).is_some();
+        }
+
+        // A clean document is its saved version
+        let version = doc.buffer.version();
+        let baseline = self.baselines
+            .entry(doc.path.clone())
+            .or_insert_with(|| Baseline {
+                source: Source::Saved,
+                lines: Vec::new(),
+                marks: Vec::new(),
+                version: None,
+            });
+        if baseline.version == Some(version) {
+            return false;
+        }
+        let marks = if baseline.source == Source::Saved && !doc.dirty {
+            baseline.lines = doc.buffer.to_vec();
+            vec![None; baseline.lines.len()]
+        } else {
+            marks(&baseline.lines, &doc.buffer.to_vec())
+        };
+        baseline.version = Some(version);
+
+        let changed = marks != baseline.marks;
+        baseline.marks = marks;
+        changed
+    }
+
+    /// The mark of the line of the document, `None` when it's the same as
+    /// on the baseline or the document isn't compared
+    pub fn mark(&self, path: &Path, line: usize) -> Option<LineChange> {
+        self.baselines.get(path)?.marks.get(line).copied().flatten()
+    }
+
+    /// The first line of the hunk after the line, or before it when not
+    /// `forward`, wrapping at the ends of the document
+    pub fn hunk(&self, path: &Path, line: usize, forward: bool)
+            -> Option<usize> {
+        let marks = &self.baselines.get(path)?.marks;
+        let len = marks.len();
+        let starts = |idx: &usize| marks[*idx].is_some()
+            && (*idx == 0 || marks[idx - 1].is_none());
+
+        (1..=len)
+            .map(|offset| if forward {
+                (line + offset) % len
+            } else {
+                (line + len - offset % len) % len
+            })
+            .find(starts)
+    }
+}
+
+/// The marks of the lines of `new` compared with `old`
+fn marks(old: &[String], new: &[String]) -> Vec<Option<LineChange>> {
+    let mut marks = vec![None; new.len()];
+
+    // Only the lines between the common start and end are compared
+    let prefix = old.iter()
+        .zip(new)
+        .take_while(|(old, new)| old == new)
+        .count();
+    let suffix = old[prefix..].iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(old, new)| old == new)
+        .count();
+    let old = &old[prefix..old.len() - suffix];
+    let new_changed = &new[prefix..new.len() - suffix];
+
+    if old.len() + new_changed.len() > MAX_DIFF_LINES {
+        let block = [(old.len(), new_changed.len(), prefix)];
+        mark_blocks(&mut marks, &block);
+        return marks;
+    }
+
+    // The runs of deleted and inserted lines between the equal ones, with
+    // the line of `new` they start at
+    let mut blocks = Vec::new();
+    let (mut deleted, mut inserted, mut start) = (0, 0, prefix);
+    for change in diff::diff(old, new_changed) {
+        match change {
+            Change::Delete(_) => deleted += 1,
+            Change::Insert(_) => inserted += 1,
+            Change::Equal(_, line) => {
+                blocks.push((deleted, inserted, start));
+                (deleted, inserted, start) = (0, 0, prefix + line + 1);
+            }
+        }
+    }
+    blocks.push((deleted, inserted, start));
+    mark_blocks(&mut marks, &blocks);
+
+    marks
+}
+
+/// Mark the blocks of deleted and inserted lines, the inserted lines of a
+/// block are modified lines as long as there are deleted ones to pair with
+fn mark_blocks(
+    marks: &mut [Option<LineChange>],
+    blocks: &[(usize, usize, usize)]
+) {
+    for &(deleted, inserted, start) in blocks {
+        for offset in 0..inserted {
+            marks[start + offset] = Some(if offset < deleted {
+                LineChange::Modified
+            } else {
+                LineChange::Added
+            });
+        }
+        if deleted > 0 && inserted == 0 {
+            let below = usize::min(start, marks.len().saturating_sub(1));
+            if let Some(mark) = marks.get_mut(below) {
+                mark.get_or_insert(LineChange::Deleted);
+            }
+        }
+    }
+}
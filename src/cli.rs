@@ -0,0 +1,141 @@
+//! The command line of the editor: the options and the files to edit. The
+//! options can go anywhere between the files, `--` ends them so the files
+//! that start with a dash can be edited
+
+use std::path::PathBuf;
+
+use crate::Result;
+
+/// Shown by `--help`
+pub const USAGE: &str = "\
+Usage: pepe [OPTIONS] [FILES...]
+
+A file can be given as FILE:LINE or FILE:LINE:COLUMN to start there. A
+directory is shown on a file tree at the left, its files are opened from
+there.
+
+Options:
+  -R, --readonly         Open the files read-only
+  -l, --line <LINE>      Start on the line of the first file
+  +LINE                  Same as --line
+      --filetype <NAME>  Force the filetype of the first file
+      --theme <NAME>     Use the color theme
+      --config <FILE>    Read the config from the file instead of the default
+      --log <FILE>       Append the messages of the editor to the file
+      --pager            Show the file, or the stdin, like less
+      --errors           Read the errors of a build from the stdin
+      --remote           Open the file on the editor running on the project
+      --resume           Open the files of the last session of the project
+  -V, --version          Print the version
+  -h, --help             Print this help";
+
+/// What was asked on the command line
+#[derive(Debug, Default)]
+pub struct Options {
+    /// The files to edit, the first one is shown. A directory goes to the
+    /// file tree
+    pub paths: Vec<PathBuf>,
+    pub readonly: bool,
+
+    /// The line of the first file to start on, from 1
+    pub line: Option<usize>,
+
+    /// Where to start on the files given as `file:line:column`, the line
+    /// and the column from 1
+    pub positions: Vec<(PathBuf, usize, Option<usize>)>,
+    pub filetype: Option<String>,
+    pub theme: Option<String>,
+    pub config: Option<PathBuf>,
+    pub log: Option<PathBuf>,
+    pub pager: bool,
+    pub errors: bool,
+    pub remote: bool,
+    pub resume: bool,
+    pub version: bool,
+    pub help: bool,
+}
+
+impl Options {
+    /// Parse the arguments, without the name of the program
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self> {
+        let mut options = Self::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            // The value goes after `=` or as the next argument
+            let (flag, inline) = match arg.split_once('=') {
+                Some((flag, value)) if flag.starts_with("--") => 
+                    (flag.to_owned(), Some(value.to_owned())),
+                _ => (arg.clone(), None),
+            };
+            let mut value = || inline.clone()
+                .or_else(|| args.next())
+                .ok_or(format!("{} needs a value, see `pepe --help`", flag));
+
+            match flag.as_str() {
+                "-R" | "--readonly" => options.readonly = true,
+                "-l" | "--line" => {
+                    let line = value()?;
+                    options.line = Some(line.parse()
+                        .ok()
+                        .filter(|line| *line > 0)
+                        .ok_or(format!("`{}` is not a line number", line))?);
+                }
+                "--filetype" => options.filetype = Some(value()?),
+                "--theme" => options.theme = Some(value()?),
+                "--config" => options.config = Some(value()?.into()),
+                "--log" => options.log = Some(value()?.into()),
+                "--pager" => options.pager = true,
+                "--errors" => options.errors = true,
+                "--remote" => options.remote = true,
+                "--resume" => options.resume = true,
+                "-V" | "--version" => options.version = true,
+                "-h" | "--help" => options.help = true,
+                "--" => {
+                    options.paths.extend(args.map(PathBuf::from));
+                    break;
+                }
+                _ if arg.starts_with('+') && arg.len() > 1 => {
+                    options.line = Some(arg[1..].parse()
+                        .ok()
+                        .filter(|line| *line > 0)
+                        .ok_or(format!("`{}` is not a line number", arg))?);
+                }
+                _ if arg.starts_with('-') && arg != "-" => return Err(format!(
+                    "unknown option `{}`, see `pepe --help`", flag).into()),
+                _ => options.push_path(arg),
+            }
+        }
+
+        Ok(options)
+    }
+
+    /// A file to edit. The paths that don't exist ending with `:line` or
+    /// `:line:column` are the file at that position, like the ones of the
+    /// compilers errors
+    fn push_path(&mut self, arg: String) {
+        let path = PathBuf::from(&arg);
+        if let (false, Some((file, line, column))) = 
+                (path.exists(), split_position(&arg)) {
+            self.positions.push((file.into(), line, column));
+            self.paths.push(file.into());
+        } else {
+            self.paths.push(path);
+        }
+    }
+}
+
+/// The file, the line and the column of `file:line` or `file:line:column`
+fn split_position(arg: &str) -> Option<(&str, usize, Option<usize>)> {
+    let number = |text: &str| text.parse().ok().filter(|n: &usize| *n > 0);
+    let (rest, last) = arg.rsplit_once(':')?;
+    let last = number(last)?;
+
+    match rest.rsplit_once(':') {
+        Some((file, line)) if !file.is_empty() => match number(line) {
+            Some(line) => Some((file, line, Some(last))),
+            None => Some((rest, last, None)),
+        },
+        _ if !rest.is_empty() => Some((rest, last, None)),
+        _ => None,
+    }
+}
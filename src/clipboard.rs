@@ -0,0 +1,183 @@
+//! Copy and paste through the clipboard of the system, using the programs
+//! each platform has for it (`clip`, `pbcopy`, `wl-copy`, `xclip`...). When
+//! none of them works the text stays on a register of the editor, so copy
+//! and paste always work at least inside it
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use crate::Result;
+use crate::shell::pipe_program;
+
+/// The programs to write and read the clipboard, with their arguments
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Provider {
+    copy: (&'static str, &'static [&'static str]),
+    paste: (&'static str, &'static [&'static str]),
+}
+
+/// The clipboard of the system and the register used without it
+#[derive(Debug, Default)]
+pub struct Clipboard {
+    /// The text copied last, pasted when the system clipboard can't be read
+    register: String,
+
+    /// The last copy didn't reach the system clipboard, so the register is
+    /// newer than it
+    register_only: bool,
+
+    /// The text selected last with the mouse, pasted with the middle button
+    /// when the system has no primary selection
+    primary: String,
+    primary_only: bool,
+}
+
+impl Clipboard {
+    /// Copy the text to the system clipboard and the register, returns if
+    /// the system clipboard got it too
+    pub fn copy(&mut self, text: &str) -> bool {
+        self.register = text.to_owned();
+        self.register_only = !providers()
+            .iter()
+            .any(|provider| write(provider, text).is_ok());
+
+        !self.register_only
+    }
+
+    /// The text copied last from the editor, even if the system clipboard
+    /// has something newer
+    pub fn register(&self) -> &str {
+        &self.register
+    }
+
+    /// The text of the system clipboard, or of the register without it
+    pub fn paste(&self) -> String {
+        if self.register_only {
+            return self.register.clone();
+        }
+
+        providers()
+            .iter()
+            .find_map(|provider| read(provider).ok())
+            .unwrap_or_else(|| self.register.clone())
+    }
+
+    /// Copy the text to the primary selection, the one of the text selected
+    /// with the mouse on X11 and Wayland. The other systems have only the
+    /// one of the editor
+    pub fn copy_primary(&mut self, text: &str) {
+        self.primary = text.to_owned();
+        self.primary_only = !primary_providers()
+            .iter()
+            .any(|provider| write(provider, text).is_ok());
+    }
+
+    /// The text of the primary selection, the one of the system if it can
+    /// be read
+    pub fn paste_primary(&self) -> String {
+        if self.primary_only {
+            return self.primary.clone();
+        }
+
+        primary_providers()
+            .iter()
+            .find_map(|provider| read(provider).ok())
+            .unwrap_or_else(|| self.primary.clone())
+    }
+}
+
+/// Write the text to the system clipboard with the programs of the
+/// provider. Their output is not read, `xclip` and others stay on the
+/// background owning the clipboard and would keep it open
+fn write(provider: &Provider, text: &str) -> Result<()> {
+    let (program, args) = provider.copy;
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+
+    // Take the stdin so it's closed after writing
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(text.as_bytes())?;
+    }
+    if !child.wait()?.success() {
+        return Err(format!("`{}` failed", program).into());
+    }
+
+    Ok(())
+}
+
+/// Read the system clipboard with the programs of the provider, the line
+/// endings are normalized to `\n`
+fn read(provider: &Provider) -> Result<String> {
+    let (program, args) = provider.paste;
+    let text = pipe_program(program, args, &[])?;
+
+    Ok(text.replace("\r\n", "\n"))
+}
+
+/// The providers that could work on this platform, in order of preference
+fn providers() -> Vec<Provider> {
+    if cfg!(target_os = "windows") {
+        return vec![Provider {
+            copy: ("clip", &[]),
+            paste: ("powershell",
+                &["-NoProfile", "-Command", "Get-Clipboard -Raw"]),
+        }];
+    }
+    if cfg!(target_os = "macos") {
+        return vec![Provider {
+            copy: ("pbcopy", &[]),
+            paste: ("pbpaste", &[]),
+        }];
+    }
+
+    let mut providers = Vec::new();
+    if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+        providers.push(Provider {
+            copy: ("wl-copy", &[]),
+            paste: ("wl-paste", &["--no-newline"]),
+        });
+    }
+    if std::env::var_os("DISPLAY").is_some() {
+        providers.push(Provider {
+            copy: ("xclip", &["-selection", "clipboard"]),
+            paste: ("xclip", &["-selection", "clipboard", "-out"]),
+        });
+        providers.push(Provider {
+            copy: ("xsel", &["--clipboard", "--input"]),
+            paste: ("xsel", &["--clipboard", "--output"]),
+        });
+    }
+
+    providers
+}
+
+/// The providers of the primary selection, only X11 and Wayland have one
+fn primary_providers() -> Vec<Provider> {
+    let mut providers = Vec::new();
+    if cfg!(any(target_os = "windows", target_os = "macos")) {
+        return providers;
+    }
+
+    if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+        providers.push(Provider {
+            copy: ("wl-copy", &["--primary"]),
+            paste: ("wl-paste", &["--primary", "--no-newline"]),
+        });
+    }
+    if std::env::var_os("DISPLAY").is_some() {
+        providers.push(Provider {
+            copy: ("xclip", &["-selection", "primary"]),
+            paste: ("xclip", &["-selection", "primary", "-out"]),
+        });
+        providers.push(Provider {
+            copy: ("xsel", &["--primary", "--input"]),
+            paste: ("xsel", &["--primary", "--output"]),
+        });
+    }
+
+    providers
+}
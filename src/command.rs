@@ -0,0 +1,20 @@
+//! The command line, typed on the minibuffer, where named commands with
+//! arguments like `rename new_name.txt` are run
+
+/// Text being typed on the command line
+#[derive(Debug, Default)]
+pub struct CommandLine {
+    pub input: String,
+}
+
+/// Split a command line into the name of the command and its arguments, a
+/// leading `:` is accepted for the vim users
+pub fn split(input: &str) -> (&str, &str) {
+    let input = input.trim();
+    let input = input.strip_prefix(':').unwrap_or(input).trim_start();
+
+    match input.split_once(char::is_whitespace) {
+        Some((name, args)) => (name, args.trim()),
+        None => (input, ""),
+    }
+}
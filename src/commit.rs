@@ -0,0 +1,71 @@
+//! Git commit messages, the files git opens the editor on to write them. The
+//! summary, the first line, should fit in 50 columns and the rest of the
+//! lines in 72, the text past them is marked. The comments git adds are
+//! dimmed, they are not part of the message. The staged changes the message
+//! describes can be shown on a panel
+
+use std::path::Path;
+use std::process::Command;
+
+use crate::Result;
+use crate::text::{self, Document};
+
+/// Columns of the summary
+pub const SUMMARY_LIMIT: usize = 50;
+
+/// Columns of the lines of the body
+pub const BODY_LIMIT: usize = 72;
+
+/// If the file is one of the messages git asks for
+pub fn is_commit_message(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| matches!(name, "COMMIT_EDITMSG" | "MERGE_MSG"))
+}
+
+/// If the line is a comment of git, removed from the message
+pub fn is_comment(line: &str) -> bool {
+    line.starts_with('#')
+}
+
+/// The columns the line `idx` of the message should fit in, `None` for the
+/// comments
+pub fn limit_of(line: &str, idx: usize) -> Option<usize> {
+    match idx {
+        _ if is_comment(line) => None,
+        0 => Some(SUMMARY_LIMIT),
+        _ => Some(BODY_LIMIT),
+    }
+}
+
+/// The chars of the summary of a commit message when it's longer than its
+/// limit
+pub fn long_summary(doc: &Document) -> Option<usize> {
+    if doc.filetype() != Some("gitcommit") {
+        return None;
+    }
+
+    let summary = doc.buffer.line(0)?;
+    let limit = limit_of(summary, 0)?;
+    text::is_overlong(summary, limit).then(|| summary.chars().count())
+}
+
+/// The diff of the changes staged on the repository of the message. The
+/// messages of git are on its directory, next to the index, the others are
+/// taken as from the repository of the current directory
+pub fn staged_diff(message: &Path) -> Result<Vec<String>> {
+    let mut git = Command::new("git");
+    if let (true, Some(git_dir)) = 
+            (is_commit_message(message), message.parent()) {
+        if !git_dir.as_os_str().is_empty() {
+            git.arg("--git-dir").arg(git_dir);
+        }
+    }
+    let output = git.args(["diff", "--cached", "--no-color"]).output()?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("git diff failed: {}", stderr.trim()).into());
+    }
+
+    Ok(text::split_lines(&output.stdout))
+}
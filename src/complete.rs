@@ -0,0 +1,105 @@
+//! Completion of the word before the cursor with the words of the open
+//! documents, the ones of the current document first. They are listed on a
+//! popup under the cursor, the arrows choose one and Tab or Enter puts it
+
+use std::collections::HashSet;
+
+use crate::hover::Popup;
+use crate::text::{CharClass, Document, WordChars};
+
+/// Words listed at most, the rest are reached typing more of the word
+const MAX_WORDS: usize = 10;
+
+/// The words that complete the one being typed
+#[derive(Debug, Clone)]
+pub struct Completion {
+    /// The part of the word already typed, before the cursor
+    pub prefix: String,
+    pub words: Vec<String>,
+    pub selected: usize,
+}
+
+impl Completion {
+    /// The completion of `prefix` with the words of the documents, `None`
+    /// when no word completes it
+    pub fn new<'a>(
+        prefix: &str,
+        docs: impl Iterator<Item = &'a Document>,
+        word_chars: &WordChars
+    ) -> Option<Self> {
+        let words = candidates(prefix, docs, word_chars);
+        (!words.is_empty()).then(|| Self {
+            prefix: prefix.to_owned(),
+            words,
+            selected: 0,
+        })
+    }
+
+    /// Choose the next word, or the previous one, going around the list
+    pub fn select(&mut self, forward: bool) {
+        let len = self.words.len();
+        self.selected = match forward {
+            true => (self.selected + 1) % len,
+            false => (self.selected + len - 1) % len,
+        };
+    }
+
+    /// What is left to type of the word chosen
+    pub fn rest(&self) -> &str {
+        &self.words[self.selected][self.prefix.len()..]
+    }
+
+    /// The popup of the words under the start of the word typed, which is
+    /// `width` cells before the terminal cell of the cursor
+    pub fn popup(&self, (column, row): (u16, u16), width: usize) -> Popup {
+        Popup {
+            column: column.saturating_sub(width as u16),
+            row,
+            lines: self.words.clone(),
+        }
+    }
+}
+
+/// The part of the word that ends at the byte `column` of the line
+pub fn prefix<'a>(line: &'a str, column: usize, word_chars: &WordChars) -> &'a str {
+    let head = &line[..column];
+    let start = head
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| word_chars.class(*c) == CharClass::Word)
+        .last()
+        .map(|(start, _)| start)
+        .unwrap_or(column);
+
+    &head[start..]
+}
+
+/// The words longer than the prefix that start with it, in the order they
+/// appear on the documents and without repeating them
+fn candidates<'a>(
+    prefix: &str,
+    docs: impl Iterator<Item = &'a Document>,
+    word_chars: &WordChars
+) -> Vec<String> {
+    let mut seen = HashSet::new();
+    let mut words = Vec::new();
+    // The lines of the large files are read from disk, they are skipped
+    for doc in docs.filter(|doc| !doc.large) {
+        for line in doc.buffer.lines() {
+            let line_words = line
+                .split(|c| word_chars.class(c) != CharClass::Word)
+                .filter(|word| word.len() > prefix.len()
+                    && word.starts_with(prefix));
+            for word in line_words {
+                if seen.insert(word) {
+                    words.push(word.to_owned());
+                    if words.len() == MAX_WORDS {
+                        return words;
+                    }
+                }
+            }
+        }
+    }
+
+    words
+}
@@ -0,0 +1,31 @@
+//! Input method that composes the accented chars from two typed ones, like
+//! the compose key does: `'e` is é and `"u` is ü. The accent is inserted
+//! as typed and the letter after it replaces both, for the layouts and the
+//! terminals whose dead keys don't reach the editor. It's enabled per buffer
+
+/// The accents, the letters they go on and what they compose, in the same
+/// order
+const DIGRAPHS: &[(char, &str, &str)] = &[
+    ('\'', "aeiouyAEIOUYcnsCNS", "áéíóúýÁÉÍÓÚÝćńśĆŃŚ"),
+    ('`', "aeiouAEIOU", "àèìòùÀÈÌÒÙ"),
+    ('"', "aeiouyAEIOUY", "äëïöüÿÄËÏÖÜŸ"),
+    ('^', "aeiouAEIOU", "âêîôûÂÊÎÔÛ"),
+    ('~', "anoANO", "ãñõÃÑÕ"),
+    (',', "cC", "çÇ"),
+];
+
+/// If the char starts a digraph
+pub fn is_accent(c: char) -> bool {
+    DIGRAPHS.iter().any(|(accent, _, _)| *accent == c)
+}
+
+/// The char the accent and the letter typed after it compose, `None` when
+/// they don't form a digraph
+pub fn compose(accent: char, letter: char) -> Option<char> {
+    let (_, letters, composed) = DIGRAPHS
+        .iter()
+        .find(|(candidate, _, _)| *candidate == accent)?;
+    let idx = letters.chars().position(|candidate| candidate == letter)?;
+
+    composed.chars().nth(idx)
+}
@@ -0,0 +1,263 @@
+//! The configuration of the user, loaded from `~/.config/pepe/pepe.toml` at
+//! startup. It's a small subset of TOML: the options of the `set` command
+//! at the top level, the key bindings on the `[keys]` table, the hooks on
+//! `[hooks]`, the auto-pairs of each filetype on `[pairs]`, the highlight
+//! rules on `[highlight]`, the preview program of each filetype on
+//! `[preview]` and the tools the text can be exported to on `[export]`
+//!
+//! ```toml
+//! tabwidth = 8
+//! linenumbers = "off"
+//! scrollmargin = 3
+//!
+//! [keys]
+//! "ctrl+q" = "quit"
+//! "alt+j" = "next-buffer"
+//!
+//! [hooks]
+//! on-save-pre = "trim-trailing-whitespace"
+//! on-save-post = "ctags -a"
+//! on-enter = "tmux rename-window"
+//!
+//! [pairs]
+//! rust = "()[]{}\"\""
+//! skipclose = false
+//! markdown.deletepair = false
+//!
+//! [highlight]
+//! "TODO|FIXME" = "black on yellow"
+//!
+//! [preview]
+//! markdown = "mdcat"
+//!
+//! [export]
+//! delta = "delta"
+//! paste = "curl -sF 'file=@-' https://0x0.st"
+//! ```
+//!
+//! Anything not on the file keeps its default
+
+use std::path::{Path, PathBuf};
+
+use crate::{EditorState, Result};
+use crate::action::Action;
+use crate::hooks::{self, HookEvent};
+use crate::input;
+use crate::keymap::KeyBinding;
+
+/// The table of the file a line belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Section {
+    Options,
+    Keys,
+    Hooks,
+    Pairs,
+    Highlight,
+    Preview,
+    Export,
+}
+
+/// Where the config file is, following `$XDG_CONFIG_HOME` when set
+pub fn path() -> Option<PathBuf> {
+    let config_home = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME")
+            .map(|home| PathBuf::from(home).join(".config")))?;
+
+    Some(config_home.join("pepe").join("pepe.toml"))
+}
+
+/// Load the config file, a missing file is not an error. The wrong lines
+/// are skipped and described on the message returned. The file given
+/// instead of the default one must exist
+pub fn load(
+    editor_state: &mut EditorState,
+    given: Option<&Path>
+) -> Option<String> {
+    let path = match given {
+        Some(given) => given.to_owned(),
+        None => path()?,
+    };
+    let text = match std::fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(err) if given.is_some() => 
+            return Some(format!("{}: {}", path.display(), err)),
+        Err(_) => return None,
+    };
+
+    let errors = apply(&text, editor_state);
+    (!errors.is_empty()).then(|| format!("{}: {}",
+        path.display(), errors.join("; ")))
+}
+
+/// Apply every line of the config, returns the errors of the lines that
+/// couldn't be applied
+fn apply(text: &str, editor_state: &mut EditorState) -> Vec<String> {
+    let mut section = Section::Options;
+    let mut errors = Vec::new();
+
+    for (idx, line) in text.lines().enumerate() {
+        match apply_line(line, &mut section, editor_state) {
+            Ok(()) => {}
+            Err(err) => errors.push(format!("line {}: {}", idx + 1, err)),
+        }
+    }
+
+    errors
+}
+
+/// Apply a line of the config, the headers of the tables change the
+/// section of the lines after them
+fn apply_line(
+    line: &str,
+    section: &mut Section,
+    editor_state: &mut EditorState
+) -> Result<()> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return Ok(());
+    }
+
+    if let Some(header) = line.strip_prefix('[') {
+        let name = strip_comment(header)
+            .strip_suffix(']')
+            .ok_or("unclosed table header")?;
+        *section = match name.trim() {
+            "keys" => Section::Keys,
+            "hooks" => Section::Hooks,
+            "pairs" => Section::Pairs,
+            "highlight" => Section::Highlight,
+            "preview" => Section::Preview,
+            "export" => Section::Export,
+            name => return Err(format!("unknown table `{}`", name).into()),
+        };
+        return Ok(());
+    }
+
+    let (key, value) = split_key(line)?;
+    let value = parse_value(value.trim())?;
+
+    match section {
+        Section::Options => {
+            input::set_option(&format!("{}={}", key, value), editor_state)?;
+        }
+        Section::Keys => {
+            let binding = KeyBinding::parse(&key)?;
+            let action = Action::from_name(&value)
+                .ok_or_else(|| format!("unknown action `{}`", value))?;
+            editor_state.keymap.bind(binding, action);
+        }
+        // The built-in actions go by their name, anything else is a shell
+        // command
+        Section::Hooks => {
+            let event = HookEvent::from_name(&key)
+                .ok_or_else(|| format!("unknown event `{}`", key))?;
+            match value.as_str() {
+                "trim-trailing-whitespace" => editor_state.hooks
+                    .attach(event, hooks::trim_trailing_whitespace),
+                command => editor_state.hooks
+                    .attach(event, hooks::shell_command(command.to_owned())),
+            }
+        }
+        // The behaviours go like their options, the rest are the pairs of
+        // the filetypes
+        Section::Pairs if matches!(key.rsplit('.').next(),
+                Some("skipclose" | "deletepair")) => {
+            input::set_option(&format!("pairs.{}={}", key, value),
+                editor_state)?;
+        }
+        Section::Pairs => editor_state.pairs.set_pairs(&key, &value),
+        Section::Highlight => editor_state.highlights.add(&key, &value)?,
+        Section::Preview => editor_state.previews.set(&key, &value),
+        Section::Export => editor_state.export_tools.set(&key, &value),
+    }
+
+    Ok(())
+}
+
+/// Split `key = value`, a quoted key can have `=` inside, like `"alt+="`
+fn split_key(line: &str) -> Result<(String, &str)> {
+    let (key, rest) = if line.starts_with('"') {
+        let end = closing_quote(line).ok_or("unclosed string")?;
+        (parse_string(&line[..=end])?, &line[end + 1..])
+    } else {
+        let end = line.find('=').unwrap_or(line.len());
+        (line[..end].trim().to_owned(), &line[end..])
+    };
+
+    let value = rest.trim_start()
+        .strip_prefix('=')
+        .ok_or("expected `key = value`")?;
+    Ok((key, value))
+}
+
+/// A value, a quoted string or a bare number or boolean that can be followed
+/// by a comment
+fn parse_value(value: &str) -> Result<String> {
+    if value.starts_with('"') {
+        let end = closing_quote(value)
+            .ok_or("unclosed string")?;
+        let rest = value[end + 1..].trim();
+        if !rest.is_empty() && !rest.starts_with('#') {
+            return Err(format!("unexpected `{}` after the string", rest)
+                .into());
+        }
+
+        return parse_string(&value[..=end]);
+    }
+
+    let value = strip_comment(value).trim();
+    if value.is_empty() {
+        return Err("missing value".into());
+    }
+    Ok(value.to_owned())
+}
+
+/// A key or a value, quoted with the escapes of TOML for the quotes and the
+/// backslashes, or bare
+fn parse_string(text: &str) -> Result<String> {
+    let inner = match text.strip_prefix('"') {
+        Some(rest) => rest.strip_suffix('"').ok_or("unclosed string")?,
+        None => return Ok(text.to_owned()),
+    };
+
+    let mut string = String::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            string.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('"') => string.push('"'),
+            Some('\\') => string.push('\\'),
+            Some('t') => string.push('\t'),
+            Some(c) => return Err(format!("unknown escape `\\{}`", c).into()),
+            None => return Err("unclosed string".into()),
+        }
+    }
+
+    Ok(string)
+}
+
+/// The byte index of the quote that closes the string at the start of the
+/// text, skipping the escaped ones
+fn closing_quote(text: &str) -> Option<usize> {
+    let mut escaped = false;
+    for (idx, c) in text.char_indices().skip(1) {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '"' => return Some(idx),
+            _ => {}
+        }
+    }
+
+    None
+}
+
+/// The text before a `#` comment
+fn strip_comment(text: &str) -> &str {
+    text.split('#').next().unwrap_or(text)
+}
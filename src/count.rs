@@ -0,0 +1,124 @@
+//! The lines, the words and the chars of the documents and the selections,
+//! and the limits of words and chars a document can be given, like the ones
+//! of a commit message or an abstract. The counts of every line are kept,
+//! so counting again after an edit only counts the lines edited
+
+use std::cell::RefCell;
+
+use crate::unicode;
+
+/// What a document or a piece of it has
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Count {
+    pub lines: usize,
+
+    /// The runs of chars between whitespace
+    pub words: usize,
+
+    /// The graphemes, every line break is one
+    pub chars: usize,
+}
+
+/// The words of the text, the ones cut at its ends count
+pub fn words(text: &str) -> usize {
+    text.split_whitespace().count()
+}
+
+/// The words and the chars of every line counted, the lines edited are
+/// forgotten and counted again when asked. The length of the line counted
+/// is kept too, so a line changed without the methods of the document is
+/// counted again most of the times
+#[derive(Debug, Default, Clone)]
+pub struct LineCounts {
+    lines: RefCell<Vec<Option<LineCount>>>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct LineCount {
+    len: usize,
+    words: usize,
+    chars: usize,
+}
+
+/// A cache, the documents with the same lines are the same whatever was
+/// counted of them
+impl PartialEq for LineCounts {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for LineCounts {}
+
+impl LineCounts {
+    /// The line was edited
+    pub fn changed(&mut self, line: usize) {
+        if let Some(count) = self.lines.get_mut().get_mut(line) {
+            *count = None;
+        }
+    }
+
+    /// The lines `start..end` were replaced with `lines` lines
+    pub fn replaced(&mut self, start: usize, end: usize, lines: usize) {
+        let counts = self.lines.get_mut();
+        let end = end.min(counts.len());
+        let start = start.min(end);
+        counts.splice(start..end, std::iter::repeat_n(None, lines));
+    }
+
+    /// The words and the chars of the line `idx`, which is `text`
+    pub fn line(&self, idx: usize, text: &str) -> (usize, usize) {
+        let mut lines = self.lines.borrow_mut();
+        if idx >= lines.len() {
+            lines.resize(idx + 1, None);
+        }
+
+        match lines[idx] {
+            Some(count) if count.len == text.len() =>
+                (count.words, count.chars),
+            _ => {
+                let count = LineCount {
+                    len: text.len(),
+                    words: words(text),
+                    chars: unicode::graphemes(text).count(),
+                };
+                lines[idx] = Some(count);
+                (count.words, count.chars)
+            }
+        }
+    }
+}
+
+/// The words and the chars a document shouldn't go past, going past them
+/// is told on the status bar but allowed
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    pub words: Option<usize>,
+    pub chars: Option<usize>,
+}
+
+impl Limits {
+    pub fn is_set(&self) -> bool {
+        self.words.is_some() || self.chars.is_some()
+    }
+
+    /// How the count is against the limits, like `[words 120/500]`
+    pub fn notes(&self, count: Count) -> Vec<String> {
+        let limits = [
+            ("words", count.words, self.words),
+            ("chars", count.chars, self.chars),
+        ];
+        limits
+            .into_iter()
+            .filter_map(|(name, count, limit)| {
+                let limit = limit?;
+                Some(if count > limit {
+                    format!("[{} {}/{}, {} over]",
+                        name, count, limit, count - limit)
+                } else {
+                    format!("[{} {}/{}]", name, count, limit)
+                })
+            })
+            .collect()
+    }
+}
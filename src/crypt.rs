@@ -0,0 +1,197 @@
+//! Transparent editing of encrypted files. The files encrypted with `gpg`
+//! or `age` are decrypted when opened and encrypted again when saved, the
+//! text is only in memory while editing, it never touches the disk
+//!
+//! The gpg files are encrypted with a passphrase (`gpg --symmetric`), it's
+//! asked on a prompt when opening them and kept with the document to
+//! encrypt it again. The age files use the identity of the `ageidentity`
+//! option, age only reads passphrases from the terminal
+
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use crate::Result;
+use crate::shell::pipe_program_bytes;
+
+/// The program a file is encrypted with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cipher {
+    Gpg,
+    Age,
+}
+
+/// Headers of the armored files, they are text so the extension is not
+/// needed to recognize them
+const GPG_ARMOR: &[u8] = b"-----BEGIN PGP MESSAGE-----";
+const AGE_ARMOR: &[u8] = b"-----BEGIN AGE ENCRYPTED FILE-----";
+const AGE_BINARY: &[u8] = b"age-encryption.org/v1";
+
+/// How a document is encrypted again when saved
+#[derive(Clone, PartialEq, Eq)]
+pub struct Encryption {
+    pub cipher: Cipher,
+
+    /// If the file is written as text instead of binary
+    pub armor: bool,
+
+    /// The key to encrypt it, the passphrase of gpg or the identity file of
+    /// age
+    key: Key,
+}
+
+#[derive(Clone, PartialEq, Eq)]
+enum Key {
+    Passphrase(String),
+    Identity(PathBuf),
+}
+
+/// The passphrase is never shown, not even on the debug output
+impl std::fmt::Debug for Encryption {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("Encryption")
+            .field("cipher", &self.cipher)
+            .field("armor", &self.armor)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The cipher of the file by its header, or by its extension for the
+/// binary gpg files that have no fixed header. `None` for plain files
+pub fn detect(path: &Path) -> Option<Cipher> {
+    let header = read_header(path);
+    if header.starts_with(GPG_ARMOR) {
+        Some(Cipher::Gpg)
+    } else if header.starts_with(AGE_ARMOR) || header.starts_with(AGE_BINARY) {
+        Some(Cipher::Age)
+    } else {
+        match path.extension()?.to_str()? {
+            "gpg" | "pgp" => Some(Cipher::Gpg),
+            _ => None,
+        }
+    }
+}
+
+/// The first bytes of the file, enough for the headers, without the BOM
+/// some editors add to the armored files
+fn read_header(path: &Path) -> Vec<u8> {
+    let mut header = vec![0; GPG_ARMOR.len() + 3];
+    let read = std::fs::File::open(path)
+        .and_then(|mut file| file.read(&mut header))
+        .unwrap_or(0);
+    header.truncate(read);
+    if header.starts_with(b"\xef\xbb\xbf") {
+        header.drain(..3);
+    }
+
+    header
+}
+
+impl Encryption {
+    /// Decrypt a gpg file with the passphrase
+    pub fn gpg(path: &Path, passphrase: String) -> Result<(Self, Vec<u8>)> {
+        let encryption = Self {
+            cipher: Cipher::Gpg,
+            armor: read_header(path).starts_with(GPG_ARMOR),
+            key: Key::Passphrase(passphrase),
+        };
+        let plaintext = encryption.decrypt(path)?;
+
+        Ok((encryption, plaintext))
+    }
+
+    /// Decrypt an age file with the identity
+    pub fn age(
+        path: &Path,
+        identity: Option<&Path>
+    ) -> Result<(Self, Vec<u8>)> {
+        let identity = identity
+            .ok_or("set ageidentity=<file> to open age files")?;
+        let encryption = Self {
+            cipher: Cipher::Age,
+            armor: read_header(path).starts_with(AGE_ARMOR),
+            key: Key::Identity(identity.to_owned()),
+        };
+        let plaintext = encryption.decrypt(path)?;
+
+        Ok((encryption, plaintext))
+    }
+
+    /// An encryption with the passphrase that isn't read from a file
+    #[cfg(test)]
+    pub fn with_passphrase(passphrase: &str) -> Self {
+        Self {
+            cipher: Cipher::Gpg,
+            armor: false,
+            key: Key::Passphrase(passphrase.to_owned()),
+        }
+    }
+
+    /// The plain contents of the encrypted file
+    pub fn decrypt(&self, path: &Path) -> Result<Vec<u8>> {
+        let path = path.display().to_string();
+        match &self.key {
+            // The passphrase is the first line of the stdin, never an
+            // argument that other users could see
+            Key::Passphrase(passphrase) => pipe_program_bytes("gpg", &[
+                    "--batch", "--quiet", "--pinentry-mode", "loopback",
+                    "--passphrase-fd", "0", "--decrypt", &path,
+                ], format!("{}\n", passphrase).as_bytes()),
+            Key::Identity(identity) => {
+                let identity = identity.display().to_string();
+                pipe_program_bytes("age",
+                    &["--decrypt", "--identity", &identity, &path], &[])
+            }
+        }
+    }
+
+    /// The contents encrypted as they are written to the file
+    pub fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>> {
+        let mut args = Vec::new();
+        let mut input = Vec::new();
+        let program = match &self.key {
+            Key::Passphrase(passphrase) => {
+                args.extend(["--batch", "--quiet", "--pinentry-mode",
+                    "loopback", "--passphrase-fd", "0", "--symmetric",
+                    "--output", "-"]);
+                input.extend(format!("{}\n", passphrase).into_bytes());
+                "gpg"
+            }
+            Key::Identity(identity) => {
+                args.extend(["--encrypt", "--identity"]);
+                args.push(identity.to_str().ok_or("invalid identity path")?);
+                "age"
+            }
+        };
+        if self.armor {
+            args.push("--armor");
+        }
+        input.extend_from_slice(plaintext);
+
+        pipe_program_bytes(program, &args, &input)
+    }
+}
+
+/// The passphrase being typed to open an encrypted file, the chars are
+/// hidden
+#[derive(Debug)]
+pub struct PassphrasePrompt {
+    pub path: PathBuf,
+    pub input: String,
+}
+
+impl PassphrasePrompt {
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        Self { path: path.as_ref().to_owned(), input: String::new() }
+    }
+
+    /// The text of the prompt, a `*` for each char typed
+    pub fn prompt(&self) -> String {
+        format!("Passphrase for {}: {}",
+            self.path.display(), "*".repeat(self.input.chars().count()))
+    }
+
+    /// Column of the prompt where the input ends, for the cursor
+    pub fn input_column(&self) -> usize {
+        self.prompt().chars().count()
+    }
+}
@@ -0,0 +1,118 @@
+//! The cursors added to the main one to edit many places at once. They are
+//! positions of the document instead of cells of the terminal, only the
+//! main cursor moves the view. The edits are done at each cursor from the
+//! last to the first, so the cursors already done are kept as positions
+//! counted from the end of the document, which the edits before them don't
+//! move
+
+use std::ops::Range;
+
+use crate::text::{CharClass, Document, Position, WordChars};
+
+/// The cursors besides the main one
+#[derive(Debug, Clone, Default)]
+pub struct CursorSet {
+    others: Vec<Position>,
+}
+
+impl CursorSet {
+    pub fn is_empty(&self) -> bool {
+        self.others.is_empty()
+    }
+
+    pub fn positions(&self) -> &[Position] {
+        &self.others
+    }
+
+    /// Add a cursor, returns if there wasn't one at the position
+    pub fn add(&mut self, position: Position) -> bool {
+        if self.others.contains(&position) {
+            return false;
+        }
+        self.others.push(position);
+
+        true
+    }
+
+    /// Take the cursors out, the set is left empty
+    pub fn take(&mut self) -> Vec<Position> {
+        std::mem::take(&mut self.others)
+    }
+
+    pub fn clear(&mut self) {
+        self.others.clear();
+    }
+}
+
+/// A position counted from the end of the document, the edits before it
+/// don't change it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FromEnd {
+    lines: usize,
+    bytes: usize,
+}
+
+impl FromEnd {
+    pub fn new(doc: &Document, position: Position) -> Self {
+        let line = doc.buffer.line(position.line).unwrap_or("");
+        Self {
+            lines: doc.buffer.len().saturating_sub(position.line),
+            bytes: line.len().saturating_sub(position.byte),
+        }
+    }
+
+    /// The position it's at on the document as it is now
+    pub fn position(&self, doc: &Document) -> Position {
+        let line = doc.buffer.len().saturating_sub(self.lines);
+        let len = doc.buffer.line(line).map(str::len).unwrap_or(0);
+
+        doc.position(line, len.saturating_sub(self.bytes))
+    }
+}
+
+/// The bytes of the word the byte of the line is on, or the word that ends
+/// right before it
+pub fn word_at(line: &str, byte: usize, word_chars: &WordChars)
+        -> Option<Range<usize>> {
+    let is_word_char = |c: char| word_chars.class(c) == CharClass::Word;
+    let start = line[..byte]
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| is_word_char(*c))
+        .last()
+        .map(|(idx, _)| idx)
+        .unwrap_or(byte);
+    let end = line[byte..]
+        .char_indices()
+        .find(|(_, c)| !is_word_char(*c))
+        .map(|(idx, _)| byte + idx)
+        .unwrap_or(line.len());
+
+    (start < end).then_some(start..end)
+}
+
+/// Where the next occurrence of the whole word starts from the position on,
+/// going around the end of the document
+pub fn next_occurrence(
+    doc: &Document,
+    word: &str,
+    from: Position,
+    word_chars: &WordChars
+) -> Option<Position> {
+    let lines = doc.buffer.len();
+    for offset in 0..=lines {
+        let idx = (from.line + offset) % lines;
+        let line = doc.buffer.line(idx)?;
+        let first = if offset == 0 { from.byte } else { 0 };
+        let found = line.match_indices(word)
+            .map(|(start, _)| start)
+            .filter(|start| offset == lines || *start >= first)
+            .find(|start| word_at(line, *start, word_chars)
+                .is_some_and(|range| range == (*start..start + word.len())));
+        if let Some(start) = found {
+            return Some(doc.position(idx, start));
+        }
+    }
+
+    None
+}
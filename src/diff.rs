@@ -0,0 +1,240 @@
+//! Line based diff between two versions of a text, used to review what the
+//! buffer changed compared to the file on disk
+
+use crossterm::style::Color;
+
+/// Lines of context printed around each hunk of a unified diff
+const CONTEXT: usize = 3;
+
+/// A single step to transform the old lines into the new ones, the indices
+/// point to the line on its version
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change {
+    /// The line is on both versions
+    Equal(usize, usize),
+
+    /// The line of the old version was removed
+    Delete(usize),
+
+    /// The line of the new version was added
+    Insert(usize),
+}
+
+/// Compute the changes between `old` and `new`, the common prefix and suffix
+/// are striped before running the Myers algorithm on the rest, the typical
+/// edit only touches a couple lines in the middle of the file
+pub fn diff(old: &[String], new: &[String]) -> Vec<Change> {
+    let mut prefix = 0;
+    while prefix < old.len()
+            && prefix < new.len()
+            && old[prefix] == new[prefix] {
+        prefix += 1;
+    }
+
+    let mut suffix = 0;
+    while suffix < old.len() - prefix
+            && suffix < new.len() - prefix
+            && old[old.len() - 1 - suffix] == new[new.len() - 1 - suffix] {
+        suffix += 1;
+    }
+
+    let mut changes: Vec<Change> = (0..prefix)
+        .map(|i| Change::Equal(i, i))
+        .collect();
+
+    changes.extend(
+        myers(
+            &old[prefix..old.len() - suffix],
+            &new[prefix..new.len() - suffix])
+        .into_iter()
+        .map(|change| match change {
+            Change::Equal(a, b) => Change::Equal(a + prefix, b + prefix),
+            Change::Delete(a)   => Change::Delete(a + prefix),
+            Change::Insert(b)   => Change::Insert(b + prefix),
+        }));
+
+    changes.extend((0..suffix).map(|i| Change::Equal(
+        old.len() - suffix + i,
+        new.len() - suffix + i)));
+
+    changes
+}
+
+/// Greedy Myers diff, it finds the shortest edit script exploring the
+/// diagonals `k = x - y`, the state of each round is kept to backtrack the
+/// path once the end is reached
+fn myers(a: &[String], b: &[String]) -> Vec<Change> {
+    let n = a.len() as isize;
+    let m = b.len() as isize;
+    let max = (n + m) as usize;
+    if max == 0 {
+        return Vec::new();
+    }
+
+    // `v[k + offset]` is the furthest x reached on the diagonal k
+    let offset = max as isize;
+    let mut v = vec![0isize; 2 * max + 2];
+    let mut trace = Vec::new();
+
+    'rounds: for d in 0..=max as isize {
+        trace.push(v.clone());
+
+        let mut k = -d;
+        while k <= d {
+            let idx = (k + offset) as usize;
+
+            // Choose between comming from an insertion (down) or a deletion
+            // (right)
+            let mut x = if k == -d || (k != d && v[idx - 1] < v[idx + 1]) {
+                v[idx + 1]
+            } else {
+                v[idx - 1] + 1
+            };
+            let mut y = x - k;
+
+            // Follow the snake of equal lines
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+
+            v[idx] = x;
+            if x >= n && y >= m {
+                break 'rounds;
+            }
+
+            k += 2;
+        }
+    }
+
+    // Walk back from the end to the start
+    let mut changes = Vec::new();
+    let mut x = n;
+    let mut y = m;
+    for (d, v) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        let k = x - y;
+        let idx = (k + offset) as usize;
+
+        let prev_k = if k == -d || (k != d && v[idx - 1] < v[idx + 1]) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let prev_x = v[(prev_k + offset) as usize];
+        let prev_y = prev_x - prev_k;
+
+        while x > prev_x && y > prev_y {
+            changes.push(Change::Equal(x as usize - 1, y as usize - 1));
+            x -= 1;
+            y -= 1;
+        }
+
+        if d > 0 {
+            if x == prev_x {
+                changes.push(Change::Insert(y as usize - 1));
+            } else {
+                changes.push(Change::Delete(x as usize - 1));
+            }
+        }
+
+        x = prev_x;
+        y = prev_y;
+    }
+
+    changes.reverse();
+    changes
+}
+
+/// Format the changes between `old` and `new` like `diff -u` does, empty
+/// when both versions are equal
+pub fn unified(
+    old: &[String],
+    new: &[String],
+    old_name: &str,
+    new_name: &str
+) -> Vec<String> {
+    let changes = diff(old, new);
+
+    // Group the changes on hunks, two changes belong to the same hunk if
+    // their contexts touch
+    let mut hunks: Vec<(usize, usize)> = Vec::new();
+    for (i, change) in changes.iter().enumerate() {
+        if let Change::Equal(..) = change {
+            continue;
+        }
+
+        let start = i.saturating_sub(CONTEXT);
+        let end = usize::min(changes.len(), i + CONTEXT + 1);
+        match hunks.last_mut() {
+            Some((_, last_end)) if *last_end >= start => *last_end = end,
+            _ => hunks.push((start, end)),
+        }
+    }
+
+    if hunks.is_empty() {
+        return Vec::new();
+    }
+
+    let mut out = vec![
+        format!("--- {}", old_name),
+        format!("+++ {}", new_name),
+    ];
+
+    for (start, end) in hunks {
+        // Lines of each version consumed before the hunk starts
+        let old_start = changes[..start]
+            .iter()
+            .filter(|c| !matches!(c, Change::Insert(_)))
+            .count();
+        let new_start = changes[..start]
+            .iter()
+            .filter(|c| !matches!(c, Change::Delete(_)))
+            .count();
+
+        let mut body = Vec::new();
+        let mut old_len = 0;
+        let mut new_len = 0;
+        for change in &changes[start..end] {
+            match *change {
+                Change::Equal(a, _) => {
+                    body.push(format!(" {}", old[a]));
+                    old_len += 1;
+                    new_len += 1;
+                }
+                Change::Delete(a) => {
+                    body.push(format!("-{}", old[a]));
+                    old_len += 1;
+                }
+                Change::Insert(b) => {
+                    body.push(format!("+{}", new[b]));
+                    new_len += 1;
+                }
+            }
+        }
+
+        // An empty range points to the line before, as `diff -u` does
+        let old_start = if old_len == 0 { old_start } else { old_start + 1 };
+        let new_start = if new_len == 0 { new_start } else { new_start + 1 };
+        out.push(format!("@@ -{},{} +{},{} @@",
+            old_start, old_len, new_start, new_len));
+        out.extend(body);
+    }
+
+    out
+}
+
+/// Color of each line of an unified diff when shown on a panel
+pub fn highlight(line: &str) -> Color {
+    if line.starts_with("+++") || line.starts_with("---") {
+        Color::White
+    } else if line.starts_with("@@") {
+        Color::Cyan
+    } else if line.starts_with('+') {
+        Color::Green
+    } else if line.starts_with('-') {
+        Color::Red
+    } else {
+        Color::Reset
+    }
+}
@@ -0,0 +1,1086 @@
+//! The editor as a state machine: the wakes of the event loop go in, the
+//! frames come out drawn on a grid that is flushed to any `Backend`. It never
+//! asks the terminal anything, its size is given, so it runs the same on
+//! the terminal and headless
+
+use std::path::PathBuf;
+use std::time::Instant;
+
+use crate::{EditorState, Result};
+use crate::backend::Backend;
+use crate::buffers::BufferList;
+use crate::events::{self, Wake};
+use crate::grid::Grid;
+use crate::input::{Cursor, CursorState, process_keypress};
+use crate::render::{RenderState, minibuffer_line, refresh_screen};
+use crate::stream::OutputStream;
+use crate::text::Document;
+use crate::window::LineNumbers;
+
+pub struct Editor {
+    pub doc: Option<Document>,
+    pub cursor: Cursor,
+    pub editor_state: EditorState,
+    pub cursor_state: CursorState,
+    pub render_state: RenderState,
+
+    /// The frames are drawn on it and only what changed is flushed
+    grid: Grid,
+
+    /// The document being edited, when it changes the terminal and the
+    /// hooks are told
+    active: Option<PathBuf>,
+
+    /// What the minibuffer shows, it's repainted only when it changes
+    minibuffer: Option<(String, bool)>,
+
+    /// The gutter and the line its numbers count from, the document area
+    /// moves when it grows and the relative numbers change with the cursor
+    gutter: (usize, usize),
+}
+
+impl Editor {
+    pub fn new(
+        doc: Option<Document>,
+        cursor: Cursor,
+        editor_state: EditorState,
+        cursor_state: CursorState,
+        render_state: RenderState
+    ) -> Self {
+        Self {
+            grid: Grid::new(editor_state.terminal),
+            gutter: (editor_state.gutter_width(), 0),
+            doc,
+            cursor,
+            editor_state,
+            cursor_state,
+            render_state,
+            active: None,
+            minibuffer: None,
+        }
+    }
+
+    /// Follow the size of the terminal, a new one leaves nothing of the last
+    /// frame
+    pub fn resize(&mut self, size: (u16, u16)) {
+        if self.grid.resize(size) {
+            self.render_state.modif_all = true;
+            self.render_state.modif_status = true;
+        }
+        self.editor_state.terminal = size;
+        self.editor_state.update_size();
+    }
+
+    /// When the editor has work to do without events, like the messages
+    /// that expire
+    pub fn deadline(&self) -> Option<Instant> {
+        let editor_state = &self.editor_state;
+        events::earliest([
+            editor_state.message.deadline(),
+            editor_state.hover.deadline(),
+            editor_state.stream.as_ref().and_then(OutputStream::deadline),
+            editor_state.spinner.deadline(),
+            editor_state.autoscroll.deadline(),
+        ])
+    }
+
+    pub fn handle(&mut self, wake: Wake) -> Result<()> {
+        process_keypress(
+            wake,
+            &mut self.doc,
+            &mut self.cursor,
+            &mut self.editor_state,
+            &mut self.cursor_state,
+            &mut self.render_state)
+    }
+
+    /// Bring the view up to date with what happened since the last frame,
+    /// draw what changed and write it to the backend. The bell is rung
+    /// after it
+    pub fn render(&mut self, backend: &mut impl Backend) -> Result<()> {
+        self.update();
+        let Self {
+            doc,
+            cursor,
+            editor_state,
+            cursor_state,
+            render_state,
+            grid,
+            ..
+        } = self;
+
+        // Repaint on the screen what needs to be repainted, when nothing
+        // changed the frame is skipped
+        if !render_state.is_clean() {
+            refresh_screen(
+                grid, doc, cursor, editor_state, cursor_state, render_state)?;
+            grid.flush(backend)?;
+            editor_state.latency.frame_flushed();
+            render_state.clear();
+        }
+        editor_state.osc.flush(backend)?;
+
+        // The bell is drawn over the frame, the status bar is repainted on
+        // the next one
+        if render_state.bell {
+            let width = editor_state.terminal.0 as usize;
+            let status_row = (editor_state.top + editor_state.rows) as u16;
+            editor_state.bell.ring(grid, backend, status_row, width)?;
+            render_state.bell = false;
+            render_state.modif_status = true;
+        }
+
+        Ok(())
+    }
+
+    /// The documents with changes not saved, of every buffer of every tab
+    pub fn unsaved(&self) -> Vec<&Document> {
+        // The state stored for the current buffer and tab is stale
+        let tabs = &self.editor_state.tabs;
+        let others = tabs.list
+            .iter()
+            .enumerate()
+            .filter(|(idx, _)| *idx != tabs.current)
+            .flat_map(|(_, tab)| tab.doc.iter().chain(
+                other_buffers(&tab.buffers)));
+
+        self.doc
+            .iter()
+            .chain(other_buffers(&self.editor_state.buffers))
+            .chain(others)
+            .filter(|doc| doc.dirty)
+            .collect()
+    }
+
+    /// The work of every frame before it's drawn: the view follows the
+    /// cursor and what depends on the document is updated
+    fn update(&mut self) {
+        let Self {
+            doc: curr_doc,
+            cursor,
+            editor_state,
+            cursor_state,
+            render_state,
+            ..
+        } = self;
+
+        let path = curr_doc.as_ref().map(|doc| doc.path.clone());
+        if path != self.active {
+            self.active = path;
+            if let Some(doc) = curr_doc {
+                if let Err(err) = editor_state.enter_document(doc) {
+                    editor_state.message.error(format!("Error: {}", err));
+                    render_state.modif_status = true;
+                }
+            }
+        }
+
+        // The wrapped lines above can leave the cursor below the view
+        if let (true, Some(doc)) = (editor_state.window.wrap, &*curr_doc) {
+            cursor.scroll_to_wrapped(
+                doc, editor_state, cursor_state, render_state);
+        }
+        if let Some(doc) = curr_doc {
+            cursor.scroll_horizontally(
+                doc, editor_state, cursor_state, render_state);
+        }
+
+        // The line the cursor sits on and the edits warm the heat map, a
+        // line that changes of level is repainted
+        if let Some(doc) = curr_doc {
+            let line = cursor.line(cursor_state);
+            let changed = editor_state.heat
+                .update(&doc.path, line, &editor_state.edits);
+            if changed && editor_state.heatmap {
+                render_state.modif_all = true;
+            }
+        }
+
+        // The marks, the jumps, the edits, the bookmarks and the ghosts of
+        // the review follow the lines inserted and removed. The bookmarks
+        // that moved are saved
+        if let Some(doc) = curr_doc.as_mut() {
+            let shifts = doc.take_line_shifts();
+            editor_state.edits.shift(&doc.path, &shifts);
+            if !shifts.is_empty() {
+                editor_state.marks.shift(&doc.path, &shifts);
+                editor_state.jumps.shift(&doc.path, &shifts);
+                editor_state.review.shift(&doc.path, &shifts);
+                if editor_state.bookmarks.shift(&doc.path, &shifts) {
+                    if let Err(err) = editor_state.bookmarks.save() {
+                        editor_state.message
+                            .error(format!("Error: {}", err));
+                    }
+                }
+            }
+        }
+
+        // The lines of a large file are read when they're needed, one that
+        // is not UTF-8 makes it binary as if it was seen when opened
+        if let Some(doc) = curr_doc.as_mut()
+                .filter(|doc| !doc.binary && doc.buffer.has_invalid_lines()) {
+            doc.binary = true;
+            doc.read_only = true;
+            editor_state.message.error(format!(
+                "{} is binary, now read-only, `hex` shows its bytes",
+                doc.path.display()));
+            render_state.modif_status = true;
+        }
+
+        // The language server of the document knows about its changes
+        if let Some(doc) = curr_doc {
+            let sender = editor_state.events.sender();
+            if let Err(err) = editor_state.lsp.sync(doc, &sender) {
+                editor_state.message.error(format!("Error: {}", err));
+            }
+        }
+
+        // The marks of the changed lines are on the gutter
+        let marks_changed = curr_doc.as_ref()
+            .is_some_and(|doc| editor_state.changes.update(doc));
+        if marks_changed {
+            render_state.modif_all = true;
+        }
+
+        // The bracket under the cursor is highlighted with its pair, both
+        // are repainted when they change
+        let brackets = curr_doc.as_ref().and_then(|doc| {
+            let at = cursor.position(doc, cursor_state);
+            doc.matching_bracket(at).map(|pair| (at, pair))
+        });
+        if brackets != editor_state.brackets {
+            editor_state.brackets = brackets;
+            render_state.modif_all = true;
+        }
+
+        let numbered_line = match editor_state.window.line_numbers {
+            LineNumbers::Relative | LineNumbers::Hybrid =>
+                cursor.line(cursor_state),
+            _ => 0,
+        };
+        if (editor_state.gutter_width(), numbered_line) != self.gutter {
+            self.gutter = (editor_state.gutter_width(), numbered_line);
+            editor_state.update_size();
+            render_state.modif_all = true;
+        }
+
+        // The messages expire on their own, without events
+        editor_state.message.expire();
+        let line = minibuffer_line(editor_state);
+        if self.minibuffer.as_ref() != Some(&line) {
+            self.minibuffer = Some(line);
+            render_state.modif_message = true;
+        }
+    }
+}
+
+/// The documents of the buffers but the current one
+fn other_buffers(buffers: &BufferList) -> impl Iterator<Item = &Document> {
+    buffers.list
+        .iter()
+        .enumerate()
+        .filter(move |(idx, _)| *idx != buffers.current)
+        .filter_map(|(_, buffer)| buffer.doc.as_ref())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crossterm::event::{
+        Event, KeyCode, KeyEvent, KeyModifiers, MouseEvent, MouseEventKind
+    };
+    use crossterm::style::{Color, ContentStyle};
+
+    use super::*;
+    use crate::backend::Recorder;
+    use crate::bookmarks::Bookmarks;
+    use crate::config;
+    use crate::crypt::Encryption;
+    use crate::fileops;
+    use crate::input::{set_option, Mode};
+    use crate::hooks::{self, HookEvent};
+    use crate::print;
+    use crate::recovery;
+    use crate::scrollbar;
+    use crate::shell;
+    use crate::text::DocKind;
+
+    /// The editor on a terminal of `size` with `text` open, the frames go
+    /// nowhere
+    fn headless(text: &str, size: (u16, u16)) -> Editor {
+        let doc = Document::from_bytes("test.txt", text.as_bytes());
+        let mut editor_state = EditorState::new(size);
+        editor_state.doc_lines = doc.buffer.len();
+        editor_state.update_size();
+
+        let mut editor = Editor::new(
+            Some(doc),
+            Cursor::default(),
+            editor_state,
+            CursorState::default(),
+            RenderState::new());
+        frame(&mut editor);
+        editor
+    }
+
+    /// Render the next frame, the operations it was drawn with
+    fn frame(editor: &mut Editor) -> Vec<String> {
+        let mut recorder = Recorder::new(editor.editor_state.terminal);
+        editor.render(&mut recorder).unwrap();
+        recorder.ops
+    }
+
+    fn press(editor: &mut Editor, code: KeyCode, modifiers: KeyModifiers) {
+        let key = Event::Key(KeyEvent::new(code, modifiers));
+        editor.handle(Wake::Input(key)).unwrap();
+        frame(editor);
+    }
+
+    fn times(editor: &mut Editor, code: KeyCode, count: usize) {
+        for _ in 0..count {
+            press(editor, code, KeyModifiers::NONE);
+        }
+    }
+
+    fn wheel(editor: &mut Editor, kind: MouseEventKind) {
+        let event = Event::Mouse(MouseEvent {
+            kind,
+            column: 10,
+            row: 2,
+            modifiers: KeyModifiers::NONE,
+        });
+        editor.handle(Wake::Input(event)).unwrap();
+        frame(editor);
+    }
+
+    /// The line and the byte of the cursor on the document
+    fn position(editor: &Editor) -> (usize, usize) {
+        let doc = editor.doc.as_ref().unwrap();
+        let position = editor.cursor.position(doc, &editor.cursor_state);
+        (position.line, position.byte)
+    }
+
+    fn scroll(editor: &Editor) -> usize {
+        editor.cursor_state.viewport.scroll_y
+    }
+
+    fn lines(count: usize) -> String {
+        (0..count).map(|line| format!("line {}\n", line)).collect()
+    }
+
+    #[test]
+    fn frames_write_only_what_changed() {
+        let mut editor = headless("hello\nworld\n", (40, 10));
+        assert_eq!(editor.grid.row(0).trim_end(), " 0 hello");
+        assert_eq!(editor.grid.row(1).trim_end(), " 1 world");
+        assert_eq!(editor.grid.cursor(), Some((3, 0)));
+
+        // Nothing changed, nothing is written
+        assert!(frame(&mut editor).is_empty());
+
+        press(&mut editor, KeyCode::Down, KeyModifiers::NONE);
+        assert_eq!(editor.grid.cursor(), Some((3, 1)));
+    }
+
+    #[test]
+    fn resize_repaints_everything() {
+        let mut editor = headless("hello\n", (40, 10));
+        editor.resize((30, 8));
+        assert_eq!(editor.editor_state.rows, 6);
+
+        assert!(!frame(&mut editor).is_empty());
+        assert_eq!(editor.grid.row(0).chars().count(), 30);
+    }
+
+    #[test]
+    fn right_and_left_cross_the_lines() {
+        // The cursor goes over the chars, past the last one is the next line
+        let mut editor = headless("ab\ncd\n", (40, 10));
+        times(&mut editor, KeyCode::Right, 1);
+        assert_eq!(position(&editor), (0, 1));
+        times(&mut editor, KeyCode::Right, 1);
+        assert_eq!(position(&editor), (1, 0));
+        times(&mut editor, KeyCode::Left, 1);
+        assert_eq!(position(&editor), (0, 1));
+    }
+
+    #[test]
+    fn right_and_left_on_the_last_line() {
+        let mut editor = headless("ab\ncd", (40, 10));
+        times(&mut editor, KeyCode::Down, 1);
+        times(&mut editor, KeyCode::Right, 1);
+        assert_eq!(position(&editor), (1, 1));
+        times(&mut editor, KeyCode::Left, 1);
+        assert_eq!(position(&editor), (1, 0));
+    }
+
+    #[test]
+    fn motions_stop_at_the_ends() {
+        let mut editor = headless("ab\ncd", (40, 10));
+        times(&mut editor, KeyCode::Left, 1);
+        times(&mut editor, KeyCode::Up, 1);
+        assert_eq!(position(&editor), (0, 0));
+
+        times(&mut editor, KeyCode::Down, 3);
+        times(&mut editor, KeyCode::Right, 5);
+        assert_eq!(position(&editor), (1, 1));
+    }
+
+    #[test]
+    fn vertical_motions_keep_the_column() {
+        // At the end of a line the cursor stays at the end of the others
+        let mut editor = headless("abcdef\nab\nabcdef\n", (40, 10));
+        times(&mut editor, KeyCode::Right, 5);
+        times(&mut editor, KeyCode::Down, 1);
+        assert_eq!(position(&editor), (1, 1));
+        times(&mut editor, KeyCode::Down, 1);
+        assert_eq!(position(&editor), (2, 5));
+    }
+
+    #[test]
+    fn vertical_motions_keep_the_column_on_the_terminal() {
+        // The wide chars take two columns and three bytes
+        let mut editor = headless("abcd\n日本\n", (40, 10));
+        times(&mut editor, KeyCode::Right, 2);
+        times(&mut editor, KeyCode::Down, 1);
+        assert_eq!(position(&editor), (1, 3));
+        assert_eq!(editor.grid.cursor(), Some((5, 1)));
+    }
+
+    #[test]
+    fn home_and_end() {
+        let mut editor = headless("    indented\n", (40, 10));
+        times(&mut editor, KeyCode::End, 1);
+        assert_eq!(position(&editor), (0, 12));
+        times(&mut editor, KeyCode::Home, 1);
+        assert_eq!(position(&editor), (0, 4));
+    }
+
+    #[test]
+    fn down_scrolls_past_the_last_row() {
+        // 10 rows, the status bar and the minibuffer leave 8 to the text
+        let mut editor = headless(&lines(20), (40, 10));
+        let rows = editor.editor_state.rows;
+        assert_eq!(rows, 8);
+
+        times(&mut editor, KeyCode::Down, rows - 1);
+        assert_eq!(scroll(&editor), 0);
+        times(&mut editor, KeyCode::Down, 1);
+        assert_eq!(scroll(&editor), 1);
+        assert_eq!(position(&editor), (rows, 0));
+        assert_eq!(editor.grid.row(0).trim_end(), " 1 line 1");
+        assert_eq!(editor.grid.cursor(), Some((3, rows as u16 - 1)));
+
+        times(&mut editor, KeyCode::Up, rows);
+        assert_eq!(scroll(&editor), 0);
+        assert_eq!(position(&editor), (0, 0));
+    }
+
+    #[test]
+    fn scroll_margin_keeps_lines_around_the_cursor() {
+        let mut editor = headless(&lines(20), (40, 10));
+        editor.editor_state.scroll_margin = 2;
+
+        times(&mut editor, KeyCode::Down, 5);
+        assert_eq!(scroll(&editor), 0);
+        times(&mut editor, KeyCode::Down, 1);
+        assert_eq!(scroll(&editor), 1);
+        assert_eq!(editor.cursor.row, 5);
+    }
+
+    #[test]
+    fn page_down_and_up_move_by_a_page() {
+        let mut editor = headless(&lines(30), (40, 10));
+        let rows = editor.editor_state.rows;
+
+        times(&mut editor, KeyCode::PageDown, 1);
+        assert_eq!(scroll(&editor), rows);
+        assert_eq!(position(&editor).0, rows);
+        times(&mut editor, KeyCode::PageUp, 1);
+        assert_eq!(scroll(&editor), 0);
+        assert_eq!(position(&editor).0, 0);
+    }
+
+    #[test]
+    fn page_down_stops_at_the_last_line() {
+        let mut editor = headless(&lines(10), (40, 10));
+        times(&mut editor, KeyCode::PageDown, 3);
+        let doc = editor.doc.as_ref().unwrap();
+        assert_eq!(position(&editor).0, doc.buffer.len() - 1);
+        assert!(editor.cursor.row < editor.editor_state.rows);
+    }
+
+    #[test]
+    fn motions_on_an_empty_document() {
+        let mut editor = headless("", (40, 10));
+        for code in [
+            KeyCode::Up,
+            KeyCode::Down,
+            KeyCode::PageDown,
+            KeyCode::PageUp,
+            KeyCode::Right,
+            KeyCode::Left,
+            KeyCode::End,
+            KeyCode::Home,
+        ] {
+            times(&mut editor, code, 1);
+            assert_eq!(editor.cursor, Cursor::default());
+        }
+    }
+
+    #[test]
+    fn wheel_leaves_the_cursor_where_it_is() {
+        let mut editor = headless(&lines(30), (40, 10));
+        times(&mut editor, KeyCode::Right, 2);
+
+        wheel(&mut editor, MouseEventKind::ScrollDown);
+        assert_eq!(scroll(&editor), 3);
+        assert_eq!(editor.grid.cursor(), None);
+        wheel(&mut editor, MouseEventKind::ScrollUp);
+        assert_eq!(scroll(&editor), 0);
+        assert_eq!(position(&editor), (0, 2));
+        assert_eq!(editor.grid.cursor(), Some((5, 0)));
+    }
+
+    #[test]
+    fn document_start_and_end() {
+        let mut editor = headless("  one\ntwo\nthree\n", (40, 10));
+        press(&mut editor, KeyCode::End, KeyModifiers::CONTROL);
+        assert_eq!(position(&editor), (2, 5));
+        press(&mut editor, KeyCode::Home, KeyModifiers::CONTROL);
+        assert_eq!(position(&editor), (0, 0));
+
+        let mut editor = headless("", (40, 10));
+        press(&mut editor, KeyCode::End, KeyModifiers::CONTROL);
+        assert_eq!(position(&editor), (0, 0));
+        press(&mut editor, KeyCode::Home, KeyModifiers::CONTROL);
+        assert_eq!(position(&editor), (0, 0));
+    }
+
+    #[test]
+    fn modal_typing_on_an_empty_buffer() {
+        let mut editor = headless("", (40, 10));
+        set_option("modal=true", &mut editor.editor_state).unwrap();
+        for key in ['a', 'i', 'A', 'o'] {
+            press(&mut editor, KeyCode::Char(key), KeyModifiers::NONE);
+            assert_eq!(editor.editor_state.modal.mode, Mode::Insert);
+            press(&mut editor, KeyCode::Esc, KeyModifiers::NONE);
+        }
+
+        press(&mut editor, KeyCode::Char('a'), KeyModifiers::NONE);
+        press(&mut editor, KeyCode::Char('x'), KeyModifiers::NONE);
+        assert_eq!(editor.doc.unwrap().buffer.line(1), Some("x"));
+    }
+
+    #[test]
+    fn focus_runs_its_hooks() {
+        let mut editor = headless("one  \ntwo\n", (40, 10));
+        editor.editor_state.hooks
+            .attach(HookEvent::Focus, hooks::trim_trailing_whitespace);
+
+        editor.handle(Wake::Input(Event::FocusLost)).unwrap();
+        assert_eq!(editor.doc.as_ref().unwrap().buffer.line(0), Some("one  "));
+        editor.handle(Wake::Input(Event::FocusGained)).unwrap();
+        frame(&mut editor);
+        assert_eq!(editor.doc.as_ref().unwrap().buffer.line(0), Some("one"));
+        assert_eq!(editor.grid.row(0).trim_end(), " 0 one");
+    }
+
+    #[test]
+    fn undo_of_encrypted_documents_is_not_spilled() {
+        for encrypted in [false, true] {
+            let mut editor = headless("one\n", (40, 10));
+            if encrypted {
+                editor.doc.as_mut().unwrap().encryption =
+                    Some(Encryption::with_passphrase("secret"));
+            }
+            editor.editor_state.undo.memory_limit = 0;
+            times(&mut editor, KeyCode::Enter, 3);
+            for _ in 0..3 {
+                press(&mut editor, KeyCode::Char('z'), KeyModifiers::CONTROL);
+            }
+
+            // The oldest changes of the encrypted one were forgotten
+            let lines = editor.doc.as_ref().unwrap().buffer.len();
+            assert_eq!(lines, if encrypted { 3 } else { 1 });
+        }
+    }
+
+    #[test]
+    fn wheel_on_an_empty_buffer() {
+        let mut editor = headless("", (40, 10));
+        wheel(&mut editor, MouseEventKind::ScrollDown);
+        wheel(&mut editor, MouseEventKind::ScrollUp);
+        assert_eq!(position(&editor), (0, 0));
+
+        press(&mut editor, KeyCode::Char('n'), KeyModifiers::CONTROL);
+        wheel(&mut editor, MouseEventKind::ScrollDown);
+        wheel(&mut editor, MouseEventKind::ScrollUp);
+        assert_eq!(position(&editor), (0, 0));
+    }
+
+    #[test]
+    fn wheel_step_is_an_option() {
+        let mut editor = headless(&lines(30), (40, 10));
+        set_option("wheelstep=5", &mut editor.editor_state).unwrap();
+        wheel(&mut editor, MouseEventKind::ScrollDown);
+        assert_eq!(scroll(&editor), 5);
+        assert!(set_option("wheelstep=0", &mut editor.editor_state).is_err());
+    }
+
+    #[test]
+    fn moving_brings_the_parked_cursor_into_the_view() {
+        let mut editor = headless(&lines(30), (40, 10));
+        wheel(&mut editor, MouseEventKind::ScrollDown);
+        wheel(&mut editor, MouseEventKind::ScrollDown);
+        times(&mut editor, KeyCode::Down, 1);
+        assert_eq!(scroll(&editor), 6);
+        assert_eq!(position(&editor), (7, 0));
+        assert_eq!(editor.grid.cursor(), Some((3, 1)));
+    }
+
+    #[test]
+    fn half_pages_move_the_view_and_the_cursor() {
+        let mut editor = headless(&lines(30), (40, 10));
+        set_option("halfpagekeys=true", &mut editor.editor_state).unwrap();
+        times(&mut editor, KeyCode::Down, 2);
+
+        press(&mut editor, KeyCode::Char('d'), KeyModifiers::CONTROL);
+        assert_eq!(scroll(&editor), 4);
+        assert_eq!(position(&editor), (6, 0));
+        press(&mut editor, KeyCode::Char('u'), KeyModifiers::CONTROL);
+        press(&mut editor, KeyCode::Char('u'), KeyModifiers::CONTROL);
+        assert_eq!(scroll(&editor), 0);
+        assert_eq!(position(&editor), (0, 0));
+    }
+
+    #[test]
+    fn rulers_go_down_the_document_area() {
+        let mut editor = headless("short\n", (40, 10));
+        set_option("rulers=10,20", &mut editor.editor_state).unwrap();
+        editor.render_state.modif_all = true;
+        frame(&mut editor);
+
+        // After the gutter, right after the columns of each ruler
+        let ruler = Some(editor.editor_state.theme.ruler);
+        for row in [0, 5] {
+            assert_eq!(editor.grid.background(13, row), ruler);
+            assert_eq!(editor.grid.background(23, row), ruler);
+            assert_eq!(editor.grid.background(12, row), None);
+        }
+        assert!(set_option("rulers=ten", &mut editor.editor_state).is_err());
+    }
+
+    #[test]
+    fn chars_past_the_maximum_length_stand_out() {
+        let mut editor = headless("0123456789\n", (40, 10));
+        set_option("maxlinelength=6", &mut editor.editor_state).unwrap();
+        editor.render_state.modif_all = true;
+        frame(&mut editor);
+
+        let overflow = Some(editor.editor_state.theme.overflow);
+        assert_eq!(editor.grid.background(8, 0), None);
+        assert_eq!(editor.grid.background(9, 0), overflow);
+        assert_eq!(editor.grid.background(12, 0), overflow);
+        assert_eq!(editor.grid.background(13, 0), None);
+    }
+
+    #[test]
+    fn the_path_under_the_cursor_opens_at_its_line() {
+        let dir = TempDir::new("links");
+        let file = dir.join("target.txt");
+        std::fs::write(&file, lines(10)).unwrap();
+
+        let mut editor = headless(
+            &format!("see ({}:4:3).\nnothing\n", file.display()), (40, 10));
+        times(&mut editor, KeyCode::Right, 8);
+        press(&mut editor, KeyCode::Enter, KeyModifiers::CONTROL);
+        assert_eq!(editor.doc.as_ref().unwrap().path, file);
+        assert_eq!(position(&editor), (3, 2));
+
+        // A word that is no file leaves the document as it is
+        press(&mut editor, KeyCode::Char('^'), KeyModifiers::CONTROL);
+        times(&mut editor, KeyCode::Down, 1);
+        press(&mut editor, KeyCode::Enter, KeyModifiers::CONTROL);
+        assert_eq!(editor.doc.as_ref().unwrap().path, PathBuf::from("test.txt"));
+    }
+
+    #[test]
+    fn pair_behaviours_are_configured() {
+        let dir = TempDir::new("pairs");
+        let path = dir.join("config.toml");
+        std::fs::write(&path, "[pairs]\nskipclose = false\n\
+            text.deletepair = false\nrust.skipclose = true\n").unwrap();
+        let mut editor = headless("\n", (40, 10));
+        let errors = config::load(&mut editor.editor_state, Some(&path));
+        assert_eq!(errors, None);
+        assert_eq!(editor.doc.as_ref().unwrap().filetype(), Some("text"));
+
+        // The closing symbol is typed again and stays when the opening one
+        // is deleted
+        press(&mut editor, KeyCode::Char('('), KeyModifiers::NONE);
+        press(&mut editor, KeyCode::Char(')'), KeyModifiers::NONE);
+        times(&mut editor, KeyCode::Left, 1);
+        times(&mut editor, KeyCode::Backspace, 1);
+        assert_eq!(editor.doc.as_ref().unwrap().buffer.line(0), Some("))"));
+
+        let pairs = &editor.editor_state.pairs;
+        assert!(pairs.skip_close.applies(Some("rust")));
+        assert!(pairs.delete_pair.applies(Some("rust")));
+        assert!(set_option("pairs.rust.wrap=on", &mut editor.editor_state)
+            .is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn saving_through_a_link_keeps_the_link() {
+        let dir = TempDir::new("link");
+        std::fs::create_dir_all(dir.join("real")).unwrap();
+        let file = dir.join("real").join("file.txt");
+        let link = dir.join("link.txt");
+        std::fs::write(&file, "old\n").unwrap();
+        std::os::unix::fs::symlink(&file, &link).unwrap();
+
+        let mut doc = Document::from_bytes(&link, b"old\n");
+        doc.save("new\n").unwrap();
+        let meta = std::fs::symlink_metadata(&link).unwrap();
+        assert!(meta.file_type().is_symlink());
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "new\n");
+
+        // No temporary file is left behind
+        let names = |dir: &Path| std::fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect::<Vec<_>>();
+        assert_eq!(names(&dir).len(), 2);
+        assert_eq!(names(&dir.join("real")), ["file.txt"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn trashing_a_link_leaves_its_file() {
+        let dir = TempDir::new("trash");
+        std::env::set_var("XDG_DATA_HOME", dir.join("data"));
+        let file = dir.join("file.txt");
+        let link = dir.join("link.txt");
+        std::fs::write(&file, "text\n").unwrap();
+        std::os::unix::fs::symlink(&file, &link).unwrap();
+
+        let trashed = fileops::trash(&link).unwrap();
+        assert!(std::fs::symlink_metadata(&link).is_err());
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "text\n");
+        trashed.restore().unwrap();
+        let meta = std::fs::symlink_metadata(&link).unwrap();
+        assert!(meta.file_type().is_symlink());
+    }
+
+    #[test]
+    fn edits_follow_the_lines_inserted_above() {
+        let mut editor = headless("one\ntwo\nthree\n", (40, 10));
+        times(&mut editor, KeyCode::Down, 2);
+        press(&mut editor, KeyCode::Char('x'), KeyModifiers::NONE);
+        times(&mut editor, KeyCode::Up, 2);
+        times(&mut editor, KeyCode::Enter, 1);
+
+        // Back past the newline to the edit, a line down now
+        press(&mut editor, KeyCode::Char(';'), KeyModifiers::ALT);
+        assert_eq!(position(&editor).0, 1);
+        press(&mut editor, KeyCode::Char(';'), KeyModifiers::ALT);
+        assert_eq!(position(&editor), (3, 1));
+    }
+
+    #[test]
+    fn bookmarks_follow_the_lines_inserted_above() {
+        let dir = TempDir::new("bookmarks");
+        let mut editor = headless("one\ntwo\nthree\n", (40, 10));
+        editor.editor_state.bookmarks = Bookmarks::load(&*dir);
+        let path = PathBuf::from("test.txt");
+        editor.editor_state.bookmarks.add(&path, 2);
+        times(&mut editor, KeyCode::Enter, 1);
+
+        assert_eq!(editor.editor_state.bookmarks.list[0].line, 3);
+        assert_eq!(Bookmarks::load(&*dir).list[0].line, 3);
+    }
+
+    #[test]
+    fn large_files_with_invalid_lines_are_not_saved() {
+        let dir = TempDir::new("invalid");
+        let path = dir.join("large.txt");
+        let mut bytes = "line\n".repeat(3000).into_bytes();
+        bytes.extend(b"\xff\n");
+        std::fs::write(&path, &bytes).unwrap();
+
+        let mut editor = headless("", (40, 10));
+        editor.editor_state.large_file = Some(1);
+        let doc = editor.editor_state.open_document(&path, None).unwrap();
+        assert!(!doc.binary);
+        editor.doc = Some(doc);
+        press(&mut editor, KeyCode::Char('x'), KeyModifiers::NONE);
+        press(&mut editor, KeyCode::Char('s'), KeyModifiers::CONTROL);
+
+        let doc = editor.doc.as_ref().unwrap();
+        assert!(doc.binary && doc.read_only);
+        assert_eq!(std::fs::read(&path).unwrap(), bytes);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn commands_that_leave_their_input_are_reported() {
+        let input = vec![b'x'; 1 << 20];
+        let err = shell::pipe_program("true", &[], &input).unwrap_err();
+        assert!(err.to_string().contains("didn't read its input"));
+    }
+
+    #[test]
+    fn tiny_terminals_have_no_scrollbar_thumb() {
+        assert_eq!(scrollbar::thumb(50, 0, 10), (0, 0));
+        assert_eq!(scrollbar::thumb(50, 1, 49), (0, 1));
+        for size in [(1, 1), (2, 2), (40, 1)] {
+            let mut editor = headless(&lines(50), size);
+            times(&mut editor, KeyCode::PageDown, 2);
+            frame(&mut editor);
+        }
+    }
+
+    #[test]
+    fn print_options_choose_the_numbers_and_the_colors() {
+        let mut editor = headless("let x = 1;\n", (40, 10));
+        let red = |_, _: &str| vec![(0, 3, ContentStyle {
+            foreground_color: Some(Color::Red),
+            ..ContentStyle::default()
+        })];
+        let doc = editor.doc.as_ref().unwrap();
+        let options = editor.editor_state.print;
+        let ps = print::format_postscript(doc, options, 4, red);
+        assert!(ps.contains("0.5 setgray (1 ) show 0 setgray (let x = 1;)"));
+
+        set_option("printnumbers=false", &mut editor.editor_state).unwrap();
+        set_option("printhighlight=true", &mut editor.editor_state).unwrap();
+        let doc = editor.doc.as_ref().unwrap();
+        let options = editor.editor_state.print;
+        let ps = print::format_postscript(doc, options, 4, red);
+        assert!(!ps.contains("0.5 setgray"));
+        assert!(ps.contains("1.000 0.000 0.000 setrgbcolor (let) show \
+            0 setgray ( x = 1;) show"));
+    }
+
+    #[test]
+    fn review_ghosts_belong_to_their_document() {
+        let path = PathBuf::from("test.txt");
+        let mut editor = headless("one\ntwo\nthree\n", (40, 10));
+        press(&mut editor, KeyCode::Char('r'), KeyModifiers::ALT);
+        times(&mut editor, KeyCode::Down, 1);
+        press(&mut editor, KeyCode::Char('k'), KeyModifiers::CONTROL);
+        assert!(editor.editor_state.review.is_ghost(&path, 1));
+
+        // Accepting on another buffer leaves the ghost where it was
+        press(&mut editor, KeyCode::Char('n'), KeyModifiers::CONTROL);
+        press(&mut editor, KeyCode::Char('Y'), KeyModifiers::ALT);
+        press(&mut editor, KeyCode::Tab, KeyModifiers::CONTROL);
+        assert_eq!(editor.doc.as_ref().unwrap().path, path);
+        assert_eq!(editor.doc.as_ref().unwrap().buffer.len(), 3);
+
+        // Any edit moves it with its line
+        editor.doc.as_mut().unwrap()
+            .replace_lines(0, 1, vec!["a".to_owned(), "b".to_owned()]);
+        frame(&mut editor);
+        let review = &editor.editor_state.review;
+        assert!(!review.is_ghost(&path, 1));
+        assert!(review.is_ghost(&path, 2));
+
+        press(&mut editor, KeyCode::Char('Y'), KeyModifiers::ALT);
+        let doc = editor.doc.as_ref().unwrap();
+        assert_eq!(doc.buffer.slice(0..doc.buffer.len()), ["a", "b", "three"]);
+    }
+
+    #[test]
+    fn tabs_share_the_document_of_a_file() {
+        let mut editor = headless("hello\n", (40, 10));
+        press(&mut editor, KeyCode::Char('t'), KeyModifiers::ALT);
+        press(&mut editor, KeyCode::Char('x'), KeyModifiers::NONE);
+        press(&mut editor, KeyCode::Char('1'), KeyModifiers::ALT);
+        let doc = editor.doc.as_ref().unwrap();
+        assert_eq!(doc.buffer.line(0), Some("xhello"));
+        assert_eq!(editor.unsaved().len(), 1);
+
+        // Closing the tab that has it gives it to the one left
+        press(&mut editor, KeyCode::Char('w'), KeyModifiers::ALT);
+        assert_eq!(editor.editor_state.tabs.list.len(), 1);
+        let doc = editor.doc.as_ref().unwrap();
+        assert_eq!(doc.kind, DocKind::File);
+        assert_eq!(doc.buffer.line(0), Some("xhello"));
+    }
+
+    #[test]
+    fn completion_leaves_read_only_documents_alone() {
+        let mut editor = headless("hello\nhe\n", (40, 10));
+        times(&mut editor, KeyCode::Down, 1);
+        times(&mut editor, KeyCode::End, 1);
+        editor.doc.as_mut().unwrap().read_only = true;
+        press(&mut editor, KeyCode::Char(' '), KeyModifiers::CONTROL);
+        assert!(editor.editor_state.completion.is_none());
+
+        // Nor the completion shown before it became read-only
+        editor.doc.as_mut().unwrap().read_only = false;
+        press(&mut editor, KeyCode::Char(' '), KeyModifiers::CONTROL);
+        assert!(editor.editor_state.completion.is_some());
+        editor.doc.as_mut().unwrap().read_only = true;
+        times(&mut editor, KeyCode::Tab, 1);
+        let doc = editor.doc.as_ref().unwrap();
+        assert_eq!(doc.buffer.line(1), Some("he"));
+        assert!(!doc.dirty);
+    }
+
+    #[test]
+    fn scratch_buffers_ask_where_to_be_saved() {
+        let mut editor = headless("text\n", (40, 10));
+        press(&mut editor, KeyCode::Char('n'), KeyModifiers::CONTROL);
+        press(&mut editor, KeyCode::Char('a'), KeyModifiers::NONE);
+        let doc = editor.doc.as_ref().unwrap();
+        assert_eq!(doc.kind, DocKind::Scratch);
+        assert_eq!(doc.path, PathBuf::from("[scratch 1]"));
+        assert!(doc.dirty);
+
+        press(&mut editor, KeyCode::Char('s'), KeyModifiers::CONTROL);
+        let prompt = editor.editor_state.prompt.as_ref().unwrap();
+        assert_eq!(prompt.input, "");
+    }
+
+    #[test]
+    fn panels_open_on_preview_buffers() {
+        let mut editor = headless("text\n", (40, 10));
+        press(&mut editor, KeyCode::F(1), KeyModifiers::NONE);
+        press(&mut editor, KeyCode::Char('b'), KeyModifiers::NONE);
+        assert!(editor.editor_state.panel.is_none());
+        let doc = editor.doc.as_ref().unwrap();
+        assert_eq!(doc.kind, DocKind::Preview);
+        assert_eq!(doc.path, PathBuf::from("[Keys]"));
+
+        // Opened again it's the same buffer
+        let buffers = editor.editor_state.buffers.list.len();
+        press(&mut editor, KeyCode::F(1), KeyModifiers::NONE);
+        press(&mut editor, KeyCode::Char('b'), KeyModifiers::NONE);
+        assert_eq!(editor.editor_state.buffers.list.len(), buffers);
+
+        let lines = editor.doc.as_ref().unwrap().buffer.len();
+        press(&mut editor, KeyCode::Char('x'), KeyModifiers::NONE);
+        assert_eq!(editor.doc.as_ref().unwrap().buffer.len(), lines);
+        assert!(!editor.doc.as_ref().unwrap().dirty);
+    }
+
+    #[test]
+    fn a_char_typed_writes_what_moved() {
+        let mut editor = headless("hello\nworld\n", (40, 6));
+        let key = Event::Key(
+            KeyEvent::new(KeyCode::Char('x'), KeyModifiers::NONE));
+        editor.handle(Wake::Input(key)).unwrap();
+
+        // The mark of the changed line, the chars that moved and the cells
+        // of the status bar that changed, the same chars are skipped
+        let ops = frame(&mut editor);
+        assert_eq!(ops[..5], [
+            "hide", "move 2 0", "print  xhe", "move 7 0", "print lo"]);
+        assert!(ops[5..ops.len() - 3].iter().all(|op|
+            op.starts_with("print") || op.ends_with(" 4")));
+        assert_eq!(ops[ops.len() - 3..], ["move 4 0", "show", "flush"]);
+    }
+
+    #[test]
+    fn unsaved_changes_are_recovered() {
+        let dir = TempDir::new("recovery");
+        let mut editor = headless("text\n", (40, 10));
+        editor.doc.as_mut().unwrap().path = dir.join("notes.txt");
+        press(&mut editor, KeyCode::Char('a'), KeyModifiers::NONE);
+
+        // The document moves to the buffers behind the scratch one
+        press(&mut editor, KeyCode::Char('n'), KeyModifiers::CONTROL);
+        assert_eq!(editor.unsaved().len(), 1);
+        press(&mut editor, KeyCode::Char('b'), KeyModifiers::NONE);
+
+        let files: Vec<_> = editor.unsaved()
+            .into_iter()
+            .map(|doc| recovery::write(doc).unwrap())
+            .collect();
+        assert_eq!(std::fs::read_to_string(&files[0]).unwrap(), "b\n");
+        assert_eq!(std::fs::read_to_string(&files[1]).unwrap(), "atext\n");
+        assert_eq!(files[1], dir.join("notes.txt.pepe-recovered"));
+
+        // A second recovery doesn't overwrite the first one
+        let doc = editor.unsaved().into_iter().nth(1).unwrap();
+        let again = recovery::write(doc).unwrap();
+        assert_ne!(again, files[1]);
+        assert_eq!(std::fs::read_to_string(&again).unwrap(), "atext\n");
+
+        std::fs::remove_file(&files[0]).unwrap();
+    }
+
+    /// A directory of its own for a test, removed with everything on it
+    /// when the test ends, also when it fails
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("pepe-{}-{}-{}",
+                name, std::process::id(), fileops::random_suffix()));
+            std::fs::create_dir_all(&dir).unwrap();
+
+            Self(dir)
+        }
+    }
+
+    impl std::ops::Deref for TempDir {
+        type Target = Path;
+
+        fn deref(&self) -> &Path {
+            &self.0
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// The row of the frame with the text, if any
+    fn row_with(editor: &Editor, text: &str) -> Option<String> {
+        let rows = editor.editor_state.terminal.1;
+        (0..rows).map(|y| editor.grid.row(y)).find(|row| row.contains(text))
+    }
+
+    #[test]
+    fn counts_follow_the_edits() {
+        let mut editor = headless("one two\nthree\n", (100, 10));
+        let count = editor.doc.as_ref().unwrap().count();
+        assert_eq!((count.lines, count.words, count.chars), (2, 3, 14));
+
+        // The lines edited are counted again
+        press(&mut editor, KeyCode::Char('x'), KeyModifiers::NONE);
+        times(&mut editor, KeyCode::Right, 2);
+        press(&mut editor, KeyCode::Enter, KeyModifiers::NONE);
+        let count = editor.doc.as_ref().unwrap().count();
+        assert_eq!((count.lines, count.words, count.chars), (3, 4, 16));
+
+        // The words of the selection, the cut ones too
+        press(&mut editor, KeyCode::Right, KeyModifiers::SHIFT);
+        press(&mut editor, KeyCode::Down, KeyModifiers::SHIFT);
+        assert!(row_with(&editor, "[2 lines, 3 words, 7 chars selected]")
+            .is_some());
+    }
+
+    #[test]
+    fn limits_are_told_on_the_status_bar() {
+        let mut editor = headless("one two three\n", (60, 10));
+        editor.doc.as_mut().unwrap().limits.words = Some(2);
+        editor.render_state.modif_status = true;
+        frame(&mut editor);
+        assert!(row_with(&editor, "[words 3/2, 1 over]").is_some());
+
+        press(&mut editor, KeyCode::Delete, KeyModifiers::CONTROL);
+        editor.doc.as_mut().unwrap().limits.words = Some(5);
+        editor.render_state.modif_status = true;
+        frame(&mut editor);
+        assert!(row_with(&editor, "[words 3/5]").is_some());
+    }
+}
@@ -0,0 +1,114 @@
+//! Positions of the recent edits, separated from the jumps so going back to
+//! where the document was being changed is always possible after browsing
+//! elsewhere
+
+use std::path::{Path, PathBuf};
+
+use crate::text::LineShift;
+
+/// Edits remembered, the oldest are forgotten first
+const MAX_EDITS: usize = 100;
+
+/// Where an edit happened
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EditPosition {
+    pub path: PathBuf,
+    pub line: usize,
+    pub column: usize,
+}
+
+/// The recent edits, the newest last
+#[derive(Debug, Default)]
+pub struct EditList {
+    list: Vec<EditPosition>,
+
+    /// The edit visited while cycling, `None` when not cycling
+    current: Option<usize>,
+
+    /// How many edits were recorded, the forgotten ones too
+    recorded: usize,
+
+    /// The newest edits recorded since the last shift, they already are
+    /// where the lines are after it
+    fresh: usize,
+}
+
+impl EditList {
+    /// Remember an edit, consecutive edits on the same line are the same
+    /// position. Recording stops the cycling
+    pub fn record(&mut self, path: &Path, line: usize, column: usize) {
+        let position = EditPosition {
+            path: path.to_owned(),
+            line,
+            column,
+        };
+        self.current = None;
+        self.recorded += 1;
+
+        match self.list.last_mut() {
+            Some(last) if last.path == position.path && last.line == line => {
+                *last = position;
+                self.fresh = self.fresh.max(1);
+            }
+            _ => {
+                self.list.push(position);
+                self.fresh += 1;
+                if self.list.len() > MAX_EDITS {
+                    self.list.remove(0);
+                    self.fresh = self.fresh.min(MAX_EDITS);
+                }
+            }
+        }
+    }
+
+    /// The edits of the document follow the lines inserted and removed on
+    /// it, but the ones recorded since the last shift are already in place
+    pub fn shift(&mut self, path: &Path, shifts: &[LineShift]) {
+        let old = self.list.len() - self.fresh;
+        self.fresh = 0;
+        for edit in &mut self.list[..old] {
+            if edit.path == path {
+                for shift in shifts {
+                    edit.line = shift.apply(edit.line);
+                }
+            }
+        }
+    }
+
+    /// Go to the previous edit of the document, the first call goes to the
+    /// newest one
+    pub fn older(&mut self, path: &Path) -> Option<&EditPosition> {
+        let start = self.current.unwrap_or(self.list.len());
+        let idx = (0..start).rev().find(|&idx| self.list[idx].path == path)?;
+        self.current = Some(idx);
+
+        Some(&self.list[idx])
+    }
+
+    /// Go back to the next edit of the document while cycling
+    pub fn newer(&mut self, path: &Path) -> Option<&EditPosition> {
+        let start = self.current? + 1;
+        let idx = (start..self.list.len())
+            .find(|&idx| self.list[idx].path == path)?;
+        self.current = Some(idx);
+
+        Some(&self.list[idx])
+    }
+
+    /// The file was renamed, its edits go with it
+    pub fn rename_file(&mut self, old_path: &Path, new_path: &Path) {
+        for edit in self.list.iter_mut().filter(|edit| edit.path == old_path) {
+            edit.path = new_path.to_owned();
+        }
+    }
+
+    /// How many edits were recorded
+    pub fn recorded(&self) -> usize {
+        self.recorded
+    }
+
+    /// The newest edit
+    pub fn last(&self) -> Option<&EditPosition> {
+        self.list.last()
+    }
+}
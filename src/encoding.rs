@@ -0,0 +1,169 @@
+//! The encodings of the files. The lines are always UTF-8, a file in
+//! another encoding is converted when read and converted back when saved,
+//! so its bytes don't change unless edited. The encoding is told by the BOM
+//! or guessed from the bytes: UTF-8 when they are valid, UTF-16 when every
+//! other byte is NUL and Latin-1 otherwise. The `encoding` command forces
+//! another one
+
+use std::borrow::Cow;
+use std::fmt;
+
+use crate::Result;
+
+const UTF8_BOM: &[u8] = b"\xef\xbb\xbf";
+const UTF16LE_BOM: &[u8] = b"\xff\xfe";
+const UTF16BE_BOM: &[u8] = b"\xfe\xff";
+
+/// Bytes looked at to guess UTF-16 without BOM
+const SNIFF_LEN: usize = 8000;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    #[default]
+    Utf8,
+
+    /// UTF-8 that starts with a BOM, it's written back
+    Utf8Bom,
+    Utf16Le,
+    Utf16Be,
+
+    /// ISO-8859-1, every byte is the char of its value
+    Latin1,
+}
+
+impl Encoding {
+    pub const ALL: [Self; 5] =
+        [Self::Utf8, Self::Utf8Bom, Self::Utf16Le, Self::Utf16Be, Self::Latin1];
+
+    /// Guess the encoding of the contents of a file. A valid UTF-8 prefix
+    /// of a longer text, cut in the middle of a char, is UTF-8 too
+    pub fn detect(bytes: &[u8]) -> Self {
+        if bytes.starts_with(UTF8_BOM) {
+            return Self::Utf8Bom;
+        }
+        if bytes.starts_with(UTF16LE_BOM) {
+            return Self::Utf16Le;
+        }
+        if bytes.starts_with(UTF16BE_BOM) {
+            return Self::Utf16Be;
+        }
+        if let Some(utf16) = guess_utf16(bytes) {
+            return utf16;
+        }
+
+        match std::str::from_utf8(bytes) {
+            Ok(_) => Self::Utf8,
+            Err(err) if err.error_len().is_none() => Self::Utf8,
+            Err(_) => Self::Latin1,
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().replace('_', "-").as_str() {
+            "utf-8" | "utf8" => Some(Self::Utf8),
+            "utf-8-bom" | "utf8-bom" => Some(Self::Utf8Bom),
+            "utf-16le" | "utf16le" | "utf-16" | "utf16" => Some(Self::Utf16Le),
+            "utf-16be" | "utf16be" => Some(Self::Utf16Be),
+            "latin-1" | "latin1" | "iso-8859-1" => Some(Self::Latin1),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Utf8 => "utf-8",
+            Self::Utf8Bom => "utf-8-bom",
+            Self::Utf16Le => "utf-16le",
+            Self::Utf16Be => "utf-16be",
+            Self::Latin1 => "latin-1",
+        }
+    }
+
+    /// The contents of a file as UTF-8, without the BOM. The bytes that
+    /// aren't valid are left for the lines to replace them
+    pub fn decode(self, bytes: &[u8]) -> Cow<'_, [u8]> {
+        match self {
+            Self::Utf8 => Cow::Borrowed(bytes),
+            Self::Utf8Bom =>
+                Cow::Borrowed(bytes.strip_prefix(UTF8_BOM).unwrap_or(bytes)),
+            Self::Utf16Le | Self::Utf16Be => {
+                let bytes = bytes
+                    .strip_prefix(self.bom())
+                    .unwrap_or(bytes);
+                let units: Vec<u16> = bytes
+                    .chunks_exact(2)
+                    .map(|pair| match self {
+                        Self::Utf16Le => u16::from_le_bytes([pair[0], pair[1]]),
+                        _ => u16::from_be_bytes([pair[0], pair[1]]),
+                    })
+                    .collect();
+                Cow::Owned(String::from_utf16_lossy(&units).into_bytes())
+            }
+            Self::Latin1 => Cow::Owned(bytes
+                .iter()
+                .map(|&byte| byte as char)
+                .collect::<String>()
+                .into_bytes()),
+        }
+    }
+
+    /// The text as it's written on the file, with the BOM. Fails when a
+    /// char can't be written in the encoding
+    pub fn encode(self, text: &str) -> Result<Vec<u8>> {
+        let mut bytes = self.bom().to_vec();
+        match self {
+            Self::Utf8 | Self::Utf8Bom => bytes.extend_from_slice(text.as_bytes()),
+            Self::Utf16Le => bytes.extend(
+                text.encode_utf16().flat_map(u16::to_le_bytes)),
+            Self::Utf16Be => bytes.extend(
+                text.encode_utf16().flat_map(u16::to_be_bytes)),
+            Self::Latin1 => for c in text.chars() {
+                let byte = u8::try_from(c).map_err(|_| format!(
+                    "{:?} can't be written in latin-1", c))?;
+                bytes.push(byte);
+            }
+        }
+
+        Ok(bytes)
+    }
+
+    fn bom(self) -> &'static [u8] {
+        match self {
+            Self::Utf8Bom => UTF8_BOM,
+            Self::Utf16Le => UTF16LE_BOM,
+            Self::Utf16Be => UTF16BE_BOM,
+            Self::Utf8 | Self::Latin1 => b"",
+        }
+    }
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// UTF-16 without BOM, most of the text is ASCII so the high byte of most
+/// of the units is NUL and the low one never is
+fn guess_utf16(bytes: &[u8]) -> Option<Encoding> {
+    let bytes = &bytes[..usize::min(bytes.len(), SNIFF_LEN) & !1];
+    if bytes.is_empty() {
+        return None;
+    }
+    let units = bytes.len() / 2;
+    let nuls = |parity: usize| bytes
+        .iter()
+        .skip(parity)
+        .step_by(2)
+        .filter(|&&byte| byte == 0)
+        .count();
+    let (even, odd) = (nuls(0), nuls(1));
+
+    if odd * 2 > units && even == 0 {
+        Some(Encoding::Utf16Le)
+    } else if even * 2 > units && odd == 0 {
+        Some(Encoding::Utf16Be)
+    } else {
+        None
+    }
+}
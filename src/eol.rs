@@ -0,0 +1,65 @@
+//! The line endings of the files. The lines are kept without them, the
+//! ending most lines of the file had is remembered and every line is saved
+//! with it. A file with both endings is told when opened and the `eol`
+//! command converts between them
+
+use std::fmt;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    Crlf,
+}
+
+impl LineEnding {
+    /// The ending of most lines of the text, `\n` when they are as many,
+    /// and if some lines end with the other one
+    pub fn detect(bytes: &[u8]) -> (Self, bool) {
+        let (crlf, lf) = count(bytes);
+        let ending = if crlf > lf { Self::Crlf } else { Self::Lf };
+
+        (ending, crlf > 0 && lf > 0)
+    }
+
+    /// The lines of the text that end with the other ending
+    pub fn others(self, bytes: &[u8]) -> usize {
+        let (crlf, lf) = count(bytes);
+        match self {
+            Self::Lf => crlf,
+            Self::Crlf => lf,
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "lf" | "unix" => Some(Self::Lf),
+            "crlf" | "dos" => Some(Self::Crlf),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Lf => "\n",
+            Self::Crlf => "\r\n",
+        }
+    }
+}
+
+impl fmt::Display for LineEnding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Lf => write!(f, "LF"),
+            Self::Crlf => write!(f, "CRLF"),
+        }
+    }
+}
+
+/// The lines ending with `\r\n` and the ones ending with a lone `\n`
+fn count(bytes: &[u8]) -> (usize, usize) {
+    let newlines = bytes.iter().filter(|&&byte| byte == b'\n').count();
+    let crlf = bytes.windows(2).filter(|pair| pair == b"\r\n").count();
+
+    (crlf, newlines - crlf)
+}
@@ -0,0 +1,108 @@
+//! The event loop of the editor. The sources of work run on their own
+//! threads and send it through a channel: the keys and the mouse are read on
+//! one, the files are polled on another, the remote files arrive on another,
+//! the programs streamed tell when they printed something, git answers
+//! about the repository of the documents and the language servers send
+//! their messages. The editor sleeps until some work
+//! arrives or one of its timers is due, like the expiry of a message or the
+//! hover delay, so it doesn't wake while idle
+
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::Instant;
+
+use crossterm::event::{self, Event};
+
+use crate::cancel::CancelToken;
+
+/// Why the editor woke up
+#[derive(Debug)]
+pub enum Wake {
+    /// A key, the mouse or a resize of the terminal
+    Input(Event),
+
+    /// Files changed on disk by other programs
+    FilesChanged(Vec<PathBuf>),
+
+    /// Files sent by `pepe --remote`
+    Remote(Vec<PathBuf>),
+
+    /// The program streamed printed more lines
+    Output,
+
+    /// Git answered about the repository of a document
+    Git,
+
+    /// A language server sent a message
+    Lsp,
+
+    /// A timer is due
+    Timer,
+}
+
+/// The channel the threads send their work through
+pub struct EventLoop {
+    sender: Sender<Wake>,
+    receiver: Receiver<Wake>,
+
+    /// The input thread cancels with it the operation running
+    cancel: CancelToken,
+}
+
+impl Default for EventLoop {
+    fn default() -> Self {
+        let (sender, receiver) = mpsc::channel();
+
+        Self { sender, receiver, cancel: CancelToken::default() }
+    }
+}
+
+impl EventLoop {
+    /// Start reading the input of the terminal on its own thread, the keys
+    /// that cancel the operation running are not sent
+    pub fn read_input(&self) {
+        let input = self.sender();
+        let cancel = self.cancel_token();
+        thread::spawn(move || {
+            while let Ok(event) = event::read() {
+                if cancel.interrupts(&event) {
+                    continue;
+                }
+                if input.send(Wake::Input(event)).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+
+    /// The token of the operations the keys can cancel
+    pub fn cancel_token(&self) -> CancelToken {
+        self.cancel.clone()
+    }
+
+    /// Where another thread sends its work
+    pub fn sender(&self) -> Sender<Wake> {
+        self.sender.clone()
+    }
+
+    /// Sleep until some work arrives or the `deadline` passes, forever
+    /// without one
+    pub fn wait(&self, deadline: Option<Instant>) -> Wake {
+        let received = match deadline {
+            Some(deadline) => self.receiver.recv_timeout(
+                deadline.saturating_duration_since(Instant::now())),
+            None => self.receiver.recv().map_err(|_|
+                RecvTimeoutError::Disconnected),
+        };
+
+        received.unwrap_or(Wake::Timer)
+    }
+}
+
+/// The earliest of the deadlines, `None` when there is none
+pub fn earliest(
+    deadlines: impl IntoIterator<Item = Option<Instant>>
+) -> Option<Instant> {
+    deadlines.into_iter().flatten().min()
+}
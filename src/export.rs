@@ -0,0 +1,53 @@
+//! Sending the selection, or the whole document when nothing is selected,
+//! to an external tool like `delta`, `bat` or a script that uploads it to a
+//! pastebin. The tools are named on the `[export]` table of the config and
+//! get the text on their stdin. What they print is shown without its
+//! escapes, a single line like the URL of a paste on the message area and
+//! longer outputs on a panel
+
+use crate::Result;
+use crate::cancel::CancelToken;
+use crate::preview::strip_escapes;
+use crate::shell::pipe_shell;
+
+/// The tools of the config, in the order they were set
+#[derive(Debug, Default)]
+pub struct ExportTools {
+    tools: Vec<(String, String)>,
+}
+
+impl ExportTools {
+    pub fn set(&mut self, name: &str, command: &str) {
+        self.tools.retain(|(tool, _)| tool != name);
+        self.tools.push((name.to_owned(), command.to_owned()));
+    }
+
+    /// The command of the tool named, or the only one when there's a single
+    /// tool and no name is given
+    pub fn command(&self, name: &str) -> Option<&str> {
+        match (name, self.tools.as_slice()) {
+            ("", [(_, command)]) => Some(command),
+            _ => self.tools
+                .iter()
+                .find(|(tool, _)| tool == name)
+                .map(|(_, command)| command.as_str()),
+        }
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.tools.iter().map(|(tool, _)| tool.as_str()).collect()
+    }
+}
+
+/// Run the command with the text on its stdin, returns the lines it printed
+/// without the trailing empty ones
+pub fn export(command: &str, text: &str, cancel: &CancelToken)
+        -> Result<Vec<String>> {
+    let output = pipe_shell(command, text.as_bytes(), cancel)?;
+    let mut lines: Vec<String> = output.lines().map(strip_escapes).collect();
+    while lines.last().is_some_and(|line| line.trim().is_empty()) {
+        lines.pop();
+    }
+
+    Ok(lines)
+}
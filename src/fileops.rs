@@ -0,0 +1,255 @@
+//! Operations over the files on disk, every change of the editor to the
+//! filesystem (apart from writing documents) goes through here
+
+use std::collections::hash_map::RandomState;
+use std::fs::{File, OpenOptions};
+use std::hash::{BuildHasher, Hasher};
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+
+use crate::Result;
+
+/// Random hexadecimal digits for the names of the files that can't be
+/// guessed, the keys of the hashers are random
+pub fn random_suffix() -> String {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u32(std::process::id());
+    format!("{:016x}", hasher.finish())
+}
+
+/// Create a file only the user can read and write on the first of the
+/// paths that is free. The file is always a new one, a link or a file left
+/// on the path by someone else is never opened
+pub fn create_private(
+    paths: impl IntoIterator<Item = PathBuf>
+) -> io::Result<(PathBuf, File)> {
+    let mut options = OpenOptions::new();
+    options.read(true).write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+    let mut last = None;
+    for path in paths {
+        match options.open(&path) {
+            Ok(file) => return Ok((path, file)),
+            Err(err) if err.kind() == ErrorKind::AlreadyExists =>
+                last = Some(err),
+            Err(err) => return Err(err),
+        }
+    }
+
+    Err(last.unwrap_or_else(|| ErrorKind::AlreadyExists.into()))
+}
+
+/// If both paths are the same file, however they are written
+pub fn same_file(a: &Path, b: &Path) -> bool {
+    if a == b {
+        return true;
+    }
+
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+/// The directory of the file at `path` when it doesn't exist yet, the file
+/// can't be written until it's created
+pub fn missing_dir(path: &Path) -> Option<&Path> {
+    path.parent().filter(|dir| !dir.as_os_str().is_empty() && !dir.exists())
+}
+
+/// Create the directory and the ones above it that are missing
+pub fn create_dir(dir: &Path) -> Result<()> {
+    std::fs::create_dir_all(dir)?;
+
+    Ok(())
+}
+
+/// Rename or move the file at `from` to `to`, if `to` is a directory the file
+/// is moved inside with the same name, like `mv` does. An existing file is
+/// never overwritten. Returns where the file ended up
+pub fn rename(from: &Path, to: &Path) -> Result<PathBuf> {
+    let to = if to.is_dir() {
+        match from.file_name() {
+            Some(name) => to.join(name),
+            None => return Err("the file has no name".into()),
+        }
+    } else {
+        to.to_owned()
+    };
+
+    if to.symlink_metadata().is_ok() {
+        return Err(format!("{} already exists", to.display()).into());
+    }
+
+    // Renaming across filesystems is not possible, in that case copy the
+    // file and remove the original only after the copy succeeds
+    if let Err(err) = std::fs::rename(from, &to) {
+        if err.kind() == ErrorKind::NotFound {
+            return Err(err.into());
+        }
+
+        copy(from, &to)?;
+        if let Err(err) = std::fs::remove_file(from) {
+            let _ = std::fs::remove_file(&to);
+            return Err(err.into());
+        }
+    }
+
+    Ok(to)
+}
+
+/// Copy the file, a link is copied as a link
+fn copy(from: &Path, to: &Path) -> std::io::Result<()> {
+    #[cfg(unix)]
+    if std::fs::symlink_metadata(from)?.file_type().is_symlink() {
+        return std::os::unix::fs::symlink(std::fs::read_link(from)?, to);
+    }
+
+    std::fs::copy(from, to).map(|_| ())
+}
+
+/// A file moved to the trash, it can be put back where it was
+#[derive(Debug, Clone)]
+pub struct Trashed {
+    pub original: PathBuf,
+    file: PathBuf,
+    info: Option<PathBuf>,
+}
+
+impl Trashed {
+    /// Move the file back from the trash, an existing file is never
+    /// overwritten
+    pub fn restore(&self) -> Result<()> {
+        rename(&self.file, &self.original)?;
+        if let Some(info) = &self.info {
+            let _ = std::fs::remove_file(info);
+        }
+
+        Ok(())
+    }
+}
+
+/// The trash of the desktop, on macOS the one of the user and elsewhere the
+/// freedesktop.org one
+fn trash_dir() -> Option<PathBuf> {
+    let home = std::env::var_os("HOME").map(PathBuf::from);
+    if cfg!(target_os = "macos") {
+        return home.map(|home| home.join(".Trash"));
+    }
+
+    std::env::var_os("XDG_DATA_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| home.map(|home| home.join(".local/share")))
+        .map(|data| data.join("Trash"))
+}
+
+/// Move the file to the trash, with the information the file managers need
+/// to put it back
+pub fn trash(path: &Path) -> Result<Trashed> {
+    // A link goes to the trash, not the file it points to, so only its
+    // directory is resolved
+    let name = path.file_name().ok_or("the file has no name")?;
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let original = parent.canonicalize()?.join(name);
+    std::fs::symlink_metadata(&original)?;
+    let trash = trash_dir().ok_or("there is no trash")?;
+    let (files, info) = if cfg!(target_os = "macos") {
+        (trash.clone(), None)
+    } else {
+        (trash.join("files"), Some(trash.join("info")))
+    };
+    std::fs::create_dir_all(&files)?;
+
+    // The files with the same name are told apart by a number
+    let mut unique = name.to_owned();
+    for count in 2.. {
+        let taken = files.join(&unique).exists() || info.as_ref()
+            .is_some_and(|info| info.join(info_name(&unique)).exists());
+        if !taken {
+            break;
+        }
+        unique = name.to_owned();
+        unique.push(format!(".{}", count));
+    }
+
+    let info = match info {
+        Some(info) => {
+            std::fs::create_dir_all(&info)?;
+            let info = info.join(info_name(&unique));
+            let seconds = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|elapsed| elapsed.as_secs())
+                .unwrap_or(0);
+            std::fs::write(&info, format!(
+                "[Trash Info]\nPath={}\nDeletionDate={}\n",
+                percent_encode(&original), timestamp(seconds)))?;
+            Some(info)
+        }
+        None => None,
+    };
+
+    let file = match rename(&original, &files.join(&unique)) {
+        Ok(file) => file,
+        Err(err) => {
+            if let Some(info) = &info {
+                let _ = std::fs::remove_file(info);
+            }
+            return Err(err);
+        }
+    };
+
+    Ok(Trashed { original, file, info })
+}
+
+/// The name of the information file of a file of the trash
+fn info_name(name: &std::ffi::OsStr) -> std::ffi::OsString {
+    let mut info = name.to_owned();
+    info.push(".trashinfo");
+    info
+}
+
+/// The path as an URL path, the bytes that aren't safe are escaped
+fn percent_encode(path: &Path) -> String {
+    path.to_string_lossy()
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9'
+                | b'-' | b'_' | b'.' | b'~' | b'/' => (byte as char).to_string(),
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+/// The seconds since the epoch as `YYYY-MM-DDThh:mm:ss`, in UTC
+fn timestamp(seconds: u64) -> String {
+    let days = (seconds / 86400) as i64;
+    let time = seconds % 86400;
+
+    // The civil date of the days since the epoch
+    let era_days = days + 719468;
+    let era = era_days.div_euclid(146097);
+    let day_of_era = era_days.rem_euclid(146097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524
+        - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era
+        - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+        year, month, day, time / 3600, time / 60 % 60, time % 60)
+}
+
+/// Remove the file for good
+pub fn remove(path: &Path) -> Result<()> {
+    std::fs::remove_file(path)?;
+    Ok(())
+}
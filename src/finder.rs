@@ -0,0 +1,78 @@
+//! Fuzzy matching of the project files for the file picker, and of the
+//! names of the commands for the command palette. The chars of
+//! the query must be on the path in the same order, not together. The
+//! matches with the chars together, at the start of a word or on the name
+//! of the file rank first. The query matches regardless of case unless it
+//! has some uppercase char
+
+use std::path::PathBuf;
+
+/// Most matches listed, nobody scrolls past them instead of typing more
+pub const MAX_MATCHES: usize = 200;
+
+/// How good the match of the query on the path is, `None` if it doesn't
+/// match
+pub fn score(query: &str, path: &str) -> Option<i64> {
+    let case_sensitive = query.chars().any(char::is_uppercase);
+    let fold = |c: char| if case_sensitive { c } else { c.to_ascii_lowercase() };
+    let name_start = path.rfind('/').map(|idx| idx + 1).unwrap_or(0);
+
+    let mut score = 0;
+    let mut chars = path.char_indices();
+    let mut before = None;
+    let mut matched_any = false;
+    for wanted in query.chars().map(fold) {
+        let mut gap = 0;
+        let idx = loop {
+            let (idx, c) = chars.next()?;
+            let previous = before.replace(c);
+            if fold(c) == wanted {
+                let at_word = previous.is_none_or(|previous| 
+                    matches!(previous, '/' | '_' | '-' | '.' | ' '));
+                if at_word {
+                    score += 6;
+                }
+                break idx;
+            }
+            gap += 1;
+        };
+
+        if gap == 0 && matched_any {
+            score += 8;
+        }
+        if idx >= name_start {
+            score += 4;
+        }
+        score -= gap;
+        matched_any = true;
+    }
+
+    Some(score)
+}
+
+/// The candidates matching the query, the best first and the shorter ones
+/// first between equals
+pub fn rank<S: AsRef<str>>(
+    candidates: impl IntoIterator<Item = S>,
+    query: &str
+) -> Vec<S> {
+    let mut matches: Vec<_> = candidates
+        .into_iter()
+        .filter_map(|candidate| 
+            Some((score(query, candidate.as_ref())?, candidate)))
+        .collect();
+    matches.sort_by(|(a_score, a), (b_score, b)| b_score.cmp(a_score)
+        .then(a.as_ref().len().cmp(&b.as_ref().len()))
+        .then(a.as_ref().cmp(b.as_ref())));
+
+    matches.into_iter().map(|(_, candidate)| candidate).collect()
+}
+
+/// The files matching the query, the best first
+pub fn matches(files: &[PathBuf], query: &str) -> Vec<String> {
+    rank(files.iter().map(|path| path.to_string_lossy()), query)
+        .into_iter()
+        .take(MAX_MATCHES)
+        .map(|path| path.into_owned())
+        .collect()
+}
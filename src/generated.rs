@@ -0,0 +1,50 @@
+//! Detection of generated files. The header of the files opened is checked
+//! against patterns like `DO NOT EDIT`, editing them is usually a mistake
+//! because the changes are lost the next time they are generated
+
+use crate::regex::Regex;
+use crate::text::Document;
+
+/// Lines at the start of a file where the header is looked for
+const HEADER_LINES: usize = 10;
+
+/// Patterns of the usual headers of the code generators
+const DEFAULT_PATTERNS: [&str; 5] = [
+    "DO NOT EDIT",
+    "[Dd]o not edit",
+    "@generated",
+    "[Aa]uto-?generated",
+    "[Gg]enerated by",
+];
+
+/// What is considered a generated file and what is done with it
+#[derive(Debug)]
+pub struct GeneratedGuard {
+    pub patterns: Vec<Regex>,
+
+    /// Open the generated files as read-only instead of only warning
+    pub read_only: bool,
+}
+
+impl Default for GeneratedGuard {
+    fn default() -> Self {
+        let patterns = DEFAULT_PATTERNS
+            .iter()
+            .map(|pattern| Regex::new(pattern).unwrap())
+            .collect();
+
+        Self { patterns, read_only: false }
+    }
+}
+
+impl GeneratedGuard {
+    /// The line of the header that tells the document is generated
+    pub fn check(&self, doc: &Document) -> Option<usize> {
+        doc.buffer
+            .lines()
+            .take(HEADER_LINES)
+            .position(|line| self.patterns
+                .iter()
+                .any(|pattern| pattern.find_at(line, 0).is_some()))
+    }
+}
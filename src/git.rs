@@ -0,0 +1,126 @@
+//! The git repository the documents are in. Git is asked on a thread so a
+//! slow repository never blocks the typing, the answer wakes the editor.
+//! The branch and if the work tree has changes go to the status bar, and
+//! the version of the file at `HEAD` becomes the baseline of the changes
+//! marked on the gutter, whose hunks can then be visited
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::Sender;
+use std::thread;
+
+use crate::events::Wake;
+use crate::text;
+
+/// The state of the repository of a file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RepoStatus {
+    /// The branch checked out, or the short hash of a detached `HEAD`
+    pub branch: String,
+
+    /// There are changes not committed to the tracked files
+    pub dirty: bool,
+}
+
+/// What git said about a file, `None` outside of a repository
+#[derive(Debug)]
+pub struct Answer {
+    pub path: PathBuf,
+    pub status: Option<RepoStatus>,
+
+    /// The lines of the file at `HEAD`, `None` when it's not committed
+    pub head: Option<Vec<String>>,
+}
+
+/// The questions to git running and what it answered
+#[derive(Debug, Default)]
+pub struct Git {
+    statuses: HashMap<PathBuf, Option<RepoStatus>>,
+    running: HashSet<PathBuf>,
+    answers: Arc<Mutex<Vec<Answer>>>,
+}
+
+impl Git {
+    /// Ask git about the file on a thread, once at a time for each file
+    pub fn query(&mut self, path: &Path, wake: Sender<Wake>) {
+        if !self.running.insert(path.to_owned()) {
+            return;
+        }
+
+        let path = path.to_owned();
+        let answers = Arc::clone(&self.answers);
+        thread::spawn(move || {
+            let answer = ask(&path);
+            if let Ok(mut answers) = answers.lock() {
+                answers.push(answer);
+            }
+            let _ = wake.send(Wake::Git);
+        });
+    }
+
+    /// The answers arrived since the last time, the statuses are kept
+    pub fn take_answers(&mut self) -> Vec<Answer> {
+        let answers = match self.answers.lock() {
+            Ok(mut answers) => std::mem::take(&mut *answers),
+            Err(_) => return Vec::new(),
+        };
+        for answer in &answers {
+            self.running.remove(&answer.path);
+            self.statuses.insert(answer.path.clone(), answer.status.clone());
+        }
+
+        answers
+    }
+
+    /// The repository of the file, as git said last time
+    pub fn status(&self, path: &Path) -> Option<&RepoStatus> {
+        self.statuses.get(path)?.as_ref()
+    }
+
+    pub fn is_busy(&self) -> bool {
+        !self.running.is_empty()
+    }
+}
+
+/// Run git on the directory of the file
+fn ask(path: &Path) -> Answer {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let git = |args: &[&str]| -> Option<Vec<u8>> {
+        let output = Command::new("git")
+            .args(args)
+            .current_dir(dir)
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .output()
+            .ok()?;
+        output.status.success().then_some(output.stdout)
+    };
+    let text = |bytes: Vec<u8>| String::from_utf8_lossy(&bytes).trim().to_owned();
+
+    let branch = match git(&["rev-parse", "--abbrev-ref", "HEAD"]).map(text) {
+        Some(branch) if branch == "HEAD" =>
+            git(&["rev-parse", "--short", "HEAD"]).map(text),
+        branch => branch,
+    };
+    let status = branch.map(|branch| RepoStatus {
+        branch,
+        dirty: git(&["status", "--porcelain", "--untracked-files=no"])
+            .is_some_and(|changes| !changes.is_empty()),
+    });
+
+    // The path relative to the directory git runs on
+    let head = status.as_ref()
+        .and_then(|_| path.file_name())
+        .and_then(|name| {
+            let spec = format!("HEAD:./{}", name.to_string_lossy());
+            git(&["show", &spec])
+        })
+        .map(|bytes| text::split_lines(&bytes));
+
+    Answer { path: path.to_owned(), status, head }
+}
@@ -0,0 +1,41 @@
+//! Search of the lines of the project files that match a pattern. It runs
+//! ripgrep when it's installed, else grep, and the matches stream into a
+//! panel as `path:line:text` lines that are jumped to
+
+use std::path::PathBuf;
+
+use crate::index::IGNORED_DIRS;
+
+/// The shell command that searches the files under the current directory,
+/// the pattern is an extended regular expression. Both programs print the
+/// same lines, without colors. The recursive grep without files searches
+/// the current directory, ripgrep would read the stdin
+pub fn command(pattern: &str) -> String {
+    let pattern = quote(pattern);
+    let excluded: String = IGNORED_DIRS
+        .iter()
+        .map(|dir| format!(" --exclude-dir={}", dir))
+        .collect();
+
+    format!("if command -v rg >/dev/null 2>&1; \
+        then rg --line-number --no-heading --with-filename --color=never \
+            -e {pattern} .; \
+        else grep -rnIE{excluded} -e {pattern}; fi")
+}
+
+/// The file and the line, from 0, of a match printed by the search
+pub fn location(line: &str) -> Option<(PathBuf, usize)> {
+    let mut fields = line.splitn(3, ':');
+    let path = fields.next().filter(|path| !path.is_empty())?;
+    let number: usize = fields.next()?.parse().ok()?;
+    fields.next()?;
+
+    // The files of the current directory are opened as the ones typed
+    let path = path.strip_prefix("./").unwrap_or(path);
+    Some((PathBuf::from(path), number.checked_sub(1)?))
+}
+
+/// The text as a single argument of the shell, whatever it has inside
+fn quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "'\\''"))
+}
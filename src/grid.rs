@@ -0,0 +1,367 @@
+//! The frames are drawn on a grid of cells instead of straight on the
+//! terminal. The grid is compared with the one flushed before and only the
+//! cells that changed are written to the backend, moving the cursor as few
+//! times as possible, so a frame that changes a char writes a char. The
+//! same crossterm commands the terminal takes are drawn with `draw!`
+
+use std::fmt::Display;
+
+use crossterm::cursor::{
+    Hide, MoveTo, MoveToRow, RestorePosition, SavePosition, Show
+};
+use crossterm::style::{Color, ContentStyle, Print, PrintStyledContent};
+use crossterm::terminal::{Clear, ClearType};
+
+use crate::Result;
+use crate::backend::Backend;
+use crate::unicode;
+
+/// A cell of the terminal, a wide char takes its cell and the one after it
+/// which is left empty
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Cell {
+    c: char,
+
+    /// The combining chars drawn over `c`, most cells have none
+    marks: String,
+    style: ContentStyle,
+}
+
+impl Cell {
+    /// The cell covered by the wide char on its left
+    const COVERED: char = '\0';
+
+    fn blank() -> Self {
+        Self { c: ' ', marks: String::new(), style: ContentStyle::default() }
+    }
+}
+
+/// The cells of the frame being drawn and of the frame on the terminal
+#[derive(Debug)]
+pub struct Grid {
+    width: u16,
+    height: u16,
+
+    /// The frame being drawn, it starts as the flushed one and the parts
+    /// not drawn again stay as they are
+    cells: Vec<Cell>,
+    flushed: Vec<Cell>,
+
+    /// The terminal shows something else than the flushed frame, the next
+    /// flush clears it and writes every cell
+    stale: bool,
+
+    /// Where the cursor is as the frame is drawn, where it was saved and
+    /// if it's shown
+    cursor: (u16, u16),
+    saved: (u16, u16),
+    visible: bool,
+    flushed_cursor: Option<((u16, u16), bool)>,
+}
+
+impl Grid {
+    pub fn new((width, height): (u16, u16)) -> Self {
+        let cells = vec![Cell::blank(); width as usize * height as usize];
+        Self {
+            width,
+            height,
+            flushed: cells.clone(),
+            cells,
+            stale: true,
+            cursor: (0, 0),
+            saved: (0, 0),
+            visible: true,
+            flushed_cursor: None,
+        }
+    }
+
+    /// Follow the size of the terminal, returns if it changed. The frame is
+    /// blank after it and the terminal is cleared on the next flush
+    pub fn resize(&mut self, size: (u16, u16)) -> bool {
+        if size == (self.width, self.height) {
+            return false;
+        }
+
+        *self = Self::new(size);
+        true
+    }
+
+    /// Write to the backend the cells that changed since the last flush,
+    /// and leave the cursor where the frame put it
+    pub fn flush(&mut self, backend: &mut (impl Backend + ?Sized))
+            -> Result<()> {
+        if self.stale {
+            backend.clear_all()?;
+            self.flushed.fill(Cell::blank());
+            self.flushed_cursor = None;
+            self.stale = false;
+        }
+
+        let cursor = (self.cursor, self.visible);
+        if self.cells == self.flushed && self.flushed_cursor == Some(cursor) {
+            return Ok(());
+        }
+
+        // The cursor is hidden while it jumps around the changes
+        backend.show_cursor(false)?;
+        let mut at = None;
+        let width = self.width as usize;
+        let mut buffer = [0; 4];
+        for y in 0..self.height {
+            let row = y as usize * width;
+
+            // Past the last cell with something the row is cleared at once
+            let blank = Cell::blank();
+            let end = self.cells[row..row + width]
+                .iter()
+                .rposition(|cell| *cell != blank)
+                .map_or(0, |last| last + 1);
+            let mut x = 0;
+            while x < width {
+                let cell = &self.cells[row + x];
+                if *cell == self.flushed[row + x] || cell.c == Cell::COVERED {
+                    x += 1;
+                    continue;
+                }
+
+                if at != Some((x, y)) {
+                    backend.move_to(x as u16, y)?;
+                }
+
+                if x >= end {
+                    backend.clear_line()?;
+                    at = Some((x, y));
+                    break;
+                }
+
+                let c = cell.c.encode_utf8(&mut buffer);
+                backend.print_styled(c, cell.style)?;
+                if !cell.marks.is_empty() {
+                    backend.print_styled(&cell.marks, cell.style)?;
+                }
+                let wide = self.cells.get(row + x + 1)
+                    .is_some_and(|next| next.c == Cell::COVERED && x + 1 < width);
+                x += if wide { 2 } else { 1 };
+                at = Some((x, y));
+            }
+        }
+
+        backend.move_to(self.cursor.0, self.cursor.1)?;
+        if self.visible {
+            backend.show_cursor(true)?;
+        }
+        backend.flush()?;
+
+        self.flushed.clone_from(&self.cells);
+        self.flushed_cursor = Some(cursor);
+
+        Ok(())
+    }
+
+    /// The text of the row `y` of the frame drawn, the covered cells left out
+    #[cfg(test)]
+    pub fn row(&self, y: u16) -> String {
+        let width = self.width as usize;
+        let start = y as usize * width;
+        self.cells[start..start + width]
+            .iter()
+            .filter(|cell| cell.c != Cell::COVERED)
+            .flat_map(|cell| std::iter::once(cell.c).chain(cell.marks.chars()))
+            .collect()
+    }
+
+    /// The background of the cell of the frame drawn
+    #[cfg(test)]
+    pub fn background(&self, x: u16, y: u16) -> Option<Color> {
+        self.cells[y as usize * self.width as usize + x as usize]
+            .style
+            .background_color
+    }
+
+    /// Where the frame drawn leaves the cursor, if it's shown
+    #[cfg(test)]
+    pub fn cursor(&self) -> Option<(u16, u16)> {
+        self.visible.then_some(self.cursor)
+    }
+
+    fn move_to(&mut self, column: u16, row: u16) {
+        self.cursor = (column, row);
+    }
+
+    /// Put the text on the cells from the cursor, what goes past the right
+    /// edge is cut
+    fn print(&mut self, text: &str, style: ContentStyle) {
+        let (mut x, y) = (self.cursor.0 as usize, self.cursor.1 as usize);
+        let width = self.width as usize;
+        if y >= self.height as usize {
+            return;
+        }
+
+        let row = y * width;
+        let mut last: Option<usize> = None;
+        for c in text.chars() {
+            let cells = unicode::char_width(c);
+            if cells == 0 {
+                // A combining char goes over the char before it
+                if let Some(idx) = last {
+                    self.cells[idx].marks.push(c);
+                }
+                continue;
+            }
+            if x + cells > width {
+                x = width;
+                break;
+            }
+
+            self.uncover(row, x);
+            self.cells[row + x] = Cell { c, marks: String::new(), style };
+            if cells == 2 {
+                self.uncover(row, x + 1);
+                self.cells[row + x + 1] =
+                    Cell { c: Cell::COVERED, marks: String::new(), style };
+            }
+            last = Some(row + x);
+            x += cells;
+        }
+
+        self.cursor.0 = x as u16;
+    }
+
+    /// Before writing on the cell `x` of the row, the halves of the wide
+    /// char it belongs to are blanked, as the terminals do
+    fn uncover(&mut self, row: usize, x: usize) {
+        let width = self.width as usize;
+        if self.cells[row + x].c == Cell::COVERED && x > 0 {
+            self.cells[row + x - 1] = Cell::blank();
+        }
+        if x + 1 < width && self.cells[row + x + 1].c == Cell::COVERED {
+            self.cells[row + x + 1] = Cell::blank();
+        }
+    }
+
+    /// Give the background to the `cells` from the cursor that have none,
+    /// what's drawn on them stays
+    fn paint(&mut self, color: Color, cells: u16) {
+        let (x, y) = (self.cursor.0 as usize, self.cursor.1 as usize);
+        let width = self.width as usize;
+        if y >= self.height as usize || x >= width {
+            return;
+        }
+
+        // A wide char is painted whole from any of its cells
+        let row = y * width;
+        let mut start = row + x;
+        if self.cells[start].c == Cell::COVERED && x > 0 {
+            start -= 1;
+        }
+        let mut end = row + usize::min(width, x + cells as usize);
+        if end < row + width && self.cells[end].c == Cell::COVERED {
+            end += 1;
+        }
+        for cell in &mut self.cells[start..end] {
+            cell.style.background_color.get_or_insert(color);
+        }
+    }
+
+    fn clear(&mut self, clear: ClearType) {
+        let width = self.width as usize;
+        let (x, y) = (self.cursor.0 as usize, self.cursor.1 as usize);
+        let row = y * width;
+        let range = match clear {
+            ClearType::All | ClearType::Purge => 0..self.cells.len(),
+            ClearType::FromCursorDown => (row + x).min(self.cells.len())
+                ..self.cells.len(),
+            ClearType::FromCursorUp => 0..(row + x + 1).min(self.cells.len()),
+            ClearType::CurrentLine => row..row + width,
+            ClearType::UntilNewLine => row + x.min(width)..row + width,
+        };
+        if range.end > self.cells.len() {
+            return;
+        }
+
+        if range.start % width > 0 {
+            self.uncover(row, range.start % width);
+        }
+        self.cells[range].fill(Cell::blank());
+    }
+}
+
+/// A command of the terminal drawn on the grid
+pub trait Draw {
+    fn draw(self, grid: &mut Grid);
+}
+
+impl Draw for MoveTo {
+    fn draw(self, grid: &mut Grid) {
+        grid.move_to(self.0, self.1);
+    }
+}
+
+impl Draw for MoveToRow {
+    fn draw(self, grid: &mut Grid) {
+        grid.move_to(grid.cursor.0, self.0);
+    }
+}
+
+impl Draw for SavePosition {
+    fn draw(self, grid: &mut Grid) {
+        grid.saved = grid.cursor;
+    }
+}
+
+impl Draw for RestorePosition {
+    fn draw(self, grid: &mut Grid) {
+        grid.cursor = grid.saved;
+    }
+}
+
+impl Draw for Show {
+    fn draw(self, grid: &mut Grid) {
+        grid.visible = true;
+    }
+}
+
+impl Draw for Hide {
+    fn draw(self, grid: &mut Grid) {
+        grid.visible = false;
+    }
+}
+
+impl Draw for Clear {
+    fn draw(self, grid: &mut Grid) {
+        grid.clear(self.0);
+    }
+}
+
+/// The background of the cells from the cursor, under what's drawn on them.
+/// The terminals have no such command, it's only drawn on the grid
+pub struct PaintBackground(pub Color, pub u16);
+
+impl Draw for PaintBackground {
+    fn draw(self, grid: &mut Grid) {
+        grid.paint(self.0, self.1);
+    }
+}
+
+/// Text without colors, the styled content goes with `PrintStyledContent`
+impl<T: Display> Draw for Print<T> {
+    fn draw(self, grid: &mut Grid) {
+        grid.print(&self.0.to_string(), ContentStyle::default());
+    }
+}
+
+impl<D: Display> Draw for PrintStyledContent<D> {
+    fn draw(self, grid: &mut Grid) {
+        grid.print(&self.0.content().to_string(), *self.0.style());
+    }
+}
+
+/// Draw the commands on the grid the way `queue!` writes them on the
+/// terminal, it never fails but keeps the same shape
+macro_rules! draw {
+    ($grid:expr $(, $command:expr)* $(,)?) => {{
+        $( $crate::grid::Draw::draw($command, $grid); )*
+        std::io::Result::Ok(())
+    }};
+}
+pub(crate) use draw;
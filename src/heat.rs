@@ -0,0 +1,119 @@
+//! Where the time goes on each document. The seconds the cursor spends on a
+//! line and the edits made on it warm the line, and the heat fades with
+//! time so only the regions worked on recently stay warm. Drawn on the
+//! gutter with `set heatmap=on`, it helps finding the way back on the long
+//! documents
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::edits::EditList;
+
+/// The longest time credited to a line between two events, past it the
+/// user is away and not working on it
+const MAX_VISIT: Duration = Duration::from_secs(30);
+
+/// The seconds of work an edit is worth
+const EDIT_HEAT: f32 = 10.0;
+
+/// The time it takes for the heat of a line to fade to the half
+const HALF_LIFE: f32 = 15.0 * 60.0;
+
+/// The heat each level starts at, in seconds of work
+const LEVELS: [f32; 3] = [5.0, 30.0, 120.0];
+
+/// The heat of a line when it was last warmed
+#[derive(Debug, Clone, Copy)]
+struct Warmth {
+    heat: f32,
+    since: Instant,
+}
+
+impl Warmth {
+    /// The heat left at `now`
+    fn at(&self, now: Instant) -> f32 {
+        let elapsed = now.saturating_duration_since(self.since).as_secs_f32();
+        self.heat * 0.5f32.powf(elapsed / HALF_LIFE)
+    }
+}
+
+/// The heat of the lines of every document visited
+#[derive(Debug)]
+pub struct Heatmap {
+    documents: HashMap<PathBuf, HashMap<usize, Warmth>>,
+
+    /// The line the cursor is on since the last update
+    current: Option<(PathBuf, usize)>,
+    last_update: Instant,
+
+    /// The edits of the list recorded at the last update
+    edits_seen: usize,
+}
+
+impl Default for Heatmap {
+    fn default() -> Self {
+        Self {
+            documents: HashMap::new(),
+            current: None,
+            last_update: Instant::now(),
+            edits_seen: 0,
+        }
+    }
+}
+
+impl Heatmap {
+    /// Credit the time since the last update to the line the cursor was on,
+    /// and the newest edit of the list to its line. Returns if the level
+    /// of any of those lines changed
+    pub fn update(&mut self, path: &Path, line: usize, edits: &EditList)
+            -> bool {
+        let now = Instant::now();
+        let spent = now.duration_since(self.last_update).min(MAX_VISIT);
+        self.last_update = now;
+
+        let mut changed = false;
+        if let Some((path, line)) = self.current.take() {
+            changed |= self.warm(path, line, spent.as_secs_f32(), now);
+        }
+        // The edits of a single event are warmed as one
+        if edits.recorded() != self.edits_seen {
+            self.edits_seen = edits.recorded();
+            if let Some(edit) = edits.last() {
+                changed |=
+                    self.warm(edit.path.clone(), edit.line, EDIT_HEAT, now);
+            }
+        }
+        self.current = Some((path.to_owned(), line));
+
+        changed
+    }
+
+    /// Add heat to the line, returns if its level changed
+    fn warm(&mut self, path: PathBuf, line: usize, heat: f32, now: Instant)
+            -> bool {
+        let warmth = self.documents
+            .entry(path)
+            .or_default()
+            .entry(line)
+            .or_insert(Warmth { heat: 0.0, since: now });
+        let before = warmth.at(now);
+        *warmth = Warmth { heat: before + heat, since: now };
+
+        level(before) != level(warmth.heat)
+    }
+
+    /// How warm the line of the document is, from 0 for the cold lines to
+    /// 3 for the hottest ones
+    pub fn level(&self, path: &Path, line: usize) -> usize {
+        self.documents.get(path)
+            .and_then(|lines| lines.get(&line))
+            .map(|warmth| level(warmth.at(Instant::now())))
+            .unwrap_or(0)
+    }
+}
+
+/// The level of the heat
+fn level(heat: f32) -> usize {
+    LEVELS.iter().filter(|start| heat >= **start).count()
+}
@@ -0,0 +1,129 @@
+//! Highlight rules of the user, a regular expression and the style of its
+//! matches, like `TODO|FIXME` in black on yellow or the ticket ids in bold.
+//! They are on the `[highlight]` table of the config and are drawn over the
+//! text of every line shown, under the matches of the search
+//!
+//! ```toml
+//! [highlight]
+//! "TODO|FIXME" = "black on yellow"
+//! "[A-Z]+-[0-9]+" = "bold cyan"
+//! ```
+
+use crossterm::style::{Attribute, Color, ContentStyle};
+
+use crate::Result;
+use crate::regex::Regex;
+
+/// A regular expression and the style of its matches
+#[derive(Debug)]
+pub struct Rule {
+    pub pattern: String,
+    regex: Regex,
+    pub style: ContentStyle,
+
+    /// The style as it was written
+    pub words: String,
+}
+
+/// The rules in the order they were added, the later ones are drawn over
+/// the earlier ones
+#[derive(Debug, Default)]
+pub struct Highlights {
+    pub rules: Vec<Rule>,
+}
+
+impl Highlights {
+    /// Add the rule, or replace the style of the one with the same pattern
+    pub fn add(&mut self, pattern: &str, style: &str) -> Result<()> {
+        let regex = Regex::new(pattern)
+            .map_err(|err| format!("invalid pattern `{}`: {}", pattern, err))?;
+        let words = style.trim().to_owned();
+        let style = parse_style(style)?;
+
+        match self.rules.iter_mut().find(|rule| rule.pattern == pattern) {
+            Some(rule) => {
+                rule.style = style;
+                rule.words = words;
+            }
+            None => self.rules.push(Rule {
+                pattern: pattern.to_owned(),
+                regex,
+                style,
+                words,
+            }),
+        }
+
+        Ok(())
+    }
+
+    /// Remove the rule of the pattern, returns if there was one
+    pub fn remove(&mut self, pattern: &str) -> bool {
+        let len = self.rules.len();
+        self.rules.retain(|rule| rule.pattern != pattern);
+        self.rules.len() != len
+    }
+
+    /// The byte ranges of the line matched by the rules and their styles,
+    /// in the order they are drawn
+    pub fn spans(&self, line: &str) -> Vec<(usize, usize, ContentStyle)> {
+        let mut spans = Vec::new();
+        for rule in &self.rules {
+            let mut start = 0;
+            while let Some(captures) = rule.regex.find_at(line, start) {
+                let (match_start, match_end) = captures.range();
+                if match_end > match_start {
+                    spans.push((match_start, match_end, rule.style));
+                }
+
+                // An empty match moves a char forward, or it's found again
+                start = match line[match_end..].chars().next() {
+                    _ if match_end > match_start => match_end,
+                    Some(c) => match_end + c.len_utf8(),
+                    None => break,
+                };
+            }
+        }
+
+        spans
+    }
+}
+
+/// A style as words: the colors of the text and of the background after
+/// `on`, and the attributes, like `bold red on black`
+pub fn parse_style(text: &str) -> Result<ContentStyle> {
+    let mut style = ContentStyle::new();
+    let mut words = text.split_whitespace();
+    while let Some(word) = words.next() {
+        match word {
+            "bold" => style.attributes.set(Attribute::Bold),
+            "italic" => style.attributes.set(Attribute::Italic),
+            "underline" => style.attributes.set(Attribute::Underlined),
+            "reverse" => style.attributes.set(Attribute::Reverse),
+            "on" => {
+                let color = words.next().ok_or("missing color after `on`")?;
+                style.background_color = Some(parse_color(color)?);
+            }
+            color => style.foreground_color = Some(parse_color(color)?),
+        }
+    }
+    if style == ContentStyle::new() {
+        return Err("empty style".into());
+    }
+
+    Ok(style)
+}
+
+/// A color by its name, like `dark_red`, or as `#rrggbb`
+fn parse_color(name: &str) -> Result<Color> {
+    if let Some(hex) = name.strip_prefix('#') {
+        let channel = |idx: usize| hex.get(idx..idx + 2)
+            .and_then(|channel| u8::from_str_radix(channel, 16).ok());
+        return match (hex.len(), channel(0), channel(2), channel(4)) {
+            (6, Some(r), Some(g), Some(b)) => Ok(Color::Rgb { r, g, b }),
+            _ => Err(format!("invalid color `{}`", name).into()),
+        };
+    }
+
+    Color::try_from(name)
+        .map_err(|_| format!("unknown color `{}`", name).into())
+}
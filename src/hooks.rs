@@ -0,0 +1,126 @@
+//! Event bus where actions can be attached to the lifecycle of a document, so
+//! things like trimming whitespace or regenerating tags run at the right
+//! moment without the core of the editor knowing about them
+
+use std::process::{Command, Stdio};
+
+use crate::Result;
+use crate::text::Document;
+
+/// The points on the lifecycle of a document where hooks can be attached
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookEvent {
+    /// The document has just been loaded from disk
+    Open,
+
+    /// The document is about to be written, changes made by the hook are
+    /// included on the write
+    SavePre,
+
+    /// The document has already been written
+    SavePost,
+
+    /// The filetype of the document has been detected
+    Filetype,
+
+    /// The terminal got the focus back
+    Focus,
+
+    /// The document became the one being edited, because it was opened or
+    /// switched to
+    Enter,
+}
+
+impl HookEvent {
+    /// Get the event from the name used on the config, `on-open`...
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "on-open"      => Some(Self::Open),
+            "on-save-pre"  => Some(Self::SavePre),
+            "on-save-post" => Some(Self::SavePost),
+            "on-filetype"  => Some(Self::Filetype),
+            "on-focus"     => Some(Self::Focus),
+            "on-enter"     => Some(Self::Enter),
+            _ => None,
+        }
+    }
+}
+
+/// An action run when its event fires, it can modify the document
+pub type Hook = Box<dyn FnMut(&mut Document) -> Result<()>>;
+
+/// All the hooks attached to the editor, they run in the order they were
+/// attached
+#[derive(Default)]
+pub struct Hooks {
+    attached: Vec<(HookEvent, Hook)>,
+}
+
+impl Hooks {
+    /// Attach a new action to the event
+    pub fn attach(
+        &mut self,
+        event: HookEvent,
+        hook: impl FnMut(&mut Document) -> Result<()> + 'static
+    ) {
+        self.attached.push((event, Box::new(hook)));
+    }
+
+    /// Check if any hook is attached to `event`
+    pub fn is_attached(&self, event: HookEvent) -> bool {
+        self.attached.iter().any(|(hook_event, _)| *hook_event == event)
+    }
+
+    /// Run all the hooks attached to `event`, the first one that fails stops
+    /// the rest from running
+    pub fn fire(&mut self, event: HookEvent, doc: &mut Document) -> Result<()> {
+        for (_, hook) in self.attached
+                .iter_mut()
+                .filter(|(hook_event, _)| *hook_event == event) {
+            hook(doc)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Built-in action that removes the whitespaces at the end of every line
+pub fn trim_trailing_whitespace(doc: &mut Document) -> Result<()> {
+    for idx in 0..doc.buffer.len() {
+        let line = &doc.buffer[idx];
+        if line.trim_end().len() != line.len() {
+            let trimmed = line.trim_end().to_owned();
+            doc.replace_lines(idx, idx + 1, vec![trimmed]);
+        }
+    }
+
+    Ok(())
+}
+
+/// Built-in action that runs `command` on the shell with the path of the
+/// document as its last argument, like `ctags -a` to regenerate the tags
+pub fn shell_command(
+    command: impl Into<String>
+) -> impl FnMut(&mut Document) -> Result<()> {
+    let command = command.into();
+
+    move |doc| {
+        // The output is discarded, otherwise it would be printed over the
+        // editor
+        let status = Command::new("sh")
+            .arg("-c")
+            .arg(format!("{} \"$0\"", command))
+            .arg(&doc.path)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()?;
+
+        if !status.success() {
+            return Err(format!("hook `{}` failed with {}", command, status)
+                .into());
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,151 @@
+//! Popups with information about what is under the mouse, they appear when
+//! the mouse rests over the same cell for the hover delay
+
+use std::time::{Duration, Instant};
+
+/// Time the mouse must rest before the popup appears
+pub const DEFAULT_HOVER_DELAY: Duration = Duration::from_millis(600);
+
+/// A popup shown next to the mouse
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Popup {
+    /// Terminal cell the popup points to
+    pub column: u16,
+    pub row: u16,
+
+    pub lines: Vec<String>,
+}
+
+/// The cells covered by a popup, its first column and row and its size
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PopupArea {
+    pub column: usize,
+    pub row: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl PopupArea {
+    pub fn contains(&self, column: usize, row: usize) -> bool {
+        (self.column..self.column + self.width).contains(&column)
+            && (self.row..self.row + self.height).contains(&row)
+    }
+}
+
+impl Popup {
+    /// Where the popup is drawn: below the cell it points to, or above it
+    /// when it doesn't fit, moved to the left if needed to not leave the
+    /// terminal. The document area starts at `top` and has `rows` rows, and
+    /// the terminal has `term_columns` before the scrollbar
+    pub fn area(
+        &self,
+        top: usize,
+        rows: usize,
+        term_columns: usize
+    ) -> PopupArea {
+        let width = self.lines.iter()
+            .map(|line| line.chars().count() + 2)
+            .max()
+            .unwrap_or(0)
+            .min(term_columns);
+
+        // The status bar is not covered
+        let bottom = top + rows;
+        let row = self.row as usize;
+        let start_row = if row + 1 + self.lines.len() <= bottom {
+            row + 1
+        } else {
+            row.saturating_sub(self.lines.len()).max(top)
+        };
+        let column = usize::min(self.column as usize, term_columns - width);
+
+        PopupArea {
+            column,
+            row: start_row,
+            width,
+            height: usize::min(self.lines.len(), bottom - start_row),
+        }
+    }
+}
+
+/// Tracking of the mouse position over time
+#[derive(Debug)]
+pub struct Hover {
+    pub delay: Duration,
+
+    /// Terminal cell the mouse is over and since when
+    position: Option<(u16, u16)>,
+    since: Instant,
+
+    /// If the popup for the current position was already looked up, so
+    /// positions with nothing to show are not asked again
+    resolved: bool,
+
+    pub popup: Option<Popup>,
+}
+
+impl Default for Hover {
+    fn default() -> Self {
+        Self {
+            delay: DEFAULT_HOVER_DELAY,
+            position: None,
+            since: Instant::now(),
+            resolved: false,
+            popup: None,
+        }
+    }
+}
+
+impl Hover {
+    /// The mouse moved to the cell, returns if a popup was hidden
+    pub fn moved(&mut self, column: u16, row: u16) -> bool {
+        if self.position == Some((column, row)) {
+            return false;
+        }
+
+        self.position = Some((column, row));
+        self.since = Instant::now();
+        self.resolved = false;
+
+        self.popup.take().is_some()
+    }
+
+    /// The cell the mouse rests over if the delay passed and the popup was
+    /// not looked up yet
+    pub fn due(&self) -> Option<(u16, u16)> {
+        match self.position {
+            Some(position) if !self.resolved
+                    && self.since.elapsed() >= self.delay => Some(position),
+            _ => None,
+        }
+    }
+
+    /// When the popup of the cell the mouse rests over is due, `None` when
+    /// there is nothing to look up
+    pub fn deadline(&self) -> Option<Instant> {
+        match self.position {
+            Some(_) if !self.resolved => Some(self.since + self.delay),
+            _ => None,
+        }
+    }
+
+    /// Show the popup for the due position, without lines nothing is shown.
+    /// Returns if a popup appeared
+    pub fn show(&mut self, lines: Vec<String>) -> bool {
+        self.resolved = true;
+        match (self.position, lines.is_empty()) {
+            (Some((column, row)), false) => {
+                self.popup = Some(Popup { column, row, lines });
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Hide the popup if any, returns if there was one. It doesn't appear
+    /// again until the mouse moves
+    pub fn hide(&mut self) -> bool {
+        self.resolved = true;
+        self.popup.take().is_some()
+    }
+}
@@ -0,0 +1,122 @@
+//! Indentation of the document, the re-indentation of lines is delegated to
+//! an external indenter configured as `equalprg`, like vim does. Shifting
+//! the lines a level right or left is done here
+
+use crate::Result;
+use crate::cancel::CancelToken;
+use crate::shell::pipe_shell;
+use crate::text::{Document, split_lines};
+
+/// Placeholder of `equalprg` replaced by the target range as `start:end`
+/// (starting from 1), with it the whole document is sent to the indenter
+/// and its output replaces the whole document, which is what tools like
+/// `clang-format --lines={lines}` expect
+pub const LINES_PLACEHOLDER: &str = "{lines}";
+
+/// Re-indent the lines in `start..end` piping them through `equalprg`, the
+/// lines are replaced in place. Returns the number of lines of the result
+/// that took the place of the range. The document is left as it was when
+/// the indenter is cancelled
+pub fn reindent(
+    doc: &mut Document,
+    start: usize,
+    end: usize,
+    equalprg: &str,
+    cancel: &CancelToken
+) -> Result<usize> {
+    let end = usize::min(end, doc.buffer.len());
+    if start >= end {
+        return Err("there are no lines to indent".into());
+    }
+
+    if equalprg.contains(LINES_PLACEHOLDER) {
+        // The indenter gets the whole document for context and only
+        // touches the lines of the range
+        let command = equalprg.replace(
+            LINES_PLACEHOLDER, &format!("{}:{}", start + 1, end));
+        let output = pipe_shell(
+            &command, join(doc.buffer.lines()).as_bytes(), cancel)?;
+        let lines = split_lines(output.as_bytes());
+
+        let new_len = (end - start + lines.len())
+            .saturating_sub(doc.buffer.len());
+        doc.set_lines(lines);
+
+        Ok(new_len)
+    } else {
+        let lines = join(doc.buffer.lines_from(start).take(end - start));
+        let output = pipe_shell(equalprg, lines.as_bytes(), cancel)?;
+        let lines = split_lines(output.as_bytes());
+        let new_len = lines.len();
+        doc.replace_lines(start, end, lines);
+
+        Ok(new_len)
+    }
+}
+
+/// The whitespace at the start of the line
+pub fn leading(line: &str) -> &str {
+    let len = line.bytes()
+        .take_while(|byte| byte.is_ascii_whitespace())
+        .count();
+
+    &line[..len]
+}
+
+/// Indent the lines `start..end` a level with `unit`, the empty lines are
+/// left alone. Returns the bytes added to each line
+pub fn indent_lines(
+    doc: &mut Document,
+    start: usize,
+    end: usize,
+    unit: &str
+) -> Vec<usize> {
+    let end = usize::min(end, doc.buffer.len());
+    let (lines, added) = doc.buffer.lines_from(start)
+        .take(end.saturating_sub(start))
+        .map(|line| if line.is_empty() {
+            (String::new(), 0)
+        } else {
+            (format!("{}{}", unit, line), unit.len())
+        })
+        .unzip();
+    doc.replace_lines(start, end, lines);
+
+    added
+}
+
+/// Dedent the lines `start..end` a level, a tab or up to `tab_width`
+/// spaces. Returns the bytes removed from each line
+pub fn dedent_lines(
+    doc: &mut Document,
+    start: usize,
+    end: usize,
+    tab_width: usize
+) -> Vec<usize> {
+    let end = usize::min(end, doc.buffer.len());
+    let (lines, removed) = doc.buffer.lines_from(start)
+        .take(end.saturating_sub(start))
+        .map(|line| {
+            let removed = if line.starts_with('\t') {
+                1
+            } else {
+                line.bytes().take(tab_width).take_while(|b| *b == b' ').count()
+            };
+            (line[removed..].to_owned(), removed)
+        })
+        .unzip();
+    doc.replace_lines(start, end, lines);
+
+    removed
+}
+
+/// Join the lines as the contents of a file
+fn join<'a>(lines: impl Iterator<Item = &'a str>) -> String {
+    let mut text = String::new();
+    for line in lines {
+        text.push_str(line);
+        text.push('\n');
+    }
+
+    text
+}
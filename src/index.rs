@@ -0,0 +1,198 @@
+//! Index of the paths of the project files, built on the background by a
+//! pool of threads so the pickers respond instantly even on huge
+//! repositories. The directories indexed are watched and rescanned when
+//! their entries change
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Condvar, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+
+use crate::finder;
+
+/// Directories that are never indexed, they are huge and not edited by hand
+pub const IGNORED_DIRS: &[&str] = 
+    &[".git", ".hg", ".svn", "target", "node_modules"];
+
+/// Directories waiting to be scanned and the workers scanning
+#[derive(Default)]
+struct Queue {
+    dirs: Vec<PathBuf>,
+    active: usize,
+}
+
+/// State shared with the workers
+#[derive(Default)]
+struct Shared {
+    queue: Mutex<Queue>,
+    queue_changed: Condvar,
+
+    /// Paths of the files found, relative to the root
+    files: Mutex<Vec<PathBuf>>,
+
+    /// Directories scanned, with the ones not yet watched apart
+    dirs: Mutex<HashSet<PathBuf>>,
+    unwatched_dirs: Mutex<Vec<PathBuf>>,
+
+    /// If the scan in progress finished
+    ready: AtomicBool,
+}
+
+/// The files of the project under `root`
+pub struct ProjectIndex {
+    root: PathBuf,
+    shared: Arc<Shared>,
+}
+
+impl ProjectIndex {
+    /// Start indexing the project on the background, the index is empty
+    /// until the workers find the files
+    pub fn build(root: impl AsRef<Path>) -> Self {
+        let index = Self {
+            root: root.as_ref().to_owned(),
+            shared: Arc::new(Shared::default()),
+        };
+        index.scan(index.root.clone());
+
+        index
+    }
+
+    /// If no scan is in progress
+    pub fn is_ready(&self) -> bool {
+        self.shared.ready.load(Ordering::Acquire)
+    }
+
+    /// Stop the scan in progress, the files found so far are kept
+    pub fn cancel(&self) {
+        self.shared.queue.lock().unwrap().dirs.clear();
+    }
+
+    /// The directory indexed
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// The files indexed so far, relative to the root
+    pub fn files(&self) -> Vec<PathBuf> {
+        self.shared.files.lock().unwrap().clone()
+    }
+
+    /// Number of files indexed so far
+    pub fn file_count(&self) -> usize {
+        self.shared.files.lock().unwrap().len()
+    }
+
+    /// The files indexed so far matching the query of the file picker, the
+    /// best first
+    pub fn matching(&self, query: &str) -> Vec<String> {
+        finder::matches(&self.shared.files.lock().unwrap(), query)
+    }
+
+    /// The directories scanned since the last call, to be watched
+    pub fn take_unwatched_dirs(&self) -> Vec<PathBuf> {
+        std::mem::take(&mut *self.shared.unwatched_dirs.lock().unwrap())
+    }
+
+    /// If the path is a directory of the index
+    pub fn contains_dir(&self, dir: &Path) -> bool {
+        self.shared.dirs.lock().unwrap().contains(dir)
+    }
+
+    /// The entries of the directory changed, forget everything under it and
+    /// scan it again. Returns if it still exists
+    pub fn rescan(&self, dir: &Path) -> bool {
+        let relative = dir.strip_prefix(&self.root).unwrap_or(dir).to_owned();
+        self.shared.files.lock().unwrap()
+            .retain(|file| !file.starts_with(&relative));
+        self.shared.dirs.lock().unwrap()
+            .retain(|known| !known.starts_with(dir) || known == dir);
+
+        if dir.is_dir() {
+            self.scan(dir.to_owned());
+            true
+        } else {
+            self.shared.dirs.lock().unwrap().remove(dir);
+            false
+        }
+    }
+
+    /// Scan the directory recursively with a new pool of workers
+    fn scan(&self, dir: PathBuf) {
+        self.shared.ready.store(false, Ordering::Release);
+        self.shared.queue.lock().unwrap().dirs.push(dir);
+
+        let workers = thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(4);
+        for _ in 0..workers {
+            let shared = Arc::clone(&self.shared);
+            let root = self.root.clone();
+            thread::spawn(move || worker(&shared, &root));
+        }
+    }
+}
+
+/// Scan the directories of the queue until there are no more and no other
+/// worker can add more
+fn worker(shared: &Shared, root: &Path) {
+    loop {
+        let dir = {
+            let mut queue = shared.queue.lock().unwrap();
+            loop {
+                if let Some(dir) = queue.dirs.pop() {
+                    queue.active += 1;
+                    break dir;
+                }
+                if queue.active == 0 {
+                    shared.ready.store(true, Ordering::Release);
+                    shared.queue_changed.notify_all();
+                    return;
+                }
+                queue = shared.queue_changed.wait(queue).unwrap();
+            }
+        };
+
+        let (files, subdirs) = read_dir(&dir, root);
+        shared.files.lock().unwrap().extend(files);
+        shared.dirs.lock().unwrap().insert(dir.clone());
+        shared.unwatched_dirs.lock().unwrap().push(dir);
+
+        let mut queue = shared.queue.lock().unwrap();
+        queue.dirs.extend(subdirs);
+        queue.active -= 1;
+        shared.queue_changed.notify_all();
+    }
+}
+
+/// The files (relative to the root) and the directories inside `dir`, the
+/// hidden and ignored directories are skipped
+fn read_dir(dir: &Path, root: &Path) -> (Vec<PathBuf>, Vec<PathBuf>) {
+    let mut files = Vec::new();
+    let mut subdirs = Vec::new();
+
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return (files, subdirs),
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let file_type = match entry.file_type() {
+            Ok(file_type) => file_type,
+            Err(_) => continue,
+        };
+
+        if file_type.is_dir() {
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            let ignored = IGNORED_DIRS.contains(&name.as_ref());
+            if !name.starts_with('.') && !ignored {
+                subdirs.push(path);
+            }
+        } else if file_type.is_file() {
+            files.push(path.strip_prefix(root).unwrap_or(&path).to_owned());
+        }
+    }
+
+    (files, subdirs)
+}
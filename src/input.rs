@@ -1,39 +1,71 @@
 //! Handle all the input and the reaction of the cursor/sroll to it
 
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 
-use crossterm::terminal;
 use crossterm::event::*;
+use crossterm::style::Color;
 
 use crate::{EditorState, Result};
-use crate::text::Document;
+use crate::action::{Action, CATEGORIES, COMMANDS, Motion};
+use crate::diff;
+use crate::binary;
+use crate::bookmarks::Bookmarks;
+use crate::buffers::{BufferList, OpenBuffer};
+use crate::command::{self, CommandLine};
+use crate::commit;
+use crate::complete::{self, Completion};
+use crate::compose;
+use crate::count::Limits;
+use crate::crypt::{self, Cipher, PassphrasePrompt};
+use crate::cursors::{self, CursorSet, FromEnd};
+use crate::encoding::Encoding;
+use crate::eol::LineEnding;
+use crate::events::Wake;
+use crate::export;
+use crate::fileops;
+use crate::finder;
+use crate::grep;
+use crate::hooks::{self, HookEvent};
+use crate::indent;
+use crate::marks::{Location, Marks};
+use crate::narrow;
+use crate::keymap::{KeyBinding, Keymap};
+use crate::links::{self, Link};
+use crate::lsp::{self, Reply};
+use crate::minibuffer::{Answer, Prompt, PromptKind};
+use crate::pairs::PairAction;
+use crate::panel::{Panel, PanelKind};
+use crate::patch;
+use crate::preview::Preview;
+use crate::print;
+use crate::prose;
+use crate::scrollbar;
+use crate::save::{self, AutoSave};
+use crate::search::Search;
+use crate::selection::Selection;
+use crate::shell;
+use crate::session::Place;
+use crate::snapshot::{Snapshot, Snapshots};
+use crate::status;
+use crate::stream::OutputStream;
+use crate::tabs::Tab;
+use crate::text::{self, DocKind, Document, Position, WordChars};
+use crate::todo::TodoList;
+use crate::tree::FileTree;
+use crate::theme;
+use crate::undo::{self, EditKind, PendingChange};
+use crate::unicode;
+use crate::mouse::{self, Target};
+use crate::viewport::Viewport;
+use crate::window::WindowOptions;
+use crate::wrap::{self, Layout};
 use crate::render::RenderState;
-
-#[repr(u32)]
-enum BeepType {
-    Simple = 0xFFFFFFFF,
-    Asterisk = 0x00000010,
-}
-
-#[link(name="User32")]
-extern "C" {
-    fn MessageBeep(uType: u32) -> i32;
-}
-
-macro_rules! beep {
-    (0) => {
-        unsafe {
-            MessageBeep(BeepType::Simple as u32);
-        }
-    };
-    (1) => {
-        unsafe {
-            MessageBeep(BeepType::Asterisk as u32);
-        }
-    };
-}
+use crate::regex::Regex;
+use crate::replace::{Replace, ReplaceStep};
 
 /// The state of the cursor, needed to handle the movements properly
+#[derive(Debug, Clone, Default)]
 pub struct CursorState {
     /// Not only if the cursor is at the last column, but if it should behave
     /// like it, changing how the up/down movemnts work
@@ -42,19 +74,69 @@ pub struct CursorState {
     /// Used to calculate the column, is the last space padding
     pub last_padding: usize,
 
-    /// The scrolling on the terminal
-    pub scroll_y: usize,
+    /// The lines of the document shown
+    pub viewport: Viewport,
+
+    /// Where the selection started, the selection goes from it to the
+    /// cursor
+    pub anchor: Option<Position>,
+
+    /// The cursors besides the main one
+    pub cursors: CursorSet,
+
+    /// Where the cursor was when the wheel scrolled it out of the view, it
+    /// stays hidden there until the next action brings it into the view
+    pub parked: Option<Position>,
 }
 
 /// Represents the cursor on the terminal screen
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
 pub struct Cursor {
     pub column: usize,
     pub row: usize
 }
 
 impl Cursor {
+    /// The line of the document the cursor is at
+    pub fn line(&self, cursor_state: &CursorState) -> usize {
+        cursor_state.viewport.scroll_y + self.row
+    }
+
+    /// The position of the document the cursor is at
+    pub fn position(
+        &self,
+        doc: &Document,
+        cursor_state: &CursorState
+    ) -> Position {
+        doc.position(self.line(cursor_state), self.column)
+    }
+
+    /// The text selected from the anchor to the cursor, if selecting
+    pub fn selection(
+        &self,
+        doc: &Document,
+        cursor_state: &CursorState
+    ) -> Option<Selection> {
+        let head = self.position(doc, cursor_state);
+        cursor_state.anchor.map(|anchor| Selection::new(anchor, head))
+    }
+
+    /// Move the cursor to the position of the document, scrolling only when
+    /// it's not visible
+    pub fn go_to_position(
+        &mut self,
+        position: Position,
+        editor_state: &EditorState,
+        cursor_state: &mut CursorState,
+        render_state: &mut RenderState
+    ) {
+        self.go_to(position.line, position.byte, 
+            editor_state, cursor_state, render_state);
+    }
+
     /// Adjust the column when a vertical movement issued to the proper column.
+    /// The column is kept on the terminal, not on the bytes, so it comes
+    /// from the line `from` the cursor was at before moving
     ///
     /// Cases:
     ///     - When the last line column was the first/last one, this will 
@@ -66,43 +148,46 @@ impl Cursor {
     pub fn adjust_column_vertical(
         &mut self,
         doc: &Document,
-        modifiers: KeyModifiers,
-        CursorState { last_column, last_padding, scroll_y }: &mut CursorState
+        from: usize,
+        exact: bool,
+        tab_width: usize,
+        CursorState {
+            last_column,
+            last_padding,
+            viewport: Viewport { scroll_y, .. },
+            ..
+        }: &mut CursorState
     ) {
         // Get a reference to the line the cursor is at on the document
-        assert!(*scroll_y < 134);
-        let curr_line = 
-            &doc.inner_lines[*scroll_y + self.row];
+        let curr_line = doc.buffer.line(*scroll_y + self.row).unwrap_or("");
+        let from_line = doc.buffer.line(from).unwrap_or("");
+        let column = unicode::column_of(from_line, self.column, tab_width);
 
-        // Update the padding
-        let mut curr_padding = 0;
-        while curr_padding < curr_line.len() 
-                && curr_line
-                    .as_bytes()[curr_padding]
-                    .is_ascii_whitespace() {
-            curr_padding += 1;
-        }
-        let padding = curr_padding as i32 - *last_padding as i32;
-
-        // If control pressed, just go forward without padding calculations
-        let new_column;
-        if !modifiers.contains(KeyModifiers::CONTROL) {
-            new_column = (self.column as i32 + padding)
-                .try_into().unwrap_or(0);
+        // Update the padding, the change of the indentation is measured on
+        // the terminal too
+        let curr_padding = leading_whitespace(curr_line);
+        let padding = unicode::column_of(curr_line, curr_padding, tab_width)
+            as i32
+            - unicode::column_of(from_line, *last_padding, tab_width) as i32;
+
+        // If exact, just go forward without padding calculations
+        let new_column = if !exact {
+            (column as i32 + padding).try_into().unwrap_or(0)
         } else {
-            new_column = self.column;
-        }
+            column
+        };
 
         *last_padding = curr_padding;
 
         // Update the cursor position knowning that, also handling the case 
         // that the last movement was on last line, so this will also be on the 
         // last line
-        let max_col = curr_line.len().checked_sub(1).unwrap_or(0);
+        let max_col = unicode::last_grapheme(curr_line);
         if *last_column {
             self.column = max_col;
         } else {
-            self.column = usize::min(max_col, new_column);
+            self.column = usize::min(max_col, 
+                unicode::byte_at_column(curr_line, new_column, tab_width));
         }
     }
 
@@ -113,27 +198,18 @@ impl Cursor {
         doc: &Document,
         CursorState {
             last_padding,
-            scroll_y,
+            viewport: Viewport { scroll_y, .. },
             ..
         }: &mut CursorState
     ) {
         // Get a reference to the line the cursor is at on the document
-        let curr_line = 
-            &doc.inner_lines[*scroll_y + self.row];
+        let curr_line = doc.buffer.line(*scroll_y + self.row).unwrap_or("");
 
         // Update the padding
-        let mut curr_padding = 0;
-        while curr_padding < curr_line.len() 
-                && curr_line
-                    .as_bytes()[curr_padding]
-                    .is_ascii_whitespace() {
-            curr_padding += 1;
-        }
-
-        *last_padding = curr_padding;
+        *last_padding = leading_whitespace(curr_line);
 
         // Update the cursor column to the start
-        self.column = curr_padding;
+        self.column = *last_padding;
     }
 
     /// Adjust the column when a vertical movement issued to be at the 
@@ -141,28 +217,24 @@ impl Cursor {
     pub fn adjust_column_end(
         &mut self,
         doc: &Document,
-        CursorState { last_column, last_padding, scroll_y }: &mut CursorState
+        CursorState {
+            last_column,
+            last_padding,
+            viewport: Viewport { scroll_y, .. },
+            ..
+        }: &mut CursorState
     ) {
         // Get a reference to the line the cursor is at on the document
-        let curr_line = 
-            &doc.inner_lines[*scroll_y + self.row];
+        let curr_line = doc.buffer.line(*scroll_y + self.row).unwrap_or("");
 
         // Update the padding
-        let mut curr_padding = 0;
-        while curr_padding < curr_line.len() 
-                && curr_line
-                    .as_bytes()[curr_padding]
-                    .is_ascii_whitespace() {
-            curr_padding += 1;
-        }
-
-        *last_padding = curr_padding;
+        *last_padding = leading_whitespace(curr_line);
 
         // We want the next left movement to go left
         *last_column = false;
 
         // Update the cursor column
-        self.column = curr_line.len().checked_sub(1).unwrap_or(0);
+        self.column = unicode::last_grapheme(curr_line);
     }
 
     /// Adjust the column when a random movement occurs, mouse for example, it
@@ -171,99 +243,164 @@ impl Cursor {
     pub fn adjust_column_random(
         &mut self,
         doc: &Document,
-        CursorState { last_column, last_padding, scroll_y }: &mut CursorState
+        CursorState {
+            last_column,
+            last_padding,
+            viewport: Viewport { scroll_y, .. },
+            ..
+        }: &mut CursorState
     ) {
         // Get a reference to the line the cursor is at on the document
-        let curr_line = 
-            &doc.inner_lines[*scroll_y + self.row];
+        let curr_line = doc.buffer.line(*scroll_y + self.row).unwrap_or("");
 
         // Update the padding
-        let mut curr_padding = 0;
-        while curr_padding < curr_line.len() 
-                && curr_line
-                    .as_bytes()[curr_padding]
-                    .is_ascii_whitespace() {
-            curr_padding += 1;
-        }
-        *last_padding = curr_padding;
+        *last_padding = leading_whitespace(curr_line);
 
-        // Update the `last_column` and the column if exceeds the line width
-        let max_col = curr_line.len().checked_sub(1).unwrap_or(0);
+        // Update the `last_column` and the column if exceeds the line width,
+        // never left inside a grapheme
+        let max_col = unicode::last_grapheme(curr_line);
         if max_col <= self.column {
             *last_column = true;
             self.column = max_col;
+        } else {
+            self.column = unicode::floor_grapheme(curr_line, self.column);
         }
     }
 
-    /// Move down by a single unit if possible on the file, alse update the
-    /// state for the refresh
-    pub fn move_up(
+    /// Move the cursor back inside the document after lines were removed,
+    /// the column is also clamped to the new line
+    pub fn clamp_to_document(
         &mut self,
-        CursorState { scroll_y, .. }: &mut CursorState,
-        RenderState { modif_all, last_cursor, .. }: &mut RenderState,
+        doc: &Document,
+        cursor_state: &mut CursorState,
+        RenderState { modif_all, .. }: &mut RenderState
     ) {
-        // Special case cursor at the top of the terminal, 
-        // so try to scroll (if possible)
-        if self.row == 0 {
-            if *scroll_y > 0 {
-                *modif_all = true;
-                *scroll_y -= 1;
+        let last_line = doc.buffer.len().saturating_sub(1);
+        if cursor_state.viewport.scroll_y + self.row > last_line {
+            *modif_all = true;
+            if last_line >= cursor_state.viewport.scroll_y {
+                self.row = last_line - cursor_state.viewport.scroll_y;
+            } else {
+                cursor_state.viewport.scroll_y = last_line;
+                self.row = 0;
             }
+        }
 
-        // Normal up
+        if !doc.buffer.is_empty() {
+            self.adjust_column_random(doc, cursor_state);
         } else {
-            *last_cursor = Some(*self);
-            self.row -= 1;
+            self.column = 0;
         }
     }
 
-    /// Move up by a single unit if possible on the file, alse update the state
-    /// for the refresh
-    pub fn move_down(
+    /// Move the cursor to a position of the document, the view only scrolls
+    /// when the line is not visible
+    pub fn go_to(
+        &mut self,
+        line: usize,
+        column: usize,
+        editor_state: &EditorState,
+        cursor_state: &mut CursorState,
+        render_state: &mut RenderState
+    ) {
+        match cursor_state.viewport.row_of(line, editor_state.rows) {
+            Some(_) => 
+                self.show_line(line, editor_state, cursor_state, render_state),
+            None => self.jump_to_line(
+                line, editor_state, cursor_state, render_state),
+        }
+
+        self.column = column;
+        cursor_state.last_column = false;
+    }
+
+    /// Move the cursor to the begining of the document `line` (clamped),
+    /// scrolling so the line ends up at the center of the terminal when
+    /// possible
+    pub fn jump_to_line(
         &mut self,
+        line: usize,
         EditorState { rows, doc_lines, .. }: &EditorState,
-        CursorState { scroll_y, .. }: &mut CursorState,
-        RenderState { modif_all, last_cursor, .. }: &mut RenderState
+        CursorState { viewport, last_column, .. }: &mut CursorState,
+        RenderState { modif_all, .. }: &mut RenderState
     ) {
-        // Special case the cursor is at the bottom of the
-        // terminal, scroll if possible
-        if self.row == *rows - 1 {
-            if *scroll_y < *doc_lines - *rows {
-                *modif_all = true;
+        let line = usize::min(line, doc_lines.saturating_sub(1));
+        *modif_all = true;
+        *last_column = false;
 
-                *scroll_y += 1;
-            }
+        viewport.center_on(line, *rows, *doc_lines);
+        self.row = line - viewport.scroll_y;
+        self.column = 0;
+    }
+
+    /// Put the cursor on the line of the document, the view scrolls the
+    /// least possible to keep the scroll margin around it. Every motion of
+    /// a line or a few goes through here
+    pub fn show_line(
+        &mut self,
+        line: usize,
+        EditorState { rows, doc_lines, scroll_margin, .. }: &EditorState,
+        CursorState { viewport, .. }: &mut CursorState,
+        RenderState { modif_all, last_cursor, .. }: &mut RenderState
+    ) {
+        if viewport.keep_inside(line, *rows, *scroll_margin, *doc_lines) {
+            *modif_all = true;
         } else {
-            // Special case of empty file
-            if *scroll_y != 0 || *doc_lines != 0 {
-                // Normal move down
-                if self.row < *doc_lines - *scroll_y - 1 {
-                    *last_cursor = Some(*self);
+            *last_cursor = Some(*self);
+        }
+        self.row = line - viewport.scroll_y;
+    }
 
-                    self.row += 1;
-                }
-            }
+    /// Move up by a single unit if possible on the file, alse update the
+    /// state for the refresh
+    pub fn move_up(
+        &mut self,
+        editor_state: &EditorState,
+        cursor_state: &mut CursorState,
+        render_state: &mut RenderState,
+    ) {
+        let line = self.line(cursor_state);
+        if line > 0 {
+            self.show_line(line - 1, editor_state, cursor_state, render_state);
+        }
+    }
+
+    /// Move down by a single unit if possible on the file, alse update the
+    /// state for the refresh
+    pub fn move_down(
+        &mut self,
+        editor_state: &EditorState,
+        cursor_state: &mut CursorState,
+        render_state: &mut RenderState
+    ) {
+        // Nothing below the last line
+        let line = self.line(cursor_state);
+        if line + 1 >= editor_state.doc_lines {
+            return;
         }
+        self.show_line(line + 1, editor_state, cursor_state, render_state);
     }
 
     /// Move the scroll up by an entire page leaving the cursor on its position
     pub fn page_up(
         &mut self,
-        EditorState { rows, .. }: &EditorState,
-        CursorState { scroll_y, .. }: &mut CursorState,
+        EditorState { rows, doc_lines, .. }: &EditorState,
+        CursorState { viewport, .. }: &mut CursorState,
         RenderState { modif_all, last_cursor, .. }: &mut RenderState
     ) {
         // Normal page up
-        if scroll_y.checked_sub(*rows).is_some() {
+        if viewport.scroll_y >= *rows {
             *modif_all = true;
-            *scroll_y -= *rows;
+            viewport.scroll_by(-(*rows as isize), *doc_lines);
 
         // Special case you only can get to the top of 
         // the terminal
         } else {
-            *last_cursor = Some(*self);            
-
-            *scroll_y = 0;
+            *last_cursor = Some(*self);
+            if viewport.scroll_y > 0 {
+                *modif_all = true;
+                viewport.scroll_y = 0;
+            }
             self.row = 0;
         }
     }
@@ -273,80 +410,235 @@ impl Cursor {
     pub fn page_down(
         &mut self,
         EditorState { rows, doc_lines, .. }: &EditorState,
-        CursorState { scroll_y, .. }: &mut CursorState,
+        CursorState { viewport, .. }: &mut CursorState,
         RenderState { modif_all, last_cursor, .. }: &mut RenderState
     ) {
-        if *scroll_y + *rows <= *doc_lines {
+        if viewport.scroll_y + *rows <= *doc_lines {
             *modif_all = true;
+            viewport.scroll_by(*rows as isize, *doc_lines);
 
-            *scroll_y += *rows;
+            // The last page may be shorter than the terminal
+            let last_row = doc_lines.saturating_sub(viewport.scroll_y + 1);
+            self.row = usize::min(self.row, last_row);
         } else {
             *last_cursor = Some(*self);
 
-            self.row = (doc_lines % rows)
-                .checked_sub(1)
-                .unwrap_or(0);
+            self.row = doc_lines.saturating_sub(viewport.scroll_y + 1);
         }
     }
 
-    pub fn scroll_down(
+    /// Move to the row above or below when the lines are wrapped, the rows
+    /// of a long line are visited one by one keeping the column on the row
+    pub fn move_visual(
         &mut self,
-        EditorState { rows, doc_lines, .. }: &EditorState,
-        CursorState { scroll_y, .. }: &mut CursorState,
-        RenderState { modif_all, .. }: &mut RenderState
+        down: bool,
+        doc: &Document,
+        EditorState { columns, tab_width, .. }: &EditorState,
+        cursor_state: &mut CursorState,
+        RenderState { modif_all, last_cursor, .. }: &mut RenderState
     ) {
-        // Special case of empty file
-        if *scroll_y != 0 || *doc_lines != 0 {
-            // Normal scroll down
-            if self.row < doc_lines - *scroll_y - 1 {
-                *modif_all = true;
+        let line = self.line(cursor_state);
+        let text = match doc.buffer.line(line) {
+            Some(text) => text,
+            None => return,
+        };
+        let segments = wrap::segments(text, *columns, *tab_width);
+        let segment = wrap::segment_of(&segments, self.column);
+        let (start, _) = segments[segment];
+        let (start, column) = 
+            wrap::columns_of(text, (start, self.column), *tab_width);
+        let column = column - start;
 
-                *scroll_y += 1;
-                if self.row != 0 {
-                    self.row -= 1;
-                }
-            }
+        let (line, segment) = if down && segment + 1 < segments.len() {
+            (line, segment + 1)
+        } else if down && line + 1 < doc.buffer.len() {
+            (line + 1, 0)
+        } else if !down && segment > 0 {
+            (line, segment - 1)
+        } else if !down && line > 0 {
+            let above = &doc.buffer[line - 1];
+            (line - 1, wrap::segments(above, *columns, *tab_width).len() - 1)
+        } else {
+            return;
+        };
+
+        let text = &doc.buffer[line];
+        let segments = wrap::segments(text, *columns, *tab_width);
+        let last = segment + 1 == segments.len();
+        *last_cursor = Some(*self);
+        self.column = wrap::byte_at(
+            text, segments[segment], column, last, *tab_width);
+
+        // Going up from the first line shown scrolls, going down is left to
+        // `scroll_to_wrapped`
+        let viewport = &mut cursor_state.viewport;
+        if line < viewport.scroll_y {
+            viewport.scroll_y = line;
+            *modif_all = true;
         }
+        self.row = line - viewport.scroll_y;
+        cursor_state.last_column = false;
     }
 
-    pub fn scroll_up(
+    /// Scroll down until the row of the cursor is shown when the lines are
+    /// wrapped, the lines above can take more rows than the terminal has
+    /// even when the line of the cursor is not far from the first one shown
+    pub fn scroll_to_wrapped(
         &mut self,
-        EditorState { rows , .. }: &EditorState,
-        CursorState { scroll_y, .. }: &mut CursorState,
+        doc: &Document,
+        EditorState { rows, columns, tab_width, .. }: &EditorState,
+        cursor_state: &mut CursorState,
         RenderState { modif_all, .. }: &mut RenderState
-    ) { 
-        // Special case cursor at the top of the terminal, so
-        // try to scroll (if possible)
-        if self.row == 0 {
-            beep!(1);
-            if *scroll_y > 0 {
-                *modif_all = true;
+    ) {
+        let line = self.line(cursor_state);
+        let viewport = &mut cursor_state.viewport;
+        while viewport.scroll_y < line && Layout::rows_until(doc, 
+                viewport.scroll_y, line, self.column, *columns, *tab_width) 
+                    > *rows {
+            viewport.scroll_y += 1;
+            *modif_all = true;
+        }
+        self.row = line - viewport.scroll_y;
+    }
 
-                *scroll_y -= 1;
-                self.row += 1;
-            }
+    /// Scroll horizontally so the cells of the grapheme under the cursor are
+    /// shown, the wrapped lines are always shown from their first column
+    pub fn scroll_horizontally(
+        &self,
+        doc: &Document,
+        EditorState { columns, window, tab_width, .. }: &EditorState,
+        cursor_state: &mut CursorState,
+        RenderState { modif_all, .. }: &mut RenderState
+    ) {
+        if window.wrap {
+            *modif_all |= std::mem::take(&mut cursor_state.viewport.scroll_x) 
+                != 0;
+            return;
+        }
+
+        let line = doc.buffer.line(self.line(cursor_state)).unwrap_or("");
+        let column = unicode::column_of(line, self.column, *tab_width);
+        let start = unicode::floor_grapheme(line, self.column);
+        let grapheme = &line[start..unicode::next_grapheme(line, start)];
+        let width = unicode::grapheme_width(grapheme, column, *tab_width);
+
+        let viewport = &mut cursor_state.viewport;
+        let moved = viewport.ensure_column_visible(
+                column + width.saturating_sub(1), *columns)
+            | viewport.ensure_column_visible(column, *columns);
+        *modif_all |= moved;
+    }
+
+    /// Scroll the view `lines` down, or up when negative, the cursor stays
+    /// on its line of the document while it's visible and is kept at the
+    /// nearest edge of the view otherwise
+    pub fn scroll(
+        &mut self,
+        lines: isize,
+        EditorState { rows, doc_lines, scroll_margin, .. }: &EditorState,
+        CursorState { viewport, .. }: &mut CursorState,
+        RenderState { modif_all, bell, .. }: &mut RenderState
+    ) {
+        let line = viewport.scroll_y + self.row;
+        if viewport.scroll_by(lines, *doc_lines) == 0 {
+            *bell = true;
+            return;
+        }
+        *modif_all = true;
+
+        // The cursor is dragged by the view, out of its margin too
+        let (first, last) = 
+            viewport.inner_lines(*rows, *scroll_margin, *doc_lines);
+        self.row = line.clamp(first, last).saturating_sub(viewport.scroll_y);
+    }
+
+    /// Scroll half a page down, or up, and move the cursor as many lines so
+    /// it keeps its row while the view can follow
+    pub fn half_page(
+        &mut self,
+        up: bool,
+        EditorState { rows, doc_lines, .. }: &EditorState,
+        CursorState { viewport, .. }: &mut CursorState,
+        RenderState { modif_all, last_cursor, bell, .. }: &mut RenderState
+    ) {
+        let half = usize::max(1, *rows / 2) as isize;
+        let lines = if up { -half } else { half };
+        let line = viewport.scroll_y + self.row;
+        let target = line
+            .saturating_add_signed(lines)
+            .min(doc_lines.saturating_sub(1));
+        if target == line {
+            *bell = true;
+            return;
+        }
 
-        // Normal up
-        } else if *scroll_y >= 0 {
+        *last_cursor = Some(*self);
+        if viewport.scroll_by(lines, *doc_lines) != 0 {
             *modif_all = true;
-            
+        }
+        self.row = target - viewport.scroll_y;
+    }
+}
 
-            *scroll_y -= 1;
-            if self.row != *rows - 1 {
-                self.row += 1;
-            }  
-        } else {
-            /*
-            beep!(0);
-            dbg!(scroll_y);
-            panic!();
-            */
+/// The modes of the modal editing, like the ones of vi
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Mode {
+    /// The keys are commands, it's where the modal editing starts
+    #[default]
+    Normal,
+
+    /// The keys type the text like without the modal editing
+    Insert,
+
+    /// The motions extend the selection from where it started
+    Visual,
+}
+
+impl Mode {
+    /// The name shown on the status bar
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Normal => "NORMAL",
+            Self::Insert => "INSERT",
+            Self::Visual => "VISUAL",
         }
     }
 }
 
-// TODO: Use async like a real castellanoleonés
+/// The modal editing, off unless the `modal` option is set. The commands
+/// are typed a key at a time: a count and then the command, some of them
+/// of two keys like `dd` or `gg`
+#[derive(Debug, Clone, Default)]
+pub struct ModalState {
+    pub enabled: bool,
+    pub mode: Mode,
+
+    /// The count typed before the command
+    count: Option<usize>,
+
+    /// The first key of a command of two keys
+    pending: Option<char>,
+}
+
+impl ModalState {
+    /// Forget the count and the first key typed
+    fn reset(&mut self) {
+        self.count = None;
+        self.pending = None;
+    }
+}
+
+/// Bytes of whitespace at the start of the line, its indentation
+fn leading_whitespace(line: &str) -> usize {
+    line.bytes()
+        .take_while(|byte| byte.is_ascii_whitespace())
+        .count()
+}
+
+/// Do the work the editor was woken for, the events of the terminal go to
+/// what takes the input
 pub fn process_keypress(
+    wake: Wake,
     doc: &mut Option<Document>,
     cursor: &mut Cursor,
     editor_state: &mut EditorState,
@@ -354,343 +646,6090 @@ pub fn process_keypress(
     render_state: &mut RenderState,
 ) -> Result<()> {
     // Extract the size of the working buffer and update the editor state
-    let (columns, rows) = terminal::size()?;
-    let rows = (rows - 2) as usize;
-    let columns = (columns - 4) as usize;
-    editor_state.rows = rows;
-    editor_state.columns = columns;
-
-    if let Ok(true) = poll(Duration::from_millis(50)) {
-        if let Ok(ref event) = read() {
-            match event {
-                Event::Key(KeyEvent {
-                    code: KeyCode::Char('q'),
-                    ..
-                }) => editor_state.running = false,
-                Event::Key(KeyEvent {
-                    code: KeyCode::Up,
-                    modifiers
-                }) => {
-                    // Page up
-                    if modifiers.contains(KeyModifiers::SHIFT) {
-                        cursor.page_up(
-                            editor_state, cursor_state, render_state);
-
-                    // Normal Up: go to previous line, to the same column 
-                    // or closest to end of line
-                    } else {
-                        cursor.move_up(
-                            cursor_state, 
-                            render_state);
-                    }
+    editor_state.update_size();
 
-                    // Adjust the move up on the file to the proper column
-                    if let Some(doc) = doc {
-                        cursor.adjust_column_vertical(
-                            &doc, 
-                            *modifiers, 
-                            cursor_state);
-                    } else {
-                        cursor.row = 0;
-                    }
-                }
-                Event::Key(KeyEvent {
-                    code: KeyCode::Down,
-                    modifiers
-                }) => {
-                    // Page down
-                    if modifiers.contains(KeyModifiers::SHIFT) {
-                        cursor.page_down(
-                            &editor_state,
-                            cursor_state,
-                            render_state);
+    // The output of the program running is taken whatever the events, at
+    // its own rate
+    stream_output(editor_state, render_state);
 
-                    // Normal Down: go to next line, to the same column or
-                    // closest to end of line
-                    } else {
-                        cursor.move_down(
-                            editor_state, 
-                            cursor_state, 
-                            render_state);
-                    }
+    // The spinner turns while something runs on the background
+    let tasks = background_tasks(editor_state);
+    if editor_state.spinner.update(tasks) {
+        render_state.modif_status = true;
+    }
 
-                    // Adjust the move down on the file to the proper column
-                    if let Some(doc) = doc {
-                        cursor.adjust_column_vertical(
-                            &doc,
-                            *modifiers,
-                            cursor_state);
-                    } else {
-                        cursor.row = 0;
-                    }
-                }
-                Event::Key(KeyEvent {
-                    code: KeyCode::Right,
-                    modifiers
-                }) => {
-                    if let Some(doc) = doc {
-                        // Get a reference to the line the cursor is at on
-                        // the document, needed to get the maximum column or 
-                        // for the simple word advance
-                        let curr_line = 
-                            &doc.inner_lines[cursor_state.scroll_y + cursor.row];
-                        let max_col = curr_line.len()
-                                .checked_sub(1).unwrap_or(0);
-
-                        // Bounds check
-                        if cursor.row == 
-                                editor_state.doc_lines -
-                                    cursor_state.scroll_y - 1 {
-                            return Ok(());
-                        }
+    // The directories indexed since the last wake are watched too
+    if let Some(index) = &editor_state.index {
+        for dir in index.take_unwatched_dirs() {
+            editor_state.watcher.watch(&dir);
+        }
+    }
 
-                        // This applies to all word movements, if at the end of
-                        // line do a Normal down
-                        if cursor.column == max_col {
-                            render_state.last_cursor = Some(*cursor);
-
-                            // Normal move down
-                            cursor.move_down(
-                                editor_state,
-                                cursor_state, 
-                                render_state);
-
-                            // Adjust the move down on the file to the proper
-                            // column
-                            cursor.adjust_column_start(
-                                &doc, 
-                                cursor_state);
-                            return Ok(());
-                        }
+    match wake {
+        Wake::Input(ref event) => {
+            editor_state.latency.event_received();
 
-                        render_state.last_cursor = Some(*cursor);
-                        // Simple word movement (until next whitespace)
-                        if modifiers.contains(KeyModifiers::CONTROL) {
-                            match curr_line.as_bytes()[cursor.column] {
-                                b' ' => {
-                                    let mut new_col = cursor.column;
-                                    while new_col <= max_col && 
-                                        curr_line
-                                            .as_bytes()[new_col] 
-                                            == b' ' {
-                                                new_col += 1;
-                                    }
-
-                                    cursor.column = 
-                                        usize::min(max_col, new_col);
-                                },
-                                _ => {
-                                    let mut new_col = cursor.column;
-                                    while new_col < max_col &&
-                                          curr_line
-                                              .as_bytes()[new_col]
-                                                    != b' ' {
-                                        new_col += 1;
-                                    }
-                                    while new_col < max_col &&
-                                          curr_line
-                                              .as_bytes()[new_col]
-                                                    == b' ' {
-                                        new_col += 1;
-                                    }
-
-                                    cursor.column = 
-                                        usize::min(max_col, new_col);
-                                }
-                            }
-
-                        // Normal cursor movement 
-                        } else {
-                            cursor.column = 
-                                usize::min(max_col, cursor.column + 1);
-                        }
+            // The frames without changes are skipped, but any event may
+            // move the cursor so at least it's put in its place
+            if editor_state.panel.is_none() {
+                render_state.last_cursor.get_or_insert(*cursor);
+            }
+
+            // The motion of the mouse only feeds the hover
+            if let Event::Mouse(MouseEvent {
+                kind: MouseEventKind::Moved,
+                column,
+                row,
+                ..
+            }) = event {
+                if editor_state.hover.moved(*column, *row) {
+                    render_state.modif_all = true;
+                }
+                return Ok(());
+            }
+
+            // The presses over the popup and the bars are theirs, they
+            // don't reach the document
+            if let Event::Mouse(mouse) = event {
+                if process_widget_mouse(
+                        mouse,
+                        doc, 
+                        cursor, 
+                        editor_state, 
+                        cursor_state, 
+                        render_state)? {
+                    return Ok(());
+                }
+            }
 
-                        // Needed to handle the case last movement was at end
-                        // of line and you go up/down and need to still be at
-                        // the end of line
-                        if cursor.column == max_col {
-                            cursor_state.last_column = true;
+            // Leaving the terminal saves the documents when asked, the
+            // bars are dimmed while it's away. Coming back runs the hooks
+            // attached to the focus
+            match event {
+                Event::FocusLost => {
+                    set_focused(false, editor_state, render_state);
+                    autosave(doc, cursor, editor_state, cursor_state, 
+                        render_state);
+                    return Ok(());
+                }
+                Event::FocusGained => {
+                    set_focused(true, editor_state, render_state);
+                    if let Some(doc) = doc.as_mut().filter(|_|
+                            editor_state.hooks.is_attached(HookEvent::Focus)) {
+                        if let Err(err) =
+                                editor_state.hooks.fire(HookEvent::Focus, doc) {
+                            editor_state.message
+                                .error(format!("Error: {}", err));
                         }
+                        cursor.clamp_to_document(doc, cursor_state,
+                            render_state);
+                        render_state.modif_all = true;
+                        render_state.modif_status = true;
                     }
-                } 
-                Event::Key(KeyEvent {
-                    code: KeyCode::Left,
-                    modifiers
-                }) => {
-                    // Every movement to the left means no more end of line
-                    cursor_state.last_column = false;
+                    return Ok(());
+                }
+                _ => {}
+            }
 
-                    if let Some(doc) = doc {
-                        // This applies to all word movements, if at the end of
-                        // line just try going to the next
-                        if cursor.column == 0 {
-                            // Normal move up
-                            cursor.move_up(
-                                cursor_state, 
-                                render_state);
-
-                            // Adjust the move down on the file to the proper
-                            // column
-                            if cursor.row != 0 {
-                                cursor.adjust_column_end(
-                                    &doc,
-                                    cursor_state);
-                            }
-
-                            return Ok(());
-                        }
+            // Any other event hides the popup
+            if editor_state.hover.hide() {
+                render_state.modif_all = true;
+            }
 
-                        // Get a reference to the line the cursor is at on the
-                        // document
-                        let curr_line = 
-                            &doc.inner_lines[cursor_state.scroll_y + cursor.row];
+            // The command line takes all the input until run or cancelled
+            if editor_state.command_line.is_some() {
+                return process_command_line_event(
+                    event,
+                    doc,
+                    cursor,
+                    editor_state,
+                    cursor_state,
+                    render_state);
+            }
 
-                        // Bounds check
-                        if cursor.row 
-                                == editor_state.doc_lines -
-                                        cursor_state.scroll_y - 1 {
-                            return Ok(());
-                        }
+            // So does the prompt of the minibuffer
+            if editor_state.prompt.is_some() {
+                return process_prompt_event(
+                    event,
+                    doc,
+                    cursor,
+                    editor_state,
+                    cursor_state,
+                    render_state);
+            }
 
-                        render_state.last_cursor = Some(*cursor);
+            // The passphrase prompt takes all the input until typed or
+            // cancelled
+            if editor_state.passphrase.is_some() {
+                return process_passphrase_event(
+                    event,
+                    doc,
+                    cursor,
+                    editor_state,
+                    cursor_state,
+                    render_state);
+            }
 
-                        // Simple word movement (until next whitespace)
-                        if modifiers.contains(KeyModifiers::CONTROL) {
-                            match curr_line.as_bytes()[cursor.column] {
-                                b' ' => {
-                                    let mut new_col = cursor.column;
-                                    while new_col != 0 && 
-                                          curr_line
-                                              .as_bytes()[new_col] 
-                                                    == b' ' {
-                                        new_col -= 1;
-                                    }
-
-                                    cursor.column = new_col;
-                                },
-                                _ => {
-                                    let mut new_col = cursor.column;
-                                    while new_col != 0 &&
-                                          curr_line
-                                              .as_bytes()[new_col]
-                                                    != b' ' {
-                                        new_col -= 1;
-                                    }
-                                    while new_col != 0 && 
-                                          curr_line
-                                              .as_bytes()[new_col] 
-                                                    == b' ' {
-                                        new_col -= 1;
-                                    }
-
-                                    cursor.column = new_col;
-                                }
-                            }
-
-                        // Normal cursor movement
-                        } else {
-                            cursor.column = usize::max(0, cursor.column
-                                .checked_sub(1).unwrap_or(0));
-                        }
-                    }
+            // The search prompt takes all the input until closed, the keys
+            // it doesn't use close it and do what they do
+            if editor_state.search.is_some() {
+                let used = process_search_event(
+                    event, doc, cursor, editor_state, cursor_state, render_state);
+                if used {
+                    return Ok(());
                 }
+            }
 
-                // Handle scroll up/down
-                Event::Mouse(MouseEvent {
-                    kind: MouseEventKind::ScrollUp,
-                    modifiers,
-                    ..
-                }) => {
-                    // Page up
-                    if modifiers.contains(KeyModifiers::SHIFT) {
-                        cursor.page_up(
-                            editor_state, 
-                            cursor_state, 
-                            render_state);
+            // So does the replace
+            if editor_state.replace.is_some() {
+                process_replace_event(
+                    event, doc, cursor, editor_state, cursor_state, render_state);
+                return Ok(());
+            }
 
-                    // Scroll Up
-                    } else {
-                        cursor.scroll_up(
-                            editor_state,
-                            cursor_state,
-                            render_state);
-                    }
+            // An open panel takes all the input until closed
+            if editor_state.panel.is_some() {
+                return process_panel_event(
+                    event,
+                    doc,
+                    cursor,
+                    editor_state,
+                    cursor_state,
+                    render_state);
+            }
 
-                    // Adjust the move up on the file to the proper column
-                    if let Some(doc) = doc {
-                        cursor.adjust_column_vertical(
-                            &doc, 
-                            *modifiers,
-                            cursor_state);
-                    } else {
-                        cursor.row = 0;
+            // The focused file tree takes the keys that go through it, the
+            // rest do what they do
+            if editor_state.tree.as_ref().is_some_and(|tree| tree.focused) {
+                if let Event::Key(key) = event {
+                    if process_tree_key(key, doc, cursor, editor_state, 
+                            cursor_state, render_state)? {
+                        return Ok(());
                     }
                 }
-                Event::Mouse(MouseEvent {
-                    kind: MouseEventKind::ScrollDown,
-                    modifiers,
-                    ..
-                }) => {
-                    // Page down
-                    if modifiers.contains(KeyModifiers::SHIFT) {
-                        cursor.page_down(
-                            editor_state,
-                            cursor_state,
-                            render_state);
+            }
 
-                    // Scroll Down
-                    } else {
-                        cursor.scroll_down(
-                            editor_state,
-                            cursor_state,
-                            render_state);
-                    }
+            // The pager has its own keys, none of them modifies the document
+            if editor_state.pager {
+                return process_pager_event(
+                    event, doc, cursor, editor_state, cursor_state, render_state);
+            }
 
-                    // Adjust the move down on the file to the proper 
-                    // column
-                    if let Some(doc) = doc {
-                        cursor.adjust_column_vertical(
-                            &doc, 
-                            *modifiers, 
-                            cursor_state);
-                    } else {
-                        cursor.row = 0;
-                    }
+            // The popup of the completion takes the keys that choose a
+            // word, the rest close it and do what they do
+            let completing = editor_state.completion.is_some();
+            if completing && process_completion_event(
+                    event, doc, cursor, editor_state, cursor_state, 
+                    render_state) {
+                return Ok(());
+            }
+
+            // The plugins see the keys before the editor does
+            if let (Some(doc), Event::Key(key)) = (doc.as_mut(), event) {
+                if editor_state.plugins.key(key, doc)? {
+                    render_state.modif_all = true;
+                    return Ok(());
                 }
-                Event::Mouse(MouseEvent {
-                    kind: MouseEventKind::Up(_),
-                    row,
-                    column,
-                    ..
-                }) => {
-                    render_state.last_cursor = Some(*cursor);
+            }
 
-                    // Translate the terminal coords to buffer coords
-                    let row = usize::min(*row as usize, 
-                        (editor_state.doc_lines % editor_state.rows)
-                        .checked_sub(1).unwrap_or(0));
-                    let column = usize::min(
-                                    column.checked_sub(4).unwrap_or(0) as usize,
-                                    editor_state.columns);
+            // The modal editing takes the keys of its commands, the rest do
+            // what they do without it
+            if let (true, Event::Key(key)) =
+                    (editor_state.modal.enabled, event) {
+                if process_modal_key(
+                        key, doc, cursor, editor_state, cursor_state,
+                        render_state)? {
+                    return Ok(());
+                }
+            }
 
-                    render_state.last_cursor = Some(*cursor);
+            let action = Action::from_event(event, editor_state);
+            if let Some(action) = action {
+                execute(
+                    action, doc, cursor, editor_state, cursor_state, 
+                    render_state)?;
+            }
 
-                    cursor.row = row;
-                    cursor.column = column;
+            // Typing more of the word goes on completing it
+            if let (true, Some(Action::InsertChar(_)), Some(doc)) = 
+                    (completing, action, doc.as_ref()) {
+                complete_word(doc, cursor, editor_state, cursor_state);
+            }
+        }
+        Wake::FilesChanged(paths) => 
+            notify_changed_files(paths, doc, editor_state, render_state),
+        Wake::Remote(paths) => open_remote_files(
+            paths, doc, cursor, editor_state, cursor_state, render_state),
 
-                    if let Some(doc) = doc {
-                        cursor.adjust_column_random(
-                            &doc, 
-                            cursor_state);
-                    } else {
-                        cursor.row = 0;
-                        cursor.column = 0;
-                    }
-                }
-                _ => {}
+        // The output was already taken
+        Wake::Output => {}
+        Wake::Git => apply_git_answers(editor_state, render_state),
+        Wake::Lsp => apply_lsp_replies(
+            doc, cursor, editor_state, cursor_state, render_state)?,
+        Wake::Timer => {
+            if let Some((column, row)) = editor_state.autoscroll.due() {
+                execute(Action::DragTo { row, column }, doc, cursor, 
+                    editor_state, cursor_state, render_state)?;
             }
+            show_hover(doc, editor_state, cursor_state, render_state);
         }
     }
 
     Ok(())
 }
+
+/// Give the mouse event to the widget under it, returns if it was used. Only
+/// the presses and the wheel are routed, the drags and the releases follow
+/// what was started, like a selection or the scrubbing of the scrollbar
+fn process_widget_mouse(
+    mouse: &MouseEvent,
+    doc: &mut Option<Document>,
+    cursor: &mut Cursor,
+    editor_state: &mut EditorState,
+    cursor_state: &mut CursorState,
+    render_state: &mut RenderState,
+) -> Result<bool> {
+    let MouseEvent { kind, column, row, .. } = *mouse;
+    if !matches!(kind, MouseEventKind::Down(_) 
+            | MouseEventKind::ScrollUp 
+            | MouseEventKind::ScrollDown) {
+        return Ok(false);
+    }
+
+    // The prompts keep the focus while open
+    let prompt_open = editor_state.command_line.is_some()
+        || editor_state.passphrase.is_some()
+        || editor_state.search.is_some()
+        || editor_state.replace.is_some()
+        || editor_state.prompt.is_some();
+
+    match mouse::target(column, row, doc, editor_state) {
+        // Clicking the popup closes it
+        Target::Popup => {
+            editor_state.hover.hide();
+            render_state.modif_all = true;
+        }
+        Target::TabsBar(Some(idx)) 
+                if kind == MouseEventKind::Down(MouseButton::Left) 
+                    && !prompt_open => {
+            execute(Action::SwitchTab(idx), doc, cursor, editor_state, 
+                cursor_state, render_state)?;
+        }
+        Target::Tree(entry) if !prompt_open => process_tree_mouse(
+            kind, entry, doc, cursor, editor_state, cursor_state, 
+            render_state)?,
+        Target::TabsBar(_) | Target::StatusBar | Target::Tree(_) => {}
+
+        // Clicking the document takes the focus from the tree
+        Target::Document => {
+            if let Some(tree) = editor_state.tree.as_mut() {
+                if tree.focused && matches!(kind, MouseEventKind::Down(_)) {
+                    tree.focused = false;
+                    render_state.modif_all = true;
+                }
+            }
+            return Ok(false);
+        }
+        Target::PanelTitle | Target::PanelRow(_) => return Ok(false),
+    }
+
+    Ok(true)
+}
+
+/// The presses on the file tree focus it and open the entry under them, a
+/// file on a buffer or a directory in place. The wheel scrolls it
+fn process_tree_mouse(
+    kind: MouseEventKind,
+    entry: Option<usize>,
+    doc: &mut Option<Document>,
+    cursor: &mut Cursor,
+    editor_state: &mut EditorState,
+    cursor_state: &mut CursorState,
+    render_state: &mut RenderState,
+) -> Result<()> {
+    let rows = editor_state.rows;
+    let tree = match editor_state.tree.as_mut() {
+        Some(tree) => tree,
+        None => return Ok(()),
+    };
+    render_state.modif_all = true;
+
+    match (kind, entry) {
+        (MouseEventKind::ScrollUp, _) => tree.scroll_by(-3, rows),
+        (MouseEventKind::ScrollDown, _) => tree.scroll_by(3, rows),
+        (MouseEventKind::Down(MouseButton::Left), Some(idx)) => {
+            tree.focused = true;
+            tree.select(idx, rows);
+            open_tree_entry(
+                doc, cursor, editor_state, cursor_state, render_state);
+        }
+        _ => tree.focused = true,
+    }
+
+    Ok(())
+}
+
+/// Handle the keys of the focused file tree: the arrows go through it,
+/// Enter and Right open the selected entry and Left collapses it. Esc gives
+/// the focus back to the document. Returns if the key was used, the keys
+/// with Ctrl or Alt that it doesn't know do what they do
+fn process_tree_key(
+    key: &KeyEvent,
+    doc: &mut Option<Document>,
+    cursor: &mut Cursor,
+    editor_state: &mut EditorState,
+    cursor_state: &mut CursorState,
+    render_state: &mut RenderState,
+) -> Result<bool> {
+    let rows = editor_state.rows;
+    let tree = match editor_state.tree.as_mut() {
+        Some(tree) => tree,
+        None => return Ok(false),
+    };
+
+    match key.code {
+        KeyCode::Up => tree.move_by(-1, rows),
+        KeyCode::Down => tree.move_by(1, rows),
+        KeyCode::PageUp => tree.move_by(-(rows as isize), rows),
+        KeyCode::PageDown => tree.move_by(rows as isize, rows),
+        KeyCode::Home => tree.select(0, rows),
+        KeyCode::End => tree.select(usize::MAX, rows),
+        KeyCode::Left => {
+            if let Err(err) = tree.collapse(rows) {
+                editor_state.message.error(format!("Error: {}", err));
+            }
+        }
+        KeyCode::Enter | KeyCode::Right => open_tree_entry(
+            doc, cursor, editor_state, cursor_state, render_state),
+        KeyCode::Esc => tree.focused = false,
+        _ if key.modifiers.intersects(
+            KeyModifiers::CONTROL | KeyModifiers::ALT) => return Ok(false),
+
+        // The text typed would go to a document that isn't seen
+        _ => {}
+    }
+    render_state.modif_all = true;
+    render_state.modif_status = true;
+
+    Ok(true)
+}
+
+/// Open the selected entry of the file tree: a directory is expanded or
+/// collapsed and a file is opened on a buffer, which takes the focus
+fn open_tree_entry(
+    doc: &mut Option<Document>,
+    cursor: &mut Cursor,
+    editor_state: &mut EditorState,
+    cursor_state: &mut CursorState,
+    render_state: &mut RenderState,
+) {
+    let tree = match editor_state.tree.as_mut() {
+        Some(tree) => tree,
+        None => return,
+    };
+    let result = match tree.toggle(tree.selected) {
+        Ok(Some(path)) => {
+            tree.focused = false;
+            open_buffer(
+                &path, doc, cursor, editor_state, cursor_state, render_state)
+        }
+        Ok(None) => Ok(()),
+        Err(err) => Err(err),
+    };
+    if let Err(err) = result {
+        editor_state.message.error(format!("Error: {}", err));
+    }
+}
+
+/// Do the action on the editor, every action of the keys and the mouse
+/// goes through here
+pub fn execute(
+    action: Action,
+    doc: &mut Option<Document>,
+    cursor: &mut Cursor,
+    editor_state: &mut EditorState,
+    cursor_state: &mut CursorState,
+    render_state: &mut RenderState,
+) -> Result<()> {
+    // Nothing changes a read-only document
+    let read_only = doc.as_ref().map(|doc| doc.read_only).unwrap_or(false);
+    if read_only && action.modifies() {
+        editor_state.message.show("The document is read-only");
+        render_state.modif_status = true;
+        return Ok(());
+    }
+
+    // Any action brings the cursor parked by the wheel into the view, the
+    // wheel parks it again
+    let parked = cursor_state.parked.take();
+
+    // The edits and the simple motions are done at every cursor, the other
+    // actions leave only the main one
+    if !cursor_state.cursors.is_empty() {
+        match action {
+            _ if action.at_every_cursor() => return at_every_cursor(
+                action, doc, cursor, editor_state, cursor_state, 
+                render_state),
+            Action::AddCursor { .. } | Action::AddCursorAtMatch 
+                | Action::EndDrag => {}
+            _ => {
+                cursor_state.cursors.clear();
+                render_state.modif_all = true;
+            }
+        }
+    }
+
+    // The presses close in time on the same cell are double and triple
+    // clicks, the drags don't count
+    let clicks = match action {
+        Action::Click { row, column } => editor_state.clicks.press(column, row),
+        _ => 0,
+    };
+
+    // Only the char typed right after an accent composes with it
+    let accent = editor_state.compose_accent.take();
+
+    // With a selection the deletions remove it instead of a char
+    if let (Action::DeleteBackward | Action::DeleteForward, Some(doc)) = 
+            (action, doc.as_mut()) {
+        if delete_selection(doc, cursor, editor_state, cursor_state, 
+                render_state) {
+            return Ok(());
+        }
+    }
+
+    // The motions with shift and the drags extend the selection from where
+    // the cursor was, the other motions and the edits drop it
+    let action = match action {
+        Action::Select(motion) => {
+            start_selection(doc, cursor, cursor_state, render_state);
+            Action::MoveCursor(motion)
+        }
+        Action::DragTo { row, column } => {
+            start_selection(doc, cursor, cursor_state, render_state);
+            scroll_drag(row, column, cursor, editor_state, cursor_state, 
+                render_state);
+            Action::Click { row, column }
+        }
+        // The clipboard and the indentation work with the selection
+        Action::Cut | Action::Paste | Action::Indent | Action::Dedent => 
+            action,
+        Action::MoveCursor(_) | Action::Click { .. } | Action::GoToLine(_) => {
+            drop_selection(cursor_state, render_state);
+            action
+        }
+        _ if action.modifies() => {
+            drop_selection(cursor_state, render_state);
+            action
+        }
+        _ => action,
+    };
+
+    // Where the cursor was before a big motion can be gone back to
+    if let (true, Some(doc)) = (action.jumps(), doc.as_ref()) {
+        editor_state.jumps.record(location(doc, cursor, cursor_state));
+    }
+
+    // With the lines wrapped the rows and the lines are different motions,
+    // which one is the default is an option
+    let action = match action {
+        Action::MoveCursor(motion) => 
+            Action::MoveCursor(wrapped_motion(motion, editor_state)),
+        _ => action,
+    };
+
+    match action {
+        Action::Quit => {
+            editor_state.quitting = Some(Vec::new());
+            quit(doc, cursor, editor_state, cursor_state, render_state)?;
+        }
+
+        // Undo and redo the edits of the document
+        Action::Undo | Action::Redo => {
+            if let Some(doc) = doc {
+                undo_redo(action == Action::Undo, doc, cursor, 
+                    editor_state, cursor_state, render_state);
+            }
+        }
+
+        // Write the document to its file
+        Action::Save => {
+            // The changes of another program are not lost without asking,
+            // the scratch buffers ask where to be saved first
+            if doc.as_ref().is_some_and(|doc| doc.kind == DocKind::Scratch) {
+                editor_state.prompt =
+                    Some(Prompt::new(PromptKind::SaveAs, "Save as: ", ""));
+                render_state.modif_status = true;
+            } else if let Some(doc) = doc.as_ref()
+                    .filter(|doc| doc.changed_on_disk()) {
+                editor_state.prompt = Some(Prompt::new(PromptKind::Overwrite, 
+                    &format!("{} changed on disk, overwrite it? (y/n) ",
+                        doc.path.display()), ""));
+                render_state.modif_status = true;
+            } else if let Some(dir) = doc.as_ref()
+                    .and_then(|doc| fileops::missing_dir(&doc.path)) {
+                editor_state.prompt = Some(Prompt::new(PromptKind::CreateDir, 
+                    &format!("{} doesn't exist, create it? (y/n) ",
+                        dir.display()), ""));
+                render_state.modif_status = true;
+            } else if let Some(doc) = doc {
+                let result = save_document(doc, None, false, editor_state);
+                match result {
+                    Ok(message) => {
+                        editor_state.message.show(message);
+                        show_refreshed_preview(doc, editor_state);
+                    }
+                    Err(err) => editor_state.message
+                        .error(format!("Could not save: {}", err)),
+                }
+                // The marks of the changed lines are gone from the gutter
+                render_state.modif_all = true;
+                cursor.clamp_to_document(doc, cursor_state, render_state);
+            }
+        }
+        Action::GoToLinePrompt => {
+            if doc.is_some() {
+                editor_state.prompt = Some(
+                    Prompt::new(PromptKind::GoToLine, "Go to line: ", ""));
+            }
+        }
+        Action::SaveAs => {
+            if let Some(doc) = doc {
+                editor_state.prompt = Some(Prompt::new(
+                    PromptKind::SaveAs, "Save as: ", &save_as_path(doc)));
+            }
+        }
+
+        // Start searching from the cursor
+        Action::Search => {
+            if let Some(doc) = doc {
+                let origin = cursor.position(doc, cursor_state);
+                editor_state.search = Some(Search::new(
+                    origin,
+                    cursor_state.viewport.scroll_y,
+                    editor_state.search_options));
+                render_state.modif_status = true;
+            }
+        }
+
+        // Replace the matches of a pattern from the cursor
+        Action::Replace => {
+            if doc.is_some() {
+                editor_state.replace = Some(Replace::new(false));
+                render_state.modif_status = true;
+            }
+        }
+
+        // More cursors, the edits are done at all of them
+        Action::AddCursor { up } => {
+            if let Some(doc) = doc {
+                add_cursor_vertically(
+                    up, doc, cursor, editor_state, cursor_state, render_state);
+            }
+        }
+        Action::AddCursorAtMatch => {
+            if let Some(doc) = doc {
+                add_cursor_at_match(
+                    doc, cursor, editor_state, cursor_state, render_state);
+            }
+        }
+
+        // The other cursors were already dropped
+        Action::DropCursors => {}
+
+        // Open the command line
+        Action::CommandLine => {
+            editor_state.command_line = Some(CommandLine::default());
+            editor_state.osc.prompt();
+            render_state.modif_status = true;
+        }
+        Action::Grep => {
+            editor_state.command_line = 
+                Some(CommandLine { input: "grep ".to_owned() });
+            editor_state.osc.prompt();
+            render_state.modif_status = true;
+        }
+
+        // Cycle through the positions of the recent edits, back to
+        // older ones or forward again to newer ones
+        Action::OlderEdit | Action::NewerEdit => {
+            if let Some(doc) = doc {
+                let edits = &mut editor_state.edits;
+                let position = if action == Action::OlderEdit {
+                    edits.older(&doc.path)
+                } else {
+                    edits.newer(&doc.path)
+                };
+
+                match position.map(|p| (p.line, p.column)) {
+                    Some((line, column)) => {
+                        let line = usize::min(
+                            line, doc.buffer.len().saturating_sub(1));
+                        cursor.go_to(line, column, editor_state, 
+                            cursor_state, render_state);
+                        cursor.clamp_to_document(
+                            doc, cursor_state, render_state);
+                    }
+                    None => {
+                        editor_state.message.show("No more edits");
+                        render_state.modif_status = true;
+                    }
+                }
+            }
+        }
+
+        // Re-indent the whole document with the `equalprg`
+        Action::Reindent => {
+            if let Some(doc) = doc {
+                let end = doc.buffer.len();
+                let result = reindent_lines(
+                    doc,
+                    0,
+                    end,
+                    cursor,
+                    editor_state,
+                    cursor_state,
+                    render_state);
+                editor_state.message.result(result);
+                render_state.modif_status = true;
+            }
+        }
+
+        // List the errors of the quickfix list
+        Action::ShowErrors => {
+            editor_state.panel = Some(Panel::list(
+                PanelKind::Quickfix,
+                "Errors -- [Enter] jump",
+                editor_state.quickfix.lines()));
+            render_state.modif_all = true;
+        }
+
+        // List the TODO comments of the project, the scan can be cancelled
+        Action::ListTodos => {
+            match todos_panel(editor_state) {
+                Ok(panel) => editor_state.panel = Some(panel),
+                Err(err) =>
+                    editor_state.message.error(format!("Error: {}", err)),
+            }
+            render_state.modif_all = true;
+        }
+
+        Action::Preview => {
+            if let Some(doc) = doc {
+                match preview_panel(doc, None, editor_state) {
+                    Ok(panel) => editor_state.panel = Some(panel),
+                    Err(err) =>
+                        editor_state.message.error(format!("Error: {}", err)),
+                }
+                render_state.modif_all = true;
+            }
+        }
+
+        // Show what changed compared with the file on disk
+        Action::ShowDiff => {
+            if let Some(doc) = doc {
+                editor_state.panel = Some(diff_panel(doc));
+                render_state.modif_all = true;
+            }
+        }
+
+        // Bookmark the current line and start writting its note
+        Action::Bookmark => {
+            if let Some(doc) = doc {
+                let bookmarks = &mut editor_state.bookmarks;
+                let line = cursor.line(cursor_state);
+                let idx = bookmarks.add(&doc.path, line);
+
+                let mut panel = bookmarks_panel(bookmarks);
+                panel.select(idx, editor_state.rows.saturating_sub(1));
+                panel.input = Some(bookmarks.list[idx].note.clone());
+                editor_state.panel = Some(panel);
+                render_state.modif_all = true;
+            }
+        }
+
+        // Print the document, or export it to PDF, with the line numbers
+        // and the highlights asked for
+        Action::Print(target) => {
+            // The printed text would be left unencrypted on the spooler or
+            // the exported file
+            if doc.as_ref().is_some_and(|doc| doc.encryption.is_some()) {
+                editor_state.message
+                    .show("Encrypted documents are not printed");
+                render_state.modif_status = true;
+            } else if let Some(doc) = doc {
+                let EditorState { plugins, highlights, .. } = &*editor_state;
+                let spans = |idx, line: &str| plugins.spans(idx, line)
+                    .into_iter()
+                    .chain(highlights.spans(line))
+                    .collect();
+                let msg = print::print_document(doc, target,
+                        editor_state.print, editor_state.tab_width, spans)
+                    .unwrap_or_else(|err| 
+                        format!("Could not print: {}", err));
+
+                editor_state.panel = Some(Panel::new("Print", vec![msg]));
+                render_state.modif_all = true;
+            }
+        }
+
+        // Toggle the crosshair on the cursor
+        Action::ToggleCrosshair => {
+            editor_state.crosshair = !editor_state.crosshair;
+            render_state.modif_all = true;
+        }
+
+        // Toggle the soft wrap of the current window
+        Action::ToggleWrap => {
+            editor_state.window.wrap = !editor_state.window.wrap;
+            render_state.modif_all = true;
+        }
+        Action::ToggleWhitespace => {
+            editor_state.window.whitespace = !editor_state.window.whitespace;
+            render_state.modif_all = true;
+        }
+
+        // The next mode of the line numbers, the gutter takes its width
+        Action::CycleLineNumbers => {
+            let window = &mut editor_state.window;
+            window.line_numbers = window.line_numbers.next();
+            editor_state.message.show(
+                format!("linenumbers={}", window.line_numbers.name()));
+            render_state.modif_all = true;
+        }
+
+        // Allow or refuse the edits, like the `readonly` command
+        Action::ToggleReadOnly => {
+            if let Some(doc) = doc {
+                doc.read_only = !doc.read_only;
+                let state = if doc.read_only {
+                    "read-only"
+                } else {
+                    "writable"
+                };
+                editor_state.message.show(
+                    format!("The document is {}", state));
+                render_state.modif_status = true;
+                render_state.modif_message = true;
+            }
+        }
+
+        // Toggle the track changes mode of the document, leaving it
+        // accepts the pending deletions
+        Action::ToggleReview => {
+            if let Some(doc) = doc {
+                let review = &mut editor_state.review;
+                if review.is_enabled(&doc.path) {
+                    let position = undo_location(doc, cursor, cursor_state);
+                    let pending = PendingChange::begin(
+                        doc, 0, doc.buffer.len(), position);
+                    review.accept_all(doc);
+                    review.disable(&doc.path);
+                    editor_state.undo.record(doc, 
+                        pending.commit(EditKind::Replace, doc, position));
+                    editor_state.doc_lines = doc.buffer.len();
+                    cursor.clamp_to_document(
+                        doc, cursor_state, render_state);
+                } else {
+                    review.enable(&doc.path);
+                }
+                render_state.modif_all = true;
+                render_state.modif_status = true;
+            }
+        }
+
+        // Delete the current line, or make it a ghost on review mode
+        Action::DeleteLine => {
+            if let Some(doc) = doc {
+                let line = cursor.line(cursor_state);
+                editor_state.edits.record(&doc.path, line, 0);
+                let position = undo_location(doc, cursor, cursor_state);
+                let pending = 
+                    PendingChange::begin(doc, line, 1, position);
+                if editor_state.review.delete_line(doc, line) {
+                    editor_state.undo.record(doc, 
+                        pending.commit(EditKind::Replace, doc, position));
+                    editor_state.doc_lines = doc.buffer.len();
+                    render_state.modif_below = Some(cursor.row);
+                    cursor.clamp_to_document(
+                        doc, cursor_state, render_state);
+                } else {
+                    render_state.modif_row = Some(cursor.row);
+                }
+                render_state.modif_status = true;
+            }
+        }
+
+        Action::DuplicateLine => {
+            if let Some(doc) = doc {
+                duplicate_line(
+                    doc, cursor, editor_state, cursor_state, render_state);
+            }
+        }
+        Action::MoveLine { up } => {
+            if let Some(doc) = doc {
+                move_line(up, doc, cursor, editor_state, cursor_state, 
+                    render_state);
+            }
+        }
+
+        // The selection goes to the clipboard, pasting replaces it
+        Action::Copy | Action::Cut => {
+            let selection = doc.as_ref()
+                .and_then(|doc| cursor.selection(doc, cursor_state))
+                .filter(|selection| !selection.is_empty());
+            if let (Some(doc), Some(selection)) = (doc.as_mut(), selection) {
+                let text = selection.text(doc);
+                let (lines, chars) = selection.extent(doc);
+                let system = editor_state.clipboard.copy(&text);
+                if action == Action::Cut {
+                    delete_selection(
+                        doc, cursor, editor_state, cursor_state, render_state);
+                }
+
+                let verb = if action == Action::Cut { "Cut" } else { "Copied" };
+                let to = if system { "" } else { " to the editor register" };
+                editor_state.message.show(format!(
+                    "{} {} lines, {} chars{}", verb, lines, chars, to));
+            } else {
+                editor_state.message.show("Nothing selected");
+            }
+            render_state.modif_status = true;
+        }
+        // The language server answers later, see `apply_lsp_replies`
+        Action::ShowHover | Action::GoToDefinition => {
+            if let Some(doc) = doc {
+                let line = cursor.line(cursor_state);
+                let column = lsp::utf16_column(
+                    doc.buffer.line(line).unwrap_or(""), cursor.column);
+                let result = match action {
+                    Action::ShowHover => 
+                        editor_state.lsp.hover(doc, line, column),
+                    _ => editor_state.lsp.definition(doc, line, column),
+                };
+                if let Err(err) = result {
+                    editor_state.message.error(format!("Error: {}", err));
+                }
+                render_state.modif_status = true;
+            }
+        }
+        Action::Complete => {
+            if let Some(doc) = doc {
+                complete_word(doc, cursor, editor_state, cursor_state);
+                if editor_state.completion.is_none() {
+                    editor_state.message.show(
+                        "No word completes the one before the cursor".to_owned());
+                }
+                render_state.modif_all = true;
+            }
+        }
+        // The selection done with the mouse is the primary selection, the
+        // middle button pastes it where it's pressed
+        Action::EndDrag => {
+            editor_state.autoscroll.release();
+            let selection = doc.as_ref()
+                .and_then(|doc| cursor.selection(doc, cursor_state))
+                .filter(|selection| !selection.is_empty());
+            if let (Some(doc), Some(selection)) = (doc.as_ref(), selection) {
+                editor_state.clipboard.copy_primary(&selection.text(doc));
+            }
+        }
+        Action::PastePrimary { row, column } => {
+            execute(Action::Click { row, column }, doc, cursor, editor_state, 
+                cursor_state, render_state)?;
+            if let Some(doc) = doc {
+                let text = editor_state.clipboard.paste_primary();
+                paste_text(
+                    &text, doc, cursor, editor_state, cursor_state, 
+                    render_state);
+            }
+        }
+        Action::Paste => {
+            if let Some(doc) = doc {
+                let text = editor_state.clipboard.paste();
+                delete_selection(
+                    doc, cursor, editor_state, cursor_state, render_state);
+                paste_text(
+                    &text, doc, cursor, editor_state, cursor_state, 
+                    render_state);
+            }
+        }
+
+        // Accept or reject the deletion of the current line or all of them
+        Action::AcceptDeletion { .. } | Action::RejectDeletion { .. } => {
+            if let Some(doc) = doc {
+                let review = &mut editor_state.review;
+                let line = cursor.line(cursor_state);
+                let position = undo_location(doc, cursor, cursor_state);
+                let pending = PendingChange::begin(
+                    doc, 0, doc.buffer.len(), position);
+                match action {
+                    Action::AcceptDeletion { all: false } => {
+                        review.accept(doc, line);
+                    }
+                    Action::AcceptDeletion { all: true } =>
+                        review.accept_all(doc),
+                    Action::RejectDeletion { all: false } => {
+                        review.reject(&doc.path, line);
+                    }
+                    _ => review.reject_all(&doc.path),
+                }
+                if doc.buffer.len() != editor_state.doc_lines {
+                    editor_state.undo.record(doc, 
+                        pending.commit(EditKind::Replace, doc, position));
+                }
+
+                editor_state.doc_lines = doc.buffer.len();
+                render_state.modif_all = true;
+                render_state.modif_status = true;
+                cursor.clamp_to_document(
+                    doc, cursor_state, render_state);
+            }
+        }
+
+        // Tab pages: open a new one with the current view, close the
+        // current one or go to the tab with that number
+        Action::NewTab => {
+            new_tab(doc, cursor, editor_state, cursor_state, render_state)?;
+        }
+        Action::CloseTab => {
+            close_tab(doc, cursor, editor_state, cursor_state, render_state)?;
+        }
+        Action::SwitchTab(to) => {
+            switch_tab(
+                to, doc, cursor, editor_state, cursor_state, render_state)?;
+        }
+
+        Action::NextLongLine => {
+            let limit = editor_state.max_line_length;
+            if let (Some(doc), Some(limit)) = (doc, limit) {
+                let line = cursor.line(cursor_state);
+                let len = doc.buffer.len();
+                let found = (1..=len)
+                    .map(|offset| (line + offset) % len)
+                    .find(|&idx| text::is_overlong(&doc.buffer[idx], limit));
+                match found {
+                    Some(idx) => {
+                        cursor.go_to(
+                            idx, 0, editor_state, cursor_state, render_state);
+                        render_state.modif_all = true;
+                    }
+                    None => {
+                        editor_state.message.show(format!(
+                            "No line is longer than {} chars", limit));
+                        render_state.modif_status = true;
+                    }
+                }
+            } else if limit.is_none() {
+                editor_state.message.show("There is no maxlinelength set");
+                render_state.modif_status = true;
+            }
+        }
+
+        Action::SetMark => if doc.is_some() {
+            editor_state.prompt = Some(Prompt::new(
+                PromptKind::SetMark, "Mark the line with the key: ", ""));
+        }
+        Action::GoToMark => {
+            editor_state.prompt = Some(Prompt::new(
+                PromptKind::GoToMark, "Go to the mark of the key: ", ""));
+        }
+        Action::JumpBack | Action::JumpForward => if let Some(here) = 
+                doc.as_ref().map(|doc| location(doc, cursor, cursor_state)) {
+            let to = match action {
+                Action::JumpBack => editor_state.jumps.back(here),
+                _ => editor_state.jumps.forward(),
+            };
+            match to {
+                Some(to) => {
+                    let result = go_to_location(
+                        &to.path, to.line, Some(to.column), doc, cursor,
+                        editor_state, cursor_state, render_state);
+                    if let Err(err) = result {
+                        editor_state.message.error(format!("Error: {}", err));
+                    }
+                }
+                None => editor_state.message.show("No more jumps"),
+            }
+            render_state.modif_status = true;
+        }
+
+        Action::JumpToHunk { forward } => if let Some(doc) = doc {
+            let line = cursor.line(cursor_state);
+            match editor_state.changes.hunk(&doc.path, line, forward) {
+                Some(idx) => {
+                    cursor.go_to(
+                        idx, 0, editor_state, cursor_state, render_state);
+                    render_state.modif_all = true;
+                    render_state.modif_status = true;
+                }
+                None => {
+                    editor_state.message.show("There are no changed lines");
+                    render_state.modif_status = true;
+                }
+            }
+        }
+
+        // The file to open is asked on the command line
+        Action::OpenFile => {
+            editor_state.command_line = Some(CommandLine {
+                input: "open ".to_owned(),
+            });
+            editor_state.osc.prompt();
+            render_state.modif_status = true;
+        }
+        Action::FindFile => {
+            let index = editor_state.index.as_ref()
+                .ok_or("the project is not indexed")?;
+            editor_state.panel = Some(Panel::list(
+                PanelKind::Files, 
+                "Files -- [Up/Down] select  [Enter] open  [Esc] cancel",
+                index.matching("")));
+            editor_state.prompt = 
+                Some(Prompt::new(PromptKind::FindFile, "Find file: ", ""));
+            render_state.modif_all = true;
+            render_state.modif_status = true;
+        }
+        Action::OpenUnderCursor => {
+            if let Err(err) = open_under_cursor(
+                    doc, cursor, editor_state, cursor_state, render_state) {
+                editor_state.message.error(format!("Error: {}", err));
+            }
+            render_state.modif_status = true;
+        }
+        Action::FileTree => {
+            match editor_state.tree.as_mut() {
+                // Shown again it has the files changed meanwhile
+                Some(tree) if !tree.visible || !tree.focused => {
+                    tree.visible = true;
+                    tree.focused = true;
+                    if let Err(err) = tree.refresh() {
+                        editor_state.message.error(format!("Error: {}", err));
+                    }
+                }
+                Some(tree) => {
+                    tree.visible = false;
+                    tree.focused = false;
+                }
+                None => match FileTree::new(".") {
+                    Ok(tree) => editor_state.tree = Some(tree),
+                    Err(err) => editor_state.message
+                        .error(format!("Error: {}", err)),
+                },
+            }
+            render_state.modif_all = true;
+            render_state.modif_status = true;
+        }
+        Action::CommandPalette => {
+            editor_state.panel = Some(Panel::list(
+                PanelKind::Commands, 
+                "Commands -- [Up/Down] select  [Enter] run  [Esc] cancel",
+                palette_lines("", &editor_state.keymap)));
+            editor_state.prompt = 
+                Some(Prompt::new(PromptKind::Palette, "Command: ", ""));
+            render_state.modif_all = true;
+            render_state.modif_status = true;
+        }
+        Action::Help => {
+            editor_state.panel = Some(
+                Panel::new(
+                    "Keys -- [Up/Down] scroll  [b] buffer  [Esc] close", 
+                    help_lines(&editor_state.keymap))
+                .with_highlight(help_highlight));
+            render_state.modif_all = true;
+            render_state.modif_status = true;
+        }
+        Action::Count => {
+            let result = run_command(
+                "count", doc, cursor, editor_state, cursor_state, render_state);
+            editor_state.message.result(result);
+            render_state.modif_status = true;
+        }
+        Action::NewBuffer => {
+            editor_state.scratches += 1;
+            let name = format!("[scratch {}]", editor_state.scratches);
+            insert_buffer(Document::scratch(name), doc, cursor, editor_state,
+                cursor_state, render_state)?;
+        }
+        Action::NextBuffer | Action::PrevBuffer => {
+            let to = editor_state.buffers.cycle(action == Action::NextBuffer);
+            switch_buffer(
+                to, doc, cursor, editor_state, cursor_state, render_state)?;
+        }
+        Action::AlternateBuffer => match editor_state.buffers.alternate {
+            Some(to) => switch_buffer(
+                to, doc, cursor, editor_state, cursor_state, render_state)?,
+            None => {
+                editor_state.message.show("There is no alternate buffer");
+                render_state.modif_status = true;
+            }
+        },
+        Action::CloseBuffer => {
+            let result = close_buffer(
+                false, doc, cursor, editor_state, cursor_state, render_state);
+            editor_state.message.result(result);
+            render_state.modif_status = true;
+        }
+
+        // List the bookmarks of the project
+        Action::ListBookmarks => {
+            editor_state.panel = 
+                Some(bookmarks_panel(&editor_state.bookmarks));
+            render_state.modif_all = true;
+        }
+
+        // Up and down go to the same column, or the closest to the end of
+        // the line, moved by the change of indentation
+        Action::MoveCursor(motion @ (Motion::Up { exact } 
+                | Motion::PageUp { exact })) => {
+            let from = cursor.line(cursor_state);
+            if let Motion::PageUp { .. } = motion {
+                cursor.page_up(editor_state, cursor_state, render_state);
+            } else {
+                cursor.move_up(editor_state, cursor_state, render_state);
+            }
+
+            // Adjust the move up on the file to the proper column
+            if let Some(doc) = doc {
+                cursor.adjust_column_vertical(
+                    doc, from, exact, editor_state.tab_width, cursor_state);
+            } else {
+                cursor.row = 0;
+            }
+        }
+        Action::MoveCursor(motion @ (Motion::Down { exact } 
+                | Motion::PageDown { exact })) => {
+            let from = cursor.line(cursor_state);
+            if let Motion::PageDown { .. } = motion {
+                cursor.page_down(editor_state, cursor_state, render_state);
+            } else {
+                cursor.move_down(editor_state, cursor_state, render_state);
+            }
+
+            // Adjust the move down on the file to the proper column
+            if let Some(doc) = doc {
+                cursor.adjust_column_vertical(
+                    doc, from, exact, editor_state.tab_width, cursor_state);
+            } else {
+                cursor.row = 0;
+            }
+        }
+        // To the pair of the bracket, on any line
+        Action::MoveCursor(Motion::MatchingBracket) => {
+            if let Some(doc) = doc {
+                let at = cursor.position(doc, cursor_state);
+                match doc.matching_bracket(at) {
+                    Some(pair) => {
+                        render_state.last_cursor = Some(*cursor);
+                        cursor.go_to_position(
+                            pair, editor_state, cursor_state, render_state);
+                        cursor_state.last_column = false;
+                    }
+                    None => {
+                        editor_state.message.show(
+                            "No matching bracket under the cursor");
+                        render_state.modif_status = true;
+                    }
+                }
+            }
+        }
+
+        // The rows of the wrapped lines, the motions are only rows when
+        // wrapped
+        Action::MoveCursor(motion @ (Motion::RowUp | Motion::RowDown)) => {
+            if let Some(doc) = doc {
+                cursor.move_visual(motion == Motion::RowDown, doc, 
+                    editor_state, cursor_state, render_state);
+            }
+        }
+        Action::MoveCursor(motion @ (Motion::Right | Motion::WordRight)) => {
+            if let Some(doc) = doc {
+                // Get a reference to the line the cursor is at on
+                // the document, needed to get the maximum column or 
+                // for the simple word advance
+                let line = cursor.line(cursor_state);
+                let curr_line = doc.buffer.line(line).unwrap_or("");
+                let max_col = unicode::last_grapheme(curr_line);
+
+                // This applies to all word movements, if at the end of
+                // line do a Normal down, the last line has none below
+                if cursor.column >= max_col {
+                    if line + 1 >= doc.buffer.len() {
+                        return Ok(());
+                    }
+                    render_state.last_cursor = Some(*cursor);
+
+                    // Normal move down
+                    cursor.move_down(
+                        editor_state,
+                        cursor_state, 
+                        render_state);
+
+                    // Adjust the move down on the file to the proper
+                    // column
+                    cursor.adjust_column_start(
+                        doc, 
+                        cursor_state);
+                    return Ok(());
+                }
+
+                render_state.last_cursor = Some(*cursor);
+                // To the start of the next word or run of punctuation
+                if motion == Motion::WordRight {
+                    let next = text::word_boundaries(
+                            curr_line, &editor_state.word_chars)
+                        .into_iter()
+                        .find(|start| *start > cursor.column);
+                    cursor.column = next
+                        .map(|start| usize::min(max_col, start))
+                        .unwrap_or(max_col);
+
+                // Normal cursor movement, a whole grapheme
+                } else {
+                    cursor.column = usize::min(max_col, 
+                        unicode::next_grapheme(curr_line, cursor.column));
+                }
+
+                // Needed to handle the case last movement was at end
+                // of line and you go up/down and need to still be at
+                // the end of line
+                if cursor.column == max_col {
+                    cursor_state.last_column = true;
+                }
+            }
+        }
+        Action::MoveCursor(motion @ (Motion::Left | Motion::WordLeft)) => {
+            // Every movement to the left means no more end of line
+            cursor_state.last_column = false;
+
+            if let Some(doc) = doc {
+                // This applies to all word movements, if at the end of
+                // line just try going to the next
+                if cursor.column == 0 {
+                    // Normal move up
+                    let line = cursor.line(cursor_state);
+                    cursor.move_up(
+                        editor_state,
+                        cursor_state, 
+                        render_state);
+
+                    // Adjust the move up on the file to the proper
+                    // column, the first line has none above
+                    if cursor.line(cursor_state) != line {
+                        cursor.adjust_column_end(
+                            doc,
+                            cursor_state);
+                    }
+
+                    return Ok(());
+                }
+
+                // Get a reference to the line the cursor is at on the
+                // document
+                let curr_line = 
+                    &doc.buffer[cursor.line(cursor_state)];
+                render_state.last_cursor = Some(*cursor);
+
+                // To the start of the word or run of punctuation before
+                if motion == Motion::WordLeft {
+                    cursor.column = text::word_boundaries(
+                            curr_line, &editor_state.word_chars)
+                        .into_iter()
+                        .rev()
+                        .find(|start| *start < cursor.column)
+                        .unwrap_or(0);
+
+                // Normal cursor movement, a whole grapheme
+                } else {
+                    cursor.column = 
+                        unicode::prev_grapheme(curr_line, cursor.column);
+                }
+            }
+        }
+        Action::MoveCursor(Motion::LineStart) => {
+            if let Some(doc) = doc {
+                render_state.last_cursor = Some(*cursor);
+                let column = cursor.column;
+                cursor.adjust_column_start(doc, cursor_state);
+                if cursor.column == column {
+                    cursor.column = 0;
+                }
+                cursor_state.last_column = false;
+            }
+        }
+        // Past the last char, so the text typed is appended
+        Action::MoveCursor(Motion::LineEnd) => {
+            if let Some(doc) = doc {
+                render_state.last_cursor = Some(*cursor);
+                cursor.adjust_column_end(doc, cursor_state);
+                cursor.column = doc.buffer.line(cursor.line(cursor_state))
+                    .map_or(0, str::len);
+                cursor_state.last_column = true;
+            }
+        }
+        Action::MoveCursor(Motion::DocumentStart) => {
+            cursor.go_to(0, 0, editor_state, cursor_state, render_state);
+            if let Some(doc) = doc {
+                cursor_state.last_padding = doc.buffer.line(0)
+                    .map_or(0, leading_whitespace);
+            }
+        }
+        Action::MoveCursor(Motion::DocumentEnd) => {
+            if let Some(doc) = doc {
+                let line = doc.buffer.len().saturating_sub(1);
+                cursor.go_to(line, 0, editor_state, cursor_state, render_state);
+                cursor.adjust_column_end(doc, cursor_state);
+                cursor.column = doc.buffer.line(line).map_or(0, str::len);
+                cursor_state.last_column = true;
+            }
+        }
+        Action::GoToLine(line) => {
+            cursor.jump_to_line(line, editor_state, cursor_state, render_state);
+        }
+        Action::Scroll { lines, exact } => {
+            let from = cursor.line(cursor_state);
+            cursor.scroll(lines, editor_state, cursor_state, render_state);
+
+            // Adjust the move on the file to the proper column
+            if let Some(doc) = doc {
+                cursor.adjust_column_vertical(
+                    doc, from, exact, editor_state.tab_width, cursor_state);
+            } else {
+                cursor.row = 0;
+            }
+        }
+        // The view is scrolled like above but the cursor is parked where it
+        // was, the cursor of the view is where it goes if the next action
+        // comes before the view is back on it
+        Action::ScrollView { lines, exact } => {
+            let Some(doc) = doc else {
+                return Ok(());
+            };
+            let parked = parked
+                .unwrap_or_else(|| cursor.position(doc, cursor_state));
+            let from = cursor.line(cursor_state);
+            cursor.scroll(lines, editor_state, cursor_state, render_state);
+            cursor.adjust_column_vertical(
+                doc, from, exact, editor_state.tab_width, cursor_state);
+
+            match cursor_state.viewport.row_of(parked.line, editor_state.rows) {
+                Some(row) => {
+                    cursor.row = row;
+                    cursor.column = parked.byte;
+                    cursor_state.last_padding = doc.buffer.line(parked.line)
+                        .map_or(0, leading_whitespace);
+                }
+                None => cursor_state.parked = Some(parked),
+            }
+        }
+        Action::HalfPage { up } => {
+            let from = cursor.line(cursor_state);
+            cursor.half_page(up, editor_state, cursor_state, render_state);
+            if let Some(doc) = doc {
+                cursor.adjust_column_vertical(
+                    doc, from, false, editor_state.tab_width, cursor_state);
+            }
+        }
+
+        // Editing of the text
+        Action::InsertChar(c) => {
+            if let Some(doc) = doc {
+                if !doc.compose || !compose_char(c, accent, doc, cursor, 
+                        editor_state, cursor_state, render_state) {
+                    type_char(
+                        c, doc, cursor, editor_state, cursor_state, 
+                        render_state);
+                }
+            }
+        }
+        Action::InsertNewline => {
+            if let Some(doc) = doc {
+                insert_newline(
+                    doc, cursor, editor_state, cursor_state, 
+                    render_state);
+            }
+        }
+        Action::Indent | Action::Dedent => {
+            if let Some(doc) = doc {
+                shift_indentation(
+                    action == Action::Dedent, doc, cursor, editor_state, 
+                    cursor_state, render_state);
+            }
+        }
+        Action::DeleteBackward => {
+            if let Some(doc) = doc {
+                delete_backward(
+                    doc, cursor, editor_state, cursor_state, 
+                    render_state);
+            }
+        }
+        Action::DeleteForward => {
+            if let Some(doc) = doc {
+                delete_forward(
+                    doc, cursor, editor_state, cursor_state, 
+                    render_state);
+            }
+        }
+
+        // Scrub dragging the scrollbar
+        Action::ScrubTo(row) => {
+            editor_state.scrubbing = true;
+            scrub(row, doc, cursor, editor_state, cursor_state, 
+                render_state);
+        }
+        Action::EndScrub => {
+            editor_state.scrubbing = false;
+        }
+
+        // Turned into the motions and the clicks above
+        Action::Select(_) | Action::DragTo { .. } => unreachable!(),
+
+        Action::Click { row, column } => {
+            render_state.last_cursor = Some(*cursor);
+
+            // Translate the terminal coords to the glyph under them
+            let screen_row = (row as usize).saturating_sub(editor_state.top);
+            if let Some(doc) = doc {
+                let position = mouse::position_at(
+                    column as usize,
+                    screen_row,
+                    doc,
+                    &cursor_state.viewport,
+                    editor_state);
+                let line = doc.buffer.line(position.line).unwrap_or("");
+                cursor.row = position.line - cursor_state.viewport.scroll_y;
+                cursor.column = position.byte;
+                cursor_state.last_column = false;
+                cursor_state.last_padding = leading_whitespace(line);
+            } else {
+                cursor.row = 0;
+                cursor.column = 0;
+            }
+
+            if let (2.., Some(doc)) = (clicks, doc) {
+                select_clicked(clicks, doc, cursor, editor_state, cursor_state, 
+                    render_state);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Scroll a row when the drag of a selection is past the top or the bottom
+/// of the document area, the timer keeps scrolling while it's held there.
+/// Without bars above the first row is the top edge
+fn scroll_drag(
+    row: u16,
+    column: u16,
+    cursor: &mut Cursor,
+    editor_state: &mut EditorState,
+    cursor_state: &mut CursorState,
+    render_state: &mut RenderState,
+) {
+    let (top, rows) = (editor_state.top, editor_state.rows);
+    let lines = if (row as usize) < top || row == 0 {
+        -1
+    } else if row as usize >= top + rows {
+        1
+    } else {
+        0
+    };
+    editor_state.autoscroll.drag(column, row, lines != 0);
+    if lines != 0 {
+        cursor.scroll(lines, editor_state, cursor_state, render_state);
+    }
+}
+
+/// Select the word of the cursor on a double click and its line on a triple
+/// click
+fn select_clicked(
+    clicks: usize,
+    doc: &Document,
+    cursor: &mut Cursor,
+    editor_state: &EditorState,
+    cursor_state: &mut CursorState,
+    render_state: &mut RenderState,
+) {
+    let line = cursor.line(cursor_state);
+    let text = doc.buffer.line(line).unwrap_or("");
+    if clicks == 2 {
+        let word_chars = &editor_state.word_chars;
+        if let Some(word) = cursors::word_at(text, cursor.column, word_chars) {
+            cursor_state.anchor = Some(doc.position(line, word.start));
+            cursor.column = word.end;
+        }
+    } else {
+        cursor_state.anchor = Some(doc.position(line, 0));
+        if line + 1 < doc.buffer.len() {
+            cursor.go_to(line + 1, 0, editor_state, cursor_state, 
+                render_state);
+        } else {
+            cursor.column = text.len();
+        }
+    }
+    cursor_state.last_column = false;
+    render_state.modif_all = true;
+    render_state.modif_status = true;
+}
+
+/// Anchor the selection where the cursor is, unless already selecting. All
+/// the rows are repainted because the selection grows or shrinks
+fn start_selection(
+    doc: &Option<Document>,
+    cursor: &Cursor,
+    cursor_state: &mut CursorState,
+    render_state: &mut RenderState,
+) {
+    if let Some(doc) = doc {
+        if cursor_state.anchor.is_none() {
+            cursor_state.anchor = Some(cursor.position(doc, cursor_state));
+        }
+        render_state.modif_all = true;
+        render_state.modif_status = true;
+    }
+}
+
+/// Forget the selection, the rows that showed it are repainted
+fn drop_selection(
+    cursor_state: &mut CursorState,
+    render_state: &mut RenderState,
+) {
+    if cursor_state.anchor.take().is_some() {
+        render_state.modif_all = true;
+        render_state.modif_status = true;
+    }
+}
+
+/// Do the action at every cursor, from the last to the first. Only the main
+/// cursor moves the view, and the edits of all of them are a single change
+/// to undo
+fn at_every_cursor(
+    action: Action,
+    doc: &mut Option<Document>,
+    cursor: &mut Cursor,
+    editor_state: &mut EditorState,
+    cursor_state: &mut CursorState,
+    render_state: &mut RenderState,
+) -> Result<()> {
+    let current = match doc {
+        Some(doc) => doc,
+        None => return Ok(()),
+    };
+    let main = cursor.position(current, cursor_state);
+    let mut positions: Vec<_> = cursor_state.cursors.take()
+        .into_iter()
+        .map(|position| (position, false))
+        .chain([(main, true)])
+        .collect();
+    positions.sort_by_key(|(position, main)|
+        (std::cmp::Reverse((position.line, position.byte)), !main));
+    positions.dedup_by_key(|(position, _)| (position.line, position.byte));
+
+    // The edits at a cursor reach at most the lines around it
+    let first = positions.last().map(|(position, _)| position.line)
+        .unwrap_or(0)
+        .saturating_sub(1);
+    let last = positions[0].0.line + 1;
+    let pending = PendingChange::begin(current, first, last - first + 1, 
+        undo_location(current, cursor, cursor_state));
+    let viewport = cursor_state.viewport;
+
+    editor_state.undo.hold();
+    let mut done = Vec::with_capacity(positions.len());
+    for (position, main) in positions {
+        cursor.go_to_position(position, editor_state, cursor_state, 
+            render_state);
+        let result = execute(
+            action, doc, cursor, editor_state, cursor_state, render_state);
+        if let Err(err) = result {
+            editor_state.undo.release();
+            return Err(err);
+        }
+        if let Some(doc) = doc {
+            let position = cursor.position(doc, cursor_state);
+            done.push((FromEnd::new(doc, position), main));
+        }
+    }
+    editor_state.undo.release();
+
+    // The edits made after a cursor moved its position, but not the one
+    // counted from the end
+    let doc = match doc {
+        Some(doc) => doc,
+        None => return Ok(()),
+    };
+    cursor_state.viewport = viewport;
+    for (position, main) in done {
+        let position = position.position(doc);
+        if main {
+            cursor.go_to_position(position, editor_state, cursor_state, 
+                render_state);
+        } else {
+            cursor_state.cursors.add(position);
+        }
+    }
+    cursor.clamp_to_document(doc, cursor_state, render_state);
+
+    if action.modifies() {
+        let location = undo_location(doc, cursor, cursor_state);
+        editor_state.undo.record(
+            doc, pending.commit(EditKind::Replace, doc, location));
+    }
+    render_state.modif_all = true;
+    render_state.modif_status = true;
+
+    Ok(())
+}
+
+/// Add a cursor on the line above the first cursor or below the last one,
+/// at the column of the main cursor on the terminal
+fn add_cursor_vertically(
+    up: bool,
+    doc: &Document,
+    cursor: &Cursor,
+    editor_state: &mut EditorState,
+    cursor_state: &mut CursorState,
+    render_state: &mut RenderState,
+) {
+    let main = cursor.position(doc, cursor_state);
+    let lines = cursor_state.cursors.positions()
+        .iter()
+        .map(|position| position.line)
+        .chain([main.line]);
+    let line = if up {
+        lines.min().and_then(|line| line.checked_sub(1))
+    } else {
+        lines.max().map(|line| line + 1).filter(|line| *line < doc.buffer.len())
+    };
+    let line = match line {
+        Some(line) => line,
+        None => {
+            render_state.bell = true;
+            return;
+        }
+    };
+
+    let tab_width = editor_state.tab_width;
+    let text = doc.buffer.line(main.line).unwrap_or("");
+    let column = unicode::column_of(text, main.byte, tab_width);
+    let text = doc.buffer.line(line).unwrap_or("");
+    let byte = unicode::byte_at_column(text, column, tab_width);
+
+    drop_selection(cursor_state, render_state);
+    cursor_state.cursors.add(doc.position(line, byte));
+    render_state.modif_all = true;
+    render_state.modif_status = true;
+}
+
+/// Add a cursor at the next occurrence of the word of the main cursor, at
+/// the same place of the word. The new cursor is the main one
+fn add_cursor_at_match(
+    doc: &Document,
+    cursor: &mut Cursor,
+    editor_state: &mut EditorState,
+    cursor_state: &mut CursorState,
+    render_state: &mut RenderState,
+) {
+    let main = cursor.position(doc, cursor_state);
+    let line = doc.buffer.line(main.line).unwrap_or("");
+    let word_chars = editor_state.word_chars.clone();
+    let word = match cursors::word_at(line, main.byte, &word_chars) {
+        Some(word) => word,
+        None => {
+            editor_state.message.show("No word under the cursor");
+            render_state.modif_status = true;
+            return;
+        }
+    };
+    let offset = main.byte - word.start;
+    let from = doc.position(main.line, word.end);
+    let word = &line[word];
+
+    let found = cursors::next_occurrence(doc, word, from, &word_chars)
+        .map(|found| doc.position(found.line, found.byte + offset))
+        .filter(|found| *found != main 
+            && !cursor_state.cursors.positions().contains(found));
+    match found {
+        Some(found) => {
+            drop_selection(cursor_state, render_state);
+            cursor_state.cursors.add(main);
+            cursor.go_to_position(found, editor_state, cursor_state, 
+                render_state);
+            render_state.modif_all = true;
+            render_state.modif_status = true;
+        }
+        None => {
+            editor_state.message.show(
+                format!("No more occurrences of `{}`", word));
+            render_state.modif_status = true;
+        }
+    }
+}
+
+/// Delete the selected text, the cursor ends where it started. Returns if
+/// there was something selected
+fn delete_selection(
+    doc: &mut Document,
+    cursor: &mut Cursor,
+    editor_state: &mut EditorState,
+    cursor_state: &mut CursorState,
+    render_state: &mut RenderState,
+) -> bool {
+    let selection = match cursor.selection(doc, cursor_state) {
+        Some(selection) if !selection.is_empty() => selection,
+        _ => return false,
+    };
+    let Selection { start, end } = selection;
+
+    let pending = PendingChange::begin(doc, start.line, 
+        end.line - start.line + 1, undo_location(doc, cursor, cursor_state));
+    selection.delete(doc);
+    editor_state.doc_lines = doc.buffer.len();
+    editor_state.edits.record(&doc.path, start.line, start.byte);
+
+    drop_selection(cursor_state, render_state);
+    cursor.go_to_position(start, editor_state, cursor_state, render_state);
+    render_state.modif_all = true;
+
+    let position = undo_location(doc, cursor, cursor_state);
+    editor_state.undo.record(
+        doc, pending.commit(EditKind::Delete, doc, position));
+
+    true
+}
+
+/// Compose the char typed with the `accent` typed before it at the cursor,
+/// they are replaced by the accented char. An accent typed is inserted as
+/// is, without its pair, waiting for the letter. Returns if the char was
+/// handled
+fn compose_char(
+    c: char,
+    accent: Option<(usize, usize)>,
+    doc: &mut Document,
+    cursor: &mut Cursor,
+    editor_state: &mut EditorState,
+    cursor_state: &mut CursorState,
+    render_state: &mut RenderState,
+) -> bool {
+    let line = cursor.line(cursor_state);
+    let composed = accent
+        .filter(|(accent_line, _)| *accent_line == line)
+        .and_then(|(_, byte)| {
+            let typed = doc.buffer.line(line)?.get(byte..cursor.column)?;
+            let mut chars = typed.chars();
+            match (chars.next(), chars.next()) {
+                (Some(accent), None) => 
+                    Some((byte, compose::compose(accent, c)?)),
+                _ => None,
+            }
+        });
+
+    let pending = PendingChange::begin(
+        doc, line, 1, undo_location(doc, cursor, cursor_state));
+    let column = match composed {
+        Some((byte, composed)) => {
+            doc.delete_char(line, byte);
+            doc.insert_char(line, byte, composed)
+        }
+        None if compose::is_accent(c) => {
+            editor_state.compose_accent = Some((line, cursor.column));
+            doc.insert_char(line, cursor.column, c)
+        }
+        None => return false,
+    };
+
+    render_state.last_cursor = Some(*cursor);
+    render_state.modif_row = Some(cursor.row);
+    render_state.modif_status = true;
+    cursor.column = column;
+    cursor_state.last_column = false;
+    editor_state.doc_lines = doc.buffer.len();
+    editor_state.edits.record(&doc.path, line, column);
+    let position = undo_location(doc, cursor, cursor_state);
+    editor_state.undo.record(
+        doc, pending.commit(EditKind::Insert, doc, position));
+
+    true
+}
+
+/// Type a char at the cursor, the auto-pairs decide if its closing symbol
+/// is inserted too or if the cursor just moves over the next char
+fn type_char(
+    c: char,
+    doc: &mut Document,
+    cursor: &mut Cursor,
+    editor_state: &mut EditorState,
+    cursor_state: &mut CursorState,
+    render_state: &mut RenderState,
+) {
+    let line = cursor.line(cursor_state);
+    let text = doc.buffer.line(line).unwrap_or("");
+    let action = editor_state.pairs.on_insert(
+        doc.filetype(), text, cursor.column, c);
+    let pending = 
+        PendingChange::begin(doc, line, 1, undo_location(doc, cursor, cursor_state));
+
+    let column = match action {
+        PairAction::Skip => cursor.column + c.len_utf8(),
+        PairAction::Insert => doc.insert_char(line, cursor.column, c),
+        PairAction::InsertPair(close) => {
+            let column = doc.insert_char(line, cursor.column, c);
+            doc.insert_char(line, column, close);
+            column
+        }
+    };
+
+    render_state.last_cursor = Some(*cursor);
+    render_state.modif_row = Some(cursor.row);
+    render_state.modif_status = true;
+    cursor.column = column;
+    cursor_state.last_column = false;
+    editor_state.doc_lines = doc.buffer.len();
+    editor_state.edits.record(&doc.path, line, column);
+    if action != PairAction::Skip {
+        let position = undo_location(doc, cursor, cursor_state);
+        editor_state.undo.record(
+            doc, pending.commit(EditKind::Insert, doc, position));
+    }
+
+    if !c.is_whitespace() {
+        auto_wrap(line, doc, cursor, editor_state, cursor_state, render_state);
+    }
+}
+
+/// Wrap the line being typed at the end when writing prose and it gets
+/// longer than the text width
+fn auto_wrap(
+    line: usize,
+    doc: &mut Document,
+    cursor: &mut Cursor,
+    editor_state: &mut EditorState,
+    cursor_state: &mut CursorState,
+    render_state: &mut RenderState,
+) {
+    let prose_settings = &editor_state.prose;
+    if !prose_settings.auto_wrap || !prose::is_prose(doc.filetype()) {
+        return;
+    }
+
+    // Only the typing at the end of the line, wrapping in the middle
+    // would move the text under the cursor
+    let text = &doc.buffer[line];
+    if text.chars().count() <= prose_settings.textwidth 
+            || cursor.column < text.len() {
+        return;
+    }
+
+    let lines = prose::wrap_line(text, prose_settings.textwidth);
+    if lines.len() < 2 {
+        return;
+    }
+    let added = lines.len() - 1;
+    let column = lines[added].len();
+    let pending = 
+        PendingChange::begin(doc, line, 1, undo_location(doc, cursor, cursor_state));
+    doc.replace_lines(line, line + 1, lines);
+
+    editor_state.doc_lines = doc.buffer.len();
+    cursor.go_to(line + added, column, editor_state, cursor_state, render_state);
+    render_state.modif_all = true;
+
+    let position = undo_location(doc, cursor, cursor_state);
+    editor_state.undo.record(
+        doc, pending.commit(EditKind::Replace, doc, position));
+}
+
+/// Split the line at the cursor, the cursor goes to the start of the new
+/// line. With `autoindent` the new line gets the indentation of the split
+/// one, which becomes the padding followed by the vertical motions
+fn insert_newline(
+    doc: &mut Document,
+    cursor: &mut Cursor,
+    editor_state: &mut EditorState,
+    cursor_state: &mut CursorState,
+    render_state: &mut RenderState,
+) {
+    let line = cursor.line(cursor_state);
+    let pending = 
+        PendingChange::begin(doc, line, 1, undo_location(doc, cursor, cursor_state));
+
+    // Splitting inside the indentation keeps the rest of it on the new line
+    let indentation = match doc.buffer.line(line) {
+        Some(text) if editor_state.auto_indent => {
+            let leading = indent::leading(text);
+            leading[..usize::min(leading.len(), cursor.column)].to_owned()
+        }
+        _ => String::new(),
+    };
+    doc.insert_newline(line, cursor.column);
+    if !indentation.is_empty() {
+        doc.insert_text(line + 1, 0, &indentation);
+    }
+    render_state.modif_status = true;
+    editor_state.doc_lines = doc.buffer.len();
+    editor_state.edits.record(&doc.path, line + 1, indentation.len());
+
+    // The lines below the split moved one row down
+    render_state.modif_below = Some(cursor.row);
+    cursor.go_to(line + 1, indentation.len(), 
+        editor_state, cursor_state, render_state);
+    cursor_state.last_padding = indentation.len();
+
+    let position = undo_location(doc, cursor, cursor_state);
+    editor_state.undo.record(
+        doc, pending.commit(EditKind::Split, doc, position));
+}
+
+/// Insert a copy of the line of the cursor below it, the cursor stays on
+/// the original. The rows below move down
+fn duplicate_line(
+    doc: &mut Document,
+    cursor: &mut Cursor,
+    editor_state: &mut EditorState,
+    cursor_state: &mut CursorState,
+    render_state: &mut RenderState,
+) {
+    let line = cursor.line(cursor_state);
+    let position = undo_location(doc, cursor, cursor_state);
+    let pending = PendingChange::begin(doc, line, 1, position);
+    if !doc.duplicate_line(line) {
+        return;
+    }
+    editor_state.doc_lines = doc.buffer.len();
+    editor_state.edits.record(&doc.path, line + 1, cursor.column);
+    editor_state.undo.record(
+        doc, pending.commit(EditKind::Replace, doc, position));
+
+    render_state.modif_below = Some(cursor.row + 1);
+    render_state.modif_status = true;
+}
+
+/// Swap the line of the cursor with the one above or below, the cursor goes
+/// with it. Only the two rows are repainted unless the view scrolls
+fn move_line(
+    up: bool,
+    doc: &mut Document,
+    cursor: &mut Cursor,
+    editor_state: &mut EditorState,
+    cursor_state: &mut CursorState,
+    render_state: &mut RenderState,
+) {
+    let line = cursor.line(cursor_state);
+    let (first, to) = match up {
+        true if line > 0 => (line - 1, line - 1),
+        false if line + 1 < doc.buffer.len() => (line, line + 1),
+        _ => return,
+    };
+
+    let pending = PendingChange::begin(
+        doc, first, 2, undo_location(doc, cursor, cursor_state));
+    doc.swap_lines(first);
+    editor_state.review.lines_swapped(&doc.path, first);
+    editor_state.edits.record(&doc.path, to, cursor.column);
+
+    let row = cursor.row;
+    cursor.go_to(to, cursor.column, editor_state, cursor_state, render_state);
+    let first_row = usize::min(row, cursor.row);
+    render_state.modif_rows = Some((first_row, first_row + 2));
+    render_state.modif_status = true;
+
+    let position = undo_location(doc, cursor, cursor_state);
+    editor_state.undo.record(
+        doc, pending.commit(EditKind::Replace, doc, position));
+}
+
+/// Indent or dedent a level the lines of the selection, or the line of the
+/// cursor. Without a selection indenting inserts a tab at the cursor, or the
+/// spaces up to the next tab stop with `expandtab`. The selection and the
+/// cursor stay on the same text
+fn shift_indentation(
+    dedent: bool,
+    doc: &mut Document,
+    cursor: &mut Cursor,
+    editor_state: &mut EditorState,
+    cursor_state: &mut CursorState,
+    render_state: &mut RenderState,
+) {
+    let line = cursor.line(cursor_state);
+    let selection = cursor.selection(doc, cursor_state)
+        .filter(|selection| !selection.is_empty());
+    let tab_width = editor_state.tab_width;
+    if selection.is_none() && !dedent {
+        let text = if editor_state.expand_tab {
+            let column = unicode::column_of(
+                doc.buffer.line(line).unwrap_or(""), cursor.column, tab_width);
+            " ".repeat(tab_width - column % tab_width)
+        } else {
+            "\t".to_owned()
+        };
+        paste_text(&text, doc, cursor, editor_state, cursor_state, 
+            render_state);
+        return;
+    }
+
+    // The line where the selection ends isn't shifted when none of its
+    // text is selected
+    let (start, end) = match selection {
+        Some(Selection { start, end }) if end.byte == 0 && end.line > start.line
+            => (start.line, end.line),
+        Some(Selection { start, end }) => (start.line, end.line + 1),
+        None => (line, line + 1),
+    };
+
+    let pending = PendingChange::begin(
+        doc, start, end - start, undo_location(doc, cursor, cursor_state));
+    let deltas: Vec<isize> = if dedent {
+        indent::dedent_lines(doc, start, end, tab_width)
+            .into_iter()
+            .map(|removed| -(removed as isize))
+            .collect()
+    } else {
+        let unit = if editor_state.expand_tab {
+            " ".repeat(tab_width)
+        } else {
+            "\t".to_owned()
+        };
+        indent::indent_lines(doc, start, end, &unit)
+            .into_iter()
+            .map(|added| added as isize)
+            .collect()
+    };
+    if deltas.iter().all(|delta| *delta == 0) {
+        return;
+    }
+
+    // Whatever was inside the indentation removed ends at the text
+    let shift = |line: usize, byte: usize| 
+        match line.checked_sub(start).and_then(|idx| deltas.get(idx)) {
+            Some(delta) => byte.saturating_add_signed(*delta),
+            None => byte,
+        };
+    if let Some(anchor) = cursor_state.anchor {
+        cursor_state.anchor = Some(
+            doc.position(anchor.line, shift(anchor.line, anchor.byte)));
+    }
+    let column = shift(line, cursor.column);
+    cursor.go_to(line, column, editor_state, cursor_state, render_state);
+    cursor_state.last_padding = indent::leading(&doc.buffer[line]).len();
+    editor_state.edits.record(&doc.path, line, column);
+    render_state.modif_all = true;
+    render_state.modif_status = true;
+
+    let position = undo_location(doc, cursor, cursor_state);
+    editor_state.undo.record(
+        doc, pending.commit(EditKind::Replace, doc, position));
+}
+
+/// Offer the words of the open documents that complete the word before the
+/// cursor, the ones of the document first. Nothing is offered without a
+/// word or when no word completes it
+fn complete_word(
+    doc: &Document,
+    cursor: &Cursor,
+    editor_state: &mut EditorState,
+    cursor_state: &CursorState,
+) {
+    let line = doc.buffer.line(cursor.line(cursor_state)).unwrap_or("");
+    let column = usize::min(cursor.column, line.len());
+    let prefix = complete::prefix(line, column, &editor_state.word_chars);
+    if prefix.is_empty() {
+        editor_state.completion = None;
+        return;
+    }
+    let others = open_documents(&None, editor_state)
+        .filter(|other| other.path != doc.path);
+    let completion = Completion::new(
+        prefix, std::iter::once(doc).chain(others), &editor_state.word_chars);
+    editor_state.completion = completion;
+}
+
+/// Handle the keys of the completion popup: the arrows choose a word, Tab or
+/// Enter insert what is left of it and Esc closes it. Returns if the key was
+/// taken, the other keys close it too
+fn process_completion_event(
+    event: &Event,
+    doc: &mut Option<Document>,
+    cursor: &mut Cursor,
+    editor_state: &mut EditorState,
+    cursor_state: &mut CursorState,
+    render_state: &mut RenderState,
+) -> bool {
+    let (completion, doc) = match (editor_state.completion.as_mut(), doc) {
+        (Some(completion), Some(doc)) => (completion, doc),
+        _ => return false,
+    };
+    let code = match event {
+        Event::Key(KeyEvent { code, modifiers: KeyModifiers::NONE, .. }) => 
+            *code,
+        _ => KeyCode::Null,
+    };
+    render_state.modif_all = true;
+    match code {
+        KeyCode::Up | KeyCode::Down => {
+            completion.select(code == KeyCode::Down);
+            true
+        }
+        KeyCode::Tab | KeyCode::Enter => {
+            let rest = completion.rest().to_owned();
+            editor_state.completion = None;
+            if doc.read_only {
+                editor_state.message.show("The document is read-only");
+                render_state.modif_status = true;
+                return true;
+            }
+            paste_text(
+                &rest, doc, cursor, editor_state, cursor_state, render_state);
+            true
+        }
+        KeyCode::Esc => {
+            editor_state.completion = None;
+            true
+        }
+        _ => {
+            editor_state.completion = None;
+            false
+        }
+    }
+}
+
+/// Insert the text at the cursor, the cursor ends after it. Only the row of
+/// the cursor is repainted unless the text has more than one line
+fn paste_text(
+    text: &str,
+    doc: &mut Document,
+    cursor: &mut Cursor,
+    editor_state: &mut EditorState,
+    cursor_state: &mut CursorState,
+    render_state: &mut RenderState,
+) {
+    if text.is_empty() {
+        return;
+    }
+
+    let line = cursor.line(cursor_state);
+    let pending = PendingChange::begin(
+        doc, line, 1, undo_location(doc, cursor, cursor_state));
+    let (end_line, end_column) = doc.insert_text(line, cursor.column, text);
+    editor_state.doc_lines = doc.buffer.len();
+    editor_state.edits.record(&doc.path, end_line, end_column);
+
+    render_state.last_cursor = Some(*cursor);
+    let row = cursor.row;
+    cursor.go_to(
+        end_line, end_column, editor_state, cursor_state, render_state);
+    if end_line == line {
+        render_state.modif_row = Some(cursor.row);
+    } else {
+        render_state.modif_below = Some(row);
+    }
+    render_state.modif_status = true;
+
+    let position = undo_location(doc, cursor, cursor_state);
+    editor_state.undo.record(
+        doc, pending.commit(EditKind::Paste, doc, position));
+}
+
+/// Delete the char before the cursor, at the start of the line it's joined
+/// to the previous one. Deleting the opening symbol of an empty pair also
+/// deletes the closing one
+fn delete_backward(
+    doc: &mut Document,
+    cursor: &mut Cursor,
+    editor_state: &mut EditorState,
+    cursor_state: &mut CursorState,
+    render_state: &mut RenderState,
+) {
+    let line = cursor.line(cursor_state);
+    if line >= doc.buffer.len() {
+        return;
+    }
+    render_state.modif_status = true;
+
+    if cursor.column == 0 {
+        if line == 0 {
+            return;
+        }
+
+        let pending = PendingChange::begin(
+            doc, line - 1, 2, undo_location(doc, cursor, cursor_state));
+        if let Some(column) = doc.join_lines(line - 1) {
+            editor_state.doc_lines = doc.buffer.len();
+            editor_state.edits.record(&doc.path, line - 1, column);
+
+            cursor.go_to(
+                line - 1, column, editor_state, cursor_state, render_state);
+
+            // The lines below the join moved one row up
+            render_state.modif_below = Some(cursor.row);
+
+            let position = undo_location(doc, cursor, cursor_state);
+            editor_state.undo.record(
+                doc, pending.commit(EditKind::Join, doc, position));
+        }
+        return;
+    }
+
+    let text = &doc.buffer[line];
+    let column = text::floor_char_boundary(text, cursor.column);
+    let prev = text[..column]
+        .char_indices()
+        .last()
+        .map(|(idx, _)| idx)
+        .unwrap_or(0);
+    let pending = 
+        PendingChange::begin(doc, line, 1, undo_location(doc, cursor, cursor_state));
+    if editor_state.pairs.on_delete(doc.filetype(), text, column) {
+        doc.delete_char(line, column);
+    }
+    doc.delete_char(line, prev);
+
+    render_state.last_cursor = Some(*cursor);
+    render_state.modif_row = Some(cursor.row);
+    cursor.column = prev;
+    cursor_state.last_column = false;
+    editor_state.edits.record(&doc.path, line, prev);
+
+    let position = undo_location(doc, cursor, cursor_state);
+    editor_state.undo.record(
+        doc, pending.commit(EditKind::Delete, doc, position));
+}
+
+/// Delete the char under the cursor, at the end of the line the next one is
+/// joined
+fn delete_forward(
+    doc: &mut Document,
+    cursor: &mut Cursor,
+    editor_state: &mut EditorState,
+    cursor_state: &mut CursorState,
+    render_state: &mut RenderState,
+) {
+    let line = cursor.line(cursor_state);
+    let joins = match doc.buffer.line(line) {
+        Some(text) => cursor.column >= text.len(),
+        None => return,
+    };
+
+    let position = undo_location(doc, cursor, cursor_state);
+    let pending = PendingChange::begin(doc, line, 2, position);
+    if doc.delete_char(line, cursor.column) {
+        render_state.modif_status = true;
+        if joins {
+            editor_state.doc_lines = doc.buffer.len();
+            render_state.modif_below = Some(cursor.row);
+        } else {
+            render_state.modif_row = Some(cursor.row);
+        }
+        editor_state.edits.record(&doc.path, line, cursor.column);
+
+        let kind = if joins { EditKind::Join } else { EditKind::Delete };
+        editor_state.undo.record(
+            doc, pending.commit(kind, doc, position));
+    }
+}
+
+/// Scroll to the part of the document that corresponds to the terminal
+/// `row` on the scrollbar, the cursor keeps its row on the terminal
+fn scrub(
+    row: u16,
+    doc: &Option<Document>,
+    cursor: &mut Cursor,
+    editor_state: &EditorState,
+    cursor_state: &mut CursorState,
+    render_state: &mut RenderState,
+) {
+    let doc = match doc {
+        Some(doc) => doc,
+        None => return,
+    };
+
+    let row = (row as usize).saturating_sub(editor_state.top);
+    let scroll_y = scrollbar::scroll_for_row(
+        row, editor_state.doc_lines, editor_state.rows);
+
+    // Only repaint when the view actually moved
+    if scroll_y != cursor_state.viewport.scroll_y {
+        cursor_state.viewport.scroll_y = scroll_y;
+        render_state.modif_all = true;
+        cursor.clamp_to_document(doc, cursor_state, render_state);
+    }
+}
+
+/// Open the files sent by `pepe --remote` on new buffers, the last one is
+/// shown. A panel open is closed so the file is seen
+fn open_remote_files(
+    paths: Vec<PathBuf>,
+    doc: &mut Option<Document>,
+    cursor: &mut Cursor,
+    editor_state: &mut EditorState,
+    cursor_state: &mut CursorState,
+    render_state: &mut RenderState,
+) {
+    for path in paths {
+        editor_state.panel = None;
+        let result = open_buffer(
+            &path, doc, cursor, editor_state, cursor_state, render_state);
+        match result {
+            Ok(()) => editor_state.message
+                .show(format!("Opened {} remotely", path.display())),
+            Err(err) => editor_state.message.error(format!(
+                "Could not open {}: {}", path.display(), err)),
+        }
+        render_state.modif_all = true;
+        render_state.modif_status = true;
+    }
+}
+
+/// The panel with what the program printed so far, scrolled to the end
+fn output_panel(stream: &OutputStream) -> Panel {
+    // The matches of a search are a list, from the first one
+    if stream.matches {
+        return Panel::list(
+            PanelKind::Matches, stream.title(), stream.lines.clone());
+    }
+
+    let mut panel = Panel::new(stream.title(), stream.lines.clone());
+    panel.kind = PanelKind::Output;
+    panel.scroll = panel.lines.len();
+
+    panel
+}
+
+/// Take what the program running printed, in batches. Its panel is
+/// repainted from the first line that changed and follows the end of the
+/// output while it's scrolled there
+fn stream_output(
+    editor_state: &mut EditorState,
+    render_state: &mut RenderState
+) {
+    let stream = match editor_state.stream.as_mut() {
+        Some(stream) => stream,
+        None => return,
+    };
+    let batch = match stream.take() {
+        Some(batch) => batch,
+        None => return,
+    };
+    // The search that finds nothing exits with an error
+    match batch.finished {
+        Some(_) if stream.matches => editor_state.message.show(format!(
+            "{} matches of `{}`", stream.lines.len(), stream.command)),
+        Some(true) => editor_state.message.show(stream.title()),
+        Some(false) => editor_state.message.error(stream.title()),
+        None => {}
+    }
+
+    let panel = match editor_state.panel.as_mut() {
+        Some(panel) if matches!(panel.kind, 
+            PanelKind::Output | PanelKind::Matches) => panel,
+        _ => return,
+    };
+    let panel_rows = editor_state.rows.saturating_sub(1);
+    let from = panel.lines.len();
+    let following = panel.kind == PanelKind::Output
+        && panel.scroll + panel_rows >= from;
+    panel.title = stream.title();
+    panel.lines.extend(batch.lines);
+
+    // The first match is selected once it arrives, the list doesn't follow
+    // the end of the output
+    if panel.kind == PanelKind::Matches && panel.selected.is_none() {
+        panel.selected = (!panel.lines.is_empty()).then_some(0);
+    }
+
+    let mut damaged = from;
+    let last_scroll = panel.lines.len().saturating_sub(panel_rows);
+    if following && panel.scroll != last_scroll {
+        panel.scroll = last_scroll;
+        damaged = last_scroll;
+    }
+    render_state.modif_panel = Some(render_state.modif_panel
+        .map_or(damaged, |modif| usize::min(modif, damaged)));
+}
+
+/// The names of the tasks running on the background, the ones `cancel`
+/// stops
+fn background_tasks(editor_state: &EditorState) -> Vec<String> {
+    let mut tasks = Vec::new();
+    if editor_state.index.as_ref().is_some_and(|index| !index.is_ready()) {
+        tasks.push("index".to_owned());
+    }
+    if editor_state.stream.as_ref()
+            .is_some_and(|stream| stream.status.is_none()) {
+        tasks.push("run".to_owned());
+    }
+    if editor_state.git.is_busy() {
+        tasks.push("git".to_owned());
+    }
+    if editor_state.lsp.is_busy() {
+        tasks.push("lsp".to_owned());
+    }
+
+    tasks
+}
+
+/// Take what the language servers answered: the diagnostics are drawn, the
+/// hover information is shown on the minibuffer and the definition is
+/// jumped to
+fn apply_lsp_replies(
+    doc: &mut Option<Document>,
+    cursor: &mut Cursor,
+    editor_state: &mut EditorState,
+    cursor_state: &mut CursorState,
+    render_state: &mut RenderState,
+) -> Result<()> {
+    for reply in editor_state.lsp.take_replies() {
+        match reply {
+            Reply::Diagnostics => render_state.modif_all = true,
+            Reply::Hover(text) if text.trim().is_empty() => editor_state
+                .message.show("Nothing is known about it".to_owned()),
+            Reply::Hover(text) => {
+                let text = text.split_whitespace().collect::<Vec<_>>();
+                editor_state.message.show(text.join(" "));
+            }
+            Reply::Definition(None) => editor_state.message
+                .show("The definition was not found".to_owned()),
+            Reply::Definition(Some((path, line, column))) => {
+                let result = jump_to_location(
+                    &path, line, None, 
+                    doc, cursor, editor_state, cursor_state, render_state);
+                if let Err(err) = result {
+                    editor_state.message.error(format!("Error: {}", err));
+                    continue;
+                }
+
+                // The column the server counts is found on the line
+                if let Some(doc) = doc.as_ref()
+                        .filter(|doc| fileops::same_file(&doc.path, &path)) {
+                    let text = doc.buffer.line(line).unwrap_or("");
+                    cursor.column = lsp::byte_of(text, column);
+                    cursor.clamp_to_document(doc, cursor_state, render_state);
+                }
+                render_state.modif_all = true;
+            }
+            Reply::Error(err) => 
+                editor_state.message.error(format!("Error: {}", err)),
+        }
+        render_state.modif_status = true;
+    }
+
+    Ok(())
+}
+
+/// Take what git answered, the version of the files at `HEAD` is the
+/// baseline of their changes
+fn apply_git_answers(
+    editor_state: &mut EditorState,
+    render_state: &mut RenderState,
+) {
+    for answer in editor_state.git.take_answers() {
+        if let Some(head) = answer.head {
+            editor_state.changes.set_baseline(&answer.path, head);
+        }
+    }
+    render_state.modif_all = true;
+    render_state.modif_status = true;
+}
+
+/// Tell about the open files changed by other programs, the directories of
+/// the project index that changed are indexed again. When the file of the
+/// document being edited changed the user is asked what to do with it
+fn notify_changed_files(
+    mut changed: Vec<PathBuf>,
+    doc: &Option<Document>,
+    editor_state: &mut EditorState,
+    render_state: &mut RenderState,
+) {
+    if let Some(index) = &editor_state.index {
+        changed.retain(|path| {
+            if !index.contains_dir(path) {
+                return true;
+            }
+            if !index.rescan(path) {
+                editor_state.watcher.unwatch(path);
+            }
+            false
+        });
+    }
+    if changed.is_empty() {
+        return;
+    }
+
+    let names: Vec<String> = changed
+        .iter()
+        .map(|path| path.display().to_string())
+        .collect();
+    editor_state.message.show(
+        format!("Changed on disk: {}", names.join(", ")));
+    render_state.modif_status = true;
+
+    // Only when nothing else is being asked, the save asks again anyway
+    let current = doc.as_ref()
+        .filter(|doc| doc.narrow.is_none() && changed.contains(&doc.path));
+    if let (Some(doc), None) = (current, &editor_state.prompt) {
+        editor_state.prompt = Some(Prompt::new(PromptKind::Reload, 
+            &format!("{} changed on disk, reload, keep or diff? (r/k/d) ",
+                doc.path.display()), ""));
+    }
+}
+
+/// Remember if the terminal has the focus, the bars are repainted when
+/// their accent changes
+fn set_focused(
+    focused: bool,
+    editor_state: &mut EditorState,
+    render_state: &mut RenderState,
+) {
+    if editor_state.focused != focused && editor_state.dim_unfocused {
+        render_state.modif_all = true;
+        render_state.modif_status = true;
+    }
+    editor_state.focused = focused;
+}
+
+/// Read the file of the document again, the changes made since it was read
+/// can be undone
+fn reload_document(
+    doc: &mut Document,
+    cursor: &mut Cursor,
+    editor_state: &mut EditorState,
+    cursor_state: &mut CursorState,
+    render_state: &mut RenderState,
+) -> Result<String> {
+    let saved = doc.saved_bytes()?;
+
+    let position = undo_location(doc, cursor, cursor_state);
+    let pending = PendingChange::begin(doc, 0, doc.buffer.len(), position);
+    doc.replace_lines(0, doc.buffer.len(), text::split_lines(&saved));
+    editor_state.undo.record(
+        doc, pending.commit(EditKind::Replace, doc, position));
+    doc.mark_reloaded(&saved);
+    editor_state.watcher.watch(&doc.path);
+
+    editor_state.doc_lines = doc.buffer.len();
+    render_state.modif_all = true;
+    render_state.modif_status = true;
+    cursor.clamp_to_document(doc, cursor_state, render_state);
+
+    Ok(format!("Reloaded {} ({} lines)", 
+        doc.path.display(), doc.buffer.len()))
+}
+
+/// Show the popup of the hovered cell once the mouse rested there long
+/// enough
+fn show_hover(
+    doc: &Option<Document>,
+    editor_state: &mut EditorState,
+    cursor_state: &CursorState,
+    render_state: &mut RenderState,
+) {
+    let (column, row) = match editor_state.hover.due() {
+        Some(position) => position,
+        None => return,
+    };
+
+    // Only the lines of the document have something to show
+    let lines = match doc {
+        Some(doc) if editor_state.panel.is_none() => 
+            hover_contents(doc, column, row, editor_state, cursor_state),
+        _ => Vec::new(),
+    };
+
+    if editor_state.hover.show(lines) {
+        render_state.modif_all = true;
+    }
+}
+
+/// What there is to know about the line at the terminal cell: its bookmark
+/// notes, its errors and if it's deleted waiting for review
+fn hover_contents(
+    doc: &Document,
+    _column: u16,
+    row: u16,
+    EditorState { top, rows, bookmarks, review, quickfix, lsp, .. }: &EditorState,
+    CursorState { viewport: Viewport { scroll_y, .. }, .. }: &CursorState,
+) -> Vec<String> {
+    let row = row as usize;
+    if row < *top || row - top >= *rows {
+        return Vec::new();
+    }
+    let line = scroll_y + row - top;
+    if line >= doc.buffer.len() {
+        return Vec::new();
+    }
+
+    let mut lines: Vec<String> = bookmarks.list
+        .iter()
+        .filter(|b| b.path == doc.path && b.line == line)
+        .map(|b| format!("bookmark: {}", b.note))
+        .collect();
+    lines.extend(quickfix.on_line(&doc.path, line)
+        .map(|entry| format!("error: {}", entry.message)));
+    lines.extend(lsp.diagnostics(&doc.path)
+        .iter()
+        .filter(|diagnostic| diagnostic.on_line(line).is_some())
+        .map(|diagnostic| format!("{}: {}",
+            diagnostic.severity, diagnostic.message.replace('\n', " "))));
+    if review.is_ghost(&doc.path, line) {
+        lines.push("deleted, waiting for review (Alt+Y accept, Alt+N reject)"
+            .to_owned());
+    }
+
+    lines
+}
+
+/// Where the cursor is, to be restored by the undo
+fn undo_location(
+    doc: &Document,
+    cursor: &Cursor,
+    cursor_state: &CursorState
+) -> undo::Location {
+    undo::Location {
+        position: cursor.position(doc, cursor_state),
+        scroll_y: cursor_state.viewport.scroll_y,
+    }
+}
+
+/// Undo the last change of the document, or redo the last one undone, the
+/// view goes back to where the cursor was
+fn undo_redo(
+    undo: bool,
+    doc: &mut Document,
+    cursor: &mut Cursor,
+    editor_state: &mut EditorState,
+    cursor_state: &mut CursorState,
+    render_state: &mut RenderState,
+) {
+    // The ghosts of the review point to lines the history doesn't know
+    if editor_state.review.is_enabled(&doc.path) {
+        editor_state.message.show("Undo is not available on review mode");
+        render_state.modif_status = true;
+        return;
+    }
+
+    let location = if undo {
+        editor_state.undo.undo(doc)
+    } else {
+        editor_state.undo.redo(doc)
+    };
+    let location = match location {
+        Some(location) => location,
+        None => {
+            let message = if undo { "Nothing to undo" } else { "Nothing to redo" };
+            editor_state.message.show(message);
+            render_state.modif_status = true;
+            return;
+        }
+    };
+
+    editor_state.doc_lines = doc.buffer.len();
+    let max_scroll = editor_state.doc_lines.saturating_sub(editor_state.rows);
+    cursor_state.viewport.scroll_y = usize::min(location.scroll_y, max_scroll);
+    let position = location.position;
+    cursor.go_to_position(position, editor_state, cursor_state, render_state);
+    editor_state.edits.record(&doc.path, position.line, position.byte);
+
+    render_state.modif_all = true;
+    render_state.modif_status = true;
+}
+
+/// Write the lines edited on a narrowed document back to the lines of its
+/// source, as a single change of the source that can be undone
+fn write_back_narrowed(
+    narrowed: &mut Document,
+    editor_state: &mut EditorState,
+) -> Result<String> {
+    let narrow = narrowed.narrow.as_ref().ok_or("not narrowed")?;
+    let buffers = &mut editor_state.buffers;
+    let idx = buffers.find(&narrow.source, &None)
+        .ok_or_else(|| format!("{} is not open on this tab", 
+            narrow.source.display()))?;
+    let buffer = &mut buffers.list[idx];
+    let source = buffer.doc.as_mut().unwrap();
+
+    let changes = narrow.changes(narrowed, source)?;
+    if let (Some((first, _)), Some((last, _))) = 
+            (changes.first(), changes.last()) {
+        let position = undo_location(source, &buffer.cursor, 
+            &buffer.cursor_state);
+        let pending = 
+            PendingChange::begin(source, *first, last - first + 1, position);
+        for (line, text) in &changes {
+            source.replace_lines(*line, line + 1, vec![text.clone()]);
+            editor_state.edits.record(&source.path, *line, 0);
+        }
+        editor_state.undo.record(source, 
+            pending.commit(EditKind::Replace, source, position));
+    }
+    let message = format!("Wrote {} lines back to {}", 
+        changes.len(), narrow.source.display());
+    narrowed.mark_clean();
+
+    Ok(message)
+}
+
+/// Write the document to its file, or to `path` from now on. Another file
+/// is only overwritten when `force`. Returns the message for the user
+fn save_document(
+    doc: &mut Document,
+    path: Option<&Path>,
+    force: bool,
+    editor_state: &mut EditorState,
+) -> Result<String> {
+    if let Some(path) = path {
+        if path != doc.path && path.exists() && !force {
+            return Err(format!("{} already exists, use saveas! to \
+                overwrite it", path.display()).into());
+        }
+    }
+
+    // A narrowed document has no file, its lines go back to the source
+    if doc.narrow.is_some() {
+        if path.is_some() {
+            return Err("a narrowed view can't be saved as a file".into());
+        }
+        return write_back_narrowed(doc, editor_state);
+    }
+
+    // What is no file is only saved as one
+    if path.is_none() {
+        match doc.kind {
+            DocKind::File => {}
+            DocKind::Scratch => return Err(
+                "the buffer has no file yet, use saveas to give it one".into()),
+            DocKind::Preview => return Err(
+                "the preview has no file, use saveas to write it".into()),
+            DocKind::Placeholder => return Err(
+                "the document is open somewhere else".into()),
+        }
+    }
+
+    // The changes another program made to the file would be lost, so would
+    // the bytes of a binary file that aren't UTF-8
+    let same_file = path.is_none_or(|path| path == doc.path);
+    if same_file && !force && doc.binary {
+        return Err(format!("{} is binary and saving it would change its \
+            bytes, use save! to write it anyway", doc.path.display()).into());
+    }
+    if same_file && !force && doc.changed_on_disk() {
+        return Err(format!("{} changed on disk since it was read, use \
+            save! to overwrite it", doc.path.display()).into());
+    }
+
+    // The directories of a new file are only created when asked to
+    if let Some(dir) = fileops::missing_dir(path.unwrap_or(&doc.path)) {
+        if !force {
+            return Err(format!("the directory {} doesn't exist, use save! \
+                to create it", dir.display()).into());
+        }
+        fileops::create_dir(dir)?;
+    }
+
+    editor_state.hooks.fire(HookEvent::SavePre, doc)?;
+    editor_state.plugins.save(doc)?;
+    let insert_final_newline = 
+        editor_state.final_newline.applies(doc.filetype());
+    let (contents, normalizations) = save::normalize(
+        doc, &editor_state.save_options, insert_final_newline);
+
+    // The lines of a large file still on it were just read, one that is
+    // not UTF-8 makes it binary
+    if same_file && !force && doc.buffer.has_invalid_lines() {
+        doc.binary = true;
+        doc.read_only = true;
+        return Err(format!("{} is binary and saving it would change its \
+            bytes, use save! to write it anyway", doc.path.display()).into());
+    }
+
+    if let Some(path) = path {
+        editor_state.watcher.unwatch(&doc.path);
+        editor_state.undo.rename_file(&doc.path, path);
+        editor_state.review.rename_file(&doc.path, path);
+        doc.path = path.to_owned();
+    }
+    doc.save(&contents)?;
+
+    // Saved as a file it's one from now on, the preview becomes editable
+    if doc.kind == DocKind::Preview {
+        doc.read_only = false;
+    }
+    doc.kind = DocKind::File;
+
+    // The own writes are not external changes, but they change the work
+    // tree of the repository
+    editor_state.watcher.watch(&doc.path);
+    editor_state.git.query(&doc.path, editor_state.events.sender());
+    editor_state.hooks.fire(HookEvent::SavePost, doc)?;
+
+    // A language server that can't be told doesn't make the save fail
+    let _ = editor_state.lsp.saved(doc);
+
+    let mut message = format!("Saved {} ({} lines)", 
+        doc.path.display(), doc.buffer.len());
+
+    // The preview follows what was saved, the save went well anyway
+    let cancel = editor_state.events.cancel_token();
+    if let Some(preview) = editor_state.preview.as_mut()
+            .filter(|preview| preview.path == doc.path) {
+        let _operation = cancel.start();
+        if let Err(err) = preview.refresh(doc, &cancel) {
+            message.push_str(&format!(", the preview failed: {}", err));
+        }
+    }
+    if !normalizations.is_empty() {
+        let normalizations: Vec<String> = normalizations
+            .iter()
+            .map(|normalization| normalization.to_string())
+            .collect();
+        message.push_str(&format!(", {}", normalizations.join(", ")));
+    }
+
+    Ok(message)
+}
+
+/// The path the save as prompt starts with, the names of the documents
+/// that are no files are left out
+fn save_as_path(doc: &Document) -> String {
+    match doc.kind {
+        DocKind::File | DocKind::Placeholder => doc.path.display().to_string(),
+        DocKind::Scratch | DocKind::Preview => String::new(),
+    }
+}
+
+/// Save the documents with changes as the `autosave` option says, the
+/// read-only ones and the ones of the pager are left alone
+fn autosave(
+    doc: &mut Option<Document>,
+    cursor: &mut Cursor,
+    editor_state: &mut EditorState,
+    cursor_state: &mut CursorState,
+    render_state: &mut RenderState,
+) {
+    if editor_state.autosave == AutoSave::Off || editor_state.pager {
+        return;
+    }
+    let needs_save = |doc: &Document|
+        doc.dirty && !doc.read_only && doc.kind == DocKind::File;
+
+    let mut saved = 0;
+    let mut errors = Vec::new();
+    let mut save = |doc: &mut Document, editor_state: &mut EditorState| {
+        match save_document(doc, None, false, editor_state) {
+            Ok(_) => saved += 1,
+            Err(err) => errors.push(
+                format!("{}: {}", doc.path.display(), err)),
+        }
+    };
+
+    if let Some(doc) = doc.as_mut().filter(|doc| needs_save(doc)) {
+        save(doc, editor_state);
+        cursor.clamp_to_document(doc, cursor_state, render_state);
+    }
+
+    // The documents of the other buffers and tabs are taken out while saved
+    if editor_state.autosave == AutoSave::All {
+        let current_tab = editor_state.tabs.current;
+        let tabs = editor_state.tabs.list.len();
+        for tab in 0..tabs {
+            let buffers = if tab == current_tab {
+                editor_state.buffers.list.len()
+            } else {
+                editor_state.tabs.list[tab].buffers.list.len()
+            };
+            for buffer in 0..buffers {
+                let other = stored_document(editor_state, tab, buffer)
+                    .and_then(Option::take);
+                let mut other = match other {
+                    Some(other) => other,
+                    None => continue,
+                };
+                if needs_save(&other) {
+                    save(&mut other, editor_state);
+                }
+                if let Some(slot) = stored_document(editor_state, tab, buffer) {
+                    *slot = Some(other);
+                }
+            }
+        }
+    }
+
+    if !errors.is_empty() {
+        editor_state.message.error(
+            format!("Could not autosave {}", errors.join(", ")));
+    } else if saved > 0 {
+        let plural = if saved == 1 { "" } else { "s" };
+        editor_state.message.show(
+            format!("Autosaved {} document{}", saved, plural));
+    }
+
+    // The marks of the changed lines are gone from the gutter
+    if saved > 0 || !errors.is_empty() {
+        render_state.modif_all = true;
+    }
+}
+
+/// Where the document of the buffer of the tab is kept, `None` for the one
+/// being edited, the editor works directly with it
+fn stored_document(
+    editor_state: &mut EditorState,
+    tab: usize,
+    buffer: usize
+) -> Option<&mut Option<Document>> {
+    if tab == editor_state.tabs.current {
+        let buffers = &mut editor_state.buffers;
+        return (buffer != buffers.current)
+            .then(|| &mut buffers.list[buffer].doc);
+    }
+
+    let tab = &mut editor_state.tabs.list[tab];
+    if buffer == tab.buffers.current {
+        Some(&mut tab.doc)
+    } else {
+        Some(&mut tab.buffers.list[buffer].doc)
+    }
+}
+
+/// Go on quitting: the next document open, on any tab, with changes not
+/// written on disk is shown and the user is asked what to do with them. The
+/// editor stops after the last one
+fn quit(
+    doc: &mut Option<Document>,
+    cursor: &mut Cursor,
+    editor_state: &mut EditorState,
+    cursor_state: &mut CursorState,
+    render_state: &mut RenderState,
+) -> Result<()> {
+    let discarded = editor_state.quitting.take().unwrap_or_default();
+    let tabs = &editor_state.tabs;
+    let unsaved = (0..tabs.list.len()).find_map(|tab| {
+        let buffer = if tab == tabs.current {
+            editor_state.buffers.unsaved(doc, &discarded)
+        } else {
+            let Tab { doc, buffers, .. } = &tabs.list[tab];
+            buffers.unsaved(doc, &discarded)
+        };
+        buffer.map(|buffer| (tab, buffer))
+    });
+    let (tab, buffer) = match unsaved {
+        Some(unsaved) => unsaved,
+        None => {
+            editor_state.running = false;
+            return Ok(());
+        }
+    };
+
+    switch_tab(tab, doc, cursor, editor_state, cursor_state, render_state)?;
+    switch_buffer(buffer, doc, cursor, editor_state, cursor_state, 
+        render_state)?;
+    if let Some(doc) = doc {
+        editor_state.prompt = Some(Prompt::new(PromptKind::Quit, 
+            &format!("{} has unsaved changes, save them? \
+                (y)es, (n)o or (c)ancel ", doc.path.display()), ""));
+        render_state.modif_status = true;
+    }
+    editor_state.quitting = Some(discarded);
+
+    Ok(())
+}
+
+/// Every document open on the buffers of every tab
+fn open_documents<'a>(
+    doc: &'a Option<Document>,
+    editor_state: &'a EditorState
+) -> impl Iterator<Item = &'a Document> {
+    let tabs = &editor_state.tabs;
+    let others = tabs.list
+        .iter()
+        .enumerate()
+        .filter(|(idx, _)| *idx != tabs.current)
+        .flat_map(|(_, tab)| tab.buffers.documents(&tab.doc));
+
+    editor_state.buffers.documents(doc).chain(others)
+}
+
+/// Handle the input on pager mode, the keys are the ones of `less`
+fn process_pager_event(
+    event: &Event,
+    doc: &mut Option<Document>,
+    cursor: &mut Cursor,
+    editor_state: &mut EditorState,
+    cursor_state: &mut CursorState,
+    render_state: &mut RenderState,
+) -> Result<()> {
+    let step = editor_state.wheel_step as isize;
+    let (code, lines) = match event {
+        Event::Key(KeyEvent { code, .. }) => (*code, 1),
+        Event::Mouse(MouseEvent { kind: MouseEventKind::ScrollDown, .. }) => 
+            (KeyCode::Down, step),
+        Event::Mouse(MouseEvent { kind: MouseEventKind::ScrollUp, .. }) => 
+            (KeyCode::Up, step),
+        _ => return Ok(()),
+    };
+    // The keys that move through the document do the actions of the editor
+    let action = match code {
+        KeyCode::Char('q') => Some(Action::Quit),
+        KeyCode::Char(' ') | KeyCode::Char('f') | KeyCode::PageDown =>
+            Some(Action::MoveCursor(Motion::PageDown { exact: true })),
+        KeyCode::Char('b') | KeyCode::PageUp =>
+            Some(Action::MoveCursor(Motion::PageUp { exact: true })),
+        KeyCode::Char('j') | KeyCode::Enter | KeyCode::Down =>
+            Some(Action::Scroll { lines, exact: true }),
+        KeyCode::Char('k') | KeyCode::Up
+                if cursor_state.viewport.scroll_y > 0 =>
+            Some(Action::Scroll { lines: -lines, exact: true }),
+        KeyCode::Char('d') => Some(Action::HalfPage { up: false }),
+        KeyCode::Char('u') => Some(Action::HalfPage { up: true }),
+        KeyCode::Char('g') | KeyCode::Home => Some(Action::GoToLine(0)),
+        KeyCode::Char('G') | KeyCode::End => doc.as_ref()
+            .map(|doc| Action::GoToLine(doc.buffer.len().saturating_sub(1))),
+        _ => None,
+    };
+    if let Some(action) = action {
+        execute(action, doc, cursor, editor_state, cursor_state, render_state)?;
+    }
+
+    let doc = match doc {
+        Some(doc) => doc,
+        None => return Ok(()),
+    };
+
+    match code {
+        // Searches are the same of the editor, `n`/`N` go through the
+        // matches of the last one
+        KeyCode::Char('/') => {
+            let origin = cursor.position(doc, cursor_state);
+            editor_state.search = Some(Search::new(
+                origin,
+                cursor_state.viewport.scroll_y,
+                editor_state.search_options));
+            render_state.modif_status = true;
+        }
+        KeyCode::Char(c @ ('n' | 'N')) => {
+            let position = cursor.position(doc, cursor_state);
+            let found = editor_state.last_search
+                .as_mut()
+                .and_then(|search| search.step(c == 'n', position));
+            match found {
+                Some(found) => cursor.go_to_position(
+                    found.start_position(doc), 
+                    editor_state, 
+                    cursor_state, 
+                    render_state),
+                None => {
+                    editor_state.message.show("Pattern not found");
+                    render_state.modif_status = true;
+                }
+            }
+        }
+        _ => {}
+    }
+
+    cursor.clamp_to_document(doc, cursor_state, render_state);
+
+    Ok(())
+}
+
+/// Handle a key of the modal editing, returns if it was used. On the insert
+/// mode only the Esc is, and the keys with Ctrl or Alt are always left to
+/// the key bindings
+fn process_modal_key(
+    key: &KeyEvent,
+    doc: &mut Option<Document>,
+    cursor: &mut Cursor,
+    editor_state: &mut EditorState,
+    cursor_state: &mut CursorState,
+    render_state: &mut RenderState,
+) -> Result<bool> {
+    if key.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) {
+        return Ok(false);
+    }
+    let mode = editor_state.modal.mode;
+    let c = match (mode, key.code) {
+        (Mode::Insert, KeyCode::Esc) => {
+            set_mode(Mode::Normal, editor_state, render_state);
+            return Ok(true);
+        }
+        (Mode::Insert, _) => return Ok(false),
+        (_, KeyCode::Esc) => {
+            editor_state.modal.reset();
+            drop_selection(cursor_state, render_state);
+            set_mode(Mode::Normal, editor_state, render_state);
+            return Ok(true);
+        }
+        (_, KeyCode::Char(c)) => c,
+
+        // The keys that edit on the insert mode move or delete a char
+        (_, KeyCode::Enter) => 'j',
+        (_, KeyCode::Backspace) => 'h',
+        (_, KeyCode::Delete) => 'x',
+        _ => return Ok(false),
+    };
+
+    // The digits make the count, a 0 that doesn't follow one is a motion
+    let modal = &mut editor_state.modal;
+    if let Some(digit) = c.to_digit(10)
+            .filter(|digit| *digit > 0 || modal.count.is_some()) {
+        let count = modal.count.unwrap_or(0);
+        modal.count = Some(
+            count.saturating_mul(10).saturating_add(digit as usize));
+        return Ok(true);
+    }
+    let count = modal.count.take();
+    let first = modal.pending.take();
+    let visual = mode == Mode::Visual;
+
+    // The commands of two keys wait for the second one with the count
+    if let (None, 'g') | (None, 'd') = (first, c) {
+        if c == 'g' || !visual {
+            modal.pending = Some(c);
+            modal.count = count;
+            return Ok(true);
+        }
+    }
+
+    let line = cursor.line(cursor_state);
+    let line_len = doc.as_ref()
+        .and_then(|doc| doc.buffer.line(line))
+        .map_or(0, str::len);
+    let motion = |motion| if visual {
+        Action::Select(motion)
+    } else {
+        Action::MoveCursor(motion)
+    };
+    let last_line = editor_state.doc_lines.saturating_sub(1);
+
+    let action = match (first, c) {
+        (Some('d'), 'd') => Action::DeleteLine,
+
+        // The count is the line to go to, from 1
+        (Some('g'), 'g') => 
+            Action::GoToLine(count.map_or(0, |count| count - 1)),
+        (None, 'G') => 
+            Action::GoToLine(count.map_or(last_line, |count| count - 1)),
+
+        (Some('g'), 'f') => Action::OpenUnderCursor,
+        (Some('g'), 'j') => motion(Motion::RowDown),
+        (Some('g'), 'k') => motion(Motion::RowUp),
+
+        // A wrong second key forgets the first one
+        (Some(_), _) => return Ok(true),
+
+        (None, 'h') => motion(Motion::Left),
+        (None, 'j') => motion(Motion::Down { exact: false }),
+        (None, 'k') => motion(Motion::Up { exact: false }),
+        (None, 'l') => motion(Motion::Right),
+        (None, 'w') => motion(Motion::WordRight),
+        (None, 'b') => motion(Motion::WordLeft),
+        (None, '%') => motion(Motion::MatchingBracket),
+        (None, 'u') => Action::Undo,
+        (None, 'p') => Action::Paste,
+        (None, ':') => Action::CommandLine,
+        (None, '/') => Action::Search,
+
+        // The start and the end of the line, the selection follows them
+        (None, '0' | '$') => {
+            if visual {
+                start_selection(doc, cursor, cursor_state, render_state);
+            }
+            let column = if c == '0' { 0 } else { line_len };
+            cursor.go_to(
+                line, column, editor_state, cursor_state, render_state);
+            return Ok(true);
+        }
+
+        // The selection is copied or deleted and the mode is left
+        (None, 'y') if visual => {
+            execute(Action::Copy, doc, cursor, editor_state, cursor_state,
+                render_state)?;
+            drop_selection(cursor_state, render_state);
+            set_mode(Mode::Normal, editor_state, render_state);
+            return Ok(true);
+        }
+        (None, 'd' | 'x') if visual => {
+            execute(Action::DeleteForward, doc, cursor, editor_state, 
+                cursor_state, render_state)?;
+            set_mode(Mode::Normal, editor_state, render_state);
+            return Ok(true);
+        }
+        (None, 'v') if visual => {
+            drop_selection(cursor_state, render_state);
+            set_mode(Mode::Normal, editor_state, render_state);
+            return Ok(true);
+        }
+        (None, 'v') => {
+            start_selection(doc, cursor, cursor_state, render_state);
+            set_mode(Mode::Visual, editor_state, render_state);
+            return Ok(true);
+        }
+
+        // The chars are deleted up to the end of the line, not joining the
+        // next one
+        (None, 'x') => {
+            for _ in 0..count.unwrap_or(1) {
+                let line_len = doc.as_ref()
+                    .and_then(|doc| doc.buffer.line(line))
+                    .map_or(0, str::len);
+                if cursor.column >= line_len {
+                    break;
+                }
+                execute(Action::DeleteForward, doc, cursor, editor_state,
+                    cursor_state, render_state)?;
+            }
+            return Ok(true);
+        }
+
+        // Typing starts at the cursor, after it, at the end of the line or
+        // on a new line below
+        (None, 'i' | 'a' | 'A' | 'o') if !visual => {
+            if let Some(doc) = doc.as_ref() {
+                let column = match c {
+                    'i' => cursor.column,
+                    'a' => usize::min(line_len,
+                        unicode::next_grapheme(
+                            doc.buffer.line(line).unwrap_or(""),
+                            cursor.column)),
+                    _ => line_len,
+                };
+                cursor.go_to(
+                    line, column, editor_state, cursor_state, render_state);
+            }
+            if c == 'o' {
+                execute(Action::InsertNewline, doc, cursor, editor_state,
+                    cursor_state, render_state)?;
+            }
+            set_mode(Mode::Insert, editor_state, render_state);
+            return Ok(true);
+        }
+
+        // The other keys do nothing, they don't type
+        _ => return Ok(true),
+    };
+
+    // Going to a line extends the selection too
+    if let (true, Action::GoToLine(line)) = (visual, action) {
+        start_selection(doc, cursor, cursor_state, render_state);
+        cursor.jump_to_line(line, editor_state, cursor_state, render_state);
+        return Ok(true);
+    }
+
+    // The count repeats the command, except when it's a line
+    let times = match action {
+        Action::GoToLine(_) | Action::CommandLine | Action::Search => 1,
+        _ => count.unwrap_or(1),
+    };
+    for _ in 0..times {
+        execute(action, doc, cursor, editor_state, cursor_state, 
+            render_state)?;
+    }
+
+    // The edits drop the selection and with it the visual mode
+    if visual && cursor_state.anchor.is_none() {
+        set_mode(Mode::Normal, editor_state, render_state);
+    }
+
+    Ok(true)
+}
+
+/// Change the mode of the modal editing, it's shown on the status bar
+fn set_mode(
+    mode: Mode,
+    editor_state: &mut EditorState,
+    render_state: &mut RenderState,
+) {
+    editor_state.modal.mode = mode;
+    render_state.modif_status = true;
+}
+
+/// Handle the input while the command line is open, the command runs on
+/// Enter and its result is left as the message
+fn process_command_line_event(
+    event: &Event,
+    doc: &mut Option<Document>,
+    cursor: &mut Cursor,
+    editor_state: &mut EditorState,
+    cursor_state: &mut CursorState,
+    render_state: &mut RenderState,
+) -> Result<()> {
+    let command_line = match editor_state.command_line.as_mut() {
+        Some(command_line) => command_line,
+        None => return Ok(()),
+    };
+    render_state.modif_status = true;
+
+    match event {
+        Event::Key(KeyEvent {
+            code: KeyCode::Char(c),
+            modifiers, ..
+        }) if !modifiers.intersects(
+                KeyModifiers::CONTROL | KeyModifiers::ALT) => {
+            command_line.input.push(*c);
+        }
+        Event::Key(KeyEvent { code: KeyCode::Backspace, .. }) => {
+            command_line.input.pop();
+        }
+        Event::Key(KeyEvent { code: KeyCode::Esc, .. }) => {
+            editor_state.command_line = None;
+            editor_state.osc.command_finished(None);
+            render_state.last_cursor = Some(*cursor);
+        }
+        Event::Key(KeyEvent { code: KeyCode::Enter, .. }) => {
+            let input = std::mem::take(&mut command_line.input);
+            editor_state.command_line = None;
+            render_state.last_cursor = Some(*cursor);
+
+            editor_state.osc.command_started();
+            let result = run_command(
+                &input, doc, cursor, editor_state, cursor_state, render_state);
+            editor_state.osc.command_finished(Some(result.is_ok()));
+            editor_state.message.result(result);
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+/// Handle the input while a prompt is open on the minibuffer, its answer
+/// goes to what it was opened for
+fn process_prompt_event(
+    event: &Event,
+    doc: &mut Option<Document>,
+    cursor: &mut Cursor,
+    editor_state: &mut EditorState,
+    cursor_state: &mut CursorState,
+    render_state: &mut RenderState,
+) -> Result<()> {
+    let (prompt, key) = match (editor_state.prompt.as_mut(), event) {
+        (Some(prompt), Event::Key(key)) => (prompt, key),
+        _ => return Ok(()),
+    };
+    if matches!(prompt.kind, PromptKind::FindFile | PromptKind::Palette) {
+        return process_finder_event(
+            key, doc, cursor, editor_state, cursor_state, render_state);
+    }
+    let answer = match prompt.key(key) {
+        Answer::Typing => return Ok(()),
+        Answer::Done(answer) => answer,
+    };
+    let kind = prompt.kind;
+    editor_state.prompt = None;
+    render_state.last_cursor = Some(*cursor);
+
+    // Cancelled or left empty nothing is done
+    if kind == PromptKind::Quit 
+            && answer.as_ref().is_none_or(|answer| answer.trim().is_empty()) {
+        editor_state.quitting = None;
+    }
+    let answer = match answer {
+        Some(answer) if !answer.trim().is_empty() => answer,
+        _ => return Ok(()),
+    };
+    match kind {
+        PromptKind::SaveAs => {
+            let result = run_command(
+                &format!("saveas {}", answer.trim()), 
+                doc, cursor, editor_state, cursor_state, render_state);
+            editor_state.message.result(result);
+        }
+        PromptKind::Reload => {
+            let command = match answer.trim() {
+                "r" | "reload" => "reload",
+                "d" | "diff" => {
+                    if let Some(doc) = doc {
+                        editor_state.panel = Some(diff_panel(doc));
+                        render_state.modif_all = true;
+                    }
+                    return Ok(());
+                }
+                _ => return Ok(()),
+            };
+            let result = run_command(
+                command, doc, cursor, editor_state, cursor_state, render_state);
+            editor_state.message.result(result);
+        }
+        PromptKind::Quit => match answer.trim() {
+            "y" | "Y" => {
+                let saved = match doc.as_mut() {
+                    Some(doc) => save_document(doc, None, false, editor_state),
+                    None => Ok(String::new()),
+                };
+                render_state.modif_all = true;
+                match saved {
+                    Ok(_) => quit(
+                        doc, cursor, editor_state, cursor_state, render_state)?,
+                    Err(err) => {
+                        editor_state.quitting = None;
+                        editor_state.message
+                            .error(format!("Could not save: {}", err));
+                    }
+                }
+            }
+            "n" | "N" => {
+                if let (Some(doc), Some(discarded)) = 
+                        (doc.as_ref(), editor_state.quitting.as_mut()) {
+                    discarded.push(doc.path.clone());
+                }
+                quit(doc, cursor, editor_state, cursor_state, render_state)?;
+            }
+            _ => editor_state.quitting = None,
+        }
+        PromptKind::Overwrite | PromptKind::CreateDir => {
+            if matches!(answer.trim(), "y" | "yes") {
+                let result = run_command(
+                    "save!", doc, cursor, editor_state, cursor_state, 
+                    render_state);
+                editor_state.message.result(result);
+            }
+        }
+        PromptKind::GoToLine => {
+            let doc = doc.as_ref().ok_or("no document opened")?;
+            let result = go_to_line(
+                answer.trim(), doc, cursor, editor_state, cursor_state,
+                render_state);
+            if let Err(err) = result {
+                editor_state.message.error(format!("Error: {}", err));
+            }
+        }
+        PromptKind::SetMark => {
+            let key = answer.chars().next().unwrap_or_default();
+            let doc = doc.as_ref().ok_or("no document opened")?;
+            if !Marks::is_valid(key) {
+                editor_state.message.error("Marks are letters or digits");
+                return Ok(());
+            }
+            let here = location(doc, cursor, cursor_state);
+            editor_state.message.show(
+                format!("Line {} marked with {}", here.line + 1, key));
+            editor_state.marks.set(key, here);
+        }
+        PromptKind::GoToMark => {
+            let key = answer.chars().next().unwrap_or_default();
+            let mark = match editor_state.marks.get(key) {
+                Some(mark) => mark.clone(),
+                None => {
+                    editor_state.message.error(
+                        format!("There is no mark {}", key));
+                    return Ok(());
+                }
+            };
+            let result = jump_to_location(
+                &mark.path, mark.line, Some(mark.column), doc, cursor,
+                editor_state, cursor_state, render_state);
+            if let Err(err) = result {
+                editor_state.message.error(format!("Error: {}", err));
+            }
+        }
+        // Answered by their own handler
+        PromptKind::FindFile | PromptKind::Palette => {}
+    }
+
+    Ok(())
+}
+
+/// Go to the `line` or `line:column` typed, both from 1, centering the view
+/// on it. The numbers past the end of the document or of the line go to its
+/// end
+fn go_to_line(
+    answer: &str,
+    doc: &Document,
+    cursor: &mut Cursor,
+    editor_state: &EditorState,
+    cursor_state: &mut CursorState,
+    render_state: &mut RenderState,
+) -> Result<()> {
+    let (line, column) = match answer.split_once(':') {
+        Some((line, column)) => (line, Some(column)),
+        None => (answer, None),
+    };
+    let parse = |number: &str| number.trim().parse::<usize>()
+        .map_err(|_| format!("`{}` is not a line or line:column", answer));
+    let line = parse(line)?.saturating_sub(1);
+    let line = usize::min(line, doc.buffer.len().saturating_sub(1));
+
+    let position = match column {
+        Some(column) =>
+            doc.position_at_grapheme(line, parse(column)?.saturating_sub(1)),
+        None => doc.position(line, 0),
+    };
+    center_on(position, cursor, editor_state, cursor_state, render_state);
+    if column.is_none() {
+        cursor.adjust_column_start(doc, cursor_state);
+    }
+    render_state.modif_status = true;
+
+    Ok(())
+}
+
+/// Handle the input while typing the passphrase of an encrypted file, the
+/// file is opened with it on enter
+fn process_passphrase_event(
+    event: &Event,
+    doc: &mut Option<Document>,
+    cursor: &mut Cursor,
+    editor_state: &mut EditorState,
+    cursor_state: &mut CursorState,
+    render_state: &mut RenderState,
+) -> Result<()> {
+    let prompt = match editor_state.passphrase.as_mut() {
+        Some(prompt) => prompt,
+        None => return Ok(()),
+    };
+    render_state.modif_status = true;
+
+    match event {
+        Event::Key(KeyEvent {
+            code: KeyCode::Char(c),
+            modifiers, ..
+        }) if !modifiers.intersects(
+                KeyModifiers::CONTROL | KeyModifiers::ALT) => {
+            prompt.input.push(*c);
+        }
+        Event::Key(KeyEvent { code: KeyCode::Backspace, .. }) => {
+            prompt.input.pop();
+        }
+        Event::Key(KeyEvent { code: KeyCode::Esc, .. }) => {
+            editor_state.passphrase = None;
+            render_state.last_cursor = Some(*cursor);
+        }
+        Event::Key(KeyEvent { code: KeyCode::Enter, .. }) => {
+            let PassphrasePrompt { path, input } = editor_state.passphrase
+                .take()
+                .unwrap();
+            render_state.last_cursor = Some(*cursor);
+
+            match editor_state.open_document(&path, Some(input)) {
+                Ok(new_doc) => insert_buffer(new_doc,
+                    doc, cursor, editor_state, cursor_state, render_state)?,
+                Err(err) => editor_state.message.error(format!(
+                    "Could not open {}: {}", path.display(), err)),
+            }
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+/// Handle the input while searching, the cursor goes to the first match
+/// after the position where the search started while the query is typed.
+/// Returns if the event was used, the other events accept the search
+fn process_search_event(
+    event: &Event,
+    doc: &mut Option<Document>,
+    cursor: &mut Cursor,
+    editor_state: &mut EditorState,
+    cursor_state: &mut CursorState,
+    render_state: &mut RenderState,
+) -> bool {
+    let (search, doc) = match (editor_state.search.as_mut(), doc.as_ref()) {
+        (Some(search), Some(doc)) => (search, doc),
+        _ => return true,
+    };
+    render_state.modif_status = true;
+
+    // The highlights of the matches change
+    render_state.modif_all = true;
+
+    match event {
+        // Toggles of the search options
+        Event::Key(KeyEvent {
+            code: KeyCode::Char('c'),
+            modifiers, ..
+        }) if modifiers.contains(KeyModifiers::ALT) => {
+            search.options.case = search.options.case.next();
+            editor_state.search_options = search.options;
+        }
+        Event::Key(KeyEvent {
+            code: KeyCode::Char('w'),
+            modifiers, ..
+        }) if modifiers.contains(KeyModifiers::ALT) => {
+            search.options.whole_word = !search.options.whole_word;
+            editor_state.search_options = search.options;
+        }
+
+        Event::Key(KeyEvent {
+            code: KeyCode::Char(c),
+            modifiers, ..
+        }) if !modifiers.intersects(
+                KeyModifiers::CONTROL | KeyModifiers::ALT) => {
+            search.input.push(*c);
+        }
+        Event::Key(KeyEvent { code: KeyCode::Backspace, .. }) => {
+            search.input.pop();
+        }
+
+        // Canceling goes back to where the search started
+        Event::Key(KeyEvent { code: KeyCode::Esc, .. }) => {
+            let Position { line, byte, .. } = search.origin;
+            cursor_state.viewport.scroll_y = search.origin_scroll_y;
+            cursor.row = line.saturating_sub(cursor_state.viewport.scroll_y);
+            cursor.column = byte;
+            cursor_state.last_column = false;
+            editor_state.search = None;
+            return true;
+        }
+
+        // On the pager Enter accepts the search like on `less`, `n`/`N`
+        // go through its matches later
+        Event::Key(KeyEvent { code: KeyCode::Enter, .. }) 
+                if editor_state.pager => {
+            editor_state.last_search = editor_state.search.take();
+            return true;
+        }
+
+        // Go to the next match, or the previous one with shift
+        Event::Key(KeyEvent { code: KeyCode::Enter, modifiers, .. }) => {
+            let position = cursor.position(doc, cursor_state);
+            let forward = !modifiers.contains(KeyModifiers::SHIFT);
+            if let Some(found) = search.step(forward, position) {
+                center_on(found.start_position(doc), 
+                    cursor, editor_state, cursor_state, render_state);
+            }
+            return true;
+        }
+
+        // The search is accepted at the current match
+        _ => {
+            editor_state.last_search = editor_state.search.take();
+            return false;
+        }
+    }
+
+    if let Some(found) = search.update(doc) {
+        center_on(found.start_position(doc), 
+            cursor, editor_state, cursor_state, render_state);
+    }
+
+    true
+}
+
+/// Handle the input while replacing, first the pattern and the replacement
+/// are typed and then every match from the cursor is confirmed
+fn process_replace_event(
+    event: &Event,
+    doc: &mut Option<Document>,
+    cursor: &mut Cursor,
+    editor_state: &mut EditorState,
+    cursor_state: &mut CursorState,
+    render_state: &mut RenderState,
+) {
+    let (replace, doc) = match (editor_state.replace.as_mut(), doc.as_mut()) {
+        (Some(replace), Some(doc)) => (replace, doc),
+        _ => return,
+    };
+    render_state.modif_status = true;
+
+    let code = match event {
+        Event::Key(KeyEvent { code, modifiers, .. }) 
+                if !modifiers.intersects(KeyModifiers::CONTROL) => {
+            // Alt+R switches between plain text and regular expression
+            if *code == KeyCode::Char('r') 
+                    && modifiers.contains(KeyModifiers::ALT) {
+                if replace.step == ReplaceStep::Pattern {
+                    replace.regex = !replace.regex;
+                }
+                return;
+            }
+            if modifiers.contains(KeyModifiers::ALT) {
+                return;
+            }
+            *code
+        }
+        _ => return,
+    };
+
+    let next = if replace.step == ReplaceStep::Confirm {
+        match code {
+            KeyCode::Char('y') => {
+                let line = cursor.line(cursor_state);
+                let position = undo_location(doc, cursor, cursor_state);
+                let pending = PendingChange::begin(doc, line, 1, position);
+                let next = replace.replace_current(doc);
+                editor_state.undo.record(
+                    doc, pending.commit(EditKind::Replace, doc, position));
+                next
+            }
+            KeyCode::Char('n') => replace.skip_current(doc),
+
+            // All the remaining matches are a single undo unit
+            KeyCode::Char('a') => {
+                let line = cursor.line(cursor_state);
+                let position = undo_location(doc, cursor, cursor_state);
+                let pending = PendingChange::begin(
+                    doc, line, doc.buffer.len() - line, position);
+                let cancel = editor_state.events.cancel_token();
+                let operation = cancel.start();
+                while let Some((line, column)) = replace.replace_current(doc) {
+                    if cancel.is_cancelled() {
+                        break;
+                    }
+                    replace.find_from(doc, line, column);
+                }
+                drop(operation);
+
+                // The lines replaced so far go back to how they were
+                if cancel.is_cancelled() {
+                    pending.abort(doc);
+                    editor_state.message.show(
+                        "Replace cancelled, the remaining matches are kept");
+                    editor_state.replace = None;
+                    cursor.clamp_to_document(doc, cursor_state, render_state);
+                    render_state.modif_all = true;
+                    return;
+                }
+                editor_state.undo.record(
+                    doc, pending.commit(EditKind::Replace, doc, position));
+                None
+            }
+            KeyCode::Char('q') | KeyCode::Esc => None,
+            _ => return,
+        }
+    } else {
+        let input = replace.input_mut().unwrap();
+        match code {
+            KeyCode::Char(c) => input.push(c),
+            KeyCode::Backspace => { input.pop(); }
+            KeyCode::Esc => {
+                editor_state.replace = None;
+                return;
+            }
+            KeyCode::Enter => {
+                if let Err(err) = replace.advance() {
+                    editor_state.message
+                        .error(format!("Invalid pattern: {}", err));
+                    editor_state.replace = None;
+                    return;
+                }
+            }
+            _ => {}
+        }
+
+        // The confirmation starts at the cursor
+        if replace.step != ReplaceStep::Confirm {
+            return;
+        }
+        Some((cursor.line(cursor_state), cursor.column))
+    };
+
+    // Go to the next match to confirm, without more the replace is done
+    let found = next.and_then(|(line, column)| 
+        replace.find_from(doc, line, column));
+    match found {
+        Some(found) => center_on(found.start_position(doc), 
+            cursor, editor_state, cursor_state, render_state),
+        None => {
+            editor_state.message.show(match replace.replaced {
+                0 => format!("No replacements of `{}`", replace.pattern),
+                1 => "Replaced 1 occurrence".to_owned(),
+                replaced => format!("Replaced {} occurrences", replaced),
+            });
+            editor_state.replace = None;
+            cursor.clamp_to_document(doc, cursor_state, render_state);
+        }
+    }
+
+    // The highlight of the current match moves
+    render_state.modif_all = true;
+}
+
+/// Move the cursor to the position scrolling so its line is at the center
+/// of the terminal when possible
+fn center_on(
+    position: Position,
+    cursor: &mut Cursor,
+    editor_state: &EditorState,
+    cursor_state: &mut CursorState,
+    render_state: &mut RenderState,
+) {
+    cursor.jump_to_line(position.line, editor_state, cursor_state, render_state);
+    cursor.column = position.byte;
+}
+
+/// Run a command typed on the command line, returns the message for the
+/// user
+fn run_command(
+    input: &str,
+    doc: &mut Option<Document>,
+    cursor: &mut Cursor,
+    editor_state: &mut EditorState,
+    cursor_state: &mut CursorState,
+    render_state: &mut RenderState,
+) -> Result<String> {
+    let (name, args) = command::split(input);
+
+    match name {
+        "rename" | "move" | "mv" => {
+            // The name is shown on the tabs bar too
+            render_state.modif_all = true;
+            rename_document(args, doc, editor_state)
+        }
+        "remove" | "rm" => {
+            render_state.modif_status = true;
+            remove_file(args, doc, editor_state)
+        }
+        "undelete" => undelete_file(doc, editor_state),
+        // The filetype is an option of the document
+        "set" | "setlocal" 
+                if args.split('=').next().map(str::trim) == Some("filetype") => {
+            render_state.modif_all = true;
+            render_state.modif_status = true;
+            let value = args.split_once('=').map_or("", |(_, value)| value);
+            let doc = doc.as_mut().ok_or("no document opened")?;
+            set_filetype(value.trim(), doc, editor_state)
+        }
+        // The options can change how everything is shown
+        "set" => {
+            render_state.modif_all = true;
+            set_option(args, editor_state)
+        }
+        "setlocal" => {
+            render_state.modif_all = true;
+            let (name, value) = args.split_once('=').unwrap_or((args, ""));
+            editor_state.window.set(name.trim(), value.trim())
+        }
+        "open" | "edit" | "e" => {
+            if args.is_empty() {
+                return Err("usage: open <path>".into());
+            }
+            open_buffer(Path::new(args), 
+                doc, cursor, editor_state, cursor_state, render_state)?;
+
+            // The warnings of the opening are the result
+            Ok(editor_state.message.take().unwrap_or_default())
+        }
+        "bnext" | "bn" | "bprev" | "bp" => {
+            let forward = matches!(name, "bnext" | "bn");
+            let to = editor_state.buffers.cycle(forward);
+            switch_buffer(
+                to, doc, cursor, editor_state, cursor_state, render_state)?;
+            Ok(String::new())
+        }
+        "narrow" | "narrow!" => {
+            let source = doc.as_ref().ok_or("no document opened")?;
+            if args.is_empty() {
+                return Err("usage: narrow <pattern>".into());
+            }
+            if source.narrow.is_some() {
+                return Err("the document is already narrowed".into());
+            }
+            let narrowed = narrow::narrow(source, args, name == "narrow!")?;
+            let lines = narrowed.buffer.len();
+            insert_buffer(narrowed,
+                doc, cursor, editor_state, cursor_state, render_state)?;
+            Ok(format!("{} lines, `save` writes them back and `widen` \
+                closes the view", lines))
+        }
+        "widen" => {
+            let narrowed = doc.as_mut().ok_or("no document opened")?;
+            if narrowed.narrow.is_none() {
+                return Err("the document is not narrowed".into());
+            }
+            let mut message = String::new();
+            if narrowed.dirty {
+                message = write_back_narrowed(narrowed, editor_state)?;
+            }
+            close_buffer(
+                false, doc, cursor, editor_state, cursor_state, render_state)?;
+            Ok(message)
+        }
+        "bclose" | "bd" | "bclose!" | "bd!" => close_buffer(
+            name.ends_with('!'), 
+            doc, cursor, editor_state, cursor_state, render_state),
+        "pipe" | "!" => {
+            if args.is_empty() {
+                return Err("usage: pipe <command>".into());
+            }
+            let doc = writable(doc)?;
+            pipe_through(
+                args, doc, cursor, editor_state, cursor_state, render_state)
+        }
+        "indent" | "=" => {
+            let doc = writable(doc)?;
+            let (start, end) = parse_range(args, doc.buffer.len())?;
+            reindent_lines(
+                doc, start, end, cursor, editor_state, cursor_state, render_state)
+        }
+        "wrap" => {
+            let doc = writable(doc)?;
+            let (start, end) = parse_range(args, doc.buffer.len())?;
+            let textwidth = editor_state.prose.textwidth;
+            let lines: Vec<String> = doc.buffer
+                .lines_from(start)
+                .take(end - start)
+                .flat_map(|line| prose::wrap_line(line, textwidth))
+                .collect();
+            let new_len = lines.len();
+            let position = undo_location(doc, cursor, cursor_state);
+            let pending = PendingChange::begin(doc, start, end - start, position);
+            doc.replace_lines(start, end, lines);
+            editor_state.undo.record(
+                doc, pending.commit(EditKind::Replace, doc, position));
+
+            editor_state.doc_lines = doc.buffer.len();
+            editor_state.edits.record(&doc.path, start, 0);
+            render_state.modif_all = true;
+            cursor.clamp_to_document(doc, cursor_state, render_state);
+
+            Ok(format!("Wrapped into {} lines", new_len))
+        }
+        // The diff comes from the clipboard, the register of the editor
+        // with `@` or a file
+        "patch" => {
+            let doc = writable(doc)?;
+            let text = match args {
+                "" => editor_state.clipboard.paste(),
+                "@" => editor_state.clipboard.register().to_owned(),
+                path => std::fs::read_to_string(path)?,
+            };
+            apply_patch(
+                &text, doc, cursor, editor_state, cursor_state, render_state)
+        }
+        "snapshot" => {
+            let doc = doc.as_ref().ok_or("no document opened")?;
+            if args.is_empty() {
+                return Err("usage: snapshot <name>".into());
+            }
+            editor_state.snapshots.take(doc, args);
+            Ok(format!("Snapshot `{}` taken", args))
+        }
+        "snapshots" => {
+            let doc = doc.as_ref().ok_or("no document opened")?;
+            editor_state.panel = 
+                Some(snapshots_panel(&editor_state.snapshots, doc));
+            render_state.modif_all = true;
+            Ok(String::new())
+        }
+        "snapdiff" | "snaprestore" => {
+            let current = doc.as_ref().ok_or("no document opened")?;
+            let idx = editor_state.snapshots.find(&current.path, args)
+                .ok_or_else(|| format!("no snapshot named `{}`", args))?;
+            if name == "snapdiff" {
+                editor_state.panel = Some(snapshot_diff_panel(
+                    &editor_state.snapshots.list[idx], current));
+                render_state.modif_all = true;
+                return Ok(String::new());
+            }
+            restore_snapshot(
+                idx, doc, cursor, editor_state, cursor_state, render_state)
+        }
+        // The changes a commit message describes
+        "todos" => {
+            editor_state.panel = Some(todos_panel(editor_state)?);
+            render_state.modif_all = true;
+            Ok(String::new())
+        }
+        // With a program it's used instead of the one of the filetype,
+        // `off` stops refreshing the preview
+        "preview" if args == "off" => {
+            editor_state.preview = None;
+            Ok("The preview is not refreshed anymore".to_owned())
+        }
+        "preview" => {
+            let doc = doc.as_ref().ok_or("no document opened")?;
+            let command = (!args.is_empty()).then_some(args);
+            editor_state.panel = 
+                Some(preview_panel(doc, command, editor_state)?);
+            render_state.modif_all = true;
+            Ok(String::new())
+        }
+        // The changes on the gutter are from the lines of another file, or
+        // from the saved version again without one
+        "baseline" => {
+            let doc = doc.as_ref().ok_or("no document opened")?;
+            render_state.modif_all = true;
+            if args.is_empty() {
+                editor_state.changes.reset_baseline(&doc.path);
+                return Ok("The changes are from the saved version".into());
+            }
+            let bytes = std::fs::read(args)
+                .map_err(|err| format!("{}: {}", args, err))?;
+            editor_state.changes.set_baseline(
+                &doc.path, text::split_lines(&bytes));
+            Ok(format!("The changes are from {}", args))
+        }
+        // To a tool of the config by its name, or to a shell command
+        "export" => {
+            let doc = doc.as_ref().ok_or("no document opened")?;
+            export_text(args, doc, cursor, editor_state, cursor_state, 
+                render_state)
+        }
+        // The highlight rules, `highlight <pattern> = <style>` adds one
+        "highlight" if args.is_empty() => {
+            let lines: Vec<String> = editor_state.highlights.rules
+                .iter()
+                .map(|rule| format!("{} = {}", rule.pattern, rule.words))
+                .collect();
+            if lines.is_empty() {
+                return Ok("There are no highlight rules".into());
+            }
+            editor_state.panel = Some(Panel::new("Highlight rules", lines));
+            render_state.modif_all = true;
+            Ok(String::new())
+        }
+        "highlight" => {
+            let (pattern, style) = args.rsplit_once('=')
+                .ok_or("usage: highlight <pattern> = <style>")?;
+            editor_state.highlights.add(pattern.trim(), style)?;
+            render_state.modif_all = true;
+            Ok(format!("Highlighting `{}`", pattern.trim()))
+        }
+        "unhighlight" => {
+            if !editor_state.highlights.remove(args) {
+                return Err(format!("`{}` is not highlighted", args).into());
+            }
+            render_state.modif_all = true;
+            Ok(format!("Not highlighting `{}` anymore", args))
+        }
+        "staged" => {
+            let path = doc.as_ref().map_or(Path::new("."), |doc| &doc.path);
+            let lines = commit::staged_diff(path)?;
+            if lines.is_empty() {
+                return Ok("Nothing staged".to_owned());
+            }
+            editor_state.panel = Some(Panel::new("Staged changes", lines));
+            render_state.modif_all = true;
+            Ok(String::new())
+        }
+        "grep" | "rg" => {
+            if args.is_empty() {
+                return Err(format!("usage: {} <pattern>", name).into());
+            }
+            if editor_state.stream.as_ref()
+                    .is_some_and(|stream| stream.status.is_none()) {
+                return Err("a program is already running".into());
+            }
+            let stream = OutputStream::grep(args, editor_state.events.sender())?;
+            editor_state.panel = Some(output_panel(&stream));
+            editor_state.stream = Some(stream);
+            render_state.modif_all = true;
+            Ok(String::new())
+        }
+        "run" => {
+            if args.is_empty() {
+                return Err("usage: run <command>".into());
+            }
+            if editor_state.stream.as_ref()
+                    .is_some_and(|stream| stream.status.is_none()) {
+                return Err("a program is already running".into());
+            }
+            let stream = OutputStream::spawn(args, editor_state.events.sender())?;
+            editor_state.panel = Some(output_panel(&stream));
+            editor_state.stream = Some(stream);
+            render_state.modif_all = true;
+            Ok(String::new())
+        }
+        // The work on the background, and how far it went
+        "tasks" => {
+            let mut lines = Vec::new();
+            for task in background_tasks(editor_state) {
+                let progress = match task.as_str() {
+                    "index" => editor_state.index.as_ref().map(|index|
+                        format!("{} files so far", index.file_count())),
+                    _ => editor_state.stream.as_ref().map(|stream|
+                        format!("`{}`, {} lines printed",
+                            stream.command, stream.lines.len())),
+                };
+                lines.push(
+                    format!("{:8}{}", task, progress.unwrap_or_default()));
+            }
+            if lines.is_empty() {
+                return Ok("Nothing is running on the background".into());
+            }
+            lines.push(String::new());
+            lines.push("`cancel <task>` stops one, `cancel` all".into());
+            editor_state.panel = Some(Panel::new("Background tasks", lines));
+            render_state.modif_all = true;
+            Ok(String::new())
+        }
+        "cancel" => {
+            let tasks = background_tasks(editor_state);
+            let cancelled: Vec<String> = match args {
+                "" => tasks,
+                task if tasks.iter().any(|running| running == task) =>
+                    vec![task.to_owned()],
+                task => return Err(format!("{} is not running", task).into()),
+            };
+            if cancelled.is_empty() {
+                return Err("nothing is running on the background".into());
+            }
+            for task in &cancelled {
+                match task.as_str() {
+                    "index" => if let Some(index) = &editor_state.index {
+                        index.cancel();
+                    },
+                    _ => if let Some(stream) = &mut editor_state.stream {
+                        stream.cancel()?;
+                    },
+                }
+            }
+            render_state.modif_status = true;
+            Ok(format!("Cancelled {}", cancelled.join(", ")))
+        }
+        "output" => {
+            let stream = editor_state.stream.as_ref()
+                .ok_or("no program was run")?;
+            editor_state.panel = Some(output_panel(stream));
+            render_state.modif_all = true;
+            Ok(String::new())
+        }
+        // The bytes of the file, for the binary files
+        "hex" => {
+            let doc = doc.as_ref().ok_or("no document opened")?;
+            let bytes = std::fs::read(&doc.path)?;
+            editor_state.panel = Some(Panel::new(
+                format!("Hex dump of {} -- {} bytes",
+                    doc.path.display(), bytes.len()),
+                binary::hex_lines(&bytes)));
+            render_state.modif_all = true;
+            Ok(String::new())
+        }
+        // The input method of the buffer
+        "compose" => {
+            let doc = doc.as_mut().ok_or("no document opened")?;
+            doc.compose = match args {
+                "" => !doc.compose,
+                value => parse_bool(value)?,
+            };
+            render_state.modif_status = true;
+
+            let state = if doc.compose { "on" } else { "off" };
+            Ok(format!("Composing the accented chars is {}", state))
+        }
+        // Like the `trimwhitespace` option does on save
+        "trim" => {
+            let doc = doc.as_mut().ok_or("no document opened")?;
+            let trimmed = doc.buffer
+                .lines()
+                .filter(|line| line.trim_end().len() != line.len())
+                .count();
+            if trimmed == 0 {
+                return Ok("There is no trailing whitespace".to_owned());
+            }
+
+            let position = undo_location(doc, cursor, cursor_state);
+            let pending = PendingChange::begin(
+                doc, 0, doc.buffer.len(), position);
+            hooks::trim_trailing_whitespace(doc)?;
+            editor_state.undo.record(
+                doc, pending.commit(EditKind::Replace, doc, position));
+            cursor.clamp_to_document(doc, cursor_state, render_state);
+            render_state.modif_all = true;
+            Ok(format!("Trailing whitespace trimmed on {} lines", trimmed))
+        }
+        "count" | "wc" => {
+            let doc = doc.as_ref().ok_or("no document opened")?;
+            let count = doc.count();
+            let size = std::fs::metadata(&doc.path)
+                .ok()
+                .filter(|_| doc.kind == DocKind::File)
+                .map_or_else(|| "not saved".to_owned(),
+                    |metadata| format!("{} bytes on disk", metadata.len()));
+            Ok(format!("{} lines, {} words, {} chars, {}",
+                count.lines, count.words, count.chars, size))
+        }
+        // The words and the chars the buffer should stay under, the status
+        // bar tells how far it is from them
+        "limit" => {
+            let doc = doc.as_mut().ok_or("no document opened")?;
+            let mut words = args.split_whitespace();
+            match (words.next(), words.next()) {
+                (None, _) => {}
+                (Some("off"), None) => doc.limits = Limits::default(),
+                (Some(kind @ ("words" | "chars")), Some(value)) => {
+                    let limit = match value {
+                        "off" => None,
+                        value => Some(value.parse::<usize>()
+                            .ok()
+                            .filter(|limit| *limit > 0)
+                            .ok_or("the limit must be a number above 0")?),
+                    };
+                    if kind == "words" {
+                        doc.limits.words = limit;
+                    } else {
+                        doc.limits.chars = limit;
+                    }
+                }
+                _ => return Err("usage: limit words|chars <count>|off, \
+                    or limit off".into()),
+            }
+            render_state.modif_status = true;
+
+            let notes = doc.limits.notes(doc.count());
+            if notes.is_empty() {
+                return Ok("The document has no limits".to_owned());
+            }
+            Ok(format!("Limits: {}", notes.join(" ")))
+        }
+        "readonly" => {
+            let doc = doc.as_mut().ok_or("no document opened")?;
+            doc.read_only = match args {
+                "" => !doc.read_only,
+                value => parse_bool(value)?,
+            };
+            render_state.modif_status = true;
+
+            let state = if doc.read_only { "read-only" } else { "writable" };
+            Ok(format!("The document is {}", state))
+        }
+        "save" | "w" | "save!" | "w!" => {
+            let doc = doc.as_mut().ok_or("no document opened")?;
+            let force = name.ends_with('!');
+            let message = save_document(doc, None, force, editor_state)?;
+            cursor.clamp_to_document(doc, cursor_state, render_state);
+
+            // The marks of the changed lines are gone from the gutter
+            render_state.modif_all = true;
+            Ok(message)
+        }
+        "reload" => {
+            let doc = doc.as_mut().ok_or("no document opened")?;
+            if doc.narrow.is_some() {
+                return Err("a narrowed view has no file to reload".into());
+            }
+            reload_document(doc, cursor, editor_state, cursor_state, 
+                render_state)
+        }
+        // A clean document is read again in the encoding, the changes are
+        // saved in it
+        "encoding" => {
+            let doc = doc.as_mut().ok_or("no document opened")?;
+            if args.is_empty() {
+                return Ok(format!("The encoding is {}", doc.encoding));
+            }
+            let encoding = Encoding::from_name(args).ok_or_else(|| {
+                let names: Vec<&str> = Encoding::ALL
+                    .iter()
+                    .map(|encoding| encoding.name())
+                    .collect();
+                format!("unknown encoding {}, try {}", args, names.join(", "))
+            })?;
+            doc.encoding = encoding;
+            render_state.modif_status = true;
+
+            let can_reload = doc.narrow.is_none() && doc.path.is_file();
+            if doc.dirty || !can_reload {
+                doc.dirty = true;
+                return Ok(format!("It will be saved as {}", encoding));
+            }
+            reload_document(doc, cursor, editor_state, cursor_state,
+                render_state)?;
+            Ok(format!("Read as {}", encoding))
+        }
+        // The lines are saved with the ending from now on
+        "eol" => {
+            let doc = doc.as_mut().ok_or("no document opened")?;
+            if args.is_empty() {
+                let mixed = if doc.mixed_line_endings { ", mixed" } else { "" };
+                return Ok(format!(
+                    "The lines end with {}{}", doc.line_ending, mixed));
+            }
+            let ending = LineEnding::from_name(args)
+                .ok_or_else(|| format!("unknown line ending {}, try lf or \
+                    crlf", args))?;
+            if ending == doc.line_ending && !doc.mixed_line_endings {
+                return Ok(format!("The lines already end with {}", ending));
+            }
+            doc.line_ending = ending;
+            doc.mixed_line_endings = false;
+            doc.dirty = true;
+            render_state.modif_status = true;
+
+            Ok(format!("The lines will be saved with {}", ending))
+        }
+        "saveas" | "saveas!" => {
+            let doc = doc.as_mut().ok_or("no document opened")?;
+
+            // Without a path it's asked on the minibuffer
+            if args.is_empty() {
+                editor_state.prompt = Some(Prompt::new(
+                    PromptKind::SaveAs, "Save as: ", &save_as_path(doc)));
+                return Ok(String::new());
+            }
+            let message = save_document(
+                doc, Some(Path::new(args)), name == "saveas!", editor_state)?;
+            cursor.clamp_to_document(doc, cursor_state, render_state);
+
+            // The name is shown on the tabs bar too
+            render_state.modif_all = true;
+            Ok(message)
+        }
+        "savepreview" | "preview-save" => {
+            let doc = doc.as_ref().ok_or("no document opened")?;
+            let insert_final_newline = 
+                editor_state.final_newline.applies(doc.filetype());
+            let lines = save::preview(
+                doc, &editor_state.save_options, insert_final_newline);
+
+            editor_state.panel = Some(Panel::new("Save preview", lines));
+            render_state.modif_all = true;
+            Ok(String::new())
+        }
+        "marks" => {
+            editor_state.panel = Some(
+                Panel::new("Marks", editor_state.marks.lines()));
+            render_state.modif_all = true;
+            Ok(String::new())
+        }
+        "diagnostics" => {
+            let doc = doc.as_ref().ok_or("no document opened")?;
+            let lines: Vec<String> = editor_state.lsp.diagnostics(&doc.path)
+                .iter()
+                .map(|diagnostic| format!("{}:{} {}: {}",
+                    diagnostic.start.0 + 1, diagnostic.start.1 + 1,
+                    diagnostic.severity,
+                    diagnostic.message.replace('\n', " ")))
+                .collect();
+            if lines.is_empty() {
+                return Ok("No diagnostics".to_owned());
+            }
+
+            editor_state.panel = Some(Panel::new("Diagnostics", lines));
+            render_state.modif_all = true;
+            Ok(String::new())
+        }
+        "plugins" => Ok(format!("Plugins: {}", 
+            editor_state.plugins.names().join(", "))),
+        "index" => match &editor_state.index {
+            Some(index) if index.is_ready() => 
+                Ok(format!("{} files indexed", index.file_count())),
+            Some(index) => 
+                Ok(format!("Indexing... {} files so far", index.file_count())),
+            None => Err("the project is not indexed".into()),
+        },
+        "latency" if args == "reset" => {
+            editor_state.latency.reset();
+            Ok("Latency samples cleared".to_owned())
+        }
+        "latency" => {
+            let lines = editor_state.latency.report();
+            editor_state.panel = Some(Panel::new("Keypress to paint", lines));
+            render_state.modif_all = true;
+            Ok(String::new())
+        }
+        "" => Ok(String::new()),
+        _ => Err(format!("unknown command `{}`", name).into()),
+    }
+}
+
+/// The document to edit, if there is one and it's not read-only
+fn writable(doc: &mut Option<Document>) -> Result<&mut Document> {
+    match doc {
+        Some(doc) if doc.read_only => Err("the document is read-only".into()),
+        Some(doc) => Ok(doc),
+        None => Err("no document opened".into()),
+    }
+}
+
+/// The motion that moves the cursor with the lines as they are shown: the
+/// rows are lines without wrapping, and when wrapping up and down are rows
+/// if `rowmotions`, the row motions becoming lines
+fn wrapped_motion(motion: Motion, editor_state: &EditorState) -> Motion {
+    let swap = editor_state.window.wrap && editor_state.row_motions;
+    match motion {
+        Motion::Up { .. } if swap => Motion::RowUp,
+        Motion::Down { .. } if swap => Motion::RowDown,
+        Motion::RowUp if !editor_state.window.wrap || swap => 
+            Motion::Up { exact: false },
+        Motion::RowDown if !editor_state.window.wrap || swap => 
+            Motion::Down { exact: false },
+        motion => motion,
+    }
+}
+
+/// Choose the filetype of the document, the hooks of the filetype run as if
+/// it was detected. Empty goes back to the detected one
+pub fn set_filetype(
+    name: &str,
+    doc: &mut Document,
+    editor_state: &mut EditorState
+) -> Result<String> {
+    doc.force_filetype(name)?;
+    if doc.filetype().is_some() {
+        editor_state.hooks.fire(HookEvent::Filetype, doc)?;
+    }
+
+    Ok(format!("filetype={}", doc.filetype().unwrap_or("none")))
+}
+
+/// Change an option of the editor, written as `name=value`
+pub fn set_option(
+    args: &str,
+    editor_state: &mut EditorState
+) -> Result<String> {
+    let (name, value) = args.split_once('=').unwrap_or((args, ""));
+    let value = value.trim();
+
+    match name.trim() {
+        "equalprg" => {
+            editor_state.equalprg = if value.is_empty() {
+                None
+            } else {
+                Some(value.to_owned())
+            };
+            Ok(format!("equalprg={}", value))
+        }
+        "trimwhitespace" => {
+            editor_state.save_options.trim_trailing_whitespace = 
+                parse_bool(value)?;
+            Ok(format!("trimwhitespace={}", value))
+        }
+        "finalnewline" => {
+            editor_state.final_newline.enabled = parse_bool(value)?;
+            Ok(format!("finalnewline={}", value))
+        }
+        "printnumbers" => {
+            editor_state.print.numbers = parse_bool(value)?;
+            Ok(format!("printnumbers={}", value))
+        }
+        "printhighlight" => {
+            editor_state.print.highlight = parse_bool(value)?;
+            Ok(format!("printhighlight={}", value))
+        }
+        name if name.starts_with("finalnewline.") => {
+            let filetype = &name["finalnewline.".len()..];
+            editor_state.final_newline.filetypes
+                .insert(filetype.to_owned(), parse_bool(value)?);
+            Ok(format!("{}={}", name, value))
+        }
+        // The behaviours of the auto-pairs, `pairs.skipclose` for all the
+        // filetypes and `pairs.rust.skipclose` for one
+        name if name.starts_with("pairs.") => {
+            let key = &name["pairs.".len()..];
+            let (filetype, behaviour) = match key.rsplit_once('.') {
+                Some((filetype, behaviour)) => (Some(filetype), behaviour),
+                None => (None, key),
+            };
+            let pairs = &mut editor_state.pairs;
+            let behaviour = match behaviour {
+                "skipclose" => &mut pairs.skip_close,
+                "deletepair" => &mut pairs.delete_pair,
+                _ => return Err(format!("unknown option `{}`", name).into()),
+            };
+            behaviour.set(filetype, parse_bool(value)?);
+            Ok(format!("{}={}", name, value))
+        }
+        "quitkey" => {
+            let binding = KeyBinding::parse(value)?;
+            editor_state.keymap.bind_only(binding, Action::Quit);
+            Ok(format!("quitkey={}", value))
+        }
+        "pollinterval" => {
+            let millis: u64 = value.parse()
+                .map_err(|_| "pollinterval must be a number of milliseconds")?;
+            editor_state.watcher.set_interval(Duration::from_millis(millis));
+            Ok(format!("pollinterval={}", millis))
+        }
+        "messagetimeout" => {
+            let millis: u64 = value.parse().map_err(
+                |_| "messagetimeout must be a number of milliseconds")?;
+            editor_state.message.timeout = Duration::from_millis(millis);
+            Ok(format!("messagetimeout={}", millis))
+        }
+        "heatmap" => {
+            editor_state.heatmap = parse_bool(value)?;
+            Ok(format!("heatmap={}", value))
+        }
+        "lsp" => {
+            editor_state.lsp.enabled = parse_bool(value)?;
+            Ok(format!("lsp={}", value))
+        }
+        name if name.starts_with("lsp.") => {
+            let filetype = &name["lsp.".len()..];
+            editor_state.lsp.commands
+                .insert(filetype.to_owned(), value.to_owned());
+            Ok(format!("{}={}", name, value))
+        }
+        "trash" => {
+            editor_state.trash = parse_bool(value)?;
+            Ok(format!("trash={}", value))
+        }
+        "wordchars" => {
+            editor_state.word_chars = WordChars::new(value);
+            Ok(format!("wordchars={}", value))
+        }
+        "resume" => {
+            editor_state.resume = parse_bool(value)?;
+            Ok(format!("resume={}", value))
+        }
+        "treewidth" => {
+            let width: usize = value.parse()
+                .map_err(|_| "treewidth must be a number of columns")?;
+            if width == 0 {
+                return Err("treewidth must be at least 1".into());
+            }
+            editor_state.tree_width = width;
+            Ok(format!("treewidth={}", width))
+        }
+        "textwidth" => {
+            let width: usize = value.parse()
+                .map_err(|_| "textwidth must be a number of columns")?;
+            if width == 0 {
+                return Err("textwidth must be at least 1".into());
+            }
+            editor_state.prose.textwidth = width;
+            Ok(format!("textwidth={}", width))
+        }
+        // The modal editing starts on the normal mode
+        "modal" => {
+            editor_state.modal = ModalState {
+                enabled: parse_bool(value)?,
+                ..ModalState::default()
+            };
+            Ok(format!("modal={}", value))
+        }
+        "autosave" => {
+            editor_state.autosave = AutoSave::from_name(value)
+                .ok_or("autosave must be off, current or all")?;
+            Ok(format!("autosave={}", value))
+        }
+        "rowmotions" => {
+            editor_state.row_motions = parse_bool(value)?;
+            Ok(format!("rowmotions={}", value))
+        }
+        "osc" => {
+            editor_state.osc.enabled = parse_bool(value)?;
+            Ok(format!("osc={}", value))
+        }
+        "linelength" => {
+            editor_state.prose.indicator = parse_bool(value)?;
+            Ok(format!("linelength={}", value))
+        }
+        "autowrap" => {
+            editor_state.prose.auto_wrap = parse_bool(value)?;
+            Ok(format!("autowrap={}", value))
+        }
+        "hoverdelay" => {
+            let millis: u64 = value.parse()
+                .map_err(|_| "hoverdelay must be a number of milliseconds")?;
+            editor_state.hover.delay = Duration::from_millis(millis);
+            Ok(format!("hoverdelay={}", millis))
+        }
+        "maxlinelength" => {
+            let limit: usize = value.parse()
+                .map_err(|_| "maxlinelength must be a number of chars")?;
+            editor_state.max_line_length = (limit > 0).then_some(limit);
+            Ok(format!("maxlinelength={}", limit))
+        }
+        // The guide of a column is drawn right after it, where the text
+        // past it would start
+        "rulers" => {
+            editor_state.rulers = value
+                .split(',')
+                .map(str::trim)
+                .filter(|column| !column.is_empty() && *column != "off")
+                .map(|column| column.parse().ok().filter(|column| *column > 0))
+                .collect::<Option<_>>()
+                .ok_or("rulers must be a list of columns separated by commas")?;
+            let rulers: Vec<_> = editor_state.rulers
+                .iter()
+                .map(usize::to_string)
+                .collect();
+            Ok(format!("rulers={}", rulers.join(",")))
+        }
+        // In megabytes, zero reads every file whole
+        "largefile" => {
+            let megabytes: u64 = value.parse()
+                .map_err(|_| "largefile must be a number of megabytes")?;
+            editor_state.large_file = (megabytes > 0).then_some(megabytes << 20);
+            Ok(format!("largefile={}", megabytes))
+        }
+        // Each pattern is added to the ones known, an empty one forgets
+        // all of them
+        "generatedpattern" if value.is_empty() => {
+            editor_state.generated.patterns.clear();
+            Ok("generatedpattern cleared".to_owned())
+        }
+        "generatedpattern" => {
+            let pattern = Regex::new(value)?;
+            editor_state.generated.patterns.push(pattern);
+            Ok(format!("generatedpattern={}", value))
+        }
+        "generatedreadonly" => {
+            editor_state.generated.read_only = parse_bool(value)?;
+            Ok(format!("generatedreadonly={}", value))
+        }
+        "swapfile" => {
+            editor_state.swaps.enabled = parse_bool(value)?;
+            Ok(format!("swapfile={}", value))
+        }
+        "dimunfocused" => {
+            editor_state.dim_unfocused = parse_bool(value)?;
+            Ok(format!("dimunfocused={}", value))
+        }
+        "autoindent" => {
+            editor_state.auto_indent = parse_bool(value)?;
+            Ok(format!("autoindent={}", value))
+        }
+        "expandtab" => {
+            editor_state.expand_tab = parse_bool(value)?;
+            Ok(format!("expandtab={}", value))
+        }
+        "tabwidth" => {
+            let width: usize = value.parse()
+                .ok()
+                .filter(|width| *width > 0)
+                .ok_or("tabwidth must be a positive number of columns")?;
+            editor_state.tab_width = width;
+            Ok(format!("tabwidth={}", width))
+        }
+        // The options of the windows change on all of them
+        name if WindowOptions::is_local(name) => {
+            let message = editor_state.window.set(name, value)?;
+            for tab in editor_state.tabs.list.iter_mut() {
+                tab.window.set(name, value)?;
+            }
+            Ok(message)
+        }
+        // Only the default colors exist for now
+        "theme" => {
+            editor_state.theme = theme::find(value).ok_or_else(|| format!(
+                "unknown theme `{}`, the themes are {}",
+                value, theme::names()))?;
+            Ok(format!("theme={}", editor_state.theme.name))
+        }
+        "scrollmargin" => {
+            editor_state.scroll_margin = value.parse()
+                .map_err(|_| "scrollmargin must be a number of lines")?;
+            Ok(format!("scrollmargin={}", value))
+        }
+        // Ctrl+U and Ctrl+D scroll half a page, like on vi and less, the
+        // last one adds a cursor at the match without it
+        "halfpagekeys" => {
+            let up = KeyBinding::new(KeyCode::Char('u'), KeyModifiers::CONTROL);
+            let down = 
+                KeyBinding::new(KeyCode::Char('d'), KeyModifiers::CONTROL);
+            if parse_bool(value)? {
+                editor_state.keymap.bind(up, Action::HalfPage { up: true });
+                editor_state.keymap.bind(down, Action::HalfPage { up: false });
+            } else {
+                editor_state.keymap.unbind(up);
+                editor_state.keymap.bind(down, Action::AddCursorAtMatch);
+            }
+            Ok(format!("halfpagekeys={}", value))
+        }
+        "wheelstep" => {
+            editor_state.wheel_step = value.parse()
+                .ok()
+                .filter(|lines| *lines > 0)
+                .ok_or("wheelstep must be a number of lines")?;
+            Ok(format!("wheelstep={}", value))
+        }
+        "bell" => {
+            editor_state.bell = crate::bell::from_name(value)
+                .ok_or("bell must be audible, visual or none")?;
+            Ok(format!("bell={}", value))
+        }
+        "ageidentity" => {
+            editor_state.age_identity = if value.is_empty() {
+                None
+            } else {
+                Some(value.into())
+            };
+            Ok(format!("ageidentity={}", value))
+        }
+        "todopatterns" => {
+            editor_state.todo_patterns = value.split(',')
+                .map(|pattern| pattern.trim().to_owned())
+                .filter(|pattern| !pattern.is_empty())
+                .collect();
+            Ok(format!("todopatterns={}", editor_state.todo_patterns.join(",")))
+        }
+        "statusleft" => {
+            editor_state.status_line.left = status::parse_list(value)?;
+            Ok(format!("statusleft={}", 
+                status::list_names(&editor_state.status_line.left)))
+        }
+        "statusright" => {
+            editor_state.status_line.right = status::parse_list(value)?;
+            Ok(format!("statusright={}", 
+                status::list_names(&editor_state.status_line.right)))
+        }
+        "undomemory" => {
+            let mebibytes: usize = value.parse()
+                .map_err(|_| "undomemory must be a number of MiB")?;
+            editor_state.undo.memory_limit = mebibytes * 1024 * 1024;
+            Ok(format!("undomemory={}", mebibytes))
+        }
+        name => Err(format!("unknown option `{}`", name).into()),
+    }
+}
+
+/// Parse the value of an on/off option
+fn parse_bool(value: &str) -> Result<bool> {
+    match value {
+        "on" | "true" | "yes" | "1" => Ok(true),
+        "off" | "false" | "no" | "0" => Ok(false),
+        _ => Err(format!("expected on or off, not `{}`", value).into()),
+    }
+}
+
+/// Parse a range of lines written as `start,end` (from 1, both included)
+/// into document indices `start..end`, no range means the whole document
+fn parse_range(args: &str, doc_lines: usize) -> Result<(usize, usize)> {
+    if args.is_empty() {
+        return Ok((0, doc_lines));
+    }
+
+    let (start, end) = args.split_once(',').unwrap_or((args, args));
+    let start: usize = start.trim().parse()?;
+    let end: usize = end.trim().parse()?;
+    if start == 0 || end < start {
+        return Err(format!("invalid range `{}`", args).into());
+    }
+
+    Ok((start - 1, usize::min(end, doc_lines)))
+}
+
+/// Re-indent the lines `start..end` of the document with the `equalprg`
+fn reindent_lines(
+    doc: &mut Document,
+    start: usize,
+    end: usize,
+    cursor: &mut Cursor,
+    editor_state: &mut EditorState,
+    cursor_state: &mut CursorState,
+    render_state: &mut RenderState,
+) -> Result<String> {
+    let equalprg = editor_state.equalprg.as_deref()
+        .ok_or("equalprg is not set, use `set equalprg=<command>`")?;
+    let position = undo_location(doc, cursor, cursor_state);
+    let pending = PendingChange::begin(doc, start, end - start, position);
+    let cancel = editor_state.events.cancel_token();
+    let _operation = cancel.start();
+    let new_len = indent::reindent(doc, start, end, equalprg, &cancel)?;
+    editor_state.edits.record(&doc.path, start, 0);
+    editor_state.undo.record(
+        doc, pending.commit(EditKind::Replace, doc, position));
+
+    editor_state.doc_lines = doc.buffer.len();
+    render_state.modif_all = true;
+    render_state.modif_status = true;
+    cursor.clamp_to_document(doc, cursor_state, render_state);
+
+    Ok(format!("Re-indented {} lines", new_len))
+}
+
+/// Replace the text selected, or the whole document, with the output of the
+/// shell `command` fed with it, like `sort` or `jq .`. The command gets no
+/// terminal, so the screen is left as it is while it runs, and its stderr is
+/// told when it fails. The document is left as it was then
+fn pipe_through(
+    command: &str,
+    doc: &mut Document,
+    cursor: &mut Cursor,
+    editor_state: &mut EditorState,
+    cursor_state: &mut CursorState,
+    render_state: &mut RenderState,
+) -> Result<String> {
+    let selection = cursor.selection(doc, cursor_state)
+        .filter(|selection| !selection.is_empty());
+    let (start, end, input) = match &selection {
+        Some(selection) => 
+            (selection.start.line, selection.end.line + 1, selection.text(doc)),
+        None => {
+            let mut input = String::new();
+            for line in doc.buffer.lines() {
+                input.push_str(line);
+                input.push('\n');
+            }
+            (0, doc.buffer.len(), input)
+        }
+    };
+
+    let cancel = editor_state.events.cancel_token();
+    let _operation = cancel.start();
+    let output = shell::pipe_shell(command, input.as_bytes(), &cancel)?;
+
+    // The text around the selection on its first and last lines stays, the
+    // newline the command adds at the end only when the input had it
+    let lines: Vec<String> = match &selection {
+        Some(Selection { start, end }) => {
+            let output = match input.ends_with('\n') {
+                true => &output[..],
+                false => output.strip_suffix('\n').unwrap_or(&output),
+            };
+            let head = &doc.buffer[start.line][..start.byte];
+            let tail = &doc.buffer[end.line][end.byte..];
+            format!("{}{}{}", head, output, tail)
+                .split('\n')
+                .map(|line| line.strip_suffix('\r').unwrap_or(line).to_owned())
+                .collect()
+        }
+        None => match text::split_lines(output.as_bytes()) {
+            lines if lines.is_empty() => vec![String::new()],
+            lines => lines,
+        },
+    };
+    let new_len = lines.len();
+
+    let position = undo_location(doc, cursor, cursor_state);
+    let pending = PendingChange::begin(doc, start, end - start, position);
+    doc.replace_lines(start, end, lines);
+    editor_state.edits.record(&doc.path, start, 0);
+    editor_state.undo.record(
+        doc, pending.commit(EditKind::Replace, doc, position));
+
+    drop_selection(cursor_state, render_state);
+    editor_state.doc_lines = doc.buffer.len();
+    render_state.modif_all = true;
+    render_state.modif_status = true;
+    cursor.clamp_to_document(doc, cursor_state, render_state);
+
+    Ok(format!("{} lines piped through `{}`, {} lines now", 
+        end - start, command, new_len))
+}
+
+/// Delete the file, the one of the document without a path. It goes to the
+/// trash unless the `trash` option is off, and from there `undelete` puts
+/// it back. The document of the file keeps its text as changes not saved
+fn remove_file(
+    args: &str,
+    doc: &mut Option<Document>,
+    editor_state: &mut EditorState,
+) -> Result<String> {
+    let path = match (args.is_empty(), doc.as_ref()) {
+        (false, _) => PathBuf::from(args),
+        (true, Some(doc)) => doc.path.clone(),
+        (true, None) => return Err("usage: remove <path>".into()),
+    };
+    if path.is_dir() {
+        return Err(format!("{} is a directory", path.display()).into());
+    }
+    let open = doc.as_mut()
+        .filter(|doc| fileops::same_file(&doc.path, &path));
+
+    let message = if editor_state.trash {
+        editor_state.trashed.push(fileops::trash(&path)?);
+        format!("{} moved to the trash, `undelete` puts it back", 
+            path.display())
+    } else {
+        fileops::remove(&path)?;
+        format!("{} removed", path.display())
+    };
+
+    if let Some(doc) = open {
+        doc.dirty = true;
+        editor_state.watcher.unwatch(&doc.path);
+    }
+
+    Ok(message)
+}
+
+/// Put back the last file moved to the trash on the session
+fn undelete_file(
+    doc: &Option<Document>,
+    editor_state: &mut EditorState,
+) -> Result<String> {
+    let trashed = editor_state.trashed.pop()
+        .ok_or("no file was moved to the trash")?;
+    if let Err(err) = trashed.restore() {
+        editor_state.trashed.push(trashed);
+        return Err(err);
+    }
+
+    if let Some(doc) = doc.as_ref() {
+        if fileops::same_file(&doc.path, &trashed.original) {
+            editor_state.watcher.watch(&doc.path);
+        }
+    }
+
+    Ok(format!("{} put back", trashed.original.display()))
+}
+
+/// Preview the document with the program given or else the one of its
+/// filetype, saving it refreshes the preview from now on
+fn preview_panel(
+    doc: &Document,
+    command: Option<&str>,
+    editor_state: &mut EditorState,
+) -> Result<Panel> {
+    let command = command
+        .or_else(|| editor_state.previews.of(doc))
+        .ok_or_else(|| format!("no preview program for the filetype {}, \
+            set one on the [preview] table of the config",
+            doc.filetype().unwrap_or("none")))?
+        .to_owned();
+
+    let cancel = editor_state.events.cancel_token();
+    let _operation = cancel.start();
+    let preview = Preview::run(doc, &command, &cancel)?;
+    let panel = preview.panel();
+    editor_state.preview = Some(preview);
+
+    Ok(panel)
+}
+
+/// Send the selection, or the document when nothing is selected, to the
+/// tool, its output is the message or a panel when it's longer than a line
+fn export_text(
+    tool: &str,
+    doc: &Document,
+    cursor: &Cursor,
+    editor_state: &mut EditorState,
+    cursor_state: &CursorState,
+    render_state: &mut RenderState,
+) -> Result<String> {
+    let command = match editor_state.export_tools.command(tool) {
+        Some(command) => command.to_owned(),
+        None if !tool.is_empty() => tool.to_owned(),
+        None => {
+            let names = editor_state.export_tools.names();
+            if names.is_empty() {
+                return Err("usage: export <command>, or set the tools on \
+                    the [export] table of the config".into());
+            }
+            return Err(format!("usage: export <tool>, one of: {}",
+                names.join(", ")).into());
+        }
+    };
+    let text = cursor.selection(doc, cursor_state)
+        .filter(|selection| !selection.is_empty())
+        .map(|selection| selection.text(doc))
+        .unwrap_or_else(|| doc.buffer.join("\n"));
+
+    let cancel = editor_state.events.cancel_token();
+    let _operation = cancel.start();
+    let mut lines = export::export(&command, &text, &cancel)?;
+    match lines.len() {
+        0 => Ok(format!("Exported to `{}`", command)),
+        1 => Ok(lines.remove(0)),
+        _ => {
+            editor_state.panel = Some(Panel::new(
+                format!("Exported to `{}`", command), lines));
+            render_state.modif_all = true;
+            Ok(String::new())
+        }
+    }
+}
+
+/// Show again the preview of the document after saving it refreshed it
+fn show_refreshed_preview(doc: &Document, editor_state: &mut EditorState) {
+    if let Some(preview) = editor_state.preview.as_ref()
+            .filter(|preview| preview.path == doc.path) {
+        editor_state.panel = Some(preview.panel());
+    }
+}
+
+/// Rename the file of the document on disk, if the rename succeeds every
+/// place that refers to the old path is updated
+fn rename_document(
+    args: &str,
+    doc: &mut Option<Document>,
+    editor_state: &mut EditorState,
+) -> Result<String> {
+    let doc = doc.as_mut().ok_or("no document opened")?;
+    if args.is_empty() {
+        return Err("usage: rename <new path>".into());
+    }
+
+    let old_path = doc.path.clone();
+    let new_path = fileops::rename(&old_path, Path::new(args))?;
+    doc.path = new_path.clone();
+
+    // The other buffers and tabs may be showing the same file
+    let EditorState { tabs, buffers, .. } = &mut *editor_state;
+    for other in tabs.others_mut().chain(buffers.others_mut()).flatten() {
+        if other.path == old_path {
+            other.path = new_path.clone();
+        }
+    }
+
+    editor_state.bookmarks.rename_file(&old_path, &new_path);
+    editor_state.undo.rename_file(&old_path, &new_path);
+    editor_state.review.rename_file(&old_path, &new_path);
+    editor_state.marks.rename_file(&old_path, &new_path);
+    editor_state.jumps.rename_file(&old_path, &new_path);
+    editor_state.edits.rename_file(&old_path, &new_path);
+    editor_state.bookmarks.save()?;
+    editor_state.watcher.unwatch(&old_path);
+    editor_state.watcher.watch(&new_path);
+    editor_state.swaps.release(&old_path);
+    editor_state.swaps.claim(&new_path);
+
+    Ok(format!("Renamed {} to {}", old_path.display(), new_path.display()))
+}
+
+/// Handle the input while a panel is open, every panel can be scrolled or
+/// closed, the rest of actions depend on its kind
+fn process_panel_event(
+    event: &Event,
+    doc: &mut Option<Document>,
+    cursor: &mut Cursor,
+    editor_state: &mut EditorState,
+    cursor_state: &mut CursorState,
+    render_state: &mut RenderState,
+) -> Result<()> {
+    // The first row of the panel is its title
+    let panel_rows = editor_state.rows.saturating_sub(1);
+    let target = match event {
+        Event::Mouse(MouseEvent { column, row, .. }) => 
+            Some(mouse::target(*column, *row, doc, editor_state)),
+        _ => None,
+    };
+
+    let panel = match editor_state.panel.as_mut() {
+        Some(panel) => panel,
+        None => return Ok(()),
+    };
+    render_state.modif_all = true;
+
+    // The selected entry is being edited, so the keys are text
+    if let Some(input) = panel.input.as_mut() {
+        match event {
+            Event::Key(KeyEvent {
+                code: KeyCode::Char(c),
+                modifiers, ..
+            }) if !modifiers.intersects(
+                    KeyModifiers::CONTROL | KeyModifiers::ALT) => {
+                input.push(*c);
+            }
+            Event::Key(KeyEvent { code: KeyCode::Backspace, .. }) => {
+                input.pop();
+            }
+            Event::Key(KeyEvent { code: KeyCode::Esc, .. }) => {
+                panel.input = None;
+            }
+            Event::Key(KeyEvent { code: KeyCode::Enter, .. }) => {
+                let text = panel.input.take().unwrap_or_default();
+                if let (PanelKind::Bookmarks, Some(idx)) = 
+                        (panel.kind, panel.selected) {
+                    editor_state.bookmarks.set_note(idx, &text);
+                    update_bookmarks_panel(
+                        panel, &editor_state.bookmarks);
+                }
+            }
+            _ => {}
+        }
+
+        return Ok(());
+    }
+
+    match event {
+        Event::Key(KeyEvent {
+            code: KeyCode::Esc | KeyCode::Char('q'),
+            ..
+        }) => {
+            editor_state.panel = None;
+            render_state.modif_status = true;
+        }
+        Event::Key(KeyEvent { code: KeyCode::Up, .. })
+        | Event::Mouse(MouseEvent {
+            kind: MouseEventKind::ScrollUp,
+            ..
+        }) => panel.scroll_up(1),
+        Event::Key(KeyEvent { code: KeyCode::Down, .. })
+        | Event::Mouse(MouseEvent {
+            kind: MouseEventKind::ScrollDown,
+            ..
+        }) => panel.scroll_down(1, panel_rows),
+        Event::Mouse(MouseEvent { 
+            kind: MouseEventKind::Down(MouseButton::Right), 
+            ..
+        }) => {
+            editor_state.panel = None;
+            render_state.modif_status = true;
+        }
+
+        // A click selects the entry under it, clicking the selected entry
+        // does what Enter does
+        Event::Mouse(MouseEvent { 
+            kind: MouseEventKind::Down(MouseButton::Left), 
+            ..
+        }) if panel.selected.is_some() => {
+            if let Some(Target::PanelRow(Some(idx))) = target {
+                if panel.selected == Some(idx) {
+                    let enter = Event::Key(
+                        KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+                    return process_panel_event(&enter, doc, cursor, 
+                        editor_state, cursor_state, render_state);
+                }
+                panel.select(idx, panel_rows);
+            }
+        }
+        Event::Key(KeyEvent { code: KeyCode::Char('b'), .. }) => {
+            let panel = editor_state.panel.take();
+            if let Some(panel) = panel {
+                panel_buffer(
+                    &panel, doc, cursor, editor_state, cursor_state,
+                    render_state)?;
+            }
+            render_state.modif_status = true;
+        }
+        Event::Key(KeyEvent { code: KeyCode::PageUp, .. }) => 
+            panel.scroll_up(panel_rows),
+        Event::Key(KeyEvent { code: KeyCode::PageDown, .. }) => 
+            panel.scroll_down(panel_rows, panel_rows),
+
+        // Bookmarks actions
+        Event::Key(KeyEvent { code: KeyCode::Enter, .. })
+                if panel.kind == PanelKind::Bookmarks => {
+            if let Some(idx) = panel.selected {
+                jump_to_bookmark(
+                    idx, doc, cursor, editor_state, cursor_state, render_state);
+            }
+        }
+        Event::Key(KeyEvent { code: KeyCode::Char('e'), .. })
+                if panel.kind == PanelKind::Bookmarks => {
+            if let Some(idx) = panel.selected {
+                panel.input = 
+                    Some(editor_state.bookmarks.list[idx].note.clone());
+            }
+        }
+        Event::Key(KeyEvent { code: KeyCode::Char('d'), .. })
+                if panel.kind == PanelKind::Bookmarks => {
+            if let Some(idx) = panel.selected {
+                editor_state.bookmarks.remove(idx);
+                update_bookmarks_panel(panel, &editor_state.bookmarks);
+            }
+        }
+
+        // Snapshots actions, the selected line is the snapshot of the
+        // document in the same position
+        Event::Key(KeyEvent { code: KeyCode::Enter, .. })
+                if panel.kind == PanelKind::Snapshots => {
+            let selected = 
+                selected_snapshot(panel, doc, &editor_state.snapshots);
+            if let (Some(idx), Some(doc)) = (selected, doc.as_ref()) {
+                *panel = snapshot_diff_panel(
+                    &editor_state.snapshots.list[idx], doc);
+            }
+        }
+        Event::Key(KeyEvent { code: KeyCode::Char('r'), .. })
+                if panel.kind == PanelKind::Snapshots => {
+            let selected = 
+                selected_snapshot(panel, doc, &editor_state.snapshots);
+            if let Some(idx) = selected {
+                editor_state.panel = None;
+                let message = restore_snapshot(
+                    idx, doc, cursor, editor_state, cursor_state, render_state)
+                    .unwrap_or_else(|err| format!("Error: {}", err));
+                editor_state.message.show(message);
+                render_state.modif_status = true;
+            }
+        }
+        Event::Key(KeyEvent { code: KeyCode::Char('d'), .. })
+                if panel.kind == PanelKind::Snapshots => {
+            let selected = 
+                selected_snapshot(panel, doc, &editor_state.snapshots);
+            if let (Some(idx), Some(doc)) = (selected, doc.as_ref()) {
+                editor_state.snapshots.list.remove(idx);
+                panel.set_lines(editor_state.snapshots.lines(&doc.path));
+            }
+        }
+
+        // TODOs actions, a file jumps to its first TODO
+        Event::Key(KeyEvent { code: KeyCode::Enter, .. })
+                if panel.kind == PanelKind::Todos => {
+            if let Some(row) = panel.selected {
+                jump_to_todo(
+                    row, doc, cursor, editor_state, cursor_state, render_state);
+            }
+        }
+
+        // Matches actions
+        Event::Key(KeyEvent { code: KeyCode::Enter, .. })
+                if panel.kind == PanelKind::Matches => {
+            if let Some(idx) = panel.selected {
+                jump_to_match(
+                    idx, doc, cursor, editor_state, cursor_state, render_state);
+            }
+        }
+
+        // Quickfix actions
+        Event::Key(KeyEvent { code: KeyCode::Enter, .. })
+                if panel.kind == PanelKind::Quickfix => {
+            if let Some(idx) = panel.selected {
+                jump_to_error(
+                    idx, doc, cursor, editor_state, cursor_state, render_state);
+            }
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+/// Handle the keys of the file picker and the command palette: the query
+/// is typed on their prompt and the matches are filtered as it changes, the
+/// arrows move through them and Enter opens the selected file or runs the
+/// selected command
+fn process_finder_event(
+    key: &KeyEvent,
+    doc: &mut Option<Document>,
+    cursor: &mut Cursor,
+    editor_state: &mut EditorState,
+    cursor_state: &mut CursorState,
+    render_state: &mut RenderState,
+) -> Result<()> {
+    let panel_rows = editor_state.rows.saturating_sub(1);
+    let (prompt, panel) = 
+        match (editor_state.prompt.as_mut(), editor_state.panel.as_mut()) {
+            (Some(prompt), Some(panel)) 
+                if matches!(panel.kind, PanelKind::Files | PanelKind::Commands)
+                => (prompt, panel),
+            _ => {
+                editor_state.prompt = None;
+                return Ok(());
+            }
+        };
+    render_state.modif_all = true;
+    render_state.modif_status = true;
+
+    match key.code {
+        KeyCode::Up => panel.scroll_up(1),
+        KeyCode::Down => panel.scroll_down(1, panel_rows),
+        KeyCode::PageUp => panel.scroll_up(panel_rows),
+        KeyCode::PageDown => panel.scroll_down(panel_rows, panel_rows),
+        _ => match prompt.key(key) {
+            Answer::Typing => {
+                let lines = match panel.kind {
+                    PanelKind::Commands => 
+                        palette_lines(&prompt.input, &editor_state.keymap),
+                    _ => match &editor_state.index {
+                        Some(index) => index.matching(&prompt.input),
+                        None => Vec::new(),
+                    },
+                };
+                panel.set_lines(lines);
+                if !panel.lines.is_empty() {
+                    panel.select(0, panel_rows);
+                }
+            }
+            Answer::Done(answer) => {
+                let kind = panel.kind;
+                let selected = panel.selected
+                    .and_then(|idx| panel.lines.get(idx))
+                    .filter(|_| answer.is_some())
+                    .cloned();
+                editor_state.prompt = None;
+                editor_state.panel = None;
+                match (kind, selected) {
+                    (PanelKind::Commands, Some(line)) => {
+                        let name = line.split_whitespace().next()
+                            .unwrap_or_default();
+                        if let Some(action) = Action::from_name(name) {
+                            execute(action, doc, cursor, editor_state, 
+                                cursor_state, render_state)?;
+                        }
+                    }
+                    (_, Some(path)) => open_buffer(
+                        Path::new(&path), doc, cursor, editor_state, 
+                        cursor_state, render_state)?,
+                    (_, None) => {}
+                }
+            }
+        },
+    }
+
+    Ok(())
+}
+
+/// The entries of the command palette matching the query: the name of each
+/// command, what it does and the key bound to it
+fn palette_lines(query: &str, keymap: &Keymap) -> Vec<String> {
+    let names = COMMANDS.iter().map(|command| command.name);
+    finder::rank(names, query)
+        .into_iter()
+        .filter_map(|name| COMMANDS.iter().find(|command| command.name == name))
+        .map(|command| {
+            let key = keymap.binding_of(command.action)
+                .map(|binding| format!("  [{}]", binding))
+                .unwrap_or_default();
+            format!("{:24}{}{}", command.name, command.description, key)
+        })
+        .collect()
+}
+
+/// The lines of the help: the commands of each category with the keys bound
+/// to them, as the keymap is now. The ones without keys are run by name
+fn help_lines(keymap: &Keymap) -> Vec<String> {
+    let keys_of = |action| keymap.bindings_of(action)
+        .iter()
+        .map(|binding| binding.to_string())
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    // The keys, the name and what it does, with the tabs switched to by
+    // their number after the rest of their category
+    let mut rows: Vec<(&str, String, String, String)> = COMMANDS
+        .iter()
+        .map(|command| (command.action.category(), keys_of(command.action), 
+            command.name.to_owned(), command.description.to_owned()))
+        .collect();
+    rows.extend((0..9)
+        .filter(|tab| keymap.binding_of(Action::SwitchTab(*tab)).is_some())
+        .map(|tab| (Action::SwitchTab(tab).category(), 
+            keys_of(Action::SwitchTab(tab)), format!("tab-{}", tab + 1), 
+            format!("Switch to the tab {}", tab + 1))));
+
+    let keys_width = rows.iter().map(|(_, keys, _, _)| keys.len()).max()
+        .unwrap_or(0);
+    let name_width = rows.iter().map(|(_, _, name, _)| name.len()).max()
+        .unwrap_or(0);
+    let mut lines = Vec::new();
+    for category in CATEGORIES {
+        if !lines.is_empty() {
+            lines.push(String::new());
+        }
+        lines.push(category.to_owned());
+        lines.extend(rows
+            .iter()
+            .filter(|(of, _, _, _)| *of == category)
+            .map(|(_, keys, name, description)| format!("  {:keys_width$}  \
+                {:name_width$}  {}", keys, name, description)));
+    }
+
+    lines
+}
+
+/// The categories of the help stand out from their commands
+fn help_highlight(line: &str) -> Color {
+    if line.starts_with(' ') { Color::Reset } else { Color::Cyan }
+}
+
+/// Create the panel listing the bookmarks of the project
+fn bookmarks_panel(bookmarks: &Bookmarks) -> Panel {
+    Panel::list(
+        PanelKind::Bookmarks,
+        "Bookmarks -- [Enter] jump  [e] edit note  [d] delete",
+        bookmarks.lines())
+}
+
+/// Persist the bookmarks after a change and show them again on the panel,
+/// if they can't be written the panel title tells it
+fn update_bookmarks_panel(panel: &mut Panel, bookmarks: &Bookmarks) {
+    panel.set_lines(bookmarks.lines());
+    if let Err(err) = bookmarks.save() {
+        panel.title = format!("Bookmarks -- could not save: {}", err);
+    }
+}
+
+/// Close the panel and go to the bookmarked line, if it's on another file
+/// that file replaces the current document
+fn jump_to_bookmark(
+    idx: usize,
+    doc: &mut Option<Document>,
+    cursor: &mut Cursor,
+    editor_state: &mut EditorState,
+    cursor_state: &mut CursorState,
+    render_state: &mut RenderState,
+) {
+    let path = editor_state.bookmarks.list[idx].path.clone();
+    let line = editor_state.bookmarks.list[idx].line;
+
+    match jump_to_location(
+            &path, line, None, doc, cursor, editor_state, cursor_state, 
+            render_state) {
+        Ok(()) => editor_state.panel = None,
+        Err(err) => {
+            if let Some(panel) = editor_state.panel.as_mut() {
+                panel.title = format!("Bookmarks -- could not open {}: {}",
+                    path.display(), err);
+            }
+        }
+    }
+}
+
+/// Close the panel and go to the position of the error
+fn jump_to_error(
+    idx: usize,
+    doc: &mut Option<Document>,
+    cursor: &mut Cursor,
+    editor_state: &mut EditorState,
+    cursor_state: &mut CursorState,
+    render_state: &mut RenderState,
+) {
+    let entry = editor_state.quickfix.entries[idx].clone();
+
+    match jump_to_location(
+            &entry.path, entry.line, Some(entry.column), doc, cursor, 
+            editor_state, cursor_state, render_state) {
+        Ok(()) => editor_state.panel = None,
+        Err(err) => {
+            if let Some(panel) = editor_state.panel.as_mut() {
+                panel.title = format!("Errors -- could not open {}: {}",
+                    entry.path.display(), err);
+            }
+        }
+    }
+}
+
+/// Jump to the match of the search on the line `idx` of the panel, the
+/// lines that aren't matches, like the errors, do nothing
+fn jump_to_match(
+    idx: usize,
+    doc: &mut Option<Document>,
+    cursor: &mut Cursor,
+    editor_state: &mut EditorState,
+    cursor_state: &mut CursorState,
+    render_state: &mut RenderState,
+) {
+    let location = editor_state.panel.as_ref()
+        .and_then(|panel| panel.lines.get(idx))
+        .and_then(|line| grep::location(line));
+    let (path, line) = match location {
+        Some(location) => location,
+        None => return,
+    };
+
+    match jump_to_location(
+            &path, line, None, doc, cursor, editor_state, cursor_state, 
+            render_state) {
+        Ok(()) => editor_state.panel = None,
+        Err(err) => {
+            if let Some(panel) = editor_state.panel.as_mut() {
+                panel.title = format!("Matches -- could not open {}: {}",
+                    path.display(), err);
+            }
+        }
+    }
+}
+
+/// Scan the project for the TODO comments and list them on a panel
+fn todos_panel(editor_state: &mut EditorState) -> Result<Panel> {
+    let index = editor_state.index.as_ref()
+        .ok_or("the project is not indexed")?;
+    let cancel = editor_state.events.cancel_token();
+    let _operation = cancel.start();
+    editor_state.todos = TodoList::scan(
+        index.root(), &index.files(), &editor_state.todo_patterns, &cancel)?;
+
+    let count = editor_state.todos.entries.len();
+    Ok(Panel::list(
+        PanelKind::Todos,
+        format!("{} TODOs -- [Enter] jump", count),
+        editor_state.todos.lines()))
+}
+
+/// Close the panel and go to the TODO of the row of the panel, its file
+/// replaces the current document
+fn jump_to_todo(
+    row: usize,
+    doc: &mut Option<Document>,
+    cursor: &mut Cursor,
+    editor_state: &mut EditorState,
+    cursor_state: &mut CursorState,
+    render_state: &mut RenderState,
+) {
+    let (todo, root) = match (editor_state.todos.at_row(row),
+            &editor_state.index) {
+        (Some(todo), Some(index)) => (todo.clone(), index.root().to_owned()),
+        _ => return,
+    };
+
+    let path = root.join(&todo.file);
+    match jump_to_location(
+            &path, todo.line, Some(todo.column), doc, cursor,
+            editor_state, cursor_state, render_state) {
+        Ok(()) => editor_state.panel = None,
+        Err(err) => {
+            if let Some(panel) = editor_state.panel.as_mut() {
+                panel.title = format!("TODOs -- could not open {}: {}",
+                    todo.file.display(), err);
+            }
+        }
+    }
+}
+
+/// Go to a position of a file, if it's not the current document it
+/// replaces it. Without column the cursor goes to the start of the text.
+/// Where the cursor was goes to the jump list
+#[allow(clippy::too_many_arguments)]
+pub fn jump_to_location(
+    path: &Path,
+    line: usize,
+    column: Option<usize>,
+    doc: &mut Option<Document>,
+    cursor: &mut Cursor,
+    editor_state: &mut EditorState,
+    cursor_state: &mut CursorState,
+    render_state: &mut RenderState,
+) -> Result<()> {
+    if let Some(doc) = doc {
+        editor_state.jumps.record(location(doc, cursor, cursor_state));
+    }
+
+    go_to_location(path, line, column, doc, cursor, editor_state,
+        cursor_state, render_state)
+}
+
+/// The place of the cursor on the document
+fn location(doc: &Document, cursor: &Cursor, cursor_state: &CursorState)
+        -> Location {
+    Location {
+        path: doc.path.clone(),
+        line: cursor.line(cursor_state),
+        column: cursor.column,
+    }
+}
+
+/// Go to a position of a file like `jump_to_location`, without remembering
+/// where the cursor was
+#[allow(clippy::too_many_arguments)]
+fn go_to_location(
+    path: &Path,
+    line: usize,
+    column: Option<usize>,
+    doc: &mut Option<Document>,
+    cursor: &mut Cursor,
+    editor_state: &mut EditorState,
+    cursor_state: &mut CursorState,
+    render_state: &mut RenderState,
+) -> Result<()> {
+    let same_file = doc.as_ref()
+        .map(|doc| fileops::same_file(&doc.path, path))
+        .unwrap_or(false);
+    if !same_file {
+        open_buffer(
+            path, doc, cursor, editor_state, cursor_state, render_state)?;
+
+        // An encrypted file waits for its passphrase
+        if editor_state.passphrase.is_some() {
+            return Ok(());
+        }
+    }
+
+    render_state.modif_status = true;
+
+    if let Some(doc) = doc {
+        cursor.jump_to_line(line, editor_state, cursor_state, render_state);
+        if !doc.buffer.is_empty() {
+            match column {
+                Some(column) => {
+                    cursor.column = column;
+                    cursor.clamp_to_document(doc, cursor_state, render_state);
+                }
+                None => cursor.adjust_column_start(doc, cursor_state),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Open a new tab after the current one showing the same view, and switch
+/// to it
+fn new_tab(
+    doc: &mut Option<Document>,
+    cursor: &mut Cursor,
+    editor_state: &mut EditorState,
+    cursor_state: &mut CursorState,
+    render_state: &mut RenderState,
+) -> Result<()> {
+    // The document stays a single one, the new tab takes it and the current
+    // one keeps its placeholder
+    let tabs = &mut editor_state.tabs;
+    tabs.list.insert(tabs.current + 1, Tab {
+        doc: doc.as_ref().map(|doc| Document::placeholder(&doc.path)),
+        cursor: *cursor,
+        cursor_state: cursor_state.clone(),
+        panel: None,
+        buffers: BufferList::default(),
+        window: editor_state.window,
+    });
+
+    let to = tabs.current + 1;
+    switch_tab(to, doc, cursor, editor_state, cursor_state, render_state)
+}
+
+/// Store the state of the current tab and load the one of the tab `to`
+fn switch_tab(
+    to: usize,
+    doc: &mut Option<Document>,
+    cursor: &mut Cursor,
+    editor_state: &mut EditorState,
+    cursor_state: &mut CursorState,
+    render_state: &mut RenderState,
+) -> Result<()> {
+    let tabs = &mut editor_state.tabs;
+    if to >= tabs.list.len() || to == tabs.current {
+        return Ok(());
+    }
+
+    // The current state goes to the tab and the state of the new tab comes
+    // out, the slot of the new current tab keeps stale data
+    for idx in [tabs.current, to] {
+        let tab = &mut tabs.list[idx];
+        std::mem::swap(&mut tab.doc, doc);
+        std::mem::swap(&mut tab.cursor, cursor);
+        std::mem::swap(&mut tab.cursor_state, cursor_state);
+        std::mem::swap(&mut tab.panel, &mut editor_state.panel);
+        std::mem::swap(&mut tab.buffers, &mut editor_state.buffers);
+        std::mem::swap(&mut tab.window, &mut editor_state.window);
+    }
+    tabs.current = to;
+
+    after_tab_change(doc, cursor, editor_state, cursor_state, render_state)
+}
+
+/// Close the current tab and load the one that takes its place, the last
+/// tab can't be closed
+fn close_tab(
+    doc: &mut Option<Document>,
+    cursor: &mut Cursor,
+    editor_state: &mut EditorState,
+    cursor_state: &mut CursorState,
+    render_state: &mut RenderState,
+) -> Result<()> {
+    let EditorState { tabs, buffers, .. } = editor_state;
+    if tabs.list.len() == 1 {
+        return Ok(());
+    }
+
+    // The documents the other tabs show go to them
+    hand_over(doc, tabs.others_mut());
+    for buffer_doc in buffers.others_mut() {
+        hand_over(buffer_doc, tabs.others_mut());
+    }
+
+    tabs.list.remove(tabs.current);
+    tabs.current = usize::min(tabs.current, tabs.list.len() - 1);
+
+    let tab = &mut tabs.list[tabs.current];
+    std::mem::swap(&mut tab.doc, doc);
+    std::mem::swap(&mut tab.cursor, cursor);
+    std::mem::swap(&mut tab.cursor_state, cursor_state);
+    std::mem::swap(&mut tab.panel, &mut editor_state.panel);
+    std::mem::swap(&mut tab.buffers, &mut editor_state.buffers);
+    std::mem::swap(&mut tab.window, &mut editor_state.window);
+
+    after_tab_change(doc, cursor, editor_state, cursor_state, render_state)
+}
+
+/// Open the path under the cursor on a buffer, at the line of its suffix,
+/// or the URL on the browser
+fn open_under_cursor(
+    doc: &mut Option<Document>,
+    cursor: &mut Cursor,
+    editor_state: &mut EditorState,
+    cursor_state: &mut CursorState,
+    render_state: &mut RenderState,
+) -> Result<()> {
+    let (link, dir) = match doc {
+        Some(doc) => {
+            let line = doc.buffer.line(cursor.line(cursor_state)).unwrap_or("");
+            let dir = doc.path.parent().map(Path::to_owned);
+            (links::at(line, cursor.column), dir)
+        }
+        None => return Ok(()),
+    };
+
+    match link {
+        Some(Link::Url(url)) => {
+            links::open_url(&url)?;
+            editor_state.message.show(format!("Opened {}", url));
+        }
+        Some(Link::Path { path, line, column }) => {
+            let file = links::resolve(&path, dir.as_deref())
+                .ok_or_else(|| format!("there is no file {}", path.display()))?;
+            match line {
+                Some(line) => jump_to_location(
+                    &file, line, column, doc, cursor, editor_state,
+                    cursor_state, render_state)?,
+                None => open_buffer(
+                    &file, doc, cursor, editor_state, cursor_state,
+                    render_state)?,
+            }
+        }
+        None => editor_state.message.show("No path or URL under the cursor"),
+    }
+
+    Ok(())
+}
+
+/// Open the file on a new buffer after the current one and switch to it,
+/// for a file already open it only switches to its buffer, even on another
+/// tab, so there are never two copies of it. The blank buffer of the editor
+/// started without a file is reused
+fn open_buffer(
+    path: &Path,
+    doc: &mut Option<Document>,
+    cursor: &mut Cursor,
+    editor_state: &mut EditorState,
+    cursor_state: &mut CursorState,
+    render_state: &mut RenderState,
+) -> Result<()> {
+    if let Some(idx) = editor_state.buffers.find(path, doc) {
+        return switch_buffer(
+            idx, doc, cursor, editor_state, cursor_state, render_state);
+    }
+    if let Some((tab, idx)) = editor_state.tabs.find_elsewhere(path) {
+        switch_tab(tab, doc, cursor, editor_state, cursor_state, render_state)?;
+        return switch_buffer(
+            idx, doc, cursor, editor_state, cursor_state, render_state);
+    }
+
+    // The gpg files are opened once their passphrase is typed
+    if crypt::detect(path) == Some(Cipher::Gpg) {
+        editor_state.passphrase = Some(PassphrasePrompt::new(path));
+        render_state.modif_status = true;
+        return Ok(());
+    }
+
+    let new_doc = editor_state.open_document(path, None)?;
+    insert_buffer(
+        new_doc, doc, cursor, editor_state, cursor_state, render_state)
+}
+
+/// Open the lines of the panel on a preview buffer named as its title, so
+/// they can be searched and copied. The preview of a panel opened before
+/// gets the new lines
+fn panel_buffer(
+    panel: &Panel,
+    doc: &mut Option<Document>,
+    cursor: &mut Cursor,
+    editor_state: &mut EditorState,
+    cursor_state: &mut CursorState,
+    render_state: &mut RenderState,
+) -> Result<()> {
+    let title = panel.title.split(" -- ").next().unwrap_or_default();
+    let preview = Document::preview(format!("[{}]", title.trim()), &panel.lines);
+    let idx = match editor_state.buffers.find(&preview.path, doc) {
+        Some(idx) => idx,
+        None => return insert_buffer(
+            preview, doc, cursor, editor_state, cursor_state, render_state),
+    };
+
+    switch_buffer(idx, doc, cursor, editor_state, cursor_state, render_state)?;
+    (*cursor, *cursor_state) = editor_state.left_at(&preview);
+    *doc = Some(preview);
+    after_tab_change(doc, cursor, editor_state, cursor_state, render_state)
+}
+
+/// Show the document just opened on a new buffer after the current one, or
+/// on the blank buffer of the editor started without a file
+fn insert_buffer(
+    new_doc: Document,
+    doc: &mut Option<Document>,
+    cursor: &mut Cursor,
+    editor_state: &mut EditorState,
+    cursor_state: &mut CursorState,
+    render_state: &mut RenderState,
+) -> Result<()> {
+    // The document opens where it was left
+    let (new_cursor, new_cursor_state) = editor_state.left_at(&new_doc);
+    if doc.is_none() {
+        *doc = Some(new_doc);
+        *cursor = new_cursor;
+        *cursor_state = new_cursor_state;
+        render_state.modif_all = true;
+        return after_tab_change(
+            doc, cursor, editor_state, cursor_state, render_state);
+    }
+
+    let buffers = &mut editor_state.buffers;
+    buffers.insert(buffers.current + 1, OpenBuffer {
+        doc: Some(new_doc),
+        cursor: new_cursor,
+        cursor_state: new_cursor_state,
+    });
+
+    let to = buffers.current + 1;
+    switch_buffer(to, doc, cursor, editor_state, cursor_state, render_state)
+}
+
+/// Store the state of the current buffer and load the one of the buffer
+/// `to`
+fn switch_buffer(
+    to: usize,
+    doc: &mut Option<Document>,
+    cursor: &mut Cursor,
+    editor_state: &mut EditorState,
+    cursor_state: &mut CursorState,
+    render_state: &mut RenderState,
+) -> Result<()> {
+    let buffers = &mut editor_state.buffers;
+    if to >= buffers.list.len() || to == buffers.current {
+        return Ok(());
+    }
+
+    // Same as the tabs, the slot of the new current buffer keeps stale data
+    for idx in [buffers.current, to] {
+        let buffer = &mut buffers.list[idx];
+        std::mem::swap(&mut buffer.doc, doc);
+        std::mem::swap(&mut buffer.cursor, cursor);
+        std::mem::swap(&mut buffer.cursor_state, cursor_state);
+    }
+    buffers.alternate = Some(buffers.current);
+    buffers.current = to;
+
+    after_tab_change(doc, cursor, editor_state, cursor_state, render_state)
+}
+
+/// Close the current buffer and load the one that takes its place, closing
+/// the last one leaves the editor blank. With unsaved changes it's only
+/// closed when forced
+fn close_buffer(
+    force: bool,
+    doc: &mut Option<Document>,
+    cursor: &mut Cursor,
+    editor_state: &mut EditorState,
+    cursor_state: &mut CursorState,
+    render_state: &mut RenderState,
+) -> Result<String> {
+    // Shown on another buffer or tab the document goes there, only the
+    // view of it is closed
+    let EditorState { tabs, buffers, .. } = editor_state;
+    hand_over(doc, tabs.others_mut().chain(buffers.others_mut()));
+
+    let closed = match doc.take() {
+        Some(closed) if closed.dirty && !force => {
+            *doc = Some(closed);
+            return Err("the buffer has unsaved changes, `bclose!` closes \
+                it anyway".into());
+        }
+        Some(closed) => closed,
+        None => return Err("no document opened".into()),
+    };
+    editor_state.places.remember(Place::of(&closed, cursor, cursor_state));
+
+    let buffers = &mut editor_state.buffers;
+    if buffers.list.len() == 1 {
+        *cursor = Cursor::default();
+        *cursor_state = CursorState::default();
+    } else {
+        buffers.remove_current();
+
+        let buffer = &mut buffers.list[buffers.current];
+        std::mem::swap(&mut buffer.doc, doc);
+        std::mem::swap(&mut buffer.cursor, cursor);
+        std::mem::swap(&mut buffer.cursor_state, cursor_state);
+    }
+
+    // The file is only left alone when no other buffer shows it
+    if !open_documents(doc, editor_state).any(|doc| doc.path == closed.path) {
+        editor_state.watcher.unwatch(&closed.path);
+        editor_state.swaps.release(&closed.path);
+    }
+    after_tab_change(doc, cursor, editor_state, cursor_state, render_state)?;
+
+    Ok(format!("Closed {}", closed.path.display()))
+}
+
+/// Update the editor to the tab or buffer just loaded, the tabs bar may
+/// have appeared or disappeared so the cursor is kept inside the document
+/// area
+fn after_tab_change(
+    doc: &mut Option<Document>,
+    cursor: &mut Cursor,
+    editor_state: &mut EditorState,
+    cursor_state: &mut CursorState,
+    render_state: &mut RenderState,
+) -> Result<()> {
+    take_shared(doc, editor_state)?;
+    editor_state.doc_lines = doc.as_ref()
+        .map(|doc| doc.buffer.len())
+        .unwrap_or(0);
+    editor_state.update_size();
+
+    let rows = editor_state.rows;
+    if rows > 0 && cursor.row >= rows {
+        // The cursor was left below the smaller view
+        let line = cursor.line(cursor_state);
+        let position = Position { line, ..Position::default() };
+        cursor_state.viewport.ensure_visible(position, rows);
+        cursor.row = line - cursor_state.viewport.scroll_y;
+    }
+
+    render_state.modif_all = true;
+    render_state.modif_status = true;
+
+    Ok(())
+}
+
+/// The document shown is the one of its file: a placeholder takes it from
+/// the buffer or the tab where it is, which keeps the placeholder instead
+fn take_shared(
+    doc: &mut Option<Document>,
+    editor_state: &mut EditorState
+) -> Result<()> {
+    let path = match doc {
+        Some(shown) if shown.kind == DocKind::Placeholder => shown.path.clone(),
+        _ => return Ok(()),
+    };
+
+    let EditorState { tabs, buffers, .. } = editor_state;
+    let slot = tabs.others_mut()
+        .chain(buffers.others_mut())
+        .find(|slot| slot.as_ref().is_some_and(|other|
+            other.kind != DocKind::Placeholder && other.path == path));
+    match slot {
+        Some(slot) => std::mem::swap(slot, doc),
+        // Nothing should be left without its document, but the file is
+        // still there
+        None => *doc = Some(editor_state.open_document(&path, None)?),
+    }
+
+    Ok(())
+}
+
+/// Give the document to a placeholder of its file on the slots, the
+/// document keeps the placeholder
+fn hand_over<'a>(
+    doc: &mut Option<Document>,
+    mut slots: impl Iterator<Item = &'a mut Option<Document>>
+) {
+    let path = match doc {
+        Some(given) if given.kind != DocKind::Placeholder => given.path.clone(),
+        _ => return,
+    };
+
+    let placeholder = slots.find(|slot| slot.as_ref().is_some_and(|other|
+        other.kind == DocKind::Placeholder && other.path == path));
+    if let Some(slot) = placeholder {
+        std::mem::swap(slot, doc);
+    }
+}
+
+/// Apply the unified diff onto the document as a single change, the hunks
+/// that don't apply are shown on a panel
+fn apply_patch(
+    text: &str,
+    doc: &mut Document,
+    cursor: &mut Cursor,
+    editor_state: &mut EditorState,
+    cursor_state: &mut CursorState,
+    render_state: &mut RenderState,
+) -> Result<String> {
+    let files = patch::parse(text)?;
+    let file = patch::for_path(&files, &doc.path)
+        .ok_or_else(|| format!("the diff doesn't change {}", 
+            doc.path.display()))?;
+    let lines = doc.buffer.to_vec();
+    let result = patch::apply(&lines, &file.hunks);
+
+    // Only the lines between the first and the last change are replaced
+    let prefix = lines.iter()
+        .zip(&result.lines)
+        .take_while(|(old, new)| old == new)
+        .count();
+    let suffix = lines[prefix..].iter().rev()
+        .zip(result.lines[prefix..].iter().rev())
+        .take_while(|(old, new)| old == new)
+        .count();
+    if result.applied > 0 {
+        let position = undo_location(doc, cursor, cursor_state);
+        let end = lines.len() - suffix;
+        let new_end = result.lines.len() - suffix;
+        let pending = PendingChange::begin(doc, prefix, end - prefix, position);
+        doc.replace_lines(prefix, end, result.lines[prefix..new_end].to_vec());
+        editor_state.undo.record(
+            doc, pending.commit(EditKind::Replace, doc, position));
+
+        editor_state.doc_lines = doc.buffer.len();
+        editor_state.edits.record(&doc.path, prefix, 0);
+        render_state.modif_all = true;
+        cursor.clamp_to_document(doc, cursor_state, render_state);
+    }
+
+    let message = format!("Applied {} of {} hunks", 
+        result.applied, file.hunks.len());
+    if result.conflicts.is_empty() {
+        return Ok(message);
+    }
+
+    // The hunks rejected are shown as they are on the diff
+    let mut rejected = Vec::new();
+    for conflict in &result.conflicts {
+        let hunk = &file.hunks[conflict.hunk];
+        rejected.push(format!("@@ hunk {} at line {} doesn't apply @@", 
+            conflict.hunk + 1, conflict.line + 1));
+        rejected.extend(hunk.old.iter().map(|line| format!("-{}", line)));
+        rejected.extend(hunk.new.iter().map(|line| format!("+{}", line)));
+    }
+    editor_state.panel = Some(Panel::new("Patch conflicts", rejected)
+        .with_highlight(diff::highlight));
+    render_state.modif_all = true;
+
+    Ok(format!("{}, {} conflicts", message, result.conflicts.len()))
+}
+
+/// Create the picker of the snapshots of the document
+fn snapshots_panel(snapshots: &Snapshots, doc: &Document) -> Panel {
+    Panel::list(
+        PanelKind::Snapshots,
+        "Snapshots -- [Enter] diff  [r] restore  [d] delete",
+        snapshots.lines(&doc.path))
+}
+
+/// The index of the snapshot selected on the picker
+fn selected_snapshot(
+    panel: &Panel,
+    doc: &Option<Document>,
+    snapshots: &Snapshots
+) -> Option<usize> {
+    let doc = doc.as_ref()?;
+    let selected = panel.selected?;
+    snapshots.of(&doc.path).get(selected).copied()
+}
+
+/// Create a panel with the unified diff from the snapshot to the document
+fn snapshot_diff_panel(snapshot: &Snapshot, doc: &Document) -> Panel {
+    let path = doc.path.display().to_string();
+    let title = format!("Diff {} (snapshot {} -> buffer)", path, snapshot.name);
+
+    let lines = diff::unified(
+        &snapshot.lines,
+        &doc.buffer.to_vec(),
+        &format!("{} ({})", path, snapshot.name),
+        &format!("{} (buffer)", path));
+    let lines = if lines.is_empty() {
+        vec!["No changes".to_owned()]
+    } else {
+        lines
+    };
+
+    Panel::new(title, lines).with_highlight(diff::highlight)
+}
+
+/// Replace the lines of the document with the ones of the snapshot, as a
+/// change that can be undone
+fn restore_snapshot(
+    idx: usize,
+    doc: &mut Option<Document>,
+    cursor: &mut Cursor,
+    editor_state: &mut EditorState,
+    cursor_state: &mut CursorState,
+    render_state: &mut RenderState,
+) -> Result<String> {
+    let doc = writable(doc)?;
+    let snapshot = &editor_state.snapshots.list[idx];
+    let (name, lines) = (snapshot.name.clone(), snapshot.lines.clone());
+
+    let position = undo_location(doc, cursor, cursor_state);
+    let pending = PendingChange::begin(doc, 0, doc.buffer.len(), position);
+    doc.replace_lines(0, doc.buffer.len(), lines);
+    editor_state.undo.record(
+        doc, pending.commit(EditKind::Replace, doc, position));
+
+    editor_state.doc_lines = doc.buffer.len();
+    render_state.modif_all = true;
+    cursor.clamp_to_document(doc, cursor_state, render_state);
+
+    Ok(format!("Restored snapshot `{}`", name))
+}
+
+/// Create a panel with the unified diff between the file on disk and the
+/// document, so the changes can be reviewed before writting them
+fn diff_panel(doc: &Document) -> Panel {
+    let path = doc.path.display().to_string();
+    let title = format!("Diff {} (disk -> buffer)", path);
+
+    let lines = match doc.saved_lines() {
+        Ok(saved) => {
+            let lines = diff::unified(
+                &saved,
+                &doc.buffer.to_vec(),
+                &format!("{} (disk)", path),
+                &format!("{} (buffer)", path));
+            if lines.is_empty() {
+                vec!["No changes".to_owned()]
+            } else {
+                lines
+            }
+        }
+        Err(err) => vec![format!("Could not read {}: {}", path, err)],
+    };
+
+    Panel::new(title, lines).with_highlight(diff::highlight)
+}
@@ -0,0 +1,337 @@
+//! Keys bound to the commands of the editor that can be changed by the user,
+//! they are written like `ctrl+q` or `alt+shift+p`
+
+use std::fmt;
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+use crate::Result;
+use crate::action::{Action, Motion};
+use crate::print::PrintTarget;
+
+/// A key with its modifiers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyBinding {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+impl KeyBinding {
+    pub const fn new(code: KeyCode, modifiers: KeyModifiers) -> Self {
+        Self { code, modifiers }
+    }
+
+    /// Parse a binding written as the modifiers followed by the key, all
+    /// separated by `+`
+    pub fn parse(spec: &str) -> Result<Self> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut parts: Vec<&str> = spec.split('+').map(str::trim).collect();
+
+        // `ctrl++` binds the plus
+        if spec.ends_with("++") {
+            parts.pop();
+            parts.pop();
+            parts.push("+");
+        }
+        let key = parts.pop().ok_or("empty key binding")?;
+
+        for modifier in parts {
+            modifiers |= match modifier.to_lowercase().as_str() {
+                "ctrl" | "control" => KeyModifiers::CONTROL,
+                "alt" | "meta" => KeyModifiers::ALT,
+                "shift" => KeyModifiers::SHIFT,
+                _ => return Err(
+                    format!("unknown modifier `{}`", modifier).into()),
+            };
+        }
+
+        let code = match key.to_lowercase().as_str() {
+            "esc" => KeyCode::Esc,
+            "enter" => KeyCode::Enter,
+            "tab" => KeyCode::Tab,
+            "backspace" => KeyCode::Backspace,
+            "delete" | "del" => KeyCode::Delete,
+            "space" => KeyCode::Char(' '),
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "pageup" => KeyCode::PageUp,
+            "pagedown" => KeyCode::PageDown,
+            "backtab" => KeyCode::BackTab,
+            name if name.starts_with('f') && name.len() > 1 => {
+                let number = name[1..].parse()
+                    .map_err(|_| format!("unknown key `{}`", key))?;
+                KeyCode::F(number)
+            }
+            _ => {
+                let mut chars = key.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => KeyCode::Char(c),
+                    _ => return Err(format!("unknown key `{}`", key).into()),
+                }
+            }
+        };
+
+        Ok(Self { code, modifiers })
+    }
+
+    /// If the event is this key. Chars typed with shift are already upper
+    /// case and not all the terminals report the shift with them, so for
+    /// chars the shift is on the case
+    pub fn matches(&self, event: &KeyEvent) -> bool {
+        if let (KeyCode::Char(expected), KeyCode::Char(got)) =
+                (self.code, event.code) {
+            let expected = if self.modifiers.contains(KeyModifiers::SHIFT) {
+                expected.to_ascii_uppercase()
+            } else {
+                expected
+            };
+
+            return expected == got
+                && event.modifiers - KeyModifiers::SHIFT
+                    == self.modifiers - KeyModifiers::SHIFT;
+        }
+
+        self.code == event.code && self.modifiers == event.modifiers
+    }
+}
+
+impl fmt::Display for KeyBinding {
+    /// Written the same way it's parsed
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (modifier, name) in [
+            (KeyModifiers::CONTROL, "ctrl+"),
+            (KeyModifiers::ALT, "alt+"),
+            (KeyModifiers::SHIFT, "shift+"),
+        ] {
+            if self.modifiers.contains(modifier) {
+                f.write_str(name)?;
+            }
+        }
+
+        match self.code {
+            KeyCode::Esc => f.write_str("esc"),
+            KeyCode::Enter => f.write_str("enter"),
+            KeyCode::Tab => f.write_str("tab"),
+            KeyCode::Backspace => f.write_str("backspace"),
+            KeyCode::Delete => f.write_str("delete"),
+            KeyCode::Char(' ') => f.write_str("space"),
+            KeyCode::Up => f.write_str("up"),
+            KeyCode::Down => f.write_str("down"),
+            KeyCode::Left => f.write_str("left"),
+            KeyCode::Right => f.write_str("right"),
+            KeyCode::Home => f.write_str("home"),
+            KeyCode::End => f.write_str("end"),
+            KeyCode::PageUp => f.write_str("pageup"),
+            KeyCode::PageDown => f.write_str("pagedown"),
+            KeyCode::BackTab => f.write_str("backtab"),
+            KeyCode::F(number) => write!(f, "f{}", number),
+            KeyCode::Char(c) => write!(f, "{}", c),
+            _ => f.write_str("?"),
+        }
+    }
+}
+
+/// The table that resolves the keys to the actions of the editing mode
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    /// The first binding that matches a key wins
+    bindings: Vec<(KeyBinding, Action)>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        use KeyCode::*;
+        const NONE: KeyModifiers = KeyModifiers::NONE;
+        const CTRL: KeyModifiers = KeyModifiers::CONTROL;
+        const ALT: KeyModifiers = KeyModifiers::ALT;
+        const SHIFT: KeyModifiers = KeyModifiers::SHIFT;
+        let ctrl_shift = CTRL | SHIFT;
+
+        let mut bindings = vec![
+            (Char('q'), CTRL, Action::Quit),
+            (Char('z'), CTRL, Action::Undo),
+            (Char('y'), CTRL, Action::Redo),
+            (Char('s'), CTRL, Action::Save),
+            (Char('f'), CTRL, Action::Search),
+            (Char('h'), CTRL, Action::Replace),
+            (Char('o'), CTRL, Action::OpenFile),
+            (Char('p'), CTRL, Action::FindFile),
+            (Char('f'), ALT, Action::FileTree),
+            (Char('g'), ALT, Action::Grep),
+            (Char('g'), CTRL, Action::GoToLinePrompt),
+            (Char('w'), CTRL, Action::CloseBuffer),
+            (Char('n'), CTRL, Action::NewBuffer),
+            (Char('c'), CTRL, Action::Copy),
+            (Char('x'), CTRL, Action::Cut),
+            (Char('v'), CTRL, Action::Paste),
+            (Char(' '), CTRL, Action::Complete),
+            (Char('d'), CTRL, Action::AddCursorAtMatch),
+            (Char('D'), ALT, Action::DuplicateLine),
+            (Char('k'), CTRL, Action::DeleteLine),
+
+            // Ctrl+^ comes as Ctrl+6 from most terminals
+            (Char('^'), CTRL, Action::AlternateBuffer),
+            (Char('6'), CTRL, Action::AlternateBuffer),
+
+            // Ctrl+Shift+Tab is reported as a back tab
+            (Tab, CTRL, Action::NextBuffer),
+            (BackTab, CTRL, Action::PrevBuffer),
+            (BackTab, ctrl_shift, Action::PrevBuffer),
+
+            (Char('x'), ALT, Action::CommandLine),
+            (Char('X'), ALT, Action::CommandPalette),
+            (Char('p'), ctrl_shift, Action::CommandPalette),
+            (Char('s'), ALT, Action::SaveAs),
+            (Char(';'), ALT, Action::OlderEdit),
+            (Char(','), ALT, Action::NewerEdit),
+            (Char('='), ALT, Action::Reindent),
+            (Char('e'), ALT, Action::ShowErrors),
+            (Char('d'), ALT, Action::ShowDiff),
+            (Char('b'), ALT, Action::Bookmark),
+            (Char('l'), ALT, Action::ListBookmarks),
+            (Char('b'), CTRL, Action::SetMark),
+            (Char('\''), ALT, Action::GoToMark),
+
+            // Ctrl+O opens a file and Ctrl+I comes as Tab from most
+            // terminals
+            (Char('o'), ALT, Action::JumpBack),
+            (Char('i'), ALT, Action::JumpForward),
+
+            (Char('p'), ALT, Action::Print(PrintTarget::Lp)),
+            (Char('P'), ALT, Action::Print(PrintTarget::Pdf)),
+            (Char('c'), ALT, Action::ToggleCrosshair),
+            (Char('z'), ALT, Action::ToggleWrap),
+            (Char('r'), ALT, Action::ToggleReview),
+            (Char('k'), ALT, Action::DeleteLine),
+            (Char('j'), ALT, Action::NextLongLine),
+            (Char('h'), ALT, Action::JumpToHunk { forward: true }),
+            (Char('H'), ALT, Action::JumpToHunk { forward: false }),
+            (Char('K'), ALT, Action::ShowHover),
+            (F(12), NONE, Action::GoToDefinition),
+            (F(1), NONE, Action::Help),
+            (Char('y'), ALT, Action::AcceptDeletion { all: false }),
+            (Char('Y'), ALT, Action::AcceptDeletion { all: true }),
+            (Char('n'), ALT, Action::RejectDeletion { all: false }),
+            (Char('N'), ALT, Action::RejectDeletion { all: true }),
+            (Char('t'), ALT, Action::NewTab),
+            (Char('w'), ALT, Action::CloseTab),
+
+            // Ctrl+M comes as Enter from most terminals
+            (Char('m'), CTRL, Action::MoveCursor(Motion::MatchingBracket)),
+            (Char('m'), ALT, Action::MoveCursor(Motion::MatchingBracket)),
+
+            // Ctrl keeps the column instead of following the indentation,
+            // shift selects
+            (Up, NONE, Action::MoveCursor(Motion::Up { exact: false })),
+            (Up, CTRL, Action::MoveCursor(Motion::Up { exact: true })),
+            (Up, SHIFT, Action::Select(Motion::Up { exact: false })),
+            (Up, ctrl_shift, Action::Select(Motion::Up { exact: true })),
+            (Up, ALT, Action::MoveCursor(Motion::RowUp)),
+            (Up, ALT | SHIFT, Action::MoveLine { up: true }),
+            (Up, CTRL | ALT, Action::AddCursor { up: true }),
+            (Down, NONE, Action::MoveCursor(Motion::Down { exact: false })),
+            (Down, CTRL, Action::MoveCursor(Motion::Down { exact: true })),
+            (Down, SHIFT, Action::Select(Motion::Down { exact: false })),
+            (Down, ctrl_shift, Action::Select(Motion::Down { exact: true })),
+            (Down, ALT, Action::MoveCursor(Motion::RowDown)),
+            (Down, ALT | SHIFT, Action::MoveLine { up: false }),
+            (Down, CTRL | ALT, Action::AddCursor { up: false }),
+            (PageUp, NONE,
+                Action::MoveCursor(Motion::PageUp { exact: false })),
+            (PageUp, CTRL,
+                Action::MoveCursor(Motion::PageUp { exact: true })),
+            (PageUp, SHIFT, Action::Select(Motion::PageUp { exact: false })),
+            (PageDown, NONE,
+                Action::MoveCursor(Motion::PageDown { exact: false })),
+            (PageDown, CTRL,
+                Action::MoveCursor(Motion::PageDown { exact: true })),
+            (PageDown, SHIFT,
+                Action::Select(Motion::PageDown { exact: false })),
+            (Left, NONE, Action::MoveCursor(Motion::Left)),
+            (Left, CTRL, Action::MoveCursor(Motion::WordLeft)),
+            (Left, SHIFT, Action::Select(Motion::Left)),
+            (Left, ctrl_shift, Action::Select(Motion::WordLeft)),
+            (Right, NONE, Action::MoveCursor(Motion::Right)),
+            (Right, CTRL, Action::MoveCursor(Motion::WordRight)),
+            (Right, SHIFT, Action::Select(Motion::Right)),
+            (Right, ctrl_shift, Action::Select(Motion::WordRight)),
+            (Home, NONE, Action::MoveCursor(Motion::LineStart)),
+            (Home, CTRL, Action::MoveCursor(Motion::DocumentStart)),
+            (Home, SHIFT, Action::Select(Motion::LineStart)),
+            (Home, ctrl_shift, Action::Select(Motion::DocumentStart)),
+            (End, NONE, Action::MoveCursor(Motion::LineEnd)),
+            (End, CTRL, Action::MoveCursor(Motion::DocumentEnd)),
+            (End, SHIFT, Action::Select(Motion::LineEnd)),
+            (End, ctrl_shift, Action::Select(Motion::DocumentEnd)),
+
+            (Enter, NONE, Action::InsertNewline),
+            (Enter, SHIFT, Action::InsertNewline),
+            (Enter, CTRL, Action::OpenUnderCursor),
+            (Backspace, NONE, Action::DeleteBackward),
+            (Delete, NONE, Action::DeleteForward),
+            (Esc, NONE, Action::DropCursors),
+            (Tab, NONE, Action::Indent),
+            (BackTab, NONE, Action::Dedent),
+            (BackTab, SHIFT, Action::Dedent),
+        ];
+        for (idx, c) in ('1'..='9').enumerate() {
+            bindings.push((Char(c), ALT, Action::SwitchTab(idx)));
+        }
+
+        Self {
+            bindings: bindings
+                .into_iter()
+                .map(|(code, modifiers, action)|
+                    (KeyBinding::new(code, modifiers), action))
+                .collect(),
+        }
+    }
+}
+
+impl Keymap {
+    /// The action bound to the key
+    pub fn lookup(&self, event: &KeyEvent) -> Option<Action> {
+        self.bindings
+            .iter()
+            .find(|(binding, _)| binding.matches(event))
+            .map(|(_, action)| *action)
+    }
+
+    /// The first key bound to the action, the one shown to the user
+    pub fn binding_of(&self, action: Action) -> Option<KeyBinding> {
+        self.bindings
+            .iter()
+            .find(|(_, bound)| *bound == action)
+            .map(|(binding, _)| *binding)
+    }
+
+    /// All the keys bound to the action, the one shown first
+    pub fn bindings_of(&self, action: Action) -> Vec<KeyBinding> {
+        self.bindings
+            .iter()
+            .filter(|(_, bound)| *bound == action)
+            .map(|(binding, _)| *binding)
+            .collect()
+    }
+
+    /// Bind the key to the action, replacing what it did before
+    pub fn bind(&mut self, binding: KeyBinding, action: Action) {
+        self.bindings.retain(|(bound, _)| *bound != binding);
+        self.bindings.insert(0, (binding, action));
+    }
+
+    /// Leave the key without action
+    pub fn unbind(&mut self, binding: KeyBinding) {
+        self.bindings.retain(|(bound, _)| *bound != binding);
+    }
+
+    /// Make the key the only one bound to the action
+    pub fn bind_only(&mut self, binding: KeyBinding, action: Action) {
+        self.bindings.retain(|(_, bound)| *bound != action);
+        self.bind(binding, action);
+    }
+}
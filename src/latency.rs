@@ -0,0 +1,62 @@
+//! Time from an input event until the frame it caused is flushed to the
+//! terminal, so the responsiveness can be measured instead of guessed
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Samples kept, the older ones are dropped
+const MAX_SAMPLES: usize = 1000;
+
+/// The latencies of the last events
+#[derive(Debug, Default)]
+pub struct LatencyTracker {
+    /// When the event being processed was read
+    pending: Option<Instant>,
+
+    samples: VecDeque<Duration>,
+}
+
+impl LatencyTracker {
+    /// An event was read, the next flush ends its sample
+    pub fn event_received(&mut self) {
+        self.pending = Some(Instant::now());
+    }
+
+    /// A frame was flushed, it has the effects of the pending event
+    pub fn frame_flushed(&mut self) {
+        if let Some(since) = self.pending.take() {
+            if self.samples.len() == MAX_SAMPLES {
+                self.samples.pop_front();
+            }
+            self.samples.push_back(since.elapsed());
+        }
+    }
+
+    /// Forget all the samples
+    pub fn reset(&mut self) {
+        self.pending = None;
+        self.samples.clear();
+    }
+
+    /// Summary of the samples, one statistic per line
+    pub fn report(&self) -> Vec<String> {
+        if self.samples.is_empty() {
+            return vec!["No events measured yet".to_owned()];
+        }
+
+        let mut sorted: Vec<Duration> = self.samples.iter().copied().collect();
+        sorted.sort_unstable();
+        let percentile = |p: usize| sorted[(sorted.len() - 1) * p / 100];
+        let total: Duration = sorted.iter().sum();
+
+        vec![
+            format!("Events measured: {}", sorted.len()),
+            format!("Mean:   {:?}", total / sorted.len() as u32),
+            format!("Min:    {:?}", sorted[0]),
+            format!("Median: {:?}", percentile(50)),
+            format!("p90:    {:?}", percentile(90)),
+            format!("p99:    {:?}", percentile(99)),
+            format!("Max:    {:?}", sorted[sorted.len() - 1]),
+        ]
+    }
+}
@@ -0,0 +1,111 @@
+//! The URL or the file path under the cursor, opened with a key: the paths
+//! on a buffer, at the line of the `path:line` and `path:line:column`
+//! suffixes the compilers print, and the URLs on the browser of the system
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::Result;
+
+const SCHEMES: [&str; 5] =
+    ["https://", "http://", "ftp://", "file://", "mailto:"];
+
+/// What a piece of text points to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Link {
+    Url(String),
+
+    /// The path as written, the line and the column from 0
+    Path { path: PathBuf, line: Option<usize>, column: Option<usize> },
+}
+
+/// The URL or the path the byte `idx` of the line is on, or right after.
+/// They end at the spaces, the quotes and the brackets, and the punctuation
+/// that ends a sentence is left out
+pub fn at(line: &str, idx: usize) -> Option<Link> {
+    let idx = idx.min(line.len());
+    let before = line[..idx].rsplit(is_delimiter).next().unwrap_or("");
+    let after = line[idx..].split(is_delimiter).next().unwrap_or("");
+    let (start, end) = (idx - before.len(), idx + after.len());
+    let text = line[start..end]
+        .trim_end_matches(['.', ',', ';', ':', '!', '?']);
+    if text.is_empty() {
+        return None;
+    }
+
+    if SCHEMES.iter().any(|scheme| text.starts_with(scheme)) {
+        return Some(Link::Url(text.to_owned()));
+    }
+    if text.starts_with("www.") {
+        return Some(Link::Url(format!("https://{}", text)));
+    }
+
+    // The numbers at the end are the line and the column, from 1
+    let mut path = text;
+    let mut numbers = Vec::new();
+    while numbers.len() < 2 {
+        match path.rsplit_once(':') {
+            Some((rest, number)) if !number.is_empty()
+                    && number.bytes().all(|b| b.is_ascii_digit()) => {
+                numbers.insert(0, number.parse::<usize>().ok()?);
+                path = rest;
+            }
+            _ => break,
+        }
+    }
+    if path.is_empty() {
+        return None;
+    }
+
+    let home = std::env::var_os("HOME");
+    let path = match (path.strip_prefix("~/"), home) {
+        (Some(rest), Some(home)) => Path::new(&home).join(rest),
+        _ => PathBuf::from(path),
+    };
+    Some(Link::Path {
+        path,
+        line: numbers.first().map(|line| line.saturating_sub(1)),
+        column: numbers.get(1).map(|column| column.saturating_sub(1)),
+    })
+}
+
+/// The file the path written on the document of `dir` is, it's looked for
+/// from the current directory and then from `dir`
+pub fn resolve(path: &Path, dir: Option<&Path>) -> Option<PathBuf> {
+    if path.is_file() {
+        return Some(path.to_owned());
+    }
+
+    dir.map(|dir| dir.join(path)).filter(|path| path.is_file())
+}
+
+/// Open the URL with the program of the system for it, which is left on
+/// the background
+pub fn open_url(url: &str) -> Result<()> {
+    // Not through `cmd`, which would run what follows a `&` of the URL
+    let (program, args): (&str, &[&str]) = if cfg!(target_os = "windows") {
+        ("rundll32", &["url.dll,FileProtocolHandler"])
+    } else if cfg!(target_os = "macos") {
+        ("open", &[])
+    } else {
+        ("xdg-open", &[])
+    };
+
+    let mut child = Command::new(program)
+        .args(args)
+        .arg(url)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|err| format!("could not run `{}`: {}", program, err))?;
+
+    // Waited for so it doesn't stay as a zombie
+    std::thread::spawn(move || child.wait());
+
+    Ok(())
+}
+
+fn is_delimiter(c: char) -> bool {
+    c.is_whitespace() || "\"'`<>()[]{}|".contains(c)
+}
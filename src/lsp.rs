@@ -0,0 +1,700 @@
+//! Client of the language servers, like `rust-analyzer` or `clangd`. A
+//! server is started for the filetype of the first document of it that is
+//! edited and it's told about every document of the filetype and their
+//! changes. It answers on its own time: its messages are read on a thread
+//! that wakes the editor, which takes them with `take_replies`, and the
+//! ones for it are written on another so it never blocks the editor. What
+//! is used of them are the diagnostics of the documents, drawn on the
+//! gutter and under their text, the hover information and the definitions
+//!
+//! The servers are only started when the `lsp` option is on, the command of
+//! each filetype is set with `lsp.<filetype>=<command>`
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::time::Duration;
+
+use crate::Result;
+use crate::events::Wake;
+use crate::json::Value;
+use crate::text::Document;
+
+/// The biggest message read from a server, a longer one is taken as the
+/// server going wrong
+const MAX_MESSAGE: usize = 64 << 20;
+
+/// What a server is given to read its last messages before it's killed
+const EXIT_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// The servers started unless the config says otherwise
+const DEFAULT_COMMANDS: [(&str, &str); 3] = [
+    ("rust", "rust-analyzer"),
+    ("c", "clangd"),
+    ("cpp", "clangd"),
+];
+
+/// How serious a diagnostic is, the numbers are the ones of the protocol
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Error = 1,
+    Warning = 2,
+    Information = 3,
+    Hint = 4,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Self::Error => "error",
+            Self::Warning => "warning",
+            Self::Information => "info",
+            Self::Hint => "hint",
+        })
+    }
+}
+
+/// A problem a server found on a document. The positions are lines and
+/// columns in UTF-16 code units, as the protocol counts them
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub start: (usize, usize),
+    pub end: (usize, usize),
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl Diagnostic {
+    /// The UTF-16 columns `start..end` of the line the diagnostic covers,
+    /// `usize::MAX` is the end of the line
+    pub fn on_line(&self, line: usize) -> Option<(usize, usize)> {
+        if line < self.start.0 || line > self.end.0 {
+            return None;
+        }
+        let start = if line == self.start.0 { self.start.1 } else { 0 };
+        let end = if line == self.end.0 { self.end.1 } else { usize::MAX };
+
+        // The empty ones still cover a char
+        Some((start, usize::max(end, start + 1)))
+    }
+}
+
+/// What a server answered that the editor has to do something with
+#[derive(Debug)]
+pub enum Reply {
+    /// The diagnostics of a document changed
+    Diagnostics,
+
+    /// The information about the text at the cursor, empty when there is
+    /// nothing to tell
+    Hover(String),
+
+    /// Where the symbol at the cursor is defined, a path and the line and
+    /// the UTF-16 column
+    Definition(Option<(PathBuf, usize, usize)>),
+
+    /// A server failed or exited
+    Error(String),
+}
+
+/// The requests sent waiting for an answer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Request {
+    Initialize,
+    Hover,
+    Definition,
+}
+
+/// A message read from the server of a filetype, `None` when the server
+/// exited
+type Message = (String, Option<Value>);
+
+/// A server running for a filetype
+struct Server {
+    child: Child,
+
+    /// The messages are written on their own thread, a server slow to read
+    /// them doesn't stop the editor. `written` is closed when it ends
+    writer: Option<Sender<String>>,
+    written: Receiver<()>,
+
+    next_id: u64,
+
+    /// The notifications that wait for the server to be initialized
+    initialized: bool,
+    queued: Vec<Value>,
+
+    pending: HashMap<u64, Request>,
+
+    /// The version of the buffers of the documents the server knows about,
+    /// by their URI
+    versions: HashMap<String, u64>,
+}
+
+impl Server {
+    fn start(
+        command: &str,
+        filetype: &str,
+        messages: Arc<Mutex<Vec<Message>>>,
+        sender: Sender<Wake>
+    ) -> Result<Self> {
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|err| format!("`{}` could not start: {}", command, err))?;
+        let mut stdin = child.stdin.take().ok_or("no stdin")?;
+        let stdout = child.stdout.take().ok_or("no stdout")?;
+
+        let (writer, texts) = mpsc::channel::<String>();
+        let (done, written) = mpsc::channel();
+        std::thread::spawn(move || {
+            let _done = done;
+            for text in texts {
+                let result = write!(stdin,
+                    "Content-Length: {}\r\n\r\n{}", text.len(), text)
+                    .and_then(|_| stdin.flush());
+                if result.is_err() {
+                    break;
+                }
+            }
+        });
+
+        let filetype = filetype.to_owned();
+        std::thread::spawn(move || {
+            let mut reader = BufReader::new(stdout);
+            loop {
+                let message = match read_message(&mut reader) {
+                    Ok(Some(message)) => Some(message),
+                    Ok(None) => continue,
+                    Err(_) => None,
+                };
+                let exited = message.is_none();
+                messages.lock().unwrap().push((filetype.clone(), message));
+                if sender.send(Wake::Lsp).is_err() || exited {
+                    break;
+                }
+            }
+        });
+
+        let mut server = Self {
+            child,
+            writer: Some(writer),
+            written,
+            next_id: 0,
+            initialized: false,
+            queued: Vec::new(),
+            pending: HashMap::new(),
+            versions: HashMap::new(),
+        };
+        let root = std::env::current_dir()?;
+        let capabilities = Value::object([
+            ("textDocument", Value::object([
+                ("synchronization", Value::object([
+                    ("didSave", Value::from(true)),
+                ])),
+                ("hover", Value::object([
+                    ("contentFormat", Value::from(vec![
+                        Value::from("plaintext"),
+                    ])),
+                ])),
+                ("definition", Value::object([])),
+                ("publishDiagnostics", Value::object([])),
+            ])),
+        ]);
+        server.request(Request::Initialize, "initialize", Value::object([
+            ("processId", Value::from(std::process::id() as u64)),
+            ("rootUri", Value::from(uri(&root))),
+            ("clientInfo", Value::object([("name", Value::from("pepe"))])),
+            ("capabilities", capabilities),
+        ]))?;
+
+        Ok(server)
+    }
+
+    fn send(&mut self, message: Value) -> Result<()> {
+        self.writer
+            .as_ref()
+            .and_then(|writer| writer.send(message.to_string()).ok())
+            .ok_or("the server doesn't read its input")?;
+
+        Ok(())
+    }
+
+    fn request(&mut self, kind: Request, method: &str, params: Value)
+            -> Result<()> {
+        self.next_id += 1;
+        self.pending.insert(self.next_id, kind);
+        self.send(Value::object([
+            ("jsonrpc", Value::from("2.0")),
+            ("id", Value::from(self.next_id)),
+            ("method", Value::from(method)),
+            ("params", params),
+        ]))
+    }
+
+    /// Send the notification, or keep it until the server is initialized
+    fn notify(&mut self, method: &str, params: Value) -> Result<()> {
+        let message = Value::object([
+            ("jsonrpc", Value::from("2.0")),
+            ("method", Value::from(method)),
+            ("params", params),
+        ]);
+        if !self.initialized {
+            self.queued.push(message);
+            return Ok(());
+        }
+
+        self.send(message)
+    }
+}
+
+impl Drop for Server {
+    /// Ask the server to exit, it's killed if it doesn't read it soon
+    fn drop(&mut self) {
+        let _ = self.send(Value::object([
+            ("jsonrpc", Value::from("2.0")),
+            ("id", Value::from(self.next_id + 1)),
+            ("method", Value::from("shutdown")),
+        ]));
+        let _ = self.send(Value::object([
+            ("jsonrpc", Value::from("2.0")),
+            ("method", Value::from("exit")),
+        ]));
+        self.writer = None;
+        let _ = self.written.recv_timeout(EXIT_TIMEOUT);
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// The servers of every filetype and what they said
+pub struct Lsp {
+    /// The servers are started when on
+    pub enabled: bool,
+
+    /// The command that starts the server of each filetype
+    pub commands: HashMap<String, String>,
+
+    servers: HashMap<String, Server>,
+
+    /// The filetypes whose server failed to start, not tried again
+    failed: HashSet<String>,
+
+    /// The messages read by the threads of the servers, not taken yet
+    messages: Arc<Mutex<Vec<Message>>>,
+
+    /// The diagnostics of the documents by their URI, and the URIs of the
+    /// paths of the documents
+    diagnostics: HashMap<String, Vec<Diagnostic>>,
+    uris: HashMap<PathBuf, String>,
+}
+
+impl Default for Lsp {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            commands: DEFAULT_COMMANDS
+                .iter()
+                .map(|(filetype, command)|
+                    (filetype.to_string(), command.to_string()))
+                .collect(),
+            servers: HashMap::new(),
+            failed: HashSet::new(),
+            messages: Arc::default(),
+            diagnostics: HashMap::new(),
+            uris: HashMap::new(),
+        }
+    }
+}
+
+impl Lsp {
+    /// Tell the server of the document about it the first time and about
+    /// its changes after, starting the server if it's not running. Nothing
+    /// is done without a server for the filetype
+    pub fn sync(&mut self, doc: &Document, sender: &Sender<Wake>) -> Result<()> {
+        // The lines of the large files are read from disk when needed
+        if !self.enabled || doc.large {
+            return Ok(());
+        }
+        let filetype = match doc.filetype() {
+            Some(filetype) if !self.failed.contains(filetype) => filetype,
+            _ => return Ok(()),
+        };
+        let command = match self.commands.get(filetype) {
+            Some(command) if !command.is_empty() => command,
+            _ => return Ok(()),
+        };
+        if !self.servers.contains_key(filetype) {
+            let server = Server::start(
+                    command, filetype, self.messages.clone(), sender.clone())
+                .inspect_err(|_| {
+                    self.failed.insert(filetype.to_owned());
+                })?;
+            self.servers.insert(filetype.to_owned(), server);
+        }
+
+        let uri = self.uri_of(&doc.path).to_owned();
+        let server = self.servers.get_mut(filetype).unwrap();
+        let version = doc.buffer.version();
+        let known = server.versions.insert(uri.clone(), version);
+        if known == Some(version) {
+            return Ok(());
+        }
+
+        let text = doc.buffer.join("\n");
+        match known {
+            None => server.notify("textDocument/didOpen", Value::object([
+                ("textDocument", Value::object([
+                    ("uri", Value::from(uri)),
+                    ("languageId", Value::from(filetype)),
+                    ("version", Value::from(version)),
+                    ("text", Value::from(text)),
+                ])),
+            ])),
+            Some(_) => server.notify("textDocument/didChange", Value::object([
+                ("textDocument", Value::object([
+                    ("uri", Value::from(uri)),
+                    ("version", Value::from(version)),
+                ])),
+                ("contentChanges", Value::from(vec![
+                    Value::object([("text", Value::from(text))]),
+                ])),
+            ])),
+        }
+    }
+
+    /// Tell the server the document was written
+    pub fn saved(&mut self, doc: &Document) -> Result<()> {
+        let uri = self.uri_of(&doc.path).to_owned();
+        match self.server_of(doc) {
+            Some(server) => server.notify("textDocument/didSave",
+                Value::object([
+                    ("textDocument", Value::object([
+                        ("uri", Value::from(uri)),
+                    ])),
+                ])),
+            None => Ok(()),
+        }
+    }
+
+    /// Ask about the text at the UTF-16 `column` of the line
+    pub fn hover(&mut self, doc: &Document, line: usize, column: usize)
+            -> Result<()> {
+        self.ask(Request::Hover, "textDocument/hover", doc, line, column)
+    }
+
+    /// Ask where the symbol at the UTF-16 `column` of the line is defined
+    pub fn definition(&mut self, doc: &Document, line: usize, column: usize)
+            -> Result<()> {
+        self.ask(Request::Definition, "textDocument/definition",
+            doc, line, column)
+    }
+
+    /// If a server is working on a request
+    pub fn is_busy(&self) -> bool {
+        self.servers.values().any(|server| server.pending
+            .values()
+            .any(|request| *request != Request::Initialize))
+    }
+
+    /// The diagnostics of the document, the most serious first
+    pub fn diagnostics(&self, path: &Path) -> &[Diagnostic] {
+        self.uris.get(path)
+            .and_then(|uri| self.diagnostics.get(uri))
+            .map(|diagnostics| &diagnostics[..])
+            .unwrap_or(&[])
+    }
+
+    /// Handle what the servers sent since the last time, what the editor
+    /// has to act on is returned
+    pub fn take_replies(&mut self) -> Vec<Reply> {
+        let messages = std::mem::take(&mut *self.messages.lock().unwrap());
+        let mut replies = Vec::new();
+        for (filetype, message) in messages {
+            let reply = match message {
+                Some(message) => self.handle(&filetype, message),
+                None => {
+                    self.servers.remove(&filetype);
+                    self.failed.insert(filetype.clone());
+                    Some(Reply::Error(format!(
+                        "the language server of {} exited", filetype)))
+                }
+            };
+            replies.extend(reply);
+        }
+
+        replies
+    }
+
+    fn handle(&mut self, filetype: &str, message: Value) -> Option<Reply> {
+        let id = message.get("id");
+        match (message.get("method").and_then(Value::as_str), id) {
+            // The requests of the servers are answered with nothing, the
+            // configuration asked with one value for each item
+            (Some(method), Some(id)) => {
+                let result = match method {
+                    "workspace/configuration" => Value::from(message
+                        .get("params")
+                        .and_then(|params| params.get("items"))
+                        .and_then(Value::as_array)
+                        .map(|items| vec![Value::Null; items.len()])
+                        .unwrap_or_default()),
+                    _ => Value::Null,
+                };
+                let answer = Value::object([
+                    ("jsonrpc", Value::from("2.0")),
+                    ("id", id.clone()),
+                    ("result", result),
+                ]);
+                let _ = self.servers.get_mut(filetype)?.send(answer);
+                None
+            }
+            (Some("textDocument/publishDiagnostics"), None) => {
+                let params = message.get("params")?;
+                let uri = params.get("uri")?.as_str()?.to_owned();
+                let mut diagnostics: Vec<Diagnostic> = params
+                    .get("diagnostics")?
+                    .as_array()?
+                    .iter()
+                    .filter_map(diagnostic)
+                    .collect();
+                diagnostics.sort_by_key(|diagnostic| diagnostic.severity);
+                self.diagnostics.insert(uri, diagnostics);
+                Some(Reply::Diagnostics)
+            }
+            (Some(_), None) => None,
+            (None, Some(id)) => 
+                self.answer(filetype, id.as_u64()?, &message),
+            (None, None) => None,
+        }
+    }
+
+    /// The answer of a server to the request `id`
+    fn answer(&mut self, filetype: &str, id: u64, message: &Value)
+            -> Option<Reply> {
+        let server = self.servers.get_mut(filetype)?;
+        let request = server.pending.remove(&id)?;
+        if let Some(error) = message.get("error") {
+            let text = error.get("message").and_then(Value::as_str);
+            return Some(Reply::Error(text.unwrap_or("failed").to_owned()));
+        }
+        let result = message.get("result").unwrap_or(&Value::Null);
+
+        match request {
+            Request::Initialize => {
+                server.initialized = true;
+                let _ = server.send(Value::object([
+                    ("jsonrpc", Value::from("2.0")),
+                    ("method", Value::from("initialized")),
+                    ("params", Value::object([])),
+                ]));
+                for queued in std::mem::take(&mut server.queued) {
+                    let _ = server.send(queued);
+                }
+                None
+            }
+            Request::Hover => Some(Reply::Hover(hover_text(result))),
+            Request::Definition => Some(Reply::Definition(location(result))),
+        }
+    }
+
+    fn ask(
+        &mut self,
+        kind: Request,
+        method: &str,
+        doc: &Document,
+        line: usize,
+        column: usize
+    ) -> Result<()> {
+        if !self.enabled {
+            return Err("the language servers are off, `set lsp=on`".into());
+        }
+        let uri = self.uri_of(&doc.path).to_owned();
+        let server = self.server_of(doc)
+            .ok_or("there is no language server for this document")?;
+        server.request(kind, method, Value::object([
+            ("textDocument", Value::object([("uri", Value::from(uri))])),
+            ("position", Value::object([
+                ("line", Value::from(line)),
+                ("character", Value::from(column)),
+            ])),
+        ]))
+    }
+
+    fn server_of(&mut self, doc: &Document) -> Option<&mut Server> {
+        self.servers.get_mut(doc.filetype()?)
+    }
+
+    /// The URI of the path, remembered so it's not made again for every
+    /// line drawn
+    fn uri_of(&mut self, path: &Path) -> &str {
+        self.uris
+            .entry(path.to_owned())
+            .or_insert_with(|| uri(path))
+    }
+}
+
+/// Read a message of a server, the header with its length and the JSON
+/// after it. `None` when it's not valid JSON
+fn read_message(reader: &mut impl BufRead) -> Result<Option<Value>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 {
+            return Err("the server closed its output".into());
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+    let length = length.ok_or("a message without length")?;
+    if length > MAX_MESSAGE {
+        return Err(format!("a message of {} bytes", length).into());
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+
+    Ok(std::str::from_utf8(&body)
+        .ok()
+        .and_then(|text| Value::parse(text).ok()))
+}
+
+/// The `file://` URI of the path, made absolute
+pub fn uri(path: &Path) -> String {
+    let absolute = path.canonicalize().unwrap_or_else(|_|
+        std::env::current_dir().unwrap_or_default().join(path));
+    let mut uri = String::from("file://");
+    for byte in absolute.to_string_lossy().bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9'
+                | b'/' | b'-' | b'_' | b'.' | b'~' => uri.push(byte as char),
+            byte => uri.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+
+    uri
+}
+
+/// The path of a `file://` URI, relative to the current directory when it's
+/// under it
+pub fn path_of(uri: &str) -> Option<PathBuf> {
+    let encoded = uri.strip_prefix("file://")?.as_bytes();
+    let mut bytes = Vec::with_capacity(encoded.len());
+    let mut idx = 0;
+    while idx < encoded.len() {
+        let decoded = (encoded[idx] == b'%')
+            .then(|| encoded.get(idx + 1..idx + 3))
+            .flatten()
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match decoded {
+            Some(byte) => {
+                bytes.push(byte);
+                idx += 3;
+            }
+            None => {
+                bytes.push(encoded[idx]);
+                idx += 1;
+            }
+        }
+    }
+    let path = PathBuf::from(String::from_utf8(bytes).ok()?);
+    let relative = std::env::current_dir().ok()
+        .and_then(|dir| path.strip_prefix(dir).ok().map(Path::to_owned));
+
+    Some(relative.unwrap_or(path))
+}
+
+/// The UTF-16 column of the byte of the line
+pub fn utf16_column(line: &str, byte: usize) -> usize {
+    line[..crate::text::floor_char_boundary(line, byte)]
+        .chars()
+        .map(char::len_utf16)
+        .sum()
+}
+
+/// The byte of the line at the UTF-16 column, the end of the line when it's
+/// past it
+pub fn byte_of(line: &str, column: usize) -> usize {
+    let mut units = 0;
+    for (byte, c) in line.char_indices() {
+        if units >= column {
+            return byte;
+        }
+        units += c.len_utf16();
+    }
+
+    line.len()
+}
+
+fn position(value: &Value) -> Option<(usize, usize)> {
+    Some((value.get("line")?.as_u64()? as usize,
+        value.get("character")?.as_u64()? as usize))
+}
+
+fn diagnostic(value: &Value) -> Option<Diagnostic> {
+    let range = value.get("range")?;
+    let severity = match value.get("severity").and_then(Value::as_u64) {
+        Some(2) => Severity::Warning,
+        Some(3) => Severity::Information,
+        Some(4) => Severity::Hint,
+        _ => Severity::Error,
+    };
+
+    Some(Diagnostic {
+        start: position(range.get("start")?)?,
+        end: position(range.get("end")?)?,
+        severity,
+        message: value.get("message")?.as_str()?.to_owned(),
+    })
+}
+
+/// The text of the contents of a hover, which can be a string, a marked
+/// string, a list of them or markup
+fn hover_text(result: &Value) -> String {
+    fn text(contents: &Value) -> String {
+        match contents {
+            Value::String(text) => text.clone(),
+            Value::Array(values) => values
+                .iter()
+                .map(text)
+                .collect::<Vec<_>>()
+                .join("\n"),
+            contents => contents.get("value")
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_owned(),
+        }
+    }
+
+    result.get("contents").map(text).unwrap_or_default()
+}
+
+/// The first location of a definition, which can be a location, a list of
+/// them or a list of links
+fn location(result: &Value) -> Option<(PathBuf, usize, usize)> {
+    let first = match result {
+        Value::Array(values) => values.first()?,
+        result => result,
+    };
+    let uri = first.get("uri").or_else(|| first.get("targetUri"))?;
+    let range = first.get("range")
+        .or_else(|| first.get("targetSelectionRange"))?;
+    let (line, column) = position(range.get("start")?)?;
+
+    Some((path_of(uri.as_str()?)?, line, column))
+}
@@ -1,15 +1,155 @@
-use std::path::PathBuf;
+use std::io::{IsTerminal, Read};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
 
-use crossterm::{execute, terminal};
-use crossterm::event::*;
+use crossterm::terminal;
 
+mod action;
+mod backend;
+mod bell;
+mod binary;
+mod bookmarks;
+mod cancel;
+mod changes;
+mod cli;
+mod buffers;
+mod clipboard;
+mod command;
+mod complete;
+mod commit;
+mod compose;
+mod config;
+mod count;
+mod crypt;
+mod cursors;
+mod diff;
+mod editor;
+mod edits;
+mod encoding;
+mod eol;
+mod events;
+mod export;
+mod fileops;
+mod finder;
+mod generated;
+mod git;
+mod grid;
+mod grep;
+mod heat;
+mod highlight;
+mod hooks;
+mod hover;
+mod indent;
+mod index;
 mod input;
+mod json;
+mod keymap;
+mod latency;
+mod links;
+mod lsp;
+mod marks;
+mod minibuffer;
+mod mouse;
+mod narrow;
+mod osc;
+mod pairs;
+mod plugin;
+mod panel;
+mod patch;
+mod preview;
+mod print;
+mod prose;
+mod quickfix;
+mod recovery;
+mod regex;
+mod remote;
 mod render;
+mod replace;
+mod review;
+mod save;
+mod screen;
+mod scrollbar;
+mod search;
+mod selection;
+mod session;
+mod shell;
+mod snapshot;
+mod status;
+mod swap;
+mod stream;
+mod tabs;
+mod tasks;
 mod text;
+mod theme;
+mod todo;
+mod tree;
+mod undo;
+mod unicode;
+mod viewport;
+mod watch;
+mod window;
+mod wrap;
 
-use crate::input::{Cursor, CursorState, process_keypress};
-use crate::render::{RenderState, refresh_screen};
-use crate::text::Document;
+use crate::backend::{Backend, Crossterm};
+use crate::bookmarks::Bookmarks;
+use crate::buffers::{BufferList, OpenBuffer};
+use crate::bell::{AudibleBell, Bell};
+use crate::changes::LineChanges;
+use crate::cli::Options;
+use crate::clipboard::Clipboard;
+use crate::command::CommandLine;
+use crate::complete::Completion;
+use crate::crypt::{Cipher, Encryption, PassphrasePrompt};
+use crate::cursors::CursorSet;
+use crate::editor::Editor;
+use crate::edits::EditList;
+use crate::events::EventLoop;
+use crate::export::ExportTools;
+use crate::fileops::Trashed;
+use crate::generated::GeneratedGuard;
+use crate::git::Git;
+use crate::heat::Heatmap;
+use crate::highlight::Highlights;
+use crate::hooks::{Hooks, HookEvent};
+use crate::hover::Hover;
+use crate::index::ProjectIndex;
+use crate::latency::LatencyTracker;
+use crate::lsp::Lsp;
+use crate::input::{Cursor, CursorState, ModalState, jump_to_location};
+use crate::keymap::Keymap;
+use crate::marks::{JumpList, Marks};
+use crate::minibuffer::{MessageArea, Prompt};
+use crate::mouse::{AutoScroll, Clicks};
+use crate::osc::ShellIntegration;
+use crate::pairs::PairSettings;
+use crate::plugin::Plugins;
+use crate::panel::Panel;
+use crate::preview::{Preview, PreviewCommands};
+use crate::print::PrintOptions;
+use crate::prose::ProseSettings;
+use crate::quickfix::Quickfix;
+use crate::remote::Server;
+use crate::render::RenderState;
+use crate::replace::Replace;
+use crate::review::Review;
+use crate::save::{AutoSave, SaveOptions};
+use crate::screen::Screen;
+use crate::search::{Search, SearchOptions};
+use crate::session::{Place, Places};
+use crate::snapshot::Snapshots;
+use crate::status::StatusLine;
+use crate::swap::SwapFiles;
+use crate::stream::OutputStream;
+use crate::tabs::Tabs;
+use crate::tasks::Spinner;
+use crate::theme::Theme;
+use crate::todo::TodoList;
+use crate::tree::FileTree;
+use crate::text::{Document, FinalNewline, Position, WordChars};
+use crate::undo::UndoHistory;
+use crate::viewport::Viewport;
+use crate::watch::FileWatcher;
+use crate::window::{LineNumbers, MIN_NUMBER_WIDTH, WindowOptions};
 
 /// Wrapper around Result
 pub type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
@@ -22,42 +162,690 @@ pub struct EditorState {
     /// Used to tell if the application should close
     running: bool,
 
-    /// Size of the terminal, updated always
+    /// Actions bound to the keys
+    keymap: Keymap,
+
+    /// While quitting, the documents whose unsaved changes are discarded
+    quitting: Option<Vec<PathBuf>>,
+
+    /// Size of the terminal, the frames are drawn on all of it
+    terminal: (u16, u16),
+
+    /// Size of the document area of the terminal, updated always
     rows: usize,
     columns: usize,
+
+    /// Rows of the terminal used above the document area
+    top: usize,
+
+    /// Actions attached to the lifecycle of the document
+    hooks: Hooks,
+
+    /// The plugins compiled with the editor
+    plugins: Plugins,
+
+    /// The words offered to complete the one before the cursor
+    completion: Option<Completion>,
+
+    /// Panel shown over the document, it takes the input while open
+    panel: Option<Panel>,
+
+    /// The files of the directory the editor was started on, on a sidebar
+    /// that takes the input while focused, and the columns of its names
+    tree: Option<FileTree>,
+    tree_width: usize,
+
+    /// Annotated lines of the project files
+    bookmarks: Bookmarks,
+
+    /// Errors of a build that point to lines of the files
+    quickfix: Quickfix,
+
+    /// Which pairs are auto-closed on each filetype and how
+    pairs: PairSettings,
+
+    /// Track changes mode, deleted lines wait for review
+    review: Review,
+
+    /// Highlight the line and the column of the cursor across the terminal
+    crosshair: bool,
+
+    /// Where the time went on each document, drawn on the gutter when
+    /// `heatmap` is enabled
+    heat: Heatmap,
+    heatmap: bool,
+
+    /// Workspaces the editor can switch between
+    tabs: Tabs,
+
+    /// Documents open on the current tab
+    buffers: BufferList,
+
+    /// The scratch buffers opened, they are numbered from 1
+    scratches: usize,
+
+    /// The command line being typed, it takes the input while open
+    command_line: Option<CommandLine>,
+
+    /// The text copied and cut
+    clipboard: Clipboard,
+
+    /// What tells the user an action couldn't be done
+    bell: Box<dyn Bell>,
+
+    /// The passphrase of an encrypted file being typed, it takes the input
+    /// while open
+    passphrase: Option<PassphrasePrompt>,
+
+    /// Identity used to decrypt and encrypt the age files
+    age_identity: Option<PathBuf>,
+
+    /// What the editor tells the user, shown on the minibuffer for a while
+    message: MessageArea,
+
+    /// The line of text being asked to the user, it takes the input while
+    /// open
+    prompt: Option<Prompt>,
+
+    /// The search being typed, it takes the input while open
+    search: Option<Search>,
+
+    /// Options of the last search, the next one starts with them
+    search_options: SearchOptions,
+
+    /// The last search accepted, to go through its matches
+    last_search: Option<Search>,
+
+    /// The replace being typed or confirmed, it takes the input while open
+    replace: Option<Replace>,
+
+    /// Read-only mode with the keys of `less`
+    pager: bool,
+
+    /// The modes of the modal editing, when enabled
+    modal: ModalState,
+
+    /// Text width and wrapping of prose
+    prose: ProseSettings,
+
+    /// Normalizations of the text made on save
+    save_options: SaveOptions,
+
+    /// If the terminal has the keyboard focus, without it the accents of
+    /// the bars are dimmed
+    focused: bool,
+
+    /// Dim the bars when the terminal loses the focus
+    dim_unfocused: bool,
+
+    /// What is saved when the terminal loses the focus
+    autosave: AutoSave,
+
+    /// If a missing final newline is added on save
+    final_newline: FinalNewline,
+
+    /// Where the recent edits happened
+    edits: EditList,
+
+    /// The lines marked with a key, and where the cursor jumped from
+    marks: Marks,
+    jumps: JumpList,
+
+    /// Polling of the open files to know when other programs change them
+    watcher: FileWatcher,
+
+    /// Paths of the files of the project, built on the background
+    index: Option<ProjectIndex>,
+
+    /// Where `--remote` sends the files to open, only the first editor
+    /// of the project listens
+    remote: Option<Server>,
+
+    /// Popup of what is under the mouse
+    hover: Hover,
+
+    /// The last clicks, for the double and triple ones
+    clicks: Clicks,
+
+    /// The drag of a selection held past the document area
+    autoscroll: AutoScroll,
+
+    /// Time from the events to the frames that show them
+    latency: LatencyTracker,
+
+    /// Where the work of the threads arrives, the editor sleeps on it
+    events: EventLoop,
+
+    /// What the terminal is told about the editor, the directory and the
+    /// commands
+    osc: ShellIntegration,
+
+    /// The changes of the documents that can be undone
+    undo: UndoHistory,
+
+    /// Named copies of the documents to compare with or go back to
+    snapshots: Snapshots,
+
+    /// The program whose output is streamed into a panel
+    stream: Option<OutputStream>,
+
+    /// Turns on the status bar while something runs on the background
+    spinner: Spinner,
+
+    /// If the mouse is dragging the scrollbar
+    scrubbing: bool,
+
+    /// How the generated files are recognized and opened
+    generated: GeneratedGuard,
+
+    /// Lines longer than this are marked on the gutter, the chars past it
+    /// are highlighted
+    max_line_length: Option<usize>,
+
+    /// The columns with a guide down the document area
+    rulers: Vec<usize>,
+
+    /// The files of this size or bigger are opened without reading them
+    /// whole, `None` reads all of them
+    large_file: Option<u64>,
+
+    /// External program used to re-indent lines, they are sent to its stdin
+    /// and replaced by its stdout
+    equalprg: Option<String>,
+
+    /// Columns of a tab when it's expanded
+    tab_width: usize,
+
+    /// The new lines start with the indentation of the line split
+    auto_indent: bool,
+
+    /// Tab inserts spaces up to the next tab stop instead of a tab, the
+    /// lines are indented by `tab_width` spaces
+    expand_tab: bool,
+
+    /// Options of the window of the current tab
+    window: WindowOptions,
+
+    /// Lines kept visible above and below the cursor when moving it
+    scroll_margin: usize,
+
+    /// Lines scrolled by each step of the mouse wheel
+    wheel_step: usize,
+
+    /// With the lines wrapped, up and down move by the rows shown and the
+    /// row motions by lines, else the other way around
+    row_motions: bool,
+
+    /// The line and the byte where the accent waiting for its letter was
+    /// typed, when composing
+    compose_accent: Option<(usize, usize)>,
+
+    /// The swap files telling other editors which files are edited here
+    swaps: SwapFiles,
+
+    /// The files removed go to the trash, else they are deleted for good.
+    /// The ones moved there on the session can be put back, the last first
+    trash: bool,
+    trashed: Vec<Trashed>,
+
+    /// The chars that form words besides the alphanumeric ones, for the
+    /// word motions and the words selected
+    word_chars: WordChars,
+
+    /// Where every file was left, and if the files of the last session are
+    /// opened when the editor starts without any
+    places: Places,
+    resume: bool,
+
+    /// The colors everything is drawn with
+    theme: &'static Theme,
+
+    /// The matches of the regular expressions of the user are styled
+    highlights: Highlights,
+
+    /// The line numbers and the highlights of the hard copies
+    print: PrintOptions,
+
+    /// The programs that preview each filetype, and the last preview
+    previews: PreviewCommands,
+    preview: Option<Preview>,
+
+    /// The lines changed from the saved version of each document, or from
+    /// the baseline given
+    changes: LineChanges,
+
+    /// The branch of the repository of each document, asked to git on the
+    /// background
+    git: Git,
+
+    /// The language servers of the filetypes and their diagnostics
+    lsp: Lsp,
+
+    /// The tools the selection or the document can be exported to
+    export_tools: ExportTools,
+
+    /// The bracket under the cursor and the one that pairs with it, both
+    /// are highlighted
+    brackets: Option<(Position, Position)>,
+
+    /// The words of the TODO comments, and the ones found on the project
+    todo_patterns: Vec<String>,
+    todos: TodoList,
+
+    /// The segments shown on the status bar
+    status_line: StatusLine,
+}
+
+impl EditorState {
+    /// The editor with the default options on a terminal of the `terminal`
+    /// size, the bookmarks of the project and the places of the files are
+    /// loaded
+    pub fn new(terminal: (u16, u16)) -> Self {
+        let mut editor_state = Self {
+            terminal,
+            running: true,
+            keymap: Keymap::default(),
+            quitting: None,
+            rows: 0,
+            columns: 0,
+            top: 0,
+            doc_lines: 0,
+            hooks: Hooks::default(),
+            plugins: Plugins::default(),
+            completion: None,
+            panel: None,
+            tree: None,
+            tree_width: tree::DEFAULT_WIDTH,
+            bookmarks: Bookmarks::load("."),
+            quickfix: Quickfix::default(),
+            pairs: PairSettings::default(),
+            review: Review::default(),
+            crosshair: false,
+            heat: Heatmap::default(),
+            heatmap: false,
+            tabs: Tabs::default(),
+            buffers: BufferList::default(),
+            scratches: 0,
+            command_line: None,
+            clipboard: Clipboard::default(),
+            bell: Box::new(AudibleBell),
+            passphrase: None,
+            age_identity: None,
+            message: MessageArea::default(),
+            prompt: None,
+            search: None,
+            search_options: SearchOptions::default(),
+            last_search: None,
+            replace: None,
+            pager: false,
+            modal: ModalState::default(),
+            equalprg: None,
+            tab_width: 4,
+            auto_indent: true,
+            expand_tab: false,
+            window: WindowOptions::default(),
+            scroll_margin: 0,
+            wheel_step: 3,
+            row_motions: true,
+            compose_accent: None,
+            swaps: SwapFiles::default(),
+            trash: true,
+            trashed: Vec::new(),
+            word_chars: WordChars::default(),
+            places: Places::load(),
+            resume: false,
+            theme: &theme::THEMES[0],
+            highlights: Highlights::default(),
+            print: PrintOptions::default(),
+            previews: PreviewCommands::default(),
+            export_tools: ExportTools::default(),
+            changes: LineChanges::default(),
+            git: Git::default(),
+            lsp: Lsp::default(),
+            preview: None,
+            brackets: None,
+            todo_patterns: todo::DEFAULT_PATTERNS.iter()
+                .map(|pattern| pattern.to_string())
+                .collect(),
+            todos: TodoList::default(),
+            status_line: StatusLine::default(),
+            max_line_length: None,
+            rulers: Vec::new(),
+            large_file: Some(text::LARGE_FILE),
+            generated: GeneratedGuard::default(),
+            prose: ProseSettings::default(),
+            save_options: SaveOptions::default(),
+            focused: true,
+            dim_unfocused: true,
+            autosave: AutoSave::default(),
+            final_newline: FinalNewline::default(),
+            edits: EditList::default(),
+            marks: Marks::default(),
+            jumps: JumpList::default(),
+            watcher: FileWatcher::default(),
+            index: None,
+            remote: None,
+            hover: Hover::default(),
+            clicks: Clicks::default(),
+            autoscroll: AutoScroll::default(),
+            latency: LatencyTracker::default(),
+            events: EventLoop::default(),
+            osc: ShellIntegration::default(),
+            undo: UndoHistory::default(),
+            snapshots: Snapshots::default(),
+            stream: None,
+            spinner: Spinner::default(),
+            scrubbing: false,
+        };
+        editor_state.update_size();
+
+        editor_state
+    }
+
+    /// Load the document at `path` running the hooks attached to its opening,
+    /// the gpg files need the `passphrase` to be decrypted
+    pub fn open_document(
+        &mut self,
+        path: impl AsRef<Path>,
+        passphrase: Option<String>
+    ) -> Result<Document> {
+        let path = path.as_ref();
+        let mut doc = match (crypt::detect(path), passphrase) {
+            (Some(Cipher::Gpg), Some(passphrase)) => {
+                let (encryption, text) = Encryption::gpg(path, passphrase)?;
+                Document::encrypted(path, encryption, &text)
+            }
+            (Some(Cipher::Gpg), None) =>
+                return Err(format!("{} is encrypted, it needs a passphrase",
+                    path.display()).into()),
+            (Some(Cipher::Age), _) => {
+                let identity = self.age_identity.as_deref();
+                let (encryption, text) = Encryption::age(path, identity)?;
+                Document::encrypted(path, encryption, &text)
+            }
+            (None, _) => {
+                let cancel = self.events.cancel_token();
+                let _operation = cancel.start();
+                Document::new(path, self.large_file, &cancel)?
+            }
+        };
+        self.hooks.fire(HookEvent::Open, &mut doc)?;
+        self.plugins.open(&mut doc)?;
+        if doc.filetype().is_some() {
+            self.hooks.fire(HookEvent::Filetype, &mut doc)?;
+        }
+        self.doc_lines = doc.buffer.len();
+        self.watcher.watch(&doc.path);
+
+        // Two editors writing the same file lose the changes of one of them
+        if let Some(pid) = self.swaps.claim(&doc.path) {
+            self.message.error(format!(
+                "{} is being edited by another pepe (pid {})",
+                doc.path.display(), pid));
+        }
+
+        // The changes being committed are a command away
+        if doc.filetype() == Some("gitcommit") {
+            self.message.show(":staged shows the changes to commit");
+        }
+
+        // Editing a binary file as text would replace its bytes
+        if doc.binary {
+            doc.read_only = true;
+            self.message.error(format!(
+                "{} is binary, opened read-only, `hex` shows its bytes",
+                doc.path.display()));
+        }
+
+        // Saving writes all the lines with the same ending
+        if doc.mixed_line_endings {
+            self.message.show(format!(
+                "{} has mixed line endings, they are saved as {}",
+                doc.path.display(), doc.line_ending));
+        }
+
+        // The changes to a generated file are lost when it's generated again
+        if let Some(line) = self.generated.check(&doc) {
+            doc.read_only = self.generated.read_only;
+            let opened = if doc.read_only { ", opened read-only" } else { "" };
+            self.message.show(format!(
+                "{} looks generated (line {}), edit its source instead{}",
+                doc.path.display(), line + 1, opened));
+        }
+
+        Ok(doc)
+    }
+
+    /// The cursor and the view where the document was left, its start when
+    /// it wasn't opened before
+    pub fn left_at(&self, doc: &Document) -> (Cursor, CursorState) {
+        self.places.of(&doc.path)
+            .map(|place| place.view(doc, self.rows))
+            .unwrap_or_default()
+    }
+
+    /// The document is now the one being edited, the terminal gets its
+    /// directory, git is asked about its repository and the hooks attached
+    /// to it run
+    pub fn enter_document(&mut self, doc: &mut Document) -> Result<()> {
+        let dir = std::env::current_dir()?.join(&doc.path);
+        let dir = dir.parent().unwrap_or(&dir);
+        self.osc.directory(&dir.canonicalize().unwrap_or(dir.to_owned()));
+        self.git.query(&doc.path, self.events.sender());
+
+        self.hooks.fire(HookEvent::Enter, doc)
+    }
+
+    /// Update the size of the document area from the terminal size, the
+    /// tabs bar takes the first row when visible, the sidebar and the
+    /// gutter the first columns and the scrollbar the last one
+    pub fn update_size(&mut self) {
+        let (columns, rows) = self.terminal;
+        self.top = if self.tabs.is_visible() { 1 } else { 0 };
+        self.rows = (rows as usize).saturating_sub(2 + self.top);
+        self.columns = (columns as usize)
+            .saturating_sub(self.gutter_width() + 1);
+    }
+
+    /// Columns of the gutter: the line numbers, as wide as the number of
+    /// lines of the document needs, and the column of the marks. The cells
+    /// of the document area start after it, and it starts after the sidebar
+    pub fn gutter_width(&self) -> usize {
+        let digits = match self.window.line_numbers {
+            LineNumbers::Off => 0,
+            _ => usize::max(MIN_NUMBER_WIDTH,
+                self.doc_lines.checked_ilog10().unwrap_or(0) as usize + 1),
+        };
+
+        self.sidebar_width() + digits + 1
+    }
+
+    /// Columns of the file tree at the left of the terminal, none when it's
+    /// hidden
+    pub fn sidebar_width(&self) -> usize {
+        match &self.tree {
+            Some(tree) if tree.visible => self.tree_width + 1,
+            _ => 0,
+        }
+    }
 }
 
 fn main() -> Result<()> {
-    // Extract the path of the file to edit and open it as a `Document`
-    let path = std::env::args()
-        // Extract just the fist argument
-        .nth(1)
-        // Convert it to a path
-        .map(|s| PathBuf::from(s));
-    let doc_lines;
-    let mut curr_doc = if let Some(path) = path {
-        let doc = Document::new(path)?;
-        doc_lines = doc.inner_lines.len();
-        Some(doc)
+    // The wrong options are told before touching the terminal
+    let options = match Options::parse(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("pepe: {}", err);
+            std::process::exit(2);
+        }
+    };
+    if options.help {
+        println!("{}", cli::USAGE);
+        return Ok(());
+    }
+    if options.version {
+        println!("pepe {}", env!("CARGO_PKG_VERSION"));
+        return Ok(());
+    }
+
+    let mut editor_state = EditorState::new(terminal::size()?);
+
+    // The messages go to the log from the first one
+    if let Some(log) = &options.log {
+        if let Err(err) = editor_state.message.log_to(log) {
+            eprintln!(
+                "pepe: could not open the log {}: {}", log.display(), err);
+            std::process::exit(1);
+        }
+    }
+
+    // The options and the keys of the user, the errors are shown once the
+    // editor starts
+    let config = options.config.as_deref();
+    if let Some(errors) = config::load(&mut editor_state, config) {
+        editor_state.message.error(errors);
+    }
+    if let Some(theme) = &options.theme {
+        let result = input::set_option(
+            &format!("theme={}", theme), &mut editor_state);
+        if let Err(err) = result {
+            editor_state.message.error(format!("Error: {}", err));
+        }
+    }
+    editor_state.pager = options.pager;
+
+    // With `--remote` the files go to the editor running on the project,
+    // when there is none this one opens them
+    let socket = remote::project_root()
+        .and_then(|root| remote::socket_path(&root))
+        .ok();
+    if let (true, Some(socket), false) = 
+            (options.remote, &socket, options.paths.is_empty()) {
+        let current_dir = std::env::current_dir()?;
+        let absolute: Vec<_> = options.paths
+            .iter()
+            .map(|path| current_dir.join(path))
+            .collect();
+        if remote::send(socket, &absolute).is_ok() {
+            return Ok(());
+        }
+    }
+
+    // The pager without a file shows its stdin, like `less` when used as
+    // `$PAGER`, the keys are still read from the terminal
+    // The pager just shows a file, there's nothing to pick from the project
+    if !editor_state.pager {
+        editor_state.index = Some(ProjectIndex::build("."));
+        let wake = editor_state.events.sender();
+        editor_state.remote = 
+            socket.and_then(|socket| Server::bind(&socket, wake));
+    }
+
+    // With the errors of a build on stdin they are loaded on the quickfix
+    // list, the first error is opened if there is no file to edit
+    if options.errors {
+        let mut text = String::new();
+        std::io::stdin().read_to_string(&mut text)?;
+        editor_state.quickfix = Quickfix::parse(&text);
+    }
+    let first_error = editor_state.quickfix.entries.first().cloned();
+
+    // Without files the ones of the last session are opened again, the
+    // shown one first
+    let resume = (options.resume || editor_state.resume)
+        && options.paths.is_empty()
+        && !editor_state.pager
+        && first_error.is_none();
+    let paths: Vec<PathBuf> = if resume {
+        session::load_session(".")
+            .into_iter()
+            .map(|place| place.path)
+            .filter(|path| path.is_file())
+            .collect()
     } else {
-        doc_lines = 0;
-        None
+        options.paths
     };
 
-    // Put the terminal in raw mode, which means that:
-    //  - The stdin doesn't go the stdout directly, its buffered.
-    //  - Any special characters `Ctrl + ...` has no special behaviours.
-    //  - New lines have no effect on the stdout, so their type must be 
-    //    explicit by sending a command to newline.
-    terminal::enable_raw_mode()?;
-
-    // Enable mouse support
-    let mut stdout = std::io::stdout();
-    execute!(stdout, 
-        terminal::EnterAlternateScreen,
-        terminal::EnableLineWrap,
-        crossterm::cursor::DisableBlinking,
-        EnableMouseCapture)?;
+    // A directory is shown on the file tree, its files are opened from
+    // there
+    let (dirs, paths): (Vec<PathBuf>, Vec<PathBuf>) = 
+        paths.into_iter().partition(|path| path.is_dir());
+    if let Some(dir) = dirs.first() {
+        editor_state.tree = Some(FileTree::new(dir)?);
+        editor_state.update_size();
+    }
+
+    // The gpg files are opened once their passphrase is typed
+    let mut paths = paths.into_iter();
+    let mut curr_doc = match paths.next() {
+        Some(path) if crypt::detect(&path) == Some(Cipher::Gpg) => {
+            editor_state.passphrase = Some(PassphrasePrompt::new(path));
+            None
+        }
+        // The editor starts anyway, with the next file that opens
+        Some(path) => match editor_state.open_document(&path, None) {
+            Ok(doc) => Some(doc),
+            Err(err) => {
+                editor_state.message.error(format!(
+                    "Could not open {}: {}", path.display(), err));
+                None
+            }
+        },
+        None if editor_state.pager && !std::io::stdin().is_terminal() => {
+            let mut bytes = Vec::new();
+            std::io::stdin().read_to_end(&mut bytes)?;
+
+            let doc = Document::from_bytes("[stdin]", &bytes);
+            editor_state.doc_lines = doc.buffer.len();
+            Some(doc)
+        }
+        None => None,
+    };
+
+    // The filetype given overrides the detected one, like for the files
+    // without extension or the stdin
+    if let (Some(filetype), Some(doc)) = (&options.filetype, &mut curr_doc) {
+        input::set_filetype(filetype, doc, &mut editor_state)?;
+    }
+
+    // The rest of the files wait on their buffers, once each
+    for path in paths {
+        let opened = editor_state.buffers.find(&path, &curr_doc).is_some();
+        if opened {
+            continue;
+        }
+        match editor_state.open_document(&path, None) {
+            Ok(doc) if curr_doc.is_none() && editor_state.passphrase.is_none()
+                => curr_doc = Some(doc),
+            Ok(doc) => editor_state.buffers.list.push(OpenBuffer {
+                doc: Some(doc),
+                ..OpenBuffer::default()
+            }),
+            Err(err) => editor_state.message.error(format!(
+                "Could not open {}: {}", path.display(), err)),
+        }
+    }
+    if options.readonly {
+        if let Some(doc) = &mut curr_doc {
+            doc.read_only = true;
+        }
+        for buffer in editor_state.buffers.list.iter_mut() {
+            if let Some(doc) = &mut buffer.doc {
+                doc.read_only = true;
+            }
+        }
+    }
+    editor_state.doc_lines = curr_doc.as_ref()
+        .map(|doc| doc.buffer.len())
+        .unwrap_or(0);
+
+    // The terminal is given back however the editor exits
+    let screen = Screen::enter()?;
+    let mut backend = Crossterm::new(std::io::stdout());
+
+    // The input and the files are read on their own threads from now on
+    editor_state.events.read_input();
+    editor_state.watcher.start(editor_state.events.sender());
 
     // Initial cursor position
     let mut cursor = Cursor {
@@ -65,70 +853,194 @@ fn main() -> Result<()> {
         row: 0
     };
 
-    // Editor state
-    let (columns, rows) = terminal::size()?;
-    let rows = (rows - 2) as usize;
-    let columns = (columns - 4) as usize;
-    let mut editor_state = EditorState {
-        running: true,
-        rows,
-        columns,
-        doc_lines
-    };
-
     // Cursor state needed to calculate movement
     let mut cursor_state = CursorState {
-        scroll_y: 0,
+        viewport: Viewport::default(),
         last_column: false,
-        last_padding: 0
+        last_padding: 0,
+        anchor: None,
+        cursors: CursorSet::default(),
+        parked: None,
     };
 
     // Render state to update the screen efficiently
-    let mut render_state = RenderState {
-        modif_row: None,
-        modif_all: true,
-        last_cursor: None,
-        modif_status: true,
-    };
+    let mut render_state = RenderState::new();
 
-    loop {
-        // Repaint on the screen what needs to be repainted
-        refresh_screen(
-            &mut stdout,
-            &curr_doc,
-            &cursor,
-            &editor_state,
-            &cursor_state,
-            &mut render_state)?;
-
-        render_state.last_cursor = None;
-        render_state.modif_status = false;
-        render_state.modif_all = false;
-        render_state.modif_row = None;
+    // The files are opened where they were left
+    if let Some(doc) = &curr_doc {
+        (cursor, cursor_state) = editor_state.left_at(doc);
+    }
+    let buffers = std::mem::take(&mut editor_state.buffers.list);
+    editor_state.buffers.list = buffers
+        .into_iter()
+        .map(|buffer| match &buffer.doc {
+            Some(doc) => {
+                let (cursor, cursor_state) = editor_state.left_at(doc);
+                OpenBuffer { cursor, cursor_state, ..buffer }
+            }
+            None => buffer,
+        })
+        .collect();
 
-        // Check if the editor should keep running, if it should close it will
-        // clear all it drawed
-        if !editor_state.running {
-            break;
+    // The positions given with the files go before where they were left
+    for (path, line, column) in &options.positions {
+        let rows = editor_state.rows;
+        match editor_state.buffers.find(path, &curr_doc) {
+            Some(idx) if idx == editor_state.buffers.current => {
+                if let Some(doc) = &curr_doc {
+                    (cursor, cursor_state) = start_at(doc, *line, *column, rows);
+                }
+            }
+            Some(idx) => {
+                let buffer = &mut editor_state.buffers.list[idx];
+                if let Some(doc) = &buffer.doc {
+                    (buffer.cursor, buffer.cursor_state) = 
+                        start_at(doc, *line, *column, rows);
+                }
+            }
+            None => {}
         }
+    }
 
-        // Process events
-        process_keypress(
+    // The line asked goes first, if the file is there
+    if let (Some(line), Some(doc)) = (options.line, &curr_doc) {
+        cursor.jump_to_line(
+            line - 1, &editor_state, &mut cursor_state, &mut render_state);
+        cursor.adjust_column_start(doc, &mut cursor_state);
+    }
+
+    if let (None, Some(error)) = (&curr_doc, first_error) {
+        let result = jump_to_location(
+            &error.path, 
+            error.line, 
+            Some(error.column),
             &mut curr_doc,
             &mut cursor,
             &mut editor_state,
             &mut cursor_state,
-            &mut render_state)?;
+            &mut render_state);
+        if let Err(err) = result {
+            editor_state.message.error(format!("Could not open {}: {}",
+                error.path.display(), err));
+        }
+    }
+
+    let mut editor = Editor::new(
+        curr_doc, cursor, editor_state, cursor_state, render_state);
+    let result = panic::catch_unwind(AssertUnwindSafe(||
+        run(&mut editor, &mut backend)));
+
+    // The unsaved changes are kept before leaving, the terminal is normal
+    // again when the error is told, a panic was told by its hook
+    let error = match result {
+        Ok(Ok(())) => None,
+        Ok(Err(err)) => Some(Some(err)),
+        Err(_) => Some(None),
+    };
+    if let Some(error) = error {
+        let recovered: Vec<_> = editor.unsaved()
+            .into_iter()
+            .map(|doc| (doc.path.clone(), recovery::write(doc)))
+            .collect();
+        drop(screen);
+
+        // Exiting skips the destructors, the swap files, the spill of the
+        // undo, the socket and the language servers are cleaned up before
+        drop(editor);
+
+        if let Some(err) = &error {
+            eprintln!("pepe: {}", err);
+        }
+        for (path, result) in recovered {
+            match result {
+                Ok(file) => eprintln!("pepe: the changes of {} are in {}",
+                    path.display(), file.display()),
+                Err(err) => eprintln!("pepe: the changes of {} are lost: {}",
+                    path.display(), err),
+            }
+        }
+        std::process::exit(if error.is_some() { 1 } else { 101 });
     }
 
-    // Disable mouse support and because we entered an alternative screen, when
-    // we leave we resume all the output that was before the editor execution
-    execute!(stdout,
-        terminal::LeaveAlternateScreen,
-        DisableMouseCapture)?;
+    let Editor { doc, cursor, mut editor_state, cursor_state, .. } = editor;
+    save_places(&doc, &cursor, &cursor_state, &mut editor_state);
 
-    // Back to normal terminal after closing
-    terminal::disable_raw_mode()?;
+    // The terminal is back on the directory of the shell
+    editor_state.osc.directory(&std::env::current_dir()?);
+    editor_state.osc.flush(&mut backend)?;
 
     Ok(())
 }
+
+/// Draw the frames and handle the events until the editor quits
+fn run(editor: &mut Editor, backend: &mut impl Backend) -> Result<()> {
+    loop {
+        editor.render(backend)?;
+
+        // Check if the editor should keep running, if it should close it will
+        // clear all it drawed
+        if !editor.editor_state.running {
+            return Ok(());
+        }
+
+        // Sleep until there is work or a timer is due, the terminal may
+        // have a new size by then
+        let wake = editor.editor_state.events.wait(editor.deadline());
+        editor.resize(backend.size()?);
+        editor.handle(wake)?;
+    }
+}
+
+/// The view of the document with the cursor on the `line` and the char
+/// `column` given on the command line, both from 1. The line is centered
+fn start_at(
+    doc: &Document,
+    line: usize,
+    column: Option<usize>,
+    rows: usize
+) -> (Cursor, CursorState) {
+    let line = line - 1;
+    let text = doc.buffer.line(line).unwrap_or("");
+    let byte = text.char_indices()
+        .nth(column.unwrap_or(1) - 1)
+        .map(|(byte, _)| byte)
+        .unwrap_or(text.len());
+    let place = Place {
+        path: doc.path.clone(),
+        line,
+        byte,
+        scroll_y: line.saturating_sub(rows / 2),
+    };
+
+    place.view(doc, rows)
+}
+
+/// Remember where every file open was left, and keep the buffers of the
+/// current tab as the session of the project. Failing to write them isn't
+/// worth keeping the editor from exiting
+fn save_places(
+    doc: &Option<Document>,
+    cursor: &Cursor,
+    cursor_state: &CursorState,
+    editor_state: &mut EditorState
+) {
+    // The state stored for the current tab is stale
+    let session = session::places(
+        doc, cursor, cursor_state, &editor_state.buffers);
+    let others: Vec<Place> = editor_state.tabs.list
+        .iter()
+        .enumerate()
+        .filter(|(idx, _)| *idx != editor_state.tabs.current)
+        .flat_map(|(_, tab)| session::places(
+            &tab.doc, &tab.cursor, &tab.cursor_state, &tab.buffers))
+        .collect();
+
+    // The files of the current tab are the newest places
+    for place in others.into_iter().chain(session.iter().cloned()) {
+        editor_state.places.remember(place);
+    }
+    let _ = editor_state.places.save();
+    if !session.is_empty() {
+        let _ = session::save_session(".", &session);
+    }
+}
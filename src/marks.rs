@@ -0,0 +1,144 @@
+//! The marks set on lines with a letter or a digit, and the jump list: the
+//! places the cursor left with a big motion, like a page, a search or going
+//! to a line, which can be visited back and forth. Both follow the lines
+//! they are on as lines are inserted or removed above them. They last for
+//! the session, the bookmarks are the ones kept on the project
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::text::LineShift;
+
+/// Places remembered on the jump list, the oldest are forgotten first
+const MAX_JUMPS: usize = 100;
+
+/// A place on a file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Location {
+    pub path: PathBuf,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Location {
+    /// Move it with the lines of its file
+    fn shift(&mut self, path: &Path, shifts: &[LineShift]) {
+        if self.path == path {
+            for shift in shifts {
+                self.line = shift.apply(self.line);
+            }
+        }
+    }
+
+    /// Follow its file to its new path
+    fn rename_file(&mut self, old_path: &Path, new_path: &Path) {
+        if self.path == old_path {
+            self.path = new_path.to_owned();
+        }
+    }
+}
+
+/// The marks of every file, by their key
+#[derive(Debug, Default)]
+pub struct Marks {
+    marks: BTreeMap<char, Location>,
+}
+
+impl Marks {
+    /// If the key can name a mark
+    pub fn is_valid(key: char) -> bool {
+        key.is_ascii_alphanumeric()
+    }
+
+    pub fn set(&mut self, key: char, location: Location) {
+        self.marks.insert(key, location);
+    }
+
+    pub fn get(&self, key: char) -> Option<&Location> {
+        self.marks.get(&key)
+    }
+
+    /// The keys of the marks and where they are, ordered by key
+    pub fn lines(&self) -> Vec<String> {
+        self.marks
+            .iter()
+            .map(|(key, Location { path, line, .. })|
+                format!("{}  {}:{}", key, path.display(), line + 1))
+            .collect()
+    }
+
+    pub fn shift(&mut self, path: &Path, shifts: &[LineShift]) {
+        for location in self.marks.values_mut() {
+            location.shift(path, shifts);
+        }
+    }
+
+    /// The file was renamed, its marks go with it
+    pub fn rename_file(&mut self, old_path: &Path, new_path: &Path) {
+        for location in self.marks.values_mut() {
+            location.rename_file(old_path, new_path);
+        }
+    }
+}
+
+/// The places jumped from, the newest last
+#[derive(Debug, Default)]
+pub struct JumpList {
+    list: Vec<Location>,
+
+    /// The place visited while going back, the length when not going back
+    current: usize,
+}
+
+impl JumpList {
+    /// Remember the place before a jump, the places gone back from are
+    /// forgotten. A jump from the same line is the same place
+    pub fn record(&mut self, location: Location) {
+        self.list.truncate(self.current);
+        let same_line = self.list.last().is_some_and(|last|
+            last.path == location.path && last.line == location.line);
+        if same_line {
+            self.list.pop();
+        }
+        self.list.push(location);
+        if self.list.len() > MAX_JUMPS {
+            self.list.remove(0);
+        }
+        self.current = self.list.len();
+    }
+
+    /// The place before the current one, going back from `here` the first
+    /// time remembers it to come forward again
+    pub fn back(&mut self, here: Location) -> Option<Location> {
+        if self.current == self.list.len() {
+            self.record(here);
+            self.current -= 1;
+        }
+        let previous = self.current.checked_sub(1)?;
+        self.current = previous;
+
+        Some(self.list[previous].clone())
+    }
+
+    /// The place after the current one while going back
+    pub fn forward(&mut self) -> Option<Location> {
+        let next = self.current + 1;
+        let location = self.list.get(next)?.clone();
+        self.current = next;
+
+        Some(location)
+    }
+
+    pub fn shift(&mut self, path: &Path, shifts: &[LineShift]) {
+        for location in &mut self.list {
+            location.shift(path, shifts);
+        }
+    }
+
+    /// The file was renamed, its places go with it
+    pub fn rename_file(&mut self, old_path: &Path, new_path: &Path) {
+        for location in &mut self.list {
+            location.rename_file(old_path, new_path);
+        }
+    }
+}
@@ -0,0 +1,230 @@
+//! The minibuffer, the row under the status bar. It shows the messages of
+//! the editor, like what was saved or what went wrong, until they expire,
+//! and the prompts that ask the user for a line of text, like the path to
+//! save the document as. The prompts of the command line, the search and the
+//! replace are typed there too
+//!
+//! A prompt doesn't block the editor, it's opened with the question and the
+//! keys go to it until the answer is typed, which is then handled by what
+//! the prompt was opened for
+
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+use crate::Result;
+
+/// A message shown on the minibuffer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Message {
+    pub text: String,
+
+    /// The errors are shown in red
+    pub error: bool,
+
+    shown: Instant,
+}
+
+/// What the editor tells the user, the last message is shown until it's
+/// replaced by another or it expires
+#[derive(Debug)]
+pub struct MessageArea {
+    current: Option<Message>,
+
+    /// How long the messages are shown, the errors twice as long
+    pub timeout: Duration,
+
+    /// Where every message is appended too, with `--log`
+    log: Option<File>,
+}
+
+impl Default for MessageArea {
+    fn default() -> Self {
+        Self {
+            current: None,
+            timeout: Duration::from_secs(4),
+            log: None,
+        }
+    }
+}
+
+impl MessageArea {
+    /// Show the text, nothing is shown when it's empty
+    pub fn show(&mut self, text: impl Into<String>) {
+        self.set(text.into(), false);
+    }
+
+    /// Show the text as an error
+    pub fn error(&mut self, text: impl Into<String>) {
+        self.set(text.into(), true);
+    }
+
+    /// Show the message of the result, or its error
+    pub fn result(&mut self, result: Result<String>) {
+        match result {
+            Ok(message) => self.show(message),
+            Err(err) => self.error(format!("Error: {}", err)),
+        }
+    }
+
+    /// Append the messages shown from now on to the file
+    pub fn log_to(&mut self, path: &Path) -> Result<()> {
+        let log = OpenOptions::new().create(true).append(true).open(path)?;
+        self.log = Some(log);
+        Ok(())
+    }
+
+    fn set(&mut self, text: String, error: bool) {
+        // A log that can't be written is not worth a message
+        if let (Some(log), false) = (&mut self.log, text.is_empty()) {
+            let seconds = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|elapsed| elapsed.as_secs())
+                .unwrap_or(0);
+            let level = if error { "error" } else { "info" };
+            let _ = writeln!(log, "{} {}: {}", seconds, level, text);
+        }
+
+        self.current = (!text.is_empty()).then(|| Message {
+            text,
+            error,
+            shown: Instant::now(),
+        });
+    }
+
+    /// The message being shown
+    pub fn current(&self) -> Option<&Message> {
+        self.current.as_ref()
+    }
+
+    /// Stop showing the message, returns its text
+    pub fn take(&mut self) -> Option<String> {
+        self.current.take().map(|message| message.text)
+    }
+
+    /// Remove the message once it has been shown long enough
+    pub fn expire(&mut self) {
+        if self.deadline().is_some_and(|deadline| Instant::now() >= deadline) {
+            self.current = None;
+        }
+    }
+
+    /// When the message shown expires, `None` without one
+    pub fn deadline(&self) -> Option<Instant> {
+        let message = self.current.as_ref()?;
+        let timeout = if message.error {
+            self.timeout * 2
+        } else {
+            self.timeout
+        };
+
+        Some(message.shown + timeout)
+    }
+}
+
+/// What the answer of a prompt is used for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PromptKind {
+    /// The path to save the document as
+    SaveAs,
+
+    /// The line, or the `line:column`, to go to
+    GoToLine,
+
+    /// What to do with a document whose file changed on disk: reload it,
+    /// keep it or see the differences
+    Reload,
+
+    /// If the file that changed on disk is overwritten with the document
+    Overwrite,
+
+    /// If the missing directory of a new file is created to save it
+    CreateDir,
+
+    /// The query of the file picker, the files matching it are listed on a
+    /// panel as it's typed
+    FindFile,
+
+    /// The name of the command to run from the palette, listed on a panel
+    /// the same way
+    Palette,
+
+    /// The key to mark the line with, or of the mark to go to
+    SetMark,
+    GoToMark,
+
+    /// If the document with unsaved changes is saved before quitting, its
+    /// changes are discarded or the editor keeps running
+    Quit,
+}
+
+impl PromptKind {
+    /// If the first key typed is the answer, without enter
+    fn takes_one_key(self) -> bool {
+        matches!(self, Self::SetMark | Self::GoToMark | Self::Quit)
+    }
+}
+
+/// A line of text asked to the user, it takes the input while open
+#[derive(Debug)]
+pub struct Prompt {
+    pub kind: PromptKind,
+    pub question: String,
+    pub input: String,
+}
+
+/// What a key did to a prompt
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Answer {
+    /// The answer is still being typed
+    Typing,
+
+    /// The answer was typed, `None` when the prompt was cancelled
+    Done(Option<String>),
+}
+
+impl Prompt {
+    /// Ask the question, the answer starts with `input`
+    pub fn new(kind: PromptKind, question: &str, input: &str) -> Self {
+        Self {
+            kind,
+            question: question.to_owned(),
+            input: input.to_owned(),
+        }
+    }
+
+    /// The text shown on the minibuffer
+    pub fn text(&self) -> String {
+        format!("{}{}", self.question, self.input)
+    }
+
+    /// The column where the input is being typed
+    pub fn input_column(&self) -> usize {
+        self.question.chars().count() + self.input.chars().count()
+    }
+
+    /// Type the key on the prompt, enter answers and escape cancels
+    pub fn key(&mut self, event: &KeyEvent) -> Answer {
+        match event.code {
+            KeyCode::Char(c) if !event.modifiers.intersects(
+                    KeyModifiers::CONTROL | KeyModifiers::ALT) => {
+                self.input.push(c);
+                if self.kind.takes_one_key() {
+                    return Answer::Done(Some(std::mem::take(&mut self.input)));
+                }
+            }
+            KeyCode::Backspace => {
+                self.input.pop();
+            }
+            KeyCode::Esc => return Answer::Done(None),
+            KeyCode::Enter =>
+                return Answer::Done(Some(std::mem::take(&mut self.input))),
+            _ => {}
+        }
+
+        Answer::Typing
+    }
+}
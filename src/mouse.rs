@@ -0,0 +1,176 @@
+//! Routing of the mouse events to what is under them. The popup is over
+//! everything, the bars and the sidebar are around the document area and
+//! an open panel covers it, so only the events that fall on the text reach
+//! the document. The widgets drawn over the document get a target here to
+//! be routed to
+
+use std::time::{Duration, Instant};
+
+use crate::EditorState;
+use crate::text::{Document, Position};
+use crate::unicode;
+use crate::viewport::Viewport;
+use crate::wrap::Layout;
+
+/// The presses closer in time than this on the same cell are double and
+/// triple clicks
+const MULTI_CLICK: Duration = Duration::from_millis(400);
+
+/// The last presses of the left button, to count the double and triple
+/// clicks
+#[derive(Debug, Default)]
+pub struct Clicks {
+    last: Option<(Instant, u16, u16)>,
+    count: usize,
+}
+
+impl Clicks {
+    /// Count a press on the cell, 1 for a single click, 2 for a double one
+    /// and 3 for a triple one. A fourth press starts again
+    pub fn press(&mut self, column: u16, row: u16) -> usize {
+        let now = Instant::now();
+        let follows = self.last.is_some_and(|(at, last_column, last_row)|
+            now.duration_since(at) < MULTI_CLICK
+                && (last_column, last_row) == (column, row));
+        self.count = if follows && self.count < 3 { self.count + 1 } else { 1 };
+        self.last = Some((now, column, row));
+
+        self.count
+    }
+}
+
+/// How often a drag held past the document area scrolls it a row
+const AUTOSCROLL: Duration = Duration::from_millis(50);
+
+/// The drag of a selection held past the top or the bottom of the document
+/// area, it keeps scrolling while the button isn't moved or released
+#[derive(Debug, Default)]
+pub struct AutoScroll {
+    held: Option<(Instant, u16, u16)>,
+}
+
+impl AutoScroll {
+    /// The drag went to the cell, `outside` when it's past the document
+    /// area
+    pub fn drag(&mut self, column: u16, row: u16, outside: bool) {
+        self.held = outside.then(|| (Instant::now() + AUTOSCROLL, column, row));
+    }
+
+    pub fn release(&mut self) {
+        self.held = None;
+    }
+
+    /// When the next scroll is due
+    pub fn deadline(&self) -> Option<Instant> {
+        self.held.map(|(at, _, _)| at)
+    }
+
+    /// The cell the drag is held on if a scroll is due
+    pub fn due(&self) -> Option<(u16, u16)> {
+        self.held
+            .filter(|(at, _, _)| *at <= Instant::now())
+            .map(|(_, column, row)| (column, row))
+    }
+}
+
+/// What is under a cell of the terminal
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    /// The hover popup
+    Popup,
+
+    /// The bar of the tabs, with the tab whose label is on the cell
+    TabsBar(Option<usize>),
+
+    /// The status bar and the minibuffer under it
+    StatusBar,
+
+    /// The title of the open panel
+    PanelTitle,
+
+    /// A row of the open panel, with the line of the panel shown on it
+    PanelRow(Option<usize>),
+
+    /// The sidebar of the file tree, with the entry shown on the row
+    Tree(Option<usize>),
+
+    /// The document area, the gutter and the scrollbar included
+    Document,
+}
+
+/// What is under the cell on the `column` and `row` of the terminal
+pub fn target(
+    column: u16,
+    row: u16,
+    document: &Option<Document>,
+    editor_state: &EditorState
+) -> Target {
+    let EditorState { top, rows, columns, panel, hover, tabs, .. } =
+        editor_state;
+    let (column, row) = (column as usize, row as usize);
+
+    let term_columns = columns + editor_state.gutter_width();
+    let over_popup = hover.popup
+        .as_ref()
+        .map(|popup| popup.area(*top, *rows, term_columns))
+        .is_some_and(|area| area.contains(column, row));
+    if over_popup {
+        return Target::Popup;
+    }
+    if row < *top {
+        return Target::TabsBar(tabs.at_column(document, column));
+    }
+    if row >= top + rows {
+        return Target::StatusBar;
+    }
+
+    match panel {
+        Some(_) if row == *top => Target::PanelTitle,
+        Some(panel) => {
+            let idx = panel.scroll + row - top - 1;
+            Target::PanelRow((idx < panel.lines.len()).then_some(idx))
+        }
+        None if column < editor_state.sidebar_width() => {
+            let idx = editor_state.tree.as_ref()
+                .map(|tree| (tree.scroll + row - top, tree.entries.len()))
+                .and_then(|(idx, len)| (idx < len).then_some(idx));
+            Target::Tree(idx)
+        }
+        None => Target::Document,
+    }
+}
+
+/// The position of the document under the cell on the `column` and `row`
+/// of the document area, the way back from where the cursor is shown. The
+/// columns are counted past the gutter and the horizontal scroll, and the
+/// tabs and the wide glyphs take all the cells they are drawn on. The
+/// cells past the end of a line are its end, and the rows past the end of
+/// the document its last line
+pub fn position_at(
+    column: usize,
+    row: usize,
+    doc: &Document,
+    Viewport { scroll_y, scroll_x }: &Viewport,
+    editor_state: &EditorState
+) -> Position {
+    let EditorState { rows, columns, tab_width, window, .. } = editor_state;
+    let column = column
+        .saturating_sub(editor_state.gutter_width())
+        .min(*columns);
+
+    // The rows of the wrapped lines are not the lines
+    if window.wrap {
+        let layout = Layout::new(doc, *scroll_y, *rows, *columns, *tab_width);
+        if let Some((line, byte)) = layout.position_at(doc, row, column) {
+            return doc.position(line, byte);
+        }
+    }
+
+    let last_line = doc.buffer.len().saturating_sub(1);
+    let line = usize::min(scroll_y + row.min(rows.saturating_sub(1)),
+        last_line);
+    let text = doc.buffer.line(line).unwrap_or("");
+    let byte = unicode::byte_at_column(text, column + scroll_x, *tab_width);
+
+    doc.position(line, byte)
+}
@@ -0,0 +1,76 @@
+//! Narrowing of a document to the lines that match a pattern, like `grep`
+//! inside the buffer. The lines are shown on a temporary document, saving
+//! it writes the lines edited back to the lines of the source they came
+//! from instead of to a file
+
+use std::path::PathBuf;
+
+use crate::Result;
+use crate::regex::Regex;
+use crate::text::Document;
+
+/// Where the lines of a narrowed document come from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Narrowed {
+    /// The document narrowed
+    pub source: PathBuf,
+
+    /// The line of the source of each line
+    pub lines: Vec<usize>,
+}
+
+/// A document with the lines of the source that match the pattern, or the
+/// ones that don't when `invert`
+pub fn narrow(
+    source: &Document,
+    pattern: &str,
+    invert: bool
+) -> Result<Document> {
+    let regex = Regex::new(pattern)?;
+    let (lines, text): (Vec<usize>, Vec<&str>) = source.buffer
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| regex.find_at(line, 0).is_some() != invert)
+        .unzip();
+    if lines.is_empty() {
+        return Err(format!("no line matches `{}`", pattern).into());
+    }
+
+    let filter = if invert { "narrow!" } else { "narrow" };
+    let path = format!("{} [{} {}]", source.path.display(), filter, pattern);
+    let mut contents = text.join("\n");
+    contents.push('\n');
+
+    let mut doc = Document::from_bytes(path, contents.as_bytes());
+    doc.read_only = source.read_only;
+    doc.narrow = Some(Narrowed { source: source.path.clone(), lines });
+
+    Ok(doc)
+}
+
+impl Narrowed {
+    /// The lines of the narrowed document that differ from their line of
+    /// the source, with the line of the source. The narrowed document must
+    /// keep one line for each line of the source
+    pub fn changes(
+        &self,
+        narrowed: &Document,
+        source: &Document
+    ) -> Result<Vec<(usize, String)>> {
+        if narrowed.buffer.len() != self.lines.len() {
+            return Err(format!("the narrowed view must keep its {} lines, \
+                they are written back one by one", self.lines.len()).into());
+        }
+        let last = self.lines.last().copied().unwrap_or(0);
+        if last >= source.buffer.len() {
+            return Err("the source has fewer lines than when narrowed".into());
+        }
+
+        Ok(narrowed.buffer
+            .lines()
+            .zip(&self.lines)
+            .filter(|(text, &line)| source.buffer[line] != **text)
+            .map(|(text, &line)| (line, text.to_owned()))
+            .collect())
+    }
+}
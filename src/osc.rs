@@ -0,0 +1,104 @@
+//! Shell integration, the terminal is told what the editor is doing through
+//! the same OSC sequences the shells send. OSC 7 has the directory of the
+//! file being edited, so the multiplexers open their new panes there, and
+//! the command line is marked with OSC 133 like a prompt of the shell, so
+//! the terminal knows where the commands start and if they failed
+//!
+//! The sequences wait until the frame is drawn, the terminals that don't
+//! know them just ignore them
+
+use std::path::Path;
+
+use crate::Result;
+use crate::backend::Backend;
+
+/// The sequences to send to the terminal
+pub struct ShellIntegration {
+    /// Disabled nothing is sent
+    pub enabled: bool,
+
+    pending: String,
+}
+
+impl Default for ShellIntegration {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            pending: String::new(),
+        }
+    }
+}
+
+impl ShellIntegration {
+    /// Queue the sequence unless disabled
+    fn queue(&mut self, sequence: &str) {
+        if self.enabled {
+            self.pending.push_str("\x1b]");
+            self.pending.push_str(sequence);
+            self.pending.push_str("\x1b\\");
+        }
+    }
+
+    /// The editor is now on the directory, it must be absolute
+    pub fn directory(&mut self, dir: &Path) {
+        self.queue(&format!("7;file://{}{}", hostname(), encode(dir)));
+    }
+
+    /// The command line was opened, the prompt is just its `:` so the input
+    /// starts right after
+    pub fn prompt(&mut self) {
+        self.queue("133;A");
+        self.queue("133;B");
+    }
+
+    /// The command typed is being run
+    pub fn command_started(&mut self) {
+        self.queue("133;C");
+    }
+
+    /// The command finished, `None` when it was cancelled before running
+    pub fn command_finished(&mut self, success: Option<bool>) {
+        match success {
+            Some(success) =>
+                self.queue(&format!("133;D;{}", if success { 0 } else { 1 })),
+            None => self.queue("133;D"),
+        }
+    }
+
+    /// Send the sequences queued since the last frame
+    pub fn flush(&mut self, backend: &mut impl Backend) -> Result<()> {
+        if !self.pending.is_empty() {
+            backend.write_raw(std::mem::take(&mut self.pending).as_bytes())?;
+            backend.flush()?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The name of the machine, the terminal checks it to know if the
+/// directory is a local one
+fn hostname() -> String {
+    std::env::var("HOSTNAME")
+        .ok()
+        .or_else(|| std::fs::read_to_string("/proc/sys/kernel/hostname").ok())
+        .or_else(|| std::fs::read_to_string("/etc/hostname").ok())
+        .map(|name| name.trim().to_owned())
+        .unwrap_or_default()
+}
+
+/// The path as the path of a `file://` URL, the bytes that are not allowed
+/// there are percent encoded
+fn encode(path: &Path) -> String {
+    let mut encoded = String::new();
+    for &byte in path.to_string_lossy().as_bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9'
+                    | b'/' | b'-' | b'_' | b'.' | b'~' =>
+                encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+
+    encoded
+}
@@ -0,0 +1,181 @@
+//! Auto-pairs: typing the opening symbol of a pair also inserts the closing
+//! one. Which pairs are used depends on the filetype, and both the pairs and
+//! the skip/delete behaviours can be changed by the user
+
+use std::collections::HashMap;
+
+/// Symbols that are inserted together
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pair {
+    pub open: char,
+    pub close: char,
+}
+
+/// What must be done when a char is typed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PairAction {
+    /// Insert the char as normal
+    Insert,
+
+    /// Insert the char and its closing symbol, leaving the cursor between
+    InsertPair(char),
+
+    /// The char is already the next one, just move over it
+    Skip,
+}
+
+/// A behaviour of the pairs, enabled unless disabled, globally or per
+/// filetype
+#[derive(Debug, Clone)]
+pub struct Behaviour {
+    pub enabled: bool,
+
+    /// Filetypes that override the global setting
+    pub filetypes: HashMap<String, bool>,
+}
+
+impl Default for Behaviour {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            filetypes: HashMap::new(),
+        }
+    }
+}
+
+impl Behaviour {
+    /// Enable or disable it for the filetype, or globally without one
+    pub fn set(&mut self, filetype: Option<&str>, enabled: bool) {
+        match filetype {
+            Some(filetype) => {
+                self.filetypes.insert(filetype.to_owned(), enabled);
+            }
+            None => self.enabled = enabled,
+        }
+    }
+
+    /// If the documents of the filetype behave this way
+    pub fn applies(&self, filetype: Option<&str>) -> bool {
+        filetype
+            .and_then(|filetype| self.filetypes.get(filetype))
+            .copied()
+            .unwrap_or(self.enabled)
+    }
+}
+
+/// The pairs used per filetype and how they behave
+pub struct PairSettings {
+    /// Typing the closing symbol when it's already the next char just moves
+    /// the cursor over it
+    pub skip_close: Behaviour,
+
+    /// Deleting the opening symbol of an empty pair also deletes the closing
+    /// one
+    pub delete_pair: Behaviour,
+
+    /// Pairs used when the filetype has no definitions of its own
+    pub default: Vec<Pair>,
+
+    /// Pairs of each filetype, they replace the default ones
+    pub filetypes: HashMap<String, Vec<Pair>>,
+}
+
+impl Default for PairSettings {
+    fn default() -> Self {
+        let mut filetypes = HashMap::new();
+
+        // Lifetimes and chars would produce a lot of unwanted `'`
+        filetypes.insert("rust".to_owned(), parse_pairs("()[]{}\"\""));
+        filetypes.insert("html".to_owned(), parse_pairs("()[]{}\"\"''<>"));
+        filetypes.insert("xml".to_owned(), parse_pairs("()[]{}\"\"''<>"));
+        filetypes.insert("markdown".to_owned(), parse_pairs("()[]{}``**"));
+        filetypes.insert("text".to_owned(), parse_pairs("()[]{}"));
+
+        Self {
+            skip_close: Behaviour::default(),
+            delete_pair: Behaviour::default(),
+            default: parse_pairs("()[]{}\"\"''"),
+            filetypes,
+        }
+    }
+}
+
+impl PairSettings {
+    /// The pairs that must be used on a document with this filetype
+    pub fn pairs(&self, filetype: Option<&str>) -> &[Pair] {
+        filetype
+            .and_then(|filetype| self.filetypes.get(filetype))
+            .unwrap_or(&self.default)
+    }
+
+    /// Define the pairs of a filetype from a string like `()[]<>`, an empty
+    /// string disables the auto-pairs for it
+    pub fn set_pairs(&mut self, filetype: &str, spec: &str) {
+        self.filetypes.insert(filetype.to_owned(), parse_pairs(spec));
+    }
+
+    /// Decide what to do when `ch` is typed at the byte `column` of `line`
+    pub fn on_insert(
+        &self,
+        filetype: Option<&str>,
+        line: &str,
+        column: usize,
+        ch: char
+    ) -> PairAction {
+        let pairs = self.pairs(filetype);
+        let next = line.get(column..).and_then(|rest| rest.chars().next());
+        let prev = line.get(..column).and_then(|rest| rest.chars().last());
+
+        // Moving over the closing symbol has preference, so typing `()` on
+        // a line with auto-pairs gives `()` and not `())`
+        if self.skip_close.applies(filetype)
+                && next == Some(ch)
+                && pairs.iter().any(|pair| pair.close == ch) {
+            return PairAction::Skip;
+        }
+
+        match pairs.iter().find(|pair| pair.open == ch) {
+            // A symmetric pair just after a word is most likely an
+            // apostrophe or a closing quote, so it's not paired
+            Some(pair) if pair.open == pair.close
+                    && prev.map(|c| c.is_alphanumeric()).unwrap_or(false) => {
+                PairAction::Insert
+            }
+            Some(pair) => PairAction::InsertPair(pair.close),
+            None => PairAction::Insert,
+        }
+    }
+
+    /// Check if deleting the char before the byte `column` of `line` must
+    /// also delete the one at `column`, because they form an empty pair
+    pub fn on_delete(
+        &self,
+        filetype: Option<&str>,
+        line: &str,
+        column: usize
+    ) -> bool {
+        if !self.delete_pair.applies(filetype) {
+            return false;
+        }
+
+        let next = line.get(column..).and_then(|rest| rest.chars().next());
+        let prev = line.get(..column).and_then(|rest| rest.chars().last());
+        match (prev, next) {
+            (Some(prev), Some(next)) => self.pairs(filetype)
+                .iter()
+                .any(|pair| pair.open == prev && pair.close == next),
+            _ => false,
+        }
+    }
+}
+
+/// Read pairs from a string where every two chars form a pair, a lone char
+/// at the end is ignored
+pub fn parse_pairs(spec: &str) -> Vec<Pair> {
+    let chars: Vec<char> = spec.chars().collect();
+
+    chars
+        .chunks_exact(2)
+        .map(|pair| Pair { open: pair[0], close: pair[1] })
+        .collect()
+}
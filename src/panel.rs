@@ -0,0 +1,154 @@
+//! Panels are views drawn over the document area, they show information
+//! about the document (like a diff) or lists of entries to pick from without
+//! modifying the document
+
+use crossterm::style::Color;
+
+/// What the panel shows, it decides how the panel reacts to the input
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PanelKind {
+    /// Plain text that can only be scrolled
+    Text,
+
+    /// The bookmarks of the project, they can be jumped to, deleted or have
+    /// its note edited
+    Bookmarks,
+
+    /// The errors of the quickfix list, they can be jumped to
+    Quickfix,
+
+    /// The snapshots of the document, they can be compared with it,
+    /// restored or deleted
+    Snapshots,
+
+    /// The output of the program running on the background, it grows
+    /// while the program runs
+    Output,
+
+    /// The project files matching the query typed on the prompt of the
+    /// file picker, the selected one is opened
+    Files,
+
+    /// The TODO comments of the project grouped by file, they can be
+    /// jumped to
+    Todos,
+
+    /// The commands whose name matches the query typed on the prompt of the
+    /// palette, the selected one is run
+    Commands,
+
+    /// The lines of the project files that match the pattern of `grep`,
+    /// they come while the search runs and the selected one is jumped to
+    Matches,
+}
+
+/// A scrollable list of lines shown instead of the document until closed
+pub struct Panel {
+    pub kind: PanelKind,
+
+    /// Shown on the first row of the panel
+    pub title: String,
+
+    /// The content of the panel
+    pub lines: Vec<String>,
+
+    /// The first line of `lines` on the screen
+    pub scroll: usize,
+
+    /// Decides the color each line is printed with
+    pub highlight: fn(&str) -> Color,
+
+    /// The selected line on panels that are a list of entries
+    pub selected: Option<usize>,
+
+    /// Text being typed to edit the selected entry, while `Some` the panel
+    /// input goes here
+    pub input: Option<String>,
+}
+
+impl Panel {
+    /// Create a text panel with no highlighting
+    pub fn new(title: impl Into<String>, lines: Vec<String>) -> Self {
+        Self {
+            kind: PanelKind::Text,
+            title: title.into(),
+            lines,
+            scroll: 0,
+            highlight: |_| Color::Reset,
+            selected: None,
+            input: None,
+        }
+    }
+
+    /// Create a panel whose lines are entries that can be selected, the
+    /// first one starts selected
+    pub fn list(
+        kind: PanelKind,
+        title: impl Into<String>,
+        lines: Vec<String>
+    ) -> Self {
+        let selected = if lines.is_empty() { None } else { Some(0) };
+
+        Self {
+            kind,
+            selected,
+            ..Self::new(title, lines)
+        }
+    }
+
+    /// Use `highlight` to color the lines of the panel
+    pub fn with_highlight(mut self, highlight: fn(&str) -> Color) -> Self {
+        self.highlight = highlight;
+        self
+    }
+
+    /// Scroll up by `n` lines if possible, on lists the selection moves
+    /// instead
+    pub fn scroll_up(&mut self, n: usize) {
+        if let Some(selected) = self.selected.as_mut() {
+            *selected = selected.saturating_sub(n);
+            self.scroll = usize::min(self.scroll, *selected);
+        } else {
+            self.scroll = self.scroll.saturating_sub(n);
+        }
+    }
+
+    /// Scroll down by `n` lines if possible, `rows` is the height of the
+    /// panel so the last line doesn't go over the top, on lists the
+    /// selection moves instead
+    pub fn scroll_down(&mut self, n: usize, rows: usize) {
+        if let Some(selected) = self.selected.as_mut() {
+            let max_selected = self.lines.len().saturating_sub(1);
+            *selected = usize::min(max_selected, *selected + n);
+            if *selected >= self.scroll + rows {
+                self.scroll = *selected + 1 - rows;
+            }
+        } else {
+            let max_scroll = self.lines.len().saturating_sub(rows);
+            self.scroll = usize::min(max_scroll, self.scroll + n);
+        }
+    }
+
+    /// Select the entry `idx` scrolling until it's visible
+    pub fn select(&mut self, idx: usize, rows: usize) {
+        self.selected = Some(idx);
+        if idx < self.scroll {
+            self.scroll = idx;
+        } else if idx >= self.scroll + rows {
+            self.scroll = idx + 1 - rows;
+        }
+    }
+
+    /// Replace the lines keeping the selection inside them
+    pub fn set_lines(&mut self, lines: Vec<String>) {
+        self.lines = lines;
+
+        if self.kind != PanelKind::Text {
+            let selected = self.selected.unwrap_or(0);
+            self.selected = self.lines.len().checked_sub(1)
+                .map(|last| usize::min(last, selected));
+        }
+        self.scroll = usize::min(
+            self.scroll, self.lines.len().saturating_sub(1));
+    }
+}
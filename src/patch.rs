@@ -0,0 +1,208 @@
+//! Unified diffs applied onto a document, like `patch` does with a file. The
+//! hunks are looked for where the diff says and, when the document changed
+//! since the diff was made, on the nearest lines where their context
+//! matches. The hunks that are not found anywhere are conflicts, the rest
+//! are applied anyway
+
+use std::path::Path;
+
+use crate::Result;
+
+/// A change of the diff, the lines it expects and the ones that replace
+/// them, both with the context
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hunk {
+    /// The line of the old version where it starts, from 0
+    pub start: usize,
+    pub old: Vec<String>,
+    pub new: Vec<String>,
+}
+
+/// The hunks of one of the files of the diff
+#[derive(Debug, Clone, Default)]
+pub struct FilePatch {
+    /// The path after `+++` without the `b/`, if the diff has it
+    pub path: Option<String>,
+    pub hunks: Vec<Hunk>,
+}
+
+/// A hunk that couldn't be applied
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Conflict {
+    /// Its index on the patch, from 0
+    pub hunk: usize,
+
+    /// Where the diff expected it, from 0
+    pub line: usize,
+}
+
+/// The lines of the document after applying a patch
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Applied {
+    pub lines: Vec<String>,
+    pub applied: usize,
+    pub conflicts: Vec<Conflict>,
+}
+
+/// Parse the files and the hunks of a unified diff, the lines that are not
+/// part of them, like the headers of `git diff` or a commit message, are
+/// skipped
+pub fn parse(text: &str) -> Result<Vec<FilePatch>> {
+    let mut files: Vec<FilePatch> = Vec::new();
+    let mut lines = text.lines().peekable();
+
+    while let Some(line) = lines.next() {
+        if let Some(path) = line.strip_prefix("+++ ") {
+            // The timestamp goes after a tab
+            let path = path.split('\t').next().unwrap_or(path).trim();
+            let path = path.strip_prefix("b/").unwrap_or(path);
+            files.push(FilePatch {
+                path: (path != "/dev/null").then(|| path.to_owned()),
+                hunks: Vec::new(),
+            });
+            continue;
+        }
+
+        let header = match line.strip_prefix("@@ ") {
+            Some(header) => header,
+            None => continue,
+        };
+        let (old_start, old_len) = parse_range(header, '-')?;
+        let (_, new_len) = parse_range(header, '+')?;
+
+        let mut hunk = Hunk {
+            start: old_start.saturating_sub(1),
+            old: Vec::new(),
+            new: Vec::new(),
+        };
+        // An empty old range starts after the line it names
+        if old_len == 0 {
+            hunk.start = old_start;
+        }
+        while hunk.old.len() < old_len || hunk.new.len() < new_len {
+            let line = match lines.next() {
+                Some(line) => line,
+                None => return Err("the diff ends in the middle of a hunk"
+                    .into()),
+            };
+
+            // Some editors remove the whitespace of the empty context lines
+            match line.chars().next() {
+                Some(' ') | None => {
+                    let text = line.get(1..).unwrap_or("").to_owned();
+                    hunk.old.push(text.clone());
+                    hunk.new.push(text);
+                }
+                Some('-') => hunk.old.push(line[1..].to_owned()),
+                Some('+') => hunk.new.push(line[1..].to_owned()),
+                Some('\\') => {}
+                _ => return Err(format!("unexpected line in a hunk: `{}`",
+                    line).into()),
+            }
+        }
+        // The missing final newline is marked after the last line
+        while lines.peek().is_some_and(|line| line.starts_with('\\')) {
+            lines.next();
+        }
+
+        match files.last_mut() {
+            Some(file) => file.hunks.push(hunk),
+            None => files.push(FilePatch { path: None, hunks: vec![hunk] }),
+        }
+    }
+
+    if files.iter().all(|file| file.hunks.is_empty()) {
+        return Err("there are no hunks in the diff".into());
+    }
+
+    Ok(files)
+}
+
+/// The `start,len` of the range after `sign` on the header of a hunk, the
+/// length is 1 when it's not there
+fn parse_range(header: &str, sign: char) -> Result<(usize, usize)> {
+    let range = header
+        .split_whitespace()
+        .find_map(|part| part.strip_prefix(sign))
+        .ok_or("the hunk header has no range")?;
+    let (start, len) = range.split_once(',').unwrap_or((range, "1"));
+
+    let parse = |number: &str| number.parse::<usize>()
+        .map_err(|_| format!("bad hunk range `{}`", range));
+    Ok((parse(start)?, parse(len)?))
+}
+
+/// The patch of the diff for the file at `path`, a diff of a single file is
+/// applied whatever its name is
+pub fn for_path<'a>(
+    files: &'a [FilePatch],
+    path: &Path
+) -> Option<&'a FilePatch> {
+    if let [file] = files {
+        return Some(file);
+    }
+
+    files.iter().find(|file| file.path
+        .as_ref()
+        .is_some_and(|name| path.ends_with(name)))
+}
+
+/// Apply the hunks onto the lines, each one is looked for where the diff
+/// says moved by what the hunks before it added or removed, and then on
+/// the nearest lines where it matches
+pub fn apply(lines: &[String], hunks: &[Hunk]) -> Applied {
+    let mut result = Vec::with_capacity(lines.len());
+    let mut conflicts = Vec::new();
+    let mut applied = 0;
+
+    // The lines before `copied` are already on the result, `offset` is how
+    // far the hunks found so far were from where the diff said
+    let mut copied = 0;
+    let mut offset: isize = 0;
+    for (idx, hunk) in hunks.iter().enumerate() {
+        let expected = (hunk.start as isize + offset).max(0) as usize;
+        match find(lines, &hunk.old, expected, copied) {
+            Some(at) => {
+                result.extend_from_slice(&lines[copied..at]);
+                result.extend_from_slice(&hunk.new);
+                copied = at + hunk.old.len();
+                offset = at as isize - hunk.start as isize;
+                applied += 1;
+            }
+            None => conflicts.push(Conflict { hunk: idx, line: hunk.start }),
+        }
+    }
+    result.extend_from_slice(&lines[copied..]);
+
+    Applied { lines: result, applied, conflicts }
+}
+
+/// The line nearest to `expected`, not before `from`, where the old lines
+/// of a hunk are
+fn find(
+    lines: &[String],
+    old: &[String],
+    expected: usize,
+    from: usize
+) -> Option<usize> {
+    let last = lines.len().checked_sub(old.len())?;
+    let matches = |at: usize| lines[at..at + old.len()] == *old;
+
+    let expected = expected.clamp(from, usize::max(from, last));
+    for distance in 0..=lines.len() {
+        let after = expected + distance;
+        if after <= last && matches(after) {
+            return Some(after);
+        }
+        match expected.checked_sub(distance) {
+            Some(before) if before >= from && before <= last
+                && matches(before) => return Some(before),
+            _ => {}
+        }
+        if after > last && expected < from + distance {
+            break;
+        }
+    }
+
+    None
+}
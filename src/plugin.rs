@@ -0,0 +1,117 @@
+//! Plugins compiled with the editor. Unlike the hooks, a plugin is a value
+//! with its own state that hears about several moments: a document opened
+//! or about to be saved, a key pressed and a line about to be drawn, where
+//! it can style parts of the line. Highlighters, linters or formatters can
+//! live on their own module this way, the core only goes through the list
+
+use crossterm::event::KeyEvent;
+use crossterm::style::{ContentStyle, Stylize};
+
+use crate::Result;
+use crate::text::Document;
+
+/// The bytes `start..end` of a line and how they are drawn
+pub type Span = (usize, usize, ContentStyle);
+
+/// Every callback does nothing unless the plugin needs it
+pub trait Plugin {
+    /// Name shown by the `plugins` command
+    fn name(&self) -> &str;
+
+    /// The document has just been loaded from disk
+    fn on_open(&mut self, _doc: &mut Document) -> Result<()> {
+        Ok(())
+    }
+
+    /// The document is about to be written, the changes are included on
+    /// the write
+    fn on_save(&mut self, _doc: &mut Document) -> Result<()> {
+        Ok(())
+    }
+
+    /// A key pressed on the document, if it's taken the editor does nothing
+    /// else with it
+    fn on_key(&mut self, _key: &KeyEvent, _doc: &mut Document) -> Result<bool> {
+        Ok(false)
+    }
+
+    /// The parts of the line `idx` that are drawn with another style
+    fn on_render_line(&self, _idx: usize, _line: &str) -> Vec<Span> {
+        Vec::new()
+    }
+}
+
+/// The plugins of the editor, they hear the events in the order they were
+/// registered and the first one that fails stops the rest
+pub struct Plugins {
+    list: Vec<Box<dyn Plugin>>,
+}
+
+impl Default for Plugins {
+    /// The plugins that come with the editor
+    fn default() -> Self {
+        let mut plugins = Self { list: Vec::new() };
+        plugins.register(ConflictMarkers);
+
+        plugins
+    }
+}
+
+impl Plugins {
+    pub fn register(&mut self, plugin: impl Plugin + 'static) {
+        self.list.push(Box::new(plugin));
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.list.iter().map(|plugin| plugin.name()).collect()
+    }
+
+    pub fn open(&mut self, doc: &mut Document) -> Result<()> {
+        self.list.iter_mut().try_for_each(|plugin| plugin.on_open(doc))
+    }
+
+    pub fn save(&mut self, doc: &mut Document) -> Result<()> {
+        self.list.iter_mut().try_for_each(|plugin| plugin.on_save(doc))
+    }
+
+    /// If a plugin took the key
+    pub fn key(&mut self, key: &KeyEvent, doc: &mut Document) -> Result<bool> {
+        for plugin in self.list.iter_mut() {
+            if plugin.on_key(key, doc)? {
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+
+    /// The spans of every plugin, the later ones are drawn over the earlier
+    pub fn spans(&self, idx: usize, line: &str) -> Vec<Span> {
+        self.list
+            .iter()
+            .flat_map(|plugin| plugin.on_render_line(idx, line))
+            .collect()
+    }
+}
+
+/// The markers left by a merge that couldn't be solved stand out, so they
+/// aren't saved by mistake
+struct ConflictMarkers;
+
+impl Plugin for ConflictMarkers {
+    fn name(&self) -> &str {
+        "conflict-markers"
+    }
+
+    fn on_render_line(&self, _idx: usize, line: &str) -> Vec<Span> {
+        let marker = ["<<<<<<<", "|||||||", "=======", ">>>>>>>"]
+            .iter()
+            .any(|marker| line.strip_prefix(marker)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with(' ')));
+        if !marker {
+            return Vec::new();
+        }
+
+        vec![(0, line.len(), ContentStyle::new().white().on_red().bold())]
+    }
+}
@@ -0,0 +1,115 @@
+//! The preview of a document rendered by an external program, like `mdcat`
+//! for markdown or `dot -Tplain` for graphviz. The program of each filetype
+//! is set on the `[preview]` table of the config, it gets the text of the
+//! document on its stdin and what it prints is shown on a panel. The
+//! escapes of its output are dropped, the panel only shows text. Once
+//! shown, saving the document runs the program again
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use crate::Result;
+use crate::cancel::CancelToken;
+use crate::panel::Panel;
+use crate::shell::pipe_shell;
+use crate::text::Document;
+
+/// The programs that preview each filetype
+#[derive(Debug, Default)]
+pub struct PreviewCommands {
+    commands: HashMap<String, String>,
+}
+
+impl PreviewCommands {
+    pub fn set(&mut self, filetype: &str, command: &str) {
+        self.commands.insert(filetype.to_owned(), command.to_owned());
+    }
+
+    /// The program of the filetype of the document
+    pub fn of(&self, doc: &Document) -> Option<&str> {
+        self.commands.get(doc.filetype()?).map(String::as_str)
+    }
+}
+
+/// The last preview of a document, refreshed when it's saved
+#[derive(Debug)]
+pub struct Preview {
+    pub path: PathBuf,
+    pub command: String,
+    pub lines: Vec<String>,
+}
+
+impl Preview {
+    /// Run the program over the text of the document
+    pub fn run(
+        doc: &Document,
+        command: &str,
+        cancel: &CancelToken
+    ) -> Result<Self> {
+        let mut preview = Self {
+            path: doc.path.clone(),
+            command: command.to_owned(),
+            lines: Vec::new(),
+        };
+        preview.refresh(doc, cancel)?;
+
+        Ok(preview)
+    }
+
+    /// Run the program again over the text the document has now
+    pub fn refresh(&mut self, doc: &Document, cancel: &CancelToken)
+            -> Result<()> {
+        let text = doc.buffer.join("\n");
+        let output = pipe_shell(&self.command, text.as_bytes(), cancel)?;
+        self.lines = output.lines().map(strip_escapes).collect();
+
+        Ok(())
+    }
+
+    /// The panel that shows the output
+    pub fn panel(&self) -> Panel {
+        Panel::new(
+            format!("Preview of {} with `{}` -- refreshed on save",
+                self.path.display(), self.command),
+            self.lines.clone())
+    }
+}
+
+/// The line without the escape sequences of the terminal, the colors and
+/// the links
+pub fn strip_escapes(line: &str) -> String {
+    let mut text = String::with_capacity(line.len());
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            text.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            // Until the final byte of the control sequence
+            Some('[') => {
+                for c in chars.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            // Until the bell or the string terminator
+            Some(']') => {
+                while let Some(c) = chars.next() {
+                    if c == '\x07' {
+                        break;
+                    }
+                    if c == '\x1b' && chars.peek() == Some(&'\\') {
+                        chars.next();
+                        break;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    text
+}
@@ -0,0 +1,298 @@
+//! Hard copies of the document, it can be sent as plain text to the printer
+//! with `lp` or exported as PostScript/PDF generated here. The line numbers
+//! (`printnumbers`) and the colors of the highlights on the PDF
+//! (`printhighlight`) are options
+
+use std::io::Write;
+use std::path::PathBuf;
+
+use crossterm::style::Color;
+
+use crate::Result;
+use crate::plugin::Span;
+use crate::shell::pipe_program;
+use crate::text::Document;
+
+/// Size of a letter page in PostScript points
+const PAGE_WIDTH: usize = 612;
+const PAGE_HEIGHT: usize = 792;
+
+/// Space left on every side of the page
+const MARGIN: usize = 36;
+
+/// The font is Courier 10, each char is 6 points wide and every line takes
+/// 12 points
+const FONT_SIZE: usize = 10;
+const CHAR_WIDTH: usize = 6;
+const LINE_HEIGHT: usize = 12;
+
+/// Where the document is sent
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PrintTarget {
+    /// The default printer through `lp`, as plain text
+    Lp,
+
+    /// A PDF next to the document, if `ps2pdf` is not available the
+    /// PostScript is left instead
+    Pdf,
+}
+
+/// What the hard copies have
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PrintOptions {
+    /// The line numbers as a gutter
+    pub numbers: bool,
+
+    /// The text in the colors of its highlights, only on the PDF
+    pub highlight: bool,
+}
+
+impl Default for PrintOptions {
+    fn default() -> Self {
+        Self { numbers: true, highlight: false }
+    }
+}
+
+/// Print the document, returns a message describing the result. The tabs
+/// are expanded to `tab_width` spaces on the PDF, and `spans` gives the
+/// highlights of a line. An existing export is never overwritten
+pub fn print_document(
+    doc: &Document,
+    target: PrintTarget,
+    options: PrintOptions,
+    tab_width: usize,
+    spans: impl Fn(usize, &str) -> Vec<Span>
+) -> Result<String> {
+    match target {
+        PrintTarget::Lp => {
+            let text = format_plain(doc, options.numbers);
+            let output = pipe_program("lp", &["-t", &doc.path.display().to_string()],
+                text.as_bytes())?;
+
+            Ok(format!("Sent to lp: {}", output.trim()))
+        }
+        PrintTarget::Pdf => {
+            let ps = format_postscript(doc, options, tab_width, spans);
+            let pdf_path = with_extension(doc, "pdf")?;
+
+            // Without ps2pdf the PostScript is still useful
+            let pdf_arg = pdf_path.display().to_string();
+            match pipe_program("ps2pdf", &["-", &pdf_arg], ps.as_bytes()) {
+                Ok(_) => Ok(format!("Exported to {}", pdf_path.display())),
+                Err(_) => {
+                    let ps_path = with_extension(doc, "ps")?;
+                    std::fs::OpenOptions::new()
+                        .write(true)
+                        .create_new(true)
+                        .open(&ps_path)?
+                        .write_all(ps.as_bytes())?;
+
+                    Ok(format!("ps2pdf not available, exported to {}",
+                        ps_path.display()))
+                }
+            }
+        }
+    }
+}
+
+/// The document as plain text, with the line numbers as a gutter if asked
+pub fn format_plain(doc: &Document, line_numbers: bool) -> String {
+    let width = number_width(doc);
+    let mut text = String::new();
+
+    for (idx, line) in doc.buffer.lines().enumerate() {
+        if line_numbers {
+            text.push_str(&format!("{:width$} ", idx + 1, width = width));
+        }
+        text.push_str(line);
+        text.push('\n');
+    }
+
+    text
+}
+
+/// The document as a PostScript program, with a header on every page and
+/// the line numbers in gray, lines longer than the page are wrapped. With
+/// the highlight the text has the colors of the `spans` of its line
+pub fn format_postscript(
+    doc: &Document,
+    options: PrintOptions,
+    tab_width: usize,
+    spans: impl Fn(usize, &str) -> Vec<Span>
+) -> String {
+    let title = escape(&doc.path.display().to_string());
+    let number_width = if options.numbers { number_width(doc) + 1 } else { 0 };
+    let text_columns = (PAGE_WIDTH - 2 * MARGIN) / CHAR_WIDTH - number_width;
+
+    // The first row of every page is the header
+    let rows_per_page = (PAGE_HEIGHT - 2 * MARGIN) / LINE_HEIGHT - 2;
+
+    // Split the lines on rows that fit the page, only the first row of each
+    // line has the number. Every char has the color of the last span over
+    // it, a tab is spaces of its color
+    let mut rows: Vec<(Option<usize>, Vec<Cell>)> = Vec::new();
+    for (idx, line) in doc.buffer.lines().enumerate() {
+        let spans = if options.highlight { spans(idx, line) } else { vec![] };
+        let mut chars = Vec::new();
+        for (byte, c) in line.char_indices() {
+            let color = spans.iter()
+                .rev()
+                .find(|(start, end, _)| (*start..*end).contains(&byte))
+                .and_then(|(_, _, style)| style.foreground_color)
+                .and_then(rgb);
+            match c {
+                '\t' => chars
+                    .extend(std::iter::repeat_n((' ', color), tab_width)),
+                c => chars.push((c, color)),
+            }
+        }
+        if chars.is_empty() {
+            rows.push((Some(idx + 1), Vec::new()));
+        }
+        for (i, chunk) in chars.chunks(text_columns).enumerate() {
+            let number = if i == 0 { Some(idx + 1) } else { None };
+            rows.push((number, chunk.to_vec()));
+        }
+    }
+    let pages = usize::max(1, rows.len().div_ceil(rows_per_page));
+
+    let mut ps = String::new();
+    ps.push_str("%!PS-Adobe-3.0\n");
+    ps.push_str(&format!("%%Title: ({})\n", title));
+    ps.push_str(&format!("%%Pages: {}\n", pages));
+    ps.push_str("%%EndComments\n");
+
+    for page in 0..pages {
+        ps.push_str(&format!("%%Page: {} {}\n", page + 1, page + 1));
+        ps.push_str(&format!("/Courier-Bold findfont {} scalefont setfont\n",
+            FONT_SIZE));
+
+        let mut y = PAGE_HEIGHT - MARGIN - LINE_HEIGHT;
+        ps.push_str(&format!("{} {} moveto ({}    page {} of {}) show\n",
+            MARGIN, y, title, page + 1, pages));
+        ps.push_str(&format!("/Courier findfont {} scalefont setfont\n",
+            FONT_SIZE));
+        y -= 2 * LINE_HEIGHT;
+
+        let page_rows = rows.iter().skip(page * rows_per_page).take(rows_per_page);
+        for (number, text) in page_rows {
+            ps.push_str(&format!("{} {} moveto ", MARGIN, y));
+            if options.numbers {
+                let number = number
+                    .map(|n| format!("{:width$} ", n, width = number_width - 1))
+                    .unwrap_or_else(|| " ".repeat(number_width));
+                ps.push_str(&format!("0.5 setgray ({}) show 0 setgray ",
+                    number));
+            }
+
+            // A run of chars for every color
+            for run in text.chunk_by(|a, b| a.1 == b.1) {
+                let text: String = run.iter().map(|(c, _)| c).collect();
+                match run[0].1 {
+                    Some((r, g, b)) => ps.push_str(&format!(
+                        "{:.3} {:.3} {:.3} setrgbcolor ({}) show 0 setgray ",
+                        r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0,
+                        escape(&text))),
+                    None => ps.push_str(&format!("({}) show ", escape(&text))),
+                }
+            }
+            ps.push('\n');
+
+            y -= LINE_HEIGHT;
+        }
+
+        ps.push_str("showpage\n");
+    }
+    ps.push_str("%%EOF\n");
+
+    ps
+}
+
+/// Digits needed by the biggest line number
+fn number_width(doc: &Document) -> usize {
+    doc.buffer.len().max(1).to_string().len()
+}
+
+/// The path of the document with another extension appended, a file that
+/// is already there is not replaced
+fn with_extension(doc: &Document, extension: &str) -> Result<PathBuf> {
+    let mut path = doc.path.clone().into_os_string();
+    path.push(".");
+    path.push(extension);
+
+    let path = PathBuf::from(path);
+    if path.symlink_metadata().is_ok() {
+        return Err(format!("{} already exists", path.display()).into());
+    }
+
+    Ok(path)
+}
+
+/// A color as its red, green and blue
+type Rgb = (u8, u8, u8);
+
+/// A char of a row of the page and its color
+type Cell = (char, Option<Rgb>);
+
+/// The color on the paper, the usual values of the terminals for the named
+/// ones. White and the default color are black, or they wouldn't be seen
+fn rgb(color: Color) -> Option<Rgb> {
+    /// The 16 colors of the terminals
+    const NAMED: [Rgb; 16] = [
+        (0, 0, 0), (205, 0, 0), (0, 205, 0), (205, 205, 0),
+        (0, 0, 238), (205, 0, 205), (0, 205, 205), (229, 229, 229),
+        (127, 127, 127), (255, 0, 0), (0, 255, 0), (255, 255, 0),
+        (92, 92, 255), (255, 0, 255), (0, 255, 255), (255, 255, 255),
+    ];
+    let named = |idx: usize| Some(NAMED[idx]);
+
+    match color {
+        Color::Reset | Color::Black | Color::White | Color::Grey => None,
+        Color::DarkRed => named(1),
+        Color::DarkGreen => named(2),
+        Color::DarkYellow => named(3),
+        Color::DarkBlue => named(4),
+        Color::DarkMagenta => named(5),
+        Color::DarkCyan => named(6),
+        Color::DarkGrey => named(8),
+        Color::Red => named(9),
+        Color::Green => named(10),
+        Color::Yellow => named(11),
+        Color::Blue => named(12),
+        Color::Magenta => named(13),
+        Color::Cyan => named(14),
+        Color::Rgb { r, g, b } => Some((r, g, b)),
+        Color::AnsiValue(value @ 0..=15) => match value {
+            0 | 7 | 15 => None,
+            value => named(value as usize),
+        },
+        // The 6x6x6 cube and the grays
+        Color::AnsiValue(value @ 16..=231) => {
+            let level = |n: u8| if n == 0 { 0 } else { 55 + 40 * n };
+            let value = value - 16;
+            Some((level(value / 36), level(value / 6 % 6), level(value % 6)))
+        }
+        Color::AnsiValue(value) => {
+            let gray = 8 + 10 * (value - 232);
+            Some((gray, gray, gray))
+        }
+    }
+}
+
+/// Escape a string to be a PostScript string literal, the standard fonts
+/// only have ASCII so the rest of chars are replaced
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '(' | ')' | '\\' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            ' '..='~' => escaped.push(c),
+            _ => escaped.push('?'),
+        }
+    }
+
+    escaped
+}
@@ -0,0 +1,59 @@
+//! Helpers for writing prose: the length of the cursor line is shown against
+//! the text width, and lines longer than it can be wrapped while typing
+
+/// Width of the text when not set
+pub const DEFAULT_TEXTWIDTH: usize = 80;
+
+/// How prose is written
+#[derive(Debug)]
+pub struct ProseSettings {
+    /// Column where the text is wrapped
+    pub textwidth: usize,
+
+    /// Show how long the cursor line is next to the text width
+    pub indicator: bool,
+
+    /// Wrap the line at the text width while typing
+    pub auto_wrap: bool,
+}
+
+impl Default for ProseSettings {
+    fn default() -> Self {
+        Self {
+            textwidth: DEFAULT_TEXTWIDTH,
+            indicator: true,
+            auto_wrap: false,
+        }
+    }
+}
+
+/// If the documents of the filetype are prose instead of code
+pub fn is_prose(filetype: Option<&str>) -> bool {
+    matches!(filetype, Some("markdown") | Some("text"))
+}
+
+/// Break the line on lines no longer than `width` chars at the whitespace,
+/// the words longer than the width are left alone on their line. The
+/// indentation of the line is kept on all the lines
+pub fn wrap_line(line: &str, width: usize) -> Vec<String> {
+    let indent_len = line.len() - line.trim_start().len();
+    let indent = &line[..indent_len];
+
+    let mut lines = Vec::new();
+    let mut current = indent.to_owned();
+    for word in line.split_whitespace() {
+        let current_len = current.chars().count();
+        let fits = current_len + 1 + word.chars().count() <= width;
+        if current.len() > indent_len && !fits {
+            lines.push(std::mem::replace(&mut current, indent.to_owned()));
+        }
+
+        if current.len() > indent_len {
+            current.push(' ');
+        }
+        current.push_str(word);
+    }
+    lines.push(current);
+
+    lines
+}
@@ -0,0 +1,76 @@
+//! Quickfix list, the errors of a build as `file:line:col: message` lines
+//! that can be jumped to, and are shown over the lines they point to
+
+use std::path::{Path, PathBuf};
+
+/// An error pointing to a position of a file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuickfixEntry {
+    pub path: PathBuf,
+
+    /// Position on the document, starting from 0
+    pub line: usize,
+    pub column: usize,
+
+    pub message: String,
+}
+
+/// All the errors loaded
+#[derive(Debug, Default)]
+pub struct Quickfix {
+    pub entries: Vec<QuickfixEntry>,
+}
+
+impl Quickfix {
+    /// Parse the output of a build, the lines that don't point to a file are
+    /// ignored. The column is optional
+    pub fn parse(text: &str) -> Self {
+        let entries = text
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.trim_start().splitn(3, ':');
+                let path = fields.next().filter(|path| !path.is_empty())?;
+                let line: usize = fields.next()?.trim().parse().ok()?;
+                let rest = fields.next().unwrap_or("");
+
+                // Without column the message is all after the line
+                let column = rest.split_once(':')
+                    .and_then(|(column, message)| {
+                        Some((column.trim().parse::<usize>().ok()?, message))
+                    });
+                let (column, message) = column.unwrap_or((1, rest));
+
+                Some(QuickfixEntry {
+                    path: PathBuf::from(path),
+                    line: line.saturating_sub(1),
+                    column: column.saturating_sub(1),
+                    message: message.trim().to_owned(),
+                })
+            })
+            .collect();
+
+        Self { entries }
+    }
+
+    /// The errors on a line of a file
+    pub fn on_line<'a>(
+        &'a self,
+        path: &'a Path,
+        line: usize
+    ) -> impl Iterator<Item = &'a QuickfixEntry> {
+        self.entries
+            .iter()
+            .filter(move |entry| entry.path == path && entry.line == line)
+    }
+
+    /// Human readable version of the errors, one per line
+    pub fn lines(&self) -> Vec<String> {
+        self.entries
+            .iter()
+            .map(|QuickfixEntry { path, line, column, message }| {
+                format!("{}:{}:{}  {}",
+                    path.display(), line + 1, column + 1, message)
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,54 @@
+//! When the editor dies, from an error or a panic, the documents with
+//! unsaved changes are written to recovery files before it exits, and the
+//! user is told where they are. They go next to their files as
+//! `<name>.pepe-recovered`, the ones that are no files and the ones whose
+//! directory can't be written go to the temporary directory. Only the user
+//! can read them
+
+use std::ffi::OsString;
+use std::io::Write;
+use std::path::PathBuf;
+
+use crate::Result;
+use crate::fileops;
+use crate::text::{DocKind, Document};
+
+/// Write the contents of the document to its recovery file, returns where
+pub fn write(doc: &Document) -> Result<PathBuf> {
+    // Its contents were never on the disk in clear
+    if doc.encryption.is_some() {
+        return Err("it's encrypted".into());
+    }
+
+    let (contents, _) = doc.contents(false);
+    let name = doc.path.file_name().unwrap_or(doc.path.as_os_str());
+    let recovered = |prefix: &str, suffix: &str| {
+        let mut recovered = OsString::from(prefix);
+        recovered.push(name);
+        recovered.push(suffix);
+        recovered.push(".pepe-recovered");
+        recovered
+    };
+
+    // An earlier recovery is never overwritten, the next one gets a random
+    // name
+    if doc.kind == DocKind::File {
+        let next_to_file = std::iter::once(recovered("", ""))
+            .chain((0..8).map(|_|
+                recovered("", &format!(".{}", fileops::random_suffix()))))
+            .map(|recovered| doc.path.with_file_name(recovered));
+        if let Ok((path, mut file)) = fileops::create_private(next_to_file) {
+            if file.write_all(contents.as_bytes()).is_ok() {
+                return Ok(path);
+            }
+            let _ = std::fs::remove_file(&path);
+        }
+    }
+
+    let temporary = (0..8).map(|_| std::env::temp_dir().join(
+        recovered(&format!("pepe-{}-", fileops::random_suffix()), "")));
+    let (path, mut file) = fileops::create_private(temporary)?;
+    file.write_all(contents.as_bytes())?;
+
+    Ok(path)
+}
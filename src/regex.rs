@@ -0,0 +1,678 @@
+//! A small regular expression engine for the replace, the patterns match
+//! inside a single line. The syntax is the usual one: `.`, classes like
+//! `[a-z]` and `[^0-9]`, the escapes `\d \w \s` (and upper case to negate),
+//! the anchors `^ $ \b`, groups `(..)` and `(?:..)`, alternation and the
+//! quantifiers `* + ? {n} {n,} {n,m}`, lazy when followed by `?`.
+//!
+//! The pattern is compiled to a program run by a backtracking machine that
+//! never visits the same instruction at the same position twice, so the
+//! matching is linear on the length of the line
+
+/// Repetitions allowed on a counted quantifier, each one is a copy of the
+/// program of the repeated part
+const MAX_REPETITIONS: usize = 1000;
+
+/// Instructions allowed on a program, nested repetitions multiply their
+/// copies
+const MAX_PROGRAM: usize = 10_000;
+
+/// A set of chars
+#[derive(Debug, Clone)]
+struct Class {
+    ranges: Vec<(char, char)>,
+    negated: bool,
+}
+
+impl Class {
+    fn matches(&self, c: char) -> bool {
+        let inside = self.ranges
+            .iter()
+            .any(|&(start, end)| start <= c && c <= end);
+
+        inside != self.negated
+    }
+
+    /// The class of an escape like `\d`, `None` if the escape is not a class
+    fn from_escape(escape: char) -> Option<Self> {
+        let ranges = match escape.to_ascii_lowercase() {
+            'd' => vec![('0', '9')],
+            'w' => vec![('a', 'z'), ('A', 'Z'), ('0', '9'), ('_', '_')],
+            's' => vec![(' ', ' '), ('\t', '\t'), ('\r', '\r'),
+                ('\u{b}', '\u{c}')],
+            _ => return None,
+        };
+
+        Some(Self {
+            ranges,
+            negated: escape.is_ascii_uppercase(),
+        })
+    }
+}
+
+/// The parsed pattern
+#[derive(Debug)]
+enum Ast {
+    Empty,
+    Literal(char),
+    Any,
+    Class(Class),
+    LineStart,
+    LineEnd,
+    WordBoundary,
+
+    /// A group, with the index of its capture when capturing
+    Group(Box<Ast>, Option<usize>),
+
+    Concat(Vec<Ast>),
+    Alternation(Vec<Ast>),
+    Repeat {
+        ast: Box<Ast>,
+        min: usize,
+        max: Option<usize>,
+        greedy: bool,
+    },
+}
+
+/// Parser of the patterns, recursive descent over the chars
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    groups: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += 1;
+        Some(c)
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn alternation(&mut self) -> Result<Ast, String> {
+        let mut alternatives = vec![self.concat()?];
+        while self.eat('|') {
+            alternatives.push(self.concat()?);
+        }
+
+        Ok(if alternatives.len() == 1 {
+            alternatives.pop().unwrap()
+        } else {
+            Ast::Alternation(alternatives)
+        })
+    }
+
+    fn concat(&mut self) -> Result<Ast, String> {
+        let mut items = Vec::new();
+        while !matches!(self.peek(), None | Some('|') | Some(')')) {
+            items.push(self.repeat()?);
+        }
+
+        Ok(match items.len() {
+            0 => Ast::Empty,
+            1 => items.pop().unwrap(),
+            _ => Ast::Concat(items),
+        })
+    }
+
+    fn repeat(&mut self) -> Result<Ast, String> {
+        let mut ast = self.atom()?;
+        loop {
+            let (min, max) = match self.peek() {
+                Some('*') => { self.pos += 1; (0, None) }
+                Some('+') => { self.pos += 1; (1, None) }
+                Some('?') => { self.pos += 1; (0, Some(1)) }
+                Some('{') => match self.counted()? {
+                    Some(bounds) => bounds,
+                    None => break,
+                },
+                _ => break,
+            };
+            if matches!(ast, Ast::LineStart | Ast::LineEnd | Ast::WordBoundary) {
+                return Err("nothing to repeat".to_owned());
+            }
+            let greedy = !self.eat('?');
+
+            ast = Ast::Repeat { ast: Box::new(ast), min, max, greedy };
+        }
+
+        Ok(ast)
+    }
+
+    /// Parse `{n}`, `{n,}` or `{n,m}`, `None` when the brace is a literal
+    fn counted(&mut self) -> Result<Option<(usize, Option<usize>)>, String> {
+        let rest: String = self.chars[self.pos..].iter().collect();
+        let end = match rest.find('}') {
+            Some(end) => end,
+            None => return Ok(None),
+        };
+        let inside = &rest[1..end];
+        let bounds = match inside.split_once(',') {
+            None => inside.parse().ok().map(|n| (n, Some(n))),
+            Some((min, "")) => min.parse().ok().map(|min| (min, None)),
+            Some((min, max)) => min.parse().ok()
+                .zip(max.parse().ok())
+                .map(|(min, max)| (min, Some(max))),
+        };
+        let (min, max) = match bounds {
+            Some(bounds) => bounds,
+            None => return Ok(None),
+        };
+
+        if max.map(|max| max < min).unwrap_or(false) {
+            return Err(format!("invalid repetition {{{}}}", inside));
+        }
+        if max.unwrap_or(min) > MAX_REPETITIONS {
+            return Err(format!("repetition over {}", MAX_REPETITIONS));
+        }
+        self.pos += inside.chars().count() + 2;
+
+        Ok(Some((min, max)))
+    }
+
+    fn atom(&mut self) -> Result<Ast, String> {
+        let c = match self.next() {
+            Some(c) => c,
+            None => return Ok(Ast::Empty),
+        };
+
+        Ok(match c {
+            '.' => Ast::Any,
+            '^' => Ast::LineStart,
+            '$' => Ast::LineEnd,
+            '(' => {
+                let capture = if self.eat('?') {
+                    if !self.eat(':') {
+                        return Err("unknown group flag".to_owned());
+                    }
+                    None
+                } else {
+                    self.groups += 1;
+                    Some(self.groups)
+                };
+                let ast = self.alternation()?;
+                if !self.eat(')') {
+                    return Err("missing `)`".to_owned());
+                }
+
+                Ast::Group(Box::new(ast), capture)
+            }
+            ')' => return Err("unmatched `)`".to_owned()),
+            '[' => Ast::Class(self.class()?),
+            '*' | '+' | '?' => return Err("nothing to repeat".to_owned()),
+            '\\' => {
+                let escape = self.next().ok_or("trailing `\\`")?;
+                match escape {
+                    'b' => Ast::WordBoundary,
+                    't' => Ast::Literal('\t'),
+                    escape => match Class::from_escape(escape) {
+                        Some(class) => Ast::Class(class),
+                        None => Ast::Literal(escape),
+                    },
+                }
+            }
+            c => Ast::Literal(c),
+        })
+    }
+
+    /// Parse a class after its `[`
+    fn class(&mut self) -> Result<Class, String> {
+        let negated = self.eat('^');
+        let mut ranges = Vec::new();
+
+        // A `]` right at the start is a literal
+        let mut first = true;
+        loop {
+            let c = self.next().ok_or("missing `]`")?;
+            match c {
+                ']' if !first => break,
+                '\\' => {
+                    let escape = self.next().ok_or("missing `]`")?;
+                    match Class::from_escape(escape) {
+                        Some(class) if !class.negated =>
+                            ranges.extend(class.ranges),
+                        Some(_) => return Err(
+                            format!("\\{} is not allowed inside []", escape)),
+                        None => ranges.push((escape, escape)),
+                    }
+                }
+                start => {
+                    let is_range = self.peek() == Some('-')
+                        && self.chars.get(self.pos + 1) != Some(&']')
+                        && self.chars.get(self.pos + 1).is_some();
+                    if is_range {
+                        self.pos += 1;
+                        let end = self.next().unwrap();
+                        if end < start {
+                            return Err(
+                                format!("invalid range {}-{}", start, end));
+                        }
+                        ranges.push((start, end));
+                    } else {
+                        ranges.push((start, start));
+                    }
+                }
+            }
+            first = false;
+        }
+
+        Ok(Class { ranges, negated })
+    }
+}
+
+/// An instruction of the program
+#[derive(Debug, Clone)]
+enum Inst {
+    Char(char),
+    Any,
+    Class(Class),
+    LineStart,
+    LineEnd,
+    WordBoundary,
+
+    /// Try the first branch, and the second when it fails
+    Split(usize, usize),
+    Jump(usize),
+
+    /// Remember the position on a capture slot
+    Save(usize),
+
+    Match,
+}
+
+/// How many instructions the tree compiles to, saturated
+fn program_len(ast: &Ast) -> usize {
+    match ast {
+        Ast::Empty => 0,
+        Ast::Literal(_) | Ast::Any | Ast::Class(_) | Ast::LineStart
+            | Ast::LineEnd | Ast::WordBoundary => 1,
+        Ast::Group(ast, None) => program_len(ast),
+        Ast::Group(ast, Some(_)) => program_len(ast).saturating_add(2),
+        Ast::Concat(items) => items
+            .iter()
+            .fold(0usize, |len, item| len.saturating_add(program_len(item))),
+        Ast::Alternation(alternatives) => alternatives
+            .iter()
+            .fold(0usize, |len, alternative|
+                len.saturating_add(program_len(alternative)))
+            .saturating_add(2 * (alternatives.len() - 1)),
+        Ast::Repeat { ast, min, max, .. } => {
+            let len = program_len(ast);
+            let optional = match max {
+                None => len.saturating_add(2),
+                Some(max) => (max - min).saturating_mul(len.saturating_add(1)),
+            };
+            min.saturating_mul(len).saturating_add(optional)
+        }
+    }
+}
+
+/// Compile the tree to instructions appended to the program
+fn compile(ast: &Ast, program: &mut Vec<Inst>) {
+    match ast {
+        Ast::Empty => {}
+        Ast::Literal(c) => program.push(Inst::Char(*c)),
+        Ast::Any => program.push(Inst::Any),
+        Ast::Class(class) => program.push(Inst::Class(class.clone())),
+        Ast::LineStart => program.push(Inst::LineStart),
+        Ast::LineEnd => program.push(Inst::LineEnd),
+        Ast::WordBoundary => program.push(Inst::WordBoundary),
+        Ast::Group(ast, None) => compile(ast, program),
+        Ast::Group(ast, Some(group)) => {
+            program.push(Inst::Save(group * 2));
+            compile(ast, program);
+            program.push(Inst::Save(group * 2 + 1));
+        }
+        Ast::Concat(items) => {
+            for item in items {
+                compile(item, program);
+            }
+        }
+        Ast::Alternation(alternatives) => {
+            // Every alternative but the last one jumps to the end
+            let mut jumps = Vec::new();
+            for (idx, alternative) in alternatives.iter().enumerate() {
+                if idx + 1 == alternatives.len() {
+                    compile(alternative, program);
+                    break;
+                }
+
+                let split = program.len();
+                program.push(Inst::Split(split + 1, 0));
+                compile(alternative, program);
+                jumps.push(program.len());
+                program.push(Inst::Jump(0));
+                let next = program.len();
+                program[split] = Inst::Split(split + 1, next);
+            }
+
+            let end = program.len();
+            for jump in jumps {
+                program[jump] = Inst::Jump(end);
+            }
+        }
+        Ast::Repeat { ast, min, max, greedy } => {
+            for _ in 0..*min {
+                compile(ast, program);
+            }
+
+            let split = |body: usize, exit: usize| if *greedy {
+                Inst::Split(body, exit)
+            } else {
+                Inst::Split(exit, body)
+            };
+            match max {
+                // Loop back to the split after every repetition
+                None => {
+                    let start = program.len();
+                    program.push(Inst::Match);
+                    compile(ast, program);
+                    program.push(Inst::Jump(start));
+                    program[start] = split(start + 1, program.len());
+                }
+
+                // Every optional repetition can exit to the end
+                Some(max) => {
+                    let mut splits = Vec::new();
+                    for _ in *min..*max {
+                        splits.push(program.len());
+                        program.push(Inst::Match);
+                        compile(ast, program);
+                    }
+
+                    let end = program.len();
+                    for start in splits {
+                        program[start] = split(start + 1, end);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The positions of the groups of a match, the group 0 is the whole match
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Captures {
+    slots: Vec<Option<usize>>,
+}
+
+impl Captures {
+    /// Byte range of the group on the line, if it took part on the match
+    pub fn get(&self, group: usize) -> Option<(usize, usize)> {
+        let start = (*self.slots.get(group * 2)?)?;
+        let end = (*self.slots.get(group * 2 + 1)?)?;
+
+        Some((start, end))
+    }
+
+    /// The byte range of the whole match
+    pub fn range(&self) -> (usize, usize) {
+        self.get(0).unwrap()
+    }
+}
+
+/// Work pending on the backtracking
+enum Job {
+    Explore(usize, usize),
+    Restore(usize, Option<usize>),
+}
+
+/// A compiled pattern
+#[derive(Debug)]
+pub struct Regex {
+    program: Vec<Inst>,
+
+    /// Capture slots, two per group counting the whole match
+    slots: usize,
+}
+
+impl Regex {
+    /// Compile the pattern, the error explains what is wrong with it
+    pub fn new(pattern: &str) -> Result<Self, String> {
+        let mut parser = Parser {
+            chars: pattern.chars().collect(),
+            pos: 0,
+            groups: 0,
+        };
+        let ast = parser.alternation()?;
+        if parser.pos < parser.chars.len() {
+            return Err("unmatched `)`".to_owned());
+        }
+
+        if program_len(&ast) > MAX_PROGRAM {
+            return Err("the pattern is too big".to_owned());
+        }
+
+        let mut program = vec![Inst::Save(0)];
+        compile(&ast, &mut program);
+        program.push(Inst::Save(1));
+        program.push(Inst::Match);
+
+        Ok(Self {
+            program,
+            slots: (parser.groups + 1) * 2,
+        })
+    }
+
+    /// Find the leftmost match that starts at or after the byte `start` of
+    /// the line
+    pub fn find_at(&self, line: &str, start: usize) -> Option<Captures> {
+        let positions = line.len() + 1;
+        let mut visited = vec![0u64; (self.program.len() * positions).div_ceil(64)];
+
+        let mut start = start;
+        while start <= line.len() {
+            if line.is_char_boundary(start) {
+                if let Some(captures) = self.match_at(line, start, &mut visited) {
+                    return Some(captures);
+                }
+            }
+            start += 1;
+        }
+
+        None
+    }
+
+    /// Run the program from the position. A state reached before with the
+    /// same instruction and position already failed, what follows doesn't
+    /// depend on the captures, so the visited states are shared between
+    /// all the starts
+    fn match_at(
+        &self,
+        line: &str,
+        start: usize,
+        visited: &mut [u64],
+    ) -> Option<Captures> {
+        let positions = line.len() + 1;
+        let mut slots = vec![None; self.slots];
+        let mut jobs = vec![Job::Explore(0, start)];
+
+        while let Some(job) = jobs.pop() {
+            let (mut pc, mut pos) = match job {
+                Job::Explore(pc, pos) => (pc, pos),
+                Job::Restore(slot, old) => {
+                    slots[slot] = old;
+                    continue;
+                }
+            };
+
+            loop {
+                let state = pc * positions + pos;
+                if visited[state / 64] & (1 << (state % 64)) != 0 {
+                    break;
+                }
+                visited[state / 64] |= 1 << (state % 64);
+
+                let next = line[pos..].chars().next();
+                let advance = |matches: bool| match next {
+                    Some(c) if matches => Some(pos + c.len_utf8()),
+                    _ => None,
+                };
+                match &self.program[pc] {
+                    Inst::Char(expected) =>
+                        match advance(next == Some(*expected)) {
+                            Some(new_pos) => { pos = new_pos; pc += 1; }
+                            None => break,
+                        },
+                    Inst::Any => match advance(true) {
+                        Some(new_pos) => { pos = new_pos; pc += 1; }
+                        None => break,
+                    },
+                    Inst::Class(class) =>
+                        match advance(next.map(|c| class.matches(c)) == Some(true)) {
+                            Some(new_pos) => { pos = new_pos; pc += 1; }
+                            None => break,
+                        },
+                    Inst::LineStart if pos == 0 => pc += 1,
+                    Inst::LineEnd if pos == line.len() => pc += 1,
+                    Inst::WordBoundary if is_word_boundary(line, pos) => pc += 1,
+                    Inst::LineStart | Inst::LineEnd | Inst::WordBoundary => break,
+                    Inst::Split(first, second) => {
+                        jobs.push(Job::Explore(*second, pos));
+                        pc = *first;
+                    }
+                    Inst::Jump(target) => pc = *target,
+                    Inst::Save(slot) => {
+                        jobs.push(Job::Restore(*slot, slots[*slot]));
+                        slots[*slot] = Some(pos);
+                        pc += 1;
+                    }
+                    Inst::Match => return Some(Captures { slots }),
+                }
+            }
+        }
+
+        None
+    }
+}
+
+/// If the position is between a word char and something else
+fn is_word_boundary(line: &str, pos: usize) -> bool {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    let before = line[..pos].chars().next_back().map(is_word).unwrap_or(false);
+    let after = line[pos..].chars().next().map(is_word).unwrap_or(false);
+
+    before != after
+}
+
+/// Expand the replacement for a match, `$1` or `${1}` are the text of a
+/// group and `$$` is a `$`
+pub fn expand(replacement: &str, line: &str, captures: &Captures) -> String {
+    let mut expanded = String::new();
+    let mut chars = replacement.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '$' {
+            expanded.push(c);
+            continue;
+        }
+
+        let braced = chars.peek() == Some(&'{');
+        if braced {
+            chars.next();
+        }
+        let mut digits = String::new();
+        while let Some(digit) = chars.peek().filter(|c| c.is_ascii_digit()) {
+            digits.push(*digit);
+            chars.next();
+        }
+        if braced {
+            chars.next_if_eq(&'}');
+        }
+
+        match digits.parse::<usize>() {
+            Ok(group) => {
+                if let Some((start, end)) = captures.get(group) {
+                    expanded.push_str(&line[start..end]);
+                }
+            }
+            Err(_) if chars.next_if_eq(&'$').is_some() => expanded.push('$'),
+            Err(_) => expanded.push('$'),
+        }
+    }
+
+    expanded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The text of the leftmost match on the line
+    fn find<'a>(pattern: &str, line: &'a str) -> Option<&'a str> {
+        let regex = Regex::new(pattern).unwrap();
+        let (start, end) = regex.find_at(line, 0)?.range();
+
+        Some(&line[start..end])
+    }
+
+    #[test]
+    fn anchors() {
+        assert_eq!(find("^ab", "abab"), Some("ab"));
+        assert_eq!(find("^b", "ab"), None);
+        assert_eq!(find("b$", "abab"), Some("b"));
+        assert_eq!(find("a$", "abab"), None);
+        assert_eq!(find(r"\bcat\b", "concat cat"), Some("cat"));
+        let regex = Regex::new(r"\bcat\b").unwrap();
+        assert_eq!(regex.find_at("concat cat", 0).unwrap().range(), (7, 10));
+    }
+
+    #[test]
+    fn classes() {
+        assert_eq!(find("[a-c]+", "xxbcaz"), Some("bca"));
+        assert_eq!(find("[^0-9]+", "12ab3"), Some("ab"));
+        assert_eq!(find(r"\d+", "ab123c"), Some("123"));
+        assert_eq!(find(r"\W+", "ab, c"), Some(", "));
+        assert_eq!(find("[]a]+", "x]a]"), Some("]a]"));
+        assert_eq!(find("[a-]+", "x-a-"), Some("-a-"));
+        assert!(Regex::new("[z-a]").is_err());
+        assert!(Regex::new("[ab").is_err());
+    }
+
+    #[test]
+    fn alternation() {
+        assert_eq!(find("cat|dog", "hotdog"), Some("dog"));
+        assert_eq!(find("a|ab", "ab"), Some("a"));
+        assert_eq!(find("x(a|b)+y", "xababy"), Some("xababy"));
+        assert_eq!(find("(?:ab|cd)$", "abcd"), Some("cd"));
+    }
+
+    #[test]
+    fn counted_repeats() {
+        assert_eq!(find("a{2}", "aaaa"), Some("aa"));
+        assert_eq!(find("a{2,}", "aaaa"), Some("aaaa"));
+        assert_eq!(find("a{1,3}", "aaaa"), Some("aaa"));
+        assert_eq!(find("a{1,3}?", "aaaa"), Some("a"));
+        assert_eq!(find("ba{2}c", "bac"), None);
+        assert!(Regex::new("a{3,1}").is_err());
+        assert!(Regex::new("a{1001}").is_err());
+    }
+
+    #[test]
+    fn nested_repeats_are_capped() {
+        assert!(Regex::new("(a{1000}){1000}").is_err());
+        assert!(Regex::new("((a{100}){100}){100}").is_err());
+        assert!(Regex::new("(a{100}){50}").is_ok());
+    }
+
+    #[test]
+    fn expand_groups() {
+        let line = "key = value";
+        let regex = Regex::new(r"(\w+) = (\w+)").unwrap();
+        let captures = regex.find_at(line, 0).unwrap();
+        assert_eq!(expand("$2 = $1", line, &captures), "value = key");
+        assert_eq!(expand("${1}s", line, &captures), "keys");
+        assert_eq!(expand("$$1 $", line, &captures), "$1 $");
+        assert_eq!(expand("[$3]", line, &captures), "[]");
+    }
+}
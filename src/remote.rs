@@ -0,0 +1,169 @@
+//! Single instance per project, `pepe --remote file` hands the file to the
+//! editor already running on the project instead of starting another one,
+//! like `gvim --remote` or `code -r` do. The running editor listens on a
+//! unix socket named after the project directory and opens the paths it
+//! receives, one per line
+//!
+//! Without unix sockets every editor is on its own and `--remote` opens the
+//! file locally
+
+use std::path::{Path, PathBuf};
+
+use crate::Result;
+
+/// Where the editor of the project at `root` listens, on the runtime
+/// directory of the user when there is one, otherwise on a directory of the
+/// temporary one only the user can enter
+pub fn socket_path(root: &Path) -> Result<PathBuf> {
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+
+    let mut hasher = DefaultHasher::new();
+    root.hash(&mut hasher);
+
+    let dir = match std::env::var_os("XDG_RUNTIME_DIR")
+        .filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => {
+            let user = std::env::var("USER")
+                .or_else(|_| std::env::var("LOGNAME"))
+                .unwrap_or_default();
+            let dir = std::env::temp_dir().join(format!("pepe-{}", user));
+            imp::private_dir(&dir)?;
+            dir
+        }
+    };
+    Ok(dir.join(format!("pepe-{:016x}.sock", hasher.finish())))
+}
+
+/// The directory of the project, the one the editor starts on
+pub fn project_root() -> Result<PathBuf> {
+    Ok(std::env::current_dir()?.canonicalize()?)
+}
+
+#[cfg(unix)]
+mod imp {
+    use std::fs::{DirBuilder, Permissions};
+    use std::io::{BufRead, BufReader, ErrorKind, Write};
+    use std::os::unix::fs::{DirBuilderExt, MetadataExt, PermissionsExt};
+    use std::os::unix::net::{UnixListener, UnixStream};
+    use std::path::{Path, PathBuf};
+    use std::sync::mpsc::Sender;
+    use std::thread;
+
+    use crate::Result;
+    use crate::events::Wake;
+    use crate::fileops;
+
+    /// Create the directory only the user can enter, an existing one must
+    /// be a directory of the user that no one else can enter
+    pub fn private_dir(dir: &Path) -> Result<()> {
+        match DirBuilder::new().mode(0o700).create(dir) {
+            Err(err) if err.kind() != ErrorKind::AlreadyExists =>
+                return Err(err.into()),
+            _ => {}
+        }
+
+        // A file created on it is the user's, so is the directory
+        let meta = std::fs::symlink_metadata(dir)?;
+        let probe = (0..8).map(|_| dir.join(
+            format!(".probe-{}", fileops::random_suffix())));
+        let (probe, file) = fileops::create_private(probe)?;
+        let user = file.metadata()?.uid();
+        std::fs::remove_file(probe)?;
+        if !meta.is_dir() || meta.uid() != user || meta.mode() & 0o077 != 0 {
+            return Err(format!("{} is not private", dir.display()).into());
+        }
+
+        Ok(())
+    }
+
+    /// The socket of the running editor, removed when dropped
+    pub struct Server {
+        path: PathBuf,
+    }
+
+    impl Server {
+        /// Listen on the socket unless another editor already does, a
+        /// socket left by an editor that crashed is replaced. The paths
+        /// received are sent to the editor
+        pub fn bind(path: &Path, wake: Sender<Wake>) -> Option<Self> {
+            match UnixStream::connect(path) {
+                Ok(_) => return None,
+                Err(err) if err.kind() == ErrorKind::ConnectionRefused => {
+                    let _ = std::fs::remove_file(path);
+                }
+                Err(_) => {}
+            }
+
+            let listener = UnixListener::bind(path).ok()?;
+            let private = Permissions::from_mode(0o600);
+            if std::fs::set_permissions(path, private).is_err() {
+                let _ = std::fs::remove_file(path);
+                return None;
+            }
+            thread::spawn(move || {
+                for stream in listener.incoming().flatten() {
+                    // The client writes everything at once and closes
+                    let paths: Vec<PathBuf> = BufReader::new(stream)
+                        .lines()
+                        .map_while(|line| line.ok())
+                        .filter(|line| !line.is_empty())
+                        .map(PathBuf::from)
+                        .collect();
+                    if wake.send(Wake::Remote(paths)).is_err() {
+                        break;
+                    }
+                }
+            });
+
+            Some(Self { path: path.to_owned() })
+        }
+    }
+
+    impl Drop for Server {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+
+    /// Send the paths to the editor listening on the socket, fails when
+    /// there is none
+    pub fn send(socket: &Path, paths: &[PathBuf]) -> Result<()> {
+        let mut stream = UnixStream::connect(socket)?;
+        for path in paths {
+            writeln!(stream, "{}", path.display())?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(not(unix))]
+mod imp {
+    use std::path::{Path, PathBuf};
+    use std::sync::mpsc::Sender;
+
+    use crate::Result;
+    use crate::events::Wake;
+
+    /// The directory is never used without unix sockets
+    pub fn private_dir(_: &Path) -> Result<()> {
+        Ok(())
+    }
+
+    /// No editor listens without unix sockets
+    pub struct Server;
+
+    impl Server {
+        pub fn bind(_: &Path, _: Sender<Wake>) -> Option<Self> {
+            None
+        }
+    }
+
+    pub fn send(_: &Path, _: &[PathBuf]) -> Result<()> {
+        Err("the remote open needs unix sockets".into())
+    }
+}
+
+pub use imp::{Server, send};
@@ -1,20 +1,49 @@
 //! All primitives related to rendering to the screen, the phylosophy is to
 //! only redraw what needs to be readrawn and NOT in loop, event model
 
-use std::io::{Write, Stdout};
-
-use crossterm::{queue, execute, terminal};
-use crossterm::style::{Print, PrintStyledContent, Color, Stylize};
+use crossterm::terminal;
+use crossterm::style::{
+    Print, PrintStyledContent, StyledContent, Color, Stylize
+};
 
 use crate::{Result, EditorState};
-use crate::input::{Cursor, CursorState};
-use crate::text::Document;
+use crate::buffers::BufferList;
+use crate::changes::LineChange;
+use crate::commit;
+use crate::git::RepoStatus;
+use crate::grid::{draw, Grid, PaintBackground};
+use crate::hover::{Popup, PopupArea};
+use crate::lsp::{self, Severity};
+use crate::input::{Cursor, CursorState, ModalState};
+use crate::panel::Panel;
+use crate::prose;
+use crate::replace::Replace;
+use crate::review::Review;
+use crate::scrollbar;
+use crate::selection::Selection;
+use crate::status::{self, Segment, StatusLine};
+use crate::text::{self, Document};
+use crate::theme::Theme;
+use crate::tree::FileTree;
+use crate::unicode;
+use crate::viewport::Viewport;
+use crate::window::LineNumbers;
+use crate::wrap::{self, Layout};
 
 /// Settings used to do the rendering on a optimized way
 pub struct RenderState {
     /// Row that needs to be repainted
     pub modif_row: Option<usize>,
 
+    /// Rows `start..end` that need to be repainted, like the two lines
+    /// swapped by moving one of them
+    pub modif_rows: Option<(usize, usize)>,
+
+    /// First row of the ones that need to be repainted down to the end of
+    /// the document area, the lines below an edit that added or removed
+    /// lines moved
+    pub modif_below: Option<usize>,
+
     /// If all the terminal needs to be repainted
     pub modif_all: bool,
 
@@ -22,180 +51,1590 @@ pub struct RenderState {
     pub last_cursor: Option<Cursor>,
 
     /// If the status bar needs to be repainted
-    pub modif_status: bool
+    pub modif_status: bool,
+
+    /// If the minibuffer needs to be repainted
+    pub modif_message: bool,
+
+    /// The first line of the panel whose rows need to be repainted, with
+    /// the title, when the rest of it didn't change
+    pub modif_panel: Option<usize>,
+
+    /// If the bell should be rung after the frame
+    pub bell: bool,
+}
+
+impl RenderState {
+    /// Everything is painted on the first frame
+    pub fn new() -> Self {
+        Self {
+            modif_row: None,
+            modif_rows: None,
+            modif_below: None,
+            modif_all: true,
+            last_cursor: None,
+            modif_status: true,
+            modif_message: true,
+            modif_panel: None,
+            bell: false,
+        }
+    }
+
+    /// If nothing needs to be repainted, the frame can be skipped
+    pub fn is_clean(&self) -> bool {
+        !self.modif_all
+            && self.modif_row.is_none()
+            && self.modif_rows.is_none()
+            && self.modif_below.is_none()
+            && self.last_cursor.is_none()
+            && !self.modif_status
+            && !self.modif_message
+            && self.modif_panel.is_none()
+    }
+
+    /// The frame was drawn, everything is up to date
+    pub fn clear(&mut self) {
+        self.last_cursor = None;
+        self.modif_status = false;
+        self.modif_message = false;
+        self.modif_panel = None;
+        self.modif_all = false;
+        self.modif_row = None;
+        self.modif_rows = None;
+        self.modif_below = None;
+    }
 }
 
 /// Update (if needed) the elements that need to be updated on the screen
 pub fn refresh_screen(
-    stdout: &mut Stdout,
+    grid: &mut Grid,
     document: &Option<Document>,
     cursor: &Cursor,
-    EditorState { rows, columns, .. }: &EditorState,
-    CursorState { scroll_y, .. }: &CursorState,
+    editor_state: &EditorState,
+    cursor_state: &CursorState,
     RenderState { 
         modif_row, 
+        modif_rows,
+        modif_below,
         modif_all, 
         last_cursor, 
-        modif_status 
+        modif_status,
+        modif_message,
+        modif_panel,
+        ..
     }: &RenderState
 ) -> Result<()> {
+    let EditorState { 
+        rows, 
+        columns, 
+        panel, 
+        review, 
+        max_line_length,
+        top, 
+        passphrase,
+        command_line, 
+        search,
+        replace,
+        prompt,
+        buffers,
+        .. 
+    } = editor_state;
+    let CursorState { viewport, anchor, cursors, .. } = cursor_state;
+    let top = *top as u16;
+    let scroll_y = &viewport.scroll_y;
+
+    // The rows of the wrapped lines come from the layout, the edit of a
+    // line can move all the rows below it. The other cursors are drawn over
+    // all the rows
+    let wrap = editor_state.window.wrap && panel.is_none();
+    let layout = match document {
+        Some(doc) if wrap => 
+            Some(Layout::new(
+                doc, *scroll_y, *rows, *columns, editor_state.tab_width)),
+        _ => None,
+    };
+    let modif_all = &(*modif_all 
+        || ((wrap || !cursors.is_empty()) && (modif_row.is_some() 
+            || modif_rows.is_some() 
+            || modif_below.is_some())));
+    let (cursor_column, cursor_row) = 
+        cursor_cell(document, cursor, viewport, &layout, editor_state);
+
     // Check if the status bar needs to be repainted
     if *modif_status {
-        queue!(stdout,
+        let status = render_status_bar(
+            document, 
+            cursor, 
+            review,
+            buffers,
+            *max_line_length,
+            *columns + editor_state.gutter_width(), 
+            cursor_state,
+            &editor_state.modal,
+            document.as_ref()
+                .and_then(|doc| editor_state.git.status(&doc.path)),
+            editor_state.spinner.status(),
+            &editor_state.status_line);
+
+        draw!(grid,
             crossterm::cursor::SavePosition,
-            crossterm::cursor::MoveTo(0, *rows as u16),
+            crossterm::cursor::MoveTo(0, top + *rows as u16),
             PrintStyledContent(
-                render_status_bar(
-                    document, 
-                    cursor, 
-                    *columns, 
-                    *scroll_y)
-                .with(Color::Black)
-                .on(Color::White)),
+                status
+                    .with(editor_state.theme.bar_text)
+                    .on(accent_color(editor_state))),
+            crossterm::cursor::RestorePosition)?;
+    }
+
+    // The minibuffer goes under the status bar, the errors stand out
+    if *modif_message || *modif_all {
+        let (text, error) = minibuffer_line(editor_state);
+        let color = if error { editor_state.theme.error } else { Color::Reset };
+        draw!(grid,
+            crossterm::cursor::SavePosition,
+            crossterm::cursor::MoveTo(0, top + *rows as u16 + 1),
+            terminal::Clear(terminal::ClearType::CurrentLine),
+            PrintStyledContent(text.with(color)),
             crossterm::cursor::RestorePosition)?;
     }
 
     // Re-draw all the rows when modif_all
     if *modif_all {
+        // The tabs bar is only shown when there is more than one tab
+        if editor_state.tabs.is_visible() {
+            render_tabs_bar(grid, document, editor_state)?;
+        }
+
+        // An open panel hides the document
+        if let Some(panel) = panel {
+            render_panel(grid, panel, top, *rows,
+                *columns + editor_state.gutter_width(), 0,
+                editor_state.theme)?;
+
         // Print the document lines
-        if let Some(doc) = document {
+        } else if let Some(doc) = document {
             // Hide the cursor
-            queue!(stdout, 
-                crossterm::cursor::MoveTo(
-                    cursor.column as u16 + 4, cursor.row as u16),
+            draw!(grid, 
+                crossterm::cursor::MoveTo(cursor_column, cursor_row),
                 crossterm::cursor::SavePosition,
                 crossterm::cursor::Hide)?;
 
-            for row in 0..*rows as u16 {
-                // Clear this line
-                queue!(stdout, 
-                    crossterm::cursor::MoveToRow(row),
-                    terminal::Clear(terminal::ClearType::CurrentLine))?;
-
-                let idx = row as usize + scroll_y;
-                if let Some(line) = &doc.inner_lines.get(idx) {
-                    // Print the document
-                    queue!(stdout,
-                        crossterm::cursor::MoveTo(0, row),
-                        PrintStyledContent(
-                            format!("{:3} ", idx)
-                                .with(Color::Yellow)),
-                        Print(line))?;
-                } else {
-                    queue!(stdout,
-                        crossterm::cursor::MoveTo(0, row),
-                        Print("~ "
-                            .with(Color::Yellow)))?;
+            // The lines shown are read in one pass instead of looking up
+            // each one on the buffer
+            if let Some(layout) = &layout {
+                render_wrapped(grid, doc, layout, *scroll_y,
+                    *scroll_y + cursor.row, editor_state)?;
+            } else {
+                let mut lines = doc.buffer.lines_from(*scroll_y);
+                for row in 0..*rows as u16 {
+                    queue_row_line(grid, doc, lines.next(), row, cursor,
+                        viewport, editor_state)?;
                 }
+                queue_other_cursors(grid, doc, cursor_state, editor_state)?;
             }
 
             // Show again the cursor
-            queue!(stdout, 
+            draw!(grid, 
                 crossterm::cursor::RestorePosition,
                 crossterm::cursor::Show)?;
 
-        // No file loaded so this is the first print of logo screen
+        // No file loaded so this is the first print of logo screen, on
+        // the columns right of the sidebar
         } else {
+            let sidebar = editor_state.sidebar_width() as u16;
             for row in 0..(*rows - 1) as u16 {
                 // Clear this line
-                queue!(stdout, 
-                    crossterm::cursor::MoveToRow(row),
+                draw!(grid, 
+                    crossterm::cursor::MoveToRow(top + row),
                     terminal::Clear(terminal::ClearType::CurrentLine))?;
 
                 // Print the intro (no document opened)
                 if row == *rows as u16 / 3 {
                     let msg = "Pepe editor -- version 0.0.1"
-                        .with(Color::Blue);
-                    let msg_start = *columns / 2 - msg.content().len() / 2;
-
-                    queue!(stdout,
-                        crossterm::cursor::MoveTo(0, row),
-                        Print("~ "
-                            .with(Color::Yellow)),
-                        crossterm::cursor::MoveTo(msg_start as u16, row),
+                        .with(editor_state.theme.splash);
+                    let msg_start = 
+                        (*columns / 2).saturating_sub(msg.content().len() / 2);
+
+                    draw!(grid,
+                        crossterm::cursor::MoveTo(sidebar, top + row),
+                        PrintStyledContent("~ "
+                            .with(editor_state.theme.gutter)),
+                        crossterm::cursor::MoveTo(
+                            sidebar + msg_start as u16, top + row),
                         PrintStyledContent(msg))?;
+                } else if row == *rows as u16 / 3 + 2 {
+                    let hint = "Ctrl+P finds a file of the project"
+                        .with(editor_state.theme.hint);
+                    let hint_start = 
+                        (*columns / 2).saturating_sub(hint.content().len() / 2);
+
+                    draw!(grid,
+                        crossterm::cursor::MoveTo(sidebar, top + row),
+                        PrintStyledContent("~ "
+                            .with(editor_state.theme.gutter)),
+                        crossterm::cursor::MoveTo(
+                            sidebar + hint_start as u16, top + row),
+                        PrintStyledContent(hint))?;
                 } else {
-                    queue!(stdout,
-                        crossterm::cursor::MoveTo(0, row),
-                        Print("~ "
-                            .with(Color::Yellow)))?;
+                    draw!(grid,
+                        crossterm::cursor::MoveTo(sidebar, top + row),
+                        PrintStyledContent("~ "
+                            .with(editor_state.theme.gutter)))?;
                 }
             }
         }
+        // The sidebar is at the left of the document, the panel covers it
+        if let (Some(tree), None) = (&editor_state.tree, panel) {
+            if tree.visible {
+                render_tree(grid, tree, editor_state)?;
+            }
+        }
+
+        // The popups go over whatever was drawn
+        if let Some(popup) = &editor_state.hover.popup {
+            render_popup(grid, popup, None, editor_state)?;
+        }
+        if let Some(completion) = &editor_state.completion {
+            let typed = unicode::width(
+                &completion.prefix, editor_state.tab_width);
+            let popup = completion.popup((cursor_column, cursor_row), typed);
+            render_popup(grid, &popup, Some(completion.selected), 
+                editor_state)?;
+        }
+    } else if let (Some(panel), Some(from)) = (panel, modif_panel) {
+        render_panel(grid, panel, top, *rows,
+            *columns + editor_state.gutter_width(), *from,
+            editor_state.theme)?;
+    } else if let (Some(from), Some(doc)) = (modif_below, document) {
+        draw!(grid, crossterm::cursor::SavePosition)?;
+        for row in *from..*rows {
+            queue_row(grid, doc, row as u16, cursor, viewport, editor_state)?;
+        }
+        draw!(grid, crossterm::cursor::RestorePosition)?;
+    } else if let (Some((start, end)), Some(doc)) = (modif_rows, document) {
+        draw!(grid, crossterm::cursor::SavePosition)?;
+        for row in *start..usize::min(*end, *rows) {
+            queue_row(grid, doc, row as u16, cursor, viewport, editor_state)?;
+        }
+        draw!(grid, crossterm::cursor::RestorePosition)?;
     } else if let Some(row) = modif_row {
-        let idx = row + scroll_y;
-        let line = &document.as_ref().unwrap().inner_lines[idx];
+        let doc = document.as_ref().unwrap();
+        draw!(grid, crossterm::cursor::SavePosition)?;
+        queue_row(grid, doc, *row as u16, cursor, viewport, editor_state)?;
+        draw!(grid, crossterm::cursor::RestorePosition)?;
+    }
 
-        queue!(stdout,
-            crossterm::cursor::SavePosition,
-            crossterm::cursor::MoveTo(0, *row as u16),
-            PrintStyledContent(
-                format!("{:3} ", idx)
-                    .with(Color::Yellow)),
-            Print(line),
-            crossterm::cursor::RestorePosition)?
+    // The crosshair follows the cursor, only the rows and cells it leaves
+    // and reaches are repainted
+    if let (true, false, false, Some(last), Some(doc)) = 
+            (editor_state.crosshair, wrap, *modif_all, last_cursor, document) {
+        if last != cursor {
+            repaint_crosshair(
+                grid, doc, last, cursor, viewport, editor_state)?;
+        }
     }
 
-    if last_cursor.is_some() && *modif_all == false {
-        let last_cursor = last_cursor.unwrap();
+    // The length of the line when writing prose moves with the cursor
+    if let (false, false, false, Some(last), Some(doc)) = 
+            (editor_state.crosshair, wrap, *modif_all, last_cursor, document) {
+        let shows_length = editor_state.prose.indicator 
+            && prose::is_prose(doc.filetype());
+        if shows_length && last.row != cursor.row {
+            queue_row(grid, doc, last.row as u16, cursor, viewport, 
+                editor_state)?;
+            queue_row(grid, doc, cursor.row as u16, cursor, viewport, 
+                editor_state)?;
+        }
+    }
 
-        queue!(stdout, 
+    if last_cursor.is_some() && !*modif_all {
+        draw!(grid, 
             crossterm::cursor::Hide,
-            crossterm::cursor::MoveTo(
-                cursor.column as u16 + 4, cursor.row as u16),
+            crossterm::cursor::MoveTo(cursor_column, cursor_row),
+            crossterm::cursor::Show)?;
+    }
+
+    // The selection goes over the text of the rows just drawn
+    let drawn = *modif_all 
+        || modif_row.is_some() 
+        || modif_rows.is_some() 
+        || modif_below.is_some() 
+        || last_cursor.is_some();
+    if let (Some(doc), Some(anchor), None, true) = 
+            (document, anchor, panel, drawn) {
+        let head = doc.position(scroll_y + cursor.row, cursor.column);
+        let selection = Selection::new(*anchor, head);
+        match &layout {
+            Some(layout) => queue_selection_wrapped(
+                grid, doc, selection, layout, editor_state)?,
+            None => queue_selection(
+                grid, doc, selection, viewport, editor_state)?,
+        }
+    }
+
+    // The cursor parked out of the view by the wheel isn't shown anywhere
+    if let (Some(_), None) = (cursor_state.parked, panel) {
+        draw!(grid, crossterm::cursor::Hide)?;
+    }
+
+    // The focused tree has the cursor on its selected entry
+    let tree_row = editor_state.tree.as_ref()
+        .filter(|tree| tree.visible && tree.focused && panel.is_none())
+        .and_then(|tree| tree.selected.checked_sub(tree.scroll))
+        .filter(|row| row < rows);
+    if let Some(row) = tree_row {
+        draw!(grid,
+            crossterm::cursor::MoveTo(0, top + row as u16),
             crossterm::cursor::Show)?;
     }
 
-    // Send all the draw commands at once
-    stdout.flush()?;
+    // While typing a command or a search the cursor is on the minibuffer,
+    // after the `:` or the `/`
+    let prompt_input = command_line.as_ref()
+        .map(|command_line| &command_line.input)
+        .or_else(|| search.as_ref().map(|search| &search.input));
+    let prompt_column = prompt_input
+        .map(|input| input.len() + 1)
+        .or_else(|| passphrase.as_ref().map(|prompt| prompt.input_column()))
+        .or_else(|| replace.as_ref().and_then(|replace| replace.input_column()))
+        .or_else(|| prompt.as_ref().map(|prompt| prompt.input_column()));
+    if let Some(column) = prompt_column {
+        draw!(grid,
+            crossterm::cursor::MoveTo(column as u16, top + *rows as u16 + 1),
+            crossterm::cursor::Show)?;
+    }
+
+    Ok(())
+}
+
+/// What the minibuffer shows and if it's an error, the prompt being typed
+/// or else the last message
+pub fn minibuffer_line(
+    EditorState {
+        passphrase,
+        command_line,
+        search,
+        replace,
+        prompt,
+        message,
+        ..
+    }: &EditorState
+) -> (String, bool) {
+    let prompts = (passphrase, command_line, search, replace, prompt);
+    match prompts {
+        (Some(passphrase), ..) => (passphrase.prompt(), false),
+        (None, Some(command_line), ..) =>
+            (format!(":{}", command_line.input), false),
+        (None, None, Some(search), ..) => (search.prompt(), false),
+        (None, None, None, Some(replace), _) => (replace.prompt(), false),
+        (None, None, None, None, Some(prompt)) => (prompt.text(), false),
+        (None, None, None, None, None) => match message.current() {
+            Some(message) => (message.text.clone(), message.error),
+            None => (String::new(), false),
+        },
+    }
+}
+
+/// The cell of the terminal where the cursor is shown, the column of the
+/// cursor moved by the horizontal scroll unless the lines are wrapped
+fn cursor_cell(
+    document: &Option<Document>,
+    cursor: &Cursor,
+    Viewport { scroll_y, scroll_x }: &Viewport,
+    layout: &Option<Layout>,
+    editor_state: &EditorState
+) -> (u16, u16) {
+    let EditorState { top, tab_width, .. } = editor_state;
+    let gutter = editor_state.gutter_width() as u16;
+    let (row, column) = match (document, layout) {
+        (Some(doc), Some(layout)) => layout
+            .cell_of(doc, scroll_y + cursor.row, cursor.column)
+            .unwrap_or((cursor.row, 0)),
+        (Some(doc), None) => (cursor.row, 
+            cursor_column(doc, cursor, *scroll_y, *tab_width)
+                .saturating_sub(*scroll_x)),
+        (None, _) => (cursor.row, 0),
+    };
+
+    (column as u16 + gutter, (*top + row) as u16)
+}
+
+/// The column of the line, not moved by the horizontal scroll, where the
+/// cursor is shown
+fn cursor_column(
+    doc: &Document,
+    cursor: &Cursor,
+    scroll_y: usize,
+    tab_width: usize
+) -> usize {
+    let line = doc.buffer.line(scroll_y + cursor.row).unwrap_or("");
+    unicode::column_of(line, cursor.column, tab_width)
+}
+
+/// The part of the cells `start..end` of the line that is inside the
+/// columns `from..to` shown on a row, what it shows and the column of the
+/// row where it starts. `None` when it's outside
+fn clip_cells(
+    line: &str,
+    (start, end): (usize, usize),
+    (from, to): (usize, usize),
+    tab_width: usize
+) -> Option<(String, usize)> {
+    let (start, end) = (usize::max(start, from), usize::min(end, to));
+    (start < end).then(|| 
+        (unicode::visible(line, (start, end), tab_width), start - from))
+}
+
+/// Style of a line of the document, lines deleted on review mode are ghosts
+fn style_line(line: &str, ghost: bool) -> StyledContent<&str> {
+    if ghost {
+        line.dim().crossed_out()
+    } else {
+        line.stylize()
+    }
+}
+
+/// Queue the gutter, the line and the scrollbar cell shown on the `row` of
+/// the document area
+fn queue_row(
+    grid: &mut Grid,
+    doc: &Document,
+    row: u16,
+    cursor: &Cursor,
+    viewport: &Viewport,
+    editor_state: &EditorState
+) -> Result<()> {
+    let line = doc.buffer.line(row as usize + viewport.scroll_y);
+    queue_row_line(grid, doc, line, row, cursor, viewport, editor_state)
+}
+
+/// Queue the `row` of the document area that shows `line`, `None` when the
+/// row is past the end of the document
+fn queue_row_line(
+    grid: &mut Grid,
+    doc: &Document,
+    line: Option<&str>,
+    row: u16,
+    cursor: &Cursor,
+    &Viewport { scroll_y, scroll_x }: &Viewport,
+    editor_state: &EditorState
+) -> Result<()> {
+    let idx = row as usize + scroll_y;
+
+    // The sidebar at the left of the row is kept
+    let sidebar = editor_state.sidebar_width() as u16;
+    draw!(grid,
+        crossterm::cursor::MoveTo(sidebar, editor_state.top as u16 + row),
+        terminal::Clear(terminal::ClearType::UntilNewLine))?;
+    if let Some(line) = line {
+        let tab_width = editor_state.tab_width;
+        let columns = (scroll_x, scroll_x + editor_state.columns);
+        let visible = unicode::visible(line, columns, tab_width);
+        let crosshair_column = 
+            cursor_column(doc, cursor, scroll_y, tab_width)
+                .checked_sub(scroll_x)
+                .filter(|column| *column < editor_state.columns);
+        queue_gutter(grid, doc, line, idx, scroll_y + cursor.row,
+            editor_state)?;
+        let ghost = editor_state.review.is_ghost(&doc.path, idx);
+        queue_line(grid, &visible, ghost, row, cursor, crosshair_column, 
+            editor_state)?;
+        queue_commit_message(grid, doc, line, idx, row, columns, 
+            editor_state)?;
+        queue_highlights(grid, line, idx, row, columns, editor_state)?;
+        queue_diagnostics(grid, doc, line, idx, row, columns, editor_state)?;
+        queue_whitespace(grid, line, row, columns, editor_state)?;
+        queue_search_matches(grid, line, idx, row, columns, editor_state)?;
+        queue_brackets(grid, line, idx, row, columns, editor_state)?;
+        queue_overflow(grid, line, row, columns, editor_state)?;
+        if row as usize == cursor.row {
+            queue_line_length(
+                grid, doc, line, row, scroll_x, editor_state)?;
+        }
+    } else {
+        draw!(grid,
+            PrintStyledContent("~ "
+                .with(editor_state.theme.gutter)))?;
+    }
+    queue_rulers(grid, row, scroll_x, editor_state)?;
+
+    // Clearing the row also cleared its part of the scrollbar
+    queue_scrollbar_cell(grid, row, scroll_y, editor_state)?;
+
+    Ok(())
+}
+
+/// Queue the gutter of the line, its number and the marks of the errors,
+/// of the lines too long and of the lines changed since the last save
+fn queue_gutter(
+    grid: &mut Grid,
+    doc: &Document,
+    line: &str,
+    idx: usize,
+    cursor_line: usize,
+    editor_state: &EditorState
+) -> Result<()> {
+    // The lines with errors of the quickfix list or of the language server
+    // are marked on the gutter
+    let has_errors = editor_state.quickfix
+        .on_line(&doc.path, idx)
+        .next()
+        .is_some()
+        || editor_state.lsp.diagnostics(&doc.path)
+            .iter()
+            .any(|diagnostic| diagnostic.severity <= Severity::Warning
+                && diagnostic.on_line(idx).is_some());
+    let theme = editor_state.theme;
+    let gutter_color = if has_errors {
+        theme.gutter_error
+    } else {
+        theme.gutter
+    };
+    // The relative numbers are the distance to the cursor line, the hybrid
+    // ones too except on the cursor line
+    let width = 
+        editor_state.gutter_width() - editor_state.sidebar_width() - 1;
+    let distance = idx.abs_diff(cursor_line);
+    let number = match editor_state.window.line_numbers {
+        LineNumbers::Absolute => format!("{:width$}", idx),
+        LineNumbers::Relative => format!("{:width$}", distance),
+        LineNumbers::Hybrid if distance == 0 => format!("{:<width$}", idx),
+        LineNumbers::Hybrid => format!("{:width$}", distance),
+        LineNumbers::Off => String::new(),
+    };
+    // The warm lines have the background of their heat, on the badge when
+    // there are no numbers
+    let heat = match editor_state.heatmap {
+        true => editor_state.heat.level(&doc.path, idx).checked_sub(1)
+            .map(|level| theme.heat[level]),
+        false => None,
+    };
+    let mut number = number.with(gutter_color);
+    if let Some(heat) = heat {
+        number = number.on(heat);
+    }
+    draw!(grid, PrintStyledContent(number))?;
+
+    // The lines longer than the limit have a badge after the number, the
+    // badge tells too how the line changed since the last save. The long
+    // documents not compared only know the lines edited
+    let overlong = editor_state.max_line_length
+        .map(|limit| text::is_overlong(line, limit))
+        .unwrap_or(false);
+    let change = if editor_state.changes.compares(&doc.path) {
+        editor_state.changes.mark(&doc.path, idx)
+    } else {
+        doc.is_line_dirty(idx).then_some(LineChange::Modified)
+    };
+    let mut badge = match (overlong, change) {
+        (true, _) => "!".with(theme.long_line),
+        (false, Some(LineChange::Deleted)) => "-".with(theme.deleted),
+        (false, _) => " ".with(theme.long_line),
+    };
+    if change == Some(LineChange::Added) {
+        badge = badge.on(theme.added);
+    } else if change == Some(LineChange::Modified) {
+        badge = badge.on(theme.modified);
+    } else if let (Some(heat), LineNumbers::Off) =
+            (heat, editor_state.window.line_numbers) {
+        badge = badge.on(heat);
+    }
+    draw!(grid, PrintStyledContent(badge))?;
+
+    Ok(())
+}
+
+/// Print the rows of the document area with the long lines wrapped, only
+/// the first row of each line has the gutter
+fn render_wrapped(
+    grid: &mut Grid,
+    doc: &Document,
+    layout: &Layout,
+    scroll_y: usize,
+    cursor_line: usize,
+    editor_state: &EditorState
+) -> Result<()> {
+    let sidebar = editor_state.sidebar_width();
+    for row in 0..editor_state.rows as u16 {
+        draw!(grid,
+            crossterm::cursor::MoveTo(
+                sidebar as u16, editor_state.top as u16 + row),
+            terminal::Clear(terminal::ClearType::UntilNewLine))?;
+
+        match layout.rows.get(row as usize) {
+            Some(visual) => {
+                let line = &doc.buffer[visual.line];
+                if visual.first {
+                    queue_gutter(grid, doc, line, visual.line, cursor_line,
+                        editor_state)?;
+                } else {
+                    let gutter = editor_state.gutter_width() - sidebar;
+                    draw!(grid, Print(format!("{:gutter$}", "")))?;
+                }
+                let columns = wrap::columns_of(line, 
+                    (visual.start, visual.end), editor_state.tab_width);
+                let segment = 
+                    unicode::visible(line, columns, editor_state.tab_width);
+                let ghost =
+                    editor_state.review.is_ghost(&doc.path, visual.line);
+                let styled = style_line(&segment, ghost);
+                draw!(grid, PrintStyledContent(styled))?;
+                queue_commit_message(
+                    grid, doc, line, visual.line, row, columns, editor_state)?;
+                queue_highlights(
+                    grid, line, visual.line, row, columns, editor_state)?;
+                queue_diagnostics(
+                    grid, doc, line, visual.line, row, columns, editor_state)?;
+                queue_whitespace(grid, line, row, columns, editor_state)?;
+                queue_search_matches(
+                    grid, line, visual.line, row, columns, editor_state)?;
+            }
+            None => draw!(grid,
+                PrintStyledContent("~ ".with(editor_state.theme.gutter)))?,
+        }
+
+        queue_scrollbar_cell(grid, row, scroll_y, editor_state)?;
+    }
+
+    Ok(())
+}
+
+/// Queue the marks of a git commit message over the line: the comments are
+/// dimmed and the text past the limit of the line is marked. Only the
+/// `columns` of the line are on the row
+fn queue_commit_message(
+    grid: &mut Grid,
+    doc: &Document,
+    line: &str,
+    idx: usize,
+    row: u16,
+    columns: (usize, usize),
+    editor_state: &EditorState
+) -> Result<()> {
+    let EditorState { top, tab_width, .. } = editor_state;
+    let gutter = editor_state.gutter_width() as u16;
+    if doc.filetype() != Some("gitcommit") {
+        return Ok(());
+    }
+
+    let width = unicode::width(line, *tab_width);
+    let (start, styled) = match commit::limit_of(line, idx) {
+        None => (0, editor_state.theme.commit_comment),
+        Some(limit) => (limit, editor_state.theme.commit_overflow),
+    };
+    let (text, column) = 
+        match clip_cells(line, (start, width), columns, *tab_width) {
+            Some(clipped) => clipped,
+            None => return Ok(()),
+        };
+    let text = if start == 0 { text.with(styled) } else { text.on(styled) };
+    draw!(grid,
+        crossterm::cursor::MoveTo(column as u16 + gutter, *top as u16 + row),
+        PrintStyledContent(text))?;
+
+    Ok(())
+}
+
+/// Queue the background of the chars of the line past the maximum length,
+/// the cells with a background of their own keep it
+fn queue_overflow(
+    grid: &mut Grid,
+    line: &str,
+    row: u16,
+    columns: (usize, usize),
+    editor_state: &EditorState
+) -> Result<()> {
+    let EditorState { max_line_length, top, tab_width, .. } = editor_state;
+    let gutter = editor_state.gutter_width();
+    let byte = match max_line_length
+        .and_then(|limit| line.char_indices().nth(limit)) {
+            Some((byte, _)) => byte,
+            None => return Ok(()),
+        };
+
+    let start = unicode::column_of(line, byte, *tab_width).max(columns.0);
+    let end = unicode::width(line, *tab_width).min(columns.1);
+    if start < end {
+        draw!(grid,
+            crossterm::cursor::MoveTo(
+                (start - columns.0 + gutter) as u16, *top as u16 + row),
+            PaintBackground(editor_state.theme.overflow, (end - start) as u16))?;
+    }
+
+    Ok(())
+}
+
+/// Queue the guides of the rulers on the row, under the text and the cells
+/// with a background of their own. Each one is right after its column
+fn queue_rulers(
+    grid: &mut Grid,
+    row: u16,
+    scroll_x: usize,
+    editor_state: &EditorState
+) -> Result<()> {
+    let EditorState { rulers, columns, top, .. } = editor_state;
+    let gutter = editor_state.gutter_width();
+    for column in rulers {
+        if let Some(column) = column.checked_sub(scroll_x)
+                .filter(|column| column < columns) {
+            draw!(grid,
+                crossterm::cursor::MoveTo(
+                    (column + gutter) as u16, *top as u16 + row),
+                PaintBackground(editor_state.theme.ruler, 1))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Queue the spans of the plugins and the matches of the highlight rules of
+/// the user on the line over the text, the rules over the plugins. Only the
+/// `columns` of the line are on the row
+fn queue_highlights(
+    grid: &mut Grid,
+    line: &str,
+    idx: usize,
+    row: u16,
+    columns: (usize, usize),
+    editor_state: &EditorState
+) -> Result<()> {
+    let EditorState { plugins, highlights, top, tab_width, .. } = editor_state;
+    let gutter = editor_state.gutter_width() as u16;
+    let spans = plugins.spans(idx, line)
+        .into_iter()
+        .chain(highlights.spans(line));
+    for (start, end, style) in spans {
+        let start = unicode::column_of(line, start, *tab_width);
+        let end = unicode::column_of(line, end, *tab_width);
+        let (text, column) = 
+            match clip_cells(line, (start, end), columns, *tab_width) {
+                Some(clipped) => clipped,
+                None => continue,
+            };
+
+        draw!(grid,
+            crossterm::cursor::MoveTo(
+                column as u16 + gutter, *top as u16 + row),
+            PrintStyledContent(StyledContent::new(style, text)))?;
+    }
+
+    Ok(())
+}
+
+/// Queue the diagnostics of the language server on the line, the text they
+/// cover is underlined in the color of how serious they are. Only the
+/// `columns` of the line are on the row
+fn queue_diagnostics(
+    grid: &mut Grid,
+    doc: &Document,
+    line: &str,
+    idx: usize,
+    row: u16,
+    columns: (usize, usize),
+    editor_state: &EditorState
+) -> Result<()> {
+    let EditorState { lsp, theme, top, tab_width, .. } = editor_state;
+    let gutter = editor_state.gutter_width() as u16;
+    // The most serious are first, they are drawn last to be on top
+    for diagnostic in lsp.diagnostics(&doc.path).iter().rev() {
+        let (start, end) = match diagnostic.on_line(idx) {
+            Some(range) => range,
+            None => continue,
+        };
+        let start = unicode::column_of(
+            line, lsp::byte_of(line, start), *tab_width);
+        let end = unicode::column_of(line, lsp::byte_of(line, end), *tab_width);
+
+        // The end of the line is marked when the diagnostic is past it
+        let (text, column) = match clip_cells(
+                line, (start, end), columns, *tab_width) {
+            Some(clipped) => clipped,
+            None if start == end && (columns.0..columns.1).contains(&start) =>
+                (" ".to_owned(), start - columns.0),
+            None => continue,
+        };
+        let color = match diagnostic.severity {
+            Severity::Error => theme.error,
+            Severity::Warning => theme.warning,
+            Severity::Information | Severity::Hint => theme.hint,
+        };
+
+        draw!(grid,
+            crossterm::cursor::MoveTo(
+                column as u16 + gutter, *top as u16 + row),
+            PrintStyledContent(text.with(color).underlined()))?;
+    }
+
+    Ok(())
+}
+
+/// Queue the marks of the whitespace of the line over the text when the
+/// window shows it: a dot for each space and an arrow at the start of each
+/// tab, the whitespace at the end of the line also stands out. Only the
+/// `columns` of the line are on the row
+fn queue_whitespace(
+    grid: &mut Grid,
+    line: &str,
+    row: u16,
+    (from, to): (usize, usize),
+    editor_state: &EditorState
+) -> Result<()> {
+    let EditorState { window, theme, top, tab_width, .. } = editor_state;
+    if !window.whitespace {
+        return Ok(());
+    }
+    let gutter = editor_state.gutter_width() as u16;
+    let trailing = line.trim_end().len();
+
+    let mut column = 0;
+    for (start, grapheme) in unicode::graphemes(line) {
+        let width = unicode::grapheme_width(grapheme, column, *tab_width);
+        let mark = match grapheme {
+            " " => '·',
+            "\t" => '→',
+            _ => {
+                column += width;
+                continue;
+            }
+        };
+        if column >= from && column < to {
+            let mut styled = StyledContent::new(
+                Default::default(), mark).with(theme.whitespace);
+            if start >= trailing {
+                styled = styled.on(theme.trailing_whitespace);
+            }
+            draw!(grid,
+                crossterm::cursor::MoveTo(
+                    (column - from) as u16 + gutter, *top as u16 + row),
+                PrintStyledContent(styled))?;
+        }
+        column += width;
+        if column >= to {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+/// Queue the matches of the open search on the line over the text, the
+/// current match stands out from the others. While replacing only the match
+/// being confirmed is shown. Only the `columns` of the line are on the row
+fn queue_search_matches(
+    grid: &mut Grid,
+    line: &str,
+    idx: usize,
+    row: u16,
+    columns: (usize, usize),
+    editor_state: &EditorState
+) -> Result<()> {
+    let EditorState { search, replace, top, tab_width, .. } = editor_state;
+    let gutter = editor_state.gutter_width() as u16;
+    let (matches, current) = match (search, replace) {
+        (Some(search), _) => (
+            search.index.on_line(idx),
+            search.current.map(|current| search.index.matches[current])),
+        (None, Some(Replace { current: Some(current), .. })) 
+                if current.line == idx => 
+            (std::slice::from_ref(current), Some(*current)),
+        _ => return Ok(()),
+    };
+
+    for m in matches {
+        let start = unicode::column_of(line, m.start, *tab_width);
+        if start >= columns.1 {
+            break;
+        }
+        let end = unicode::column_of(line, m.end, *tab_width);
+        let (text, column) = 
+            match clip_cells(line, (start, end), columns, *tab_width) {
+                Some(clipped) => clipped,
+                None => continue,
+            };
+
+        let theme = editor_state.theme;
+        let styled = if Some(*m) == current {
+            text.with(theme.match_text).on(theme.current_match)
+        } else {
+            text.with(theme.match_text).on(theme.search_match)
+        };
+        draw!(grid,
+            crossterm::cursor::MoveTo(
+                column as u16 + gutter, *top as u16 + row),
+            PrintStyledContent(styled))?;
+    }
+
+    Ok(())
+}
+
+/// Queue the selected text of the rows shown with inverted colors, a
+/// selected newline is an inverted cell after the line
+fn queue_selection(
+    grid: &mut Grid,
+    doc: &Document,
+    selection: Selection,
+    &Viewport { scroll_y, scroll_x }: &Viewport,
+    editor_state: &EditorState
+) -> Result<()> {
+    let EditorState { rows, columns, top, tab_width, .. } = editor_state;
+    let gutter = editor_state.gutter_width() as u16;
+    if selection.is_empty() {
+        return Ok(());
+    }
+
+    draw!(grid, crossterm::cursor::SavePosition)?;
+    let first = usize::max(selection.start.line, scroll_y);
+    for (offset, line) in doc.buffer.lines_from(first).enumerate() {
+        let idx = first + offset;
+        let row = idx - scroll_y;
+        let (start, end) = match selection.on_line(idx, line.len()) {
+            Some(range) if row < *rows => range,
+            _ => break,
+        };
+
+        // Only the part of the selection between the edges is shown
+        let shown = (scroll_x, scroll_x + columns);
+        let selected = selected_cells(line, (start, end), shown, *tab_width);
+        if let Some((selected, column)) = selected {
+            draw!(grid,
+                crossterm::cursor::MoveTo(
+                    column as u16 + gutter, (*top + row) as u16),
+                PrintStyledContent(selected
+                    .with(editor_state.theme.selection_text)
+                    .on(editor_state.theme.selection)))?;
+        }
+    }
+    draw!(grid, crossterm::cursor::RestorePosition)?;
+
+    Ok(())
+}
+
+/// Queue the selected text of the rows shown with the long lines wrapped,
+/// like `queue_selection` but through the rows of the layout
+fn queue_selection_wrapped(
+    grid: &mut Grid,
+    doc: &Document,
+    selection: Selection,
+    layout: &Layout,
+    editor_state: &EditorState
+) -> Result<()> {
+    let EditorState { top, tab_width, .. } = editor_state;
+    let gutter = editor_state.gutter_width() as u16;
+    if selection.is_empty() {
+        return Ok(());
+    }
+
+    draw!(grid, crossterm::cursor::SavePosition)?;
+    for (row, visual) in layout.rows.iter().enumerate() {
+        let line = &doc.buffer[visual.line];
+        let range = match selection.on_line(visual.line, line.len()) {
+            Some(range) => range,
+            None => continue,
+        };
+
+        // The newline is only shown after the last row of the line
+        let (from, to) = 
+            wrap::columns_of(line, (visual.start, visual.end), *tab_width);
+        let shown = (from, to + (visual.end == line.len()) as usize);
+        let selected = selected_cells(line, range, shown, *tab_width);
+        if let Some((selected, column)) = selected {
+            draw!(grid,
+                crossterm::cursor::MoveTo(
+                    column as u16 + gutter, (*top + row) as u16),
+                PrintStyledContent(selected
+                    .with(editor_state.theme.selection_text)
+                    .on(editor_state.theme.selection)))?;
+        }
+    }
+    draw!(grid, crossterm::cursor::RestorePosition)?;
 
     Ok(())
 }
 
-/// Print the status bar
-///
-/// TODO: Modifications in-place of the `status_msg` might improve perf
+/// What the bytes `start..end` of a selection show inside the columns
+/// `shown` of the line, and the column of the row where it starts. A
+/// selected newline is a whitespace after the line
+fn selected_cells(
+    line: &str,
+    (start, end): (usize, usize),
+    shown: (usize, usize),
+    tab_width: usize
+) -> Option<(String, usize)> {
+    let width = unicode::width(line, tab_width);
+    let start = unicode::column_of(line, start, tab_width);
+    let end = if end > line.len() {
+        width + 1
+    } else {
+        unicode::column_of(line, end, tab_width)
+    };
+
+    let (mut selected, column) = 
+        clip_cells(line, (start, end), shown, tab_width)?;
+    if end > width && shown.1 > width {
+        selected.push(' ');
+    }
+
+    Some((selected, column))
+}
+
+/// Queue the length of the cursor line when writing prose, after the text
+/// width or after the line when it's longer, red if it's too long
+fn queue_line_length(
+    grid: &mut Grid,
+    doc: &Document,
+    line: &str,
+    row: u16,
+    scroll_x: usize,
+    editor_state: &EditorState
+) -> Result<()> {
+    let EditorState { prose, columns, top, tab_width, .. } = editor_state;
+    let gutter = editor_state.gutter_width() as u16;
+    if !prose.indicator || !prose::is_prose(doc.filetype()) {
+        return Ok(());
+    }
+
+    let len = unicode::width(line, *tab_width);
+    let label = format!("{}/{}", len, prose.textwidth);
+    let column = usize::max(len, prose.textwidth) + 1;
+    let column = match column.checked_sub(scroll_x) {
+        Some(column) if column + label.len() <= *columns => column,
+        _ => return Ok(()),
+    };
+
+    let color = if len > prose.textwidth {
+        editor_state.theme.line_too_long
+    } else {
+        editor_state.theme.line_length
+    };
+    draw!(grid,
+        crossterm::cursor::MoveTo(column as u16 + gutter, *top as u16 + row),
+        PrintStyledContent(label.with(color)))?;
+
+    Ok(())
+}
+
+/// Queue the cell of the scrollbar on the `row` of the document area, part
+/// of the thumb or of the track
+fn queue_scrollbar_cell(
+    grid: &mut Grid,
+    row: u16,
+    scroll_y: usize,
+    editor_state: &EditorState
+) -> Result<()> {
+    let EditorState { top, rows, columns, doc_lines, .. } = editor_state;
+    let gutter = editor_state.gutter_width() as u16;
+    let (start, end) = scrollbar::thumb(*doc_lines, *rows, scroll_y);
+    let cell = if (start..end).contains(&(row as usize)) {
+        " ".on(editor_state.theme.scrollbar_thumb)
+    } else {
+        " ".on(editor_state.theme.scrollbar_track)
+    };
+
+    draw!(grid,
+        crossterm::cursor::MoveTo(*columns as u16 + gutter, *top as u16 + row),
+        PrintStyledContent(cell))?;
+
+    Ok(())
+}
+
+/// Queue the visible part of a line of the document after its gutter, the
+/// cells shown with the tabs already expanded. With the crosshair enabled
+/// the cursor line is highlighted entirely and the rest only on the
+/// `crosshair_column` of the row. A ghost of the review is crossed out
+fn queue_line(
+    grid: &mut Grid,
+    visible: &str,
+    ghost: bool,
+    row: u16,
+    cursor: &Cursor,
+    crosshair_column: Option<usize>,
+    editor_state: &EditorState
+) -> Result<()> {
+    let EditorState { columns, crosshair, tab_width, .. } = editor_state;
+    if !*crosshair {
+        draw!(grid, PrintStyledContent(style_line(visible, ghost)))?;
+        return Ok(());
+    }
+
+    if row as usize == cursor.row {
+        let width = unicode::width(visible, *tab_width);
+        let padded = format!("{}{:pad$}", visible, "", 
+            pad = columns.saturating_sub(width));
+        draw!(grid,
+            PrintStyledContent(
+                style_line(&padded, ghost)
+                    .on(editor_state.theme.crosshair)))?;
+    } else {
+        draw!(grid, PrintStyledContent(style_line(visible, ghost)))?;
+        if let Some(column) = crosshair_column {
+            queue_cell(grid, visible, ghost, row, column, true, editor_state)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Queue the bracket under the cursor and its pair if any of them is on the
+/// line, with their background
+fn queue_brackets(
+    grid: &mut Grid,
+    line: &str,
+    idx: usize,
+    row: u16,
+    columns: (usize, usize),
+    editor_state: &EditorState
+) -> Result<()> {
+    let EditorState { brackets, top, tab_width, .. } = editor_state;
+    let gutter = editor_state.gutter_width() as u16;
+    let (at, pair) = match brackets {
+        Some(brackets) => brackets,
+        None => return Ok(()),
+    };
+
+    for bracket in [at, pair].into_iter().filter(|p| p.line == idx) {
+        let start = unicode::column_of(line, bracket.byte, *tab_width);
+        let cells = (start, start + 1);
+        if let Some((text, column)) = 
+                clip_cells(line, cells, columns, *tab_width) {
+            draw!(grid,
+                crossterm::cursor::MoveTo(
+                    column as u16 + gutter, *top as u16 + row),
+                PrintStyledContent(text.on(editor_state.theme.bracket)))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Queue the other cursors as inverted cells over the rows, the wrapped lines
+/// don't show them
+fn queue_other_cursors(
+    grid: &mut Grid,
+    doc: &Document,
+    CursorState { viewport, cursors, .. }: &CursorState,
+    editor_state: &EditorState
+) -> Result<()> {
+    let EditorState { rows, columns, top, tab_width, .. } = editor_state;
+    let gutter = editor_state.gutter_width();
+    for position in cursors.positions() {
+        let row = match position.line.checked_sub(viewport.scroll_y) {
+            Some(row) if row < *rows => row,
+            _ => continue,
+        };
+        let line = doc.buffer.line(position.line).unwrap_or("");
+        let column = unicode::column_of(line, position.byte, *tab_width)
+            .checked_sub(viewport.scroll_x)
+            .filter(|column| column < columns);
+        let column = match column {
+            Some(column) => column,
+            None => continue,
+        };
+
+        let end = unicode::next_grapheme(line, position.byte);
+        let cell = match &line[position.byte..end] {
+            "" | "\t" => " ",
+            cell => cell,
+        };
+        draw!(grid,
+            crossterm::cursor::MoveTo(
+                (column + gutter) as u16, (*top + row) as u16),
+            PrintStyledContent(cell.reverse()))?;
+    }
+
+    Ok(())
+}
+
+/// Queue the cell of `column` of the visible part of a line, past the end
+/// of the line it's a whitespace so the crosshair column is continuous. A
+/// wide glyph is queued whole from its first cell
+fn queue_cell(
+    grid: &mut Grid,
+    visible: &str,
+    ghost: bool,
+    row: u16,
+    column: usize,
+    highlighted: bool,
+    editor_state: &EditorState
+) -> Result<()> {
+    let EditorState { top, tab_width, .. } = editor_state;
+    let gutter = editor_state.gutter_width() as u16;
+    let byte = unicode::byte_at_column(visible, column, *tab_width);
+    let (cell, column) = if byte < visible.len() {
+        let end = unicode::next_grapheme(visible, byte);
+        (&visible[byte..end], unicode::column_of(visible, byte, *tab_width))
+    } else {
+        (" ", column)
+    };
+
+    let mut styled = style_line(cell, ghost);
+    if highlighted {
+        styled = styled.on(editor_state.theme.crosshair);
+    }
+    draw!(grid,
+        crossterm::cursor::MoveTo(column as u16 + gutter, *top as u16 + row),
+        PrintStyledContent(styled))?;
+
+    Ok(())
+}
+
+/// Move the crosshair from the `last` cursor to the current one, the rows of
+/// both cursors are repainted and on the other rows only the cells of both
+/// columns
+fn repaint_crosshair(
+    grid: &mut Grid,
+    doc: &Document,
+    last: &Cursor,
+    cursor: &Cursor,
+    viewport: &Viewport,
+    editor_state: &EditorState
+) -> Result<()> {
+    let EditorState { rows, columns, tab_width, .. } = *editor_state;
+    let Viewport { scroll_y, scroll_x } = *viewport;
+
+    // The columns are the ones of the terminal, each cursor on its line
+    let last_column = cursor_column(doc, last, scroll_y, tab_width);
+    let cursor_column = cursor_column(doc, cursor, scroll_y, tab_width);
+    if last_column != cursor_column {
+        for row in 0..rows {
+            if row == last.row || row == cursor.row {
+                continue;
+            }
+
+            let idx = row + scroll_y;
+            if let Some(line) = doc.buffer.line(idx) {
+                let visible = unicode::visible(
+                    line, (scroll_x, scroll_x + columns), tab_width);
+                let row = row as u16;
+                for (column, highlighted) in 
+                        [(last_column, false), (cursor_column, true)] {
+                    let column = column.checked_sub(scroll_x)
+                        .filter(|column| *column < columns);
+                    if let Some(column) = column {
+                        let ghost =
+                            editor_state.review.is_ghost(&doc.path, idx);
+                        queue_cell(grid, &visible, ghost, row, column, 
+                            highlighted, editor_state)?;
+                    }
+                }
+
+                // The cell left by the crosshair is back on the guides
+                queue_overflow(
+                    grid, line, row, (scroll_x, scroll_x + columns), 
+                    editor_state)?;
+                queue_rulers(grid, row, scroll_x, editor_state)?;
+            }
+        }
+    }
+
+    queue_row(grid, doc, last.row as u16, cursor, viewport, editor_state)?;
+    if last.row != cursor.row {
+        queue_row(
+            grid, doc, cursor.row as u16, cursor, viewport, editor_state)?;
+    }
+
+    Ok(())
+}
+
+/// Print the file tree on the sidebar, the selected entry stands out while
+/// the tree has the focus. The border separates it from the gutter
+fn render_tree(
+    grid: &mut Grid,
+    tree: &FileTree,
+    editor_state: &EditorState
+) -> Result<()> {
+    let EditorState { top, rows, theme, .. } = editor_state;
+    let width = editor_state.tree_width;
+    for row in 0..*rows {
+        let idx = tree.scroll + row;
+        let label = match idx < tree.entries.len() {
+            true => tree.label(idx),
+            false => String::new(),
+        };
+        let visible: String = label.chars().take(width).collect();
+        let mut styled = format!("{:width$}", visible).stylize();
+        if tree.entries.get(idx).is_some_and(|entry| entry.dir) {
+            styled = styled.bold();
+        }
+        if idx == tree.selected {
+            styled = match tree.focused {
+                true => styled.with(theme.selection_text).on(theme.selection),
+                false => styled.underlined(),
+            };
+        }
+        draw!(grid,
+            crossterm::cursor::MoveTo(0, (*top + row) as u16),
+            PrintStyledContent(styled),
+            PrintStyledContent("│".with(theme.gutter)))?;
+    }
+
+    Ok(())
+}
+
+/// Print the bar with the name of every tab on the first row of the
+/// terminal, the current one inverted
+fn render_tabs_bar(
+    grid: &mut Grid,
+    document: &Option<Document>,
+    editor_state: &EditorState
+) -> Result<()> {
+    let EditorState { tabs, columns, .. } = editor_state;
+    draw!(grid,
+        crossterm::cursor::MoveTo(0, 0),
+        terminal::Clear(terminal::ClearType::CurrentLine))?;
+
+    let mut width = 0;
+    for (idx, label) in tabs.labels(document).into_iter().enumerate() {
+        width += label.len();
+        if width > columns + editor_state.gutter_width() {
+            break;
+        }
+
+        if idx == tabs.current {
+            draw!(grid,
+                PrintStyledContent(label
+                    .with(editor_state.theme.bar_text)
+                    .on(accent_color(editor_state))))?;
+        } else {
+            draw!(grid, PrintStyledContent(
+                label.with(editor_state.theme.inactive_tab)))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// The background of the bars, dimmed when the terminal lost the focus
+fn accent_color(editor_state: &EditorState) -> Color {
+    if editor_state.focused || !editor_state.dim_unfocused {
+        editor_state.theme.accent
+    } else {
+        editor_state.theme.dimmed_accent
+    }
+}
+
+/// Print the panel over the document area, the first row is for the title
+/// and the rest for the visible lines, only the ones of the lines from
+/// `from`. It covers the gutter too, it's `width` columns wide
+fn render_panel(
+    grid: &mut Grid,
+    panel: &Panel,
+    top: u16,
+    rows: usize,
+    width: usize,
+    from: usize,
+    theme: &Theme
+) -> Result<()> {
+    // The cursor is not used while the panel is open
+    draw!(grid,
+        crossterm::cursor::Hide,
+        crossterm::cursor::MoveTo(0, top),
+        terminal::Clear(terminal::ClearType::CurrentLine),
+        PrintStyledContent(
+            format!("{:width$}", panel.title, width = width)
+                .with(theme.panel_text)
+                .on(theme.panel)))?;
+
+    // Only the rows of the lines from `from` are repainted
+    for row in 1..rows as u16 {
+        let idx = panel.scroll + row as usize - 1;
+        if idx < from {
+            continue;
+        }
+        draw!(grid,
+            crossterm::cursor::MoveTo(0, top + row),
+            terminal::Clear(terminal::ClearType::CurrentLine))?;
+
+        // The lines are truncated so they don't wrap over the next rows, the
+        // selected one is inverted and shows the input if it's being edited
+        if let Some(line) = panel.lines.get(idx) {
+            if panel.selected == Some(idx) {
+                let content = match &panel.input {
+                    Some(input) => format!("> {}_", input),
+                    None => line.clone(),
+                };
+                let visible: String = content.chars().take(width).collect();
+                draw!(grid,
+                    PrintStyledContent(
+                        format!("{:width$}", visible, width = width)
+                            .with(theme.panel_text)
+                            .on(theme.panel)))?;
+            } else {
+                let visible: String = line.chars().take(width).collect();
+                draw!(grid,
+                    PrintStyledContent(
+                        visible.with((panel.highlight)(line))))?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Print the popup on its area, over the document. The `selected` line
+/// stands out
+fn render_popup(
+    grid: &mut Grid,
+    popup: &Popup,
+    selected: Option<usize>,
+    editor_state: &EditorState,
+) -> Result<()> {
+    let &Theme { popup: background, popup_text: text, .. } = editor_state.theme;
+    let EditorState { top, rows, columns, .. } = editor_state;
+    let PopupArea { column, row, width, height } = popup.area(
+        *top, *rows, *columns + editor_state.gutter_width());
+
+    draw!(grid, 
+        crossterm::cursor::SavePosition,
+        crossterm::cursor::Hide)?;
+    for (i, line) in popup.lines.iter().take(height).enumerate() {
+        let visible: String = line.chars().take(width - 2).collect();
+        let (text, background) = match selected == Some(i) {
+            true => (background, text),
+            false => (text, background),
+        };
+        draw!(grid,
+            crossterm::cursor::MoveTo(column as u16, (row + i) as u16),
+            PrintStyledContent(
+                format!(" {:width$} ", visible, width = width - 2)
+                    .with(text)
+                    .on(background)))?;
+    }
+    draw!(grid, 
+        crossterm::cursor::RestorePosition,
+        crossterm::cursor::Show)?;
+
+    Ok(())
+}
+
+/// The text of the status bar, the segments of `status_line` that fit on
+/// the `columns`
+#[allow(clippy::too_many_arguments)]
 fn render_status_bar(
     document: &Option<Document>, 
     cursor: &Cursor,
+    review: &Review,
+    buffers: &BufferList,
+    max_line_length: Option<usize>,
     columns: usize,
-    scroll_y: usize
+    CursorState {
+        viewport: Viewport { scroll_y, scroll_x },
+        anchor,
+        parked,
+        ..
+    }: &CursorState,
+    modal: &ModalState,
+    repo: Option<&RepoStatus>,
+    busy: Option<String>,
+    status_line: &StatusLine
 ) -> String {
-    let mut status_msg = String::with_capacity(columns);
-    if let Some(doc) = document {
-        // Insert the path and a couple whitespaces, not sure if the conversion
-        // from path -> str can really fail
-        status_msg.push_str(doc.path.to_str().unwrap());
-
-        // Create the sub-string with the cursor location + percentage of file
-        // explored
-        let percentage = 
-            (scroll_y + cursor.row) as f32 / doc.inner_lines.len() as f32;
-
-        // It must have a whitespace inside always, so look for it and insert
-        // more whitespaces until `doc_status` can fill all the remaining 
-        // status bar characters
-        let doc_status = format!("{},{}{:8}%", 
-                cursor.column, cursor.row, (percentage * 100.0) as u32);
-        let mut ws_between = columns.checked_sub(
-                                    status_msg.len())
-                                        .and_then(|x| 
-                                            x.checked_sub(doc_status.len()))
-                                    .unwrap_or(0);
-        if ws_between != 0 {
-            while ws_between != 0 {
-                status_msg.push(' ');
-                ws_between -= 1;
+    // On case no document loaded the status bar is this simple
+    let doc = match document {
+        Some(doc) => doc,
+        None => return status::layout(
+            vec![(Segment::Path, "[blank]".to_owned())], Vec::new(), columns),
+    };
+    let head = parked
+        .unwrap_or_else(|| doc.position(scroll_y + cursor.row, cursor.column));
+    let (anchor, scroll_x) = (*anchor, *scroll_x);
+
+    let text = |segment: Segment| -> String {
+        match segment {
+            Segment::Mode if modal.enabled =>
+                format!("-- {} --", modal.mode.name()),
+            Segment::Mode => String::new(),
+            Segment::Buffer =>
+                format!("[{}/{}]", buffers.current + 1, buffers.list.len()),
+            Segment::Path => doc.path.to_string_lossy().into_owned(),
+            Segment::Flags => {
+                let flags = [
+                    (doc.dirty, "[+]"),
+                    (doc.read_only, "[RO]"),
+                    (doc.binary, "[binary]"),
+                    (doc.compose, "[compose]"),
+                    (doc.large, "[large]"),
+                    (doc.new_file, "[new file]"),
+                ];
+                flags
+                    .iter()
+                    .filter(|(set, _)| *set)
+                    .map(|(_, flag)| *flag)
+                    .collect::<Vec<_>>()
+                    .join(" ")
             }
-            status_msg.push_str(&doc_status);
-        }
-    } else {
-        // On case no document loaded the status bar is this simple
-        status_msg.push_str("[blank]");
-        for _ in 0..columns - 7 {
-            status_msg.push(' ');
+            Segment::Git => repo
+                .map(|repo| {
+                    let dirty = if repo.dirty { "*" } else { "" };
+                    format!("[git {}{}]", repo.branch, dirty)
+                })
+                .unwrap_or_default(),
+            Segment::Notes => {
+                let mut notes = Vec::new();
+
+                // Tell that the deletions are being tracked
+                if let Some(pending) = review.pending(&doc.path) {
+                    notes.push(format!("[review: {} pending]", pending));
+                }
+
+                // And how many lines are longer than the limit, a large
+                // file would be read whole to know it
+                if let Some(limit) = max_line_length.filter(|_| !doc.large) {
+                    let overlong = doc.buffer
+                        .lines()
+                        .filter(|line| text::is_overlong(line, limit))
+                        .count();
+                    if overlong > 0 {
+                        notes.push(format!("[{} long lines]", overlong));
+                    }
+                }
+
+                // The summary of a commit message should be short
+                if let Some(len) = commit::long_summary(doc) {
+                    notes.push(format!(
+                        "[summary {}/{}]", len, commit::SUMMARY_LIMIT));
+                }
+
+                // The words and the chars against the limits of the document
+                if doc.limits.is_set() && !doc.large {
+                    notes.extend(doc.limits.notes(doc.count()));
+                }
+
+                // The long lines are shown from this column
+                if scroll_x > 0 {
+                    notes.push(format!("[from column {}]", scroll_x));
+                }
+                notes.join(" ")
+            }
+            Segment::Selection => anchor
+                .map(|anchor| {
+                    let selection = Selection::new(anchor, head);
+                    let (lines, chars) = selection.extent(doc);
+                    format!("[{} lines, {} words, {} chars selected]",
+                        lines, selection.words(doc), chars)
+                })
+                .unwrap_or_default(),
+            Segment::Busy => busy.clone().unwrap_or_default(),
+
+            // With a selection both ends are shown, the anchor first
+            Segment::Position => match anchor {
+                Some(anchor) => format!("{}:{}-{}:{}",
+                    anchor.line + 1, anchor.grapheme + 1,
+                    head.line + 1, head.grapheme + 1),
+                None => format!("{}:{}", head.line + 1, head.grapheme + 1),
+            },
+            Segment::Percent => format!("{:>3}%",
+                (head.line + 1) * 100 / doc.buffer.len().max(1)),
+            Segment::Encoding => doc.encoding.to_string(),
+            Segment::Eol if doc.mixed_line_endings => "mixed EOL".to_owned(),
+            Segment::Eol => doc.line_ending.to_string(),
+            Segment::Filetype => doc.filetype().unwrap_or_default().to_owned(),
         }
-    }
+    };
 
-    status_msg
+    let texts = |segments: &[Segment]| -> Vec<(Segment, String)> {
+        segments
+            .iter()
+            .map(|&segment| (segment, text(segment)))
+            .collect()
+    };
+    status::layout(texts(&status_line.left), texts(&status_line.right), columns)
 }
@@ -0,0 +1,179 @@
+//! Search and replace, the pattern and the replacement are typed on the
+//! prompt and then every match is confirmed with yes/no/all. The pattern is
+//! plain text or a regular expression, whose groups can be used on the
+//! replacement as `$1`
+
+use crate::regex::{self, Regex};
+use crate::search::Match;
+use crate::text::Document;
+
+/// What the prompt is asking for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplaceStep {
+    Pattern,
+    Replacement,
+
+    /// Replace the current match or not
+    Confirm,
+}
+
+/// The state of a replace while open
+#[derive(Debug)]
+pub struct Replace {
+    pub step: ReplaceStep,
+    pub pattern: String,
+    pub replacement: String,
+
+    /// The pattern is a regular expression instead of plain text
+    pub regex: bool,
+    compiled: Option<Regex>,
+
+    /// The match being confirmed
+    pub current: Option<Match>,
+
+    /// Matches replaced so far
+    pub replaced: usize,
+}
+
+impl Replace {
+    pub fn new(regex: bool) -> Self {
+        Self {
+            step: ReplaceStep::Pattern,
+            pattern: String::new(),
+            replacement: String::new(),
+            regex,
+            compiled: None,
+            current: None,
+            replaced: 0,
+        }
+    }
+
+    /// The text being typed on the current step
+    pub fn input_mut(&mut self) -> Option<&mut String> {
+        match self.step {
+            ReplaceStep::Pattern => Some(&mut self.pattern),
+            ReplaceStep::Replacement => Some(&mut self.replacement),
+            ReplaceStep::Confirm => None,
+        }
+    }
+
+    /// Go to the next step, the pattern is compiled when leaving its step
+    pub fn advance(&mut self) -> Result<(), String> {
+        match self.step {
+            ReplaceStep::Pattern => {
+                if self.pattern.is_empty() {
+                    return Err("empty pattern".to_owned());
+                }
+                if self.regex {
+                    self.compiled = Some(Regex::new(&self.pattern)?);
+                }
+                self.step = ReplaceStep::Replacement;
+            }
+            ReplaceStep::Replacement | ReplaceStep::Confirm =>
+                self.step = ReplaceStep::Confirm,
+        }
+
+        Ok(())
+    }
+
+    /// Find the first match at or after the position, `current` is set to it
+    pub fn find_from(
+        &mut self,
+        doc: &Document,
+        line: usize,
+        column: usize
+    ) -> Option<Match> {
+        let mut column = column;
+        self.current = None;
+        for (idx, text) in doc.buffer.lines_from(line).enumerate() {
+            let found = match &self.compiled {
+                Some(regex) => regex.find_at(text, column)
+                    .map(|captures| captures.range()),
+                None => text.get(column..)
+                    .and_then(|rest| rest.find(&self.pattern))
+                    .map(|start|
+                        (column + start, column + start + self.pattern.len())),
+            };
+
+            if let Some((start, end)) = found {
+                self.current = Some(Match { line: line + idx, start, end });
+                return self.current;
+            }
+            column = 0;
+        }
+
+        None
+    }
+
+    /// Replace the current match on the document, returns where to continue
+    /// looking for the next match
+    pub fn replace_current(
+        &mut self,
+        doc: &mut Document
+    ) -> Option<(usize, usize)> {
+        let Match { line, start, end } = self.current.take()?;
+        let text = &doc.buffer[line];
+        let replacement = match &self.compiled {
+            Some(regex) => {
+                let captures = regex.find_at(text, start)?;
+                regex::expand(&self.replacement, text, &captures)
+            }
+            None => self.replacement.clone(),
+        };
+
+        let mut new_text = text.to_owned();
+        new_text.replace_range(start..end, &replacement);
+        doc.replace_lines(line, line + 1, vec![new_text]);
+        self.replaced += 1;
+
+        let column = start + replacement.len();
+        Some((line, self.next_column(doc, line, column, start == end)))
+    }
+
+    /// Skip the current match, returns where to continue looking
+    pub fn skip_current(&mut self, doc: &Document) -> Option<(usize, usize)> {
+        let Match { line, start, end } = self.current.take()?;
+        Some((line, self.next_column(doc, line, end, start == end)))
+    }
+
+    /// The column after a match, past the next char when it was empty so
+    /// the same position doesn't match forever
+    fn next_column(
+        &self,
+        doc: &Document,
+        line: usize,
+        column: usize,
+        empty: bool
+    ) -> usize {
+        let text = &doc.buffer[line];
+        if !empty {
+            return column;
+        }
+
+        text[column..].chars().next()
+            .map(|c| column + c.len_utf8())
+            .unwrap_or(text.len() + 1)
+    }
+
+    /// The text of the prompt
+    pub fn prompt(&self) -> String {
+        let kind = if self.regex { "regex" } else { "text" };
+        match self.step {
+            ReplaceStep::Pattern =>
+                format!("Replace {}: {}", kind, self.pattern),
+            ReplaceStep::Replacement => format!("Replace {} `{}` with: {}",
+                kind, self.pattern, self.replacement),
+            ReplaceStep::Confirm => format!(
+                "Replace with `{}`? (y)es (n)o (a)ll (q)uit    [{} replaced]",
+                self.replacement, self.replaced),
+        }
+    }
+
+    /// Column of the prompt where the input ends, for the cursor
+    pub fn input_column(&self) -> Option<usize> {
+        match self.step {
+            ReplaceStep::Confirm => None,
+            _ => Some(self.prompt().chars().count()),
+        }
+    }
+}
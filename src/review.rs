@@ -0,0 +1,156 @@
+//! Track changes review mode, deleted lines are not removed at once but stay
+//! on the document rendered as ghosts until the deletion is accepted or
+//! rejected, useful when editing documents shared with other people. Each
+//! document is reviewed on its own, its ghosts follow the lines inserted
+//! and removed by any edit
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::text::{Document, LineShift};
+
+/// The documents being reviewed
+#[derive(Debug, Default)]
+pub struct Review {
+    /// Lines of each document pending to be deleted, always sorted
+    documents: HashMap<PathBuf, Vec<usize>>,
+}
+
+impl Review {
+    /// Check if the deletions of the document must be reviewed
+    pub fn is_enabled(&self, path: &Path) -> bool {
+        self.documents.contains_key(path)
+    }
+
+    /// Start reviewing the deletions of the document
+    pub fn enable(&mut self, path: &Path) {
+        self.documents.entry(path.to_owned()).or_default();
+    }
+
+    /// Stop reviewing the deletions of the document, the ghosts it has must
+    /// have been accepted or rejected before
+    pub fn disable(&mut self, path: &Path) {
+        self.documents.remove(path);
+    }
+
+    /// The lines of the document pending to be deleted, `None` when it's
+    /// not being reviewed
+    pub fn pending(&self, path: &Path) -> Option<usize> {
+        self.documents.get(path).map(Vec::len)
+    }
+
+    /// Check if the line of the document is pending to be deleted
+    pub fn is_ghost(&self, path: &Path, line: usize) -> bool {
+        self.documents
+            .get(path)
+            .is_some_and(|ghosts| ghosts.binary_search(&line).is_ok())
+    }
+
+    /// Delete a line of the document, on review mode is just marked as a
+    /// ghost. Returns if the line was really removed
+    pub fn delete_line(&mut self, doc: &mut Document, line: usize) -> bool {
+        if line >= doc.buffer.len() {
+            return false;
+        }
+
+        match self.documents.get_mut(&doc.path) {
+            Some(ghosts) => {
+                if let Err(idx) = ghosts.binary_search(&line) {
+                    ghosts.insert(idx, line);
+                }
+                false
+            }
+            None => {
+                doc.remove_line(line);
+                true
+            }
+        }
+    }
+
+    /// Accept the deletion of the line, so it's really removed, returns if
+    /// the line was a ghost. The ghosts below move up with the shifts of
+    /// the document
+    pub fn accept(&mut self, doc: &mut Document, line: usize) -> bool {
+        if !self.reject(&doc.path, line) {
+            return false;
+        }
+
+        doc.remove_line(line);
+        true
+    }
+
+    /// Reject the deletion of the line, so it stays on the document, returns
+    /// if the line was a ghost
+    pub fn reject(&mut self, path: &Path, line: usize) -> bool {
+        let ghosts = match self.documents.get_mut(path) {
+            Some(ghosts) => ghosts,
+            None => return false,
+        };
+
+        match ghosts.binary_search(&line) {
+            Ok(idx) => {
+                ghosts.remove(idx);
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Remove all the ghosts from the document
+    pub fn accept_all(&mut self, doc: &mut Document) {
+        if let Some(ghosts) = self.documents.get_mut(&doc.path) {
+            // From the bottom so the indices of the remaining ghosts are
+            // valid
+            while let Some(line) = ghosts.pop() {
+                doc.remove_line(line);
+            }
+        }
+    }
+
+    /// Keep all the ghosts on the document as normal lines
+    pub fn reject_all(&mut self, path: &Path) {
+        if let Some(ghosts) = self.documents.get_mut(path) {
+            ghosts.clear();
+        }
+    }
+
+    /// The ghosts follow the lines inserted and removed on the document, a
+    /// ghost removed by an edit is gone
+    pub fn shift(&mut self, path: &Path, shifts: &[LineShift]) {
+        let ghosts = match self.documents.get_mut(path) {
+            Some(ghosts) => ghosts,
+            None => return,
+        };
+
+        for shift in shifts {
+            ghosts.retain(|ghost| !shift.removes(*ghost));
+            for ghost in ghosts.iter_mut() {
+                *ghost = shift.apply(*ghost);
+            }
+        }
+    }
+
+    /// The ghosts follow the line and the next one, which were swapped
+    pub fn lines_swapped(&mut self, path: &Path, line: usize) {
+        let ghosts = match self.documents.get_mut(path) {
+            Some(ghosts) => ghosts,
+            None => return,
+        };
+
+        for ghost in ghosts.iter_mut() {
+            if *ghost == line {
+                *ghost = line + 1;
+            } else if *ghost == line + 1 {
+                *ghost = line;
+            }
+        }
+        ghosts.sort_unstable();
+    }
+
+    /// The file was renamed, its review goes with it
+    pub fn rename_file(&mut self, old_path: &Path, new_path: &Path) {
+        if let Some(ghosts) = self.documents.remove(old_path) {
+            self.documents.insert(new_path.to_owned(), ghosts);
+        }
+    }
+}
@@ -0,0 +1,134 @@
+//! Writing the documents to disk. The save normalizes the text (line
+//! endings, trailing whitespace, final newline), what it would change can be
+//! previewed before writing so cleanups never come as a surprise
+
+use std::fmt;
+
+use crate::eol::LineEnding;
+use crate::text::Document;
+
+/// A change the save makes to the text besides the edits
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Normalization {
+    /// Lines that ended otherwise on disk are written with the ending of
+    /// the document
+    LineEndings(usize, LineEnding),
+
+    /// Lines that lose their trailing whitespace
+    TrimmedWhitespace(usize),
+
+    /// The missing final newline is added
+    FinalNewline,
+}
+
+impl fmt::Display for Normalization {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::LineEndings(lines, ending) =>
+                write!(f, "{} line endings become {}", lines, ending),
+            Self::TrimmedWhitespace(lines) =>
+                write!(f, "trailing whitespace trimmed on {} lines", lines),
+            Self::FinalNewline => write!(f, "final newline added"),
+        }
+    }
+}
+
+/// The normalizations applied on save
+#[derive(Debug, Default)]
+pub struct SaveOptions {
+    /// Remove the whitespace at the end of the lines
+    pub trim_trailing_whitespace: bool,
+}
+
+/// Which documents are saved when the terminal loses the focus, like when
+/// switching to the terminal of a build
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum AutoSave {
+    #[default]
+    Off,
+
+    /// Only the document being edited
+    Current,
+
+    /// Every document open, on every tab
+    All,
+}
+
+impl AutoSave {
+    /// The autosave of the `autosave` option
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "off" => Some(Self::Off),
+            "current" => Some(Self::Current),
+            "all" => Some(Self::All),
+            _ => None,
+        }
+    }
+}
+
+/// Apply the normalizations to the document, returns the text that must be
+/// written and the normalizations applied
+pub fn normalize(
+    doc: &mut Document,
+    options: &SaveOptions,
+    insert_final_newline: bool
+) -> (String, Vec<Normalization>) {
+    let mut normalizations = Vec::new();
+
+    // The lines never have the line endings, they are all written with the
+    // one of the document, the ones on disk tell what changes
+    let others = doc.saved_bytes()
+        .map(|bytes| doc.line_ending.others(&bytes))
+        .unwrap_or(0);
+    if others > 0 {
+        normalizations.push(
+            Normalization::LineEndings(others, doc.line_ending));
+    }
+
+    if options.trim_trailing_whitespace {
+        let trimmed_lines: Vec<usize> = doc.buffer
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| line.trim_end().len() != line.len())
+            .map(|(idx, _)| idx)
+            .collect();
+
+        for &idx in &trimmed_lines {
+            let trimmed = doc.buffer[idx].trim_end().to_owned();
+            doc.replace_lines(idx, idx + 1, vec![trimmed]);
+        }
+        if !trimmed_lines.is_empty() {
+            normalizations.push(
+                Normalization::TrimmedWhitespace(trimmed_lines.len()));
+        }
+    }
+
+    let (contents, added) = doc.contents(insert_final_newline);
+    if added {
+        normalizations.push(Normalization::FinalNewline);
+    }
+
+    (contents, normalizations)
+}
+
+/// Lines describing what the save would change, for the preview panel
+pub fn preview(
+    doc: &Document,
+    options: &SaveOptions,
+    insert_final_newline: bool
+) -> Vec<String> {
+    // The document itself is not touched
+    let mut normalized = doc.clone();
+    let (_, normalizations) = 
+        normalize(&mut normalized, options, insert_final_newline);
+
+    let mut lines = vec![format!("Saving {} would:", doc.path.display())];
+    if normalizations.is_empty() {
+        lines.push("  write the text as it is, no normalizations".to_owned());
+    }
+    for normalization in normalizations {
+        lines.push(format!("  - {}", normalization));
+    }
+
+    lines
+}
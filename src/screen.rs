@@ -0,0 +1,79 @@
+//! The terminal taken by the editor: raw mode, the alternate screen, the
+//! mouse and the focus events. It's given back when the guard is dropped,
+//! also when the editor exits with an error or unwinds from a panic, so the
+//! shell is never left unusable. A panic gives it back before its message
+//! is printed, else the message would go with the alternate screen
+
+use std::io::stdout;
+use std::panic;
+use std::sync::Once;
+
+use crossterm::{execute, terminal};
+use crossterm::event::{
+    DisableFocusChange, DisableMouseCapture, EnableFocusChange,
+    EnableMouseCapture
+};
+
+use crate::Result;
+
+/// While alive the terminal is the one of the editor
+pub struct Screen;
+
+impl Screen {
+    /// Put the terminal in raw mode, which means that:
+    ///  - The stdin doesn't go the stdout directly, its buffered.
+    ///  - Any special characters `Ctrl + ...` has no special behaviours.
+    ///  - New lines have no effect on the stdout, so their type must be
+    ///    explicit by sending a command to newline.
+    ///
+    /// And enter an alternative screen with mouse support
+    pub fn enter() -> Result<Self> {
+        restore_on_panic();
+        terminal::enable_raw_mode()?;
+
+        // From here on the guard restores whatever was enabled
+        let screen = Self;
+        execute!(stdout(),
+            terminal::EnterAlternateScreen,
+            terminal::EnableLineWrap,
+            crossterm::cursor::DisableBlinking,
+            EnableMouseCapture,
+            EnableFocusChange)?;
+
+        Ok(screen)
+    }
+}
+
+impl Drop for Screen {
+    /// Leave the alternative screen, which resumes all the output that was
+    /// before the editor execution, and go back to the normal terminal. The
+    /// errors are ignored, there is nothing else to do
+    fn drop(&mut self) {
+        restore();
+    }
+}
+
+fn restore() {
+    let _ = execute!(stdout(),
+        terminal::LeaveAlternateScreen,
+        DisableMouseCapture,
+        DisableFocusChange,
+        crossterm::cursor::Show);
+    let _ = terminal::disable_raw_mode();
+}
+
+/// Give the terminal back when the main thread panics, before the message
+/// is printed. The threads on the background panic without touching it,
+/// the editor keeps running
+fn restore_on_panic() {
+    static HOOK: Once = Once::new();
+    HOOK.call_once(|| {
+        let report = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if std::thread::current().name() == Some("main") {
+                restore();
+            }
+            report(info);
+        }));
+    });
+}
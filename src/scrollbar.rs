@@ -0,0 +1,31 @@
+//! Scrollbar on the right edge of the document area, the thumb shows the
+//! visible part of the document and dragging it scrubs through the document
+
+/// Rows of the scrollbar covered by the thumb as `start..end`, the thumb
+/// size is proportional to the visible part of the document
+pub fn thumb(doc_lines: usize, rows: usize, scroll_y: usize) -> (usize, usize) {
+    // No room for a thumb
+    if rows == 0 {
+        return (0, 0);
+    }
+    if doc_lines <= rows {
+        return (0, rows);
+    }
+
+    let len = usize::max(1, rows * rows / doc_lines);
+    let max_scroll = doc_lines - rows;
+    let start = (rows - len) * usize::min(scroll_y, max_scroll) / max_scroll;
+
+    (start, start + len)
+}
+
+/// The scroll that corresponds to the `row` of the scrollbar, the first row
+/// is the top of the document and the last one the end
+pub fn scroll_for_row(row: usize, doc_lines: usize, rows: usize) -> usize {
+    let max_scroll = doc_lines.saturating_sub(rows);
+    if rows <= 1 {
+        return 0;
+    }
+
+    usize::min(row, rows - 1) * max_scroll / (rows - 1)
+}
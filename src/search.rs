@@ -0,0 +1,339 @@
+//! Incremental search over the document, the matches of the query are kept
+//! on an index that is refined as the query is typed instead of scanning
+//! the whole document on every keypress
+
+use crate::text::{Document, Position};
+
+/// An occurrence of the query on the document, `start..end` is the byte
+/// range inside the line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Match {
+    pub line: usize,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Match {
+    /// Where the match starts on the document
+    pub fn start_position(&self, doc: &Document) -> Position {
+        doc.position(self.line, self.start)
+    }
+}
+
+/// How the case of the query is treated
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum CaseMode {
+    /// Ignore the case unless the query contains upper case chars
+    #[default]
+    Smart,
+    Sensitive,
+    Insensitive,
+}
+
+impl CaseMode {
+    /// The next mode when toggling it on the prompt
+    pub fn next(self) -> Self {
+        match self {
+            Self::Smart => Self::Sensitive,
+            Self::Sensitive => Self::Insensitive,
+            Self::Insensitive => Self::Smart,
+        }
+    }
+
+    /// If the case must be ignored searching for `query`
+    pub fn ignore_case(self, query: &str) -> bool {
+        match self {
+            Self::Smart => !query.chars().any(char::is_uppercase),
+            Self::Sensitive => false,
+            Self::Insensitive => true,
+        }
+    }
+}
+
+/// Options that change what is a match
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SearchOptions {
+    pub case: CaseMode,
+
+    /// Only match entire words, not parts of them
+    pub whole_word: bool,
+}
+
+impl SearchOptions {
+    /// Flags shown on the prompt
+    pub fn indicators(&self) -> String {
+        let case = match self.case {
+            CaseMode::Smart => "[smart-case]",
+            CaseMode::Sensitive => "[case]",
+            CaseMode::Insensitive => "[no-case]",
+        };
+
+        if self.whole_word {
+            format!("{} [word]", case)
+        } else {
+            case.to_owned()
+        }
+    }
+}
+
+/// All the matches of the query on the document, sorted by position
+#[derive(Debug, Default)]
+pub struct MatchIndex {
+    pub query: String,
+    pub options: SearchOptions,
+
+    /// The matches shown, `raw` filtered by the options
+    pub matches: Vec<Match>,
+
+    /// Every occurrence of the query, the whole word option is not applied
+    /// here because the whole word matches of `abc` are not a subset of the
+    /// ones of `ab`, but the occurrences are
+    raw: Vec<Match>,
+
+    /// The occurrences of the shorter versions of the query, so deleting
+    /// chars of the query doesn't need to scan again
+    history: Vec<(String, Vec<Match>)>,
+}
+
+impl MatchIndex {
+    /// Update the index for the new query. When the query extends the last
+    /// one the new occurrences are a subset of the old ones, every
+    /// occurrence of `abc` is an occurrence of `ab` at the same position, so
+    /// only those are checked. That holds also with smart case, extending a
+    /// query can only make it case sensitive
+    pub fn update(
+        &mut self,
+        doc: &Document,
+        query: &str,
+        options: SearchOptions
+    ) {
+        // Other options invalidate everything
+        if options != self.options {
+            *self = Self {
+                options,
+                ..Self::default()
+            };
+        }
+
+        if query == self.query && !self.history.is_empty() {
+            return;
+        }
+
+        // Going back to a previous query (deleting chars)
+        while !query.starts_with(self.query.as_str()) {
+            match self.history.pop() {
+                Some((old_query, old_raw)) => {
+                    self.query = old_query;
+                    self.raw = old_raw;
+                }
+                None => break,
+            }
+        }
+
+        if query != self.query {
+            let ignore_case = options.case.ignore_case(query);
+            let raw = if !self.query.is_empty()
+                    && query.starts_with(self.query.as_str()) {
+                self.raw
+                    .iter()
+                    .filter_map(|m| {
+                        let line = &doc.buffer[m.line];
+                        let end = match_at(line, m.start, query, ignore_case)?;
+
+                        Some(Match { end, ..*m })
+                    })
+                    .collect()
+            } else {
+                scan(doc, query, ignore_case)
+            };
+
+            let old_query = 
+                std::mem::replace(&mut self.query, query.to_owned());
+            let old_raw = std::mem::replace(&mut self.raw, raw);
+            self.history.push((old_query, old_raw));
+        }
+
+        self.matches = if options.whole_word {
+            self.raw
+                .iter()
+                .filter(|m| is_whole_word(&doc.buffer[m.line], m))
+                .copied()
+                .collect()
+        } else {
+            self.raw.clone()
+        };
+    }
+
+    /// The matches on a line of the document
+    pub fn on_line(&self, line: usize) -> &[Match] {
+        let start = self.matches.partition_point(|m| m.line < line);
+        let end = self.matches.partition_point(|m| m.line <= line);
+
+        &self.matches[start..end]
+    }
+
+    /// Index of the first match at or after the position, wrapping around
+    /// the end of the document
+    pub fn next_from(&self, line: usize, column: usize) -> Option<usize> {
+        if self.matches.is_empty() {
+            return None;
+        }
+
+        let idx = self.matches
+            .partition_point(|m| (m.line, m.start) < (line, column));
+        if idx == self.matches.len() {
+            Some(0)
+        } else {
+            Some(idx)
+        }
+    }
+
+    /// Index of the last match before the position, wrapping around the
+    /// start of the document
+    pub fn prev_from(&self, line: usize, column: usize) -> Option<usize> {
+        if self.matches.is_empty() {
+            return None;
+        }
+
+        let idx = self.matches
+            .partition_point(|m| (m.line, m.start) < (line, column));
+        if idx == 0 {
+            Some(self.matches.len() - 1)
+        } else {
+            Some(idx - 1)
+        }
+    }
+}
+
+/// Find all the occurrences of the query, overlapping ones included
+fn scan(doc: &Document, query: &str, ignore_case: bool) -> Vec<Match> {
+    let mut matches = Vec::new();
+    if query.is_empty() {
+        return matches;
+    }
+
+    for (line_idx, line) in doc.buffer.lines().enumerate() {
+        for (start, _) in line.char_indices() {
+            if let Some(end) = match_at(line, start, query, ignore_case) {
+                matches.push(Match {
+                    line: line_idx,
+                    start,
+                    end,
+                });
+            }
+        }
+    }
+
+    matches
+}
+
+/// Check if the query is at the byte `start` of the line, returns where the
+/// occurrence ends
+fn match_at(
+    line: &str,
+    start: usize,
+    query: &str,
+    ignore_case: bool
+) -> Option<usize> {
+    let rest = line.get(start..)?;
+    if !ignore_case {
+        return rest.starts_with(query).then(|| start + query.len());
+    }
+
+    // The lower case version of a char may have another length, so the
+    // comparison is char by char
+    let mut chars = rest.char_indices();
+    for query_char in query.chars() {
+        let (_, c) = chars.next()?;
+        if c != query_char && !c.to_lowercase().eq(query_char.to_lowercase()) {
+            return None;
+        }
+    }
+
+    Some(start + chars.offset())
+}
+
+/// The chars that form words
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Check that the match is not part of a bigger word
+fn is_whole_word(line: &str, m: &Match) -> bool {
+    let before = line[..m.start].chars().next_back();
+    let after = line[m.end..].chars().next();
+
+    !before.map(is_word_char).unwrap_or(false)
+        && !after.map(is_word_char).unwrap_or(false)
+}
+
+/// The state of the search prompt while open
+#[derive(Debug, Default)]
+pub struct Search {
+    /// The query being typed
+    pub input: String,
+
+    pub index: MatchIndex,
+
+    /// The match the cursor is at
+    pub current: Option<usize>,
+
+    /// Where the cursor was when the search started, as document position,
+    /// and the scroll then, canceling the search goes back there
+    pub origin: Position,
+    pub origin_scroll_y: usize,
+
+    pub options: SearchOptions,
+}
+
+impl Search {
+    /// Start a search from the cursor position
+    pub fn new(
+        origin: Position,
+        origin_scroll_y: usize,
+        options: SearchOptions
+    ) -> Self {
+        Self {
+            origin,
+            origin_scroll_y,
+            options,
+            ..Self::default()
+        }
+    }
+
+    /// Update the matches after the query changed, returns the match the
+    /// cursor must go to
+    pub fn update(&mut self, doc: &Document) -> Option<Match> {
+        self.index.update(doc, &self.input, self.options);
+        self.current = self.index.next_from(self.origin.line, self.origin.byte);
+
+        self.current.map(|idx| self.index.matches[idx])
+    }
+
+    /// Go to the next match after the position, or the previous one before
+    /// it, the query doesn't change
+    pub fn step(&mut self, forward: bool, position: Position) -> Option<Match> {
+        let Position { line, byte, .. } = position;
+        self.current = if forward {
+            self.index.next_from(line, byte + 1)
+        } else {
+            self.index.prev_from(line, byte)
+        };
+
+        self.current.map(|idx| self.index.matches[idx])
+    }
+
+    /// The text of the prompt, with the position of the current match among
+    /// all of them
+    pub fn prompt(&self) -> String {
+        let position = match self.current {
+            _ if self.input.is_empty() => String::new(),
+            Some(idx) => format!("[match {} of {}]",
+                idx + 1, self.index.matches.len()),
+            None => "[no matches]".to_owned(),
+        };
+
+        format!("/{}    {} {}",
+            self.input, position, self.options.indicators())
+    }
+}
@@ -0,0 +1,116 @@
+//! The selected text, from the anchor where the selection started to the
+//! cursor. The operations on a region of the document, like deleting it or
+//! copying it, take the region from here
+
+use crate::count;
+use crate::text::{Document, Position};
+
+/// A region of the document, `start` is never after `end`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Selection {
+    pub start: Position,
+    pub end: Position,
+}
+
+impl Selection {
+    /// The region between the anchor and the cursor, in any order
+    pub fn new(anchor: Position, head: Position) -> Self {
+        if anchor <= head {
+            Self { start: anchor, end: head }
+        } else {
+            Self { start: head, end: anchor }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    /// The bytes of the line inside the selection, the end is past the
+    /// line when the newline after it is selected too. `None` for the lines
+    /// outside of it
+    pub fn on_line(&self, line: usize, len: usize) -> Option<(usize, usize)> {
+        if line < self.start.line || line > self.end.line {
+            return None;
+        }
+
+        let start = if line == self.start.line { self.start.byte } else { 0 };
+        let end = if line == self.end.line { self.end.byte } else { len + 1 };
+        Some((start, end))
+    }
+
+    /// Lines touched and chars inside the selection, the newlines between
+    /// lines count as chars
+    pub fn extent(&self, doc: &Document) -> (usize, usize) {
+        let Self { start, end } = self;
+        let chars = if start.line == end.line {
+            end.grapheme - start.grapheme
+        } else {
+            let line_chars = |line: usize|
+                doc.position(line, usize::MAX).grapheme;
+            let first = line_chars(start.line) - start.grapheme + 1;
+            let middle: usize = (start.line + 1..end.line)
+                .map(|line| line_chars(line) + 1)
+                .sum();
+
+            first + middle + end.grapheme
+        };
+
+        (end.line - start.line + 1, chars)
+    }
+
+    /// The words inside the selection, the ones cut by its ends count. The
+    /// lines selected whole are counted once until they change
+    pub fn words(&self, doc: &Document) -> usize {
+        let Self { start, end } = self;
+        let text = |line: usize| doc.buffer.line(line).unwrap_or("");
+        let cut = |line: usize, from: usize, to: usize|
+            count::words(text(line).get(from..to).unwrap_or(""));
+        if start.line == end.line {
+            return cut(start.line, start.byte, end.byte);
+        }
+
+        let middle: usize = (start.line + 1..end.line)
+            .map(|line| doc.line_words(line))
+            .sum();
+        cut(start.line, start.byte, text(start.line).len())
+            + middle
+            + cut(end.line, 0, end.byte)
+    }
+
+    /// The selected text, the lines joined with newlines
+    pub fn text(&self, doc: &Document) -> String {
+        let Self { start, end } = self;
+        let mut text = String::new();
+        for (idx, line) in doc.buffer.lines_from(start.line).enumerate() {
+            let line_idx = start.line + idx;
+            let (from, to) = match self.on_line(line_idx, line.len()) {
+                Some(range) => range,
+                None => break,
+            };
+            text.push_str(&line[from..usize::min(to, line.len())]);
+            if to > line.len() {
+                text.push('\n');
+            }
+            if line_idx == end.line {
+                break;
+            }
+        }
+
+        text
+    }
+
+    /// Remove the selected text from the document, the lines at both ends
+    /// are joined
+    pub fn delete(&self, doc: &mut Document) {
+        let Self { start, end } = self;
+        let (head, tail) = match (doc.buffer.line(start.line),
+                doc.buffer.line(end.line)) {
+            (Some(head), Some(tail)) => (head, tail),
+            _ => return,
+        };
+
+        let joined = format!("{}{}", &head[..start.byte], &tail[end.byte..]);
+        doc.replace_lines(start.line, end.line + 1, vec![joined]);
+    }
+}
@@ -0,0 +1,202 @@
+//! The editing session saved on exit. The buffers open on the current tab
+//! with their cursor and scroll are written to the state directory of the
+//! user, one file per project, and `--resume` (or the `resume` option)
+//! opens them again when no file is given. Apart from that the last place
+//! of every file edited is kept there too, so opening a file again goes
+//! back to where it was left
+
+use std::path::{Path, PathBuf};
+
+use crate::Result;
+use crate::buffers::BufferList;
+use crate::input::{Cursor, CursorState};
+use crate::text::Document;
+use crate::viewport::Viewport;
+
+/// The files whose place is remembered, the oldest are forgotten first
+const MAX_PLACES: usize = 1000;
+
+/// Where the cursor of a file was and the first line shown
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Place {
+    pub path: PathBuf,
+    pub line: usize,
+    pub byte: usize,
+    pub scroll_y: usize,
+}
+
+impl Place {
+    /// The place of the cursor on the document
+    pub fn of(doc: &Document, cursor: &Cursor, cursor_state: &CursorState)
+            -> Self {
+        Self {
+            path: doc.path.clone(),
+            line: cursor.line(cursor_state),
+            byte: cursor.column,
+            scroll_y: cursor_state.viewport.scroll_y,
+        }
+    }
+
+    /// The cursor and the view at the place, inside the document as it is
+    /// now and with the cursor on one of the `rows` shown
+    pub fn view(&self, doc: &Document, rows: usize) -> (Cursor, CursorState) {
+        let line = usize::min(self.line, doc.buffer.len().saturating_sub(1));
+        let mut scroll_y = usize::min(self.scroll_y, line);
+        if line - scroll_y >= rows {
+            scroll_y = line - rows / 2;
+        }
+        let position = doc.position(line, self.byte);
+
+        let cursor_state = CursorState {
+            viewport: Viewport { scroll_y, ..Viewport::default() },
+            ..CursorState::default()
+        };
+        let cursor = Cursor {
+            row: line - scroll_y,
+            column: position.byte,
+        };
+
+        (cursor, cursor_state)
+    }
+
+    /// An entry of the files, `line<TAB>byte<TAB>scroll<TAB>path`
+    fn parse(entry: &str) -> Option<Self> {
+        let mut fields = entry.splitn(4, '\t');
+        Some(Self {
+            line: fields.next()?.parse().ok()?,
+            byte: fields.next()?.parse().ok()?,
+            scroll_y: fields.next()?.parse().ok()?,
+            path: PathBuf::from(fields.next()?),
+        })
+    }
+
+    fn entry(&self) -> String {
+        format!("{}\t{}\t{}\t{}\n",
+            self.line, self.byte, self.scroll_y, self.path.display())
+    }
+}
+
+/// The places of the buffers of a tab, the shown one first. The state
+/// stored for the buffer shown is stale, so its document and cursor are
+/// given apart. Only the files on disk have a place
+pub fn places(
+    shown: &Option<Document>,
+    cursor: &Cursor,
+    cursor_state: &CursorState,
+    buffers: &BufferList
+) -> Vec<Place> {
+    let shown = shown.as_ref()
+        .map(|doc| Place::of(doc, cursor, cursor_state));
+    let others = buffers.list
+        .iter()
+        .enumerate()
+        .filter(|(idx, _)| *idx != buffers.current)
+        .filter_map(|(_, buffer)| buffer.doc.as_ref().map(|doc|
+            Place::of(doc, &buffer.cursor, &buffer.cursor_state)));
+
+    shown.into_iter()
+        .chain(others)
+        .filter(|place| place.path.is_file())
+        .collect()
+}
+
+/// The places of the last session of the project at `root`, the buffer
+/// shown first
+pub fn load_session(root: impl AsRef<Path>) -> Vec<Place> {
+    session_file(root.as_ref())
+        .map(|file| read_places(&file))
+        .unwrap_or_default()
+}
+
+/// Write the places of the session of the project at `root`
+pub fn save_session(root: impl AsRef<Path>, places: &[Place]) -> Result<()> {
+    let file = session_file(root.as_ref()).ok_or("no state directory")?;
+    write_places(&file, places)
+}
+
+/// `$XDG_STATE_HOME/pepe/sessions/<project>`, the project is known by a
+/// hash of its full path
+fn session_file(root: &Path) -> Option<PathBuf> {
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+
+    let mut hasher = DefaultHasher::new();
+    root.canonicalize().ok()?.hash(&mut hasher);
+    let name = format!("{:016x}", hasher.finish());
+
+    Some(state_dir()?.join("sessions").join(name))
+}
+
+/// The directory of the state of the editor, `None` without a home
+fn state_dir() -> Option<PathBuf> {
+    std::env::var_os("XDG_STATE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME")
+            .map(|home| PathBuf::from(home).join(".local/state")))
+        .map(|state| state.join("pepe"))
+}
+
+/// The last place of every file edited, the newest last
+#[derive(Debug, Default)]
+pub struct Places {
+    list: Vec<Place>,
+
+    /// Where they are persisted, `None` without a state directory
+    file: Option<PathBuf>,
+}
+
+impl Places {
+    /// Load the places remembered, on `$XDG_STATE_HOME/pepe/places`
+    pub fn load() -> Self {
+        let file = state_dir().map(|state| state.join("places"));
+        let list = file.as_deref().map(read_places).unwrap_or_default();
+
+        Self { list, file }
+    }
+
+    /// Remember the place of its file, replacing the one it had
+    pub fn remember(&mut self, place: Place) {
+        let path = match place.path.canonicalize() {
+            Ok(path) => path,
+            Err(_) => return,
+        };
+        self.list.retain(|known| known.path != path);
+        self.list.push(Place { path, ..place });
+        if self.list.len() > MAX_PLACES {
+            self.list.remove(0);
+        }
+    }
+
+    /// The place where the file was left
+    pub fn of(&self, path: &Path) -> Option<&Place> {
+        let path = path.canonicalize().ok()?;
+        self.list.iter().rev().find(|place| place.path == path)
+    }
+
+    pub fn save(&self) -> Result<()> {
+        match &self.file {
+            Some(file) => write_places(file, &self.list),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The places of the file, the malformed entries are skipped
+fn read_places(file: &Path) -> Vec<Place> {
+    std::fs::read_to_string(file)
+        .unwrap_or_default()
+        .lines()
+        .filter_map(Place::parse)
+        .collect()
+}
+
+fn write_places(file: &Path, places: &[Place]) -> Result<()> {
+    if let Some(dir) = file.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let contents: String = places.iter().map(Place::entry).collect();
+    std::fs::write(file, contents)?;
+
+    Ok(())
+}
@@ -0,0 +1,123 @@
+//! Running external programs that transform text, like formatters or the
+//! printer spooler
+
+use std::io::{Read, Write};
+use std::process::{Command, Stdio};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::Result;
+use crate::cancel::CancelToken;
+
+/// Time between the checks of an external program to cancel
+const WAIT_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Run `program` with `input` as its stdin, returns its stdout
+pub fn pipe_program(program: &str, args: &[&str], input: &[u8]) -> Result<String> {
+    let mut command = Command::new(program);
+    command.args(args);
+
+    pipe(command, program, input)
+}
+
+/// Run `program` with `input` as its stdin, returns its stdout as it is,
+/// for the programs whose output is not text
+pub fn pipe_program_bytes(
+    program: &str,
+    args: &[&str],
+    input: &[u8]
+) -> Result<Vec<u8>> {
+    let mut command = Command::new(program);
+    command.args(args);
+
+    pipe_bytes(command, program, input, None)
+}
+
+/// Run `command` on the shell with `input` as its stdin, returns its
+/// stdout. The command is killed when the operation is cancelled
+pub fn pipe_shell(
+    command_line: &str,
+    input: &[u8],
+    cancel: &CancelToken
+) -> Result<String> {
+    let mut command = Command::new("sh");
+    command.arg("-c").arg(command_line);
+
+    let output = pipe_bytes(command, command_line, input, Some(cancel))?;
+    Ok(String::from_utf8_lossy(&output).into_owned())
+}
+
+/// Feed the input to the command, its output is read as text
+fn pipe(command: Command, name: &str, input: &[u8]) -> Result<String> {
+    let output = pipe_bytes(command, name, input, None)?;
+
+    Ok(String::from_utf8_lossy(&output).into_owned())
+}
+
+/// Feed the input to the command, a command that fails is reported with its
+/// stderr. The output is read while the input is written, or a command that
+/// fills its stdout pipe before reading all its stdin never ends
+fn pipe_bytes(
+    mut command: Command,
+    name: &str,
+    input: &[u8],
+    cancel: Option<&CancelToken>
+) -> Result<Vec<u8>> {
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let stdout = read_pipe(child.stdout.take());
+    let stderr = read_pipe(child.stderr.take());
+
+    // Take the stdin so it's closed after writing, otherwise the child
+    // waits forever. A child that doesn't take all of it is not left
+    // running, nor as a zombie
+    if let Some(mut stdin) = child.stdin.take() {
+        if let Err(err) = stdin.write_all(input) {
+            drop(stdin);
+            let _ = child.kill();
+            let status = child.wait()?;
+            let stderr = stderr.join().unwrap_or_default();
+            return Err(format!("`{}` didn't read its input ({}, {}): {}",
+                name, err, status,
+                String::from_utf8_lossy(&stderr).trim()).into());
+        }
+    }
+
+    let status = loop {
+        if cancel.is_some_and(CancelToken::is_cancelled) {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(format!("`{}` was cancelled", name).into());
+        }
+        match child.try_wait()? {
+            Some(status) => break status,
+            None => thread::sleep(WAIT_INTERVAL),
+        }
+    };
+
+    let stdout = stdout.join().unwrap_or_default();
+    if !status.success() {
+        let stderr = stderr.join().unwrap_or_default();
+        return Err(format!("`{}` failed: {}", name,
+            String::from_utf8_lossy(&stderr).trim()).into());
+    }
+
+    Ok(stdout)
+}
+
+/// Read everything from the pipe on its own thread
+fn read_pipe(
+    pipe: Option<impl Read + Send + 'static>
+) -> JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut bytes = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut bytes);
+        }
+
+        bytes
+    })
+}
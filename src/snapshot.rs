@@ -0,0 +1,78 @@
+//! Named copies of a whole document kept in memory, checkpoints like "before
+//! refactor" that don't depend on how the undo history groups the edits.
+//! The buffer can be compared with them or go back to them, they are lost
+//! when the editor closes
+
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use crate::text::Document;
+
+/// The lines of a document at some moment
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    pub path: PathBuf,
+    pub name: String,
+    pub lines: Vec<String>,
+    pub taken: Instant,
+}
+
+/// The snapshots of all the documents, oldest first
+#[derive(Debug, Default)]
+pub struct Snapshots {
+    pub list: Vec<Snapshot>,
+}
+
+impl Snapshots {
+    /// Take a snapshot of the document, one with the same name is replaced
+    pub fn take(&mut self, doc: &Document, name: &str) {
+        if let Some(idx) = self.find(&doc.path, name) {
+            self.list.remove(idx);
+        }
+
+        self.list.push(Snapshot {
+            path: doc.path.clone(),
+            name: name.to_owned(),
+            lines: doc.buffer.to_vec(),
+            taken: Instant::now(),
+        });
+    }
+
+    /// The index of the snapshot of the document at `path` with the name
+    pub fn find(&self, path: &Path, name: &str) -> Option<usize> {
+        self.list
+            .iter()
+            .position(|snapshot| snapshot.path == path && snapshot.name == name)
+    }
+
+    /// The indices of the snapshots of the document at `path`
+    pub fn of(&self, path: &Path) -> Vec<usize> {
+        (0..self.list.len())
+            .filter(|&idx| self.list[idx].path == path)
+            .collect()
+    }
+
+    /// A line for each snapshot of the document at `path`, to list them
+    pub fn lines(&self, path: &Path) -> Vec<String> {
+        self.of(path)
+            .into_iter()
+            .map(|idx| {
+                let snapshot = &self.list[idx];
+                format!("{:24} {:6} lines  {} ago",
+                    snapshot.name,
+                    snapshot.lines.len(),
+                    elapsed(snapshot.taken))
+            })
+            .collect()
+    }
+}
+
+/// How long ago the moment was, in the largest unit
+fn elapsed(since: Instant) -> String {
+    let seconds = since.elapsed().as_secs();
+    match seconds {
+        0..=59 => format!("{}s", seconds),
+        60..=3599 => format!("{}m", seconds / 60),
+        _ => format!("{}h", seconds / 3600),
+    }
+}
@@ -0,0 +1,247 @@
+//! The status bar is made of segments, the ones of the left go one after
+//! the other from its start and the ones of the right end at its end. Which
+//! segments are shown and their order are options. The bar always fits the
+//! width: the path is shortened from its start and then the segments that
+//! matter less are left out
+
+use crate::Result;
+use crate::unicode;
+
+/// Columns the path keeps when shortened, the ellipsis included, as long as
+/// the other segments can be left out instead
+const MIN_PATH_WIDTH: usize = 16;
+
+/// A piece of the status bar, it's left out while it has nothing to say
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Segment {
+    /// The mode of the modal editing
+    Mode,
+
+    /// Which of the open buffers it is, of how many
+    Buffer,
+    Path,
+
+    /// The state of the document: changed, read-only, binary, new...
+    Flags,
+
+    /// The branch of the repository, starred with uncommitted changes
+    Git,
+
+    /// What's worth knowing about the lines and the view: the ones too
+    /// long, the deletions being reviewed, the column they are shown from
+    Notes,
+
+    /// The extent of the selection
+    Selection,
+
+    /// What runs on the background, with a spinner
+    Busy,
+
+    /// The line and the column of the cursor, and of the anchor of the
+    /// selection
+    Position,
+
+    /// How far through the document the cursor is
+    Percent,
+    Encoding,
+    Eol,
+    Filetype,
+}
+
+const SEGMENTS: [(&str, Segment); 13] = [
+    ("mode", Segment::Mode),
+    ("buffer", Segment::Buffer),
+    ("path", Segment::Path),
+    ("flags", Segment::Flags),
+    ("git", Segment::Git),
+    ("notes", Segment::Notes),
+    ("selection", Segment::Selection),
+    ("busy", Segment::Busy),
+    ("position", Segment::Position),
+    ("percent", Segment::Percent),
+    ("encoding", Segment::Encoding),
+    ("eol", Segment::Eol),
+    ("filetype", Segment::Filetype),
+];
+
+impl Segment {
+    pub fn parse(name: &str) -> Result<Self> {
+        SEGMENTS
+            .iter()
+            .find(|(segment, _)| *segment == name)
+            .map(|(_, segment)| *segment)
+            .ok_or_else(|| format!("unknown status segment `{}`, they are: {}",
+                name, SEGMENTS.map(|(name, _)| name).join(", ")).into())
+    }
+
+    pub fn name(self) -> &'static str {
+        SEGMENTS
+            .iter()
+            .find(|(_, segment)| *segment == self)
+            .map(|(name, _)| *name)
+            .unwrap_or_default()
+    }
+
+    /// The segments with less priority are the first left out when the
+    /// bar doesn't fit, the path never is
+    fn priority(self) -> u8 {
+        match self {
+            Self::Encoding | Self::Eol | Self::Filetype => 0,
+            Self::Busy | Self::Git => 1,
+            Self::Notes | Self::Percent => 2,
+            Self::Mode | Self::Buffer | Self::Selection => 3,
+            Self::Flags | Self::Position => 4,
+            Self::Path => 5,
+        }
+    }
+}
+
+/// The segments of each side of the bar, in order
+#[derive(Debug, Clone)]
+pub struct StatusLine {
+    pub left: Vec<Segment>,
+    pub right: Vec<Segment>,
+}
+
+impl Default for StatusLine {
+    fn default() -> Self {
+        Self {
+            left: vec![
+                Segment::Mode,
+                Segment::Buffer,
+                Segment::Path,
+                Segment::Flags,
+                Segment::Git,
+                Segment::Notes,
+                Segment::Selection,
+                Segment::Busy,
+            ],
+            right: vec![
+                Segment::Position,
+                Segment::Percent,
+                Segment::Encoding,
+                Segment::Eol,
+                Segment::Filetype,
+            ],
+        }
+    }
+}
+
+/// The segments of a list of names separated by commas
+pub fn parse_list(value: &str) -> Result<Vec<Segment>> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(Segment::parse)
+        .collect()
+}
+
+/// The names of the segments, as they are parsed
+pub fn list_names(segments: &[Segment]) -> String {
+    segments
+        .iter()
+        .map(|segment| segment.name())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// The bar of `columns` cells with the texts of the segments of each side,
+/// the empty ones are left out
+pub fn layout(
+    left: Vec<(Segment, String)>,
+    right: Vec<(Segment, String)>,
+    columns: usize
+) -> String {
+    let mut left: Vec<_> = left
+        .into_iter()
+        .filter(|(_, text)| !text.is_empty())
+        .collect();
+    let mut right: Vec<_> = right
+        .into_iter()
+        .filter(|(_, text)| !text.is_empty())
+        .collect();
+
+    loop {
+        let (left_width, right_width) = (width(&left), width(&right));
+        let gap = usize::from(left_width > 0 && right_width > 0);
+        let excess = (left_width + gap + right_width).saturating_sub(columns);
+        if excess == 0 {
+            break;
+        }
+
+        // The path is shortened as much as it's needed or it can be
+        let path = left.iter_mut().find(|(segment, _)| *segment == Segment::Path);
+        if let Some((_, path)) = path {
+            let path_width = unicode::width(path, 1);
+            if path_width > MIN_PATH_WIDTH {
+                let target = path_width.saturating_sub(excess).max(MIN_PATH_WIDTH);
+                *path = shorten(path, target);
+                continue;
+            }
+        }
+
+        // Then the segment that matters less goes, the last one of the ties
+        let least = left
+            .iter()
+            .chain(&right)
+            .enumerate()
+            .filter(|(_, (segment, _))| *segment != Segment::Path)
+            .min_by_key(|(idx, (segment, _))|
+                (segment.priority(), usize::MAX - idx))
+            .map(|(idx, _)| idx);
+        match least {
+            Some(idx) if idx < left.len() => { left.remove(idx); }
+            Some(idx) => { right.remove(idx - left.len()); }
+            None => {
+                // Only the path is left, it's cut to whatever fits
+                if let Some((_, path)) = left.first_mut() {
+                    *path = shorten(path, columns);
+                }
+                break;
+            }
+        }
+    }
+
+    let left = join(&left);
+    let right = join(&right);
+    let padding = columns
+        .saturating_sub(unicode::width(&left, 1) + unicode::width(&right, 1));
+
+    format!("{}{}{}", left, " ".repeat(padding), right)
+}
+
+/// The text with its start replaced by an ellipsis so it takes `width` cells
+/// at most, the end of a path is its file name
+fn shorten(text: &str, width: usize) -> String {
+    if unicode::width(text, 1) <= width {
+        return text.to_owned();
+    }
+    if width == 0 {
+        return String::new();
+    }
+
+    let mut tail = Vec::new();
+    let mut used = 1;
+    for c in text.chars().rev() {
+        used += unicode::char_width(c);
+        if used > width {
+            break;
+        }
+        tail.push(c);
+    }
+
+    std::iter::once('…').chain(tail.into_iter().rev()).collect()
+}
+
+fn join(segments: &[(Segment, String)]) -> String {
+    segments
+        .iter()
+        .map(|(_, text)| text.as_str())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn width(segments: &[(Segment, String)]) -> usize {
+    unicode::width(&join(segments), 1)
+}
@@ -0,0 +1,204 @@
+//! The output of a program streamed into a panel while it runs, like a build.
+//! The lines are read on a thread and the editor takes them in batches at a
+//! bounded rate, so a program that prints thousands of lines doesn't repaint
+//! the panel on every one of them and the typing is not slowed down. All the
+//! output is kept, the panel can be closed and opened again while it runs
+
+use std::io::{BufRead, BufReader};
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::Sender;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::Result;
+use crate::events::Wake;
+use crate::grep;
+
+/// What the reader thread has for the editor
+#[derive(Default)]
+struct Shared {
+    /// The lines read since the last batch
+    pending: Vec<String>,
+
+    /// If the program exited successfully, once it exited
+    status: Option<bool>,
+}
+
+/// The lines taken since the last batch
+#[derive(Debug, Default)]
+pub struct Batch {
+    pub lines: Vec<String>,
+
+    /// If the program exited successfully, on the batch after it exited
+    pub finished: Option<bool>,
+}
+
+/// A program running on the background, its stdout and stderr together
+pub struct OutputStream {
+    /// The command line it was started with
+    pub command: String,
+
+    /// Everything printed so far
+    pub lines: Vec<String>,
+
+    /// If the program exited successfully, once it exited
+    pub status: Option<bool>,
+
+    /// The batches are taken at most once per interval
+    pub interval: Duration,
+
+    /// The user stopped it
+    pub cancelled: bool,
+
+    /// The lines are the matches of a search of the project, the command
+    /// is the pattern
+    pub matches: bool,
+
+    /// The program is the leader of its process group, the programs it
+    /// runs are stopped with it
+    pid: u32,
+
+    shared: Arc<Mutex<Shared>>,
+    last_batch: Instant,
+}
+
+impl OutputStream {
+    /// Start running the command on the shell, the editor is woken when
+    /// there are lines to take
+    pub fn spawn(command: &str, wake: Sender<Wake>) -> Result<Self> {
+        // The shell sends its stderr to the same pipe, so the lines of both
+        // come in the order they were printed
+        let mut shell = Command::new("sh");
+        shell.arg("-c")
+            .arg(format!("exec 2>&1; {}", command))
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped());
+        #[cfg(unix)]
+        std::os::unix::process::CommandExt::process_group(&mut shell, 0);
+        let mut child = shell.spawn()?;
+        let pid = child.id();
+        let stdout = child.stdout.take().ok_or("the output can't be read")?;
+
+        let shared = Arc::new(Mutex::new(Shared::default()));
+        let reader_shared = Arc::clone(&shared);
+        thread::spawn(move || {
+            let mut reader = BufReader::new(stdout);
+            let mut line = Vec::new();
+            while let Ok(1..) = reader.read_until(b'\n', &mut line) {
+                let text = String::from_utf8_lossy(&line);
+                let mut shared = reader_shared.lock().unwrap();
+                shared.pending
+                    .push(text.trim_end_matches(['\n', '\r']).to_owned());
+
+                // The first line of a batch is enough to wake the editor,
+                // the rest wait for it to take them
+                if shared.pending.len() == 1 {
+                    let _ = wake.send(Wake::Output);
+                }
+                drop(shared);
+                line.clear();
+            }
+
+            let success = child.wait().is_ok_and(|status| status.success());
+            reader_shared.lock().unwrap().status = Some(success);
+            let _ = wake.send(Wake::Output);
+        });
+
+        Ok(Self {
+            command: command.to_owned(),
+            lines: Vec::new(),
+            status: None,
+            interval: Duration::from_millis(100),
+            cancelled: false,
+            matches: false,
+            pid,
+            shared,
+            last_batch: Instant::now(),
+        })
+    }
+
+    /// Search the project for the lines that match the pattern
+    pub fn grep(pattern: &str, wake: Sender<Wake>) -> Result<Self> {
+        let mut stream = Self::spawn(&grep::command(pattern), wake)?;
+        stream.command = pattern.to_owned();
+        stream.matches = true;
+
+        Ok(stream)
+    }
+
+    /// The title of the panel that shows the output
+    pub fn title(&self) -> String {
+        match self.status {
+            _ if self.cancelled => format!("`{}` cancelled", self.command),
+            None if self.matches => format!("Searching `{}`... {} matches",
+                self.command, self.lines.len()),
+            Some(_) if self.matches => format!(
+                "{} matches of `{}` -- [Up/Down] select  [Enter] jump  \
+                [Esc] close", self.lines.len(), self.command),
+            None => format!("Running `{}`", self.command),
+            Some(true) => format!("`{}` finished", self.command),
+            Some(false) => format!("`{}` failed", self.command),
+        }
+    }
+
+    /// Stop the program and the ones it runs, what they printed is kept
+    pub fn cancel(&mut self) -> Result<()> {
+        if self.status.is_some() {
+            return Err(format!("`{}` already exited", self.command).into());
+        }
+
+        // The whole group, or the programs the shell runs would keep the
+        // pipe open
+        let group = if cfg!(unix) {
+            format!("-{}", self.pid)
+        } else {
+            self.pid.to_string()
+        };
+        let killed = Command::new("kill")
+            .args(["-TERM", "--", &group])
+            .status()?;
+        if !killed.success() {
+            return Err(format!("`{}` can't be stopped", self.command).into());
+        }
+        self.cancelled = true;
+
+        Ok(())
+    }
+
+    /// When the next batch can be taken, `None` when there is nothing to
+    /// take
+    pub fn deadline(&self) -> Option<Instant> {
+        if self.status.is_some() {
+            return None;
+        }
+
+        let shared = self.shared.lock().unwrap();
+        let waiting = !shared.pending.is_empty() || shared.status.is_some();
+        waiting.then(|| self.last_batch + self.interval)
+    }
+
+    /// The lines printed since the last batch, `None` when it's too soon or
+    /// nothing changed
+    pub fn take(&mut self) -> Option<Batch> {
+        if self.status.is_some() || self.last_batch.elapsed() < self.interval {
+            return None;
+        }
+
+        let mut shared = self.shared.lock().unwrap();
+        if shared.pending.is_empty() && shared.status.is_none() {
+            return None;
+        }
+        let batch = Batch {
+            lines: std::mem::take(&mut shared.pending),
+            finished: shared.status,
+        };
+        drop(shared);
+
+        self.lines.extend_from_slice(&batch.lines);
+        self.status = batch.finished;
+        self.last_batch = Instant::now();
+
+        Some(batch)
+    }
+}
@@ -0,0 +1,80 @@
+//! Swap files telling other instances of the editor which files are being
+//! edited. They hold nothing but the pid of the editor, there's no recovery
+//! from them, and sit next to the file as `.<name>.pepe.swp`. The swap file
+//! of an editor that died is just taken over
+
+use std::path::{Path, PathBuf};
+
+/// The swap files of the files this editor opened, they are removed on
+/// close
+#[derive(Debug, Default)]
+pub struct SwapFiles {
+    /// Write the swap files and warn about the ones of other editors
+    pub enabled: bool,
+
+    /// The swap files written by this editor
+    owned: Vec<PathBuf>,
+}
+
+impl SwapFiles {
+    /// Mark the file as being edited here, returns the pid of another
+    /// editor that is already editing it
+    pub fn claim(&mut self, path: &Path) -> Option<u32> {
+        if !self.enabled {
+            return None;
+        }
+
+        let swap = swap_path(path)?;
+        if self.owned.contains(&swap) {
+            return None;
+        }
+        if let Some(pid) = owner(&swap).filter(|pid| is_alive(*pid)) {
+            return Some(pid);
+        }
+
+        // Without a writable directory the file is edited without swap
+        if std::fs::write(&swap, std::process::id().to_string()).is_ok() {
+            self.owned.push(swap);
+        }
+        None
+    }
+
+    /// Remove the swap file of the file, if this editor wrote it
+    pub fn release(&mut self, path: &Path) {
+        if let Some(swap) = swap_path(path) {
+            if let Some(idx) = self.owned.iter().position(|owned| *owned == swap) {
+                let _ = std::fs::remove_file(self.owned.remove(idx));
+            }
+        }
+    }
+}
+
+impl Drop for SwapFiles {
+    fn drop(&mut self) {
+        for swap in &self.owned {
+            let _ = std::fs::remove_file(swap);
+        }
+    }
+}
+
+/// Where the swap file of the file goes
+fn swap_path(path: &Path) -> Option<PathBuf> {
+    let name = path.file_name()?.to_string_lossy();
+
+    Some(path.with_file_name(format!(".{}.pepe.swp", name)))
+}
+
+/// The pid written on the swap file
+fn owner(swap: &Path) -> Option<u32> {
+    std::fs::read_to_string(swap).ok()?.trim().parse().ok()
+}
+
+/// If the process still runs, only known on Linux, elsewhere any other
+/// editor is taken as running
+fn is_alive(pid: u32) -> bool {
+    if pid == std::process::id() {
+        return false;
+    }
+
+    !cfg!(target_os = "linux") || Path::new("/proc").join(pid.to_string()).exists()
+}
@@ -0,0 +1,116 @@
+//! Tab pages, independent workspaces with their own view of a document and
+//! their own panel, switchable with keys and listed on a bar at the top of
+//! the terminal. There are no splits, so the layout of a tab is a single
+//! window, with its own options, plus its optional panel
+
+use std::path::Path;
+
+use crate::buffers::BufferList;
+use crate::input::{Cursor, CursorState};
+use crate::panel::Panel;
+use crate::text::Document;
+use crate::window::WindowOptions;
+
+/// Everything that belongs to a single tab
+#[derive(Default)]
+pub struct Tab {
+    pub doc: Option<Document>,
+    pub cursor: Cursor,
+    pub cursor_state: CursorState,
+    pub panel: Option<Panel>,
+    pub buffers: BufferList,
+    pub window: WindowOptions,
+}
+
+/// All the tab pages of the editor
+pub struct Tabs {
+    /// The state of every tab, the one of the current tab is stale because
+    /// the editor works directly with it until it switches to another tab
+    pub list: Vec<Tab>,
+
+    /// Index of the tab being shown
+    pub current: usize,
+}
+
+impl Default for Tabs {
+    fn default() -> Self {
+        Self {
+            list: vec![Tab::default()],
+            current: 0,
+        }
+    }
+}
+
+impl Tabs {
+    /// The bar is only needed when there is something to switch to
+    pub fn is_visible(&self) -> bool {
+        self.list.len() > 1
+    }
+
+    /// The tab other than the current one and its buffer where the file is
+    /// open
+    pub fn find_elsewhere(&self, path: &Path) -> Option<(usize, usize)> {
+        self.list
+            .iter()
+            .enumerate()
+            .filter(|(idx, _)| *idx != self.current)
+            .find_map(|(idx, tab)| Some((idx, tab.buffers.find(path, &tab.doc)?)))
+    }
+
+    /// The documents of every buffer of the tabs other than the current
+    /// one, the stale ones left out
+    pub fn others_mut(
+        &mut self
+    ) -> impl Iterator<Item = &mut Option<Document>> {
+        let current = self.current;
+        self.list
+            .iter_mut()
+            .enumerate()
+            .filter(move |(idx, _)| *idx != current)
+            .flat_map(|(_, tab)| std::iter::once(&mut tab.doc)
+                .chain(tab.buffers.others_mut()))
+    }
+
+    /// Name of each tab, the current one uses the document being edited
+    /// because its stored state is stale
+    pub fn names(&self, current_doc: &Option<Document>) -> Vec<String> {
+        self.list
+            .iter()
+            .enumerate()
+            .map(|(idx, tab)| {
+                let doc = if idx == self.current {
+                    current_doc
+                } else {
+                    &tab.doc
+                };
+
+                doc.as_ref()
+                    .and_then(|doc| doc.path.file_name())
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_else(|| "[blank]".to_owned())
+            })
+            .collect()
+    }
+
+    /// The label of each tab on the bar, its number and its name
+    pub fn labels(&self, current_doc: &Option<Document>) -> Vec<String> {
+        self.names(current_doc)
+            .iter()
+            .enumerate()
+            .map(|(idx, name)| format!(" {}:{} ", idx + 1, name))
+            .collect()
+    }
+
+    /// The tab whose label is on the column of the bar
+    pub fn at_column(
+        &self,
+        current_doc: &Option<Document>,
+        column: usize
+    ) -> Option<usize> {
+        let mut end = 0;
+        self.labels(current_doc).iter().position(|label| {
+            end += label.len();
+            column < end
+        })
+    }
+}
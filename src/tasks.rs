@@ -0,0 +1,65 @@
+//! The indicator of the work running on the background, like the program
+//! streamed or the indexing of the project. A spinner turns on the status
+//! bar while there is some, so the user knows the editor is not idle
+
+use std::time::{Duration, Instant};
+
+/// The frames of the spinner, in order
+const FRAMES: &[char] = &['|', '/', '-', '\\'];
+
+/// Time each frame is shown
+const FRAME_INTERVAL: Duration = Duration::from_millis(150);
+
+/// The spinner of the status bar
+#[derive(Debug, Default)]
+pub struct Spinner {
+    /// When the work started, `None` while idle
+    started: Option<Instant>,
+
+    /// The frame drawn last, to only repaint when it changes
+    drawn: Option<char>,
+
+    /// What is running, in a few words
+    pub tasks: Vec<String>,
+}
+
+impl Spinner {
+    /// Turn while there are tasks, returns if the status bar must be
+    /// repainted
+    pub fn update(&mut self, tasks: Vec<String>) -> bool {
+        match (tasks.is_empty(), self.started) {
+            (true, _) => self.started = None,
+            (false, None) => self.started = Some(Instant::now()),
+            (false, Some(_)) => {}
+        }
+        let changed = tasks != self.tasks;
+        self.tasks = tasks;
+
+        let frame = self.frame();
+        let turned = frame != self.drawn;
+        self.drawn = frame;
+
+        changed || turned
+    }
+
+    /// The frame to draw, `None` while idle
+    pub fn frame(&self) -> Option<char> {
+        let elapsed = self.started?.elapsed();
+        let idx = elapsed.as_millis() / FRAME_INTERVAL.as_millis();
+
+        Some(FRAMES[idx as usize % FRAMES.len()])
+    }
+
+    /// When the next frame is due, `None` while idle
+    pub fn deadline(&self) -> Option<Instant> {
+        let started = self.started?;
+        let frames = started.elapsed().as_millis() / FRAME_INTERVAL.as_millis();
+
+        Some(started + FRAME_INTERVAL * (frames as u32 + 1))
+    }
+
+    /// What the status bar shows, like `[/ indexing, run]`
+    pub fn status(&self) -> Option<String> {
+        Some(format!("[{} {}]", self.frame()?, self.tasks.join(", ")))
+    }
+}
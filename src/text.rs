@@ -1,88 +1,978 @@
 //! All text processing/loading/storing related thing, on the future syntax
 //! highlighting will be handled here
 
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{ErrorKind, Read, Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 
 use crate::Result;
+use crate::binary;
+use crate::cancel::{self, CancelToken};
+use crate::commit;
+use crate::count::{Count, LineCounts, Limits};
+use crate::crypt::Encryption;
+use crate::encoding::Encoding;
+use crate::eol::LineEnding;
+use crate::fileops;
+use crate::narrow::Narrowed;
+use crate::unicode;
+use crate::watch;
+
+mod buffer;
+
+pub use buffer::Buffer;
+
+/// The brackets that pair, the opening one first
+const BRACKETS: [(u8, u8); 3] = [(b'(', b')'), (b'[', b']'), (b'{', b'}')];
+
+/// The lines searched for the pair of a bracket, so a bracket without pair
+/// on a huge document doesn't scan all of it
+const MAX_BRACKET_LINES: usize = 10_000;
+
+/// Files of this size or more are only indexed when opened, the `largefile`
+/// option changes it
+pub const LARGE_FILE: u64 = 64 << 20;
+
+/// A position on a document, the column is both the index of the grapheme
+/// (what is seen as a single char, for now a char) and the byte offset
+/// inside the line. Build it with the methods of `Document` so both agree
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Position {
+    pub line: usize,
+    pub grapheme: usize,
+    pub byte: usize,
+}
+
+/// The lines `start..end` were replaced with `lines` lines, the lines below
+/// moved. What's attached to the lines follows them with it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineShift {
+    pub start: usize,
+    pub end: usize,
+    pub lines: usize,
+}
+
+impl LineShift {
+    /// Where the line is after the change. The lines replaced keep their
+    /// place while there are new lines for them, the rest go to the last
+    /// new line or to the line that took their place
+    pub fn apply(&self, line: usize) -> usize {
+        if line < self.start {
+            line
+        } else if line >= self.end {
+            line + self.lines - (self.end - self.start)
+        } else if line - self.start < self.lines {
+            line
+        } else {
+            self.start + self.lines.saturating_sub(1)
+        }
+    }
+
+    /// If the line was removed by the change, no new line took its place
+    pub fn removes(&self, line: usize) -> bool {
+        line >= self.start && line < self.end && line - self.start >= self.lines
+    }
+}
+
+/// What the document is for, only the files are saved where they're read
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum DocKind {
+    #[default]
+    File,
+
+    /// A buffer not bound to a file yet, the path is only its name and it
+    /// gets one when saved as a file
+    Scratch,
+
+    /// Text of the editor, like the help or the matches of a search,
+    /// shown read-only to be searched and copied
+    Preview,
+
+    /// Stands for the document of a file open on another tab or buffer,
+    /// there is a single copy of it and it's taken from there when shown
+    Placeholder,
+}
 
 /// A document the editor opens for read and (probably) write.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Document {
     /// The path for reading/writting into actual permanent memory.
     pub path: PathBuf,
+    pub kind: DocKind,
 
     /// The file is read in a particular way, newlines are not included, so the
     /// file on save will have a consistent newline type, changes are made
     /// inside here.
-    pub inner_lines: Vec<String>,
+    pub buffer: Buffer,
+
+    /// If the file ended with a newline when it was read, the lines don't
+    /// tell it
+    pub final_newline: bool,
+
+    /// The ending every line is saved with, the one most lines of the file
+    /// had, and if some had the other one
+    pub line_ending: LineEnding,
+    pub mixed_line_endings: bool,
+
+    /// If there are changes not written to the file
+    pub dirty: bool,
+
+    /// The edits are refused
+    pub read_only: bool,
+
+    /// The file has NUL bytes, its invalid UTF-8 was replaced when read
+    pub binary: bool,
+
+    /// The encoding of the file, the lines are converted from it when read
+    /// and back to it when saved
+    pub encoding: Encoding,
+
+    /// The file is encrypted, the contents are decrypted on open and
+    /// encrypted again on save
+    pub encryption: Option<Encryption>,
+
+    /// The document shows some lines of another one, saving it writes them
+    /// back there
+    pub narrow: Option<Narrowed>,
+
+    /// The file was too big to be read, its lines are read from it when
+    /// they are shown or edited
+    pub large: bool,
+
+    /// The file doesn't exist yet, the first save creates it
+    pub new_file: bool,
+
+    /// The filetype chosen by the user, the extension is ignored
+    pub forced_filetype: Option<&'static str>,
+
+    /// The accented chars are composed from the accent and the letter typed
+    /// after it
+    pub compose: bool,
+
+    /// The words and the chars the document is meant to stay under
+    pub limits: Limits,
+
+    /// When the file was modified as the document knows it, from the read
+    /// or the last save. `None` when it wasn't read from a file
+    pub mtime: Option<SystemTime>,
+
+    /// Which lines changed since the last save, kept with the same length
+    /// as `buffer`
+    dirty_lines: Vec<bool>,
+
+    /// The words and the chars of the lines, counted when asked
+    counts: LineCounts,
+
+    /// The lines inserted and removed since they were taken last
+    line_shifts: Vec<LineShift>,
 }
 
 impl Document {
-    /// Creates a new document with a associated path
-    pub fn new(path: impl AsRef<Path>) -> Result<Self> {
-        /// Helper struct just to be more explicit
-        struct Line {
-            start: usize,
-            len: usize
-        }
-
-        let bytes = std::fs::read(path.as_ref())?;
-        let mut lines = Vec::new();
-
-        // Iterate over the file and create `Line` struct to delimitate the
-        // start and end of each line without including the newline symbols
-        let mut start = 0;
-        let mut len = 0;
-        let mut i = 0;
-        while i < bytes.len() {
-            if bytes[i] == b'\r' {
-                if i < bytes.len() - 1 {
-                    if bytes[i + 1] == b'\n' {
-                        lines.push(Line {
-                            start,
-                            len
-                        });
-
-                        start = i + 2;
-                        len = 0;
-
-                        i += 2;
-
-                        continue;
+    /// Creates a new document with a associated path, the read of a huge
+    /// file stops when cancelled. A UTF-8 file of `large_file` bytes or more
+    /// is only indexed, its lines are read as they are needed
+    pub fn new(
+        path: impl AsRef<Path>,
+        large_file: Option<u64>,
+        cancel: &CancelToken
+    ) -> Result<Self> {
+        let path = path.as_ref();
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Self {
+                new_file: true,
+                ..Self::from_bytes(path, &[])
+            }),
+            Err(err) => return Err(err.into()),
+        };
+        let size = file.metadata()?.len();
+
+        // Only the start tells if it's binary and its encoding, the lines
+        // on the file must be UTF-8 to be read straight from it
+        let mut head = Vec::new();
+        (&file).take(binary::SNIFF_LEN as u64).read_to_end(&mut head)?;
+        let indexed = size > 0
+            && large_file.is_some_and(|limit| size >= limit)
+            && Encoding::detect(&head) == Encoding::Utf8;
+        if !indexed {
+            let bytes = cancel::read(path, cancel)?;
+            return Ok(Self {
+                mtime: watch::mtime(path),
+                ..Self::from_bytes(path, &bytes)
+            });
+        }
+        let mut last = [0];
+        (&file).seek(SeekFrom::End(-1))?;
+        (&file).read_exact(&mut last)?;
+
+        let buffer = Buffer::index_file(file, cancel)?;
+        let (line_ending, mixed_line_endings) = LineEnding::detect(&head);
+        Ok(Self {
+            dirty_lines: vec![false; buffer.len()],
+            buffer,
+            final_newline: last == [b'\n'],
+            line_ending,
+            mixed_line_endings,
+            binary: binary::is_binary(&head),
+            large: true,
+            mtime: watch::mtime(path),
+            ..Self::from_bytes(path, &[])
+        })
+    }
+
+    /// Creates a document with the contents already read, the path is only
+    /// its name. The encoding of the contents is guessed, the binary files
+    /// are UTF-8 with their invalid bytes replaced
+    pub fn from_bytes(path: impl AsRef<Path>, bytes: &[u8]) -> Self {
+        // The NULs of UTF-16 don't make it binary
+        let encoding = Encoding::detect(bytes);
+        let utf16 = matches!(encoding, Encoding::Utf16Le | Encoding::Utf16Be);
+        let binary = !utf16 && binary::is_binary(bytes);
+        let encoding = if binary { Encoding::Utf8 } else { encoding };
+        let text = encoding.decode(bytes);
+        let buffer = Buffer::from_bytes(&text);
+        let (line_ending, mixed_line_endings) = LineEnding::detect(&text);
+
+        Self {
+            path: path.as_ref().to_owned(),
+            kind: DocKind::File,
+            dirty_lines: vec![false; buffer.len()],
+            buffer,
+            final_newline: text.is_empty() || text.ends_with(b"\n"),
+            line_ending,
+            mixed_line_endings,
+            dirty: false,
+            read_only: false,
+            binary,
+            encoding,
+            encryption: None,
+            narrow: None,
+            large: false,
+            new_file: false,
+            forced_filetype: None,
+            compose: false,
+            limits: Limits::default(),
+            mtime: None,
+            line_shifts: Vec::new(),
+            counts: LineCounts::default(),
+        }
+    }
+
+    /// Creates an empty document that is no file, `name` is shown as its
+    /// path
+    pub fn scratch(name: impl AsRef<Path>) -> Self {
+        Self { kind: DocKind::Scratch, ..Self::from_bytes(name, &[]) }
+    }
+
+    /// Creates the placeholder of the document of the file, it has no text
+    pub fn placeholder(path: impl AsRef<Path>) -> Self {
+        Self { kind: DocKind::Placeholder, ..Self::from_bytes(path, &[]) }
+    }
+
+    /// Creates a read-only document with the lines, `name` is shown as its
+    /// path
+    pub fn preview(name: impl AsRef<Path>, lines: &[String]) -> Self {
+        let mut text = lines.join("\n");
+        text.push('\n');
+        Self {
+            kind: DocKind::Preview,
+            read_only: true,
+            ..Self::from_bytes(name, text.as_bytes())
+        }
+    }
+
+    /// Creates a document with the contents of an encrypted file already
+    /// decrypted, it's written encrypted the same way
+    pub fn encrypted(
+        path: impl AsRef<Path>,
+        encryption: Encryption,
+        plaintext: &[u8]
+    ) -> Self {
+        Self {
+            encryption: Some(encryption),
+            mtime: watch::mtime(path.as_ref()),
+            ..Self::from_bytes(path, plaintext)
+        }
+    }
+
+    /// Write the contents to the file atomically: they go to a temporary
+    /// file next to it that then replaces the file, so a crash never leaves
+    /// it half written. A link is followed and the file it points to is the
+    /// one replaced, keeping its permissions and its owner. When the file
+    /// can't be replaced that way, because its directory can't be written
+    /// or its owner can't be kept, it's written in place. A new file is
+    /// just created. The document is clean after it
+    pub fn save(&mut self, contents: &str) -> Result<()> {
+        let target = std::fs::canonicalize(&self.path)
+            .unwrap_or_else(|_| self.path.clone());
+        let bytes = self.encoding.encode(contents)?;
+        let bytes = match &self.encryption {
+            Some(encryption) => encryption.encrypt(&bytes)?,
+            None => bytes,
+        };
+
+        write_file(&target, &bytes)?;
+
+        self.final_newline = contents.is_empty() || contents.ends_with('\n');
+        self.mixed_line_endings = false;
+        self.mtime = watch::mtime(&self.path);
+        self.new_file = false;
+        self.mark_clean();
+
+        Ok(())
+    }
+
+    /// The file was read again into the document, `saved` are its contents.
+    /// The document is clean and knows the file as it is now
+    pub fn mark_reloaded(&mut self, saved: &[u8]) {
+        self.final_newline = saved.is_empty() || saved.ends_with(b"\n");
+        (self.line_ending, self.mixed_line_endings) = LineEnding::detect(saved);
+        self.mtime = watch::mtime(&self.path);
+        self.mark_clean();
+    }
+
+    /// If another program modified the file since the document read or
+    /// saved it, a deleted file too
+    pub fn changed_on_disk(&self) -> bool {
+        self.mtime.is_some() && watch::mtime(&self.path) != self.mtime
+    }
+
+    /// Forget the changes, they are already stored
+    pub fn mark_clean(&mut self) {
+        self.dirty = false;
+        self.dirty_lines = vec![false; self.buffer.len()];
+    }
+
+    /// If the line changed since the last save
+    pub fn is_line_dirty(&self, line: usize) -> bool {
+        self.dirty_lines.get(line).copied().unwrap_or(false)
+    }
+
+    /// Mark the line as changed
+    fn mark_dirty(&mut self, line: usize) {
+        self.counts.changed(line);
+        self.dirty = true;
+        self.sync_dirty_lines();
+        if let Some(dirty) = self.dirty_lines.get_mut(line) {
+            *dirty = true;
+        }
+    }
+
+    /// The lines modified without the methods of the document are unknown,
+    /// so they are considered changed
+    fn sync_dirty_lines(&mut self) {
+        self.dirty_lines.resize(self.buffer.len(), true);
+    }
+
+    /// Remember that lines were inserted or removed, the ones replaced by as
+    /// many don't move anything but are counted again
+    fn shift_lines(&mut self, start: usize, end: usize, lines: usize) {
+        self.counts.replaced(start, end, lines);
+        if end - start != lines {
+            self.line_shifts.push(LineShift { start, end, lines });
+        }
+    }
+
+    /// The lines inserted and removed since the last call, in order
+    pub fn take_line_shifts(&mut self) -> Vec<LineShift> {
+        std::mem::take(&mut self.line_shifts)
+    }
+
+    /// The lines, the words and the chars of the document, only the lines
+    /// changed since the last count are counted
+    pub fn count(&self) -> Count {
+        let mut count = Count { lines: self.buffer.len(), ..Count::default() };
+        for (idx, line) in self.buffer.lines().enumerate() {
+            let (words, chars) = self.counts.line(idx, line);
+            count.words += words;
+            count.chars += chars;
+        }
+
+        // The line breaks between the lines and the final one
+        let final_newline = self.final_newline && count.lines > 0;
+        count.chars +=
+            count.lines.saturating_sub(1) + usize::from(final_newline);
+        count
+    }
+
+    /// The words of the line, counted once until it changes
+    pub fn line_words(&self, line: usize) -> usize {
+        self.counts.line(line, self.buffer.line(line).unwrap_or("")).0
+    }
+
+    /// The contents of the document as they are written to the file, if
+    /// `insert_final_newline` a missing final newline is added. Returns also
+    /// if it had to be added
+    pub fn contents(&self, insert_final_newline: bool) -> (String, bool) {
+        let newline = self.line_ending.as_str();
+        let mut contents = self.buffer.join(newline);
+        let final_newline = 
+            self.final_newline || insert_final_newline;
+        if final_newline && !self.buffer.is_empty() {
+            contents.push_str(newline);
+        }
+
+        let added = insert_final_newline 
+            && !self.final_newline 
+            && !self.buffer.is_empty();
+
+        (contents, added)
+    }
+
+    /// Read again the contents of the file as they are on disk, decrypted
+    /// if encrypted and as UTF-8, without modifying the document
+    pub fn saved_bytes(&self) -> Result<Vec<u8>> {
+        let bytes = match &self.encryption {
+            Some(encryption) => encryption.decrypt(&self.path)?,
+            None => std::fs::read(&self.path)?,
+        };
+
+        Ok(self.encoding.decode(&bytes).into_owned())
+    }
+
+    /// Read again the lines of the file as they are on disk, without
+    /// modifying the document
+    pub fn saved_lines(&self) -> Result<Vec<String>> {
+        Ok(split_lines(&self.saved_bytes()?))
+    }
+
+    /// Insert the char at the byte `column` of the line, returns the column
+    /// right after it. An empty document gets its first line
+    pub fn insert_char(&mut self, line: usize, column: usize, c: char) -> usize {
+        self.create_lines_until(line);
+        let column = self.buffer.edit_line(line, |text| {
+            let column = floor_char_boundary(text, column);
+            text.insert(column, c);
+            column
+        });
+        self.mark_dirty(line);
+
+        column + c.len_utf8()
+    }
+
+    /// Delete the grapheme at the byte `column` of the line, at the end of
+    /// the line the next one is joined. Returns if something was deleted
+    pub fn delete_char(&mut self, line: usize, column: usize) -> bool {
+        let text = match self.buffer.line(line) {
+            Some(text) => text,
+            None => return false,
+        };
+
+        let column = unicode::floor_grapheme(text, column);
+        if column < text.len() {
+            let end = unicode::next_grapheme(text, column);
+            self.buffer.edit_line(line, |text| {
+                text.replace_range(column..end, "")
+            });
+            self.mark_dirty(line);
+            true
+        } else {
+            self.join_lines(line).is_some()
+        }
+    }
+
+    /// Split the line at the byte `column`, the rest of the line goes to a
+    /// new line below
+    pub fn insert_newline(&mut self, line: usize, column: usize) {
+        self.create_lines_until(line);
+        let text = &self.buffer[line];
+        let column = floor_char_boundary(text, column);
+        let (head, rest) = text.split_at(column);
+        let lines = vec![head.to_owned(), rest.to_owned()];
+
+        self.sync_dirty_lines();
+        self.buffer.splice(line..line + 1, lines);
+        self.dirty_lines.insert(line + 1, true);
+        self.mark_dirty(line);
+
+        // Splitting at the start pushes the whole line down
+        let below = if column == 0 { line } else { line + 1 };
+        self.shift_lines(below, below, 1);
+    }
+
+    /// Append the next line to the line, returns the column where the
+    /// joined text starts or `None` when there is no next line
+    pub fn join_lines(&mut self, line: usize) -> Option<usize> {
+        if line + 1 >= self.buffer.len() {
+            return None;
+        }
+
+        self.sync_dirty_lines();
+        self.dirty_lines.remove(line + 1);
+        let column = self.buffer[line].len();
+        let joined = format!("{}{}", &self.buffer[line], &self.buffer[line + 1]);
+        self.buffer.splice(line..line + 2, vec![joined]);
+        self.mark_dirty(line);
+        self.shift_lines(line, line + 2, 1);
+
+        Some(column)
+    }
+
+    /// The position at the byte of the line, the byte is moved back to the
+    /// grapheme it's inside and to the end of the line when past it
+    pub fn position(&self, line: usize, byte: usize) -> Position {
+        let text = self.buffer.line(line).unwrap_or("");
+        let byte = unicode::floor_grapheme(text, byte);
+
+        Position {
+            line,
+            grapheme: unicode::graphemes(&text[..byte]).count(),
+            byte,
+        }
+    }
+
+    /// The position of the bracket that pairs with the one at the position,
+    /// the brackets of the same kind between them are skipped, on any line
+    pub fn matching_bracket(&self, at: Position) -> Option<Position> {
+        let line = self.buffer.line(at.line)?;
+        let bracket = *line.as_bytes().get(at.byte)?;
+        let (open, close, forward) = BRACKETS.iter()
+            .find_map(|&(open, close)| match bracket {
+                _ if bracket == open => Some((open, close, true)),
+                _ if bracket == close => Some((open, close, false)),
+                _ => None,
+            })?;
+
+        // The brackets are ASCII, so their bytes are never inside a char
+        let mut depth = 0usize;
+        let lines = usize::min(self.buffer.len(), at.line + MAX_BRACKET_LINES);
+        let first = at.line.saturating_sub(MAX_BRACKET_LINES);
+        let mut visit = |idx: usize, byte: usize, c: u8| {
+            if c == open || c == close {
+                if (c == open) == forward {
+                    depth += 1;
+                } else {
+                    depth -= 1;
+                }
+            }
+            (depth == 0).then(|| self.position(idx, byte))
+        };
+        if forward {
+            for idx in at.line..lines {
+                let text = self.buffer.line(idx)?.as_bytes();
+                let from = if idx == at.line { at.byte } else { 0 };
+                for (byte, &c) in text.iter().enumerate().skip(from) {
+                    if let Some(found) = visit(idx, byte, c) {
+                        return Some(found);
                     }
                 }
             }
-            
-            if bytes[i] == b'\n' {
-                lines.push(Line {
-                    start,
-                    len
-                });
+        } else {
+            for idx in (first..=at.line).rev() {
+                let text = self.buffer.line(idx)?.as_bytes();
+                let to = if idx == at.line { at.byte + 1 } else { text.len() };
+                for (byte, &c) in text[..to].iter().enumerate().rev() {
+                    if let Some(found) = visit(idx, byte, c) {
+                        return Some(found);
+                    }
+                }
+            }
+        }
 
-                start = i + 1;
-                len = 0;
+        None
+    }
 
-                i += 1;
+    /// The position of the grapheme of the line, the end of the line when
+    /// past it
+    pub fn position_at_grapheme(
+        &self,
+        line: usize,
+        grapheme: usize
+    ) -> Position {
+        let text = self.buffer.line(line).unwrap_or("");
+        let byte = unicode::graphemes(text)
+            .nth(grapheme)
+            .map(|(byte, _)| byte)
+            .unwrap_or(text.len());
 
-                continue;
-            }
+        self.position(line, byte)
+    }
+
+    /// Insert the text at the byte `column` of the line, its newlines split
+    /// the line. Returns the line and the byte right after the text
+    pub fn insert_text(
+        &mut self,
+        line: usize,
+        column: usize,
+        text: &str
+    ) -> (usize, usize) {
+        self.create_lines_until(line);
+        let current = &self.buffer[line];
+        let column = floor_char_boundary(current, column);
+        let (head, tail) = current.split_at(column);
+
+        let mut lines: Vec<String> = text.split('\n')
+            .map(|piece| piece.to_owned())
+            .collect();
+        let last = lines.len() - 1;
+        let end = if last == 0 { column } else { 0 } + lines[last].len();
+        lines[0].insert_str(0, head);
+        lines[last].push_str(tail);
+
+        self.replace_lines(line, line + 1, lines);
+
+        (line + last, end)
+    }
+
+    /// The lines missing up to the line to modify are created
+    fn create_lines_until(&mut self, line: usize) {
+        if line >= self.buffer.len() {
+            self.sync_dirty_lines();
+            self.buffer.extend_to(line + 1);
+            self.dirty_lines.resize(line + 1, true);
+        }
+    }
+
+    /// Replace the lines in `start..end` with `lines`
+    pub fn replace_lines(
+        &mut self,
+        start: usize,
+        end: usize,
+        lines: Vec<String>
+    ) {
+        let end = usize::min(end, self.buffer.len());
+        let start = usize::min(start, end);
+        self.sync_dirty_lines();
+        self.dirty_lines.splice(start..end, vec![true; lines.len()]);
+        self.shift_lines(start, end, lines.len());
+        self.buffer.splice(start..end, lines);
+        self.dirty = true;
+    }
+
+    /// Insert a copy of the line below it, returns if the line exists
+    pub fn duplicate_line(&mut self, line: usize) -> bool {
+        let copy = match self.buffer.line(line) {
+            Some(text) => text.to_owned(),
+            None => return false,
+        };
+
+        self.sync_dirty_lines();
+        self.buffer.insert(line + 1, copy);
+        self.dirty_lines.insert(line + 1, true);
+        self.shift_lines(line + 1, line + 1, 1);
+        self.dirty = true;
+        true
+    }
+
+    /// Swap the line with the next one, returns if there was a next line
+    pub fn swap_lines(&mut self, line: usize) -> bool {
+        if line + 1 >= self.buffer.len() {
+            return false;
+        }
+
+        let swapped = vec![
+            self.buffer[line + 1].to_owned(), 
+            self.buffer[line].to_owned()
+        ];
+        self.buffer.splice(line..line + 2, swapped);
+        self.mark_dirty(line);
+        self.mark_dirty(line + 1);
+        true
+    }
+
+    /// Remove the line if exists
+    pub fn remove_line(&mut self, line: usize) {
+        if line < self.buffer.len() {
+            self.sync_dirty_lines();
+            self.dirty_lines.remove(line);
+            self.buffer.remove(line);
+            self.shift_lines(line, line + 1, 0);
+            self.dirty = true;
+        }
+    }
+
+    /// Replace all the lines, all of them are changed
+    pub fn set_lines(&mut self, lines: Vec<String>) {
+        self.shift_lines(0, self.buffer.len(), lines.len());
+        self.dirty_lines = vec![true; lines.len()];
+        self.buffer = Buffer::from_lines(&lines);
+        self.dirty = true;
+    }
+
+    /// The filetype chosen by the user or else the one detected from the
+    /// extension of the path, `None` when unknown
+    pub fn filetype(&self) -> Option<&'static str> {
+        self.forced_filetype.or_else(|| self.detect_filetype())
+    }
+
+    /// Choose the filetype whatever the extension, an empty name goes back
+    /// to the detected one
+    pub fn force_filetype(&mut self, name: &str) -> Result<()> {
+        self.forced_filetype = if name.is_empty() {
+            None
+        } else {
+            let filetype = FILETYPES.iter()
+                .find(|filetype| **filetype == name)
+                .ok_or_else(|| format!("unknown filetype `{}`", name))?;
+            Some(filetype)
+        };
+
+        Ok(())
+    }
+
+    /// Detect the filetype from the extension of the path, `None` when
+    /// unknown
+    fn detect_filetype(&self) -> Option<&'static str> {
+        if commit::is_commit_message(&self.path) {
+            return Some("gitcommit");
+        }
+
+        let extension = self.path.extension()?.to_str()?;
+        let filetype = match extension {
+            "rs"                         => "rust",
+            "c" | "h"                    => "c",
+            "cpp" | "cc" | "hpp" | "hh"  => "cpp",
+            "py"                         => "python",
+            "js"                         => "javascript",
+            "ts"                         => "typescript",
+            "html" | "htm"               => "html",
+            "xml"                        => "xml",
+            "md" | "markdown"            => "markdown",
+            "toml"                       => "toml",
+            "json"                       => "json",
+            "sh"                         => "sh",
+            "txt"                        => "text",
+            _ => return None,
+        };
+
+        Some(filetype)
+    }
+}
+
+/// The filetypes known, the ones that can be detected
+pub const FILETYPES: &[&str] = &[
+    "rust", "c", "cpp", "python", "javascript", "typescript", "html", "xml",
+    "markdown", "toml", "json", "sh", "text", "gitcommit",
+];
+
+/// The largest char boundary of the text at or before the byte `column`
+pub fn floor_char_boundary(text: &str, column: usize) -> usize {
+    let mut column = usize::min(column, text.len());
+    while !text.is_char_boundary(column) {
+        column -= 1;
+    }
+
+    column
+}
+
+/// If the line has more than `limit` chars
+pub fn is_overlong(line: &str, limit: usize) -> bool {
+    // The bytes are never less than the chars
+    line.len() > limit && line.chars().count() > limit
+}
+
+/// The kinds of chars, the words are the runs of chars of the same kind
+/// that isn't whitespace
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CharClass {
+    Whitespace,
+    Word,
+    Punctuation,
+}
+
+/// The chars that form words besides the alphanumeric ones, `_` unless set
+/// otherwise with `set wordchars`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WordChars(String);
+
+impl Default for WordChars {
+    fn default() -> Self {
+        Self("_".to_owned())
+    }
+}
+
+impl WordChars {
+    pub fn new(chars: &str) -> Self {
+        Self(chars.to_owned())
+    }
+
+    pub fn class(&self, c: char) -> CharClass {
+        if c.is_whitespace() {
+            CharClass::Whitespace
+        } else if c.is_alphanumeric() || self.0.contains(c) {
+            CharClass::Word
+        } else {
+            CharClass::Punctuation
+        }
+    }
+}
+
+/// The bytes of the line where its words start, a word being a run of word
+/// chars or a run of punctuation. `foo.bar(baz)` has the words `foo`, `.`,
+/// `bar`, `(`, `baz` and `)`
+pub fn word_boundaries(line: &str, word_chars: &WordChars) -> Vec<usize> {
+    let mut boundaries = Vec::new();
+    let mut prev = CharClass::Whitespace;
+    for (start, grapheme) in unicode::graphemes(line) {
+        let class = grapheme.chars()
+            .next()
+            .map(|c| word_chars.class(c))
+            .unwrap_or(CharClass::Whitespace);
+        if class != prev && class != CharClass::Whitespace {
+            boundaries.push(start);
+        }
+        prev = class;
+    }
+
+    boundaries
+}
+
+/// If a final newline must be inserted on save, POSIX text files end with a
+/// newline so it's enabled unless disabled, globally or per filetype
+#[derive(Debug)]
+pub struct FinalNewline {
+    pub enabled: bool,
+
+    /// Filetypes that override the global setting
+    pub filetypes: HashMap<String, bool>,
+}
+
+impl Default for FinalNewline {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            filetypes: HashMap::new(),
+        }
+    }
+}
+
+impl FinalNewline {
+    /// If the documents of the filetype get the final newline
+    pub fn applies(&self, filetype: Option<&str>) -> bool {
+        filetype
+            .and_then(|filetype| self.filetypes.get(filetype))
+            .copied()
+            .unwrap_or(self.enabled)
+    }
+}
+
+/// Split the contents of a file on lines without including the newline
+/// symbols, both `\r\n` and `\n` are accepted
+pub fn split_lines(bytes: &[u8]) -> Vec<String> {
+    /// Helper struct just to be more explicit
+    struct Line {
+        start: usize,
+        len: usize
+    }
+
+    let mut lines = Vec::new();
+
+    // Iterate over the file and create `Line` struct to delimitate the
+    // start and end of each line without including the newline symbols
+    let mut start = 0;
+    let mut len = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'\r' && i < bytes.len() - 1 && bytes[i + 1] == b'\n' {
+            lines.push(Line {
+                start,
+                len
+            });
+
+            start = i + 2;
+            len = 0;
+
+            i += 2;
+
+            continue;
+        }
+        
+        if bytes[i] == b'\n' {
+            lines.push(Line {
+                start,
+                len
+            });
+
+            start = i + 1;
+            len = 0;
 
-            len += 1;
             i += 1;
+
+            continue;
         }
 
-        // Create owned `String`s from the data to create the 
-        // `self.inner_lines`
-        let mut inner_lines = Vec::new();
-        for line in &lines {
-            inner_lines.push(
-                String::from_utf8_lossy(
-                    &bytes[line.start..line.start + line.len]).into_owned());
+        len += 1;
+        i += 1;
+    }
+
+    // The last line may not end with a newline
+    if len != 0 {
+        lines.push(Line {
+            start,
+            len
+        });
+    }
+
+    // Create owned `String`s from the data
+    let mut inner_lines = Vec::new();
+    for line in &lines {
+        inner_lines.push(
+            String::from_utf8_lossy(
+                &bytes[line.start..line.start + line.len]).into_owned());
+    }
+
+    inner_lines
+}
+
+/// Write the bytes on the file through a temporary file only the user can
+/// read and write, which gets the permissions and the owner of the file and
+/// replaces it. The file is written in place when that's not possible, and
+/// just created when it doesn't exist
+fn write_file(target: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    let meta = match std::fs::metadata(target) {
+        Ok(meta) => meta,
+        Err(err) if err.kind() == ErrorKind::NotFound => {
+            let mut file = std::fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(target)?;
+            file.write_all(bytes)?;
+            return file.sync_all();
         }
+        Err(err) => return Err(err),
+    };
 
-        Ok(Self {
-            path: path.as_ref().to_owned(),
-            inner_lines,
-        })
+    let file_name = target.file_name().unwrap_or_default();
+    let tmp_paths = (0..8).map(|_| {
+        let mut tmp_name = std::ffi::OsString::from(".");
+        tmp_name.push(file_name);
+        tmp_name.push(format!(".{}.pepe-tmp", fileops::random_suffix()));
+        target.with_file_name(tmp_name)
+    });
+    let (tmp_path, mut file) = match fileops::create_private(tmp_paths) {
+        Ok(created) => created,
+        Err(err) if err.kind() == ErrorKind::PermissionDenied =>
+            return std::fs::write(target, bytes),
+        Err(err) => return Err(err),
+    };
+    let mut replace = || -> std::io::Result<()> {
+        file.write_all(bytes)?;
+        file.sync_all()?;
+
+        // The new file must keep the permissions and the owner of the old
+        // one
+        std::fs::set_permissions(&tmp_path, meta.permissions())?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+            let owner = (meta.uid(), meta.gid());
+            let created = file.metadata()?;
+            if (created.uid(), created.gid()) != owner {
+                std::os::unix::fs::chown(
+                    &tmp_path, Some(owner.0), Some(owner.1))?;
+            }
+        }
+        std::fs::rename(&tmp_path, target)
+    };
+    if let Err(err) = replace() {
+        let _ = std::fs::remove_file(&tmp_path);
+        if err.kind() != ErrorKind::PermissionDenied {
+            return Err(err);
+        }
+        std::fs::write(target, bytes)?;
     }
+
+    Ok(())
 }
@@ -0,0 +1,539 @@
+//! Storage of the lines of a document. The lines are kept on chunks of a
+//! bounded number of lines, each chunk is a single `String` with the lines
+//! separated by newlines, so loading a huge file doesn't allocate once per
+//! line and an edit only rewrites the chunk of the line. The first line of
+//! every chunk is known, a line is found with a binary search.
+//!
+//! The files too big to be read are only indexed: their chunks know where
+//! their lines are on the file, and read them the first time one of them
+//! is needed. An edit copies the chunks it touches, the rest stay on the
+//! file
+
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::ops::{Index, Range};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use crate::Result;
+use crate::cancel::CancelToken;
+
+/// Lines on a chunk when it's built, it can grow to twice this before it's
+/// split
+const CHUNK_LINES: usize = 256;
+
+/// Bytes read at once while indexing a file
+const INDEX_BLOCK: usize = 1 << 20;
+
+/// The last version given to a buffer, the versions are never repeated even
+/// across buffers
+static VERSIONS: AtomicU64 = AtomicU64::new(0);
+
+fn next_version() -> u64 {
+    VERSIONS.fetch_add(1, Ordering::Relaxed) + 1
+}
+
+/// Consecutive lines, each one followed by a newline on `text`
+#[derive(Debug, Clone, Default)]
+struct Text {
+    text: String,
+
+    /// Byte where every line starts
+    starts: Vec<usize>,
+}
+
+impl Text {
+    fn push(&mut self, line: &str) {
+        debug_assert!(!line.contains('\n'));
+        self.starts.push(self.text.len());
+        self.text.push_str(line);
+        self.text.push('\n');
+    }
+
+    fn len(&self) -> usize {
+        self.starts.len()
+    }
+
+    fn line(&self, idx: usize) -> &str {
+        let end = self.starts.get(idx + 1).copied().unwrap_or(self.text.len());
+        &self.text[self.starts[idx]..end - 1]
+    }
+}
+
+/// The bytes of a file where the lines of a chunk are
+#[derive(Debug, Clone)]
+struct Extent {
+    file: Arc<Mutex<File>>,
+    start: u64,
+    len: usize,
+
+    /// Set when a line of the file is not UTF-8, shared by all the extents
+    invalid: Arc<AtomicBool>,
+}
+
+impl Extent {
+    fn new(
+        file: &Arc<Mutex<File>>,
+        invalid: &Arc<AtomicBool>,
+        bytes: Range<u64>,
+    ) -> Self {
+        Self {
+            file: Arc::clone(file),
+            start: bytes.start,
+            len: (bytes.end - bytes.start) as usize,
+            invalid: Arc::clone(invalid),
+        }
+    }
+
+    /// The lines on the file, the missing ones are empty when the file was
+    /// truncated by another program. The bytes that aren't UTF-8 are
+    /// replaced, and the buffer knows it
+    fn read(&self, lines: usize) -> Text {
+        let mut bytes = Vec::with_capacity(self.len);
+        if let Ok(mut file) = self.file.lock() {
+            let read = file.seek(SeekFrom::Start(self.start))
+                .and_then(|_| (&mut *file)
+                    .take(self.len as u64)
+                    .read_to_end(&mut bytes));
+            if read.is_err() {
+                bytes.clear();
+            }
+        }
+
+        let mut text = Text::default();
+        for line in byte_lines(&bytes).take(lines) {
+            let line = String::from_utf8_lossy(line);
+            if matches!(line, Cow::Owned(_)) {
+                self.invalid.store(true, Ordering::Relaxed);
+            }
+            text.push(&line);
+        }
+        while text.len() < lines {
+            text.push("");
+        }
+
+        text
+    }
+}
+
+/// Lines of the buffer, on memory or still on the file
+#[derive(Debug, Clone, Default)]
+struct Chunk {
+    len: usize,
+
+    /// The lines, set from the start or read from `extent` when needed
+    text: OnceLock<Text>,
+    extent: Option<Extent>,
+}
+
+impl Chunk {
+    fn from_lines<'a>(lines: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut text = Text::default();
+        for line in lines {
+            text.push(line);
+        }
+
+        Self::from_text(text)
+    }
+
+    fn from_text(text: Text) -> Self {
+        Self { len: text.len(), text: OnceLock::from(text), extent: None }
+    }
+
+    fn on_file(extent: Extent, len: usize) -> Self {
+        Self { len, text: OnceLock::new(), extent: Some(extent) }
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    /// The lines, read from the file the first time
+    fn text(&self) -> &Text {
+        self.text.get_or_init(|| match &self.extent {
+            Some(extent) => extent.read(self.len),
+            None => Text::default(),
+        })
+    }
+
+    /// The lines, read from the file without keeping them when they
+    /// weren't needed before
+    fn peek(&self) -> Cow<'_, Text> {
+        match (self.text.get(), &self.extent) {
+            (Some(text), _) => Cow::Borrowed(text),
+            (None, Some(extent)) => Cow::Owned(extent.read(self.len)),
+            (None, None) => Cow::Owned(Text::default()),
+        }
+    }
+
+    fn line(&self, idx: usize) -> &str {
+        self.text().line(idx)
+    }
+
+    fn lines(&self) -> impl Iterator<Item = &str> {
+        let text = self.text();
+        (0..self.len()).map(move |idx| text.line(idx))
+    }
+}
+
+/// The lines of a document
+#[derive(Debug, Clone, Default)]
+pub struct Buffer {
+    chunks: Vec<Chunk>,
+
+    /// The line where every chunk starts
+    firsts: Vec<usize>,
+
+    len: usize,
+
+    /// Changes on every modification, to know if the lines are still the
+    /// ones seen before
+    version: u64,
+    /// Set once a line read from the file is not UTF-8, writing the lines
+    /// back would change its bytes
+    invalid: Arc<AtomicBool>,
+}
+
+impl Buffer {
+    /// Build the buffer with the lines, none of them may contain newlines
+    pub fn from_lines<S: AsRef<str>>(lines: &[S]) -> Self {
+        let chunks = lines
+            .chunks(CHUNK_LINES)
+            .map(|lines| Chunk::from_lines(lines.iter().map(AsRef::as_ref)))
+            .collect();
+
+        let mut buffer = Self { chunks, ..Self::default() };
+        buffer.update_firsts(0);
+        buffer.version = next_version();
+        buffer
+    }
+
+    /// Build the buffer with the contents of a file, the lines end with
+    /// `\r\n` or `\n`. The chunks are filled straight from the bytes
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        let mut chunks = Vec::new();
+        let mut text = Text::default();
+        for line in byte_lines(bytes) {
+            text.push(&String::from_utf8_lossy(line));
+
+            if text.len() == CHUNK_LINES {
+                chunks.push(Chunk::from_text(std::mem::take(&mut text)));
+            }
+        }
+        if text.len() > 0 {
+            chunks.push(Chunk::from_text(text));
+        }
+
+        let mut buffer = Self { chunks, ..Self::default() };
+        buffer.update_firsts(0);
+        buffer.version = next_version();
+        buffer
+    }
+
+    /// Build the buffer indexing the lines of the file without reading
+    /// them, they are read when needed. The file is kept open, so the
+    /// lines are still found if it's replaced. Stops when cancelled
+    pub fn index_file(file: File, cancel: &CancelToken) -> Result<Self> {
+        let file = Arc::new(Mutex::new(file));
+        let mut reader = {
+            let file = file.lock().map_err(|_| "the file can't be read")?;
+            let mut clone = file.try_clone()?;
+            clone.seek(SeekFrom::Start(0))?;
+            BufReader::with_capacity(INDEX_BLOCK, clone)
+        };
+
+        let invalid = Arc::new(AtomicBool::new(false));
+
+        // Where the chunk being indexed starts, the lines it has and the
+        // offset of the block read
+        let mut chunks = Vec::new();
+        let (mut start, mut lines, mut offset) = (0u64, 0, 0u64);
+        let mut block = vec![0; INDEX_BLOCK];
+        let mut last_byte = b'\n';
+        loop {
+            cancel.check()?;
+            let read = reader.read(&mut block)?;
+            if read == 0 {
+                break;
+            }
+            let newlines = block[..read]
+                .iter()
+                .enumerate()
+                .filter(|(_, byte)| **byte == b'\n');
+            for (newline, _) in newlines {
+                lines += 1;
+                if lines == CHUNK_LINES {
+                    let end = offset + newline as u64 + 1;
+                    let extent = Extent::new(&file, &invalid, start..end);
+                    chunks.push(Chunk::on_file(extent, lines));
+                    (start, lines) = (end, 0);
+                }
+            }
+            offset += read as u64;
+            last_byte = block[read - 1];
+        }
+
+        // A last line without newline counts if not empty
+        if last_byte != b'\n' {
+            lines += 1;
+        }
+        if lines > 0 {
+            let extent = Extent::new(&file, &invalid, start..offset);
+            chunks.push(Chunk::on_file(extent, lines));
+        }
+
+        let mut buffer = Self { chunks, invalid, ..Self::default() };
+        buffer.update_firsts(0);
+        buffer.version = next_version();
+        Ok(buffer)
+    }
+
+    /// Number of lines
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// If a line read from the file was not UTF-8, its bytes were replaced
+    pub fn has_invalid_lines(&self) -> bool {
+        self.invalid.load(Ordering::Relaxed)
+    }
+
+    /// The version of the lines, a different one after any modification
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// The line, `None` when past the end
+    pub fn line(&self, idx: usize) -> Option<&str> {
+        if idx >= self.len {
+            return None;
+        }
+        let (chunk, line) = self.locate(idx);
+
+        Some(self.chunks[chunk].line(line))
+    }
+
+    /// Iterator over all the lines
+    pub fn lines(&self) -> Lines<'_> {
+        self.lines_from(0)
+    }
+
+    /// Iterator over the lines that starts at the line `start`, only the
+    /// first line is searched
+    pub fn lines_from(&self, start: usize) -> Lines<'_> {
+        let (chunk, line) = if start < self.len {
+            self.locate(start)
+        } else {
+            (self.chunks.len(), 0)
+        };
+
+        Lines { buffer: self, chunk, line }
+    }
+
+    /// Copy of the lines of the range
+    pub fn slice(&self, range: Range<usize>) -> Vec<String> {
+        let len = range.end.saturating_sub(range.start);
+        self.lines_from(range.start)
+            .take(len)
+            .map(str::to_owned)
+            .collect()
+    }
+
+    /// All the lines joined with the separator, the chunks still on the
+    /// file are read but not kept
+    pub fn join(&self, separator: &str) -> String {
+        let mut text = String::new();
+        let mut first = true;
+        for chunk in &self.chunks {
+            let chunk = chunk.peek();
+            for idx in 0..chunk.len() {
+                if !first {
+                    text.push_str(separator);
+                }
+                text.push_str(chunk.line(idx));
+                first = false;
+            }
+        }
+
+        text
+    }
+
+    /// Copy of all the lines
+    pub fn to_vec(&self) -> Vec<String> {
+        self.slice(0..self.len)
+    }
+
+    /// Replace the lines of the range with others, only the chunks of the
+    /// range are rebuilt
+    pub fn splice(&mut self, range: Range<usize>, lines: Vec<String>) {
+        let end = usize::min(range.end, self.len);
+        let start = usize::min(range.start, end);
+
+        // The chunks touched, inserting at the end goes to the last chunk
+        let (first_chunk, first_line) = if start < self.len {
+            self.locate(start)
+        } else if let Some(last) = self.chunks.last() {
+            (self.chunks.len() - 1, last.len())
+        } else {
+            (0, 0)
+        };
+        let last_chunk = if end > start {
+            self.locate(end - 1).0
+        } else {
+            first_chunk
+        };
+        let chunk_range = 
+            first_chunk..usize::min(last_chunk + 1, self.chunks.len());
+
+        let mut chunk_lines: Vec<&str> = self.chunks[chunk_range.clone()]
+            .iter()
+            .flat_map(Chunk::lines)
+            .collect();
+        chunk_lines.splice(
+            first_line..first_line + (end - start),
+            lines.iter().map(String::as_str));
+
+        // Chunks of the normal size, unless the lines fit on one
+        let rebuilt: Vec<Chunk> = if chunk_lines.len() <= CHUNK_LINES * 2 {
+            vec![Chunk::from_lines(chunk_lines)]
+        } else {
+            chunk_lines
+                .chunks(CHUNK_LINES)
+                .map(|lines| Chunk::from_lines(lines.iter().copied()))
+                .collect()
+        };
+        let rebuilt = rebuilt.into_iter().filter(|chunk| chunk.len() > 0);
+
+        self.chunks.splice(chunk_range, rebuilt.collect::<Vec<_>>());
+        self.update_firsts(first_chunk);
+        self.version = next_version();
+    }
+
+    /// Insert a line before the line `idx`, at the end when it's the length
+    pub fn insert(&mut self, idx: usize, line: String) {
+        self.splice(idx..idx, vec![line]);
+    }
+
+    /// Remove the line and return it
+    pub fn remove(&mut self, idx: usize) -> String {
+        let line = self[idx].to_owned();
+        self.splice(idx..idx + 1, Vec::new());
+
+        line
+    }
+
+    /// Modify the line in place
+    pub fn edit_line<R>(
+        &mut self,
+        idx: usize,
+        f: impl FnOnce(&mut String) -> R
+    ) -> R {
+        let mut line = self[idx].to_owned();
+        let result = f(&mut line);
+        self.splice(idx..idx + 1, vec![line]);
+
+        result
+    }
+
+    /// Add empty lines at the end until there are `len` lines
+    pub fn extend_to(&mut self, len: usize) {
+        if len > self.len {
+            let start = self.len;
+            self.splice(start..start, vec![String::new(); len - start]);
+        }
+    }
+
+    /// The chunk of the line and the line inside the chunk
+    fn locate(&self, idx: usize) -> (usize, usize) {
+        let chunk = self.firsts.partition_point(|&first| first <= idx) - 1;
+
+        (chunk, idx - self.firsts[chunk])
+    }
+
+    /// Recompute the first lines of the chunks after they changed from
+    /// `from` on
+    fn update_firsts(&mut self, from: usize) {
+        self.firsts.truncate(from);
+        let mut first = match from {
+            0 => 0,
+            _ => self.firsts[from - 1] + self.chunks[from - 1].len(),
+        };
+        for chunk in &self.chunks[from..] {
+            self.firsts.push(first);
+            first += chunk.len();
+        }
+        self.len = first;
+    }
+}
+
+impl Index<usize> for Buffer {
+    type Output = str;
+
+    fn index(&self, idx: usize) -> &str {
+        match self.line(idx) {
+            Some(line) => line,
+            None => panic!("line {} out of a buffer of {} lines", idx, self.len),
+        }
+    }
+}
+
+/// The chunks are an implementation detail, two buffers are equal when they
+/// have the same lines
+impl PartialEq for Buffer {
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len && self.lines().eq(other.lines())
+    }
+}
+
+impl Eq for Buffer {}
+
+/// Iterator over lines of a buffer
+pub struct Lines<'a> {
+    buffer: &'a Buffer,
+    chunk: usize,
+    line: usize,
+}
+
+impl<'a> Iterator for Lines<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<&'a str> {
+        let chunk = self.buffer.chunks.get(self.chunk)?;
+        let line = chunk.line(self.line);
+
+        self.line += 1;
+        if self.line == chunk.len() {
+            self.chunk += 1;
+            self.line = 0;
+        }
+
+        Some(line)
+    }
+}
+
+/// The lines of the bytes without the newlines, `\r\n` and `\n` end lines
+/// and a last line without newline counts if not empty
+fn byte_lines(bytes: &[u8]) -> impl Iterator<Item = &[u8]> {
+    let mut rest = bytes;
+    std::iter::from_fn(move || {
+        if rest.is_empty() {
+            return None;
+        }
+
+        let line = match rest.iter().position(|&byte| byte == b'\n') {
+            Some(newline) => {
+                let line = &rest[..newline];
+                rest = &rest[newline + 1..];
+                line.strip_suffix(b"\r").unwrap_or(line)
+            }
+            None => std::mem::take(&mut rest),
+        };
+
+        Some(line)
+    })
+}
@@ -0,0 +1,294 @@
+//! The color themes, every color the editor draws with is a role of the
+//! theme instead of being fixed where it's drawn. A few themes are built in
+//! and picked by name with `set theme=<name>`, on the config too
+
+use crossterm::style::Color;
+
+/// The colors of the syntax highlighting, by kind of token
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SyntaxColors {
+    pub keyword: Color,
+    pub string: Color,
+    pub comment: Color,
+    pub number: Color,
+    pub kind: Color,
+}
+
+/// The colors of every role
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Theme {
+    pub name: &'static str,
+
+    /// The line numbers and the rows past the end of the document
+    pub gutter: Color,
+
+    /// The line numbers of the lines with errors
+    pub gutter_error: Color,
+
+    /// The badge of the lines longer than the limit, and the background it
+    /// has when the line was added or modified since the last save. The
+    /// badge of the line below deleted ones is a dash of `deleted`
+    pub long_line: Color,
+    pub added: Color,
+    pub modified: Color,
+    pub deleted: Color,
+
+    /// The background of the status bar and of the current tab, and the
+    /// one they have while the terminal doesn't have the focus. Their text
+    /// is `bar_text`
+    pub accent: Color,
+    pub dimmed_accent: Color,
+    pub bar_text: Color,
+
+    /// The labels of the other tabs
+    pub inactive_tab: Color,
+
+    /// The title of the splash screen and its hint
+    pub splash: Color,
+    pub hint: Color,
+
+    /// The errors of the minibuffer, and the warnings of the language
+    /// servers under the text
+    pub error: Color,
+    pub warning: Color,
+
+    /// The background of the selected text
+    pub selection: Color,
+    pub selection_text: Color,
+
+    /// The background of the matches of the search, the current one apart
+    pub search_match: Color,
+    pub current_match: Color,
+    pub match_text: Color,
+
+    /// The background of the cells highlighted by the crosshair
+    pub crosshair: Color,
+
+    /// The background of the columns of the rulers, and of the chars of the
+    /// lines past the maximum length
+    pub ruler: Color,
+    pub overflow: Color,
+
+    /// The comments of a commit message and the text past its limit
+    pub commit_comment: Color,
+    pub commit_overflow: Color,
+
+    /// The length of the line when writing prose, and when it's too long
+    pub line_length: Color,
+    pub line_too_long: Color,
+
+    /// The thumb and the track of the scrollbar
+    pub scrollbar_thumb: Color,
+    pub scrollbar_track: Color,
+
+    /// The title and the selected line of the panels, their text is
+    /// `panel_text`
+    pub panel: Color,
+    pub panel_text: Color,
+
+    /// The background of the hover popup, its text is `popup_text`
+    pub popup: Color,
+    pub popup_text: Color,
+
+    /// The background of the bracket under the cursor and of its pair
+    pub bracket: Color,
+
+    /// The marks of the spaces and the tabs when the whitespace is shown,
+    /// and the background of the whitespace at the end of the lines
+    pub whitespace: Color,
+    pub trailing_whitespace: Color,
+
+    /// The backgrounds of the gutter by heat, the coolest first
+    pub heat: [Color; 3],
+
+    pub syntax: SyntaxColors,
+}
+
+/// The colors the editor always had, for dark terminals
+pub const DARK: Theme = Theme {
+    name: "dark",
+    gutter: Color::Yellow,
+    gutter_error: Color::Red,
+    long_line: Color::Magenta,
+    added: Color::DarkGreen,
+    modified: Color::DarkYellow,
+    deleted: Color::Red,
+    accent: Color::White,
+    dimmed_accent: Color::DarkGrey,
+    bar_text: Color::Black,
+    inactive_tab: Color::White,
+    splash: Color::Blue,
+    hint: Color::DarkGrey,
+    error: Color::Red,
+    warning: Color::Yellow,
+    selection: Color::White,
+    selection_text: Color::Black,
+    search_match: Color::DarkYellow,
+    current_match: Color::Yellow,
+    match_text: Color::Black,
+    crosshair: Color::DarkGrey,
+    ruler: Color::DarkGrey,
+    overflow: Color::DarkRed,
+    commit_comment: Color::DarkGrey,
+    commit_overflow: Color::DarkRed,
+    line_length: Color::DarkGrey,
+    line_too_long: Color::Red,
+    scrollbar_thumb: Color::Grey,
+    scrollbar_track: Color::DarkGrey,
+    panel: Color::White,
+    panel_text: Color::Black,
+    popup: Color::Yellow,
+    popup_text: Color::Black,
+    bracket: Color::DarkCyan,
+    whitespace: Color::DarkGrey,
+    trailing_whitespace: Color::DarkRed,
+    heat: [
+        Color::AnsiValue(236),
+        Color::AnsiValue(238),
+        Color::AnsiValue(240),
+    ],
+    syntax: SyntaxColors {
+        keyword: Color::Magenta,
+        string: Color::Green,
+        comment: Color::DarkGrey,
+        number: Color::Cyan,
+        kind: Color::Yellow,
+    },
+};
+
+/// For light terminals, the dark text is on the light background
+pub const LIGHT: Theme = Theme {
+    name: "light",
+    gutter: Color::DarkYellow,
+    gutter_error: Color::DarkRed,
+    long_line: Color::DarkMagenta,
+    added: Color::Green,
+    modified: Color::Yellow,
+    deleted: Color::DarkRed,
+    accent: Color::DarkBlue,
+    dimmed_accent: Color::Grey,
+    bar_text: Color::White,
+    inactive_tab: Color::DarkGrey,
+    splash: Color::DarkBlue,
+    hint: Color::Grey,
+    error: Color::DarkRed,
+    warning: Color::DarkYellow,
+    selection: Color::DarkBlue,
+    selection_text: Color::White,
+    search_match: Color::Yellow,
+    current_match: Color::DarkYellow,
+    match_text: Color::Black,
+    crosshair: Color::Grey,
+    ruler: Color::Grey,
+    overflow: Color::Red,
+    commit_comment: Color::Grey,
+    commit_overflow: Color::Red,
+    line_length: Color::Grey,
+    line_too_long: Color::DarkRed,
+    scrollbar_thumb: Color::DarkGrey,
+    scrollbar_track: Color::Grey,
+    panel: Color::DarkBlue,
+    panel_text: Color::White,
+    popup: Color::DarkYellow,
+    popup_text: Color::Black,
+    bracket: Color::Cyan,
+    whitespace: Color::Grey,
+    trailing_whitespace: Color::Red,
+    heat: [
+        Color::AnsiValue(254),
+        Color::AnsiValue(252),
+        Color::AnsiValue(250),
+    ],
+    syntax: SyntaxColors {
+        keyword: Color::DarkMagenta,
+        string: Color::DarkGreen,
+        comment: Color::Grey,
+        number: Color::DarkCyan,
+        kind: Color::DarkYellow,
+    },
+};
+
+/// The dark solarized palette, its colors are exact so it needs a
+/// terminal with true colors
+pub const SOLARIZED: Theme = Theme {
+    name: "solarized",
+    gutter: SOLARIZED_BASE01,
+    gutter_error: SOLARIZED_RED,
+    long_line: SOLARIZED_MAGENTA,
+    added: SOLARIZED_GREEN,
+    modified: SOLARIZED_YELLOW,
+    deleted: SOLARIZED_RED,
+    accent: SOLARIZED_BASE1,
+    dimmed_accent: SOLARIZED_BASE01,
+    bar_text: SOLARIZED_BASE03,
+    inactive_tab: SOLARIZED_BASE0,
+    splash: SOLARIZED_BLUE,
+    hint: SOLARIZED_BASE01,
+    error: SOLARIZED_RED,
+    warning: SOLARIZED_YELLOW,
+    selection: SOLARIZED_BASE01,
+    selection_text: SOLARIZED_BASE3,
+    search_match: SOLARIZED_BASE1,
+    current_match: SOLARIZED_YELLOW,
+    match_text: SOLARIZED_BASE03,
+    crosshair: SOLARIZED_BASE02,
+    ruler: SOLARIZED_BASE02,
+    overflow: SOLARIZED_RED,
+    commit_comment: SOLARIZED_BASE01,
+    commit_overflow: SOLARIZED_RED,
+    line_length: SOLARIZED_BASE01,
+    line_too_long: SOLARIZED_RED,
+    scrollbar_thumb: SOLARIZED_BASE0,
+    scrollbar_track: SOLARIZED_BASE02,
+    panel: SOLARIZED_BASE1,
+    panel_text: SOLARIZED_BASE03,
+    popup: SOLARIZED_YELLOW,
+    popup_text: SOLARIZED_BASE03,
+    bracket: SOLARIZED_BASE01,
+    whitespace: SOLARIZED_BASE01,
+    trailing_whitespace: SOLARIZED_RED,
+    heat: [
+        SOLARIZED_BASE02,
+        Color::Rgb { r: 0x1d, g: 0x47, b: 0x52 },
+        Color::Rgb { r: 0x3a, g: 0x5a, b: 0x63 },
+    ],
+    syntax: SyntaxColors {
+        keyword: SOLARIZED_GREEN,
+        string: SOLARIZED_CYAN,
+        comment: SOLARIZED_BASE01,
+        number: SOLARIZED_MAGENTA,
+        kind: SOLARIZED_YELLOW,
+    },
+};
+
+const SOLARIZED_BASE03: Color = Color::Rgb { r: 0x00, g: 0x2b, b: 0x36 };
+const SOLARIZED_BASE02: Color = Color::Rgb { r: 0x07, g: 0x36, b: 0x42 };
+const SOLARIZED_BASE01: Color = Color::Rgb { r: 0x58, g: 0x6e, b: 0x75 };
+const SOLARIZED_BASE0: Color = Color::Rgb { r: 0x83, g: 0x94, b: 0x96 };
+const SOLARIZED_BASE1: Color = Color::Rgb { r: 0x93, g: 0xa1, b: 0xa1 };
+const SOLARIZED_BASE3: Color = Color::Rgb { r: 0xfd, g: 0xf6, b: 0xe3 };
+const SOLARIZED_YELLOW: Color = Color::Rgb { r: 0xb5, g: 0x89, b: 0x00 };
+const SOLARIZED_RED: Color = Color::Rgb { r: 0xdc, g: 0x32, b: 0x2f };
+const SOLARIZED_MAGENTA: Color = Color::Rgb { r: 0xd3, g: 0x36, b: 0x82 };
+const SOLARIZED_BLUE: Color = Color::Rgb { r: 0x26, g: 0x8b, b: 0xd2 };
+const SOLARIZED_CYAN: Color = Color::Rgb { r: 0x2a, g: 0xa1, b: 0x98 };
+const SOLARIZED_GREEN: Color = Color::Rgb { r: 0x85, g: 0x99, b: 0x00 };
+
+/// The built-in themes, the first is the default
+pub const THEMES: &[Theme] = &[DARK, LIGHT, SOLARIZED];
+
+/// The built-in theme with the name, `default` is the first one
+pub fn find(name: &str) -> Option<&'static Theme> {
+    match name {
+        "default" => THEMES.first(),
+        name => THEMES.iter().find(|theme| theme.name == name),
+    }
+}
+
+/// The names of the built-in themes, for the errors
+pub fn names() -> String {
+    THEMES.iter()
+        .map(|theme| theme.name)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
@@ -0,0 +1,118 @@
+//! The TODO comments of the project, the lines of the files indexed with one
+//! of the patterns as a whole word, like `TODO`, `FIXME` or `HACK`. They are
+//! listed on a panel grouped by file and any of them can be jumped to
+
+use std::path::{Path, PathBuf};
+
+use crate::Result;
+use crate::binary;
+use crate::cancel::CancelToken;
+
+/// The patterns looked for unless `todopatterns` says otherwise
+pub const DEFAULT_PATTERNS: &[&str] = &["TODO", "FIXME", "HACK"];
+
+/// Files larger than this are not scanned, they aren't written by hand
+const MAX_FILE_SIZE: u64 = 1 << 20;
+
+/// A line with one of the patterns
+#[derive(Debug, Clone)]
+pub struct Todo {
+    /// The file, relative to the root of the project
+    pub file: PathBuf,
+    pub line: usize,
+    pub column: usize,
+    pub text: String,
+}
+
+/// The TODOs found and the one each line of the panel stands for, the
+/// headers of the files stand for their first TODO
+#[derive(Debug, Default)]
+pub struct TodoList {
+    pub entries: Vec<Todo>,
+    rows: Vec<usize>,
+}
+
+impl TodoList {
+    /// Scan the `files` of the project under `root`, the scan stops when
+    /// cancelled
+    pub fn scan(
+        root: &Path,
+        files: &[PathBuf],
+        patterns: &[String],
+        cancel: &CancelToken
+    ) -> Result<Self> {
+        let mut files = files.to_vec();
+        files.sort();
+
+        let mut entries = Vec::new();
+        for file in files {
+            if cancel.is_cancelled() {
+                return Err("the scan of the TODOs was cancelled".into());
+            }
+
+            let path = root.join(&file);
+            let small = std::fs::metadata(&path)
+                .is_ok_and(|meta| meta.len() <= MAX_FILE_SIZE);
+            if !small {
+                continue;
+            }
+            let bytes = match std::fs::read(&path) {
+                Ok(bytes) if !binary::is_binary(&bytes) => bytes,
+                _ => continue,
+            };
+
+            let text = String::from_utf8_lossy(&bytes);
+            for (line, text) in text.lines().enumerate() {
+                if let Some(column) = find_pattern(text, patterns) {
+                    entries.push(Todo {
+                        file: file.clone(),
+                        line,
+                        column,
+                        text: text.trim().to_owned(),
+                    });
+                }
+            }
+        }
+
+        Ok(Self { entries, rows: Vec::new() })
+    }
+
+    /// The lines of the panel: the path of each file followed by its TODOs
+    pub fn lines(&mut self) -> Vec<String> {
+        let mut lines = Vec::new();
+        self.rows.clear();
+
+        let mut last_file: Option<&Path> = None;
+        for (idx, todo) in self.entries.iter().enumerate() {
+            if last_file != Some(&todo.file) {
+                last_file = Some(&todo.file);
+                lines.push(todo.file.display().to_string());
+                self.rows.push(idx);
+            }
+            lines.push(format!("  {:5} {}", todo.line + 1, todo.text));
+            self.rows.push(idx);
+        }
+
+        lines
+    }
+
+    /// The TODO of the line of the panel
+    pub fn at_row(&self, row: usize) -> Option<&Todo> {
+        self.entries.get(*self.rows.get(row)?)
+    }
+}
+
+/// The byte where one of the patterns is as a whole word on the line
+fn find_pattern(line: &str, patterns: &[String]) -> Option<usize> {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    patterns.iter()
+        .filter(|pattern| !pattern.is_empty())
+        .flat_map(|pattern| line.match_indices(pattern.as_str()))
+        .filter(|(start, pattern)| {
+            let before = line[..*start].chars().next_back();
+            let after = line[start + pattern.len()..].chars().next();
+            !before.is_some_and(is_word) && !after.is_some_and(is_word)
+        })
+        .map(|(start, _)| start)
+        .min()
+}
@@ -0,0 +1,195 @@
+//! The file tree shown on a sidebar at the left of the document area when
+//! the editor starts on a directory. The directories are expanded and
+//! collapsed in place and the files are opened on buffers, with the keys
+//! while the tree has the focus or with the mouse
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use crate::Result;
+use crate::index::IGNORED_DIRS;
+
+/// Columns of the names of the tree by default, the border goes after them
+pub const DEFAULT_WIDTH: usize = 24;
+
+/// A row of the tree, a file or a directory under the root
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub path: PathBuf,
+    pub depth: usize,
+    pub dir: bool,
+}
+
+/// The entries of the root and of the directories expanded below it
+#[derive(Debug)]
+pub struct FileTree {
+    pub root: PathBuf,
+    pub entries: Vec<Entry>,
+    expanded: HashSet<PathBuf>,
+
+    /// The entry the keys act on, and the first entry shown
+    pub selected: usize,
+    pub scroll: usize,
+
+    /// Hidden the tree keeps its state, focused it takes the keys
+    pub visible: bool,
+    pub focused: bool,
+}
+
+impl FileTree {
+    /// The tree of the directory `root`, shown and focused
+    pub fn new(root: impl AsRef<Path>) -> Result<Self> {
+        let mut tree = Self {
+            root: root.as_ref().to_owned(),
+            entries: Vec::new(),
+            expanded: HashSet::new(),
+            selected: 0,
+            scroll: 0,
+            visible: true,
+            focused: true,
+        };
+        tree.entries = tree.read(&tree.root, 0)?;
+
+        Ok(tree)
+    }
+
+    /// Read again the entries of the expanded directories, the selected
+    /// path keeps being selected while it exists
+    pub fn refresh(&mut self) -> Result<()> {
+        let selected = self.entries.get(self.selected).map(|e| e.path.clone());
+        self.entries = self.read(&self.root, 0)?;
+        if let Some(idx) = selected.and_then(|path| self.find(&path)) {
+            self.selected = idx;
+        }
+        self.selected = self.selected.min(self.entries.len().saturating_sub(1));
+
+        Ok(())
+    }
+
+    pub fn find(&self, path: &Path) -> Option<usize> {
+        self.entries.iter().position(|entry| entry.path == path)
+    }
+
+    /// Expand or collapse the directory of the entry `idx`, the path of a
+    /// file to be opened
+    pub fn toggle(&mut self, idx: usize) -> Result<Option<PathBuf>> {
+        let entry = match self.entries.get(idx) {
+            Some(entry) => entry.clone(),
+            None => return Ok(None),
+        };
+        if !entry.dir {
+            return Ok(Some(entry.path));
+        }
+
+        if self.expanded.remove(&entry.path) {
+            let end = self.subtree_end(idx);
+            self.entries.drain(idx + 1..end);
+        } else {
+            let children = self.read(&entry.path, entry.depth + 1)?;
+            self.expanded.insert(entry.path);
+            self.entries.splice(idx + 1..idx + 1, children);
+        }
+
+        Ok(None)
+    }
+
+    /// Collapse the selected directory, or go to the directory of the
+    /// selected entry
+    pub fn collapse(&mut self, rows: usize) -> Result<()> {
+        let entry = match self.entries.get(self.selected) {
+            Some(entry) => entry.clone(),
+            None => return Ok(()),
+        };
+        if entry.dir && self.expanded.contains(&entry.path) {
+            self.toggle(self.selected)?;
+        } else if let Some(parent) = self.entries[..self.selected]
+                .iter()
+                .rposition(|other| other.depth < entry.depth) {
+            self.select(parent, rows);
+        }
+
+        Ok(())
+    }
+
+    /// Select the entry `idx`, scrolling the tree so it's on the `rows`
+    pub fn select(&mut self, idx: usize, rows: usize) {
+        self.selected = idx.min(self.entries.len().saturating_sub(1));
+        if self.selected < self.scroll {
+            self.scroll = self.selected;
+        } else if self.selected >= self.scroll + rows {
+            self.scroll = self.selected + 1 - rows;
+        }
+    }
+
+    /// Move the selection by `delta` entries
+    pub fn move_by(&mut self, delta: isize, rows: usize) {
+        let idx = self.selected.saturating_add_signed(delta);
+        self.select(idx, rows);
+    }
+
+    /// Scroll the tree by `delta` rows, without moving the selection
+    pub fn scroll_by(&mut self, delta: isize, rows: usize) {
+        let last = self.entries.len().saturating_sub(rows);
+        self.scroll = self.scroll.saturating_add_signed(delta).min(last);
+    }
+
+    /// The text of the entry `idx`: indented by its depth, the directories
+    /// with the mark of their state and a slash
+    pub fn label(&self, idx: usize) -> String {
+        let entry = &self.entries[idx];
+        let name = entry.path.file_name()
+            .map(|name| name.to_string_lossy())
+            .unwrap_or_default();
+        let indent = "  ".repeat(entry.depth);
+        match (entry.dir, self.expanded.contains(&entry.path)) {
+            (true, true) => format!("{}▾ {}/", indent, name),
+            (true, false) => format!("{}▸ {}/", indent, name),
+            (false, _) => format!("{}  {}", indent, name),
+        }
+    }
+
+    /// The index past the last entry below the directory `idx`
+    fn subtree_end(&self, idx: usize) -> usize {
+        let depth = self.entries[idx].depth;
+        self.entries[idx + 1..]
+            .iter()
+            .position(|entry| entry.depth <= depth)
+            .map(|offset| idx + 1 + offset)
+            .unwrap_or(self.entries.len())
+    }
+
+    /// The entries of the directory, the directories first and each group
+    /// sorted by name. The hidden and the ignored ones are left out, the
+    /// expanded directories come with their entries
+    fn read(&self, dir: &Path, depth: usize) -> Result<Vec<Entry>> {
+        let mut children: Vec<Entry> = std::fs::read_dir(dir)?
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| {
+                let name = entry.file_name();
+                let name = name.to_string_lossy();
+                !name.starts_with('.') && !IGNORED_DIRS.contains(&&*name)
+            })
+            .map(|entry| {
+                // The paths of the current directory are opened as the
+                // ones typed, without the dot
+                let path = entry.path();
+                let path = path.strip_prefix(".").unwrap_or(&path).to_owned();
+                Entry { dir: path.is_dir(), path, depth }
+            })
+            .collect();
+        children.sort_by(|a, b| b.dir.cmp(&a.dir).then(a.path.cmp(&b.path)));
+
+        let mut entries = Vec::with_capacity(children.len());
+        for child in children {
+            let expanded = child.dir && self.expanded.contains(&child.path);
+            let path = child.path.clone();
+            entries.push(child);
+            if expanded {
+                // A directory that can't be read anymore is shown empty
+                entries.extend(self.read(&path, depth + 1).unwrap_or_default());
+            }
+        }
+
+        Ok(entries)
+    }
+}
@@ -0,0 +1,437 @@
+//! Undo and redo of the edits. Every edit is recorded as the lines it
+//! replaced and the lines it left, with the position of the cursor before
+//! and after, so undoing restores the view too. The chars typed or deleted
+//! one after another are grouped on a single undo unit.
+//!
+//! The memory of the history is bounded, past the limit the oldest changes
+//! are spilled to a temporary file and read back when undone
+
+use std::collections::{HashMap, VecDeque};
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use crate::fileops;
+use crate::text::{Document, Position};
+
+/// Memory the changes kept in memory can use by default
+pub const DEFAULT_MEMORY_LIMIT: usize = 64 * 1024 * 1024;
+
+/// The kind of an edit, only the edits of the same kind are grouped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EditKind {
+    /// Chars typed
+    Insert,
+
+    /// Chars deleted
+    Delete,
+
+    /// A line split in two
+    Split,
+
+    /// Two lines joined
+    Join,
+
+    /// Text pasted
+    Paste,
+
+    /// Lines replaced by a command, like a re-indent
+    Replace,
+}
+
+/// Where the cursor was and what was shown
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Location {
+    pub position: Position,
+
+    /// The first line shown on the terminal
+    pub scroll_y: usize,
+}
+
+/// An undo unit, the lines `start..start + after.len()` were
+/// `start..start + before.len()`
+#[derive(Debug, Clone)]
+pub struct Change {
+    kind: EditKind,
+    start: usize,
+    before: Vec<String>,
+    after: Vec<String>,
+    cursor_before: Location,
+    cursor_after: Location,
+}
+
+/// The lines an edit is about to replace, taken before the edit
+#[derive(Debug)]
+pub struct PendingChange {
+    start: usize,
+    before: Vec<String>,
+    cursor_before: Location,
+
+    /// Lines after the range, the edit doesn't touch them
+    lines_after: usize,
+}
+
+impl PendingChange {
+    /// Remember the `len` lines from `start` before editing them
+    pub fn begin(
+        doc: &Document,
+        start: usize,
+        len: usize,
+        cursor: Location
+    ) -> Self {
+        let end = usize::min(start + len, doc.buffer.len());
+        let start = usize::min(start, end);
+        Self {
+            start,
+            before: doc.buffer.slice(start..end),
+            cursor_before: cursor,
+            lines_after: doc.buffer.len() - end,
+        }
+    }
+
+    /// The edit of the range was cancelled, its lines are put back
+    pub fn abort(self, doc: &mut Document) {
+        let end = doc.buffer.len().saturating_sub(self.lines_after)
+            .max(self.start);
+        doc.replace_lines(self.start, end, self.before);
+    }
+
+    /// The edit of the range is done, the lines it left are taken
+    pub fn commit(
+        self,
+        kind: EditKind,
+        doc: &Document,
+        cursor: Location
+    ) -> Change {
+        let end = doc.buffer.len().saturating_sub(self.lines_after)
+            .max(self.start);
+        Change {
+            kind,
+            start: self.start,
+            before: self.before,
+            after: doc.buffer.slice(self.start..end),
+            cursor_before: self.cursor_before,
+            cursor_after: cursor,
+        }
+    }
+}
+
+impl Change {
+    /// Approximate memory used by the change
+    fn size(&self) -> usize {
+        let text: usize = self.before.iter()
+            .chain(&self.after)
+            .map(|line| line.len() + std::mem::size_of::<String>())
+            .sum();
+
+        text + std::mem::size_of::<Self>()
+    }
+
+    /// Append the change to the spill file
+    fn write_to(&self, writer: &mut impl Write) -> io::Result<()> {
+        let kind = match self.kind {
+            EditKind::Insert => 0,
+            EditKind::Delete => 1,
+            EditKind::Split => 2,
+            EditKind::Join => 3,
+            EditKind::Paste => 4,
+            EditKind::Replace => 5,
+        };
+        writer.write_all(&[kind])?;
+
+        let mut numbers = vec![self.start];
+        for location in [self.cursor_before, self.cursor_after] {
+            let Location { position, scroll_y } = location;
+            let Position { line, grapheme, byte } = position;
+            numbers.extend([line, grapheme, byte, scroll_y]);
+        }
+        for number in numbers {
+            writer.write_all(&(number as u64).to_le_bytes())?;
+        }
+
+        for lines in [&self.before, &self.after] {
+            writer.write_all(&(lines.len() as u64).to_le_bytes())?;
+            for line in lines {
+                writer.write_all(&(line.len() as u64).to_le_bytes())?;
+                writer.write_all(line.as_bytes())?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Read back a change written with `write_to`
+    fn read_from(reader: &mut impl Read) -> io::Result<Self> {
+        let mut kind = [0; 1];
+        reader.read_exact(&mut kind)?;
+        let kind = match kind[0] {
+            0 => EditKind::Insert,
+            1 => EditKind::Delete,
+            2 => EditKind::Split,
+            3 => EditKind::Join,
+            4 => EditKind::Paste,
+            _ => EditKind::Replace,
+        };
+
+        let mut numbers = [0; 9];
+        for number in numbers.iter_mut() {
+            *number = read_number(reader)?;
+        }
+
+        let mut read_lines = || -> io::Result<Vec<String>> {
+            let count = read_number(reader)?;
+            (0..count)
+                .map(|_| {
+                    let mut bytes = vec![0; read_number(reader)?];
+                    reader.read_exact(&mut bytes)?;
+                    String::from_utf8(bytes).map_err(|err| 
+                        io::Error::new(io::ErrorKind::InvalidData, err))
+                })
+                .collect()
+        };
+        let before = read_lines()?;
+        let after = read_lines()?;
+
+        Ok(Self {
+            kind,
+            start: numbers[0],
+            before,
+            after,
+            cursor_before: location(&numbers[1..5]),
+            cursor_after: location(&numbers[5..9]),
+        })
+    }
+
+    /// If the change continues the last one, typing or deleting on the same
+    /// line from where the last one left the cursor
+    fn continues(&self, last: &Change) -> bool {
+        let single_line = |change: &Change|
+            change.before.len() == 1 && change.after.len() == 1;
+
+        matches!(self.kind, EditKind::Insert | EditKind::Delete)
+            && self.kind == last.kind
+            && single_line(self)
+            && single_line(last)
+            && self.start == last.start
+            && self.cursor_before.position == last.cursor_after.position
+    }
+}
+
+/// Read a number written by `Change::write_to`
+fn read_number(reader: &mut impl Read) -> io::Result<usize> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+
+    Ok(u64::from_le_bytes(bytes) as usize)
+}
+
+/// Build a location from the numbers written by `Change::write_to`
+fn location(numbers: &[usize]) -> Location {
+    Location {
+        position: Position {
+            line: numbers[0],
+            grapheme: numbers[1],
+            byte: numbers[2],
+        },
+        scroll_y: numbers[3],
+    }
+}
+
+/// Where a spilled change is on the spill file
+#[derive(Debug, Clone, Copy)]
+struct Spilled {
+    offset: u64,
+    len: u64,
+}
+
+/// The changes of a document that can be undone and redone
+#[derive(Debug, Default)]
+struct History {
+    /// The newest last, the ones older than all of them are spilled
+    undo: VecDeque<Change>,
+    spilled: Vec<Spilled>,
+
+    redo: Vec<Change>,
+}
+
+/// The history of every document edited
+#[derive(Debug)]
+pub struct UndoHistory {
+    documents: HashMap<PathBuf, History>,
+
+    /// Memory the changes in memory can use, and the one they use
+    pub memory_limit: usize,
+    memory: usize,
+
+    /// Temporary file with the changes spilled, created when first needed
+    spill: Option<(PathBuf, File)>,
+
+    /// The changes are not recorded, someone records them as one
+    held: bool,
+}
+
+impl Default for UndoHistory {
+    fn default() -> Self {
+        Self {
+            documents: HashMap::new(),
+            memory_limit: DEFAULT_MEMORY_LIMIT,
+            memory: 0,
+            spill: None,
+            held: false,
+        }
+    }
+}
+
+impl Drop for UndoHistory {
+    fn drop(&mut self) {
+        if let Some((path, _)) = &self.spill {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+impl UndoHistory {
+    /// Stop recording the changes until released, the edits made meanwhile
+    /// are recorded as a single change by who held it
+    pub fn hold(&mut self) {
+        self.held = true;
+    }
+
+    pub fn release(&mut self) {
+        self.held = false;
+    }
+
+    /// Record a change of the document, redoing is not possible after it.
+    /// The changes that only continue the last one join its unit
+    pub fn record(&mut self, doc: &Document, change: Change) {
+        if self.held {
+            return;
+        }
+        let history = self.documents.entry(doc.path.clone()).or_default();
+        for change in history.redo.drain(..) {
+            self.memory -= change.size();
+        }
+
+        match history.undo.back_mut() {
+            Some(last) if change.continues(last) => {
+                self.memory -= last.size();
+                last.after = change.after;
+                last.cursor_after = change.cursor_after;
+                self.memory += last.size();
+            }
+            _ => {
+                self.memory += change.size();
+                history.undo.push_back(change);
+            }
+        }
+
+        self.spill_oldest(&doc.path, doc.encryption.is_none());
+    }
+
+    /// Revert the last change of the document, returns where the cursor was
+    /// before it
+    pub fn undo(&mut self, doc: &mut Document) -> Option<Location> {
+        let history = self.documents.get_mut(&doc.path)?;
+        let change = match history.undo.pop_back() {
+            Some(change) => change,
+            None => {
+                let spilled = history.spilled.pop()?;
+                let change = read_spilled(&mut self.spill, spilled).ok()?;
+                self.memory += change.size();
+                change
+            }
+        };
+
+        let end = change.start + change.after.len();
+        doc.replace_lines(change.start, end, change.before.clone());
+        let position = change.cursor_before;
+        history.redo.push(change);
+
+        Some(position)
+    }
+
+    /// Make again the last change undone, returns where the cursor was
+    /// after it
+    pub fn redo(&mut self, doc: &mut Document) -> Option<Location> {
+        let history = self.documents.get_mut(&doc.path)?;
+        let change = history.redo.pop()?;
+
+        let end = change.start + change.before.len();
+        doc.replace_lines(change.start, end, change.after.clone());
+        let position = change.cursor_after;
+        history.undo.push_back(change);
+
+        Some(position)
+    }
+
+    /// Move the oldest changes of the document to the spill file until the
+    /// memory is under the limit, the newest change always stays. If the
+    /// file can't be written, or the document is encrypted and its text
+    /// can't go to disk unencrypted, they are forgotten instead
+    fn spill_oldest(&mut self, path: &Path, can_spill: bool) {
+        let history = match self.documents.get_mut(path) {
+            Some(history) => history,
+            None => return,
+        };
+
+        while self.memory > self.memory_limit && history.undo.len() > 1 {
+            let change = history.undo.pop_front().unwrap();
+            self.memory -= change.size();
+
+            if !can_spill {
+                history.spilled.clear();
+                continue;
+            }
+            match write_spilled(&mut self.spill, &change) {
+                Ok(spilled) => history.spilled.push(spilled),
+                Err(_) => history.spilled.clear(),
+            }
+        }
+    }
+
+    /// The file was renamed, its history goes with it
+    pub fn rename_file(&mut self, old_path: &Path, new_path: &Path) {
+        if let Some(history) = self.documents.remove(old_path) {
+            self.documents.insert(new_path.to_owned(), history);
+        }
+    }
+}
+
+/// Append the change to the spill file, creating it if needed
+fn write_spilled(
+    spill: &mut Option<(PathBuf, File)>,
+    change: &Change
+) -> io::Result<Spilled> {
+    if spill.is_none() {
+        *spill = Some(create_spill()?);
+    }
+    let (_, file) = spill.as_mut().unwrap();
+
+    let mut bytes = Vec::new();
+    change.write_to(&mut bytes)?;
+    let offset = file.seek(SeekFrom::End(0))?;
+    file.write_all(&bytes)?;
+
+    Ok(Spilled { offset, len: bytes.len() as u64 })
+}
+
+/// Create the spill file on the temporary directory, the text of the
+/// documents goes there so only the user can read it. The name can't be
+/// guessed and an existing file is never opened, a link left there by
+/// someone else can't take the writes somewhere else
+fn create_spill() -> io::Result<(PathBuf, File)> {
+    fileops::create_private((0..8).map(|_| std::env::temp_dir()
+        .join(format!("pepe-undo-{}.tmp", fileops::random_suffix()))))
+}
+
+/// Read back a change of the spill file
+fn read_spilled(
+    spill: &mut Option<(PathBuf, File)>,
+    Spilled { offset, len }: Spilled
+) -> io::Result<Change> {
+    let (_, file) = spill.as_mut()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no spill"))?;
+    file.seek(SeekFrom::Start(offset))?;
+
+    Change::read_from(&mut file.take(len))
+}
@@ -0,0 +1,230 @@
+//! The graphemes of the lines and the cells of the terminal they take. The
+//! cursor moves by graphemes, what the user sees as a single char even when
+//! it's made of many, like a letter with its accents or an emoji with its
+//! skin tone, and the columns of the terminal are the cells shown before
+//! the grapheme, where the wide glyphs take two and the tabs expand to the
+//! next tab stop
+//!
+//! There are no unicode tables here, only the ranges of the marks and of the
+//! wide glyphs that show up on real files, close enough to what terminals do
+
+/// Joins the chars at both sides on a single grapheme, used by the emojis
+const ZWJ: char = '\u{200D}';
+
+/// Asks for the emoji presentation of the char before, shown wide
+const EMOJI_PRESENTATION: char = '\u{FE0F}';
+
+/// If the char doesn't start a grapheme but extends the one before it, the
+/// combining marks, the variation selectors and the emoji modifiers
+fn is_extend(c: char) -> bool {
+    matches!(c as u32,
+        0x0300..=0x036F | 0x0483..=0x0489 | 0x0591..=0x05BD
+        | 0x0610..=0x061A | 0x064B..=0x065F | 0x0670 | 0x06D6..=0x06DC
+        | 0x0900..=0x0903 | 0x093A..=0x094F | 0x0E31 | 0x0E34..=0x0E3A
+        | 0x0E47..=0x0E4E | 0x1AB0..=0x1AFF | 0x1DC0..=0x1DFF
+        | 0x200C..=0x200D | 0x20D0..=0x20FF | 0x302A..=0x302F
+        | 0x3099..=0x309A | 0xFE00..=0xFE0F | 0xFE20..=0xFE2F
+        | 0x1F3FB..=0x1F3FF | 0xE0020..=0xE007F | 0xE0100..=0xE01EF)
+}
+
+/// The letters of the flags, they go in pairs
+fn is_regional(c: char) -> bool {
+    matches!(c as u32, 0x1F1E6..=0x1F1FF)
+}
+
+/// If the char takes two cells, the CJK ideographs, the hangul, the
+/// fullwidth forms and the emojis
+fn is_wide(c: char) -> bool {
+    matches!(c as u32,
+        0x1100..=0x115F | 0x231A..=0x231B | 0x2329..=0x232A
+        | 0x23E9..=0x23EC | 0x23F0 | 0x23F3 | 0x25FD..=0x25FE
+        | 0x2614..=0x2615 | 0x2648..=0x2653 | 0x267F | 0x2693 | 0x26A1
+        | 0x26AA..=0x26AB | 0x26BD..=0x26BE | 0x26C4..=0x26C5 | 0x26CE
+        | 0x26D4 | 0x26EA | 0x26F2..=0x26F3 | 0x26F5 | 0x26FA | 0x26FD
+        | 0x2705 | 0x270A..=0x270B | 0x2728 | 0x274C | 0x274E
+        | 0x2753..=0x2755 | 0x2757 | 0x2795..=0x2797 | 0x27B0 | 0x27BF
+        | 0x2B1B..=0x2B1C | 0x2B50 | 0x2B55 | 0x2E80..=0x303E
+        | 0x3041..=0x33FF | 0x3400..=0x4DBF | 0x4E00..=0x9FFF
+        | 0xA000..=0xA4CF | 0xA960..=0xA97F | 0xAC00..=0xD7A3
+        | 0xF900..=0xFAFF | 0xFE10..=0xFE19 | 0xFE30..=0xFE6F
+        | 0xFF00..=0xFF60 | 0xFFE0..=0xFFE6 | 0x16FE0..=0x16FE4
+        | 0x17000..=0x18AFF | 0x1B000..=0x1B2FF | 0x1F004 | 0x1F0CF
+        | 0x1F18E | 0x1F191..=0x1F19A | 0x1F200..=0x1F251
+        | 0x1F300..=0x1F64F | 0x1F680..=0x1F6FF | 0x1F7E0..=0x1F7EB
+        | 0x1F90C..=0x1F9FF | 0x1FA70..=0x1FAFF | 0x20000..=0x2FFFD
+        | 0x30000..=0x3FFFD)
+}
+
+/// Cells taken by the char on its own, the marks take none
+pub fn char_width(c: char) -> usize {
+    if is_extend(c) || c == '\u{200B}' {
+        0
+    } else if is_wide(c) {
+        2
+    } else {
+        1
+    }
+}
+
+/// Cells taken by the grapheme when it's shown at `column`, a tab goes until
+/// the next multiple of `tab_width`
+pub fn grapheme_width(
+    grapheme: &str,
+    column: usize,
+    tab_width: usize
+) -> usize {
+    let mut chars = grapheme.chars();
+    let first = match chars.next() {
+        Some('\t') => {
+            let tab_width = usize::max(tab_width, 1);
+            return tab_width - column % tab_width;
+        }
+        Some(first) => first,
+        None => return 0,
+    };
+
+    // The flags and the emojis asked to be shown as such are wide even when
+    // their chars are not
+    let width = char_width(first);
+    if width == 1 && (is_regional(first)
+            || grapheme.contains(EMOJI_PRESENTATION)) {
+        2
+    } else {
+        width
+    }
+}
+
+/// The byte right after the grapheme that starts at the byte of the line,
+/// the end of the line when it's the last one
+pub fn next_grapheme(line: &str, byte: usize) -> usize {
+    let byte = crate::text::floor_char_boundary(line, byte);
+    let mut chars = line[byte..].char_indices();
+    let mut prev = match chars.next() {
+        Some((_, first)) => first,
+        None => return line.len(),
+    };
+    let mut regionals = is_regional(prev) as usize;
+
+    for (idx, c) in chars {
+        let pairs_flag = is_regional(c) && regionals % 2 == 1;
+        if !is_extend(c) && prev != ZWJ && !pairs_flag {
+            return byte + idx;
+        }
+
+        regionals += is_regional(c) as usize;
+        prev = c;
+    }
+
+    line.len()
+}
+
+/// The graphemes of the line with the byte where each one starts
+pub fn graphemes(line: &str) -> Graphemes<'_> {
+    Graphemes { line, byte: 0 }
+}
+
+/// Iterator of the graphemes of a line, made by `graphemes`
+pub struct Graphemes<'a> {
+    line: &'a str,
+    byte: usize,
+}
+
+impl<'a> Iterator for Graphemes<'a> {
+    type Item = (usize, &'a str);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.byte >= self.line.len() {
+            return None;
+        }
+
+        let start = self.byte;
+        self.byte = next_grapheme(self.line, start);
+        Some((start, &self.line[start..self.byte]))
+    }
+}
+
+/// The start of the grapheme the byte is inside, the end of the line when
+/// past it
+pub fn floor_grapheme(line: &str, byte: usize) -> usize {
+    graphemes(line)
+        .map(|(start, grapheme)| (start, start + grapheme.len()))
+        .find(|&(_, end)| byte < end)
+        .map(|(start, _)| start)
+        .unwrap_or(line.len())
+}
+
+/// The start of the grapheme before the one at the byte, the first one stays
+pub fn prev_grapheme(line: &str, byte: usize) -> usize {
+    graphemes(line)
+        .map(|(start, _)| start)
+        .take_while(|&start| start < byte)
+        .last()
+        .unwrap_or(0)
+}
+
+/// The start of the last grapheme of the line, 0 when it's empty
+pub fn last_grapheme(line: &str) -> usize {
+    prev_grapheme(line, line.len())
+}
+
+/// The column of the terminal, counted from the start of the line, where
+/// the grapheme at the byte is shown
+pub fn column_of(line: &str, byte: usize, tab_width: usize) -> usize {
+    let mut column = 0;
+    for (start, grapheme) in graphemes(line) {
+        if start >= byte {
+            break;
+        }
+        column += grapheme_width(grapheme, column, tab_width);
+    }
+
+    column
+}
+
+/// Cells taken by the whole line
+pub fn width(line: &str, tab_width: usize) -> usize {
+    column_of(line, line.len(), tab_width)
+}
+
+/// The start of the grapheme shown on the column, the end of the line when
+/// the column is past it
+pub fn byte_at_column(line: &str, column: usize, tab_width: usize) -> usize {
+    let mut current = 0;
+    for (start, grapheme) in graphemes(line) {
+        current += grapheme_width(grapheme, current, tab_width);
+        if current > column {
+            return start;
+        }
+    }
+
+    line.len()
+}
+
+/// What the line shows on the columns `from..to`, the tabs expanded and the
+/// glyphs cut at the edges replaced by whitespaces so the text takes exactly
+/// the cells of the terminal it should. Shorter when the line ends before
+pub fn visible(
+    line: &str,
+    (from, to): (usize, usize),
+    tab_width: usize
+) -> String {
+    let mut visible = String::with_capacity(to.saturating_sub(from));
+    let mut column = 0;
+    for (_, grapheme) in graphemes(line) {
+        if column >= to {
+            break;
+        }
+        let next = column + grapheme_width(grapheme, column, tab_width);
+        if next > from {
+            if column < from || next > to || grapheme == "\t" {
+                let cells = usize::min(next, to) - usize::max(column, from);
+                visible.extend(std::iter::repeat_n(' ', cells));
+            } else {
+                visible.push_str(grapheme);
+            }
+        }
+        column = next;
+    }
+
+    visible
+}
@@ -0,0 +1,139 @@
+//! The part of the document shown on the terminal. The viewport only knows
+//! what is visible, moving it never moves the cursor, the callers decide
+//! what happens with a cursor that is left out of the view
+//!
+//! The scroll margin keeps some lines of context around the cursor, the view
+//! scrolls before the cursor reaches its top or bottom rows unless those
+//! rows show the ends of the document
+//!
+//! Without soft wrap the long lines are cut at the right edge, the viewport
+//! scrolls horizontally so the column of the cursor is always shown
+
+use crate::text::Position;
+
+/// The part of the document shown
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Viewport {
+    /// The first line of the document shown
+    pub scroll_y: usize,
+
+    /// The first column of the lines shown, the gutter stays fixed
+    pub scroll_x: usize,
+}
+
+impl Viewport {
+    /// If the line is shown when the view has `rows` rows
+    pub fn contains(&self, line: usize, rows: usize) -> bool {
+        line >= self.scroll_y && line < self.scroll_y + rows
+    }
+
+    /// The row of the terminal that shows the line, `None` if not shown
+    pub fn row_of(&self, line: usize, rows: usize) -> Option<usize> {
+        self.contains(line, rows).then(|| line - self.scroll_y)
+    }
+
+    /// Scroll the least possible so the position is shown, returns if the
+    /// view moved
+    pub fn ensure_visible(&mut self, position: Position, rows: usize) -> bool {
+        let old_scroll = self.scroll_y;
+        if position.line < self.scroll_y {
+            self.scroll_y = position.line;
+        } else if rows > 0 && position.line >= self.scroll_y + rows {
+            self.scroll_y = position.line + 1 - rows;
+        }
+
+        self.scroll_y != old_scroll
+    }
+
+    /// The first and the last lines the cursor can be on without scrolling,
+    /// the `margin` rows at the top and the bottom of the view are left out
+    /// unless they show the ends of the document
+    pub fn inner_lines(
+        &self,
+        rows: usize,
+        margin: usize,
+        doc_lines: usize
+    ) -> (usize, usize) {
+        let margin = usize::min(margin, rows.saturating_sub(1) / 2);
+        let last = usize::min(self.scroll_y + rows.saturating_sub(1),
+            doc_lines.saturating_sub(1));
+        let first = match self.scroll_y {
+            0 => 0,
+            scroll_y => usize::min(scroll_y + margin, last),
+        };
+        let last = if self.scroll_y + rows >= doc_lines {
+            last
+        } else {
+            last.saturating_sub(margin)
+        };
+
+        (first, usize::max(first, last))
+    }
+
+    /// Scroll the least possible so the line is one of the inner lines with
+    /// the `margin`, returns if the view moved
+    pub fn keep_inside(
+        &mut self,
+        line: usize,
+        rows: usize,
+        margin: usize,
+        doc_lines: usize
+    ) -> bool {
+        let old_scroll = self.scroll_y;
+        let margin = usize::min(margin, rows.saturating_sub(1) / 2);
+        let (first, last) = self.inner_lines(rows, margin, doc_lines);
+        if line < first {
+            self.scroll_y = line.saturating_sub(margin);
+        } else if rows > 0 && line > last {
+            // The last line of the document doesn't go above the bottom row
+            let max_scroll = usize::max(doc_lines.saturating_sub(rows),
+                (line + 1).saturating_sub(rows));
+            self.scroll_y = usize::min(
+                    (line + margin + 1).saturating_sub(rows), max_scroll)
+                .max(old_scroll);
+        }
+
+        self.scroll_y != old_scroll
+    }
+
+    /// Scroll `lines` down, or up when negative, the last line of the
+    /// document can reach the top but not go past it. Returns the lines the
+    /// view actually moved
+    pub fn scroll_by(&mut self, lines: isize, doc_lines: usize) -> isize {
+        let old_scroll = self.scroll_y;
+        let max_scroll = doc_lines.saturating_sub(1);
+        self.scroll_y = if lines < 0 {
+            self.scroll_y.saturating_sub(lines.unsigned_abs())
+        } else {
+            // A view already past the end, after removing lines, stays
+            usize::min(max_scroll, self.scroll_y + lines as usize)
+                .max(self.scroll_y)
+        };
+
+        self.scroll_y as isize - old_scroll as isize
+    }
+
+    /// Scroll so the line ends up at the center of the view, without
+    /// leaving empty rows at the end when the document is long enough
+    pub fn center_on(&mut self, line: usize, rows: usize, doc_lines: usize) {
+        let max_scroll = doc_lines.saturating_sub(rows);
+        self.scroll_y = usize::min(max_scroll, line.saturating_sub(rows / 2));
+    }
+
+    /// Scroll horizontally the least possible so the column is shown when the
+    /// lines have `width` columns, returns if the view moved
+    pub fn ensure_column_visible(
+        &mut self,
+        column: usize,
+        width: usize
+    ) -> bool {
+        let old_scroll = self.scroll_x;
+        if column < self.scroll_x {
+            self.scroll_x = column;
+        } else if width > 0 && column >= self.scroll_x + width {
+            self.scroll_x = column + 1 - width;
+        }
+
+        self.scroll_x != old_scroll
+    }
+}
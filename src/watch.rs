@@ -0,0 +1,112 @@
+//! Detection of changes made to the open files by other programs. There is
+//! no inotify/FSEvents watcher, which don't work on NFS or SSHFS anyway, so
+//! the modification time of the files is polled on its own thread, a slow
+//! file system never blocks the editor
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::Sender;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use crate::events::Wake;
+
+/// Time between polls by default, long enough to not burn CPU
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// What the polling thread shares with the editor
+#[derive(Debug)]
+struct Watched {
+    /// Time between polls, zero disables the polling
+    interval: Duration,
+
+    /// Last modification time known of every watched file
+    mtimes: HashMap<PathBuf, Option<SystemTime>>,
+}
+
+/// Polling of the modification time of the watched files
+#[derive(Debug)]
+pub struct FileWatcher {
+    shared: Arc<Mutex<Watched>>,
+}
+
+impl Default for FileWatcher {
+    fn default() -> Self {
+        Self {
+            shared: Arc::new(Mutex::new(Watched {
+                interval: DEFAULT_POLL_INTERVAL,
+                mtimes: HashMap::new(),
+            })),
+        }
+    }
+}
+
+impl FileWatcher {
+    /// Start polling the files, the changed ones are sent to the editor
+    pub fn start(&self, wake: Sender<Wake>) {
+        let shared = Arc::clone(&self.shared);
+        thread::spawn(move || loop {
+            let interval = shared.lock().unwrap().interval;
+            if interval.is_zero() {
+                thread::sleep(DEFAULT_POLL_INTERVAL);
+                continue;
+            }
+            thread::sleep(interval);
+
+            let changed = poll(&shared);
+            if !changed.is_empty() && wake.send(Wake::FilesChanged(changed))
+                    .is_err() {
+                break;
+            }
+        });
+    }
+
+    /// Change the time between polls, zero disables the polling
+    pub fn set_interval(&self, interval: Duration) {
+        self.shared.lock().unwrap().interval = interval;
+    }
+
+    /// Start watching the file, or forget its changes if already watched,
+    /// like after the editor writes it
+    pub fn watch(&self, path: &Path) {
+        let current = mtime(path);
+        self.shared.lock().unwrap().mtimes.insert(path.to_owned(), current);
+    }
+
+    /// Stop watching the file
+    pub fn unwatch(&self, path: &Path) {
+        self.shared.lock().unwrap().mtimes.remove(path);
+    }
+}
+
+/// Files that changed since the last poll. The files are read without
+/// holding the lock, a change the editor made to one of them meanwhile, like
+/// watching it again after writing it, wins
+fn poll(shared: &Mutex<Watched>) -> Vec<PathBuf> {
+    let known = shared.lock().unwrap().mtimes.clone();
+    let current: Vec<_> = known
+        .into_iter()
+        .map(|(path, known)| (mtime(&path), path, known))
+        .filter(|(current, _, known)| current != known)
+        .collect();
+
+    let mut watched = shared.lock().unwrap();
+    let mut changed = Vec::new();
+    for (current, path, known) in current {
+        match watched.mtimes.get_mut(&path) {
+            Some(mtime) if *mtime == known => {
+                *mtime = current;
+                changed.push(path);
+            }
+            _ => {}
+        }
+    }
+
+    changed
+}
+
+/// Modification time of the file, `None` if it doesn't exist anymore
+pub fn mtime(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
@@ -0,0 +1,106 @@
+//! The options local to a window, each window shows its documents its own
+//! way. Every tab is a single window for now, so the options follow the tab
+//! when switching between tabs
+//!
+//! `set` changes the option on every window and `setlocal` only on the
+//! current one
+
+use crate::Result;
+
+/// Columns the line numbers take at least, the short documents don't move
+/// the text as they grow
+pub const MIN_NUMBER_WIDTH: usize = 2;
+
+/// What the gutter shows next to each line
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum LineNumbers {
+    /// The number of the line
+    #[default]
+    Absolute,
+
+    /// The distance to the cursor line, the lines a motion takes to reach
+    Relative,
+
+    /// The number of the cursor line and the distance to it of the others
+    Hybrid,
+
+    /// Nothing, the gutter keeps its width for the marks
+    Off,
+}
+
+impl LineNumbers {
+    /// The name of the mode, as `set linenumbers` takes it
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Absolute => "absolute",
+            Self::Relative => "relative",
+            Self::Hybrid => "hybrid",
+            Self::Off => "off",
+        }
+    }
+
+    /// The mode after this one when cycling through them
+    pub fn next(self) -> Self {
+        match self {
+            Self::Absolute => Self::Relative,
+            Self::Relative => Self::Hybrid,
+            Self::Hybrid => Self::Off,
+            Self::Off => Self::Absolute,
+        }
+    }
+}
+
+/// The options of a window
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct WindowOptions {
+    pub line_numbers: LineNumbers,
+
+    /// The lines longer than the document area continue on the next rows
+    pub wrap: bool,
+
+    /// The spaces and the tabs are marked, and the whitespace at the end of
+    /// the lines stands out
+    pub whitespace: bool,
+}
+
+impl WindowOptions {
+    /// If the option is local to the windows
+    pub fn is_local(name: &str) -> bool {
+        matches!(name, "linenumbers" | "wrap" | "whitespace")
+    }
+
+    /// Change the option from its `name=value` parts, returns the message
+    /// for the user
+    pub fn set(&mut self, name: &str, value: &str) -> Result<String> {
+        match name {
+            "linenumbers" => {
+                self.line_numbers = match value {
+                    "absolute" => LineNumbers::Absolute,
+                    "relative" => LineNumbers::Relative,
+                    "hybrid" => LineNumbers::Hybrid,
+                    "off" => LineNumbers::Off,
+                    _ => return Err("linenumbers must be absolute, \
+                        relative, hybrid or off".into()),
+                };
+                Ok(format!("linenumbers={}", value))
+            }
+            "wrap" => {
+                self.wrap = match value {
+                    "on" => true,
+                    "off" => false,
+                    _ => return Err("wrap must be on or off".into()),
+                };
+                Ok(format!("wrap={}", value))
+            }
+            "whitespace" => {
+                self.whitespace = match value {
+                    "on" => true,
+                    "off" => false,
+                    _ => return Err("whitespace must be on or off".into()),
+                };
+                Ok(format!("whitespace={}", value))
+            }
+            name => Err(format!("`{}` is not a window option", name).into()),
+        }
+    }
+}
@@ -0,0 +1,205 @@
+//! Soft wrap of the lines longer than the document area. The document keeps
+//! its lines, only the rows of the terminal that show them change: each line
+//! is split on segments that fit, preferably after a whitespace, and every
+//! segment gets its own row
+//!
+//! The cursor still has its line and byte, the layout of the rows shown
+//! translates them to the cells of the terminal and back
+
+use crate::text::Document;
+use crate::unicode;
+
+/// A row of the terminal showing the bytes `start..end` of a line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VisualRow {
+    pub line: usize,
+    pub start: usize,
+    pub end: usize,
+
+    /// If it's the first row of the line, the one with the line number
+    pub first: bool,
+}
+
+/// The bytes of the line shown on each row when wrapped at `width` cells,
+/// an empty line still takes a row
+pub fn segments(
+    line: &str,
+    width: usize,
+    tab_width: usize
+) -> Vec<(usize, usize)> {
+    let width = usize::max(width, 1);
+    let mut segments = Vec::new();
+    let (mut start, mut start_column) = (0, 0);
+    let mut column = 0;
+
+    // Where the segment ends when broken after its last whitespace, and the
+    // column of the end
+    let mut space = None;
+    for (byte, grapheme) in unicode::graphemes(line) {
+        let next = column
+            + unicode::grapheme_width(grapheme, column, tab_width);
+
+        // Break after the last whitespace when there is one, the words are
+        // only split when they don't fit on a row by themselves. A grapheme
+        // wider than the row takes one anyway
+        while next - start_column > width && byte > start {
+            let (end, end_column) = space.take().unwrap_or((byte, column));
+            segments.push((start, end));
+            (start, start_column) = (end, end_column);
+        }
+        if byte > start && grapheme.starts_with(char::is_whitespace) {
+            space = Some((byte + grapheme.len(), next));
+        }
+        column = next;
+    }
+
+    segments.push((start, line.len()));
+    segments
+}
+
+/// Which of the segments has the byte, the end of a segment belongs to the
+/// next one except for the last
+pub fn segment_of(segments: &[(usize, usize)], byte: usize) -> usize {
+    segments
+        .iter()
+        .position(|&(_, end)| byte < end)
+        .unwrap_or(segments.len() - 1)
+}
+
+/// The byte shown `column` cells after the start of the segment, kept
+/// inside it
+pub fn byte_at(
+    line: &str,
+    (start, end): (usize, usize),
+    column: usize,
+    last: bool,
+    tab_width: usize
+) -> usize {
+    let start_column = unicode::column_of(line, start, tab_width);
+    let byte = usize::min(end, 
+        unicode::byte_at_column(line, start_column + column, tab_width));
+
+    // The end of a segment is shown at the start of the next one
+    if byte == end && !last && end > start {
+        unicode::prev_grapheme(line, end)
+    } else {
+        byte
+    }
+}
+
+/// The columns of the line shown on the row of the segment, counted from
+/// the start of the line so the tabs keep their stops
+pub fn columns_of(
+    line: &str,
+    (start, end): (usize, usize),
+    tab_width: usize
+) -> (usize, usize) {
+    (unicode::column_of(line, start, tab_width),
+        unicode::column_of(line, end, tab_width))
+}
+
+/// The rows of the document area with the view starting at `scroll_y`
+#[derive(Debug, Clone, Default)]
+pub struct Layout {
+    pub rows: Vec<VisualRow>,
+
+    /// The tabs are expanded to its multiples
+    pub tab_width: usize,
+}
+
+impl Layout {
+    pub fn new(
+        doc: &Document,
+        scroll_y: usize,
+        rows: usize,
+        width: usize,
+        tab_width: usize
+    ) -> Self {
+        let mut visual = Vec::with_capacity(rows);
+        for (offset, line) in doc.buffer.lines_from(scroll_y).enumerate() {
+            for (idx, (start, end)) in segments(line, width, tab_width)
+                    .into_iter()
+                    .enumerate() {
+                if visual.len() == rows {
+                    return Self { rows: visual, tab_width };
+                }
+                visual.push(VisualRow {
+                    line: scroll_y + offset,
+                    start,
+                    end,
+                    first: idx == 0,
+                });
+            }
+        }
+
+        Self { rows: visual, tab_width }
+    }
+
+    /// The row and the column of the document area that show the byte of
+    /// the line, `None` when it's not shown
+    pub fn cell_of(
+        &self,
+        doc: &Document,
+        line: usize,
+        byte: usize
+    ) -> Option<(usize, usize)> {
+        let text = doc.buffer.line(line)?;
+        let on_line = || self.rows
+            .iter()
+            .enumerate()
+            .filter(move |(_, row)| row.line == line);
+        let (idx, row) = on_line()
+            .find(|(_, row)| byte < row.end)
+            .or_else(|| on_line().next_back())?;
+
+        // The rest of the line is below the view
+        if byte >= row.end && row.end < text.len() {
+            return None;
+        }
+
+        let byte = usize::clamp(byte, row.start, text.len());
+        let (start, column) = 
+            columns_of(text, (row.start, byte), self.tab_width);
+        Some((idx, column - start))
+    }
+
+    /// The line and the byte shown on the cell, the cells after the end of
+    /// a row point to the end of it
+    pub fn position_at(
+        &self,
+        doc: &Document,
+        row: usize,
+        column: usize
+    ) -> Option<(usize, usize)> {
+        let visual = self.rows.get(row).or_else(|| self.rows.last())?;
+        let text = doc.buffer.line(visual.line)?;
+        let last = visual.end == text.len();
+
+        let segment = (visual.start, visual.end);
+        Some((visual.line,
+            byte_at(text, segment, column, last, self.tab_width)))
+    }
+
+    /// Rows used by the lines from the first one shown to the byte of the
+    /// line, the row of the byte included. Used to know when the view needs
+    /// to scroll to show it
+    pub fn rows_until(
+        doc: &Document,
+        scroll_y: usize,
+        line: usize,
+        byte: usize,
+        width: usize,
+        tab_width: usize
+    ) -> usize {
+        let before: usize = doc.buffer
+            .lines_from(scroll_y)
+            .take(line.saturating_sub(scroll_y))
+            .map(|text| segments(text, width, tab_width).len())
+            .sum();
+        let segment = doc.buffer.line(line)
+            .map(|text| segment_of(&segments(text, width, tab_width), byte))
+            .unwrap_or(0);
+
+        before + segment + 1
+    }
+}